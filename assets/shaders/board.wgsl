@@ -0,0 +1,45 @@
+// The whole grid in one quad. Each fragment looks up the tile it's in and
+// draws that tile's color over the checkerboard texture, with a gold dot in
+// the middle of valuable tiles.
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+struct BoardTile {
+    color: vec4<f32>,
+    value_dot: vec4<f32>,
+}
+
+// Width and height in tiles
+@group(2) @binding(0) var<uniform> size: vec2<u32>;
+@group(2) @binding(1) var<storage, read> tiles: array<BoardTile>;
+@group(2) @binding(2) var light_texture: texture_2d<f32>;
+@group(2) @binding(3) var light_sampler: sampler;
+@group(2) @binding(4) var dark_texture: texture_2d<f32>;
+@group(2) @binding(5) var dark_sampler: sampler;
+
+// Half the width of a value dot, in tiles
+const VALUE_DOT_HALF: f32 = 0.15;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let scaled = mesh.uv * vec2<f32>(size);
+    // Texture coordinates run down from the top, tile rows up from the bottom
+    let cell = min(vec2<u32>(scaled), size - vec2<u32>(1u));
+    let x = cell.x;
+    let y = size.y - 1u - cell.y;
+    let tile = tiles[y * size.x + x];
+    let within = fract(scaled);
+
+    // Both are sampled, sampling has to happen the same way for every tile
+    let light = textureSample(light_texture, light_sampler, within);
+    let dark = textureSample(dark_texture, dark_sampler, within);
+    let texel = select(light, dark, (x + y) % 2u == 0u);
+    var color = texel * tile.color;
+
+    let gold = tile.value_dot;
+    if all(abs(within - vec2<f32>(0.5)) < vec2<f32>(VALUE_DOT_HALF)) && gold.a > 0.0 {
+        let alpha = gold.a + color.a * (1.0 - gold.a);
+        let rgb = (gold.rgb * gold.a + color.rgb * color.a * (1.0 - gold.a)) / alpha;
+        color = vec4<f32>(rgb, alpha);
+    }
+    return color;
+}
@@ -0,0 +1,105 @@
+// Balance report: plays a batch of bot-only matches under one rule set and
+// sums up how they went, so rule tweaks can be compared on numbers instead
+// of feel.
+use crate::headless::{run_batch, MatchSetup, MatchSummary};
+use crate::resources::GameRules;
+use serde::Serialize;
+use std::thread;
+
+const BOTS_PER_MATCH: usize = 4;
+// Width of each bar in the match length histogram
+const LENGTH_BUCKET_SECONDS: f32 = 30.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LengthBucket {
+    // Matches that ended before this many seconds, and after the previous bucket
+    pub up_to_seconds: f32,
+    pub matches: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BalanceReport {
+    pub matches: usize,
+    // Fraction of the map the winner held, averaged over matches
+    pub average_winner_share: f32,
+    pub total_kills: usize,
+    pub average_kills: f32,
+    pub average_deaths: f32,
+    pub average_seconds: f32,
+    pub match_lengths: Vec<LengthBucket>,
+}
+
+impl BalanceReport {
+    pub fn from_summaries(summaries: &[MatchSummary]) -> Self {
+        let matches = summaries.len();
+        let average = |value: &dyn Fn(&MatchSummary) -> f32| {
+            if matches == 0 {
+                0.0
+            } else {
+                summaries.iter().map(value).sum::<f32>() / matches as f32
+            }
+        };
+
+        let longest = summaries
+            .iter()
+            .map(|summary| summary.seconds)
+            .fold(0.0, f32::max);
+        let buckets = (longest / LENGTH_BUCKET_SECONDS).floor() as usize + 1;
+        let mut match_lengths: Vec<LengthBucket> = (1..=buckets)
+            .map(|bucket| LengthBucket {
+                up_to_seconds: bucket as f32 * LENGTH_BUCKET_SECONDS,
+                matches: 0,
+            })
+            .collect();
+        for summary in summaries {
+            let bucket = (summary.seconds / LENGTH_BUCKET_SECONDS).floor() as usize;
+            match_lengths[bucket.min(buckets - 1)].matches += 1;
+        }
+
+        Self {
+            matches,
+            average_winner_share: average(&|summary| summary.winner_share()),
+            total_kills: summaries.iter().map(|summary| summary.kills).sum(),
+            average_kills: average(&|summary| summary.kills as f32),
+            average_deaths: average(&|summary| summary.deaths as f32),
+            average_seconds: average(&|summary| summary.seconds),
+            match_lengths,
+        }
+    }
+
+    // Summary row first, then the histogram as its own table
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "matches,average_winner_share,total_kills,average_kills,average_deaths,average_seconds\n",
+        );
+        csv.push_str(&format!(
+            "{},{:.4},{},{:.2},{:.2},{:.1}\n\nup_to_seconds,matches\n",
+            self.matches,
+            self.average_winner_share,
+            self.total_kills,
+            self.average_kills,
+            self.average_deaths,
+            self.average_seconds
+        ));
+        for bucket in self.match_lengths.iter() {
+            csv.push_str(&format!("{},{}\n", bucket.up_to_seconds, bucket.matches));
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+// Plays `matches` full-length bot matches under `rules` on every core
+pub fn simulate(rules: &GameRules, matches: usize) -> BalanceReport {
+    let setup = MatchSetup {
+        rules: rules.clone(),
+        bots: BOTS_PER_MATCH,
+        ..MatchSetup::default()
+    };
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+
+    BalanceReport::from_summaries(&run_batch(&vec![setup; matches], threads))
+}
@@ -0,0 +1,182 @@
+// brain.rs
+// Public interface for bot AIs. A brain looks at a read-only snapshot of the
+// match and answers with the direction it wants to go; the game takes care
+// of movement, trails and claims. Brains are registered by name, so other
+// crates can add their own and pick them in the rules.
+use crate::territory::{TileMap, ZoneBounds};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Name of the brain bots use when the rules don't pick one
+pub const DEFAULT_BRAIN: &str = "loops";
+
+// What a brain can see of one player
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayerView {
+    pub entity: Entity,
+    pub tile: (i32, i32),
+    pub direction: Vec2,
+    pub drawing_trail: bool,
+    pub score: u32,
+    pub is_bot: bool,
+}
+
+// The whole match as of the start of this frame's input stage
+#[derive(Clone, Debug)]
+pub struct WorldSnapshot {
+    pub map: TileMap,
+    pub players: Vec<PlayerView>,
+    // Seconds left on the match clock
+    pub time_left: f32,
+    // Set in the shrinking-zone mode
+    pub zone: Option<ZoneView>,
+}
+
+// Where it's safe to be, and where it will be once the next ring closes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZoneView {
+    pub bounds: ZoneBounds,
+    pub next: ZoneBounds,
+    // None once the zone has stopped shrinking
+    pub seconds_to_shrink: Option<f32>,
+}
+
+// Knobs on how a bot plays, written by the difficulty director. Brains are
+// free to ignore them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BotTuning {
+    // 0 plays small and safe, 1 goes for big risky loops
+    pub aggression: f32,
+    pub speed_scale: f32,
+}
+
+impl Default for BotTuning {
+    fn default() -> Self {
+        Self {
+            aggression: 0.5,
+            speed_scale: 1.0,
+        }
+    }
+}
+
+// How hard bots are picked to be for a match, each a fixed tuning to start
+// from
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum BotDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Expert,
+}
+
+impl BotDifficulty {
+    pub const ALL: [BotDifficulty; 4] = [
+        BotDifficulty::Easy,
+        BotDifficulty::Normal,
+        BotDifficulty::Hard,
+        BotDifficulty::Expert,
+    ];
+
+    pub fn tuning(self) -> BotTuning {
+        let (aggression, speed_scale) = match self {
+            BotDifficulty::Easy => (0.2, 0.85),
+            BotDifficulty::Normal => (0.5, 1.0),
+            BotDifficulty::Hard => (0.75, 1.1),
+            BotDifficulty::Expert => (0.9, 1.2),
+        };
+        BotTuning {
+            aggression,
+            speed_scale,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BotDifficulty::Easy => "Easy",
+            BotDifficulty::Normal => "Normal",
+            BotDifficulty::Hard => "Hard",
+            BotDifficulty::Expert => "Expert",
+        }
+    }
+
+    // One step up or down, None past either end
+    pub fn harder(self) -> Option<Self> {
+        let index = Self::ALL
+            .iter()
+            .position(|&difficulty| difficulty == self)?;
+        Self::ALL.get(index + 1).copied()
+    }
+
+    pub fn easier(self) -> Option<Self> {
+        let index = Self::ALL
+            .iter()
+            .position(|&difficulty| difficulty == self)?;
+        index.checked_sub(1).map(|index| Self::ALL[index])
+    }
+}
+
+// Everything handed to a brain when it has to decide
+pub struct BrainInput<'a> {
+    pub me: &'a PlayerView,
+    pub tuning: BotTuning,
+    pub world: &'a WorldSnapshot,
+}
+
+pub trait BotBrain: Send + Sync + 'static {
+    // Direction to steer in, one of the four cardinals or zero to keep going.
+    // Reversals are ignored by the game, as they are for humans.
+    fn decide(&mut self, input: &BrainInput) -> Vec2;
+}
+
+type BrainFactory = Box<dyn Fn() -> Box<dyn BotBrain> + Send + Sync>;
+
+// Brains that can be picked by name
+#[derive(Resource, Default)]
+pub struct BrainRegistry {
+    factories: BTreeMap<String, BrainFactory>,
+}
+
+impl BrainRegistry {
+    pub fn register<B: BotBrain>(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn() -> B + Send + Sync + 'static,
+    ) {
+        self.factories
+            .insert(name.into(), Box::new(move || Box::new(factory())));
+    }
+
+    pub fn create(&self, name: &str) -> Option<Box<dyn BotBrain>> {
+        self.factories.get(name).map(|factory| factory())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+}
+
+// Lets plugins add brains with `app.register_bot_brain("name", MyBrain::new)`
+pub trait RegisterBotBrain {
+    fn register_bot_brain<B: BotBrain>(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn() -> B + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterBotBrain for App {
+    fn register_bot_brain<B: BotBrain>(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn() -> B + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<BrainRegistry>()
+            .register(name, factory);
+        self
+    }
+}
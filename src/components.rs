@@ -1,5 +1,9 @@
 // components.rs
+use crate::resources::TrailPointRules;
+use crate::territory::land_color;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
 #[derive(Component)]
 pub struct Player {
@@ -7,32 +11,265 @@ pub struct Player {
     pub direction: Vec2,
     pub buffered_direction: Option<Vec2>,
     pub score: u32,
+    // Points on top of what the player's land is worth, from bounties and
+    // double claims. Counted in `score` and kept through deaths and decay.
+    pub bonus: u32,
     pub color: Color,
     pub is_drawing_trail: bool,
+    // Tile the player is on, and how far they've got towards the next one
+    // along `direction`, from 0 up to arriving at 1
     pub last_tile_pos: (i32, i32),
-    pub is_moving_to_next_tile: bool,
+    pub progress: f32,
+    pub spawn_tile: (i32, i32),
+    // Heading back over their own trail after bouncing off a mirrored edge,
+    // which doesn't count as running into it
+    pub retracing: bool,
+}
+
+impl Player {
+    // Score for land now worth `value`, plus whatever bonus they've earned
+    pub fn set_land_value(&mut self, value: u32) {
+        self.score = value + self.bonus;
+    }
+
+    pub fn add_bonus(&mut self, points: u32) {
+        self.bonus += points;
+        self.score += points;
+    }
+}
+
+// Marks the player controlled from this machine (as opposed to enemies)
+#[derive(Component)]
+pub struct LocalPlayer;
+
+// The shared camera the game is drawn and the UI laid out with, as opposed
+// to cameras for picture-in-picture or split screen
+#[derive(Component)]
+pub struct MainCamera;
+
+// Player an observer's camera is following
+#[derive(Component)]
+pub struct Followed;
+
+// Rolling buffer of (time, position) samples covering the last few seconds
+#[derive(Component, Default)]
+pub struct PositionHistory {
+    pub samples: VecDeque<(f32, Vec2)>,
+}
+
+// Player is dead and waiting to come back, input is ignored until the timer ends
+#[derive(Component)]
+pub struct Respawning {
+    pub timer: Timer,
+}
+
+// Stands up off the map rather than lying flat on it, which matters in the
+// isometric view: it keeps its shape and what's nearer is drawn over it
+#[derive(Component)]
+pub struct Upright;
+
+// Dead player watching whoever killed them until they respawn
+#[derive(Component)]
+pub struct Spectating {
+    pub killer: Option<Entity>,
 }
 
 #[derive(Component)]
 pub struct Trail {
     pub owner: Entity,
-    pub points: Vec<Vec2>,
+    // Oldest first, see `push`
+    pub points: VecDeque<Vec2>,
     pub is_active: bool,
 }
 
-#[derive(Component)]
+impl Trail {
+    // Adds a point to the end of the trail. A point carrying on in a straight
+    // line moves the last one up instead, and a trail at `max_points` drops
+    // its oldest point to make room.
+    pub fn push(&mut self, point: Vec2, rules: &TrailPointRules) {
+        let len = self.points.len();
+        if len >= 2 {
+            let (before, last) = (self.points[len - 2], self.points[len - 1]);
+            let along = point - before;
+            let off_line = if along.length_squared() > 0.0 {
+                along.perp_dot(last - before).abs() / along.length()
+            } else {
+                before.distance(last)
+            };
+            // Only while still heading the same way, a turn back keeps the corner
+            if off_line <= rules.tolerance && (last - before).dot(point - last) >= 0.0 {
+                self.points[len - 1] = point;
+                return;
+            }
+        }
+
+        self.points.push_back(point);
+        while self.points.len() > rules.max_points.max(2) {
+            self.points.pop_front();
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Tile {
     pub x: i32,
     pub y: i32,
+    // Whose land the tile is
     pub owner: Option<Entity>,
-    pub is_trail: bool,
+    // Whose trail runs over it, separate so a trail crossing someone's land
+    // leaves the land theirs
+    pub trail_owner: Option<Entity>,
+    // Points the tile is worth to whoever holds it, 1 outside value zones
+    // and nothing off the map's shape
+    pub value: u32,
+    // Off the map's shape: nobody can hold it and entering it is deadly
+    pub void: bool,
+    // Stairs to the same spot on the other floor
+    pub stairs: bool,
+}
+
+// What a tile looks like, the checkerboard or whoever has it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileColor(pub Color);
+
+// Square patch of the map whose tiles are worth `value` points each
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueZone {
+    pub center: (i32, i32),
+    // In tiles, like the spawn territory a radius of 2 is 5x5
+    pub radius: i32,
+    pub value: u32,
+}
+
+impl ValueZone {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (x - self.center.0).abs() <= self.radius && (y - self.center.1).abs() <= self.radius
+    }
 }
 
-#[derive(Resource, Clone)]
+// What happens to a player heading off the edge of the map
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GridTopology {
+    // Held on the edge tile until they turn
+    #[default]
+    Clamp,
+    // Bounced back the way they came
+    Mirrored,
+}
+
+impl GridTopology {
+    pub const ALL: [GridTopology; 2] = [GridTopology::Clamp, GridTopology::Mirrored];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GridTopology::Clamp => "solid",
+            GridTopology::Mirrored => "mirrored",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&topology| topology == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+// Outline of the playable part of the map. Tiles outside it are void.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MapShape {
+    #[default]
+    Rectangle,
+    // The biggest circle that fits
+    Circle,
+    // Two bars a third of the map wide crossing in the middle
+    Cross,
+    // An island in the middle and one in each quarter, joined up by
+    // causeways three tiles wide
+    Islands,
+    // Drawn top row first, `#` for a playable tile and anything else for
+    // void. Whatever the rows don't reach is void.
+    Mask(Vec<String>),
+}
+
+impl MapShape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapShape::Rectangle => "rectangle",
+            MapShape::Circle => "circle",
+            MapShape::Cross => "cross",
+            MapShape::Islands => "islands",
+            MapShape::Mask(_) => "custom",
+        }
+    }
+
+    // Whether (x, y) is part of the map on a `width` x `height` grid
+    pub fn playable(&self, x: i32, y: i32, width: i32, height: i32) -> bool {
+        if x < 0 || x >= width || y < 0 || y >= height {
+            return false;
+        }
+        // Offset from the center of the grid, in tiles
+        let (dx, dy) = (
+            x as f32 + 0.5 - width as f32 / 2.0,
+            y as f32 + 0.5 - height as f32 / 2.0,
+        );
+        match self {
+            MapShape::Rectangle => true,
+            MapShape::Circle => {
+                let radius = width.min(height) as f32 / 2.0;
+                dx * dx + dy * dy <= radius * radius
+            }
+            MapShape::Cross => dx.abs() <= width as f32 / 6.0 || dy.abs() <= height as f32 / 6.0,
+            MapShape::Islands => {
+                let radius = width.min(height) as f32 / 6.0;
+                let islands = [
+                    (width / 2, height / 2),
+                    (width / 4, height / 4),
+                    (3 * width / 4, height / 4),
+                    (width / 4, 3 * height / 4),
+                    (3 * width / 4, 3 * height / 4),
+                ];
+                let on_island = islands.iter().any(|&(cx, cy)| {
+                    let (ix, iy) = ((x - cx) as f32, (y - cy) as f32);
+                    ix * ix + iy * iy <= radius * radius
+                });
+                // Along the top and bottom pairs, and down the middle
+                // between them
+                let across = (x >= width / 4 && x <= 3 * width / 4)
+                    && ((y - height / 4).abs() <= 1 || (y - 3 * height / 4).abs() <= 1);
+                let down = (x - width / 2).abs() <= 1 && y >= height / 4 && y <= 3 * height / 4;
+                on_island || across || down
+            }
+            MapShape::Mask(rows) => rows
+                .get((height - 1 - y) as usize)
+                .and_then(|row| row.as_bytes().get(x as usize))
+                .is_some_and(|&cell| cell == b'#'),
+        }
+    }
+}
+
+// A second floor beside the first, the two joined by stairs. Each floor is
+// its own grid with its own land, laid out side by side with a column of
+// void between them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpperFloor {
+    // Ground floor tiles with stairs up to the same tile upstairs
+    pub stairs: Vec<(i32, i32)>,
+}
+
+#[derive(Resource, Clone, Debug, PartialEq)]
 pub struct GridSettings {
     pub tile_size: f32,
     pub grid_width: i32,
     pub grid_height: i32,
+    // Overlapping zones count the most valuable one
+    pub value_zones: Vec<ValueZone>,
+    pub topology: GridTopology,
+    // Applies to each floor on its own
+    pub shape: MapShape,
+    // With one, the grid's width covers both floors and the gap
+    pub upper_floor: Option<UpperFloor>,
 }
 
 impl Default for GridSettings {
@@ -41,6 +278,196 @@ impl Default for GridSettings {
             tile_size: 20.0, // Each tile is 20x20 pixels
             grid_width: 40,  // 40 tiles across (800 pixels)
             grid_height: 30, // 30 tiles high (600 pixels)
+            value_zones: Vec::new(),
+            topology: GridTopology::Clamp,
+            shape: MapShape::Rectangle,
+            upper_floor: None,
+        }
+    }
+}
+
+impl GridSettings {
+    // Adds a floor the same size as the one there is, widening the grid to
+    // fit it
+    pub fn with_upper_floor(mut self, upper_floor: UpperFloor) -> Self {
+        if self.upper_floor.is_none() {
+            self.grid_width = self.grid_width * 2 + 1;
+        }
+        self.upper_floor = Some(upper_floor);
+        self
+    }
+
+    // Columns a floor takes up
+    pub fn floor_width(&self) -> i32 {
+        match self.upper_floor {
+            Some(_) => (self.grid_width - 1) / 2,
+            None => self.grid_width,
         }
     }
+
+    pub fn floor_columns(&self, floor: u8) -> std::ops::Range<i32> {
+        let start = floor as i32 * (self.floor_width() + 1);
+        start..start + self.floor_width()
+    }
+
+    // Floor a column is on, 0 for the ground and 1 upstairs. The gap
+    // between them and anything off the grid is on neither.
+    pub fn floor_of(&self, x: i32) -> Option<u8> {
+        let floors = if self.upper_floor.is_some() { 2 } else { 1 };
+        (0..floors).find(|&floor| self.floor_columns(floor).contains(&x))
+    }
+
+    // Whether a step from one tile to another stays on the grid and on the
+    // same floor
+    pub fn same_floor(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        to.1 >= 0
+            && to.1 < self.grid_height
+            && self.floor_of(to.0).is_some()
+            && self.floor_of(to.0) == self.floor_of(from.0)
+    }
+
+    // Where the stairs on (x, y) come out, if there are any
+    pub fn stairs_from(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        let upper_floor = self.upper_floor.as_ref()?;
+        let up = self.floor_width() + 1;
+        match self.floor_of(x)? {
+            0 => upper_floor.stairs.contains(&(x, y)).then_some((x + up, y)),
+            _ => upper_floor
+                .stairs
+                .contains(&(x - up, y))
+                .then_some((x - up, y)),
+        }
+    }
+
+    // Whether (x, y) is on the map and inside its shape
+    pub fn playable(&self, x: i32, y: i32) -> bool {
+        let Some(floor) = self.floor_of(x) else {
+            return false;
+        };
+        let x = x - self.floor_columns(floor).start;
+        self.shape
+            .playable(x, y, self.floor_width(), self.grid_height)
+    }
+
+    // Tile closest to `near` with a whole 5x5 block of the map around it
+    // for starting territory, or failing that the closest playable tile
+    pub fn spawn_tile_near(&self, near: (i32, i32)) -> (i32, i32) {
+        if self.shape == MapShape::Rectangle && self.playable(near.0, near.1) {
+            return near;
+        }
+        let distance = |(x, y): (i32, i32)| (x - near.0).pow(2) + (y - near.1).pow(2);
+        let tiles = (0..self.grid_height).flat_map(|y| (0..self.grid_width).map(move |x| (x, y)));
+        let roomy = tiles
+            .clone()
+            .filter(|&(x, y)| (-2..=2).all(|dy| (-2..=2).all(|dx| self.playable(x + dx, y + dy))))
+            .min_by_key(|&tile| distance(tile));
+        roomy
+            .or_else(|| {
+                tiles
+                    .filter(|&(x, y)| self.playable(x, y))
+                    .min_by_key(|&tile| distance(tile))
+            })
+            .unwrap_or(near)
+    }
+
+    pub fn tile_value(&self, x: i32, y: i32) -> u32 {
+        if !self.playable(x, y) {
+            return 0;
+        }
+        self.value_zones
+            .iter()
+            .filter(|zone| zone.contains(x, y))
+            .map(|zone| zone.value)
+            .max()
+            .unwrap_or(1)
+    }
+
+    // Every tile's value, row by row from the bottom
+    pub fn tile_values(&self) -> Vec<u32> {
+        (0..self.grid_height)
+            .flat_map(|y| (0..self.grid_width).map(move |x| (x, y)))
+            .map(|(x, y)| self.tile_value(x, y))
+            .collect()
+    }
+}
+
+// Every tile on the map, row by row from the bottom. Tiles are plain data
+// side by side rather than an entity each, so even huge maps stay small and
+// spawning a new one is a single allocation.
+#[derive(Resource, Clone, Default)]
+pub struct Tiles {
+    pub width: i32,
+    pub height: i32,
+    tiles: Vec<Tile>,
+    colors: Vec<TileColor>,
+}
+
+impl Tiles {
+    // A fresh, unclaimed grid
+    pub fn new(grid_settings: &GridSettings) -> Self {
+        let (width, height) = (grid_settings.grid_width, grid_settings.grid_height);
+        let mut tiles = Vec::with_capacity((width.max(0) * height.max(0)) as usize);
+        let mut colors = Vec::with_capacity(tiles.capacity());
+        for y in 0..height {
+            for x in 0..width {
+                let tile = Tile {
+                    x,
+                    y,
+                    owner: None,
+                    trail_owner: None,
+                    value: grid_settings.tile_value(x, y),
+                    void: !grid_settings.playable(x, y),
+                    stairs: grid_settings.stairs_from(x, y).is_some(),
+                };
+                colors.push(TileColor(land_color(&tile, None)));
+                tiles.push(tile);
+            }
+        }
+        Self {
+            width,
+            height,
+            tiles,
+            colors,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        (x >= 0 && x < self.width && y >= 0 && y < self.height)
+            .then(|| (y * self.width + x) as usize)
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<&Tile> {
+        self.index(x, y).and_then(|i| self.tiles.get(i))
+    }
+
+    pub fn get_mut(&mut self, x: i32, y: i32) -> Option<(&mut Tile, &mut TileColor)> {
+        let i = self.index(x, y)?;
+        self.tiles.get_mut(i).zip(self.colors.get_mut(i))
+    }
+
+    pub fn color(&self, x: i32, y: i32) -> Option<Color> {
+        self.index(x, y)
+            .and_then(|i| self.colors.get(i))
+            .map(|color| color.0)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Tile> {
+        self.tiles.iter()
+    }
+
+    pub fn iter_colored(&self) -> impl Iterator<Item = (&Tile, &TileColor)> {
+        self.tiles.iter().zip(self.colors.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&mut Tile, &mut TileColor)> {
+        self.tiles.iter_mut().zip(self.colors.iter_mut())
+    }
 }
@@ -0,0 +1,81 @@
+// config.rs
+// User settings that survive restarts, stored as RON in the data directory.
+use crate::net::browser::BrowserSettings;
+use crate::net::rotation::Playlist;
+use crate::paths::{write_file, Paths};
+use crate::resources::{GameRules, RulesPreset};
+use crate::systems::accessibility::AccessibilitySettings;
+use crate::systems::audio::AudioMixer;
+use crate::systems::camera::CameraMode;
+use crate::systems::display::DisplaySettings;
+use crate::systems::input::ControlSettings;
+use crate::systems::sandbox::SandboxSettings;
+use crate::systems::telemetry::TelemetrySettings;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub audio: AudioMixer,
+    // When set, overrides `rules` with a named preset
+    pub preset: Option<RulesPreset>,
+    pub rules: GameRules,
+    pub telemetry: TelemetrySettings,
+    // Maps a dedicated server rotates through
+    pub playlist: Playlist,
+    pub browser: BrowserSettings,
+    pub camera: CameraMode,
+    pub display: DisplaySettings,
+    pub controls: ControlSettings,
+    pub accessibility: AccessibilitySettings,
+    // What the practice sandbox starts with
+    pub sandbox: SandboxSettings,
+    // Directory under assets/themes, the built-in look if unset
+    pub theme: Option<String>,
+}
+
+impl GameConfig {
+    // Missing or unreadable config falls back to defaults
+    pub fn load(paths: &Paths) -> Self {
+        Self::try_load(paths).unwrap_or_else(|err| {
+            println!("{}, using defaults", err);
+            Self::default()
+        })
+    }
+
+    // Missing config is the defaults, config that can't be parsed an error
+    pub fn try_load(paths: &Paths) -> Result<Self, String> {
+        let path = paths.config();
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Ok(Self::default());
+        };
+
+        ron::from_str(&contents)
+            .map_err(|err| format!("Failed to parse {}: {}", path.display(), err))
+    }
+
+    // Rules the match should be played with
+    pub fn game_rules(&self) -> GameRules {
+        match self.preset {
+            Some(preset) => preset.rules(),
+            None => self.rules.clone(),
+        }
+    }
+
+    pub fn save(&self, paths: &Paths) {
+        let contents = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(contents) => contents,
+            Err(err) => {
+                println!("Failed to serialize config: {}", err);
+                return;
+            }
+        };
+
+        let path = paths.config();
+        if let Err(err) = write_file(&path, &contents) {
+            println!("Failed to write {}: {}", path.display(), err);
+        }
+    }
+}
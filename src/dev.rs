@@ -0,0 +1,220 @@
+// Hot reload for working on the game, built with the `dev` feature. The
+// config file and the level files are polled for changes while the game
+// runs. Edited rules, speeds and colors go straight into a running sandbox,
+// and an edited level is laid out on the sandbox map to try it.
+use crate::components::GridSettings;
+use crate::config::GameConfig;
+use crate::events::GameError;
+use crate::levels::{Campaign, LEVELS_DIR};
+use crate::paths::Paths;
+use crate::resources::{ActivePreset, GameRules};
+use crate::states::{AppState, GameSet};
+use crate::systems::sandbox::{SandboxEvent, SandboxSettings};
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// How often files are checked, in real seconds
+const POLL_SECONDS: f32 = 0.5;
+
+pub struct DevPlugin;
+
+impl Plugin for DevPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HotReload>()
+            .init_resource::<Campaign>()
+            .init_resource::<GameConfig>()
+            .add_event::<HotReloadEvent>()
+            .add_systems(
+                Update,
+                (
+                    watch_files_system,
+                    reload_config_system,
+                    reload_levels_system,
+                )
+                    .chain()
+                    .before(GameSet::Input),
+            );
+    }
+}
+
+#[derive(Event, Clone, Debug, PartialEq)]
+pub enum HotReloadEvent {
+    Config,
+    // Level files added, edited or removed, with the ids of the added and
+    // edited ones, most recent last
+    Levels { edited: Vec<String> },
+}
+
+// Files being watched and when they were last seen changing
+#[derive(Resource)]
+pub struct HotReload {
+    pub levels_dir: PathBuf,
+    pub poll_seconds: f32,
+    since_poll: f32,
+    // Nothing's reported on the first poll, it only notes what's there
+    started: bool,
+    config: Option<SystemTime>,
+    levels: HashMap<PathBuf, SystemTime>,
+}
+
+impl Default for HotReload {
+    fn default() -> Self {
+        Self::new(LEVELS_DIR)
+    }
+}
+
+impl HotReload {
+    pub fn new(levels_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            levels_dir: levels_dir.into(),
+            poll_seconds: POLL_SECONDS,
+            since_poll: 0.0,
+            started: false,
+            config: None,
+            levels: HashMap::new(),
+        }
+    }
+
+    pub fn with_poll_seconds(mut self, seconds: f32) -> Self {
+        self.poll_seconds = seconds;
+        self
+    }
+
+    // What changed since the last poll
+    fn poll(&mut self, paths: &Paths) -> Vec<HotReloadEvent> {
+        let config = modified(&paths.config());
+        let levels = level_files(&self.levels_dir);
+
+        let mut events = Vec::new();
+        if self.started {
+            if config != self.config {
+                events.push(HotReloadEvent::Config);
+            }
+            let mut edited: Vec<(SystemTime, String)> = levels
+                .iter()
+                .filter(|&(path, time)| self.levels.get(path) != Some(time))
+                .filter_map(|(path, &time)| {
+                    let stem = path.file_stem()?.to_string_lossy().into_owned();
+                    Some((time, stem))
+                })
+                .collect();
+            edited.sort();
+            let edited: Vec<String> = edited.into_iter().map(|(_, id)| id).collect();
+            let removed = self.levels.keys().any(|path| !levels.contains_key(path));
+            if !edited.is_empty() || removed {
+                events.push(HotReloadEvent::Levels { edited });
+            }
+        }
+
+        self.started = true;
+        self.config = config;
+        self.levels = levels;
+        events
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn level_files(dir: &Path) -> HashMap<PathBuf, SystemTime> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .filter_map(|path| modified(&path).map(|time| (path, time)))
+        .collect()
+}
+
+// Counts real time, so edits come through while the game is paused too
+pub fn watch_files_system(
+    time: Res<Time<Real>>,
+    paths: Res<Paths>,
+    mut reload: ResMut<HotReload>,
+    mut reload_events: EventWriter<HotReloadEvent>,
+) {
+    reload.since_poll += time.delta_secs();
+    if reload.started && reload.since_poll < reload.poll_seconds {
+        return;
+    }
+    reload.since_poll = 0.0;
+    reload_events.send_batch(reload.poll(&paths));
+}
+
+// A config that doesn't parse is left alone, it's likely half edited
+pub fn reload_config_system(
+    mut reload_events: EventReader<HotReloadEvent>,
+    paths: Res<Paths>,
+    state: Res<State<AppState>>,
+    mut config: ResMut<GameConfig>,
+    mut rules: ResMut<GameRules>,
+    mut preset: ResMut<ActivePreset>,
+    mut sandbox: ResMut<SandboxSettings>,
+) {
+    let mut changed = false;
+    for event in reload_events.read() {
+        changed |= *event == HotReloadEvent::Config;
+    }
+    if !changed {
+        return;
+    }
+
+    let loaded = match GameConfig::try_load(&paths) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            println!("{}, keeping the running config", err);
+            return;
+        }
+    };
+    println!("Reloaded {}", paths.config().display());
+
+    if *state.get() == AppState::Sandbox {
+        *rules = loaded.game_rules();
+        *preset = ActivePreset(loaded.preset);
+        *sandbox = SandboxSettings {
+            painting: sandbox.painting,
+            ..loaded.sandbox.clone()
+        };
+    }
+    *config = loaded;
+}
+
+// Reloads the campaign, and in the sandbox lays out the last edited level
+pub fn reload_levels_system(
+    mut reload_events: EventReader<HotReloadEvent>,
+    reload: Res<HotReload>,
+    state: Res<State<AppState>>,
+    grid_settings: Res<GridSettings>,
+    mut campaign: ResMut<Campaign>,
+    mut sandbox_events: EventWriter<SandboxEvent>,
+    mut error_events: EventWriter<GameError>,
+) {
+    for event in reload_events.read() {
+        let HotReloadEvent::Levels { edited } = event else {
+            continue;
+        };
+
+        let selected = campaign.selected;
+        *campaign = Campaign::load_from(&reload.levels_dir);
+        campaign.selected = selected.min(campaign.levels.len().saturating_sub(1));
+        println!("Reloaded {} levels", campaign.levels.len());
+        error_events.send_batch(campaign.errors());
+
+        if *state.get() != AppState::Sandbox {
+            continue;
+        }
+        let level = edited
+            .iter()
+            .rev()
+            .find_map(|id| campaign.levels.iter().find(|level| level.id == *id));
+        if let Some(level) = level {
+            println!("Laying out {} in the sandbox", level.name);
+            sandbox_events.send(SandboxEvent::Layout(level.tiles(&grid_settings)));
+        }
+    }
+}
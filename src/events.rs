@@ -1,3 +1,7 @@
+use crate::systems::emotes::Emote;
+use crate::systems::stats::MatchAward;
+use crate::systems::weather::WeatherFront;
+use crate::territory::HazardWall;
 use bevy::prelude::*;
 
 // Event that gets triggered when a player should be killed and respawned
@@ -5,6 +9,9 @@ use bevy::prelude::*;
 pub struct PlayerDeathEvent {
     pub player_entity: Entity,
     pub reason: PlayerDeathReason,
+    pub killer: Option<Entity>, // Player responsible, None for self-inflicted deaths
+    pub tile: (i32, i32),       // Tile the player died on
+    pub trail_length: u32,      // Trail tiles that were lost with the death
 }
 
 // Enum to track the reason for player death
@@ -14,4 +21,291 @@ pub enum PlayerDeathReason {
     CrossedTrail,   // Player crossed their trail without returning to territory
     OutOfBounds,    // Player went out of bounds
     HitOtherPlayer, // Player collided with another player
+    TrailCut,       // Another player ran through the trail
+    ZoneClosed,     // Caught outside the shrinking zone
+    HazardWall,     // Run over by a sweeping hazard wall
+}
+
+impl PlayerDeathReason {
+    // One-line explanation shown on the death recap
+    pub fn description(self) -> &'static str {
+        match self {
+            PlayerDeathReason::TrailCollision => "You ran into your own trail",
+            PlayerDeathReason::CrossedTrail => "You crossed your own trail",
+            PlayerDeathReason::OutOfBounds => "You left the arena",
+            PlayerDeathReason::HitOtherPlayer => "You collided with another player",
+            PlayerDeathReason::TrailCut => "Someone cut your trail",
+            PlayerDeathReason::ZoneClosed => "The zone closed in on you",
+            PlayerDeathReason::HazardWall => "A hazard wall swept over you",
+        }
+    }
+}
+
+// A player closed a loop by getting back to their own territory
+#[derive(Event)]
+pub struct TrailCompletedEvent {
+    pub player: Entity,
+    pub entry_point: (i32, i32), // Territory tile the loop was closed on
+}
+
+// A background claim finished and is ready to be applied to the tiles. A
+// territory bomb claims the same way, with no trail.
+#[derive(Event)]
+pub struct ClaimComputedEvent {
+    pub player: Entity,
+    pub trail_tiles: Vec<(i32, i32)>, // Trail the loop was closed with
+    pub pockets: Vec<Vec<(i32, i32)>>, // Free tiles inside the loop, per pocket
+    pub from_bomb: bool,              // Detonated territory bomb, not a loop
+}
+
+// A claim was applied. A trail can close off several separate pockets at
+// once, each reported with the number of tiles actually taken from it.
+#[derive(Event, Clone, Debug)]
+pub struct TerritoryClaimedEvent {
+    pub player: Entity,
+    pub trail_tiles: u32,
+    pub pockets: Vec<u32>,
+    pub points: u32, // Score the claim was worth
+}
+
+// The match clock ran out or someone met the win condition. Standings are
+// ordered best first, and tied scores share a placement (0 = first).
+#[derive(Event, Clone, Debug)]
+pub struct MatchEndedEvent {
+    pub standings: Vec<Standing>,
+    pub awards: Vec<MatchAward>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Standing {
+    pub player: Entity,
+    pub score: u32,
+    pub placement: usize,
+}
+
+// Fired once when the match clock passes each milestone
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MatchTimerEvent {
+    pub milestone: TimerMilestone,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerMilestone {
+    OneMinuteLeft, // Also where the hurry-up phase starts
+    TenSecondsLeft,
+}
+
+impl TimerMilestone {
+    pub const ALL: [TimerMilestone; 2] = [
+        TimerMilestone::OneMinuteLeft,
+        TimerMilestone::TenSecondsLeft,
+    ];
+
+    pub fn seconds_left(self) -> f32 {
+        match self {
+            TimerMilestone::OneMinuteLeft => 60.0,
+            TimerMilestone::TenSecondsLeft => 10.0,
+        }
+    }
+
+    pub fn announcement(self) -> &'static str {
+        match self {
+            TimerMilestone::OneMinuteLeft => "One minute left!",
+            TimerMilestone::TenSecondsLeft => "Ten seconds!",
+        }
+    }
+}
+
+// A player chained kills close together, `kills` so far in the chain
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MultiKillEvent {
+    pub player: Entity,
+    pub kills: u32,
+}
+
+impl MultiKillEvent {
+    pub fn announcement(self) -> String {
+        match self.kills {
+            2 => "Double kill!".to_string(),
+            3 => "Triple kill!".to_string(),
+            kills => format!("{} kill streak!", kills),
+        }
+    }
+}
+
+// A trail cut was soaked up by the victim's comeback shield instead of
+// killing them
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ShieldBrokenEvent {
+    pub player: Entity,
+    pub attacker: Entity,
+}
+
+// Someone killed the leader and collected the bounty on them
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BountyClaimedEvent {
+    pub killer: Entity,
+    pub victim: Entity,
+    pub points: u32,
+}
+
+// A hazard wall was scheduled and is about to start sweeping
+#[derive(Event, Clone, Copy, Debug)]
+pub struct HazardWarningEvent {
+    pub wall: HazardWall,
+}
+
+// A weather front was scheduled and is about to set in
+#[derive(Event, Clone, Copy, Debug)]
+pub struct WeatherWarningEvent {
+    pub front: WeatherFront,
+}
+
+// A player said something in a speech bubble
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct EmoteEvent {
+    pub player: Entity,
+    pub emote: Emote,
+}
+
+// Request to play a one-shot sound effect
+#[derive(Event)]
+pub struct PlaySoundEvent {
+    pub sound: SoundEffect,
+}
+
+// Seconds a toast stays up unless it asks for longer
+pub const TOAST_SECONDS: f32 = 3.0;
+
+// Short notice in the corner of the screen. Toasts queue up behind each
+// other, higher priorities first, so any system can send one.
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct ToastEvent {
+    pub message: String,
+    pub icon: ToastIcon,
+    pub priority: ToastPriority,
+    pub seconds: f32,
+}
+
+impl ToastEvent {
+    pub fn new(icon: ToastIcon, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            icon,
+            priority: ToastPriority::Normal,
+            seconds: TOAST_SECONDS,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: ToastPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn lasting(mut self, seconds: f32) -> Self {
+        self.seconds = seconds;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ToastPriority {
+    Low,
+    #[default]
+    Normal,
+    // Jumps the queue and can push a lower priority toast off the screen
+    High,
+}
+
+// What a toast is about, shown as a small colored badge
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToastIcon {
+    Info,
+    Achievement,
+    Network,
+    Save,
+    Error,
+}
+
+// Something went wrong that the game can carry on from. Shown to the player
+// as a dialog when there's something to retry, a toast otherwise.
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct GameError {
+    pub kind: GameErrorKind,
+    pub detail: String,
+    pub retry: Option<RetryAction>,
+}
+
+impl GameError {
+    pub fn new(kind: GameErrorKind, detail: impl Into<String>) -> Self {
+        Self {
+            kind,
+            detail: detail.into(),
+            retry: None,
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryAction) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    pub fn message(&self) -> String {
+        format!("{}: {}", self.kind.title(), self.detail)
+    }
+
+    pub fn toast(&self) -> ToastEvent {
+        ToastEvent::new(ToastIcon::Error, self.message())
+            .with_priority(ToastPriority::High)
+            .lasting(6.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameErrorKind {
+    MapLoad,
+    // A save that couldn't be read, set aside so it isn't overwritten
+    SaveCorrupt,
+    SaveFailed,
+    ConnectFailed,
+    ConnectionLost,
+    JoinRejected,
+    // Part of a theme was unusable and the built-in look stands in for it
+    ThemeFallback,
+}
+
+impl GameErrorKind {
+    pub fn title(self) -> &'static str {
+        match self {
+            GameErrorKind::MapLoad => "Map failed to load",
+            GameErrorKind::SaveCorrupt => "Save couldn't be read",
+            GameErrorKind::SaveFailed => "Couldn't save",
+            GameErrorKind::ConnectFailed => "Couldn't connect",
+            GameErrorKind::ConnectionLost => "Connection lost",
+            GameErrorKind::JoinRejected => "Couldn't join",
+            GameErrorKind::ThemeFallback => "Theme incomplete",
+        }
+    }
+}
+
+// What trying again means for an error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryAction {
+    ReloadLevels,
+    SaveProfiles,
+    Reconnect,
+}
+
+// The player asked to try something that failed again
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryEvent {
+    pub action: RetryAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEffect {
+    Claim,
+    Death,
+    UiClick,
+    TimerWarning,
 }
@@ -0,0 +1,74 @@
+// grid.rs
+// Conversions between world positions and tile coordinates. The grid is
+// centered on the world origin with tile (0, 0) in the bottom-left corner,
+// and a tile's position is its center.
+use crate::components::GridSettings;
+use bevy::prelude::*;
+
+// The four tiles sharing an edge with a tile
+const EDGE_NEIGHBORS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridMath {
+    pub width: i32,
+    pub height: i32,
+    pub tile_size: f32,
+}
+
+impl GridMath {
+    pub fn new(grid_settings: &GridSettings) -> Self {
+        Self {
+            width: grid_settings.grid_width,
+            height: grid_settings.grid_height,
+            tile_size: grid_settings.tile_size,
+        }
+    }
+
+    // Half the grid's size in world units
+    pub fn half_extent(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32) * self.tile_size / 2.0
+    }
+
+    // Tile a world position falls in, which may be off the grid
+    pub fn tile_of(&self, position: Vec2) -> (i32, i32) {
+        let tile = ((position + self.half_extent()) / self.tile_size).floor();
+        (tile.x as i32, tile.y as i32)
+    }
+
+    // World position of a tile's center
+    pub fn center_of(&self, x: i32, y: i32) -> Vec2 {
+        self.corner_of(x, y) + Vec2::splat(self.tile_size / 2.0)
+    }
+
+    // World position of a tile's bottom-left corner
+    pub fn corner_of(&self, x: i32, y: i32) -> Vec2 {
+        Vec2::new(x as f32, y as f32) * self.tile_size - self.half_extent()
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    // Nearest tile on the grid
+    pub fn clamp(&self, x: i32, y: i32) -> (i32, i32) {
+        (x.clamp(0, self.width - 1), y.clamp(0, self.height - 1))
+    }
+
+    // Tiles on the grid sharing an edge with (x, y)
+    pub fn neighbors(&self, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> {
+        let grid = *self;
+        EDGE_NEIGHBORS
+            .into_iter()
+            .map(move |(dx, dy)| (x + dx, y + dy))
+            .filter(move |&(nx, ny)| grid.in_bounds(nx, ny))
+    }
+
+    // Tiles on the grid within `radius` steps of (x, y) in every direction,
+    // a square including (x, y) itself
+    pub fn around(&self, x: i32, y: i32, radius: i32) -> impl Iterator<Item = (i32, i32)> {
+        let grid = *self;
+        (y - radius..=y + radius)
+            .flat_map(move |ny| (x - radius..=x + radius).map(move |nx| (nx, ny)))
+            .filter(move |&(nx, ny)| grid.in_bounds(nx, ny))
+    }
+}
@@ -0,0 +1,268 @@
+// Reinforcement learning environment over the headless simulation. Agents
+// are ordinary players driven through `InputSource::External`, stepped a
+// few frames at a time with one action each.
+use crate::components::{GridSettings, Player, Tiles};
+use crate::headless::{HeadlessMatch, MatchSetup};
+use crate::resources::GameRules;
+use crate::systems::input::DirectionIntent;
+use bevy::prelude::*;
+
+// Planes in an observation, in this order
+pub const OBSERVATION_CHANNELS: usize = 6;
+pub const OWN_LAND: usize = 0;
+pub const OWN_TRAIL: usize = 1;
+pub const ENEMY_LAND: usize = 2;
+pub const ENEMY_TRAIL: usize = 3;
+pub const OWN_HEAD: usize = 4;
+pub const ENEMY_HEADS: usize = 5;
+
+// Marks the players the environment is stepping
+#[derive(Component)]
+pub struct GymAgent {
+    pub index: usize,
+}
+
+#[derive(Clone)]
+pub struct GymConfig {
+    pub agents: usize,
+    // Built-in bots playing alongside the agents
+    pub bots: usize,
+    // Simulation frames run for every step
+    pub frames_per_step: usize,
+    pub rules: GameRules,
+    pub grid: GridSettings,
+}
+
+impl Default for GymConfig {
+    fn default() -> Self {
+        Self {
+            agents: 1,
+            bots: 0,
+            frames_per_step: 4,
+            rules: GameRules::default(),
+            grid: GridSettings::default(),
+        }
+    }
+}
+
+// Discrete action space, Keep leaves the current heading alone
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Action {
+    #[default]
+    Keep,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Keep,
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+    ];
+
+    pub fn from_index(index: usize) -> Option<Action> {
+        Action::ALL.get(index).copied()
+    }
+
+    fn direction(self) -> Vec2 {
+        match self {
+            Action::Keep => Vec2::ZERO,
+            Action::Up => Vec2::Y,
+            Action::Down => Vec2::NEG_Y,
+            Action::Left => Vec2::NEG_X,
+            Action::Right => Vec2::X,
+        }
+    }
+}
+
+// One agent's view of the grid as a flat channel-major (C, H, W) tensor of
+// zeros and ones
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+}
+
+impl Observation {
+    fn empty(grid: &GridSettings) -> Self {
+        let (width, height) = (grid.grid_width as usize, grid.grid_height as usize);
+        Self {
+            width,
+            height,
+            data: vec![0.0; OBSERVATION_CHANNELS * width * height],
+        }
+    }
+
+    pub fn shape(&self) -> [usize; 3] {
+        [OBSERVATION_CHANNELS, self.height, self.width]
+    }
+
+    pub fn get(&self, channel: usize, x: i32, y: i32) -> f32 {
+        self.index(channel, x, y).map_or(0.0, |i| self.data[i])
+    }
+
+    fn set(&mut self, channel: usize, x: i32, y: i32) {
+        if let Some(i) = self.index(channel, x, y) {
+            self.data[i] = 1.0;
+        }
+    }
+
+    fn index(&self, channel: usize, x: i32, y: i32) -> Option<usize> {
+        let in_bounds = x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height;
+        (in_bounds && channel < OBSERVATION_CHANNELS)
+            .then(|| (channel * self.height + y as usize) * self.width + x as usize)
+    }
+}
+
+impl GymConfig {
+    fn setup(&self) -> MatchSetup {
+        MatchSetup {
+            rules: self.rules.clone(),
+            grid: self.grid.clone(),
+            external_players: self.agents,
+            bots: self.bots,
+            ..default()
+        }
+    }
+}
+
+pub struct GymEnv {
+    config: GymConfig,
+    game: HeadlessMatch,
+    scores: Vec<u32>,
+}
+
+impl GymEnv {
+    pub fn new(config: GymConfig) -> Self {
+        let game = start_match(&config);
+        let mut env = Self {
+            config,
+            game,
+            scores: Vec::new(),
+        };
+        env.scores = env.current_scores();
+        env
+    }
+
+    // Throws the match away and starts a fresh one
+    pub fn reset(&mut self) -> Vec<Observation> {
+        self.game = start_match(&self.config);
+        self.scores = self.current_scores();
+        self.observations()
+    }
+
+    // Applies one action per agent and runs the simulation forward. Rewards
+    // are each agent's change in owned tiles as a fraction of the map, so
+    // claims pay out and deaths cost whatever land they take away.
+    pub fn step(&mut self, actions: &[Action]) -> (Vec<Observation>, Vec<f32>, bool) {
+        let agents = self.game.external_players().to_vec();
+        let world = self.game.app_mut().world_mut();
+        for (agent, action) in agents.into_iter().zip(actions) {
+            if let Some(mut intent) = world.get_mut::<DirectionIntent>(agent) {
+                intent.0 = action.direction();
+            }
+        }
+
+        for _ in 0..self.config.frames_per_step {
+            self.game.step();
+        }
+
+        let scores = self.current_scores();
+        let map_tiles = (self.config.grid.grid_width * self.config.grid.grid_height) as f32;
+        let rewards = scores
+            .iter()
+            .zip(&self.scores)
+            .map(|(&now, &before)| (now as f32 - before as f32) / map_tiles)
+            .collect();
+        self.scores = scores;
+
+        let done = self.game.finished();
+        (self.observations(), rewards, done)
+    }
+
+    pub fn observations(&mut self) -> Vec<Observation> {
+        let agents = self.game.external_players().to_vec();
+        let world = self.game.app_mut().world_mut();
+        let tiles: Vec<(i32, i32, Option<Entity>, Option<Entity>)> = world
+            .resource::<Tiles>()
+            .iter()
+            .map(|tile| (tile.x, tile.y, tile.owner, tile.trail_owner))
+            .collect();
+        let heads: Vec<(Entity, (i32, i32))> = world
+            .query::<(Entity, &Player)>()
+            .iter(world)
+            .map(|(entity, player)| (entity, player.last_tile_pos))
+            .collect();
+
+        agents
+            .into_iter()
+            .map(|agent| {
+                let mut observation = Observation::empty(&self.config.grid);
+                // A trail and the land under it each show on their own channel
+                for &(x, y, owner, trail_owner) in tiles.iter() {
+                    if let Some(owner) = owner {
+                        let channel = if owner == agent { OWN_LAND } else { ENEMY_LAND };
+                        observation.set(channel, x, y);
+                    }
+                    if let Some(trail_owner) = trail_owner {
+                        let channel = if trail_owner == agent {
+                            OWN_TRAIL
+                        } else {
+                            ENEMY_TRAIL
+                        };
+                        observation.set(channel, x, y);
+                    }
+                }
+                for &(entity, (x, y)) in heads.iter() {
+                    let channel = if entity == agent {
+                        OWN_HEAD
+                    } else {
+                        ENEMY_HEADS
+                    };
+                    observation.set(channel, x, y);
+                }
+                observation
+            })
+            .collect()
+    }
+
+    pub fn agents(&self) -> &[Entity] {
+        self.game.external_players()
+    }
+
+    pub fn app_mut(&mut self) -> &mut App {
+        self.game.app_mut()
+    }
+
+    fn current_scores(&self) -> Vec<u32> {
+        self.game
+            .external_players()
+            .iter()
+            .map(|&agent| {
+                self.game
+                    .app()
+                    .world()
+                    .get::<Player>(agent)
+                    .map_or(0, |player| player.score)
+            })
+            .collect()
+    }
+}
+
+// Plays a fresh match with the agents tagged
+fn start_match(config: &GymConfig) -> HeadlessMatch {
+    let mut game = HeadlessMatch::new(&config.setup());
+    for (index, agent) in game.external_players().to_vec().into_iter().enumerate() {
+        game.app_mut()
+            .world_mut()
+            .entity_mut(agent)
+            .insert(GymAgent { index });
+    }
+    game
+}
@@ -0,0 +1,224 @@
+// Matches run without a window, renderer or audio: just `GamePlugin` stepped
+// on a fixed clock. Each match owns its own `App`, so any number of them can
+// run side by side, on as many threads as are handy.
+use crate::components::{GridSettings, Player};
+use crate::events::PlayerDeathEvent;
+use crate::player_bundle;
+use crate::resources::{GameRules, GameState, MatchSeed};
+use crate::states::AppState;
+use crate::systems::bots::Bot;
+use crate::systems::countdown::MatchCountdown;
+use crate::systems::input::{DirectionIntent, InputSource};
+use crate::GamePlugin;
+use bevy::ecs::event::EventCursor;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+use std::thread;
+use std::time::Duration;
+
+pub const FRAME: Duration = Duration::from_millis(1000 / 60);
+
+// Everything that decides how a headless match plays out
+#[derive(Clone)]
+pub struct MatchSetup {
+    pub rules: GameRules,
+    pub grid: GridSettings,
+    // Players steered from outside through their `DirectionIntent`
+    pub external_players: usize,
+    pub bots: usize,
+    pub match_seconds: f32,
+    // Seed for the map's randomness, a fresh one if unset
+    pub seed: Option<u32>,
+}
+
+impl Default for MatchSetup {
+    fn default() -> Self {
+        Self {
+            rules: GameRules::default(),
+            grid: GridSettings::default(),
+            external_players: 0,
+            bots: 4,
+            match_seconds: 300.0,
+            seed: None,
+        }
+    }
+}
+
+// How a finished (or abandoned) match went
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchSummary {
+    // Final scores, highest first
+    pub scores: Vec<u32>,
+    // What the map is worth, its tile count unless it has value zones
+    pub map_tiles: u32,
+    pub seconds: f32,
+    pub deaths: usize,
+    // Deaths caused by another player
+    pub kills: usize,
+}
+
+impl MatchSummary {
+    // Fraction of the map the winner ended up holding
+    pub fn winner_share(&self) -> f32 {
+        self.scores.first().map_or(0.0, |&score| score as f32) / self.map_tiles as f32
+    }
+}
+
+pub struct HeadlessMatch {
+    app: App,
+    external_players: Vec<Entity>,
+    map_tiles: u32,
+    death_cursor: EventCursor<PlayerDeathEvent>,
+    deaths: usize,
+    kills: usize,
+}
+
+impl HeadlessMatch {
+    // Builds the app, spawns everyone and skips the countdown, so the first
+    // `step` is already live play
+    pub fn new(setup: &MatchSetup) -> Self {
+        let game_state = GameState {
+            timer: Timer::from_seconds(setup.match_seconds, TimerMode::Once),
+            ..default()
+        };
+
+        let mut app = App::new();
+        app.insert_resource(setup.rules.clone())
+            .insert_resource(setup.grid.clone())
+            .insert_resource(game_state)
+            .add_plugins((MinimalPlugins, StatesPlugin, GamePlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+            .init_resource::<ButtonInput<KeyCode>>();
+        if let Some(seed) = setup.seed {
+            app.insert_resource(MatchSeed(seed));
+        }
+        app.update();
+
+        app.world_mut()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+
+        let world = app.world_mut();
+        let external_players = (0..setup.external_players)
+            .map(|slot| {
+                world
+                    .spawn((
+                        player_bundle(&setup.grid, slot),
+                        InputSource::External,
+                        DirectionIntent::default(),
+                    ))
+                    .id()
+            })
+            .collect();
+        for slot in setup.external_players..setup.external_players + setup.bots {
+            world.spawn((
+                player_bundle(&setup.grid, slot),
+                InputSource::External,
+                DirectionIntent::default(),
+                Bot::default(),
+            ));
+        }
+
+        if let Some(mut countdown) = world.get_resource_mut::<MatchCountdown>() {
+            let duration = countdown.timer.duration();
+            countdown.timer.set_elapsed(duration);
+        }
+
+        let mut headless = Self {
+            app,
+            external_players,
+            map_tiles: setup.grid.tile_values().iter().sum(),
+            death_cursor: EventCursor::default(),
+            deaths: 0,
+            kills: 0,
+        };
+        headless.step();
+        headless
+    }
+
+    // Advances the simulation by one frame
+    pub fn step(&mut self) {
+        self.app.update();
+
+        let events = self.app.world().resource::<Events<PlayerDeathEvent>>();
+        for event in self.death_cursor.read(events) {
+            self.deaths += 1;
+            if event.killer.is_some() {
+                self.kills += 1;
+            }
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        !self.app.world().resource::<GameState>().game_running
+    }
+
+    // Steps until the clock runs out or a goal ends the match
+    pub fn run_to_end(mut self) -> MatchSummary {
+        while !self.finished() {
+            self.step();
+        }
+        self.summary()
+    }
+
+    pub fn summary(&mut self) -> MatchSummary {
+        let world = self.app.world_mut();
+        let mut scores: Vec<u32> = world
+            .query::<&Player>()
+            .iter(world)
+            .map(|player| player.score)
+            .collect();
+        scores.sort_unstable_by(|a, b| b.cmp(a));
+
+        MatchSummary {
+            scores,
+            map_tiles: self.map_tiles,
+            seconds: world.resource::<GameState>().timer.elapsed_secs(),
+            deaths: self.deaths,
+            kills: self.kills,
+        }
+    }
+
+    pub fn external_players(&self) -> &[Entity] {
+        &self.external_players
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+}
+
+// Plays every setup to the end, spread across `threads` worker threads.
+// Summaries come back in the same order as the setups.
+pub fn run_batch(setups: &[MatchSetup], threads: usize) -> Vec<MatchSummary> {
+    let threads = threads.clamp(1, setups.len().max(1));
+
+    let mut results: Vec<(usize, MatchSummary)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|worker| {
+                scope.spawn(move || {
+                    // Apps aren't Send, so each match is built on the thread
+                    // that plays it
+                    (worker..setups.len())
+                        .step_by(threads)
+                        .map(|index| (index, HeadlessMatch::new(&setups[index]).run_to_end()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("headless match panicked"))
+            .collect()
+    });
+
+    results.sort_by_key(|&(index, _)| index);
+    results.into_iter().map(|(_, summary)| summary).collect()
+}
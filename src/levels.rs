@@ -0,0 +1,184 @@
+// levels.rs
+// Hand-authored puzzle levels for the campaign, one RON file per level in
+// assets/levels, played in file name order.
+use crate::components::GridSettings;
+use crate::events::{GameError, GameErrorKind, RetryAction};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+pub const LEVELS_DIR: &str = "assets/levels";
+
+// One puzzle. The layout is drawn top row first and centred on the grid:
+//   .  free tile
+//   #  enemy territory
+//   +  enemy trail
+//   S  player start
+#[derive(Clone, Debug, Deserialize)]
+pub struct Level {
+    // File stem, used to remember stars across renames of the title
+    #[serde(skip)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub hint: String,
+    pub layout: Vec<String>,
+    // Territory the player has to hold to finish
+    pub goal_tiles: u32,
+    #[serde(default)]
+    pub max_loops: Option<u32>,
+    #[serde(default)]
+    pub max_deaths: Option<u32>,
+    #[serde(default)]
+    pub time_limit: Option<f32>,
+    // Finishing within par earns three stars, within twice par two
+    pub par_seconds: f32,
+}
+
+// Level layout resolved to grid tiles
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelTiles {
+    pub start: Option<(i32, i32)>,
+    pub enemy_land: Vec<(i32, i32)>,
+    pub enemy_trail: Vec<(i32, i32)>,
+}
+
+impl Level {
+    pub fn tiles(&self, grid_settings: &GridSettings) -> LevelTiles {
+        let rows = self.layout.len() as i32;
+        let columns = self
+            .layout
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0) as i32;
+        let offset_x = (grid_settings.grid_width - columns) / 2;
+        let offset_y = (grid_settings.grid_height - rows) / 2;
+
+        let mut tiles = LevelTiles::default();
+        for (row, line) in self.layout.iter().enumerate() {
+            let y = offset_y + rows - 1 - row as i32;
+            for (column, cell) in line.chars().enumerate() {
+                let tile = (offset_x + column as i32, y);
+                if tile.0 < 0
+                    || tile.0 >= grid_settings.grid_width
+                    || tile.1 < 0
+                    || tile.1 >= grid_settings.grid_height
+                {
+                    continue;
+                }
+
+                match cell {
+                    '#' => tiles.enemy_land.push(tile),
+                    '+' => tiles.enemy_trail.push(tile),
+                    'S' => tiles.start = Some(tile),
+                    _ => {}
+                }
+            }
+        }
+        tiles
+    }
+
+    pub fn stars(&self, seconds: f32) -> u8 {
+        if seconds <= self.par_seconds {
+            3
+        } else if seconds <= self.par_seconds * 2.0 {
+            2
+        } else {
+            1
+        }
+    }
+
+    // Constraints in a line, for the level select and HUD
+    pub fn rules_summary(&self) -> String {
+        let mut rules = vec![format!("hold {} tiles", self.goal_tiles)];
+        if let Some(loops) = self.max_loops {
+            rules.push(format!(
+                "max {} loop{}",
+                loops,
+                if loops == 1 { "" } else { "s" }
+            ));
+        }
+        if let Some(deaths) = self.max_deaths {
+            rules.push(match deaths {
+                0 => "no deaths".to_string(),
+                _ => format!("max {} deaths", deaths),
+            });
+        }
+        if let Some(limit) = self.time_limit {
+            rules.push(format!("{:.0}s limit", limit));
+        }
+        rules.join(", ")
+    }
+}
+
+// Every level found on disk, and the one highlighted on the level select
+#[derive(Resource, Default)]
+pub struct Campaign {
+    pub levels: Vec<Level>,
+    pub selected: usize,
+    // Level files that couldn't be read, with why
+    pub failed: Vec<String>,
+}
+
+impl Campaign {
+    // Broken level files are skipped and remembered rather than failing
+    pub fn load() -> Self {
+        Self::load_from(Path::new(LEVELS_DIR))
+    }
+
+    pub fn load_from(dir: &Path) -> Self {
+        let Ok(entries) = fs::read_dir(dir) else {
+            println!("No levels found in {}", dir.display());
+            return Self::default();
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect();
+        paths.sort();
+
+        let mut levels = Vec::new();
+        let mut failed = Vec::new();
+        for path in paths {
+            let parsed = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|contents| {
+                    ron::from_str::<Level>(&contents).map_err(|err| err.to_string())
+                });
+
+            match parsed {
+                Ok(mut level) => {
+                    level.id = path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    levels.push(level);
+                }
+                Err(err) => {
+                    println!("Failed to load level {}: {}", path.display(), err);
+                    failed.push(format!("{}: {}", path.display(), err));
+                }
+            }
+        }
+
+        Self {
+            levels,
+            selected: 0,
+            failed,
+        }
+    }
+
+    // One error per level that couldn't be read, each offering a reload
+    pub fn errors(&self) -> Vec<GameError> {
+        self.failed
+            .iter()
+            .map(|failure| {
+                GameError::new(GameErrorKind::MapLoad, failure.clone())
+                    .with_retry(RetryAction::ReloadLevels)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,1108 @@
+// lib.rs
+// The game is split in two plugins: `GamePlugin` is the simulation and runs
+// headless (tests drive it with `MinimalPlugins`), `ClientPlugin` adds the
+// camera, audio and UI on top of it.
+use bevy::prelude::*;
+use bevy::render::view::VisibilitySystems;
+use bevy::sprite::Material2dPlugin;
+pub mod balance;
+pub mod brain;
+pub mod components;
+pub mod config;
+#[cfg(feature = "dev")]
+pub mod dev;
+pub mod events;
+pub mod grid;
+#[cfg(feature = "gym")]
+pub mod gym;
+pub mod headless;
+pub mod levels;
+pub mod net;
+pub mod paths;
+pub mod profiles;
+pub mod progression;
+pub mod resources;
+pub mod share;
+pub mod states;
+pub mod stats;
+pub mod systems;
+pub mod territory;
+pub mod themes;
+pub mod tournament;
+pub mod win_condition;
+
+use brain::{BrainRegistry, RegisterBotBrain, DEFAULT_BRAIN};
+use components::*;
+use config::GameConfig;
+use events::{
+    BountyClaimedEvent, ClaimComputedEvent, EmoteEvent, GameError, HazardWarningEvent,
+    MatchEndedEvent, MatchTimerEvent, MultiKillEvent, PlaySoundEvent, PlayerDeathEvent, RetryEvent,
+    ShieldBrokenEvent, Standing, TerritoryClaimedEvent, TimerMilestone, ToastEvent,
+    TrailCompletedEvent, WeatherWarningEvent,
+};
+use grid::GridMath;
+use levels::Campaign;
+use paths::Paths;
+use profiles::{ActiveProfiles, ProfileStore};
+use resources::*;
+use states::{AppState, GameSet, PauseState};
+use stats::StatsStore;
+use systems::abilities::*;
+use systems::accessibility::*;
+use systems::analysis::{sync_ownership_layers_system, update_territory_analysis_system};
+use systems::announcer::*;
+use systems::attract::*;
+use systems::audio::*;
+use systems::board::*;
+use systems::bots::{assign_bot_brains_system, bot_ai_system, LoopBrain};
+use systems::bounty::*;
+use systems::camera::*;
+use systems::challenges::*;
+use systems::cinematic::*;
+use systems::claim_preview::*;
+use systems::coach::*;
+use systems::collision::*;
+use systems::comeback::*;
+use systems::countdown::*;
+use systems::daily::*;
+use systems::decoy::*;
+use systems::difficulty::*;
+use systems::director::*;
+use systems::display::*;
+use systems::emotes::*;
+use systems::errors::*;
+use systems::floors::*;
+use systems::hazards::*;
+use systems::heatmap::*;
+use systems::hints::*;
+use systems::history::*;
+use systems::home_view::*;
+use systems::input::*;
+use systems::join::*;
+use systems::killcam::*;
+use systems::minimap::*;
+use systems::movement::*;
+use systems::music::*;
+use systems::pause::*;
+use systems::photo::*;
+use systems::pickups::*;
+use systems::player::{handle_player_death, respawn_timer_system, territory_decay_system};
+use systems::profiles::*;
+use systems::projection::*;
+use systems::proximity::*;
+use systems::puzzle::*;
+use systems::quick_play::*;
+use systems::rating::update_ratings_system;
+use systems::results::*;
+use systems::sandbox::*;
+use systems::score_hud::*;
+use systems::settings::*;
+use systems::split_screen::*;
+use systems::stats::*;
+use systems::telemetry::*;
+use systems::theme::*;
+use systems::tile_effects::*;
+use systems::toasts::*;
+use systems::tournament::*;
+use systems::trails::*;
+use systems::weather::*;
+use systems::zone::*;
+use themes::{ThemeManifest, ThemeManifestLoader};
+use win_condition::WinVariables;
+
+// Grid, players, movement, trails, deaths and claims
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .add_sub_state::<PauseState>()
+            .add_event::<PlayerDeathEvent>()
+            .add_event::<PlaySoundEvent>()
+            .add_event::<ToastEvent>()
+            .add_event::<GameError>()
+            .add_event::<RetryEvent>()
+            .add_event::<TrailCompletedEvent>()
+            .add_event::<ClaimComputedEvent>()
+            .add_event::<TerritoryClaimedEvent>()
+            .add_event::<MatchTimerEvent>()
+            .add_event::<MatchEndedEvent>()
+            .add_event::<HazardWarningEvent>()
+            .add_event::<WeatherWarningEvent>()
+            .add_event::<MultiKillEvent>()
+            .add_event::<BountyClaimedEvent>()
+            .add_event::<ShieldBrokenEvent>()
+            .add_event::<EmoteEvent>()
+            .init_resource::<GameRules>()
+            .init_resource::<Paths>()
+            .init_resource::<ControlSettings>()
+            .init_resource::<GameState>()
+            .init_resource::<GridSettings>()
+            .init_resource::<Tiles>()
+            .init_resource::<TileFlashes>()
+            .init_resource::<BotChatter>()
+            .init_resource::<JoinedPlayers>()
+            .init_resource::<SelectedMap>()
+            .init_resource::<ActivePreset>()
+            .init_resource::<BotDifficultySetting>()
+            .init_resource::<ShareCodeEntry>()
+            .init_resource::<MatchSeed>()
+            .init_resource::<MatchRng>()
+            .init_resource::<TerritoryAnalysis>()
+            .init_resource::<OwnershipLayers>()
+            .init_resource::<PendingClaims>()
+            .init_resource::<TrailHistory>()
+            .init_resource::<AttractMode>()
+            .add_systems(Startup, setup_grid)
+            .add_systems(
+                Update,
+                join_detection_system
+                    .run_if(in_state(AppState::Join).and(not(entering_share_code))),
+            )
+            .add_systems(
+                Update,
+                attract_mode_system
+                    .run_if(in_state(AppState::Join))
+                    .before(GameSet::Input),
+            )
+            .add_systems(
+                OnExit(AppState::Join),
+                (
+                    stop_attract_mode_system,
+                    apply_selected_map.after(stop_attract_mode_system),
+                ),
+            )
+            .add_event::<SandboxEvent>()
+            .init_resource::<SandboxSettings>()
+            .init_resource::<DifficultyDirector>()
+            .init_resource::<PickupDirector>()
+            .init_resource::<BrainRegistry>()
+            .init_resource::<TelemetrySettings>()
+            .init_resource::<MatchStats>()
+            .register_bot_brain(DEFAULT_BRAIN, LoopBrain::default)
+            .add_systems(
+                OnEnter(AppState::Playing),
+                (
+                    spawn_joined_players,
+                    start_tournament_match,
+                    start_daily_challenge,
+                    start_quick_play.after(spawn_joined_players),
+                    start_level.after(spawn_joined_players),
+                    start_countdown,
+                    reset_difficulty_director,
+                    start_telemetry,
+                    reset_match_stats,
+                    setup_safe_zone,
+                    setup_hazard_schedule,
+                    setup_weather_schedule,
+                    seed_match_rng,
+                ),
+            )
+            .add_systems(
+                OnExit(AppState::Playing),
+                (
+                    stop_telemetry,
+                    cleanup_safe_zone,
+                    cleanup_hazard_schedule,
+                    cleanup_weather_schedule,
+                    cleanup_decoys,
+                    next_match_seed,
+                    end_daily_challenge,
+                ),
+            )
+            .add_systems(
+                OnEnter(AppState::Sandbox),
+                (spawn_joined_players, seed_match_rng),
+            )
+            .add_systems(OnEnter(AppState::Join), end_quick_play)
+            .add_systems(
+                Update,
+                (
+                    leave_level_system,
+                    leave_daily_challenge_system,
+                    record_tournament_match_system.after(GameSet::Claim),
+                    leave_tournament_match_system.after(record_tournament_match_system),
+                )
+                    .run_if(in_state(AppState::Playing)),
+            )
+            .add_systems(
+                Update,
+                record_telemetry_system
+                    .after(GameSet::Claim)
+                    .run_if(resource_exists::<TelemetryWriter>),
+            )
+            .add_systems(
+                Update,
+                (
+                    sandbox_speed_system,
+                    sandbox_bot_count_system,
+                    sandbox_event_system,
+                    sandbox_color_system,
+                )
+                    .before(GameSet::Input)
+                    .run_if(in_state(AppState::Sandbox)),
+            )
+            .add_systems(
+                Update,
+                sandbox_instant_respawn_system
+                    .before(respawn_timer_system)
+                    .in_set(GameSet::Collision)
+                    .run_if(in_state(AppState::Sandbox)),
+            )
+            .init_resource::<WindowFocus>()
+            .add_systems(
+                Update,
+                auto_pause_system
+                    .run_if(resource_exists::<State<PauseState>>.and(one_local_player))
+                    .before(GameSet::Input),
+            )
+            .add_systems(OnEnter(PauseState::Paused), pause_time)
+            .add_systems(OnExit(PauseState::Paused), resume_time)
+            .add_systems(OnEnter(PauseState::Photo), pause_time)
+            .add_systems(OnExit(PauseState::Photo), resume_time)
+            .add_systems(Update, init_player_territory.before(GameSet::Input))
+            .add_systems(Update, game_speed_system.before(GameSet::Input))
+            .add_systems(Update, assign_bot_brains_system.before(GameSet::Input))
+            .add_systems(Update, grant_energy_system.before(GameSet::Input))
+            .configure_sets(
+                Update,
+                (
+                    GameSet::Input,
+                    GameSet::Movement,
+                    GameSet::TrailUpdate,
+                    GameSet::Collision,
+                    GameSet::Claim,
+                    GameSet::Render,
+                )
+                    .chain(),
+            )
+            .configure_sets(
+                Update,
+                (
+                    GameSet::Input,
+                    GameSet::Movement,
+                    GameSet::TrailUpdate,
+                    GameSet::Collision,
+                    GameSet::Claim,
+                )
+                    .run_if(simulation_active.and(not(match_held))),
+            )
+            // Online the server simulates, but the visuals still follow along
+            .configure_sets(
+                Update,
+                GameSet::Render.run_if(simulation_active.or(in_state(AppState::Online))),
+            )
+            // Nobody moves until the countdown says GO
+            .configure_sets(
+                Update,
+                (GameSet::Input, GameSet::Movement).run_if(countdown_finished),
+            )
+            .add_systems(
+                Update,
+                countdown_system
+                    .run_if(in_state(AppState::Playing))
+                    .before(GameSet::Input),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        device_input_system,
+                        ability_input_system,
+                        scripted_input_system,
+                        bot_ai_system,
+                    ),
+                    player_input_system,
+                )
+                    .chain()
+                    .in_set(GameSet::Input),
+            )
+            .add_systems(
+                Update,
+                (
+                    (start_trail_system, player_movement_system).chain(),
+                    move_decoys_system,
+                    weather_system
+                        .before(player_movement_system)
+                        .run_if(resource_exists::<WeatherSchedule>),
+                )
+                    .in_set(GameSet::Movement),
+            )
+            .add_systems(Update, update_trail_system.in_set(GameSet::TrailUpdate))
+            .add_systems(
+                Update,
+                (
+                    (
+                        collision_detection_system,
+                        trail_cut_system,
+                        handle_player_death,
+                    )
+                        .chain(),
+                    track_chases_system.before(handle_player_death),
+                    count_match_deaths_system.after(handle_player_death),
+                    collect_bounty_system.after(handle_player_death),
+                    break_comeback_shields_system.after(trail_cut_system),
+                    pop_decoys_system,
+                    shrink_zone_system
+                        .before(handle_player_death)
+                        .run_if(resource_exists::<SafeZone>),
+                    sweep_hazard_walls_system
+                        .before(handle_player_death)
+                        .run_if(resource_exists::<HazardSchedule>),
+                    respawn_timer_system,
+                )
+                    .in_set(GameSet::Collision),
+            )
+            .add_systems(
+                Update,
+                record_trail_history_system
+                    .after(GameSet::Claim)
+                    .run_if(simulation_active.and(|rules: Res<GameRules>| rules.trail_cuts)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        claim_territory_system,
+                        poll_claim_tasks_system,
+                        apply_claim_system,
+                        territory_decay_system,
+                        collect_pickups_system,
+                        sync_ownership_layers_system,
+                        update_territory_analysis_system,
+                    )
+                        .chain(),
+                    game_timer_system,
+                    win_condition_system
+                        .after(game_timer_system)
+                        .after(sync_ownership_layers_system),
+                    daily_challenge_goal_system
+                        .after(game_timer_system)
+                        .after(sync_ownership_layers_system),
+                    level_goal_system
+                        .after(game_timer_system)
+                        .after(sync_ownership_layers_system),
+                    difficulty_director_system
+                        .after(sync_ownership_layers_system)
+                        .run_if(in_state(AppState::Playing)),
+                    speed_boost_system,
+                    ghost_system,
+                    use_abilities_system.before(apply_claim_system),
+                    track_bounty_leader_system.after(apply_claim_system),
+                    count_claim_streaks_system.after(apply_claim_system),
+                    bot_chatter_system.after(apply_claim_system),
+                    comeback_system.after(sync_ownership_layers_system),
+                    sample_match_stats_system.after(sync_ownership_layers_system),
+                    // Puzzles and the daily challenge are played without pickups
+                    pickup_director_system.after(collect_pickups_system).run_if(
+                        in_state(AppState::Playing)
+                            .and(not(resource_exists::<ActiveLevel>))
+                            .and(not(resource_exists::<DailyChallenge>)),
+                    ),
+                )
+                    .in_set(GameSet::Claim),
+            );
+    }
+}
+
+// Camera, visuals, audio and menus on top of the simulation
+pub struct ClientPlugin;
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameConfig>()
+            .init_resource::<ProfileStore>()
+            .init_resource::<ActiveProfiles>()
+            .init_resource::<WeeklyChallenges>()
+            .init_resource::<Campaign>()
+            .init_resource::<StatsStore>()
+            .init_resource::<HeatmapView>()
+            .init_resource::<AudioMixer>()
+            .init_resource::<VoiceCooldowns>()
+            .init_resource::<ProximitySettings>()
+            .init_resource::<ProximityWarnings>()
+            .init_resource::<DangerScore>()
+            .init_resource::<MusicIntensity>()
+            .init_resource::<KillCamFocus>()
+            .init_resource::<CameraMode>()
+            .init_resource::<CameraTween>()
+            .init_resource::<PendingResults>()
+            .init_resource::<CoachOverlay>()
+            .init_resource::<ClaimPreviews>()
+            .init_resource::<DisplaySettings>()
+            .init_resource::<DisplayRevert>()
+            .init_resource::<AccessibilitySettings>()
+            .init_resource::<SettingsTab>()
+            .init_resource::<Rebinding>()
+            .init_resource::<ToastQueue>()
+            .init_resource::<StartupErrors>()
+            .init_resource::<ErrorDialogs>()
+            .init_resource::<Theme>()
+            .init_resource::<Board>()
+            .init_resource::<ActiveFloor>()
+            .init_resource::<Vision>()
+            .init_resource::<EmoteWheel>()
+            .init_asset::<ThemeManifest>()
+            .init_asset_loader::<ThemeManifestLoader>()
+            .add_plugins(Material2dPlugin::<BoardMaterial>::default())
+            .add_systems(
+                Startup,
+                (
+                    setup_camera,
+                    setup_toast_stack,
+                    report_startup_errors_system,
+                    setup_minimap,
+                    load_sounds,
+                    load_theme_system,
+                    start_music,
+                    setup_settings_panel,
+                    setup_home_view,
+                ),
+            )
+            .add_systems(OnEnter(PauseState::Paused), setup_pause_overlay)
+            .add_systems(OnExit(PauseState::Paused), cleanup_pause_overlay)
+            .add_systems(
+                Update,
+                toggle_photo_mode_system.run_if(resource_exists::<State<PauseState>>),
+            )
+            .add_systems(OnEnter(PauseState::Photo), enter_photo_mode)
+            .add_systems(OnExit(PauseState::Photo), exit_photo_mode)
+            .add_systems(
+                Update,
+                (
+                    photo_input_system,
+                    photo_camera_system,
+                    photo_ui_system,
+                    take_photo_system,
+                )
+                    .chain()
+                    .run_if(resource_exists::<PhotoMode>)
+                    .after(kill_cam_camera_system),
+            )
+            .add_systems(
+                PreUpdate,
+                track_window_focus_system.after(bevy::input::InputSystem),
+            )
+            .add_systems(Update, input_lost_indicator_system)
+            .add_systems(OnEnter(AppState::Sandbox), setup_sandbox_panel)
+            .add_systems(OnExit(AppState::Sandbox), cleanup_sandbox_panel)
+            .add_systems(
+                Update,
+                (
+                    sandbox_slider_system,
+                    sandbox_button_system,
+                    update_sandbox_panel_system,
+                    sandbox_paint_system,
+                )
+                    .run_if(in_state(AppState::Sandbox)),
+            )
+            .add_systems(
+                OnEnter(AppState::Playing),
+                (
+                    setup_countdown_text,
+                    setup_daily_challenge_hud,
+                    setup_energy_hud,
+                    setup_level_hud,
+                    setup_score_hud,
+                    setup_tournament_hud,
+                    remember_quick_play_system.run_if(
+                        not(resource_exists::<TournamentMatch>)
+                            .and(not(resource_exists::<ActiveLevel>))
+                            .and(not(resource_exists::<DailyChallenge>)),
+                    ),
+                ),
+            )
+            .add_systems(
+                OnExit(AppState::Playing),
+                (
+                    cleanup_level_hud,
+                    cleanup_energy_hud,
+                    cleanup_score_hud,
+                    cleanup_tournament_hud,
+                    cleanup_results_screen,
+                    save_stats,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    emote_wheel_input_system,
+                    emote_wheel_ui_system,
+                    spawn_emote_bubbles_system,
+                    place_emote_bubbles_system,
+                )
+                    .chain()
+                    .after(GameSet::Render)
+                    .run_if(in_state(AppState::Playing).or(in_state(AppState::Online))),
+            )
+            .add_systems(OnExit(AppState::Playing), cleanup_emotes)
+            .add_systems(OnExit(AppState::Online), cleanup_emotes)
+            .add_systems(OnEnter(AppState::Tournament), setup_tournament_screen)
+            .add_systems(OnExit(AppState::Tournament), cleanup_tournament_screen)
+            .add_systems(
+                Update,
+                (tournament_input_system, update_bracket_system)
+                    .chain()
+                    .run_if(in_state(AppState::Tournament)),
+            )
+            .add_systems(OnEnter(AppState::LevelSelect), setup_level_select)
+            .add_systems(OnExit(AppState::LevelSelect), cleanup_level_select)
+            .add_systems(
+                Update,
+                (level_select_input_system, update_level_select_system)
+                    .run_if(in_state(AppState::LevelSelect)),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_daily_challenge_hud_system,
+                    update_energy_hud_system,
+                    update_level_hud_system,
+                    update_tournament_hud_system,
+                    (
+                        sync_score_hud_system,
+                        tween_score_counters_system,
+                        show_score_deltas_system,
+                        update_streak_counters_system,
+                    )
+                        .chain(),
+                    (
+                        skip_camera_tween_system,
+                        start_end_of_match_camera_system,
+                        camera_tween_system,
+                        show_results_system,
+                    )
+                        .chain()
+                        .after(kill_cam_camera_system),
+                )
+                    .run_if(in_state(AppState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_countdown_text_system,
+                    announce_timer_milestones_system,
+                    announce_hazard_walls_system,
+                    announce_weather_system,
+                    announce_multi_kills_system,
+                    announce_bounties_system,
+                    fade_announcements_system,
+                ),
+            )
+            .add_systems(
+                OnEnter(AppState::Join),
+                (setup_join_screen, setup_challenges_panel),
+            )
+            .add_systems(
+                OnExit(AppState::Join),
+                (cleanup_join_screen, cleanup_challenges_panel, hide_heatmap),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_join_screen_system,
+                    update_quick_play_text_system,
+                    (
+                        calibrate_bot_difficulty_system,
+                        update_bot_difficulty_text_system,
+                    )
+                        .chain(),
+                    update_challenges_panel_system,
+                    heatmap_hotkey_system.run_if(not(entering_share_code)),
+                )
+                    .run_if(in_state(AppState::Join)),
+            )
+            .add_systems(Update, heatmap_overlay_system)
+            .add_systems(
+                Update,
+                record_heatmap_system
+                    .after(GameSet::Claim)
+                    .run_if(in_state(AppState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    cycle_profile_system,
+                    cycle_cosmetics_system,
+                    cycle_map_system,
+                    cycle_rules_preset_system,
+                    cycle_bot_difficulty_system,
+                    cycle_game_speed_system,
+                    // P1 gets a profile the same frame Q joins them
+                    quick_play_system.before(assign_profiles_system),
+                    cycle_topology_system,
+                )
+                    .run_if(in_state(AppState::Join).and(not(entering_share_code))),
+            )
+            .add_systems(
+                Update,
+                share_code_entry_system
+                    .after(join_detection_system)
+                    .run_if(in_state(AppState::Join)),
+            )
+            // Profiles are picked as soon as a device joins, and applied before
+            // the new player's starting territory is painted in their color
+            .add_systems(
+                Update,
+                (
+                    assign_profiles_system.after(join_detection_system),
+                    apply_profile_system.before(init_player_territory),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        update_ratings_system,
+                        record_profile_stats_system,
+                        record_bot_matches_system,
+                        record_daily_challenge_system,
+                        record_weekly_challenges_system,
+                        record_level_stars_system,
+                    ),
+                    persist_profiles_system,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                record_position_history_system.in_set(GameSet::TrailUpdate),
+            )
+            .add_systems(
+                Update,
+                start_kill_cam_system
+                    .before(handle_player_death)
+                    .in_set(GameSet::Collision),
+            )
+            .add_systems(
+                Update,
+                (
+                    spawn_home_arrows_system,
+                    render_trail_system,
+                    home_arrow_system,
+                    proximity_warning_system,
+                    update_minimap_texture_system,
+                    minimap_layout_system,
+                    update_minimap_markers_system,
+                    minimap_ping_system,
+                    detect_tile_ownership_change_system,
+                    animate_tile_flash_system,
+                    kill_cam_playback_system,
+                    kill_cam_camera_system,
+                    coach_overlay_system,
+                    claim_preview_system,
+                )
+                    .in_set(GameSet::Render),
+            )
+            // Outside the render set so the countdown is cleared after the match
+            .add_systems(
+                Update,
+                spectate_killer_system
+                    .after(GameSet::Claim)
+                    .after(kill_cam_playback_system)
+                    .before(kill_cam_camera_system),
+            )
+            // Outside the render set so the overlay is cleared after the match
+            .add_systems(
+                Update,
+                (
+                    zone_overlay_system,
+                    hazard_wall_render_system,
+                    weather_overlay_system,
+                    minimap_hazard_system,
+                    minimap_bounty_system,
+                    bounty_crown_system,
+                    comeback_shield_bubble_system,
+                    home_view_system,
+                    (
+                        split_screen_system,
+                        split_screen_main_camera_system,
+                        split_screen_hud_system,
+                    )
+                        .chain(),
+                )
+                    .after(GameSet::Claim),
+            )
+            .add_systems(
+                Update,
+                (
+                    danger_score_system,
+                    music_crossfade_system,
+                    play_sound_system,
+                    (voice_finished_system, announcer_voice_system).chain(),
+                    mute_hotkey_system.run_if(not(entering_share_code)),
+                    apply_mixer_system,
+                    persist_mixer_system,
+                    toggle_settings_panel_system,
+                    toggle_coach_overlay_system,
+                    toggle_camera_mode_system.run_if(not(entering_share_code)),
+                    volume_slider_system,
+                    mute_button_system,
+                    update_audio_settings_ui_system,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    display_button_system,
+                    update_display_settings_ui_system,
+                    persist_display_settings_system,
+                    apply_window_settings_system,
+                    apply_update_mode_system,
+                    display_revert_system,
+                    revert_prompt_system,
+                    revert_button_system,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    settings_tab_system,
+                    update_settings_tabs_system,
+                    rebind_button_system,
+                    capture_rebind_system,
+                    update_controls_ui_system,
+                    persist_controls_system,
+                    gameplay_button_system,
+                    update_gameplay_ui_system,
+                    accessibility_button_system,
+                    update_accessibility_ui_system,
+                    apply_ui_scale_system,
+                    persist_accessibility_settings_system,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    retry_system,
+                    route_game_errors_system,
+                    show_error_dialog_system,
+                    error_dialog_button_system,
+                    queue_toasts_system,
+                    show_toasts_system,
+                    expire_toasts_system,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    resolve_theme_system,
+                    theme_fallback_system,
+                    apply_theme_to_world_system,
+                    apply_theme_to_ui_system,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    active_floor_system,
+                    floor_fade_system,
+                    vision_system,
+                    fog_visibility_system,
+                    update_board_system,
+                    draw_board_system,
+                )
+                    .chain()
+                    .after(GameSet::Render)
+                    .after(apply_theme_to_ui_system),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    reproject_on_change_system.before(TransformSystem::TransformPropagate),
+                    project_view_system
+                        .after(TransformSystem::TransformPropagate)
+                        .before(VisibilitySystems::UpdateFrusta)
+                        .before(VisibilitySystems::CheckVisibility),
+                ),
+            )
+            .add_systems(Last, frame_limiter_system);
+    }
+}
+
+fn setup_camera(mut commands: Commands) {
+    // UI without a camera of its own is always laid out over the whole window
+    commands.spawn((Camera2d, MainCamera, IsDefaultUiCamera));
+}
+
+fn setup_grid(mut commands: Commands, grid_settings: Res<GridSettings>) {
+    spawn_grid(&mut commands, &grid_settings);
+}
+
+// Lays out a fresh, unclaimed grid of tiles
+pub fn spawn_grid(commands: &mut Commands, grid_settings: &GridSettings) {
+    commands.insert_resource(Tiles::new(grid_settings));
+}
+
+// Colors for player slots 1-4
+const SLOT_COLORS: [Color; MAX_LOCAL_PLAYERS] = [
+    Color::srgb(0.2, 0.7, 0.9),
+    Color::srgb(0.95, 0.55, 0.15),
+    Color::srgb(0.3, 0.8, 0.3),
+    Color::srgb(0.7, 0.35, 0.85),
+];
+
+// Starting tile for each slot. The first player keeps the map center, the
+// others are spread out towards the corners, as near as the map's shape
+// allows. With an upper floor every other slot starts up there.
+fn spawn_tile_for_slot(grid_settings: &GridSettings, slot: usize) -> (i32, i32) {
+    let (w, h) = (grid_settings.floor_width(), grid_settings.grid_height);
+    let (x, y) = match slot {
+        0 => (w / 2, h / 2),
+        1 => (w / 4, h / 4),
+        2 => (3 * w / 4, 3 * h / 4),
+        _ => (w / 4, 3 * h / 4),
+    };
+    let floor = if grid_settings.upper_floor.is_some() {
+        slot % 2
+    } else {
+        0
+    };
+    grid_settings.spawn_tile_near((grid_settings.floor_columns(floor as u8).start + x, y))
+}
+
+// Everything a player needs apart from where its input comes from
+pub(crate) fn player_bundle(grid_settings: &GridSettings, slot: usize) -> impl Bundle {
+    player_bundle_at(
+        grid_settings,
+        slot,
+        spawn_tile_for_slot(grid_settings, slot),
+    )
+}
+
+// Same as `player_bundle`, starting on a given tile instead of the slot's own
+pub(crate) fn player_bundle_at(
+    grid_settings: &GridSettings,
+    slot: usize,
+    (start_tile_x, start_tile_y): (i32, i32),
+) -> impl Bundle {
+    let grid = GridMath::new(grid_settings);
+    let tile_size = grid.tile_size;
+
+    let player_color = SLOT_COLORS[slot % SLOT_COLORS.len()];
+
+    // Calculate the exact pixel position of the start tile
+    let player_start = grid.center_of(start_tile_x, start_tile_y);
+
+    (
+        Sprite {
+            color: player_color,
+            custom_size: Some(Vec2::new(tile_size * 0.8, tile_size * 0.8)), // Slightly smaller than tile
+            ..default()
+        },
+        Transform::from_translation(player_start.extend(0.0)),
+        GlobalTransform::default(),
+        Visibility::default(),
+        InheritedVisibility::default(),
+        ViewVisibility::default(),
+        Player {
+            // Tiles per second, brought up to the rules' speed once spawned
+            speed: PLAYER_SPEED,
+            direction: Vec2::ZERO,
+            buffered_direction: None,
+            score: 0,
+            bonus: 0,
+            color: player_color,
+            is_drawing_trail: false,
+            last_tile_pos: (start_tile_x, start_tile_y), // Set to the exact tile position
+            progress: 0.0,
+            spawn_tile: (start_tile_x, start_tile_y),
+            retracing: false,
+        },
+        PositionHistory::default(),
+        Upright,
+    )
+}
+
+// Spawns a player for every device that joined on the join screen. A
+// tournament match only spawns its two sides.
+fn spawn_joined_players(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    joined: Res<JoinedPlayers>,
+    tournament_match: Option<Res<TournamentMatch>>,
+) {
+    if tournament_match.is_some() {
+        return;
+    }
+    for (slot, &device) in joined.devices.iter().enumerate() {
+        commands.spawn((
+            player_bundle(&grid_settings, slot),
+            LocalPlayer,
+            InputSource::Device(device),
+            DirectionIntent::default(),
+        ));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn game_timer_system(
+    time: Res<Time>,
+    mut game_state: ResMut<GameState>,
+    analysis: Res<TerritoryAnalysis>,
+    layers: Res<OwnershipLayers>,
+    stats: Res<MatchStats>,
+    player_query: Query<(Entity, &Player)>,
+    mut timer_events: EventWriter<MatchTimerEvent>,
+    mut match_end_events: EventWriter<MatchEndedEvent>,
+) {
+    if game_state.game_running {
+        let remaining_before = game_state.timer.remaining_secs();
+        game_state.timer.tick(time.delta());
+        let remaining = game_state.timer.remaining_secs();
+
+        // Publish each milestone the clock passed this frame
+        for milestone in TimerMilestone::ALL {
+            let at = milestone.seconds_left();
+            if remaining_before > at && remaining <= at {
+                if milestone == TimerMilestone::OneMinuteLeft {
+                    game_state.hurry_up = true;
+                }
+                timer_events.send(MatchTimerEvent { milestone });
+            }
+        }
+
+        if game_state.timer.finished() {
+            game_state.game_running = false;
+
+            let scores = player_query
+                .iter()
+                .map(|(entity, player)| (entity, player.score));
+            let standings = rank_standings(scores, &[]);
+            match_end_events.send(MatchEndedEvent {
+                awards: stats.awards(&standings, game_state.timer.elapsed_secs()),
+                standings,
+            });
+
+            // Here you would display the winner
+            println!("Game over! Winner determined.");
+
+            for (entity, player) in player_query.iter() {
+                let Some(regions) = analysis.get(entity) else {
+                    continue;
+                };
+                let largest = regions.largest().map_or(0, |region| region.size);
+                println!(
+                    "Score {}: {} tiles in {} regions (largest {})",
+                    player.score,
+                    layers.tile_count(entity),
+                    regions.region_count(),
+                    largest
+                );
+            }
+        }
+    }
+}
+
+// Ranks everyone by score, ties share a placement. Players in `winners`
+// met the win condition and place ahead of everyone else.
+pub(crate) fn rank_standings(
+    scores: impl IntoIterator<Item = (Entity, u32)>,
+    winners: &[Entity],
+) -> Vec<Standing> {
+    let mut ranked: Vec<(Entity, (bool, u32))> = scores
+        .into_iter()
+        .map(|(entity, score)| (entity, (winners.contains(&entity), score)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    ranked
+        .iter()
+        .map(|&(player, rank)| Standing {
+            player,
+            score: rank.1,
+            placement: ranked.iter().filter(|other| other.1 > rank).count(),
+        })
+        .collect()
+}
+
+// Ends the match early as soon as anyone meets the rules' win condition
+fn win_condition_system(
+    rules: Res<GameRules>,
+    mut game_state: ResMut<GameState>,
+    layers: Res<OwnershipLayers>,
+    stats: Res<MatchStats>,
+    player_query: Query<(Entity, &Player)>,
+    mut match_end_events: EventWriter<MatchEndedEvent>,
+) {
+    let Some(condition) = &rules.win_condition else {
+        return;
+    };
+    if !game_state.game_running {
+        return;
+    }
+
+    // An empty map is nobody's, rather than NaN
+    let map_value = layers.total_value() as f32;
+    let share = |value: u32| {
+        if map_value > 0.0 {
+            value as f32 / map_value
+        } else {
+            0.0
+        }
+    };
+    let winners: Vec<Entity> = player_query
+        .iter()
+        .filter(|&(entity, player)| {
+            condition.met(&WinVariables {
+                territory: layers.tile_count(entity) as f32,
+                territory_pct: share(layers.territory_value(entity)),
+                score: player.score as f32,
+                kills: stats.kills(entity) as f32,
+                deaths: stats.deaths(entity) as f32,
+                elapsed: game_state.timer.elapsed_secs(),
+                time_left: game_state.timer.remaining_secs(),
+            })
+        })
+        .map(|(entity, _)| entity)
+        .collect();
+    if winners.is_empty() {
+        return;
+    }
+
+    game_state.game_running = false;
+    let scores = player_query
+        .iter()
+        .map(|(entity, player)| (entity, player.score));
+    let standings = rank_standings(scores, &winners);
+    match_end_events.send(MatchEndedEvent {
+        awards: stats.awards(&standings, game_state.timer.elapsed_secs()),
+        standings,
+    });
+    println!("Game over! Win condition met: {}", condition.source());
+}
+
+// Gives newly spawned players their starting territory
+fn init_player_territory(
+    mut player_query: Query<(Entity, &mut Player), Added<Player>>,
+    mut tiles: ResMut<Tiles>,
+) {
+    // Claim starting territory around each player's spawn tile
+    let territory_radius = 2; // Claim a 5x5 area
+
+    for (player_entity, mut player) in player_query.iter_mut() {
+        let (spawn_x, spawn_y) = player.spawn_tile;
+        let mut territory_value = 0;
+
+        for (tile, tile_color) in tiles.iter_mut() {
+            let dx = (tile.x - spawn_x).abs();
+            let dy = (tile.y - spawn_y).abs();
+
+            if dx <= territory_radius && dy <= territory_radius && !tile.void {
+                // Mark as player territory
+                tile.owner = Some(player_entity);
+                tile_color.0 = player.color.with_alpha(0.5);
+                territory_value += tile.value;
+            }
+        }
+
+        // Give player initial score based on territory
+        let territory_size = (territory_radius * 2 + 1).pow(2);
+        player.score = territory_value;
+
+        println!("Player starting with {} territory tiles", territory_size);
+    }
+}
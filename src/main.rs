@@ -1,205 +1,351 @@
 use bevy::prelude::*;
-mod components;
-mod events;
-mod resources;
-mod systems;
-
-use components::*;
-use events::PlayerDeathEvent;
-use resources::*;
-use systems::collision::*;
-use systems::input::*;
-use systems::movement::*;
-use systems::player::handle_player_death;
-use systems::trails::*;
+use landio::balance::simulate;
+use landio::config::GameConfig;
+use landio::events::{GameError, GameErrorKind};
+use landio::levels::Campaign;
+use landio::net::client::{NetClient, NetClientPlugin};
+use landio::net::invite::Invite;
+#[cfg(target_arch = "wasm32")]
+use landio::net::web::{WebSocketClient, WebTransportClient};
+use landio::net::NetTransport;
+use landio::paths::Paths;
+use landio::profiles::ProfileStore;
+use landio::resources::ActivePreset;
+use landio::states::AppState;
+use landio::stats::StatsStore;
+use landio::systems::errors::StartupErrors;
+use landio::{ClientPlugin, GamePlugin};
+use std::env;
+use std::fs;
+use std::io;
+// Browsers can't host, and only reach servers through the page
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    bevy::app::ScheduleRunnerPlugin,
+    bevy::state::app::StatesPlugin,
+    landio::net::discovery::{LanBeacon, MasterRegistration},
+    landio::net::invite::generate_code,
+    landio::net::multi::MultiTransport,
+    landio::net::protocol::DISCOVERY_PORT,
+    landio::net::server::{NetServer, NetServerPlugin},
+    landio::net::udp::UdpTransport,
+    landio::net::websocket::WebSocketTransport,
+    landio::net::webtransport::WebTransportTransport,
+    std::time::Duration,
+};
 
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Land.io Clone".into(),
-                resolution: (800., 600.).into(),
-                ..default()
-            }),
-            ..default()
-        }))
-        .add_event::<PlayerDeathEvent>()
-        .insert_resource(GameState::default())
-        .add_systems(Startup, setup_game)
-        .add_systems(
-            Update,
-            (
-                player_input_system,
-                start_trail_system,
-                player_movement_system,
-                update_trail_system,
-                render_trail_system,
-                collision_detection_system,
-                handle_player_death,
-                claim_territory_system,
-                game_timer_system,
-                init_player_territory.run_if(run_once()),
-            ),
-        )
-        .run();
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|position| args.get(position + 1))
 }
 
-fn setup_game(mut commands: Commands) {
-    // Spawn camera
-    commands.spawn(Camera2d::default());
-
-    // Add grid settings resource
-    let grid_settings = GridSettings::default();
-    commands.insert_resource(grid_settings.clone());
-
-    // Create grid of tiles
-    let tile_size = grid_settings.tile_size;
-    let half_width = (grid_settings.grid_width as f32 * tile_size) / 2.0;
-    let half_height = (grid_settings.grid_height as f32 * tile_size) / 2.0;
-
-    for y in 0..grid_settings.grid_height {
-        for x in 0..grid_settings.grid_width {
-            // Calculate position (centered in window)
-            let pos_x = (x as f32 * tile_size) - half_width + (tile_size / 2.0);
-            let pos_y = (y as f32 * tile_size) - half_height + (tile_size / 2.0);
-
-            // Checkerboard pattern for visibility
-            let is_dark = (x + y) % 2 == 0;
-            let tile_color = if is_dark {
-                Color::srgb(0.8, 0.8, 0.8) // Light gray
-            } else {
-                Color::srgb(0.9, 0.9, 0.9) // Lighter gray
-            };
-
-            commands.spawn((
-                Sprite {
-                    color: tile_color,
-                    custom_size: Some(Vec2::new(tile_size, tile_size)),
-                    ..default()
-                },
-                Transform::from_translation(Vec3::new(pos_x, pos_y, -0.1)),
-                GlobalTransform::default(),
-                Visibility::default(),
-                InheritedVisibility::default(),
-                ViewVisibility::default(),
-                Tile {
-                    x,
-                    y,
-                    owner: None,
-                    is_trail: false,
-                },
-            ));
+// `--simulate N [--json] [--report FILE]` plays N bot matches under the
+// configured rules and prints a balance report instead of opening the game
+fn run_simulation(config: &GameConfig, args: &[String]) -> bool {
+    let Some(position) = args.iter().position(|arg| arg == "--simulate") else {
+        return false;
+    };
+    let matches = args
+        .get(position + 1)
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(10);
+
+    let report = simulate(&config.game_rules(), matches);
+    let output = if args.iter().any(|arg| arg == "--json") {
+        report.to_json()
+    } else {
+        report.to_csv()
+    };
+
+    match arg_value(args, "--report") {
+        Some(path) => {
+            if let Err(err) = fs::write(path, output) {
+                println!("Failed to write {}: {}", path, err);
+            }
         }
+        None => println!("{}", output),
     }
+    true
+}
 
-    // Spawn player centered on a tile
-    let player_color = Color::srgb(0.2, 0.7, 0.9);
+// `--host ADDR [--websocket ADDR] [--webtransport ADDR] [--name NAME]
+// [--master URL] [--code CODE] [--private] [--bots N]` runs a dedicated
+// server without a window, with bots filling in until N players are on.
+// Desktop players join over UDP on ADDR, browsers on the WebSocket and
+// WebTransport addresses, all in the same match.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_server(config: &GameConfig, paths: &Paths, args: &[String]) -> bool {
+    let Some(addr) = arg_value(args, "--host") else {
+        return false;
+    };
+    let mut transport = match UdpTransport::bind(addr.as_str()) {
+        Ok(udp) => MultiTransport::default().with(udp),
+        Err(err) => {
+            println!("Failed to listen on {}: {}", addr, err);
+            return true;
+        }
+    };
+    if let Some(websocket) = arg_value(args, "--websocket") {
+        match WebSocketTransport::bind(websocket.as_str()) {
+            Ok(listener) => {
+                println!("Browsers can join over WebSocket on {}", websocket);
+                transport = transport.with(listener);
+            }
+            Err(err) => println!("Failed to listen on {}: {}", websocket, err),
+        }
+    }
+    if let Some(webtransport) = arg_value(args, "--webtransport") {
+        match WebTransportTransport::bind(webtransport.as_str()) {
+            Ok(listener) => {
+                let hash: String = listener
+                    .certificate_hash()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                println!(
+                    "Browsers can join over WebTransport on {} with certificate hash {}",
+                    webtransport, hash
+                );
+                transport = transport.with(listener);
+            }
+            Err(err) => println!("Failed to listen on {}: {}", webtransport, err),
+        }
+    }
+    let transport = NetTransport::new(transport);
+    println!("Hosting on {}", addr);
 
-    // Calculate center tile coordinates (this ensures we're on an actual tile)
-    let center_tile_x = grid_settings.grid_width / 2;
-    let center_tile_y = grid_settings.grid_height / 2;
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / 60.0,
+        ))),
+        StatesPlugin,
+    ))
+    .insert_resource(config.game_rules())
+    .insert_resource(ActivePreset(config.preset))
+    .insert_resource(config.playlist.clone())
+    .insert_resource(paths.clone())
+    .insert_resource(transport)
+    .init_resource::<ButtonInput<KeyCode>>();
+    let mut server = NetServer::default();
+    if let Some(name) = arg_value(args, "--name") {
+        server.name = name.clone();
+    }
+    if let Some(fill_to) = arg_value(args, "--bots").and_then(|count| count.parse().ok()) {
+        server.fill_to = fill_to;
+    }
+    // Private lobbies always get a code and stay out of server lists
+    let private = args.iter().any(|arg| arg == "--private");
+    server.code = arg_value(args, "--code")
+        .cloned()
+        .or_else(|| private.then(generate_code));
+    if server.code.is_some() {
+        let invite = Invite {
+            address: addr.clone(),
+            code: server.code.clone(),
+        };
+        println!("Invite: {}", invite);
+    }
+    app.insert_resource(server);
+    // Advertised under the UDP address, which the server browser joins on
+    if !private {
+        let port = addr
+            .rsplit(':')
+            .next()
+            .and_then(|port| port.parse().ok())
+            .unwrap_or_default();
+        match LanBeacon::bind(DISCOVERY_PORT, port) {
+            Ok(beacon) => {
+                app.insert_resource(beacon);
+            }
+            Err(err) => println!("LAN discovery is off: {}", err),
+        }
+        if let Some(url) = arg_value(args, "--master").or(config.browser.master_url.as_ref()) {
+            app.insert_resource(MasterRegistration::new(url.clone(), addr.clone()));
+        }
+    }
+    // The first match is played on the top of the playlist
+    if let Some(entry) = config.playlist.entries.first() {
+        app.insert_resource(entry.rules())
+            .insert_resource(entry.grid_settings())
+            .insert_resource(entry.game_state());
+    }
+    app.add_plugins((GamePlugin, NetServerPlugin))
+        .insert_state(AppState::Playing)
+        .run();
+    true
+}
 
-    // Calculate the exact pixel position of the center tile
-    let player_start_x = (center_tile_x as f32 * tile_size) - half_width + (tile_size / 2.0);
-    let player_start_y = (center_tile_y as f32 * tile_size) - half_height + (tile_size / 2.0);
+// Certificate hashes are given as 64 hex digits
+fn parse_hash(text: &str) -> Option<[u8; 32]> {
+    let mut hash = [0; 32];
+    if text.len() != 64 || !text.is_ascii() {
+        return None;
+    }
+    for (byte, digits) in hash.iter_mut().zip(text.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
 
-    // Spawn the player entity
-    commands.spawn((
-        Sprite {
-            color: player_color,
-            custom_size: Some(Vec2::new(tile_size * 0.8, tile_size * 0.8)), // Slightly smaller than tile
-            ..default()
-        },
-        Transform::from_translation(Vec3::new(player_start_x, player_start_y, 0.0)),
-        GlobalTransform::default(),
-        Visibility::default(),
-        InheritedVisibility::default(),
-        ViewVisibility::default(),
-        Player {
-            speed: 5.0, // Speed in tiles per second
-            direction: Vec2::ZERO,
-            buffered_direction: None,
-            score: 0,
-            color: player_color,
-            is_drawing_trail: false,
-            last_tile_pos: (center_tile_x, center_tile_y), // Set to the exact tile position
-            is_moving_to_next_tile: false,
-        },
-    ));
+// Which transport `--connect` joins with
+#[derive(Clone, Copy)]
+enum Link {
+    Udp,
+    WebSocket,
+    WebTransport([u8; 32]),
 }
 
-fn game_timer_system(
-    time: Res<Time>,
-    mut game_state: ResMut<GameState>,
-    player_query: Query<(Entity, &Player)>,
-) {
-    if game_state.game_running {
-        game_state.timer.tick(time.delta());
-
-        if game_state.timer.finished() {
-            game_state.game_running = false;
-
-            // Determine winner
-            let mut highest_score = 0;
-            let mut _winner = None;
-
-            for (entity, player) in player_query.iter() {
-                if player.score > highest_score {
-                    highest_score = player.score;
-                    _winner = Some(entity);
-                }
-            }
+#[cfg(not(target_arch = "wasm32"))]
+fn open_link(addr: &str, link: Link) -> io::Result<NetTransport> {
+    match link {
+        Link::Udp => UdpTransport::connect(addr).map(NetTransport::new),
+        Link::WebSocket => WebSocketTransport::connect(addr, addr).map(NetTransport::new),
+        Link::WebTransport(hash) => {
+            WebTransportTransport::connect(addr, hash).map(NetTransport::new)
+        }
+    }
+}
 
-            // Here you would display the winner
-            println!("Game over! Winner determined.");
+// Pages can't open UDP sockets, so browsers need one of the web transports
+#[cfg(target_arch = "wasm32")]
+fn open_link(addr: &str, link: Link) -> io::Result<NetTransport> {
+    let url = |scheme: &str| {
+        if addr.contains("://") {
+            addr.to_string()
+        } else {
+            format!("{}://{}", scheme, addr)
+        }
+    };
+    match link {
+        Link::Udp => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "browsers join with websocket or webtransport",
+        )),
+        Link::WebSocket => WebSocketClient::connect(&url("ws")).map(NetTransport::new),
+        Link::WebTransport(hash) => {
+            WebTransportClient::connect(&url("https"), hash).map(NetTransport::new)
         }
     }
 }
 
-fn init_player_territory(
-    grid_settings: Res<GridSettings>,
-    mut player_query: Query<(Entity, &mut Player)>,
-    mut tile_query: Query<(&mut Tile, &mut Sprite)>,
-) {
-    // Get the player entity
-    if let Ok((player_entity, player)) = player_query.get_single() {
-        // Calculate center tile coordinates
-        let center_tile_x = grid_settings.grid_width / 2;
-        let center_tile_y = grid_settings.grid_height / 2;
-
-        // Claim starting territory for the player
-        let territory_radius = 2; // Claim a 5x5 area
-
-        for (mut tile, mut sprite) in tile_query.iter_mut() {
-            let dx = (tile.x - center_tile_x).abs();
-            let dy = (tile.y - center_tile_y).abs();
-
-            if dx <= territory_radius && dy <= territory_radius {
-                // Mark as player territory
-                tile.owner = Some(player_entity);
-                sprite.color = player.color.with_alpha(0.5);
+// `--connect ADDR[/CODE] [--name NAME] [--websocket | --webtransport HASH]
+// [--observe]` joins a server instead of playing locally, taking an invite
+// as well as a bare address. WebTransport servers print the hash of their
+// certificate when they start. Observers watch the match without playing
+// in it.
+fn connect(args: &[String]) -> Option<io::Result<(NetTransport, NetClient)>> {
+    let invite = Invite::parse(arg_value(args, "--connect")?);
+    let addr = invite.address.clone();
+    let link = if let Some(hash) = arg_value(args, "--webtransport") {
+        match parse_hash(hash) {
+            Some(hash) => Link::WebTransport(hash),
+            None => {
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "certificate hash should be 64 hex digits",
+                )))
             }
         }
+    } else if args.iter().any(|arg| arg == "--websocket") {
+        Link::WebSocket
+    } else {
+        Link::Udp
+    };
+    let open = move || open_link(&addr, link);
+    let name = arg_value(args, "--name").map_or("Player", |name| name.as_str());
+    let observe = args.iter().any(|arg| arg == "--observe");
+    Some(open().map(|transport| {
+        let client = NetClient::new(name)
+            .with_reconnect(Box::new(open))
+            .with_invite(invite);
+        (
+            transport,
+            if observe {
+                client.with_observer()
+            } else {
+                client
+            },
+        )
+    }))
+}
 
-        // Give player initial score based on territory
-        let territory_size = (territory_radius * 2 + 1).pow(2);
-        if let Ok((_, mut player)) = player_query.get_single_mut() {
-            player.score = territory_size as u32;
-        }
+// `--portable` keeps settings, saves and captures next to the binary rather
+// than in the platform's data directory
+fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    let args: Vec<String> = env::args().collect();
+    #[cfg(target_arch = "wasm32")]
+    let args = landio::net::web::page_args();
+    let paths = Paths::from_args(&args);
+    println!("Keeping settings and saves in {}", paths.root.display());
+    let config = GameConfig::load(&paths);
 
-        println!("Player starting with {} territory tiles", territory_size);
+    if run_simulation(&config, &args) {
+        return;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if run_server(&config, &paths, &args) {
+        return;
     }
-}
 
-// Add this helper for running a system only once
-fn run_once() -> impl FnMut() -> bool {
-    let mut has_run = false;
-    move || {
-        if !has_run {
-            has_run = true;
-            true
-        } else {
-            false
+    // Reported once there's a window to show them in
+    let mut startup_errors = Vec::new();
+    let profiles = ProfileStore::try_load(&paths).unwrap_or_else(|err| {
+        startup_errors.push(GameError::new(GameErrorKind::SaveCorrupt, err));
+        ProfileStore::default()
+    });
+    let campaign = Campaign::load();
+    startup_errors.extend(campaign.errors());
+
+    let (width, height) = config.display.resolution;
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            title: "Land.io Clone".into(),
+            resolution: (width as f32, height as f32).into(),
+            mode: config.display.window_mode.window_mode(),
+            ..default()
+        }),
+        ..default()
+    }))
+    // Saved settings go in before the plugins so they aren't replaced by defaults
+    .insert_resource(config.audio.clone())
+    .insert_resource(config.game_rules())
+    .insert_resource(ActivePreset(config.preset))
+    .insert_resource(config.telemetry.clone())
+    .insert_resource(config.browser.clone())
+    .insert_resource(config.camera)
+    .insert_resource(config.display)
+    .insert_resource(config.controls)
+    .insert_resource(config.accessibility)
+    .insert_resource(config.sandbox.clone())
+    .insert_resource(config)
+    .insert_resource(profiles)
+    .insert_resource(campaign)
+    .insert_resource(StatsStore::load(&paths))
+    .insert_resource(paths)
+    .add_plugins((GamePlugin, ClientPlugin, NetClientPlugin));
+
+    match connect(&args) {
+        Some(Ok((transport, client))) => {
+            app.insert_resource(transport)
+                .insert_resource(client)
+                .insert_state(AppState::Online);
+        }
+        Some(Err(err)) => {
+            println!("Failed to connect: {}", err);
+            startup_errors.push(GameError::new(
+                GameErrorKind::ConnectFailed,
+                err.to_string(),
+            ));
         }
+        None => {}
     }
+    app.insert_resource(StartupErrors(startup_errors));
+    #[cfg(feature = "dev")]
+    app.add_plugins(landio::dev::DevPlugin);
+    app.run();
 }
@@ -0,0 +1,66 @@
+// Bots standing in for missing players on a server that isn't full. A human
+// joining mid-match takes one over, land, score and all, so nobody else sees
+// a player vanish.
+use crate::components::{GridSettings, Player, Tiles};
+use crate::net::server::{release_player, NetServer, RemotePlayer};
+use crate::net::transport::ConnectionId;
+use crate::player_bundle;
+use crate::systems::bots::Bot;
+use crate::systems::collision::LagCompensation;
+use crate::systems::input::{DirectionIntent, InputSource};
+use bevy::prelude::*;
+
+// A bot the server spawned to fill a slot, as opposed to one from elsewhere
+#[derive(Component)]
+pub struct BackfillBot;
+
+// Bots the match needs on top of its humans
+pub fn bots_wanted(server: &NetServer) -> usize {
+    server
+        .fill_to
+        .min(server.max_players)
+        .saturating_sub(server.player_count())
+}
+
+// Spawns or removes bots until the match has as many players as it should
+pub fn backfill_bots_system(
+    mut commands: Commands,
+    server: Res<NetServer>,
+    grid_settings: Res<GridSettings>,
+    bot_query: Query<Entity, With<BackfillBot>>,
+    player_query: Query<&Player>,
+    mut tiles: ResMut<Tiles>,
+) {
+    let bots: Vec<Entity> = bot_query.iter().collect();
+    let wanted = bots_wanted(&server);
+
+    for slot in bots.len()..wanted {
+        commands.spawn((
+            player_bundle(&grid_settings, server.player_count() + slot),
+            InputSource::External,
+            DirectionIntent::default(),
+            Bot::default(),
+            BackfillBot,
+        ));
+    }
+
+    for &bot in bots.iter().skip(wanted) {
+        release_player(&mut commands, bot, &player_query, &mut tiles);
+    }
+}
+
+// Hands a backfill bot's player to a client that just joined
+pub fn take_over_bot(
+    commands: &mut Commands,
+    bot: Entity,
+    scale: f32,
+    player: &mut Player,
+    connection: ConnectionId,
+) {
+    // Undo the bot's speed tuning, humans play at the normal speed
+    player.speed /= scale;
+    commands
+        .entity(bot)
+        .remove::<(Bot, BackfillBot)>()
+        .insert((RemotePlayer { connection }, LagCompensation::default()));
+}
@@ -0,0 +1,368 @@
+// Server list screen: LAN servers found by broadcast and public ones from
+// the master server, joined with a click or Enter.
+use crate::net::client::NetClient;
+use crate::net::discovery::{fetch_master_list, LanProbe, MasterListing};
+use crate::net::invite::Invite;
+use crate::net::protocol::{ServerInfo, DISCOVERY_PORT, PROTOCOL_VERSION};
+use crate::net::transport::NetTransport;
+use crate::net::udp::UdpTransport;
+use crate::states::AppState;
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, IoTaskPool, Task};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrowserSettings {
+    // Lists public servers, e.g. "http://master.example.com/servers"
+    pub master_url: Option<String>,
+    // Name we join servers under
+    pub player_name: String,
+}
+
+impl Default for BrowserSettings {
+    fn default() -> Self {
+        Self {
+            master_url: None,
+            player_name: "Player".into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrowserEntry {
+    pub address: SocketAddr,
+    pub name: String,
+    pub map: String,
+    pub players: u32,
+    pub max_players: u32,
+    // None until the server answers a probe
+    pub ping: Option<Duration>,
+    pub compatible: bool,
+    // Needs an invite code, which the list doesn't have
+    pub locked: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct ServerBrowser {
+    pub entries: Vec<BrowserEntry>,
+    pub selected: usize,
+    probe: Option<LanProbe>,
+    master: Option<Task<io::Result<Vec<MasterListing>>>>,
+}
+
+impl ServerBrowser {
+    // Starts looking for servers from scratch
+    pub fn refresh(settings: &BrowserSettings) -> Self {
+        let probe = LanProbe::bind(DISCOVERY_PORT)
+            .and_then(|mut probe| probe.broadcast().map(|_| probe))
+            .inspect_err(|err| println!("Failed to search the LAN: {}", err))
+            .ok();
+        let master = settings
+            .master_url
+            .clone()
+            .map(|url| IoTaskPool::get().spawn(async move { fetch_master_list(&url) }));
+        Self {
+            probe,
+            master,
+            ..default()
+        }
+    }
+
+    // Adds a server, or updates it if it's listed already
+    pub fn upsert(&mut self, entry: BrowserEntry) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|known| known.address == entry.address)
+        {
+            Some(known) => {
+                // A master listing must not wipe a ping we measured
+                let ping = entry.ping.or(known.ping);
+                *known = BrowserEntry { ping, ..entry };
+            }
+            None => self.entries.push(entry),
+        }
+    }
+
+    // Whether anything new came in
+    fn poll(&mut self) -> bool {
+        let mut found = false;
+        if let Some(task) = self.master.as_mut() {
+            if let Some(result) = block_on(future::poll_once(task)) {
+                self.master = None;
+                match result {
+                    Ok(listings) => {
+                        for listing in listings {
+                            let Some(address) = listing
+                                .address
+                                .to_socket_addrs()
+                                .ok()
+                                .and_then(|mut addrs| addrs.next())
+                            else {
+                                continue;
+                            };
+                            // Asked directly for fresh info and a ping
+                            if let Some(probe) = self.probe.as_mut() {
+                                let _ = probe.probe(address.ip());
+                            }
+                            self.upsert(BrowserEntry {
+                                address,
+                                name: listing.name,
+                                map: listing.map,
+                                players: listing.players,
+                                max_players: listing.max_players,
+                                ping: None,
+                                compatible: true,
+                                locked: listing.locked,
+                            });
+                            found = true;
+                        }
+                    }
+                    Err(err) => println!("Failed to fetch the server list: {}", err),
+                }
+            }
+        }
+
+        let replies = self
+            .probe
+            .as_mut()
+            .map(|probe| probe.replies())
+            .unwrap_or_default();
+        for (address, info, ping) in replies {
+            self.upsert(entry_from_info(address, info, ping));
+            found = true;
+        }
+        found
+    }
+}
+
+fn entry_from_info(address: SocketAddr, info: ServerInfo, ping: Duration) -> BrowserEntry {
+    BrowserEntry {
+        address,
+        name: info.name,
+        map: info.map,
+        players: info.players,
+        max_players: info.max_players,
+        ping: Some(ping),
+        compatible: info.protocol_version == PROTOCOL_VERSION,
+        locked: info.locked,
+    }
+}
+
+#[derive(Component)]
+pub struct ServerBrowserScreen;
+
+#[derive(Component)]
+pub struct ServerList;
+
+#[derive(Component)]
+pub struct ServerRow {
+    pub index: usize,
+}
+
+pub fn setup_server_browser(mut commands: Commands, settings: Res<BrowserSettings>) {
+    commands.insert_resource(ServerBrowser::refresh(&settings));
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+            ServerBrowserScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((Text::new("Servers"), TextFont::from_font_size(32.0)));
+            screen.spawn((
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ServerList,
+            ));
+            screen.spawn((
+                Text::new("Click a server or Enter to join, R to refresh, Esc to go back"),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+        });
+}
+
+pub fn cleanup_server_browser(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<ServerBrowserScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<ServerBrowser>();
+}
+
+// Collects answers to our probes and the master server's list. Polled
+// every frame, but only counts as a change when a server turned up.
+pub fn poll_server_browser_system(mut browser: ResMut<ServerBrowser>) {
+    if browser.bypass_change_detection().poll() {
+        browser.set_changed();
+    }
+}
+
+// Opens a connection to the server and goes online
+fn join_server(
+    commands: &mut Commands,
+    next_state: &mut NextState<AppState>,
+    address: SocketAddr,
+    name: &str,
+) {
+    let open = move || UdpTransport::connect(address).map(NetTransport::new);
+    match open() {
+        Ok(transport) => {
+            let invite = Invite {
+                address: address.to_string(),
+                code: None,
+            };
+            commands.insert_resource(transport);
+            commands.insert_resource(
+                NetClient::new(name)
+                    .with_reconnect(Box::new(open))
+                    .with_invite(invite),
+            );
+            next_state.set(AppState::Online);
+        }
+        Err(err) => println!("Failed to connect to {}: {}", address, err),
+    }
+}
+
+pub fn server_browser_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<BrowserSettings>,
+    mut browser: ResMut<ServerBrowser>,
+    mut next_state: ResMut<NextState<AppState>>,
+    row_query: Query<(&Interaction, &ServerRow), Changed<Interaction>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(AppState::Join);
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        *browser = ServerBrowser::refresh(&settings);
+        return;
+    }
+
+    let count = browser.entries.len();
+    if count == 0 {
+        return;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::ArrowUp, KeyCode::KeyW]) {
+        browser.selected = (browser.selected + count - 1) % count;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::ArrowDown, KeyCode::KeyS]) {
+        browser.selected = (browser.selected + 1) % count;
+    }
+
+    let clicked = row_query
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, row)| row.index);
+    let chosen = clicked.or_else(|| {
+        keyboard_input
+            .just_pressed(KeyCode::Enter)
+            .then_some(browser.selected)
+    });
+    let Some(entry) = chosen.and_then(|index| browser.entries.get(index)) else {
+        return;
+    };
+    if entry.locked {
+        println!(
+            "{} needs an invite code, join with --connect {}/CODE",
+            entry.name, entry.address
+        );
+    } else {
+        join_server(
+            &mut commands,
+            &mut next_state,
+            entry.address,
+            &settings.player_name,
+        );
+    }
+}
+
+fn row_text(entry: &BrowserEntry) -> String {
+    let ping = entry
+        .ping
+        .map_or("?".to_string(), |ping| ping.as_millis().to_string());
+    let note = if !entry.compatible {
+        "  (other version)"
+    } else if entry.locked {
+        "  (invite only)"
+    } else {
+        ""
+    };
+    format!(
+        "{}  -  {}  -  {}/{} players  -  {} ms{}",
+        entry.name, entry.map, entry.players, entry.max_players, ping, note
+    )
+}
+
+// Redraws the list when servers come and go
+pub fn update_server_list_system(
+    mut commands: Commands,
+    browser: Res<ServerBrowser>,
+    list_query: Query<Entity, With<ServerList>>,
+) {
+    if !browser.is_changed() {
+        return;
+    }
+    let Ok(list) = list_query.get_single() else {
+        return;
+    };
+
+    commands
+        .entity(list)
+        .despawn_descendants()
+        .with_children(|list| {
+            if browser.entries.is_empty() {
+                list.spawn((
+                    Text::new("Looking for servers..."),
+                    TextFont::from_font_size(18.0),
+                ));
+            }
+
+            for (index, entry) in browser.entries.iter().enumerate() {
+                let color = if index == browser.selected {
+                    Color::srgb(1.0, 0.85, 0.3)
+                } else if entry.compatible && !entry.locked {
+                    Color::WHITE
+                } else {
+                    Color::srgb(0.5, 0.5, 0.5)
+                };
+                list.spawn((
+                    Button,
+                    Node {
+                        padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                        ..default()
+                    },
+                    ServerRow { index },
+                ))
+                .with_children(|row| {
+                    row.spawn((
+                        Text::new(row_text(entry)),
+                        TextFont::from_font_size(18.0),
+                        TextColor(color),
+                    ));
+                });
+            }
+        });
+}
@@ -0,0 +1,666 @@
+// Client side of a networked match. The simulation runs on the server, so
+// this sends the local player's steering and mirrors what comes back, with
+// only our own movement predicted ahead of it.
+use crate::components::{GridSettings, LocalPlayer, Player, Tiles};
+use crate::events::{
+    EmoteEvent, GameError, GameErrorKind, PlaySoundEvent, RetryAction, RetryEvent, SoundEffect,
+    ToastEvent, ToastIcon, ToastPriority,
+};
+use crate::grid::GridMath;
+use crate::net::browser::{
+    cleanup_server_browser, poll_server_browser_system, server_browser_input_system,
+    setup_server_browser, update_server_list_system, BrowserSettings,
+};
+use crate::net::diagnostics::{
+    cleanup_net_stats_overlay, toggle_net_stats_system, update_net_stats_overlay_system, NetStats,
+    NetStatsOverlay,
+};
+use crate::net::interpolation::{
+    interpolate_remote_players_system, server_time, InterpolationClock, PositionSample,
+    SnapshotBuffer,
+};
+use crate::net::invite::{cleanup_invite_button, copy_invite_system, setup_invite_button, Invite};
+use crate::net::observer::{
+    cleanup_observer_hud, is_observer, observer_camera_system, observer_input_system,
+    setup_observer_hud, update_observer_hud_system, ObserverCamera,
+};
+use crate::net::prediction::{predict_local_player_system, reconcile, Prediction};
+use crate::net::protocol::{
+    ClientMessage, NetId, PlayerState, RejectReason, ServerMessage, TileUpdate, PROTOCOL_VERSION,
+};
+use crate::net::server::RECONNECT_GRACE_SECONDS;
+use crate::net::transport::{Channel, NetTransport, TransportEvent, SERVER_CONNECTION};
+use crate::net::vote::{cast_vote_system, vote_screen_system};
+use crate::player_bundle_at;
+use crate::resources::{GameRules, GameSpeed};
+use crate::states::{AppState, GameSet};
+use crate::systems::countdown::{cleanup_countdown, mirror_countdown_system, MatchCountdown};
+use crate::systems::input::{device_input_system, DirectionIntent, InputDevice, InputSource};
+use crate::systems::movement::player_position;
+use crate::territory::land_color;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Connecting,
+    Joined,
+    // Lost the server, trying to get back to our player
+    Reconnecting,
+    Rejected(RejectReason),
+    Disconnected,
+}
+
+// Seconds between attempts to reach the server again
+const RECONNECT_INTERVAL: f32 = 2.0;
+
+// Opens a fresh transport to the same server
+pub type Reconnect = Box<dyn FnMut() -> std::io::Result<NetTransport> + Send + Sync>;
+
+#[derive(Resource)]
+pub struct NetClient {
+    pub name: String,
+    pub status: ConnectionStatus,
+    // Our own player, once the server has let us in
+    pub player: Option<NetId>,
+    // Server ids of everyone we've heard of, mapped to our own entities
+    pub players: HashMap<NetId, Entity>,
+    pub colors: HashMap<NetId, Color>,
+    pub input_tick: u32,
+    // Newest snapshot applied, older ones are dropped
+    pub server_tick: u32,
+    // Tile updates for owners we haven't had a snapshot of yet
+    pending_tiles: Vec<TileUpdate>,
+    // Handed out by the server, quoted to resume our player after a drop
+    pub session: Option<u64>,
+    // How to reach the server again, no reconnecting without it
+    pub reconnect: Option<Reconnect>,
+    // Time spent reconnecting, and since the last attempt
+    reconnecting_for: f32,
+    since_attempt: f32,
+    // Open vote on the next map, between matches
+    pub ballot: Option<Ballot>,
+    // Where we joined, with the lobby's code, to pass on to friends
+    pub invite: Option<Invite>,
+    // Watching the match rather than playing in it
+    pub observer: bool,
+}
+
+// What the server offered for the next map, and how it's going
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ballot {
+    pub options: Vec<String>,
+    pub votes: Vec<u32>,
+    pub seconds_left: f32,
+    // Our own pick, if we made one
+    pub choice: Option<u32>,
+}
+
+impl NetClient {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: ConnectionStatus::Connecting,
+            player: None,
+            players: HashMap::new(),
+            colors: HashMap::new(),
+            input_tick: 0,
+            server_tick: 0,
+            pending_tiles: Vec::new(),
+            session: None,
+            reconnect: None,
+            reconnecting_for: 0.0,
+            since_attempt: 0.0,
+            ballot: None,
+            invite: None,
+            observer: false,
+        }
+    }
+
+    pub fn with_observer(mut self) -> Self {
+        self.observer = true;
+        self
+    }
+
+    pub fn with_invite(mut self, invite: Invite) -> Self {
+        self.invite = Some(invite);
+        self
+    }
+
+    pub fn with_reconnect(mut self, reconnect: Reconnect) -> Self {
+        self.reconnect = Some(reconnect);
+        self
+    }
+}
+
+// A player mirrored from the server
+#[derive(Component)]
+pub struct NetworkedPlayer {
+    pub id: NetId,
+}
+
+// Joins the server behind `NetTransport` and mirrors its match while in
+// `AppState::Online`
+pub struct NetClientPlugin;
+
+impl Plugin for NetClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Prediction>()
+            .init_resource::<InterpolationClock>()
+            .init_resource::<BrowserSettings>()
+            .init_resource::<NetStatsOverlay>()
+            .add_systems(
+                OnEnter(AppState::Online),
+                (setup_invite_button, setup_observer_hud.run_if(is_observer)),
+            )
+            .add_systems(
+                OnExit(AppState::Online),
+                (
+                    cleanup_invite_button,
+                    cleanup_net_stats_overlay,
+                    cleanup_observer_hud,
+                    cleanup_countdown,
+                ),
+            )
+            .add_systems(OnEnter(AppState::ServerBrowser), setup_server_browser)
+            .add_systems(OnExit(AppState::ServerBrowser), cleanup_server_browser)
+            .add_systems(
+                Update,
+                (
+                    poll_server_browser_system,
+                    server_browser_input_system,
+                    update_server_list_system,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::ServerBrowser)),
+            )
+            .add_systems(
+                Update,
+                (
+                    retry_reconnect_system,
+                    client_reconnect_system,
+                    client_receive_system,
+                    mirror_countdown_system,
+                    device_input_system,
+                    client_send_input_system,
+                    client_send_emotes_system,
+                    predict_local_player_system,
+                    interpolate_remote_players_system,
+                    cast_vote_system,
+                    vote_screen_system,
+                    copy_invite_system,
+                    toggle_net_stats_system,
+                    update_net_stats_overlay_system,
+                )
+                    .chain()
+                    .run_if(
+                        in_state(AppState::Online)
+                            .and(resource_exists::<NetTransport>)
+                            .and(resource_exists::<NetClient>),
+                    ),
+            )
+            .add_systems(
+                Update,
+                (
+                    observer_input_system,
+                    observer_camera_system,
+                    update_observer_hud_system,
+                )
+                    .chain()
+                    .after(GameSet::Render)
+                    .run_if(in_state(AppState::Online).and(resource_exists::<ObserverCamera>)),
+            );
+    }
+}
+
+pub(crate) fn send(transport: &mut NetTransport, channel: Channel, message: &ClientMessage) {
+    if let Err(err) = transport
+        .0
+        .send(SERVER_CONNECTION, channel, &message.encode())
+    {
+        println!("Failed to send to the server: {}", err);
+    }
+}
+
+fn color_from_bytes([red, green, blue]: [u8; 3]) -> Color {
+    Color::srgb_u8(red, green, blue)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn client_receive_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut transport: ResMut<NetTransport>,
+    mut client: ResMut<NetClient>,
+    mut grid_settings: ResMut<GridSettings>,
+    mut rules: ResMut<GameRules>,
+    mut player_query: MirroredPlayerQuery,
+    mut tiles: ResMut<Tiles>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+    mut toast_events: EventWriter<ToastEvent>,
+    mut error_events: EventWriter<GameError>,
+    mut emote_events: EventWriter<EmoteEvent>,
+    mut prediction: ResMut<Prediction>,
+) {
+    let mut tile_updates = std::mem::take(&mut client.pending_tiles);
+
+    for event in transport.0.poll() {
+        let payload = match event {
+            TransportEvent::Connected(_) => {
+                let name = client.name.clone();
+                let code = client
+                    .invite
+                    .as_ref()
+                    .and_then(|invite| invite.code.clone());
+                send(
+                    &mut transport,
+                    Channel::Reliable,
+                    &ClientMessage::JoinRequest {
+                        protocol_version: PROTOCOL_VERSION,
+                        name,
+                        resume: client.session,
+                        code,
+                        observer: client.observer,
+                    },
+                );
+                continue;
+            }
+            TransportEvent::Disconnected(_) => {
+                if client.status == ConnectionStatus::Joined {
+                    if client.reconnect.is_some() && client.session.is_some() {
+                        println!("Lost the connection to the server, reconnecting");
+                        client.status = ConnectionStatus::Reconnecting;
+                        client.reconnecting_for = 0.0;
+                        client.since_attempt = RECONNECT_INTERVAL;
+                        toast_events.send(
+                            ToastEvent::new(ToastIcon::Network, "Connection lost, reconnecting")
+                                .with_priority(ToastPriority::High),
+                        );
+                    } else {
+                        println!("Lost the connection to the server");
+                        client.status = ConnectionStatus::Disconnected;
+                        error_events.send(GameError::new(
+                            GameErrorKind::ConnectionLost,
+                            "the server went away",
+                        ));
+                    }
+                }
+                continue;
+            }
+            TransportEvent::Message { payload, .. } => payload,
+        };
+        let message = match ServerMessage::decode(&payload) {
+            Ok(message) => message,
+            Err(err) => {
+                println!("Bad message from the server: {}", err);
+                continue;
+            }
+        };
+
+        match message {
+            ServerMessage::JoinAccepted {
+                player,
+                session,
+                grid_width,
+                grid_height,
+                value_zones,
+                game_speed,
+                topology,
+                shape,
+                upper_floor,
+            } => {
+                if client.status == ConnectionStatus::Reconnecting {
+                    toast_events.send(ToastEvent::new(ToastIcon::Network, "Reconnected"));
+                }
+                client.status = ConnectionStatus::Joined;
+                client.player = player;
+                client.session = Some(session);
+                client.ballot = None;
+                // Played at the server's pace, or prediction runs ahead of it
+                rules.game_speed = game_speed;
+
+                // A new match may be on a map of another size or layout
+                if (grid_width, grid_height)
+                    != (grid_settings.grid_width, grid_settings.grid_height)
+                    || value_zones != grid_settings.value_zones
+                    || shape != grid_settings.shape
+                    || upper_floor != grid_settings.upper_floor
+                {
+                    grid_settings.grid_width = grid_width;
+                    grid_settings.grid_height = grid_height;
+                    grid_settings.value_zones = value_zones;
+                    grid_settings.shape = shape;
+                    grid_settings.upper_floor = upper_floor;
+                    *tiles = Tiles::new(&grid_settings);
+                }
+                grid_settings.topology = topology;
+            }
+            ServerMessage::JoinRejected { reason } => {
+                println!("Server refused to let us in: {}", reason);
+                error_events.send(GameError::new(
+                    GameErrorKind::JoinRejected,
+                    reason.to_string(),
+                ));
+                client.status = ConnectionStatus::Rejected(reason);
+                transport.0.disconnect(SERVER_CONNECTION);
+            }
+            ServerMessage::Snapshot {
+                tick,
+                last_input,
+                live,
+                players,
+            } => {
+                if tick <= client.server_tick {
+                    continue;
+                }
+                client.server_tick = tick;
+                prediction.active = live
+                    && players
+                        .iter()
+                        .any(|state| Some(state.player) == client.player && state.alive);
+                apply_snapshot(
+                    &mut commands,
+                    &mut client,
+                    &grid_settings,
+                    &players,
+                    &mut player_query,
+                    &mut prediction,
+                    last_input,
+                    rules.game_speed,
+                    time.elapsed_secs(),
+                );
+            }
+            ServerMessage::TileDelta { tiles, .. } => tile_updates.extend(tiles),
+            ServerMessage::ClaimResult { player, .. } => {
+                if client.player == Some(player) {
+                    sound_events.send(PlaySoundEvent {
+                        sound: SoundEffect::Claim,
+                    });
+                }
+            }
+            ServerMessage::DeathNotice { player, .. } => {
+                if client.player == Some(player) {
+                    sound_events.send(PlaySoundEvent {
+                        sound: SoundEffect::Death,
+                    });
+                }
+            }
+            // Our own emotes were shown as soon as we picked them
+            ServerMessage::Emote { player, emote } => {
+                if client.player == Some(player) {
+                    continue;
+                }
+                if let Some(&player) = client.players.get(&player) {
+                    emote_events.send(EmoteEvent { player, emote });
+                }
+            }
+            // Counted down from when it was sent, about half a round trip ago
+            ServerMessage::Countdown { seconds } => {
+                let travel = transport
+                    .0
+                    .stats(SERVER_CONNECTION)
+                    .and_then(|stats| stats.rtt)
+                    .map_or(0.0, |rtt| rtt.as_secs_f32() / 2.0);
+                commands.insert_resource(MatchCountdown {
+                    timer: Timer::from_seconds((seconds - travel).max(0.0), TimerMode::Once),
+                });
+            }
+            ServerMessage::VoteStarted { options, seconds } => {
+                client.ballot = Some(Ballot {
+                    votes: vec![0; options.len()],
+                    options,
+                    seconds_left: seconds,
+                    choice: None,
+                });
+            }
+            ServerMessage::VoteTally { votes } => {
+                if let Some(ballot) = client.ballot.as_mut() {
+                    ballot.votes = votes;
+                }
+            }
+            ServerMessage::VoteEnded { option } => {
+                if let Some(name) = client
+                    .ballot
+                    .take()
+                    .and_then(|ballot| ballot.options.get(option as usize).cloned())
+                {
+                    println!("Next map: {}", name);
+                }
+            }
+        }
+    }
+
+    // Tiles of a player we can't color yet wait for their first snapshot
+    let tile_index: HashMap<(i32, i32), TileUpdate> = tile_updates
+        .into_iter()
+        .filter_map(|update| {
+            let known = |owner: Option<NetId>| owner.is_none_or(|o| client.colors.contains_key(&o));
+            if known(update.owner) && known(update.trail_owner) {
+                Some(((update.x, update.y), update))
+            } else {
+                client.pending_tiles.push(update);
+                None
+            }
+        })
+        .collect();
+    if tile_index.is_empty() {
+        return;
+    }
+    for (tile, tile_color) in tiles.iter_mut() {
+        let Some(update) = tile_index.get(&(tile.x, tile.y)) else {
+            continue;
+        };
+        tile.owner = update
+            .owner
+            .and_then(|owner| client.players.get(&owner).copied());
+        tile.trail_owner = update
+            .trail_owner
+            .and_then(|owner| client.players.get(&owner).copied());
+        let color_of = |owner: Option<NetId>| owner.and_then(|owner| client.colors.get(&owner));
+        tile_color.0 = match (color_of(update.trail_owner), color_of(update.owner)) {
+            (Some(color), _) => color.with_alpha(0.8),
+            (None, Some(color)) => color.with_alpha(0.5),
+            (None, None) => land_color(tile, None),
+        };
+    }
+}
+
+type MirroredPlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Player,
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static mut Visibility,
+        Option<&'static mut SnapshotBuffer>,
+        Option<&'static mut NetStats>,
+    ),
+>;
+
+// Spawns, moves and removes mirrored players to match the server
+#[allow(clippy::too_many_arguments)]
+fn apply_snapshot(
+    commands: &mut Commands,
+    client: &mut NetClient,
+    grid_settings: &GridSettings,
+    players: &[PlayerState],
+    player_query: &mut MirroredPlayerQuery,
+    prediction: &mut Prediction,
+    last_input: u32,
+    game_speed: GameSpeed,
+    now: f32,
+) {
+    client.players.retain(|id, entity| {
+        let present = players.iter().any(|state| state.player == *id);
+        if !present {
+            commands.entity(*entity).despawn_recursive();
+        }
+        present
+    });
+
+    for state in players {
+        let color = color_from_bytes(state.color);
+        client.colors.insert(state.player, color);
+
+        let Some(&entity) = client.players.get(&state.player) else {
+            let mut entity = commands.spawn((
+                player_bundle_at(grid_settings, 0, state.tile),
+                NetworkedPlayer { id: state.player },
+                NetStats::default(),
+            ));
+            if client.player == Some(state.player) {
+                entity.insert((
+                    LocalPlayer,
+                    InputSource::Device(InputDevice::KeyboardWasd),
+                    DirectionIntent::default(),
+                ));
+            } else {
+                entity.insert(SnapshotBuffer::default());
+            }
+            client.players.insert(state.player, entity.id());
+            continue;
+        };
+        let Ok((mut player, mut transform, mut sprite, mut visibility, buffer, stats)) =
+            player_query.get_mut(entity)
+        else {
+            continue;
+        };
+        if let Some(mut stats) = stats {
+            stats.ping_ms = state.ping_ms;
+            stats.loss = state.loss;
+            stats.updated_at = now;
+        }
+
+        player.score = state.score;
+        player.color = color;
+        player.is_drawing_trail = state.drawing_trail;
+        if client.player == Some(state.player) {
+            reconcile(
+                prediction,
+                state,
+                last_input,
+                &mut player,
+                &mut transform,
+                grid_settings,
+            );
+        } else if let Some(mut buffer) = buffer {
+            // Drawn from the buffer, a little behind
+            player.direction = state.direction;
+            player.last_tile_pos = state.tile;
+            player.progress = state.progress;
+            buffer.push(PositionSample {
+                time: server_time(client.server_tick, game_speed),
+                position: player_position(&player, &GridMath::new(grid_settings)),
+                velocity: state.direction * state.speed * grid_settings.tile_size,
+            });
+        }
+        sprite.color = color;
+        *visibility = if state.alive {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+// Keeps trying to get back to the server after a drop, for as long as the
+// server holds our player
+pub fn client_reconnect_system(
+    time: Res<Time>,
+    mut transport: ResMut<NetTransport>,
+    mut client: ResMut<NetClient>,
+    mut error_events: EventWriter<GameError>,
+) {
+    if client.status != ConnectionStatus::Reconnecting {
+        return;
+    }
+    client.reconnecting_for += time.delta_secs();
+    client.since_attempt += time.delta_secs();
+    if client.reconnecting_for > RECONNECT_GRACE_SECONDS {
+        println!("Couldn't get back to the server");
+        client.status = ConnectionStatus::Disconnected;
+        error_events.send(
+            GameError::new(
+                GameErrorKind::ConnectionLost,
+                "couldn't get back to the server",
+            )
+            .with_retry(RetryAction::Reconnect),
+        );
+        return;
+    }
+    // Still waiting on the last attempt to answer
+    if client.since_attempt < RECONNECT_INTERVAL || !transport.0.connections().is_empty() {
+        return;
+    }
+    client.since_attempt = 0.0;
+
+    let Some(reconnect) = client.reconnect.as_mut() else {
+        return;
+    };
+    match reconnect() {
+        Ok(fresh) => *transport = fresh,
+        Err(err) => println!("Failed to reconnect: {}", err),
+    }
+}
+
+// Picks reconnecting back up when the player asks to try again
+pub fn retry_reconnect_system(
+    mut retry_events: EventReader<RetryEvent>,
+    mut client: ResMut<NetClient>,
+) {
+    let retry = retry_events
+        .read()
+        .any(|event| event.action == RetryAction::Reconnect);
+    if retry && client.status == ConnectionStatus::Disconnected && client.reconnect.is_some() {
+        println!("Trying the server again");
+        client.status = ConnectionStatus::Reconnecting;
+        client.reconnecting_for = 0.0;
+        client.since_attempt = RECONNECT_INTERVAL;
+    }
+}
+
+// Sends the local player's steering every frame. It's unreliable, a lost
+// tick is covered by the next one.
+pub fn client_send_input_system(
+    mut transport: ResMut<NetTransport>,
+    mut client: ResMut<NetClient>,
+    intent_query: Query<&DirectionIntent, With<LocalPlayer>>,
+) {
+    if client.status != ConnectionStatus::Joined {
+        return;
+    }
+    let Ok(intent) = intent_query.get_single() else {
+        return;
+    };
+
+    client.input_tick += 1;
+    send(
+        &mut transport,
+        Channel::Unreliable,
+        &ClientMessage::InputTick {
+            tick: client.input_tick,
+            acked_tick: client.server_tick,
+            direction: intent.0,
+        },
+    );
+}
+
+// Passes what our player picked on the emote wheel on to the server
+pub fn client_send_emotes_system(
+    mut transport: ResMut<NetTransport>,
+    client: Res<NetClient>,
+    mut emote_events: EventReader<EmoteEvent>,
+    local_query: Query<(), With<LocalPlayer>>,
+) {
+    for event in emote_events.read() {
+        if client.status != ConnectionStatus::Joined || !local_query.contains(event.player) {
+            continue;
+        }
+        send(
+            &mut transport,
+            Channel::Reliable,
+            &ClientMessage::SendEmote { emote: event.emote },
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// Network stats overlay, toggled with F4. Shows each player's ping and loss
+// as the server measures them, and how stale their last snapshot is here,
+// which is usually enough to tell lag from rubber-banding.
+use crate::components::{LocalPlayer, Player};
+use crate::net::client::NetworkedPlayer;
+use crate::net::prediction::Prediction;
+use crate::net::transport::{NetTransport, SERVER_CONNECTION};
+use bevy::prelude::*;
+
+// Times a second the overlay is redrawn, fast enough to watch a spike
+const REFRESH_SECONDS: f32 = 0.25;
+
+// A mirrored player's connection, from the latest snapshot with them in it
+#[derive(Component, Default)]
+pub struct NetStats {
+    pub ping_ms: Option<u16>,
+    // Percent
+    pub loss: u8,
+    // Local time that snapshot came in
+    pub updated_at: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct NetStatsOverlay {
+    pub visible: bool,
+}
+
+#[derive(Component)]
+pub struct NetStatsPanel;
+
+pub fn toggle_net_stats_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<NetStatsOverlay>,
+    panel_query: Query<Entity, With<NetStatsPanel>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F4) {
+        return;
+    }
+    overlay.visible = !overlay.visible;
+
+    if !overlay.visible {
+        for entity in panel_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        NetStatsPanel,
+    ));
+}
+
+pub fn cleanup_net_stats_overlay(
+    mut commands: Commands,
+    mut overlay: ResMut<NetStatsOverlay>,
+    panel_query: Query<Entity, With<NetStatsPanel>>,
+) {
+    overlay.visible = false;
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn ping_text(ping_ms: Option<u16>) -> String {
+    ping_ms.map_or("? ms".to_string(), |ping| format!("{} ms", ping))
+}
+
+pub fn update_net_stats_overlay_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    transport: Res<NetTransport>,
+    prediction: Res<Prediction>,
+    player_query: Query<(&NetworkedPlayer, &Player, &NetStats, Has<LocalPlayer>)>,
+    panel_query: Query<Entity, With<NetStatsPanel>>,
+    mut since_refresh: Local<f32>,
+) {
+    let Ok(panel) = panel_query.get_single() else {
+        return;
+    };
+    *since_refresh += time.delta_secs();
+    if *since_refresh < REFRESH_SECONDS {
+        return;
+    }
+    *since_refresh = 0.0;
+
+    // Our own link, as we measure it
+    let link = transport.0.stats(SERVER_CONNECTION).unwrap_or_default();
+    let header = format!(
+        "Server: {}, {:.0}% loss, prediction off by {:.1} px",
+        ping_text(link.rtt.map(|rtt| rtt.as_millis() as u16)),
+        link.loss * 100.0,
+        prediction.last_error
+    );
+
+    // Ourselves first, then everyone else in a stable order
+    let mut players: Vec<_> = player_query.iter().collect();
+    players.sort_by_key(|(networked, _, _, local)| (!local, networked.id));
+
+    let now = time.elapsed_secs();
+    commands
+        .entity(panel)
+        .despawn_descendants()
+        .with_children(|panel| {
+            panel.spawn((Text::new(header), TextFont::from_font_size(14.0)));
+            for (index, (_, player, stats, local)) in players.into_iter().enumerate() {
+                let label = if local {
+                    "You".to_string()
+                } else {
+                    format!("Player {}", index + 1)
+                };
+                let age = ((now - stats.updated_at) * 1000.0).max(0.0);
+                panel.spawn((
+                    Text::new(format!(
+                        "{}: {}, {}% loss, snapshot {:.0} ms old",
+                        label,
+                        ping_text(stats.ping_ms),
+                        stats.loss,
+                        age
+                    )),
+                    TextFont::from_font_size(14.0),
+                    TextColor(player.color),
+                ));
+            }
+        });
+}
@@ -0,0 +1,279 @@
+// Finding servers to join. Servers on the LAN answer a broadcast probe, and
+// public ones register with a master server that lists them over HTTP.
+use crate::net::protocol::{discovery_probe, is_discovery_probe, ServerInfo, PROTOCOL_VERSION};
+use crate::net::rotation::{MatchRotation, Playlist};
+use crate::net::server::NetServer;
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+// How often a server tells the master server it's still up
+pub const HEARTBEAT_SECONDS: f32 = 30.0;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(3);
+// Big enough for any server info
+const MAX_DATAGRAM: usize = 1024;
+
+// A public server, as the master server lists it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MasterListing {
+    pub name: String,
+    // Where the game listens. A master server should swap an unspecified
+    // host for the address the registration came from.
+    pub address: String,
+    pub map: String,
+    pub players: u32,
+    pub max_players: u32,
+    #[serde(default)]
+    pub locked: bool,
+}
+
+fn server_info(server: &NetServer, map: &str, port: u16) -> ServerInfo {
+    ServerInfo {
+        name: server.name.clone(),
+        map: map.to_string(),
+        players: server.player_count() as u32,
+        max_players: server.max_players as u32,
+        port,
+        protocol_version: PROTOCOL_VERSION,
+        locked: server.code.is_some(),
+    }
+}
+
+// Answers LAN probes for a server whose game listens on `port`
+#[derive(Resource)]
+pub struct LanBeacon {
+    socket: UdpSocket,
+    pub port: u16,
+}
+
+impl LanBeacon {
+    pub fn bind(discovery_port: u16, port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, discovery_port))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, port })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    // Replies to every probe that came in since the last call
+    pub fn answer(&self, info: &ServerInfo) {
+        let mut buffer = [0; MAX_DATAGRAM];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(err) => {
+                    println!("LAN discovery failed: {}", err);
+                    return;
+                }
+            };
+            if !is_discovery_probe(&buffer[..len]) {
+                continue;
+            }
+            if let Err(err) = self.socket.send_to(&info.encode(), from) {
+                println!("Failed to answer a LAN probe from {}: {}", from, err);
+            }
+        }
+    }
+}
+
+pub fn lan_beacon_system(
+    beacon: Res<LanBeacon>,
+    server: Res<NetServer>,
+    rotation: Res<MatchRotation>,
+    playlist: Res<Playlist>,
+) {
+    beacon.answer(&server_info(
+        &server,
+        rotation.current_map(&playlist),
+        beacon.port,
+    ));
+}
+
+// Looks for servers: a broadcast on the LAN, plus direct probes to servers
+// heard of elsewhere, so they get a ping too
+pub struct LanProbe {
+    socket: UdpSocket,
+    discovery_port: u16,
+    broadcast_at: Instant,
+    // Direct probes by host, to time their replies
+    probed_at: HashMap<IpAddr, Instant>,
+}
+
+impl LanProbe {
+    pub fn bind(discovery_port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            discovery_port,
+            broadcast_at: Instant::now(),
+            probed_at: HashMap::new(),
+        })
+    }
+
+    // Asks every server on the LAN at once
+    pub fn broadcast(&mut self) -> io::Result<()> {
+        self.socket.send_to(
+            &discovery_probe(),
+            (Ipv4Addr::BROADCAST, self.discovery_port),
+        )?;
+        self.broadcast_at = Instant::now();
+        Ok(())
+    }
+
+    pub fn probe(&mut self, host: IpAddr) -> io::Result<()> {
+        self.socket
+            .send_to(&discovery_probe(), (host, self.discovery_port))?;
+        self.probed_at.insert(host, Instant::now());
+        Ok(())
+    }
+
+    // Servers that answered since the last call, with the address of their
+    // game and how long they took
+    pub fn replies(&mut self) -> Vec<(SocketAddr, ServerInfo, Duration)> {
+        let mut replies = Vec::new();
+        let mut buffer = [0; MAX_DATAGRAM];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    println!("LAN discovery failed: {}", err);
+                    break;
+                }
+            };
+            let Ok(info) = ServerInfo::decode(&buffer[..len]) else {
+                continue;
+            };
+            let sent = self
+                .probed_at
+                .get(&from.ip())
+                .copied()
+                .unwrap_or(self.broadcast_at);
+            replies.push((SocketAddr::new(from.ip(), info.port), info, sent.elapsed()));
+        }
+        replies
+    }
+}
+
+// Just enough HTTP/1.0 to talk to a master server. Only plain `http://`
+// URLs, and the whole response is read before returning.
+fn http_request(method: &str, url: &str, body: &str) -> io::Result<String> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| invalid("only http:// master servers are supported"))?;
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = if path.is_empty() { "/" } else { path };
+    let addr = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| invalid("master server host didn't resolve"))?;
+
+    let mut stream = TcpStream::connect_timeout(&addr, HTTP_TIMEOUT)?;
+    stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+    stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+    write!(
+        stream,
+        "{} {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        host,
+        body.len(),
+        body
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP response"))?;
+    let status = head.lines().next().unwrap_or_default();
+    if status
+        .split_whitespace()
+        .nth(1)
+        .is_none_or(|code| !code.starts_with('2'))
+    {
+        return Err(io::Error::other(format!(
+            "master server answered {}",
+            status
+        )));
+    }
+    Ok(body.to_string())
+}
+
+pub fn fetch_master_list(url: &str) -> io::Result<Vec<MasterListing>> {
+    let body = http_request("GET", url, "")?;
+    serde_json::from_str(&body).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn register_with_master(url: &str, listing: &MasterListing) -> io::Result<()> {
+    let body = serde_json::to_string(listing)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    http_request("POST", url, &body).map(|_| ())
+}
+
+// Keeps a public server on the master server's list
+#[derive(Resource)]
+pub struct MasterRegistration {
+    pub url: String,
+    // Game address to list
+    pub address: String,
+    since_heartbeat: f32,
+}
+
+impl MasterRegistration {
+    pub fn new(url: impl Into<String>, address: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            address: address.into(),
+            // Register straight away
+            since_heartbeat: HEARTBEAT_SECONDS,
+        }
+    }
+}
+
+pub fn master_heartbeat_system(
+    time: Res<Time>,
+    mut registration: ResMut<MasterRegistration>,
+    server: Res<NetServer>,
+    rotation: Res<MatchRotation>,
+    playlist: Res<Playlist>,
+) {
+    registration.since_heartbeat += time.delta_secs();
+    if registration.since_heartbeat < HEARTBEAT_SECONDS {
+        return;
+    }
+    registration.since_heartbeat = 0.0;
+
+    let url = registration.url.clone();
+    let listing = MasterListing {
+        name: server.name.clone(),
+        address: registration.address.clone(),
+        map: rotation.current_map(&playlist).to_string(),
+        players: server.player_count() as u32,
+        max_players: server.max_players as u32,
+        locked: server.code.is_some(),
+    };
+    // Off the main thread, a slow master server mustn't stall the match
+    IoTaskPool::get()
+        .spawn(async move {
+            if let Err(err) = register_with_master(&url, &listing) {
+                println!("Failed to register with {}: {}", url, err);
+            }
+        })
+        .detach();
+}
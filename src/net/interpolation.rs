@@ -0,0 +1,116 @@
+// Entity interpolation for remote players. Snapshots arrive in uneven bursts,
+// so instead of jumping players to each one as it lands, they're drawn a
+// little in the past, between the two snapshots either side of that moment.
+use crate::components::GridSettings;
+use crate::net::client::NetClient;
+use crate::resources::{GameRules, GameSpeed};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+// The server steps, and so numbers its snapshots, at this rate in real time
+pub const SERVER_TICK_SECONDS: f32 = 1.0 / 60.0;
+// How far behind the newest snapshot remote players are drawn
+pub const INTERPOLATION_DELAY: f32 = 0.1;
+// When snapshots stop coming, players keep going for at most this long
+pub const MAX_EXTRAPOLATION: f32 = 0.25;
+// Samples older than this are dropped
+const BUFFER_SECONDS: f32 = 1.0;
+// Further apart than this (in tiles) between two snapshots is a respawn,
+// not movement, and isn't smoothed over
+const TELEPORT_TILES: f32 = 2.0;
+
+// Match time on the server at a tick. A faster game fits more of it into
+// each tick, and the local clock runs just as fast.
+pub fn server_time(tick: u32, game_speed: GameSpeed) -> f32 {
+    tick as f32 * SERVER_TICK_SECONDS * game_speed.time_scale()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionSample {
+    // Server time, in seconds
+    pub time: f32,
+    pub position: Vec2,
+    // Pixels per second
+    pub velocity: Vec2,
+}
+
+// Recent authoritative positions of a remote player, oldest first
+#[derive(Component, Default)]
+pub struct SnapshotBuffer {
+    pub samples: VecDeque<PositionSample>,
+}
+
+impl SnapshotBuffer {
+    pub fn push(&mut self, sample: PositionSample) {
+        if self
+            .samples
+            .back()
+            .is_some_and(|newest| newest.time >= sample.time)
+        {
+            return;
+        }
+        self.samples.push_back(sample);
+        while self
+            .samples
+            .front()
+            .is_some_and(|oldest| sample.time - oldest.time > BUFFER_SECONDS)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    // Where the player was at `time`: interpolated between the samples
+    // around it, or extrapolated a short way past the newest one
+    pub fn sample(&self, time: f32, tile_size: f32) -> Option<Vec2> {
+        let newest = self.samples.back()?;
+        if time >= newest.time {
+            let ahead = (time - newest.time).min(MAX_EXTRAPOLATION);
+            return Some(newest.position + newest.velocity * ahead);
+        }
+
+        let after = self.samples.iter().position(|sample| sample.time > time)?;
+        if after == 0 {
+            return Some(self.samples[0].position);
+        }
+        let (from, to) = (self.samples[after - 1], self.samples[after]);
+        if from.position.distance(to.position) > TELEPORT_TILES * tile_size {
+            return Some(from.position);
+        }
+        let t = (time - from.time) / (to.time - from.time);
+        Some(from.position.lerp(to.position, t))
+    }
+}
+
+// Server time remote players are currently drawn at. Runs on the local
+// clock, and is nudged towards the snapshots so it neither drifts nor
+// jumps with every late packet.
+#[derive(Resource, Default)]
+pub struct InterpolationClock {
+    pub render_time: f32,
+}
+
+pub fn interpolate_remote_players_system(
+    time: Res<Time>,
+    client: Res<NetClient>,
+    grid_settings: Res<GridSettings>,
+    rules: Res<GameRules>,
+    mut clock: ResMut<InterpolationClock>,
+    // Only remote players have a buffer, ours is predicted instead
+    mut player_query: Query<(&SnapshotBuffer, &mut Transform)>,
+) {
+    let target = server_time(client.server_tick, rules.game_speed) - INTERPOLATION_DELAY;
+    clock.render_time += time.delta_secs();
+    let drift = target - clock.render_time;
+    if drift.abs() > 0.25 {
+        clock.render_time = target;
+    } else {
+        clock.render_time += drift * 0.1;
+    }
+
+    for (buffer, mut transform) in player_query.iter_mut() {
+        if let Some(position) = buffer.sample(clock.render_time, grid_settings.tile_size) {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// Invites to a lobby: the server's address, plus its code when it has one,
+// written `ADDR/CODE` so it can be pasted straight into `--connect`.
+use crate::net::client::NetClient;
+use bevy::prelude::*;
+use rand::Rng;
+use std::fmt;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+// No 0/O or 1/I, codes get read out loud
+pub const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 6;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invite {
+    pub address: String,
+    pub code: Option<String>,
+}
+
+impl Invite {
+    pub fn parse(invite: &str) -> Self {
+        match invite.trim().split_once('/') {
+            Some((address, code)) if !code.is_empty() => Self {
+                address: address.to_string(),
+                code: Some(code.to_string()),
+            },
+            Some((address, _)) => Self {
+                address: address.to_string(),
+                code: None,
+            },
+            None => Self {
+                address: invite.trim().to_string(),
+                code: None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Invite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{}/{}", self.address, code),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+// Fresh code for a private lobby
+pub fn generate_code() -> String {
+    let mut rng = rand::rng();
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rng.random_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+// Hands the text to whichever clipboard tool the platform has
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let tools: &[(&str, &[&str])] = if cfg!(target_os = "windows") {
+        &[("clip", &[])]
+    } else if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+
+    let mut last_err = io::Error::from(io::ErrorKind::NotFound);
+    for (tool, args) in tools {
+        let child = Command::new(tool).args(*args).stdin(Stdio::piped()).spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) => {
+                last_err = err;
+                continue;
+            }
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        child.wait()?;
+        return Ok(());
+    }
+    Err(last_err)
+}
+
+#[derive(Component)]
+pub struct InviteButton;
+
+pub fn setup_invite_button(mut commands: Commands, client: Option<Res<NetClient>>) {
+    if client.is_none_or(|client| client.invite.is_none()) {
+        return;
+    }
+
+    commands
+        .spawn((
+            Button,
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                top: Val::Px(10.0),
+                padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            InviteButton,
+        ))
+        .with_children(|button| {
+            button.spawn((Text::new("Copy invite"), TextFont::from_font_size(14.0)));
+        });
+}
+
+pub fn cleanup_invite_button(
+    mut commands: Commands,
+    button_query: Query<Entity, With<InviteButton>>,
+) {
+    for entity in button_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn copy_invite_system(
+    client: Res<NetClient>,
+    button_query: Query<&Interaction, (Changed<Interaction>, With<InviteButton>)>,
+) {
+    let Some(invite) = client.invite.as_ref() else {
+        return;
+    };
+    for interaction in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // Printed either way, so it can still be copied from the console
+        println!("Invite: {}", invite);
+        if let Err(err) = copy_to_clipboard(&invite.to_string()) {
+            println!("Failed to copy the invite: {}", err);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::net::transport::{Channel, ConnectionId, Transport, TransportEvent, SERVER_CONNECTION};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+type Mailbox = Arc<Mutex<VecDeque<TransportEvent>>>;
+
+// In-process transport, for hosting a local client and for tests. Both
+// channels are reliable since nothing can get lost.
+pub struct MemoryTransport {
+    inbox: Mailbox,
+    // Where messages to each connection go, tagged with our id on their end
+    peers: Vec<(ConnectionId, ConnectionId, Mailbox)>,
+}
+
+impl MemoryTransport {
+    // A server with `clients` clients already connected to it
+    pub fn server_with_clients(clients: usize) -> (MemoryTransport, Vec<MemoryTransport>) {
+        let mut server = MemoryTransport {
+            inbox: Mailbox::default(),
+            peers: Vec::new(),
+        };
+        let clients = (1..=clients as ConnectionId)
+            .map(|id| server.add_client(id))
+            .collect();
+        (server, clients)
+    }
+
+    // Connects another client to this server under the given id
+    pub fn add_client(&mut self, id: ConnectionId) -> MemoryTransport {
+        let client = MemoryTransport {
+            inbox: Mailbox::default(),
+            peers: vec![(SERVER_CONNECTION, id, self.inbox.clone())],
+        };
+        self.peers
+            .push((id, SERVER_CONNECTION, client.inbox.clone()));
+
+        self.inbox
+            .lock()
+            .unwrap()
+            .push_back(TransportEvent::Connected(id));
+        client
+            .inbox
+            .lock()
+            .unwrap()
+            .push_back(TransportEvent::Connected(SERVER_CONNECTION));
+        client
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send(&mut self, to: ConnectionId, _channel: Channel, payload: &[u8]) -> io::Result<()> {
+        let Some((_, from, mailbox)) = self.peers.iter().find(|(id, _, _)| *id == to) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "unknown connection",
+            ));
+        };
+        mailbox.lock().unwrap().push_back(TransportEvent::Message {
+            from: *from,
+            payload: payload.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        let events: Vec<TransportEvent> = self.inbox.lock().unwrap().drain(..).collect();
+        for event in events.iter() {
+            if let TransportEvent::Disconnected(connection) = event {
+                self.peers.retain(|(id, _, _)| id != connection);
+            }
+        }
+        events
+    }
+
+    fn disconnect(&mut self, connection: ConnectionId) {
+        let Some(index) = self.peers.iter().position(|(id, _, _)| *id == connection) else {
+            return;
+        };
+        let (_, our_id, mailbox) = self.peers.remove(index);
+        mailbox
+            .lock()
+            .unwrap()
+            .push_back(TransportEvent::Disconnected(our_id));
+        self.inbox
+            .lock()
+            .unwrap()
+            .push_back(TransportEvent::Disconnected(connection));
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        self.peers.iter().map(|(id, _, _)| *id).collect()
+    }
+}
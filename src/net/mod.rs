@@ -0,0 +1,30 @@
+// Networking. Transports move whole messages between a server and its
+// clients, so nothing above this layer touches a socket.
+pub mod backfill;
+pub mod browser;
+pub mod client;
+pub mod diagnostics;
+pub mod discovery;
+pub mod interpolation;
+pub mod invite;
+pub mod memory;
+pub mod multi;
+pub mod observer;
+pub mod prediction;
+pub mod protocol;
+pub mod rotation;
+pub mod server;
+pub mod transport;
+pub mod udp;
+pub mod vote;
+// Browser builds connect through the page, native ones open their own sockets
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod websocket;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod webtransport;
+
+pub use transport::{
+    Channel, ConnectionId, ConnectionStats, NetTransport, Transport, TransportEvent,
+};
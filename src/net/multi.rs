@@ -0,0 +1,103 @@
+use crate::net::transport::{
+    Channel, ConnectionId, ConnectionStats, Transport, TransportEvent, SERVER_CONNECTION,
+};
+use std::io;
+
+// Serves clients over several transports at once, so desktop players on UDP
+// and browser players on WebSocket or WebTransport share one server. Each
+// transport numbers its own connections, so they're renumbered here to keep
+// them apart.
+pub struct MultiTransport {
+    transports: Vec<Box<dyn Transport>>,
+    // Our id for each connection, with the transport it's on and its id there
+    connections: Vec<(ConnectionId, usize, ConnectionId)>,
+    next_id: ConnectionId,
+}
+
+impl Default for MultiTransport {
+    fn default() -> Self {
+        Self {
+            transports: Vec::new(),
+            connections: Vec::new(),
+            next_id: SERVER_CONNECTION + 1,
+        }
+    }
+}
+
+impl MultiTransport {
+    pub fn with(mut self, transport: impl Transport) -> Self {
+        self.transports.push(Box::new(transport));
+        self
+    }
+
+    fn route(&self, connection: ConnectionId) -> Option<(usize, ConnectionId)> {
+        self.connections
+            .iter()
+            .find(|(id, _, _)| *id == connection)
+            .map(|(_, index, inner)| (*index, *inner))
+    }
+
+    fn outer_id(&self, index: usize, inner: ConnectionId) -> Option<ConnectionId> {
+        self.connections
+            .iter()
+            .find(|(_, on, id)| *on == index && *id == inner)
+            .map(|(id, _, _)| *id)
+    }
+}
+
+impl Transport for MultiTransport {
+    fn send(&mut self, to: ConnectionId, channel: Channel, payload: &[u8]) -> io::Result<()> {
+        let Some((index, inner)) = self.route(to) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "unknown connection",
+            ));
+        };
+        self.transports[index].send(inner, channel, payload)
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        let mut events = Vec::new();
+        for index in 0..self.transports.len() {
+            for event in self.transports[index].poll() {
+                match event {
+                    TransportEvent::Connected(inner) => {
+                        let id = self.next_id;
+                        self.next_id += 1;
+                        self.connections.push((id, index, inner));
+                        events.push(TransportEvent::Connected(id));
+                    }
+                    TransportEvent::Disconnected(inner) => {
+                        let Some(id) = self.outer_id(index, inner) else {
+                            continue;
+                        };
+                        self.connections.retain(|(known, _, _)| *known != id);
+                        events.push(TransportEvent::Disconnected(id));
+                    }
+                    TransportEvent::Message { from, payload } => {
+                        if let Some(from) = self.outer_id(index, from) {
+                            events.push(TransportEvent::Message { from, payload });
+                        }
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    fn disconnect(&mut self, connection: ConnectionId) {
+        if let Some((index, inner)) = self.route(connection) {
+            self.connections.retain(|(id, _, _)| *id != connection);
+            self.transports[index].disconnect(inner);
+        }
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        self.connections.iter().map(|(id, _, _)| *id).collect()
+    }
+
+    fn stats(&self, connection: ConnectionId) -> Option<ConnectionStats> {
+        let (index, inner) = self.route(connection)?;
+        self.transports[index].stats(inner)
+    }
+}
@@ -0,0 +1,213 @@
+// Observer mode, for casting a match: joined without a player, with a free
+// camera and shortcuts to follow any player. Tab steps through the players,
+// 1-9 picks one, Space lets the camera go again.
+use crate::components::{Followed, MainCamera, Player};
+use crate::net::client::{NetClient, NetworkedPlayer};
+use crate::net::protocol::NetId;
+use bevy::prelude::*;
+
+// World units a second the free camera pans at full zoom
+const PAN_SPEED: f32 = 400.0;
+// Zoom change a second while a zoom key is held
+const ZOOM_SPEED: f32 = 1.5;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 2.0;
+// How quickly the camera catches up with a followed player
+const FOLLOW_EASE: f32 = 6.0;
+
+const FOLLOW_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+#[derive(Resource)]
+pub struct ObserverCamera {
+    pub position: Vec2,
+    pub zoom: f32,
+    // Player the camera sticks to, None for a free camera
+    pub following: Option<NetId>,
+}
+
+impl Default for ObserverCamera {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+            following: None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ObserverHud;
+
+pub fn is_observer(client: Option<Res<NetClient>>) -> bool {
+    client.is_some_and(|client| client.observer)
+}
+
+pub fn setup_observer_hud(mut commands: Commands) {
+    commands.insert_resource(ObserverCamera::default());
+    commands.spawn((
+        Text::new("Observing"),
+        TextFont::from_font_size(16.0),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            top: Val::Px(10.0),
+            ..default()
+        },
+        ObserverHud,
+    ));
+}
+
+pub fn cleanup_observer_hud(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<ObserverHud>>,
+    followed_query: Query<Entity, With<Followed>>,
+) {
+    for entity in hud_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in followed_query.iter() {
+        commands.entity(entity).remove::<Followed>();
+    }
+    commands.remove_resource::<ObserverCamera>();
+}
+
+// Everyone in the match, in a stable order for the follow shortcuts
+fn players_in_order(client: &NetClient) -> Vec<NetId> {
+    let mut players: Vec<NetId> = client.players.keys().copied().collect();
+    players.sort();
+    players
+}
+
+pub fn observer_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    client: Res<NetClient>,
+    mut camera: ResMut<ObserverCamera>,
+) {
+    let players = players_in_order(&client);
+
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        let next = camera
+            .following
+            .and_then(|id| players.iter().position(|&player| player == id))
+            .map_or(0, |index| index + 1);
+        camera.following = players.get(next).copied();
+    }
+    // The number keys are for voting while a vote is open
+    if client.ballot.is_none() {
+        if let Some(index) = FOLLOW_KEYS
+            .iter()
+            .position(|key| keyboard_input.just_pressed(*key))
+        {
+            if let Some(&player) = players.get(index) {
+                camera.following = Some(player);
+            }
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        camera.following = None;
+    }
+
+    let mut pan = Vec2::ZERO;
+    if keyboard_input.any_pressed([KeyCode::KeyW, KeyCode::ArrowUp]) {
+        pan.y += 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyS, KeyCode::ArrowDown]) {
+        pan.y -= 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyA, KeyCode::ArrowLeft]) {
+        pan.x -= 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyD, KeyCode::ArrowRight]) {
+        pan.x += 1.0;
+    }
+    // Panning takes the camera back off whoever it was following
+    if pan != Vec2::ZERO {
+        camera.following = None;
+        let step = pan.normalize() * PAN_SPEED * camera.zoom * time.delta_secs();
+        camera.position += step;
+    }
+
+    let mut zoom = 0.0;
+    if keyboard_input.any_pressed([KeyCode::KeyQ, KeyCode::Minus]) {
+        zoom += 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyE, KeyCode::Equal]) {
+        zoom -= 1.0;
+    }
+    camera.zoom =
+        (camera.zoom * (1.0 + zoom * ZOOM_SPEED * time.delta_secs())).clamp(MIN_ZOOM, MAX_ZOOM);
+}
+
+type ObserverCameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, &'static mut OrthographicProjection),
+    (With<MainCamera>, Without<Player>),
+>;
+
+// Moves the view to where the observer wants it. Runs after the kill cam,
+// which would otherwise pull the camera back to the whole map.
+pub fn observer_camera_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut camera: ResMut<ObserverCamera>,
+    player_query: Query<(Entity, &NetworkedPlayer, &Transform, Has<Followed>), With<Player>>,
+    mut camera_query: ObserverCameraQuery,
+) {
+    for (entity, networked, transform, followed) in player_query.iter() {
+        let target = camera.following == Some(networked.id);
+        if target && !followed {
+            commands.entity(entity).insert(Followed);
+        } else if followed && !target {
+            commands.entity(entity).remove::<Followed>();
+        }
+        if target {
+            let blend = (FOLLOW_EASE * time.delta_secs()).min(1.0);
+            camera.position = camera
+                .position
+                .lerp(transform.translation.truncate(), blend);
+        }
+    }
+
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        transform.translation.x = camera.position.x;
+        transform.translation.y = camera.position.y;
+        projection.scale = camera.zoom;
+    }
+}
+
+pub fn update_observer_hud_system(
+    client: Res<NetClient>,
+    camera: Res<ObserverCamera>,
+    mut hud_query: Query<&mut Text, With<ObserverHud>>,
+) {
+    let players = players_in_order(&client);
+    let status = match camera
+        .following
+        .and_then(|id| players.iter().position(|&player| player == id))
+    {
+        Some(index) => format!("following player {}", index + 1),
+        None => "free camera".to_string(),
+    };
+    let line = format!(
+        "Observing, {} - Tab/1-9 to follow, Space to let go, WASD to pan, Q/E to zoom",
+        status
+    );
+    for mut text in hud_query.iter_mut() {
+        // Only touched when it changes, to spare the text layout
+        if text.0 != line {
+            text.0 = line.clone();
+        }
+    }
+}
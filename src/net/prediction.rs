@@ -0,0 +1,123 @@
+// Client-side prediction for the local player. Turns happen as soon as the
+// key goes down instead of a round trip later: every input is played locally
+// straight away and kept until a snapshot says the server has played it too.
+// Reconciling puts the player where the server had them and replays the
+// inputs it hadn't seen yet on top.
+use crate::components::{GridSettings, LocalPlayer, Player};
+use crate::grid::GridMath;
+use crate::net::client::{ConnectionStatus, NetClient};
+use crate::net::protocol::PlayerState;
+use crate::systems::input::{apply_direction, DirectionIntent};
+use crate::systems::movement::{
+    advance_player, arrive_at_tile, bounce_off_edge, player_position, take_stairs,
+};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+// Unconfirmed inputs kept at most, two seconds' worth at 60 fps
+const MAX_PREDICTED_INPUTS: usize = 120;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PredictedInput {
+    pub tick: u32,
+    pub direction: Vec2,
+    pub delta_secs: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct Prediction {
+    // Played locally, not yet confirmed by the server, oldest first
+    pub inputs: VecDeque<PredictedInput>,
+    // How far the last snapshot moved the local player, in pixels
+    pub last_error: f32,
+    // The server only moves players once the countdown is over and while
+    // they're alive, and neither do we
+    pub active: bool,
+}
+
+// One frame of the local player's movement, played the way the server
+// plays it: the input is applied, then the player moves
+pub fn predict_step(
+    player: &mut Player,
+    translation: &mut Vec3,
+    input: &PredictedInput,
+    grid_settings: &GridSettings,
+) {
+    apply_direction(player, input.direction);
+    let mut delta_secs = input.delta_secs;
+    while delta_secs > 0.0 && player.direction.length_squared() > 0.0 {
+        if arrive_at_tile(player).is_some() {
+            bounce_off_edge(player, grid_settings);
+            take_stairs(player, grid_settings);
+        }
+        delta_secs = advance_player(player, delta_secs, grid_settings);
+    }
+    let position = player_position(player, &GridMath::new(grid_settings));
+    translation.x = position.x;
+    translation.y = position.y;
+}
+
+// Rewinds the local player to the server's state for the newest input it has
+// played, then replays the rest
+pub fn reconcile(
+    prediction: &mut Prediction,
+    state: &PlayerState,
+    last_input: u32,
+    player: &mut Player,
+    transform: &mut Transform,
+    grid_settings: &GridSettings,
+) {
+    prediction.inputs.retain(|input| input.tick > last_input);
+    let predicted = transform.translation.truncate();
+
+    player.direction = state.direction;
+    player.buffered_direction = state.buffered_direction;
+    player.speed = state.speed;
+    player.last_tile_pos = state.tile;
+    player.progress = state.progress;
+    let position = player_position(player, &GridMath::new(grid_settings));
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+
+    if prediction.active {
+        for input in prediction.inputs.iter() {
+            predict_step(player, &mut transform.translation, input, grid_settings);
+        }
+    } else {
+        prediction.inputs.clear();
+    }
+    prediction.last_error = predicted.distance(transform.translation.truncate());
+}
+
+// Plays this frame's input on the local player right away
+pub fn predict_local_player_system(
+    time: Res<Time>,
+    client: Res<NetClient>,
+    grid_settings: Res<GridSettings>,
+    mut prediction: ResMut<Prediction>,
+    mut player_query: Query<(&DirectionIntent, &mut Player, &mut Transform), With<LocalPlayer>>,
+) {
+    if client.status != ConnectionStatus::Joined || !prediction.active {
+        return;
+    }
+    let Ok((intent, mut player, mut transform)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    let input = PredictedInput {
+        tick: client.input_tick,
+        direction: intent.0,
+        delta_secs: time.delta_secs(),
+    };
+    predict_step(
+        &mut player,
+        &mut transform.translation,
+        &input,
+        &grid_settings,
+    );
+
+    prediction.inputs.push_back(input);
+    if prediction.inputs.len() > MAX_PREDICTED_INPUTS {
+        prediction.inputs.pop_front();
+    }
+}
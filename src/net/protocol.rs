@@ -0,0 +1,287 @@
+// Wire format for networked play. Messages are serde types encoded with
+// postcard: the variant index, then fields as varints (zigzagged when
+// signed), little-endian floats and length-prefixed strings and lists.
+use crate::components::{GridTopology, MapShape, UpperFloor, ValueZone};
+use crate::resources::GameSpeed;
+use crate::systems::emotes::Emote;
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Bump whenever a message changes shape. Clients on another version are
+// turned away during the join handshake.
+pub const PROTOCOL_VERSION: u16 = 18;
+
+// `JoinRequest` stays the first variant, with its version as the first
+// field, in every protocol version, so any server can read it well enough to
+// reject it
+const JOIN_REQUEST: u32 = 0;
+
+// LAN discovery runs on its own port, outside any connection. A probe is
+// the magic alone, a reply is the magic followed by the server's info.
+pub const DISCOVERY_PORT: u16 = 47_810;
+const DISCOVERY_PROBE: [u8; 4] = *b"LDS?";
+const DISCOVERY_REPLY: [u8; 4] = *b"LDS!";
+
+// Server-side id of a player, stable for as long as they're connected
+pub type NetId = u64;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    // `resume` is the session of a player we dropped out of, to take them
+    // back over if the server is still holding them. `code` is the lobby's
+    // invite code, for servers that have one. Observers watch without a
+    // player of their own.
+    JoinRequest {
+        protocol_version: u16,
+        name: String,
+        resume: Option<u64>,
+        code: Option<String>,
+        observer: bool,
+    },
+    // Direction the player is steering in on their `tick`, sent while
+    // looking at the server's snapshot `acked_tick`
+    InputTick {
+        tick: u32,
+        acked_tick: u32,
+        direction: Vec2,
+    },
+    // Pick for the next map, an index into the open vote's options
+    CastVote {
+        option: u32,
+    },
+    // Our player said something from the emote wheel
+    SendEmote {
+        emote: Emote,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    // `session` lets the client resume this player after a dropped
+    // connection. Observers get no player.
+    JoinAccepted {
+        player: Option<NetId>,
+        session: u64,
+        grid_width: i32,
+        grid_height: i32,
+        value_zones: Vec<ValueZone>,
+        game_speed: GameSpeed,
+        topology: GridTopology,
+        shape: MapShape,
+        upper_floor: Option<UpperFloor>,
+    },
+    JoinRejected {
+        reason: RejectReason,
+    },
+    // Where everyone is, sent every tick. `last_input` is the newest input
+    // tick the server played for the receiving client, `live` whether
+    // players can move yet.
+    Snapshot {
+        tick: u32,
+        last_input: u32,
+        live: bool,
+        players: Vec<PlayerState>,
+    },
+    // Tiles that changed hands since the last delta
+    TileDelta {
+        tick: u32,
+        tiles: Vec<TileUpdate>,
+    },
+    ClaimResult {
+        player: NetId,
+        tiles: u32,
+    },
+    DeathNotice {
+        player: NetId,
+        killer: Option<NetId>,
+        tile: (i32, i32),
+    },
+    // The match is over, the next map is picked from `options` by vote
+    VoteStarted {
+        options: Vec<String>,
+        seconds: f32,
+    },
+    // Votes per option so far
+    VoteTally {
+        votes: Vec<u32>,
+    },
+    // The next match is played on this option. A fresh `JoinAccepted`
+    // follows once it's set up.
+    VoteEnded {
+        option: u32,
+    },
+    // Someone's speech bubble, bots included
+    Emote {
+        player: NetId,
+        emote: Emote,
+    },
+    // The match starts this many seconds after it was sent. Sent when the
+    // countdown starts, and to anyone joining while it runs.
+    Countdown {
+        seconds: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    VersionMismatch { server_version: u16 },
+    ServerFull,
+    WrongCode,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RejectReason::VersionMismatch { server_version } => write!(
+                f,
+                "server runs protocol version {}, this game uses {}",
+                server_version, PROTOCOL_VERSION
+            ),
+            RejectReason::ServerFull => write!(f, "server is full"),
+            RejectReason::WrongCode => write!(f, "wrong invite code"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub player: NetId,
+    // Logical position, the tile and how far towards the next one. Where the
+    // player is drawn follows from these and `direction`.
+    pub tile: (i32, i32),
+    pub progress: f32,
+    pub direction: Vec2,
+    // Movement state the client needs to replay its own inputs
+    pub buffered_direction: Option<Vec2>,
+    pub speed: f32,
+    pub score: u32,
+    pub drawing_trail: bool,
+    pub alive: bool,
+    // sRGB bytes
+    pub color: [u8; 3],
+    // The server's view of this player's connection, none for bots and
+    // players it has no measurement for yet
+    pub ping_ms: Option<u16>,
+    // Percent
+    pub loss: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileUpdate {
+    pub x: i32,
+    pub y: i32,
+    // Whose land and whose trail, as on `Tile`
+    pub owner: Option<NetId>,
+    pub trail_owner: Option<NetId>,
+}
+
+// What a server tells browsers about itself
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub name: String,
+    pub map: String,
+    pub players: u32,
+    pub max_players: u32,
+    // Where the game itself listens, on the same host
+    pub port: u16,
+    pub protocol_version: u16,
+    // Joining takes an invite code
+    pub locked: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolError {
+    // Cut short, or not a message at all
+    Malformed(postcard::Error),
+    NotDiscovery,
+}
+
+impl From<postcard::Error> for ProtocolError {
+    fn from(err: postcard::Error) -> Self {
+        ProtocolError::Malformed(err)
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Malformed(err) => write!(f, "malformed message: {}", err),
+            ProtocolError::NotDiscovery => write!(f, "not a discovery packet"),
+        }
+    }
+}
+
+// Whether a server on our version lets this client in
+pub fn negotiate(protocol_version: u16) -> Result<(), RejectReason> {
+    if protocol_version == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(RejectReason::VersionMismatch {
+            server_version: PROTOCOL_VERSION,
+        })
+    }
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        encode(self)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        // A client on another version may have changed the rest of the
+        // request, so only its version is read
+        if let Ok(((JOIN_REQUEST, protocol_version), _)) =
+            postcard::take_from_bytes::<(u32, u16)>(bytes)
+        {
+            if protocol_version != PROTOCOL_VERSION {
+                return Ok(ClientMessage::JoinRequest {
+                    protocol_version,
+                    name: String::new(),
+                    resume: None,
+                    code: None,
+                    observer: false,
+                });
+            }
+        }
+        Ok(postcard::from_bytes(bytes)?)
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        encode(self)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+}
+
+fn encode(message: &impl Serialize) -> Vec<u8> {
+    // Only fails on types postcard can't write, which no message has
+    postcard::to_stdvec(message).expect("network messages always encode")
+}
+
+pub fn discovery_probe() -> Vec<u8> {
+    DISCOVERY_PROBE.to_vec()
+}
+
+pub fn is_discovery_probe(bytes: &[u8]) -> bool {
+    bytes == DISCOVERY_PROBE
+}
+
+impl ServerInfo {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = DISCOVERY_REPLY.to_vec();
+        bytes.extend(encode(self));
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let bytes = bytes
+            .strip_prefix(&DISCOVERY_REPLY)
+            .ok_or(ProtocolError::NotDiscovery)?;
+        Ok(postcard::from_bytes(bytes)?)
+    }
+}
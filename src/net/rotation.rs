@@ -0,0 +1,328 @@
+// Map rotation for the dedicated server. Matches are played off a playlist,
+// and between two of them connected clients vote on which entry comes next.
+use crate::components::{GridSettings, GridTopology, MapShape, Player, UpperFloor, ValueZone};
+use crate::events::MatchEndedEvent;
+use crate::net::protocol::ServerMessage;
+use crate::net::server::{net_id, send, NetServer, RemotePlayer};
+use crate::net::transport::{Channel, NetTransport};
+use crate::resources::{ActivePreset, GameRules, GameSpeed, GameState, PendingClaims, RulesPreset};
+use crate::states::AppState;
+use crate::systems::collision::LagCompensation;
+use crate::systems::input::{DirectionIntent, InputSource};
+use crate::{player_bundle, spawn_grid};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// A map and mode to play a match on
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistEntry {
+    pub name: String,
+    pub grid_width: i32,
+    pub grid_height: i32,
+    pub preset: RulesPreset,
+    pub match_seconds: f32,
+    #[serde(default)]
+    pub speed: GameSpeed,
+    // Patches of the map worth more, e.g. a centre hill worth 3 a tile
+    #[serde(default)]
+    pub value_zones: Vec<ValueZone>,
+    // What the map's edges do, solid unless set
+    #[serde(default)]
+    pub topology: GridTopology,
+    // Outline of the playable area, e.g. `Circle` or a `Mask` of rows
+    #[serde(default)]
+    pub shape: MapShape,
+    // Stairs up to a second floor the same size, none for a single floor
+    #[serde(default)]
+    pub upper_floor: Option<UpperFloor>,
+}
+
+impl PlaylistEntry {
+    pub fn grid_settings(&self) -> GridSettings {
+        let grid_settings = GridSettings {
+            grid_width: self.grid_width,
+            grid_height: self.grid_height,
+            value_zones: self.value_zones.clone(),
+            topology: self.topology,
+            shape: self.shape.clone(),
+            ..default()
+        };
+        match &self.upper_floor {
+            Some(upper_floor) => grid_settings.with_upper_floor(upper_floor.clone()),
+            None => grid_settings,
+        }
+    }
+
+    pub fn rules(&self) -> GameRules {
+        GameRules {
+            game_speed: self.speed,
+            ..self.preset.rules()
+        }
+    }
+
+    pub fn game_state(&self) -> GameState {
+        GameState {
+            timer: Timer::from_seconds(self.match_seconds, TimerMode::Once),
+            ..default()
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Playlist {
+    // Played in order unless a vote picks otherwise
+    pub entries: Vec<PlaylistEntry>,
+    pub vote_seconds: f32,
+    // Entries offered in each vote, the next ones in rotation order
+    pub vote_options: usize,
+}
+
+impl Default for Playlist {
+    fn default() -> Self {
+        let entry = |name: &str, grid_width, grid_height, preset, match_seconds| PlaylistEntry {
+            name: name.into(),
+            grid_width,
+            grid_height,
+            preset,
+            match_seconds,
+            speed: GameSpeed::Normal,
+            value_zones: Vec::new(),
+            topology: GridTopology::Clamp,
+            shape: MapShape::Rectangle,
+            upper_floor: None,
+        };
+        Self {
+            entries: vec![
+                entry("Classic", 40, 30, RulesPreset::Classic, 300.0),
+                entry("Casual", 40, 30, RulesPreset::Casual, 180.0),
+                entry("Skirmish", 30, 22, RulesPreset::Classic, 180.0),
+            ],
+            vote_seconds: 15.0,
+            vote_options: 3,
+        }
+    }
+}
+
+// A vote in progress between two matches
+pub struct MapVote {
+    // Playlist indices, the first is the default pick
+    pub options: Vec<usize>,
+    pub seconds_left: f32,
+    // Last tally sent to clients
+    pub tally: Vec<u32>,
+}
+
+impl MapVote {
+    // Option with the most votes, ties go to the one earlier in rotation
+    pub fn winner(&self) -> usize {
+        let mut winner = 0;
+        for (option, &count) in self.tally.iter().enumerate() {
+            if count > self.tally[winner] {
+                winner = option;
+            }
+        }
+        winner
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MatchRotation {
+    // Playlist index of the match being played
+    pub current: usize,
+    pub vote: Option<MapVote>,
+}
+
+impl MatchRotation {
+    pub fn current_map<'a>(&self, playlist: &'a Playlist) -> &'a str {
+        playlist
+            .entries
+            .get(self.current)
+            .map_or("", |entry| entry.name.as_str())
+    }
+}
+
+// The next `count` entries after `current`, wrapping around the playlist
+pub fn upcoming_entries(current: usize, len: usize, count: usize) -> Vec<usize> {
+    (1..=count.min(len))
+        .map(|step| (current + step) % len)
+        .collect()
+}
+
+// Opens the vote for the next map once the match is over
+pub fn start_map_vote_system(
+    mut match_end_events: EventReader<MatchEndedEvent>,
+    mut transport: ResMut<NetTransport>,
+    mut server: ResMut<NetServer>,
+    mut rotation: ResMut<MatchRotation>,
+    playlist: Res<Playlist>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if match_end_events.read().last().is_none() || playlist.entries.is_empty() {
+        return;
+    }
+
+    let options = upcoming_entries(
+        rotation.current,
+        playlist.entries.len(),
+        playlist.vote_options.max(1),
+    );
+    let message = ServerMessage::VoteStarted {
+        options: options
+            .iter()
+            .map(|&index| playlist.entries[index].name.clone())
+            .collect(),
+        seconds: playlist.vote_seconds,
+    };
+    for connection in server.peers() {
+        send(&mut transport, connection, Channel::Reliable, &message);
+    }
+
+    server.votes.clear();
+    rotation.vote = Some(MapVote {
+        tally: vec![0; options.len()],
+        options,
+        seconds_left: playlist.vote_seconds,
+    });
+    next_state.set(AppState::Intermission);
+}
+
+// Counts votes as they come in, then sets up the winning map and starts the
+// next match on it
+#[allow(clippy::too_many_arguments)]
+pub fn map_vote_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut transport: ResMut<NetTransport>,
+    mut server: ResMut<NetServer>,
+    mut rotation: ResMut<MatchRotation>,
+    playlist: Res<Playlist>,
+    mut pending_claims: ResMut<PendingClaims>,
+    mut next_state: ResMut<NextState<AppState>>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    let Some(vote) = rotation.vote.as_mut() else {
+        return;
+    };
+
+    // Votes from clients that left don't count
+    let mut tally = vec![0; vote.options.len()];
+    for (connection, &option) in server.votes.iter() {
+        if server.clients.contains_key(connection) {
+            if let Some(count) = tally.get_mut(option as usize) {
+                *count += 1;
+            }
+        }
+    }
+    if tally != vote.tally {
+        let message = ServerMessage::VoteTally {
+            votes: tally.clone(),
+        };
+        for connection in server.peers() {
+            send(&mut transport, connection, Channel::Reliable, &message);
+        }
+        vote.tally = tally;
+    }
+
+    vote.seconds_left -= time.delta_secs();
+    if vote.seconds_left > 0.0 {
+        return;
+    }
+
+    let winner = vote.winner();
+    let index = vote.options[winner];
+    rotation.vote = None;
+    rotation.current = index;
+    let entry = &playlist.entries[index];
+    println!("Next up: {}", entry.name);
+    let message = ServerMessage::VoteEnded {
+        option: winner as u32,
+    };
+    for connection in server.peers() {
+        send(&mut transport, connection, Channel::Reliable, &message);
+    }
+
+    // Everyone starts over on a fresh map, players held for a reconnect
+    // included
+    for entity in player_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    server.away.clear();
+    server.votes.clear();
+    pending_claims.tasks.clear();
+    pending_claims.cancelled.clear();
+
+    let grid_settings = entry.grid_settings();
+    spawn_grid(&mut commands, &grid_settings);
+    commands.insert_resource(grid_settings);
+    commands.insert_resource(entry.rules());
+    commands.insert_resource(ActivePreset(Some(entry.preset)));
+    commands.insert_resource(entry.game_state());
+    next_state.set(AppState::Playing);
+}
+
+// Gives every connected client a new player for the match that's starting,
+// and tells observers about the new map
+pub fn respawn_remote_players_system(
+    mut commands: Commands,
+    mut transport: ResMut<NetTransport>,
+    mut server: ResMut<NetServer>,
+    grid_settings: Res<GridSettings>,
+    rules: Res<GameRules>,
+) {
+    let mut connections: Vec<_> = server.clients.keys().copied().collect();
+    connections.sort();
+
+    for (slot, connection) in connections.into_iter().enumerate() {
+        let Some(client) = server.clients.get_mut(&connection) else {
+            continue;
+        };
+        client.player = commands
+            .spawn((
+                player_bundle(&grid_settings, slot),
+                InputSource::External,
+                DirectionIntent::default(),
+                RemotePlayer { connection },
+                LagCompensation::default(),
+            ))
+            .id();
+        client.inputs.clear();
+
+        send(
+            &mut transport,
+            connection,
+            Channel::Reliable,
+            &ServerMessage::JoinAccepted {
+                player: Some(net_id(client.player)),
+                session: client.session,
+                grid_width: grid_settings.grid_width,
+                grid_height: grid_settings.grid_height,
+                value_zones: grid_settings.value_zones.clone(),
+                game_speed: rules.game_speed,
+                topology: grid_settings.topology,
+                shape: grid_settings.shape.clone(),
+                upper_floor: grid_settings.upper_floor.clone(),
+            },
+        );
+    }
+
+    let observers: Vec<_> = server.observers.keys().copied().collect();
+    for connection in observers {
+        send(
+            &mut transport,
+            connection,
+            Channel::Reliable,
+            &ServerMessage::JoinAccepted {
+                player: None,
+                session: 0,
+                grid_width: grid_settings.grid_width,
+                grid_height: grid_settings.grid_height,
+                value_zones: grid_settings.value_zones.clone(),
+                game_speed: rules.game_speed,
+                topology: grid_settings.topology,
+                shape: grid_settings.shape.clone(),
+                upper_floor: grid_settings.upper_floor.clone(),
+            },
+        );
+    }
+}
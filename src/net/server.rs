@@ -0,0 +1,677 @@
+// Authoritative side of a networked match. The server runs the normal
+// simulation; clients only send their steering and get the results back.
+use crate::components::{GridSettings, Player, Respawning, Tile, Tiles};
+use crate::events::{ClaimComputedEvent, EmoteEvent, PlayerDeathEvent};
+use crate::net::backfill::{backfill_bots_system, take_over_bot, BackfillBot};
+use crate::net::discovery::{
+    lan_beacon_system, master_heartbeat_system, LanBeacon, MasterRegistration,
+};
+use crate::net::protocol::{
+    negotiate, ClientMessage, NetId, PlayerState, RejectReason, ServerMessage, TileUpdate,
+};
+use crate::net::rotation::{
+    map_vote_system, respawn_remote_players_system, start_map_vote_system, MatchRotation, Playlist,
+};
+use crate::net::transport::{Channel, ConnectionId, NetTransport, TransportEvent};
+use crate::player_bundle;
+use crate::resources::{GameRules, GameState};
+use crate::states::{AppState, GameSet};
+use crate::systems::bots::Bot;
+use crate::systems::collision::LagCompensation;
+use crate::systems::countdown::MatchCountdown;
+use crate::systems::input::{DirectionIntent, InputSource};
+use crate::territory::land_color;
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+pub const DEFAULT_MAX_PLAYERS: usize = 8;
+// How long a dropped player is held for their client to reconnect
+pub const RECONNECT_GRACE_SECONDS: f32 = 30.0;
+// Inputs held for a client before the oldest are dropped. A few frames of
+// buffer soak up jitter without adding much delay.
+const MAX_QUEUED_INPUTS: usize = 6;
+// Shortest gap between a client's emotes, any sent sooner are dropped
+pub const EMOTE_COOLDOWN_SECONDS: f32 = 1.0;
+
+// A client that made it through the join handshake
+pub struct RemoteClient {
+    pub player: Entity,
+    pub name: String,
+    // Secret the client quotes to take this player back after a drop
+    pub session: u64,
+    // Newest input tick applied for this client
+    pub last_input: u32,
+    // Inputs received but not played yet, one is played per frame so the
+    // client can replay them the same way when predicting
+    pub inputs: VecDeque<QueuedInput>,
+    // Seconds since the server started, as of the client's last emote
+    pub last_emote: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueuedInput {
+    pub tick: u32,
+    // Newest snapshot the client had when it sent this
+    pub acked_tick: u32,
+    pub direction: Vec2,
+}
+
+// A client that dropped out, whose player is kept around for a while
+pub struct AwayClient {
+    pub client: RemoteClient,
+    pub seconds_left: f32,
+}
+
+#[derive(Resource)]
+pub struct NetServer {
+    // Shown in server browsers
+    pub name: String,
+    // Invite code clients have to join with, if the lobby is locked
+    pub code: Option<String>,
+    pub clients: HashMap<ConnectionId, RemoteClient>,
+    // Dropped clients by session
+    pub away: HashMap<u64, AwayClient>,
+    // Connections watching without a player, by name
+    pub observers: HashMap<ConnectionId, String>,
+    pub max_players: usize,
+    // Bots fill the match up to this many players, 0 for none
+    pub fill_to: usize,
+    pub grace_seconds: f32,
+    pub emote_cooldown: f32,
+    pub tick: u32,
+    // Map picks while a vote is open, by connection
+    pub votes: HashMap<ConnectionId, u32>,
+    // Tile state as of the last delta, row by row
+    sent_tiles: Vec<(Option<NetId>, Option<NetId>)>,
+}
+
+impl NetServer {
+    pub fn new(max_players: usize) -> Self {
+        Self {
+            name: "Landio server".into(),
+            code: None,
+            clients: HashMap::new(),
+            away: HashMap::new(),
+            observers: HashMap::new(),
+            max_players,
+            fill_to: 0,
+            grace_seconds: RECONNECT_GRACE_SECONDS,
+            emote_cooldown: EMOTE_COOLDOWN_SECONDS,
+            tick: 0,
+            votes: HashMap::new(),
+            sent_tiles: Vec::new(),
+        }
+    }
+
+    // Human players in the match, counting the ones held for a reconnect
+    pub fn player_count(&self) -> usize {
+        self.clients.len() + self.away.len()
+    }
+
+    // Connections that joined, as a player or to watch. Match traffic only
+    // goes to these, never to ones still joining or turned away.
+    pub fn peers(&self) -> Vec<ConnectionId> {
+        self.clients
+            .keys()
+            .chain(self.observers.keys())
+            .copied()
+            .collect()
+    }
+}
+
+impl Default for NetServer {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PLAYERS)
+    }
+}
+
+// Player steered by a remote client
+#[derive(Component)]
+pub struct RemotePlayer {
+    pub connection: ConnectionId,
+}
+
+pub fn net_id(entity: Entity) -> NetId {
+    entity.to_bits()
+}
+
+pub(crate) fn send(
+    transport: &mut NetTransport,
+    to: ConnectionId,
+    channel: Channel,
+    message: &ServerMessage,
+) {
+    if let Err(err) = transport.0.send(to, channel, &message.encode()) {
+        println!("Failed to send to connection {}: {}", to, err);
+    }
+}
+
+fn tile_update(tile: &Tile) -> TileUpdate {
+    TileUpdate {
+        x: tile.x,
+        y: tile.y,
+        owner: tile.owner.map(net_id),
+        trail_owner: tile.trail_owner.map(net_id),
+    }
+}
+
+// Runs the server side of a match over whatever `NetTransport` is inserted
+pub struct NetServerPlugin;
+
+impl Plugin for NetServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetServer>()
+            .init_resource::<Playlist>()
+            .init_resource::<MatchRotation>()
+            .add_systems(
+                Update,
+                (
+                    server_receive_system.before(GameSet::Input),
+                    server_send_system.after(GameSet::Claim),
+                )
+                    .run_if(
+                        in_state(AppState::Playing)
+                            .or(in_state(AppState::Intermission))
+                            .and(resource_exists::<NetTransport>),
+                    ),
+            )
+            .add_systems(
+                Update,
+                (
+                    start_map_vote_system
+                        .after(GameSet::Claim)
+                        .run_if(in_state(AppState::Playing)),
+                    map_vote_system
+                        .after(server_receive_system)
+                        .run_if(in_state(AppState::Intermission)),
+                    backfill_bots_system
+                        .after(server_receive_system)
+                        .before(GameSet::Input)
+                        .run_if(in_state(AppState::Playing)),
+                )
+                    .run_if(resource_exists::<NetTransport>),
+            )
+            .add_systems(
+                OnEnter(AppState::Playing),
+                respawn_remote_players_system.run_if(resource_exists::<NetTransport>),
+            )
+            .add_systems(
+                Update,
+                (
+                    lan_beacon_system.run_if(resource_exists::<LanBeacon>),
+                    master_heartbeat_system.run_if(resource_exists::<MasterRegistration>),
+                ),
+            );
+    }
+}
+
+// Hands a player's land back to the map
+pub(crate) fn release_player(
+    commands: &mut Commands,
+    player: Entity,
+    player_query: &Query<&Player>,
+    tiles: &mut Tiles,
+) {
+    commands.entity(player).despawn_recursive();
+
+    for (tile, tile_color) in tiles.iter_mut() {
+        if tile.owner != Some(player) && tile.trail_owner != Some(player) {
+            continue;
+        }
+        if tile.owner == Some(player) {
+            tile.owner = None;
+        }
+        if tile.trail_owner == Some(player) {
+            tile.trail_owner = None;
+        }
+
+        // Land under the trail keeps its owner's color, someone else's trail
+        // stays drawn over the land
+        if tile.trail_owner.is_none() {
+            let owner_color = tile
+                .owner
+                .and_then(|owner| player_query.get(owner).ok())
+                .map(|owner| owner.color);
+            tile_color.0 = land_color(tile, owner_color);
+        }
+    }
+}
+
+// Handles joins, leaves and inputs from clients
+#[allow(clippy::too_many_arguments)]
+pub fn server_receive_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut transport: ResMut<NetTransport>,
+    mut server: ResMut<NetServer>,
+    grid_settings: Res<GridSettings>,
+    rules: Res<GameRules>,
+    mut intent_query: Query<(&mut DirectionIntent, &mut LagCompensation), With<RemotePlayer>>,
+    mut player_query: Query<&mut Player>,
+    mut tiles: ResMut<Tiles>,
+    bot_query: Query<(Entity, &Bot), With<BackfillBot>>,
+    mut emote_events: EventWriter<EmoteEvent>,
+    countdown: Option<Res<MatchCountdown>>,
+) {
+    // Bots handed to a client this frame
+    let mut taken = Vec::new();
+    for event in transport.0.poll() {
+        match event {
+            TransportEvent::Connected(_) => {}
+            TransportEvent::Disconnected(connection) => {
+                if let Some(name) = server.observers.remove(&connection) {
+                    println!("{} stopped watching", name);
+                    continue;
+                }
+                let Some(client) = server.clients.remove(&connection) else {
+                    continue;
+                };
+                println!("{} dropped, holding their player", client.name);
+
+                // Parked on their tile until they come back
+                if let Ok(mut player) = player_query.get_mut(client.player) {
+                    player.direction = Vec2::ZERO;
+                    player.buffered_direction = None;
+                    player.progress = 0.0;
+                }
+                if let Ok((mut intent, _)) = intent_query.get_mut(client.player) {
+                    intent.0 = Vec2::ZERO;
+                }
+                let seconds_left = server.grace_seconds;
+                server.away.insert(
+                    client.session,
+                    AwayClient {
+                        client,
+                        seconds_left,
+                    },
+                );
+            }
+            TransportEvent::Message {
+                from: connection,
+                payload,
+            } => {
+                let message = match ClientMessage::decode(&payload) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        println!("Bad message from connection {}: {}", connection, err);
+                        continue;
+                    }
+                };
+
+                match message {
+                    ClientMessage::JoinRequest {
+                        protocol_version,
+                        name,
+                        resume,
+                        code,
+                        observer,
+                    } => {
+                        if server.clients.contains_key(&connection)
+                            || server.observers.contains_key(&connection)
+                        {
+                            continue;
+                        }
+                        // Observers don't take a player slot, or anyone's player
+                        let resuming = resume
+                            .filter(|_| !observer)
+                            .filter(|session| server.away.contains_key(session));
+                        let verdict = negotiate(protocol_version).and_then(|_| {
+                            if server.code.is_some() && code != server.code {
+                                Err(RejectReason::WrongCode)
+                            } else if observer
+                                || resuming.is_some()
+                                || server.player_count() < server.max_players
+                            {
+                                Ok(())
+                            } else {
+                                Err(RejectReason::ServerFull)
+                            }
+                        });
+                        if let Err(reason) = verdict {
+                            println!("Turned away connection {}: {}", connection, reason);
+                            send(
+                                &mut transport,
+                                connection,
+                                Channel::Reliable,
+                                &ServerMessage::JoinRejected { reason },
+                            );
+                            transport.0.disconnect(connection);
+                            continue;
+                        }
+
+                        // Held players only leave `away` once they're let back in
+                        let resumed = resuming.and_then(|session| server.away.remove(&session));
+                        let watcher = observer.then(|| name.clone());
+                        let client = match resumed {
+                            _ if observer => {
+                                println!("{} is watching", name);
+                                None
+                            }
+                            Some(away) => {
+                                println!("{} is back", away.client.name);
+                                commands
+                                    .entity(away.client.player)
+                                    .insert(RemotePlayer { connection });
+                                Some(RemoteClient {
+                                    // Their tick count may have started over
+                                    last_input: 0,
+                                    inputs: VecDeque::new(),
+                                    ..away.client
+                                })
+                            }
+                            None => {
+                                let bot = bot_query
+                                    .iter()
+                                    .find(|(bot, _)| !taken.contains(bot))
+                                    .map(|(bot, brain)| (bot, brain.applied_speed_scale));
+                                let player = match bot {
+                                    Some((bot, scale)) => {
+                                        if let Ok(mut player) = player_query.get_mut(bot) {
+                                            take_over_bot(
+                                                &mut commands,
+                                                bot,
+                                                scale,
+                                                &mut player,
+                                                connection,
+                                            );
+                                        }
+                                        taken.push(bot);
+                                        println!("{} joined, taking over a bot", name);
+                                        bot
+                                    }
+                                    None => {
+                                        let slot = server.player_count();
+                                        println!("{} joined", name);
+                                        commands
+                                            .spawn((
+                                                player_bundle(&grid_settings, slot),
+                                                InputSource::External,
+                                                DirectionIntent::default(),
+                                                RemotePlayer { connection },
+                                                LagCompensation::default(),
+                                            ))
+                                            .id()
+                                    }
+                                };
+                                Some(RemoteClient {
+                                    player,
+                                    name,
+                                    session: rand::random(),
+                                    last_input: 0,
+                                    inputs: VecDeque::new(),
+                                    last_emote: None,
+                                })
+                            }
+                        };
+                        send(
+                            &mut transport,
+                            connection,
+                            Channel::Reliable,
+                            &ServerMessage::JoinAccepted {
+                                player: client.as_ref().map(|client| net_id(client.player)),
+                                session: client.as_ref().map_or(0, |client| client.session),
+                                grid_width: grid_settings.grid_width,
+                                grid_height: grid_settings.grid_height,
+                                value_zones: grid_settings.value_zones.clone(),
+                                game_speed: rules.game_speed,
+                                topology: grid_settings.topology,
+                                shape: grid_settings.shape.clone(),
+                                upper_floor: grid_settings.upper_floor.clone(),
+                            },
+                        );
+
+                        // Everything claimed so far, later changes come as deltas
+                        let tiles: Vec<TileUpdate> = tiles
+                            .iter()
+                            .filter(|tile| tile.owner.is_some() || tile.trail_owner.is_some())
+                            .map(tile_update)
+                            .collect();
+                        send(
+                            &mut transport,
+                            connection,
+                            Channel::Reliable,
+                            &ServerMessage::TileDelta {
+                                tick: server.tick,
+                                tiles,
+                            },
+                        );
+                        if let Some(countdown) = countdown.as_ref() {
+                            send(
+                                &mut transport,
+                                connection,
+                                Channel::Reliable,
+                                &ServerMessage::Countdown {
+                                    seconds: countdown.timer.remaining_secs(),
+                                },
+                            );
+                        }
+                        match (client, watcher) {
+                            (Some(client), _) => {
+                                server.clients.insert(connection, client);
+                            }
+                            (None, Some(name)) => {
+                                server.observers.insert(connection, name);
+                            }
+                            (None, None) => {}
+                        }
+                    }
+                    ClientMessage::InputTick {
+                        tick,
+                        acked_tick,
+                        direction,
+                    } => {
+                        let Some(client) = server.clients.get_mut(&connection) else {
+                            continue;
+                        };
+                        // Unreliable, so older ticks can turn up late
+                        let newest = client
+                            .inputs
+                            .back()
+                            .map_or(client.last_input, |input| input.tick);
+                        if tick <= newest {
+                            continue;
+                        }
+                        client.inputs.push_back(QueuedInput {
+                            tick,
+                            acked_tick,
+                            direction,
+                        });
+                        if client.inputs.len() > MAX_QUEUED_INPUTS {
+                            client.inputs.pop_front();
+                        }
+                    }
+                    ClientMessage::CastVote { option } => {
+                        if server.clients.contains_key(&connection) {
+                            server.votes.insert(connection, option);
+                        }
+                    }
+                    ClientMessage::SendEmote { emote } => {
+                        let cooldown = server.emote_cooldown;
+                        let Some(client) = server.clients.get_mut(&connection) else {
+                            continue;
+                        };
+                        let now = time.elapsed_secs();
+                        let rested = client.last_emote.is_none_or(|last| now - last >= cooldown);
+                        if !rested {
+                            continue;
+                        }
+                        client.last_emote = Some(now);
+                        emote_events.send(EmoteEvent {
+                            player: client.player,
+                            emote,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // Players nobody came back for are gone for good
+    let mut expired = Vec::new();
+    server.away.retain(|_, away| {
+        away.seconds_left -= time.delta_secs();
+        if away.seconds_left > 0.0 {
+            return true;
+        }
+        expired.push(away.client.player);
+        println!("{} didn't come back", away.client.name);
+        false
+    });
+    for player in expired {
+        release_player(
+            &mut commands,
+            player,
+            &player_query.to_readonly(),
+            &mut tiles,
+        );
+    }
+
+    // Without a new input the player keeps steering the same way
+    let tick = server.tick;
+    for client in server.clients.values_mut() {
+        let Some(input) = client.inputs.pop_front() else {
+            continue;
+        };
+        client.last_input = input.tick;
+        if let Ok((mut intent, mut lag)) = intent_query.get_mut(client.player) {
+            intent.0 = input.direction;
+            lag.frames_behind = tick.saturating_sub(input.acked_tick) as usize;
+        }
+    }
+}
+
+type SnapshotPlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Player,
+        Has<Respawning>,
+        Option<&'static RemotePlayer>,
+    ),
+>;
+
+// Sends everyone the frame's results: positions every tick, and countdowns,
+// tile changes, claims, deaths and emotes as they happen
+#[allow(clippy::too_many_arguments)]
+pub fn server_send_system(
+    mut transport: ResMut<NetTransport>,
+    mut server: ResMut<NetServer>,
+    grid_settings: Res<GridSettings>,
+    player_query: SnapshotPlayerQuery,
+    tiles: Res<Tiles>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    mut claim_events: EventReader<ClaimComputedEvent>,
+    mut emote_events: EventReader<EmoteEvent>,
+    game_state: Res<GameState>,
+    countdown: Option<Res<MatchCountdown>>,
+) {
+    // Set at GO, cleared when the clock runs out
+    let live = game_state.game_running;
+    server.tick = server.tick.wrapping_add(1);
+    let tick = server.tick;
+
+    let width = grid_settings.grid_width.max(0) as usize;
+    let tile_count = width * grid_settings.grid_height.max(0) as usize;
+    if server.sent_tiles.len() != tile_count {
+        server.sent_tiles = vec![(None, None); tile_count];
+    }
+    let mut changed = Vec::new();
+    for tile in tiles.iter() {
+        let Some(sent) = server
+            .sent_tiles
+            .get_mut(tile.y as usize * width + tile.x as usize)
+        else {
+            continue;
+        };
+        let update = tile_update(tile);
+        if *sent != (update.owner, update.trail_owner) {
+            *sent = (update.owner, update.trail_owner);
+            changed.push(update);
+        }
+    }
+
+    let mut reliable = Vec::new();
+    if !changed.is_empty() {
+        reliable.push(ServerMessage::TileDelta {
+            tick,
+            tiles: changed,
+        });
+    }
+    reliable.extend(claim_events.read().map(|event| ServerMessage::ClaimResult {
+        player: net_id(event.player),
+        tiles: (event.trail_tiles.len() + event.pockets.iter().map(Vec::len).sum::<usize>()) as u32,
+    }));
+    reliable.extend(death_events.read().map(|event| ServerMessage::DeathNotice {
+        player: net_id(event.player_entity),
+        killer: event.killer.map(net_id),
+        tile: event.tile,
+    }));
+    reliable.extend(emote_events.read().map(|event| ServerMessage::Emote {
+        player: net_id(event.player),
+        emote: event.emote,
+    }));
+    let peers = server.peers();
+    for message in reliable.iter() {
+        for &connection in peers.iter() {
+            send(&mut transport, connection, Channel::Reliable, message);
+        }
+    }
+
+    let players: Vec<PlayerState> = player_query
+        .iter()
+        .map(|(entity, player, respawning, remote)| {
+            let color = player.color.to_srgba();
+            let stats = remote
+                .and_then(|remote| transport.0.stats(remote.connection))
+                .unwrap_or_default();
+            PlayerState {
+                player: net_id(entity),
+                tile: player.last_tile_pos,
+                progress: player.progress,
+                direction: player.direction,
+                buffered_direction: player.buffered_direction,
+                speed: player.speed,
+                score: player.score,
+                drawing_trail: player.is_drawing_trail,
+                alive: !respawning,
+                color: [color.red, color.green, color.blue].map(|channel| (channel * 255.0) as u8),
+                ping_ms: stats
+                    .rtt
+                    .map(|rtt| rtt.as_millis().min(u16::MAX as u128) as u16),
+                loss: (stats.loss * 100.0).round() as u8,
+            }
+        })
+        .collect();
+    let server = &*server;
+
+    // Anyone joining later is sent what's left of it along with their welcome
+    if let Some(countdown) = countdown.filter(|countdown| countdown.is_added()) {
+        let message = ServerMessage::Countdown {
+            seconds: countdown.timer.remaining_secs(),
+        };
+        for &connection in peers.iter() {
+            send(&mut transport, connection, Channel::Reliable, &message);
+        }
+    }
+
+    let observers = server.observers.keys().map(|&connection| (connection, 0));
+    for (connection, last_input) in server
+        .clients
+        .iter()
+        .map(|(&connection, client)| (connection, client.last_input))
+        .chain(observers)
+    {
+        send(
+            &mut transport,
+            connection,
+            Channel::Unreliable,
+            &ServerMessage::Snapshot {
+                tick,
+                last_input,
+                live,
+                players: players.clone(),
+            },
+        );
+    }
+}
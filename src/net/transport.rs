@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+use std::io;
+use std::time::Duration;
+
+// Peer on the other end of a transport. Clients see the server as
+// `SERVER_CONNECTION`; servers number clients as they connect.
+pub type ConnectionId = u64;
+
+pub const SERVER_CONNECTION: ConnectionId = 0;
+
+// How hard a transport has to try to get a message through
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    // Delivered once and in order, resent until acknowledged
+    Reliable,
+    // Fire and forget, may be dropped or arrive out of order. Used for state
+    // that is superseded every tick.
+    Unreliable,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransportEvent {
+    Connected(ConnectionId),
+    Disconnected(ConnectionId),
+    Message {
+        from: ConnectionId,
+        payload: Vec<u8>,
+    },
+}
+
+// Link quality to a peer, as far as the transport can tell
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConnectionStats {
+    pub rtt: Option<Duration>,
+    // Share of packets that didn't make it, 0 to 1
+    pub loss: f32,
+}
+
+// Moves whole messages between peers. Framing, reliability and keepalives
+// are each transport's own business; callers only see message payloads.
+pub trait Transport: Send + Sync + 'static {
+    fn send(&mut self, to: ConnectionId, channel: Channel, payload: &[u8]) -> io::Result<()>;
+
+    // Reads whatever arrived since the last poll, and does housekeeping like
+    // resends and timeouts. Must be called regularly, once a frame is fine.
+    fn poll(&mut self) -> Vec<TransportEvent>;
+
+    fn disconnect(&mut self, connection: ConnectionId);
+
+    fn connections(&self) -> Vec<ConnectionId>;
+
+    // Transports that don't track their links report nothing
+    fn stats(&self, _connection: ConnectionId) -> Option<ConnectionStats> {
+        None
+    }
+
+    fn broadcast(&mut self, channel: Channel, payload: &[u8]) {
+        for connection in self.connections() {
+            if let Err(err) = self.send(connection, channel, payload) {
+                println!("Failed to send to connection {}: {}", connection, err);
+            }
+        }
+    }
+}
+
+// The transport a networked game talks through
+#[derive(Resource)]
+pub struct NetTransport(pub Box<dyn Transport>);
+
+impl NetTransport {
+    pub fn new(transport: impl Transport) -> Self {
+        Self(Box::new(transport))
+    }
+}
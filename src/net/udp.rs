@@ -0,0 +1,475 @@
+use crate::net::transport::{
+    Channel, ConnectionId, ConnectionStats, Transport, TransportEvent, SERVER_CONNECTION,
+};
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+// Every datagram starts with this so stray traffic is ignored
+const PROTOCOL_ID: [u8; 2] = *b"LD";
+// Largest message either channel takes
+pub const MAX_PAYLOAD: usize = 60_000;
+// Most of a message that goes in one datagram, so nothing leans on IP
+// fragmentation: with headers it fits the 1280 byte IPv6 minimum MTU. Bigger
+// reliable messages are sent in pieces.
+const MAX_FRAGMENT: usize = 1_150;
+// Protocol id, kind, sequence and the more-pieces flag
+const MAX_DATAGRAM: usize = MAX_FRAGMENT + 6;
+// Reliable messages that can be unacked or waiting on a missing one, and the
+// bytes they can take up, before the peer is dropped rather than buffered for
+const RELIABLE_WINDOW: usize = 512;
+const MAX_BUFFERED: usize = 512 * 1024;
+
+const CONNECT_INTERVAL: Duration = Duration::from_millis(250);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(5);
+const MIN_RESEND: Duration = Duration::from_millis(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum PacketKind {
+    Connect = 1,
+    Accept = 2,
+    Disconnect = 3,
+    Heartbeat = 4,
+    Unreliable = 5,
+    Reliable = 6,
+    Ack = 7,
+}
+
+impl PacketKind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            1 => PacketKind::Connect,
+            2 => PacketKind::Accept,
+            3 => PacketKind::Disconnect,
+            4 => PacketKind::Heartbeat,
+            5 => PacketKind::Unreliable,
+            6 => PacketKind::Reliable,
+            7 => PacketKind::Ack,
+            _ => return None,
+        })
+    }
+}
+
+fn packet(kind: PacketKind, sequence: Option<u16>, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(payload.len() + 5);
+    packet.extend_from_slice(&PROTOCOL_ID);
+    packet.push(kind as u8);
+    if let Some(sequence) = sequence {
+        packet.extend_from_slice(&sequence.to_be_bytes());
+    }
+    packet.extend_from_slice(payload);
+    packet
+}
+
+// Reliable message, or piece of one, waiting for its ack
+struct InFlight {
+    packet: Vec<u8>,
+    first_sent: Instant,
+    last_sent: Instant,
+    resent: bool,
+}
+
+struct Peer {
+    id: ConnectionId,
+    addr: SocketAddr,
+    // Clients count as connected once the server accepts them
+    connected: bool,
+    last_heard: Instant,
+    last_sent: Instant,
+    next_send_sequence: u16,
+    in_flight: BTreeMap<u16, InFlight>,
+    next_receive_sequence: u16,
+    // Reliable pieces that arrived ahead of a missing one
+    out_of_order: BTreeMap<u16, Vec<u8>>,
+    // Pieces of a reliable message still coming in
+    partial: Vec<u8>,
+    // Left too many of our reliable messages unacked, dropped at the next poll
+    stalled: bool,
+    // Smoothed round trip, measured from acks of messages sent once
+    rtt: Option<Duration>,
+    // Smoothed share of reliable messages that needed a resend
+    loss: f32,
+}
+
+impl Peer {
+    fn new(id: ConnectionId, addr: SocketAddr, connected: bool) -> Self {
+        let now = Instant::now();
+        Self {
+            id,
+            addr,
+            connected,
+            last_heard: now,
+            last_sent: now,
+            next_send_sequence: 0,
+            in_flight: BTreeMap::new(),
+            next_receive_sequence: 0,
+            out_of_order: BTreeMap::new(),
+            partial: Vec::new(),
+            stalled: false,
+            rtt: None,
+            loss: 0.0,
+        }
+    }
+
+    fn resend_after(&self) -> Duration {
+        self.rtt.map_or(Duration::from_millis(200), |rtt| {
+            (rtt * 3 / 2).max(MIN_RESEND)
+        })
+    }
+
+    // Hands back reliable messages that are now whole and in order. Errors
+    // if the peer sends further ahead or more than it's allowed to.
+    fn receive_reliable(&mut self, sequence: u16, piece: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        let ahead = sequence.wrapping_sub(self.next_receive_sequence);
+        // Anything "behind" the next expected sequence is a duplicate
+        if ahead >= u16::MAX / 2 {
+            return Ok(Vec::new());
+        }
+        let buffered: usize = self.out_of_order.values().map(Vec::len).sum();
+        if ahead as usize >= RELIABLE_WINDOW
+            || buffered + self.partial.len() + piece.len() > MAX_BUFFERED
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "reliable window overrun",
+            ));
+        }
+        self.out_of_order.insert(sequence, piece.to_vec());
+
+        let mut ready = Vec::new();
+        while let Some(piece) = self.out_of_order.remove(&self.next_receive_sequence) {
+            self.next_receive_sequence = self.next_receive_sequence.wrapping_add(1);
+            let Some((&more, data)) = piece.split_first() else {
+                continue;
+            };
+            if self.partial.len() + data.len() > MAX_PAYLOAD {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "reliable message too large",
+                ));
+            }
+            self.partial.extend_from_slice(data);
+            if more == 0 {
+                ready.push(std::mem::take(&mut self.partial));
+            }
+        }
+        Ok(ready)
+    }
+}
+
+// Plain UDP with a thin reliability layer: sequenced, acked and resent
+// reliable messages delivered in order, plus raw unreliable datagrams
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peers: Vec<Peer>,
+    is_server: bool,
+    next_id: ConnectionId,
+}
+
+impl UdpTransport {
+    // Listens for clients on `addr`
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            peers: Vec::new(),
+            is_server: true,
+            next_id: SERVER_CONNECTION + 1,
+        })
+    }
+
+    // Starts connecting to a server, `Connected` is reported once it answers
+    pub fn connect(server: impl ToSocketAddrs) -> io::Result<Self> {
+        let server = server
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no server address"))?;
+        let local: SocketAddr = if server.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        let mut transport = Self {
+            socket,
+            peers: vec![Peer::new(SERVER_CONNECTION, server, false)],
+            is_server: false,
+            next_id: SERVER_CONNECTION + 1,
+        };
+        transport.send_raw(0, &packet(PacketKind::Connect, None, &[]));
+        Ok(transport)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    // Smoothed round trip time to a peer, once one has been measured
+    pub fn rtt(&self, connection: ConnectionId) -> Option<Duration> {
+        self.peers
+            .iter()
+            .find(|peer| peer.id == connection)
+            .and_then(|peer| peer.rtt)
+    }
+
+    fn send_raw(&mut self, index: usize, packet: &[u8]) {
+        let peer = &mut self.peers[index];
+        peer.last_sent = Instant::now();
+        // Losing a datagram is normal for UDP, reliability covers it
+        let _ = self.socket.send_to(packet, peer.addr);
+    }
+
+    fn drop_peer(&mut self, index: usize, events: &mut Vec<TransportEvent>) {
+        self.send_raw(index, &packet(PacketKind::Disconnect, None, &[]));
+        let peer = self.peers.remove(index);
+        if peer.connected {
+            events.push(TransportEvent::Disconnected(peer.id));
+        }
+    }
+
+    fn handle_packet(&mut self, from: SocketAddr, data: &[u8], events: &mut Vec<TransportEvent>) {
+        if data.len() < 3 || data.len() > MAX_DATAGRAM || data[..2] != PROTOCOL_ID {
+            return;
+        }
+        let Some(kind) = PacketKind::from_byte(data[2]) else {
+            return;
+        };
+        let body = &data[3..];
+
+        let index = match self.peers.iter().position(|peer| peer.addr == from) {
+            Some(index) => index,
+            None if self.is_server && kind == PacketKind::Connect => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.peers.push(Peer::new(id, from, true));
+                events.push(TransportEvent::Connected(id));
+                self.peers.len() - 1
+            }
+            None => return,
+        };
+        let peer = &mut self.peers[index];
+        peer.last_heard = Instant::now();
+        // Anything from the server means it accepted us, even if the accept
+        // itself got lost
+        if !peer.connected && kind != PacketKind::Disconnect {
+            peer.connected = true;
+            events.push(TransportEvent::Connected(peer.id));
+        }
+
+        match kind {
+            // Also answers repeats, in case our accept got lost
+            PacketKind::Connect => {
+                self.send_raw(index, &packet(PacketKind::Accept, None, &[]));
+            }
+            PacketKind::Disconnect => {
+                let peer = self.peers.remove(index);
+                if peer.connected {
+                    events.push(TransportEvent::Disconnected(peer.id));
+                }
+            }
+            PacketKind::Accept | PacketKind::Heartbeat => {}
+            PacketKind::Unreliable => {
+                events.push(TransportEvent::Message {
+                    from: self.peers[index].id,
+                    payload: body.to_vec(),
+                });
+            }
+            PacketKind::Reliable if body.len() >= 2 => {
+                let sequence = u16::from_be_bytes([body[0], body[1]]);
+                self.send_raw(index, &packet(PacketKind::Ack, Some(sequence), &[]));
+                let peer = &mut self.peers[index];
+                let from = peer.id;
+                match peer.receive_reliable(sequence, &body[2..]) {
+                    Ok(ready) => events.extend(
+                        ready
+                            .into_iter()
+                            .map(|payload| TransportEvent::Message { from, payload }),
+                    ),
+                    Err(err) => {
+                        println!("Dropping connection {}: {}", from, err);
+                        self.drop_peer(index, events);
+                    }
+                }
+            }
+            PacketKind::Ack if body.len() >= 2 => {
+                let sequence = u16::from_be_bytes([body[0], body[1]]);
+                let peer = &mut self.peers[index];
+                if let Some(in_flight) = peer.in_flight.remove(&sequence) {
+                    let lost = if in_flight.resent { 1.0 } else { 0.0 };
+                    peer.loss = (peer.loss * 7.0 + lost) / 8.0;
+                    if !in_flight.resent {
+                        let sample = in_flight.first_sent.elapsed();
+                        peer.rtt = Some(match peer.rtt {
+                            Some(rtt) => (rtt * 7 + sample) / 8,
+                            None => sample,
+                        });
+                    }
+                }
+            }
+            PacketKind::Reliable | PacketKind::Ack => {}
+        }
+    }
+}
+
+impl Transport for UdpTransport {
+    fn stats(&self, connection: ConnectionId) -> Option<ConnectionStats> {
+        let peer = self.peers.iter().find(|peer| peer.id == connection)?;
+        Some(ConnectionStats {
+            rtt: peer.rtt,
+            loss: peer.loss,
+        })
+    }
+
+    fn send(&mut self, to: ConnectionId, channel: Channel, payload: &[u8]) -> io::Result<()> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "message too large",
+            ));
+        }
+        let Some(index) = self
+            .peers
+            .iter()
+            .position(|peer| peer.id == to && peer.connected)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "unknown connection",
+            ));
+        };
+
+        // Unreliable messages too big for one datagram go reliably, in pieces
+        if channel == Channel::Unreliable && payload.len() <= MAX_FRAGMENT {
+            self.send_raw(index, &packet(PacketKind::Unreliable, None, payload));
+            return Ok(());
+        }
+
+        // A peer that stopped acking isn't buffered for without limit
+        let peer = &mut self.peers[index];
+        let pieces: Vec<&[u8]> = match payload.len() {
+            0 => vec![&[]],
+            _ => payload.chunks(MAX_FRAGMENT).collect(),
+        };
+        let unacked: usize = peer.in_flight.values().map(|sent| sent.packet.len()).sum();
+        if peer.in_flight.len() + pieces.len() > RELIABLE_WINDOW
+            || unacked + payload.len() > MAX_BUFFERED
+        {
+            peer.stalled = true;
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "too many unacked messages",
+            ));
+        }
+
+        let now = Instant::now();
+        let last = pieces.len() - 1;
+        for (i, piece) in pieces.into_iter().enumerate() {
+            let peer = &mut self.peers[index];
+            let sequence = peer.next_send_sequence;
+            peer.next_send_sequence = sequence.wrapping_add(1);
+
+            let mut body = Vec::with_capacity(piece.len() + 1);
+            body.push((i < last) as u8);
+            body.extend_from_slice(piece);
+            let packet = packet(PacketKind::Reliable, Some(sequence), &body);
+            peer.in_flight.insert(
+                sequence,
+                InFlight {
+                    packet: packet.clone(),
+                    first_sent: now,
+                    last_sent: now,
+                    resent: false,
+                },
+            );
+            self.send_raw(index, &packet);
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        let mut events = Vec::new();
+        // One byte spare so oversized datagrams show up as such
+        let mut buffer = vec![0; MAX_DATAGRAM + 1];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, from)) => {
+                    let data = buffer[..len].to_vec();
+                    self.handle_packet(from, &data, &mut events);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                // ICMP errors from a peer that went away show up here on
+                // some platforms, the timeout deals with them
+                Err(_) => break,
+            }
+        }
+
+        let now = Instant::now();
+        let mut index = 0;
+        while index < self.peers.len() {
+            let peer = &self.peers[index];
+            if peer.stalled {
+                println!("Dropping connection {}: too many unacked messages", peer.id);
+                self.drop_peer(index, &mut events);
+                continue;
+            }
+            if now.duration_since(peer.last_heard) > TIMEOUT {
+                let peer = self.peers.remove(index);
+                if peer.connected {
+                    events.push(TransportEvent::Disconnected(peer.id));
+                }
+                continue;
+            }
+
+            if !peer.connected {
+                if now.duration_since(peer.last_sent) > CONNECT_INTERVAL {
+                    self.send_raw(index, &packet(PacketKind::Connect, None, &[]));
+                }
+                index += 1;
+                continue;
+            }
+
+            let resend_after = peer.resend_after();
+            let due: Vec<Vec<u8>> = self.peers[index]
+                .in_flight
+                .values_mut()
+                .filter(|in_flight| now.duration_since(in_flight.last_sent) > resend_after)
+                .map(|in_flight| {
+                    in_flight.last_sent = now;
+                    in_flight.resent = true;
+                    in_flight.packet.clone()
+                })
+                .collect();
+            for packet in due {
+                self.send_raw(index, &packet);
+            }
+
+            if now.duration_since(self.peers[index].last_sent) > HEARTBEAT_INTERVAL {
+                self.send_raw(index, &packet(PacketKind::Heartbeat, None, &[]));
+            }
+            index += 1;
+        }
+
+        events
+    }
+
+    fn disconnect(&mut self, connection: ConnectionId) {
+        if let Some(index) = self.peers.iter().position(|peer| peer.id == connection) {
+            self.send_raw(index, &packet(PacketKind::Disconnect, None, &[]));
+            self.peers.remove(index);
+        }
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        self.peers
+            .iter()
+            .filter(|peer| peer.connected)
+            .map(|peer| peer.id)
+            .collect()
+    }
+}
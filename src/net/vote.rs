@@ -0,0 +1,130 @@
+// Vote screen shown to clients between matches on a dedicated server. The
+// number keys pick a map, and the tally updates as others vote.
+use crate::net::client::{send, Ballot, NetClient};
+use crate::net::protocol::ClientMessage;
+use crate::net::transport::{Channel, NetTransport};
+use bevy::prelude::*;
+
+const VOTE_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+#[derive(Component)]
+pub struct VoteScreen;
+
+// Runs the vote clock down and sends our pick when a number key goes down
+pub fn cast_vote_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut transport: ResMut<NetTransport>,
+    mut client: ResMut<NetClient>,
+    time: Res<Time>,
+) {
+    // Observers get to watch the vote, not take part
+    let observer = client.observer;
+    let Some(ballot) = client.ballot.as_mut() else {
+        return;
+    };
+    ballot.seconds_left = (ballot.seconds_left - time.delta_secs()).max(0.0);
+    if observer {
+        return;
+    }
+
+    let Some(option) = VOTE_KEYS
+        .iter()
+        .take(ballot.options.len())
+        .position(|key| keyboard_input.just_pressed(*key))
+    else {
+        return;
+    };
+    ballot.choice = Some(option as u32);
+    send(
+        &mut transport,
+        Channel::Reliable,
+        &ClientMessage::CastVote {
+            option: option as u32,
+        },
+    );
+}
+
+// Redraws the vote screen whenever what it shows changes
+pub fn vote_screen_system(
+    mut commands: Commands,
+    client: Res<NetClient>,
+    screen_query: Query<Entity, With<VoteScreen>>,
+    // Ballot as last drawn, with the countdown in whole seconds
+    mut shown: Local<Option<Ballot>>,
+) {
+    let ballot = client.ballot.clone().map(|ballot| Ballot {
+        seconds_left: ballot.seconds_left.ceil(),
+        ..ballot
+    });
+    if *shown == ballot {
+        return;
+    }
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *shown = ballot;
+    let Some(ballot) = shown.as_ref() else {
+        return;
+    };
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            VoteScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Text::new("Vote for the next map"),
+                TextFont::from_font_size(32.0),
+            ));
+
+            for (option, name) in ballot.options.iter().enumerate() {
+                let votes = ballot.votes.get(option).copied().unwrap_or(0);
+                let color = if ballot.choice == Some(option as u32) {
+                    Color::srgb(1.0, 0.85, 0.3)
+                } else {
+                    Color::WHITE
+                };
+                screen.spawn((
+                    Text::new(format!(
+                        "{} - {} ({} vote{})",
+                        option + 1,
+                        name,
+                        votes,
+                        if votes == 1 { "" } else { "s" }
+                    )),
+                    TextFont::from_font_size(18.0),
+                    TextColor(color),
+                ));
+            }
+
+            screen.spawn((
+                Text::new(format!(
+                    "Press a number to vote, next match in {}s",
+                    ballot.seconds_left
+                )),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+        });
+}
@@ -0,0 +1,337 @@
+// Transports for browser builds, which can't open sockets of their own and
+// go through the page's WebSocket and WebTransport instead. Those report
+// through callbacks and promises, which queue events for `poll` to hand out
+// the way the native transports do.
+use crate::net::transport::{Channel, ConnectionId, Transport, TransportEvent, SERVER_CONNECTION};
+use js_sys::{Array, ArrayBuffer, Reflect, Uint8Array};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::io;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    BinaryType, MessageEvent, ReadableStream, ReadableStreamDefaultReader, WebSocket, WebTransport,
+    WebTransportBidirectionalStream, WebTransportHash, WebTransportOptions,
+    WritableStreamDefaultWriter,
+};
+
+type Inbox = Rc<RefCell<VecDeque<TransportEvent>>>;
+
+fn js_error(err: JsValue) -> io::Error {
+    io::Error::other(err.as_string().unwrap_or_else(|| format!("{:?}", err)))
+}
+
+fn not_connected() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "not connected")
+}
+
+// The page's query string as command line flags, so
+// `?connect=example.com:7778&websocket&name=Ann` works like
+// `--connect example.com:7778 --websocket --name Ann`
+pub fn page_args() -> Vec<String> {
+    let search = web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .unwrap_or_default();
+    let decode = |text: &str| {
+        js_sys::decode_uri_component(&text.replace('+', " "))
+            .map(String::from)
+            .unwrap_or_else(|_| text.to_string())
+    };
+
+    // Stands in for the program name
+    let mut args = vec![String::new()];
+    for pair in search.trim_start_matches('?').split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        args.push(format!("--{}", decode(key)));
+        if !value.is_empty() {
+            args.push(decode(value));
+        }
+    }
+    args
+}
+
+// Callback that queues whatever event `make` turns the page's event into
+fn queue(
+    inbox: &Inbox,
+    make: impl Fn(JsValue) -> Option<TransportEvent> + 'static,
+) -> Closure<dyn FnMut(JsValue)> {
+    let inbox = inbox.clone();
+    Closure::new(move |event: JsValue| {
+        if let Some(event) = make(event) {
+            inbox.borrow_mut().push_back(event);
+        }
+    })
+}
+
+// Client of a `WebSocketTransport` server
+pub struct WebSocketClient {
+    socket: WebSocket,
+    inbox: Inbox,
+    // Kept alive for as long as the socket can call them
+    _callbacks: [Closure<dyn FnMut(JsValue)>; 3],
+}
+
+// Browsers run the game on a single thread, so the page objects are never
+// actually shared between threads
+unsafe impl Send for WebSocketClient {}
+unsafe impl Sync for WebSocketClient {}
+
+impl WebSocketClient {
+    // Opens `url`, `Connected` is reported once the server accepts
+    pub fn connect(url: &str) -> io::Result<Self> {
+        let socket = WebSocket::new(url).map_err(js_error)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let inbox = Inbox::default();
+        let opened = Rc::new(Cell::new(false));
+        let on_open = {
+            let opened = opened.clone();
+            queue(&inbox, move |_| {
+                opened.set(true);
+                Some(TransportEvent::Connected(SERVER_CONNECTION))
+            })
+        };
+        let on_message = queue(&inbox, |event| {
+            let data = event.unchecked_into::<MessageEvent>().data();
+            let buffer = data.dyn_ref::<ArrayBuffer>()?;
+            Some(TransportEvent::Message {
+                from: SERVER_CONNECTION,
+                payload: Uint8Array::new(buffer).to_vec(),
+            })
+        });
+        // Failing to connect at all isn't a disconnect
+        let on_close = queue(&inbox, move |_| {
+            opened
+                .replace(false)
+                .then_some(TransportEvent::Disconnected(SERVER_CONNECTION))
+        });
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            socket,
+            inbox,
+            _callbacks: [on_open, on_message, on_close],
+        })
+    }
+
+    fn is_open(&self) -> bool {
+        self.socket.ready_state() == WebSocket::OPEN
+    }
+}
+
+impl Drop for WebSocketClient {
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+impl Transport for WebSocketClient {
+    fn send(&mut self, _to: ConnectionId, _channel: Channel, payload: &[u8]) -> io::Result<()> {
+        if !self.is_open() {
+            return Err(not_connected());
+        }
+        self.socket.send_with_u8_array(payload).map_err(js_error)
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        self.inbox.borrow_mut().drain(..).collect()
+    }
+
+    fn disconnect(&mut self, _connection: ConnectionId) {
+        let _ = self.socket.close();
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        if self.is_open() {
+            vec![SERVER_CONNECTION]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+struct Writers {
+    // Reliable messages, each behind a u32 length like the server expects
+    messages: WritableStreamDefaultWriter,
+    datagrams: WritableStreamDefaultWriter,
+}
+
+// Client of a `WebTransportTransport` server. Unreliable messages go as
+// datagrams and reliable ones on one bidirectional stream.
+pub struct WebTransportClient {
+    transport: WebTransport,
+    inbox: Inbox,
+    // Set while the session is open
+    writers: Rc<RefCell<Option<Writers>>>,
+}
+
+// Browsers run the game on a single thread, so the page objects are never
+// actually shared between threads
+unsafe impl Send for WebTransportClient {}
+unsafe impl Sync for WebTransportClient {}
+
+impl WebTransportClient {
+    // Opens a session with the server at `url`, trusting the self-signed
+    // certificate with this SHA-256 hash. `Connected` is reported once the
+    // session is open.
+    pub fn connect(url: &str, certificate_hash: [u8; 32]) -> io::Result<Self> {
+        let hash = WebTransportHash::new();
+        hash.set_algorithm("sha-256");
+        hash.set_value(&Uint8Array::from(&certificate_hash[..]));
+        let options = WebTransportOptions::new();
+        options.set_server_certificate_hashes(&Array::of1(&hash));
+        let transport = WebTransport::new_with_options(url, &options).map_err(js_error)?;
+
+        let inbox = Inbox::default();
+        let writers = Rc::new(RefCell::new(None));
+        spawn_local(run_session(
+            transport.clone(),
+            inbox.clone(),
+            writers.clone(),
+        ));
+        Ok(Self {
+            transport,
+            inbox,
+            writers,
+        })
+    }
+}
+
+// Reads a stream until it ends, handing over each chunk
+async fn read_stream(stream: ReadableStream, mut chunk: impl FnMut(Vec<u8>)) {
+    let reader: ReadableStreamDefaultReader = stream.get_reader().unchecked_into();
+    while let Ok(result) = JsFuture::from(reader.read()).await {
+        let done = Reflect::get(&result, &"done".into())
+            .ok()
+            .and_then(|done| done.as_bool())
+            .unwrap_or(true);
+        if done {
+            return;
+        }
+        let Ok(value) = Reflect::get(&result, &"value".into()) else {
+            return;
+        };
+        chunk(value.unchecked_into::<Uint8Array>().to_vec());
+    }
+}
+
+async fn run_session(transport: WebTransport, inbox: Inbox, writers: Rc<RefCell<Option<Writers>>>) {
+    let opened = async {
+        JsFuture::from(transport.ready()).await?;
+        let stream: WebTransportBidirectionalStream =
+            JsFuture::from(transport.create_bidirectional_stream())
+                .await?
+                .unchecked_into();
+        let messages = stream.writable().get_writer()?;
+        let datagrams = transport.datagrams().writable().get_writer()?;
+        Ok::<_, JsValue>((ReadableStream::from(stream.readable()), messages, datagrams))
+    }
+    .await;
+    // Failing to open at all isn't a disconnect
+    let Ok((readable, messages, datagrams)) = opened else {
+        return;
+    };
+    *writers.borrow_mut() = Some(Writers {
+        messages,
+        datagrams,
+    });
+    inbox
+        .borrow_mut()
+        .push_back(TransportEvent::Connected(SERVER_CONNECTION));
+
+    let datagram_inbox = inbox.clone();
+    spawn_local(read_stream(
+        transport.datagrams().readable(),
+        move |payload| {
+            datagram_inbox
+                .borrow_mut()
+                .push_back(TransportEvent::Message {
+                    from: SERVER_CONNECTION,
+                    payload,
+                });
+        },
+    ));
+
+    let mut buffer = Vec::new();
+    read_stream(readable, |chunk| {
+        buffer.extend(chunk);
+        while buffer.len() >= 4 {
+            let len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+            if buffer.len() < 4 + len {
+                break;
+            }
+            let payload = buffer.drain(..4 + len).skip(4).collect();
+            inbox.borrow_mut().push_back(TransportEvent::Message {
+                from: SERVER_CONNECTION,
+                payload,
+            });
+        }
+    })
+    .await;
+
+    // The message stream only ends with the session
+    if writers.borrow_mut().take().is_some() {
+        inbox
+            .borrow_mut()
+            .push_back(TransportEvent::Disconnected(SERVER_CONNECTION));
+    }
+}
+
+impl Drop for WebTransportClient {
+    fn drop(&mut self) {
+        self.writers.borrow_mut().take();
+        self.transport.close();
+    }
+}
+
+impl Transport for WebTransportClient {
+    fn send(&mut self, _to: ConnectionId, channel: Channel, payload: &[u8]) -> io::Result<()> {
+        let writers = self.writers.borrow();
+        let Some(writers) = writers.as_ref() else {
+            return Err(not_connected());
+        };
+        // Messages too big for one datagram go reliably instead
+        let fits = payload.len() <= self.transport.datagrams().max_datagram_size() as usize;
+        // Writes resolve later, and a session that dies meanwhile is reported
+        // by the message stream ending
+        if channel == Channel::Unreliable && fits {
+            let _ = writers
+                .datagrams
+                .write_with_chunk(&Uint8Array::from(payload));
+        } else {
+            let mut framed = (payload.len() as u32).to_be_bytes().to_vec();
+            framed.extend_from_slice(payload);
+            let _ = writers
+                .messages
+                .write_with_chunk(&Uint8Array::from(&framed[..]));
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        self.inbox.borrow_mut().drain(..).collect()
+    }
+
+    fn disconnect(&mut self, _connection: ConnectionId) {
+        self.writers.borrow_mut().take();
+        self.transport.close();
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        if self.writers.borrow().is_some() {
+            vec![SERVER_CONNECTION]
+        } else {
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,426 @@
+// WebSocket transport, so browser builds can talk to the same servers as
+// desktop ones. Runs over TCP, so both channels are delivered reliably and in
+// order. Browsers that have WebTransport can use `webtransport` instead.
+use crate::net::transport::{Channel, ConnectionId, Transport, TransportEvent, SERVER_CONNECTION};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// Refuse frames and fragmented messages bigger than this, and HTTP heads
+// longer than MAX_HEAD, rather than buffering without limit
+const MAX_FRAME: usize = 1 << 20;
+const MAX_HEAD: usize = 8 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+struct Socket {
+    id: ConnectionId,
+    stream: TcpStream,
+    // Still waiting for the HTTP upgrade to finish
+    handshaking: bool,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    // Pieces of a fragmented message
+    fragments: Vec<u8>,
+    // What a client expects the server's Sec-WebSocket-Accept to be
+    accept: Option<String>,
+    closed: bool,
+}
+
+impl Socket {
+    fn new(id: ConnectionId, stream: TcpStream) -> Self {
+        Self {
+            id,
+            stream,
+            handshaking: true,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            fragments: Vec::new(),
+            accept: None,
+            closed: false,
+        }
+    }
+
+    fn read_available(&mut self) {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(len) => self.incoming.extend_from_slice(&buffer[..len]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(len) => {
+                    self.outgoing.drain(..len);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    // The HTTP header block, once all of it has arrived
+    fn take_http_head(&mut self) -> io::Result<Option<String>> {
+        let end = self
+            .incoming
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n");
+        let too_long = || io::Error::new(io::ErrorKind::InvalidData, "HTTP head too long");
+        let Some(end) = end else {
+            if self.incoming.len() > MAX_HEAD {
+                return Err(too_long());
+            }
+            return Ok(None);
+        };
+        if end + 4 > MAX_HEAD {
+            return Err(too_long());
+        }
+        let head: Vec<u8> = self.incoming.drain(..end + 4).collect();
+        Ok(Some(String::from_utf8_lossy(&head).into_owned()))
+    }
+}
+
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+// Value the server answers a client's Sec-WebSocket-Key with
+pub fn accept_key(key: &str) -> String {
+    BASE64.encode(Sha1::digest(format!("{}{}", key, HANDSHAKE_GUID)))
+}
+
+fn encode_frame(opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len if len < 126 => frame.push(mask_bit | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    match mask {
+        Some(key) => {
+            frame.extend_from_slice(&key);
+            frame.extend(
+                payload
+                    .iter()
+                    .enumerate()
+                    .map(|(i, byte)| byte ^ key[i % 4]),
+            );
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    frame
+}
+
+// Next whole frame in `buffer` as (fin, opcode, payload), removing it. Frames
+// from clients have to be masked and frames from servers must not be.
+fn decode_frame(
+    buffer: &mut Vec<u8>,
+    from_client: bool,
+) -> io::Result<Option<(bool, u8, Vec<u8>)>> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+    let fin = buffer[0] & 0x80 != 0;
+    let opcode = buffer[0] & 0x0F;
+    let masked = buffer[1] & 0x80 != 0;
+    if masked != from_client {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame masking doesn't match the sender",
+        ));
+    }
+
+    let (len, mut offset) = match buffer[1] & 0x7F {
+        126 if buffer.len() >= 4 => (u16::from_be_bytes([buffer[2], buffer[3]]) as usize, 4),
+        127 if buffer.len() >= 10 => {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&buffer[2..10]);
+            (u64::from_be_bytes(bytes) as usize, 10)
+        }
+        126 | 127 => return Ok(None),
+        len => (len as usize, 2),
+    };
+    if len > MAX_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+
+    let mask = if masked {
+        if buffer.len() < offset + 4 {
+            return Ok(None);
+        }
+        let key = [
+            buffer[offset],
+            buffer[offset + 1],
+            buffer[offset + 2],
+            buffer[offset + 3],
+        ];
+        offset += 4;
+        Some(key)
+    } else {
+        None
+    };
+
+    if buffer.len() < offset + len {
+        return Ok(None);
+    }
+    let mut payload: Vec<u8> = buffer.drain(..offset + len).skip(offset).collect();
+    if let Some(key) = mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= key[i % 4];
+        }
+    }
+    Ok(Some((fin, opcode, payload)))
+}
+
+// WebSocket server for browser clients, or a native client of one
+pub struct WebSocketTransport {
+    listener: Option<TcpListener>,
+    sockets: Vec<Socket>,
+    next_id: ConnectionId,
+}
+
+impl WebSocketTransport {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener: Some(listener),
+            sockets: Vec::new(),
+            next_id: SERVER_CONNECTION + 1,
+        })
+    }
+
+    // Opens a connection and sends the upgrade request, `Connected` is
+    // reported once the server agrees
+    pub fn connect(addr: impl ToSocketAddrs, host: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+
+        let mut socket = Socket::new(SERVER_CONNECTION, stream);
+        let key = BASE64.encode(rand::random::<[u8; 16]>());
+        socket.accept = Some(accept_key(&key));
+        socket.outgoing.extend_from_slice(
+            format!(
+                "GET / HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                 Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+                host, key
+            )
+            .as_bytes(),
+        );
+        socket.flush();
+
+        Ok(Self {
+            listener: None,
+            sockets: vec![socket],
+            next_id: SERVER_CONNECTION + 1,
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<std::net::SocketAddr> {
+        self.listener
+            .as_ref()
+            .and_then(|listener| listener.local_addr().ok())
+    }
+
+    fn is_server(&self) -> bool {
+        self.listener.is_some()
+    }
+
+    // Servers send plain frames, clients have to mask theirs
+    fn frame(&self, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = (!self.is_server()).then(rand::random::<[u8; 4]>);
+        encode_frame(opcode, payload, mask)
+    }
+
+    fn process(&mut self, index: usize, events: &mut Vec<TransportEvent>) {
+        let is_server = self.is_server();
+
+        if self.sockets[index].handshaking {
+            let socket = &mut self.sockets[index];
+            let head = match socket.take_http_head() {
+                Ok(Some(head)) => head,
+                Ok(None) => return,
+                Err(_) => {
+                    socket.closed = true;
+                    return;
+                }
+            };
+
+            if is_server {
+                let Some(key) = header(&head, "Sec-WebSocket-Key") else {
+                    socket
+                        .outgoing
+                        .extend_from_slice(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+                    socket.closed = true;
+                    return;
+                };
+                let response = format!(
+                    "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                     Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                    accept_key(key)
+                );
+                socket.outgoing.extend_from_slice(response.as_bytes());
+            } else if !head.starts_with("HTTP/1.1 101")
+                || header(&head, "Sec-WebSocket-Accept") != socket.accept.as_deref()
+            {
+                socket.closed = true;
+                return;
+            }
+
+            socket.handshaking = false;
+            events.push(TransportEvent::Connected(socket.id));
+        }
+
+        loop {
+            let frame = match decode_frame(&mut self.sockets[index].incoming, is_server) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(_) => {
+                    self.sockets[index].closed = true;
+                    break;
+                }
+            };
+
+            match frame {
+                (fin, OPCODE_BINARY | OPCODE_CONTINUATION, payload) => {
+                    let socket = &mut self.sockets[index];
+                    if socket.fragments.len() + payload.len() > MAX_FRAME {
+                        socket.closed = true;
+                        break;
+                    }
+                    socket.fragments.extend_from_slice(&payload);
+                    if fin {
+                        events.push(TransportEvent::Message {
+                            from: socket.id,
+                            payload: std::mem::take(&mut socket.fragments),
+                        });
+                    }
+                }
+                (_, OPCODE_PING, payload) => {
+                    let pong = self.frame(OPCODE_PONG, &payload);
+                    self.sockets[index].outgoing.extend_from_slice(&pong);
+                }
+                (_, OPCODE_CLOSE, _) => {
+                    let close = self.frame(OPCODE_CLOSE, &[]);
+                    self.sockets[index].outgoing.extend_from_slice(&close);
+                    self.sockets[index].closed = true;
+                    break;
+                }
+                // Text frames and pongs aren't part of the game protocol
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn send(&mut self, to: ConnectionId, _channel: Channel, payload: &[u8]) -> io::Result<()> {
+        let frame = self.frame(OPCODE_BINARY, payload);
+        let Some(socket) = self
+            .sockets
+            .iter_mut()
+            .find(|socket| socket.id == to && !socket.handshaking && !socket.closed)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "unknown connection",
+            ));
+        };
+        socket.outgoing.extend_from_slice(&frame);
+        socket.flush();
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        let mut events = Vec::new();
+
+        if let Some(listener) = self.listener.as_ref() {
+            while let Ok((stream, _)) = listener.accept() {
+                if stream.set_nonblocking(true).is_err() {
+                    continue;
+                }
+                let _ = stream.set_nodelay(true);
+                self.sockets.push(Socket::new(self.next_id, stream));
+                self.next_id += 1;
+            }
+        }
+
+        for index in 0..self.sockets.len() {
+            self.sockets[index].read_available();
+            self.process(index, &mut events);
+            self.sockets[index].flush();
+        }
+
+        self.sockets.retain(|socket| {
+            if socket.closed && !socket.handshaking {
+                events.push(TransportEvent::Disconnected(socket.id));
+            }
+            !socket.closed
+        });
+        events
+    }
+
+    fn disconnect(&mut self, connection: ConnectionId) {
+        let close = self.frame(OPCODE_CLOSE, &[]);
+        if let Some(index) = self
+            .sockets
+            .iter()
+            .position(|socket| socket.id == connection)
+        {
+            let mut socket = self.sockets.remove(index);
+            socket.outgoing.extend_from_slice(&close);
+            socket.flush();
+        }
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        self.sockets
+            .iter()
+            .filter(|socket| !socket.handshaking && !socket.closed)
+            .map(|socket| socket.id)
+            .collect()
+    }
+}
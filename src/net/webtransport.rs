@@ -0,0 +1,914 @@
+// WebTransport server for browser clients, or a native client of one.
+// Sessions run over HTTP/3 on QUIC: unreliable messages go out as QUIC
+// datagrams and reliable ones on a single bidirectional stream, so both
+// channels behave the way they do over UDP. Only as much HTTP/3 is spoken as
+// opening a session takes.
+use crate::net::transport::{
+    Channel, ConnectionId, ConnectionStats, Transport, TransportEvent, SERVER_CONNECTION,
+};
+use bytes::{Bytes, BytesMut};
+use quinn_proto::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn_proto::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use quinn_proto::rustls::crypto::{
+    ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider,
+};
+use quinn_proto::rustls::pki_types::{
+    CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime,
+};
+use quinn_proto::rustls::{self, CertificateError, DigitallySignedStruct, SignatureScheme};
+use quinn_proto::{
+    ClientConfig, Connection, ConnectionHandle, DatagramEvent, Dir, Endpoint, EndpointConfig,
+    Event, ServerConfig, StreamEvent, StreamId, TransportConfig, VarInt,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const ALPN: &[u8] = b"h3";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(5);
+// Refuse messages bigger than this rather than buffering without limit
+const MAX_MESSAGE: usize = 1 << 20;
+
+const STREAM_CONTROL: u64 = 0x00;
+const STREAM_WEBTRANSPORT: u64 = 0x41;
+const FRAME_HEADERS: u64 = 0x01;
+const FRAME_SETTINGS: u64 = 0x04;
+// Extended CONNECT and datagrams, plus WebTransport itself under both the
+// draft-02 setting browsers still send and the newer session limit
+const SETTINGS: [(u64, u64); 5] = [
+    (0x08, 1),
+    (0x33, 1),
+    (0xffd277, 1),
+    (0x2b60_3742, 1),
+    (0xc671_706a, 1),
+];
+// `:status 200` in the QPACK static table
+const STATUS_OK: u8 = 0xC0 | 25;
+// Static table index of `:status 400`
+const STATUS_BAD_REQUEST: usize = 67;
+
+fn write_varint(out: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0x3F => out.push(value as u8),
+        0x40..=0x3FFF => out.extend_from_slice(&(value as u16 | 0x4000).to_be_bytes()),
+        0x4000..=0x3FFF_FFFF => out.extend_from_slice(&(value as u32 | 0x8000_0000).to_be_bytes()),
+        _ => out.extend_from_slice(&(value | 0xC000_0000_0000_0000).to_be_bytes()),
+    }
+}
+
+// Varint at the front of `data`, with how many bytes it took
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = 1 << (first >> 6);
+    let bytes = data.get(1..len)?;
+    let value = bytes.iter().fold((first & 0x3F) as u64, |value, byte| {
+        value << 8 | *byte as u64
+    });
+    Some((value, len))
+}
+
+fn frame(kind: u64, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 4);
+    write_varint(&mut frame, kind);
+    write_varint(&mut frame, payload.len() as u64);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+// Next whole HTTP/3 frame in `data` as (type, payload, bytes taken)
+fn read_frame(data: &[u8]) -> Option<(u64, &[u8], usize)> {
+    let (kind, kind_len) = read_varint(data)?;
+    let (len, len_len) = read_varint(&data[kind_len..])?;
+    let start = kind_len + len_len;
+    let payload = data.get(start..start + len as usize)?;
+    Some((kind, payload, start + len as usize))
+}
+
+// QPACK integer with an N-bit prefix, sharing its first byte with `flags`
+fn qpack_int(out: &mut Vec<u8>, flags: u8, prefix: u32, value: usize) {
+    let max = (1 << prefix) - 1;
+    if value < max {
+        out.push(flags | value as u8);
+        return;
+    }
+    out.push(flags | max as u8);
+    let mut rest = value - max;
+    while rest >= 0x80 {
+        out.push(rest as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.push(rest as u8);
+}
+
+fn qpack_string(out: &mut Vec<u8>, value: &str) {
+    qpack_int(out, 0, 7, value.len());
+    out.extend_from_slice(value.as_bytes());
+}
+
+// Static Huffman code for QPACK string literals (RFC 7541 Appendix B), by
+// symbol with end-of-string last
+const HUFFMAN_CODES: [u32; 257] = [
+    0x1FF8, 0x7FFFD8, 0xFFFFFE2, 0xFFFFFE3, 0xFFFFFE4, 0xFFFFFE5, 0xFFFFFE6, 0xFFFFFE7, 0xFFFFFE8,
+    0xFFFFEA, 0x3FFFFFFC, 0xFFFFFE9, 0xFFFFFEA, 0x3FFFFFFD, 0xFFFFFEB, 0xFFFFFEC, 0xFFFFFED,
+    0xFFFFFEE, 0xFFFFFEF, 0xFFFFFF0, 0xFFFFFF1, 0xFFFFFF2, 0x3FFFFFFE, 0xFFFFFF3, 0xFFFFFF4,
+    0xFFFFFF5, 0xFFFFFF6, 0xFFFFFF7, 0xFFFFFF8, 0xFFFFFF9, 0xFFFFFFA, 0xFFFFFFB, 0x14, 0x3F8,
+    0x3F9, 0xFFA, 0x1FF9, 0x15, 0xF8, 0x7FA, 0x3FA, 0x3FB, 0xF9, 0x7FB, 0xFA, 0x16, 0x17, 0x18,
+    0x0, 0x1, 0x2, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F, 0x5C, 0xFB, 0x7FFC, 0x20, 0xFFB,
+    0x3FC, 0x1FFA, 0x21, 0x5D, 0x5E, 0x5F, 0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x6B, 0x6C, 0x6D, 0x6E, 0x6F, 0x70, 0x71, 0x72, 0xFC, 0x73, 0xFD, 0x1FFB, 0x7FFF0,
+    0x1FFC, 0x3FFC, 0x22, 0x7FFD, 0x3, 0x23, 0x4, 0x24, 0x5, 0x25, 0x26, 0x27, 0x6, 0x74, 0x75,
+    0x28, 0x29, 0x2A, 0x7, 0x2B, 0x76, 0x2C, 0x8, 0x9, 0x2D, 0x77, 0x78, 0x79, 0x7A, 0x7B, 0x7FFE,
+    0x7FC, 0x3FFD, 0x1FFD, 0xFFFFFFC, 0xFFFE6, 0x3FFFD2, 0xFFFE7, 0xFFFE8, 0x3FFFD3, 0x3FFFD4,
+    0x3FFFD5, 0x7FFFD9, 0x3FFFD6, 0x7FFFDA, 0x7FFFDB, 0x7FFFDC, 0x7FFFDD, 0x7FFFDE, 0xFFFFEB,
+    0x7FFFDF, 0xFFFFEC, 0xFFFFED, 0x3FFFD7, 0x7FFFE0, 0xFFFFEE, 0x7FFFE1, 0x7FFFE2, 0x7FFFE3,
+    0x7FFFE4, 0x1FFFDC, 0x3FFFD8, 0x7FFFE5, 0x3FFFD9, 0x7FFFE6, 0x7FFFE7, 0xFFFFEF, 0x3FFFDA,
+    0x1FFFDD, 0xFFFE9, 0x3FFFDB, 0x3FFFDC, 0x7FFFE8, 0x7FFFE9, 0x1FFFDE, 0x7FFFEA, 0x3FFFDD,
+    0x3FFFDE, 0xFFFFF0, 0x1FFFDF, 0x3FFFDF, 0x7FFFEB, 0x7FFFEC, 0x1FFFE0, 0x1FFFE1, 0x3FFFE0,
+    0x1FFFE2, 0x7FFFED, 0x3FFFE1, 0x7FFFEE, 0x7FFFEF, 0xFFFEA, 0x3FFFE2, 0x3FFFE3, 0x3FFFE4,
+    0x7FFFF0, 0x3FFFE5, 0x3FFFE6, 0x7FFFF1, 0x3FFFFE0, 0x3FFFFE1, 0xFFFEB, 0x7FFF1, 0x3FFFE7,
+    0x7FFFF2, 0x3FFFE8, 0x1FFFFEC, 0x3FFFFE2, 0x3FFFFE3, 0x3FFFFE4, 0x7FFFFDE, 0x7FFFFDF,
+    0x3FFFFE5, 0xFFFFF1, 0x1FFFFED, 0x7FFF2, 0x1FFFE3, 0x3FFFFE6, 0x7FFFFE0, 0x7FFFFE1, 0x3FFFFE7,
+    0x7FFFFE2, 0xFFFFF2, 0x1FFFE4, 0x1FFFE5, 0x3FFFFE8, 0x3FFFFE9, 0xFFFFFFD, 0x7FFFFE3, 0x7FFFFE4,
+    0x7FFFFE5, 0xFFFEC, 0xFFFFF3, 0xFFFED, 0x1FFFE6, 0x3FFFE9, 0x1FFFE7, 0x1FFFE8, 0x7FFFF3,
+    0x3FFFEA, 0x3FFFEB, 0x1FFFFEE, 0x1FFFFEF, 0xFFFFF4, 0xFFFFF5, 0x3FFFFEA, 0x7FFFF4, 0x3FFFFEB,
+    0x7FFFFE6, 0x3FFFFEC, 0x3FFFFED, 0x7FFFFE7, 0x7FFFFE8, 0x7FFFFE9, 0x7FFFFEA, 0x7FFFFEB,
+    0xFFFFFFE, 0x7FFFFEC, 0x7FFFFED, 0x7FFFFEE, 0x7FFFFEF, 0x7FFFFF0, 0x3FFFFEE, 0x3FFFFFFF,
+];
+
+const HUFFMAN_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 30, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6, 5, 5,
+    5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10, 13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6, 15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6,
+    6, 5, 6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28, 20, 22, 20, 20, 22, 22, 22, 23, 22,
+    23, 23, 23, 23, 23, 24, 23, 24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24, 22,
+    21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23, 21, 21, 22, 21, 23, 22, 23, 23, 20,
+    22, 22, 22, 23, 22, 22, 23, 26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25, 19,
+    21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27, 20, 24, 20, 21, 22, 21, 21, 23, 22,
+    22, 25, 25, 24, 24, 26, 23, 26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26, 30,
+];
+
+// QPACK integer with an N-bit prefix at the front of `data`, with how many
+// bytes it took
+fn read_qpack_int(data: &[u8], prefix: u32) -> Option<(usize, usize)> {
+    let max = (1 << prefix) - 1;
+    let value = (*data.first()? as usize) & max;
+    if value < max {
+        return Some((value, 1));
+    }
+    let mut value = max;
+    for (i, byte) in data.iter().enumerate().skip(1) {
+        value = value.checked_add(((byte & 0x7F) as usize).checked_shl(7 * (i as u32 - 1))?)?;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn huffman_decode(data: &[u8]) -> Option<String> {
+    let mut out = Vec::new();
+    let (mut code, mut len) = (0u32, 0u8);
+    for bit in data
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1))
+    {
+        code = code << 1 | bit as u32;
+        len += 1;
+        let symbol = (0..HUFFMAN_CODES.len())
+            .find(|&symbol| HUFFMAN_LENGTHS[symbol] == len && HUFFMAN_CODES[symbol] == code);
+        match symbol {
+            Some(256) => return None,
+            Some(symbol) => {
+                out.push(symbol as u8);
+                (code, len) = (0, 0);
+            }
+            None if len >= 30 => return None,
+            None => {}
+        }
+    }
+    // Whatever is left is padding, the start of end-of-string
+    if len >= 8 || code != (1 << len) - 1 {
+        return None;
+    }
+    String::from_utf8(out).ok()
+}
+
+// QPACK string literal at the front of `data` whose length has an N-bit prefix
+// with the Huffman flag just above it, with how many bytes it took
+fn read_qpack_string(data: &[u8], prefix: u32) -> Option<(String, usize)> {
+    let huffman = data.first()? & (1 << prefix) != 0;
+    let (len, len_len) = read_qpack_int(data, prefix)?;
+    let bytes = data.get(len_len..len_len.checked_add(len)?)?;
+    let value = if huffman {
+        huffman_decode(bytes)?
+    } else {
+        String::from_utf8(bytes.to_vec()).ok()?
+    };
+    Some((value, len_len + len))
+}
+
+// The pseudo-headers of the QPACK static table a CONNECT request can use
+fn static_field(index: usize) -> Option<(&'static str, &'static str)> {
+    Some(match index {
+        0 => (":authority", ""),
+        1 => (":path", "/"),
+        15 => (":method", "CONNECT"),
+        16 => (":method", "DELETE"),
+        17 => (":method", "GET"),
+        18 => (":method", "HEAD"),
+        19 => (":method", "OPTIONS"),
+        20 => (":method", "POST"),
+        21 => (":method", "PUT"),
+        22 => (":scheme", "http"),
+        23 => (":scheme", "https"),
+        _ => return None,
+    })
+}
+
+// Fields of a header block sent without the dynamic table, as (name, value).
+// Static entries outside `static_field` come back with an empty name.
+pub fn read_header_block(block: &[u8]) -> Option<Vec<(String, String)>> {
+    // Required insert count and base, both zero without a dynamic table
+    let (0, insert_len) = read_qpack_int(block, 8)? else {
+        return None;
+    };
+    let (_, base_len) = read_qpack_int(&block[insert_len..], 7)?;
+    let mut data = &block[insert_len + base_len..];
+
+    let mut fields = Vec::new();
+    while let Some(&first) = data.first() {
+        let name_of = |index| static_field(index).map_or("", |(name, _)| name).to_string();
+        let (field, len) = match first {
+            // Indexed field line, static table only
+            0xC0..=0xFF => {
+                let (index, len) = read_qpack_int(data, 6)?;
+                let (name, value) = static_field(index).unwrap_or_default();
+                ((name.to_string(), value.to_string()), len)
+            }
+            // Literal value with a static table name
+            0x50..=0x5F | 0x70..=0x7F => {
+                let (index, name_len) = read_qpack_int(data, 4)?;
+                let (value, value_len) = read_qpack_string(&data[name_len..], 7)?;
+                ((name_of(index), value), name_len + value_len)
+            }
+            // Literal name and value
+            0x20..=0x3F => {
+                let (name, name_len) = read_qpack_string(data, 3)?;
+                let (value, value_len) = read_qpack_string(&data[name_len..], 7)?;
+                ((name, value), name_len + value_len)
+            }
+            // Anything pointing into the dynamic table
+            _ => return None,
+        };
+        fields.push(field);
+        data = &data[len..];
+    }
+    Some(fields)
+}
+
+// Header blocks are written without a dynamic table, so every field is a
+// static table entry or a literal
+fn connect_request(authority: &str) -> Vec<u8> {
+    let mut fields = vec![0, 0];
+    // :method CONNECT, :scheme https, :path /
+    fields.extend_from_slice(&[0xC0 | 15, 0xC0 | 23, 0xC0 | 1]);
+    // :authority, by static table name
+    qpack_int(&mut fields, 0x50, 4, 0);
+    qpack_string(&mut fields, authority);
+    for (name, value) in [
+        (":protocol", "webtransport"),
+        ("sec-webtransport-http3-draft02", "1"),
+    ] {
+        qpack_int(&mut fields, 0x20, 3, name.len());
+        fields.extend_from_slice(name.as_bytes());
+        qpack_string(&mut fields, value);
+    }
+    frame(FRAME_HEADERS, &fields)
+}
+
+fn session_accepted() -> Vec<u8> {
+    let mut fields = vec![0, 0, STATUS_OK];
+    let name = "sec-webtransport-http3-draft";
+    qpack_int(&mut fields, 0x20, 3, name.len());
+    fields.extend_from_slice(name.as_bytes());
+    qpack_string(&mut fields, "draft02");
+    frame(FRAME_HEADERS, &fields)
+}
+
+// `:status 400`, for requests that aren't a WebTransport CONNECT
+fn request_refused() -> Vec<u8> {
+    let mut fields = vec![0, 0];
+    qpack_int(&mut fields, 0xC0, 6, STATUS_BAD_REQUEST);
+    frame(FRAME_HEADERS, &fields)
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn transport_config() -> Arc<TransportConfig> {
+    let mut config = TransportConfig::default();
+    config
+        .max_idle_timeout(TIMEOUT.try_into().ok())
+        .keep_alive_interval(Some(HEARTBEAT_INTERVAL));
+    Arc::new(config)
+}
+
+// Browsers only pin self-signed certificates that are ECDSA and valid for
+// at most two weeks, so a fresh one is made each time a server starts
+fn self_signed_certificate() -> io::Result<(CertificateDer<'static>, PrivateKeyDer<'static>)> {
+    let mut params =
+        rcgen::CertificateParams::new(vec!["localhost".to_string()]).map_err(io::Error::other)?;
+    let now = time::OffsetDateTime::now_utc();
+    params.not_before = now - time::Duration::hours(1);
+    params.not_after = now + time::Duration::days(13);
+    let key_pair = rcgen::KeyPair::generate().map_err(io::Error::other)?;
+    let certificate = params.self_signed(&key_pair).map_err(io::Error::other)?;
+    Ok((
+        certificate.der().clone(),
+        PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der())),
+    ))
+}
+
+// Trusts the one certificate with this hash, the way browsers are told to
+// trust a server's with `serverCertificateHashes`
+#[derive(Debug)]
+struct PinnedCertificate {
+    hash: [u8; 32],
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if Sha256::digest(end_entity)[..] == self.hash {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::UnknownIssuer,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+struct Peer {
+    id: ConnectionId,
+    handle: ConnectionHandle,
+    connection: Connection,
+    // The stream the CONNECT request went over, its id names the session
+    session: Option<StreamId>,
+    // Carries reliable messages both ways, each behind a u32 length
+    messages: Option<StreamId>,
+    // What's been read from each bidirectional stream and not used yet
+    incoming: HashMap<StreamId, Vec<u8>>,
+    // Reliable bytes the message stream hasn't taken yet
+    outgoing: Vec<u8>,
+    // Reported as connected and not yet as disconnected
+    connected: bool,
+    // We hung up and are only waiting for the close to go out
+    closing: bool,
+}
+
+impl Peer {
+    fn new(id: ConnectionId, handle: ConnectionHandle, connection: Connection) -> Self {
+        Self {
+            id,
+            handle,
+            connection,
+            session: None,
+            messages: None,
+            incoming: HashMap::new(),
+            outgoing: Vec::new(),
+            connected: false,
+            closing: false,
+        }
+    }
+
+    fn open_control_stream(&mut self) {
+        let Some(stream) = self.connection.streams().open(Dir::Uni) else {
+            return;
+        };
+        let mut settings = Vec::new();
+        for (id, value) in SETTINGS {
+            write_varint(&mut settings, id);
+            write_varint(&mut settings, value);
+        }
+        let mut data = Vec::new();
+        write_varint(&mut data, STREAM_CONTROL);
+        data.extend(frame(FRAME_SETTINGS, &settings));
+        let _ = self.connection.send_stream(stream).write(&data);
+    }
+
+    fn open_session(&mut self, authority: &str) {
+        let Some(stream) = self.connection.streams().open(Dir::Bi) else {
+            return;
+        };
+        let _ = self
+            .connection
+            .send_stream(stream)
+            .write(&connect_request(authority));
+        self.session = Some(stream);
+        self.incoming.insert(stream, Vec::new());
+    }
+
+    fn close(&mut self) {
+        self.connection
+            .close(Instant::now(), VarInt::from_u32(0), Bytes::new());
+        self.closing = true;
+    }
+
+    fn flush(&mut self) {
+        let Some(stream) = self.messages else {
+            return;
+        };
+        if let Ok(written) = self.connection.send_stream(stream).write(&self.outgoing) {
+            self.outgoing.drain(..written);
+        }
+    }
+
+    fn read(&mut self, stream: StreamId, is_server: bool, events: &mut Vec<TransportEvent>) {
+        let mut data = Vec::new();
+        if let Ok(mut chunks) = self.connection.recv_stream(stream).read(true) {
+            while let Ok(Some(chunk)) = chunks.next(usize::MAX) {
+                data.extend_from_slice(&chunk.bytes);
+            }
+            let _ = chunks.finalize();
+        }
+        // Control and QPACK streams carry nothing this needs
+        let Some(buffer) = self.incoming.get_mut(&stream) else {
+            return;
+        };
+        buffer.extend(data);
+        self.parse(stream, is_server, events);
+    }
+
+    fn parse(&mut self, stream: StreamId, is_server: bool, events: &mut Vec<TransportEvent>) {
+        let Some(mut buffer) = self.incoming.remove(&stream) else {
+            return;
+        };
+
+        if Some(stream) == self.messages {
+            while buffer.len() >= 4 {
+                let len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+                if len > MAX_MESSAGE {
+                    self.close();
+                    return;
+                }
+                if buffer.len() < 4 + len {
+                    break;
+                }
+                let payload = buffer.drain(..4 + len).skip(4).collect();
+                events.push(TransportEvent::Message {
+                    from: self.id,
+                    payload,
+                });
+            }
+            self.incoming.insert(stream, buffer);
+            return;
+        }
+
+        // A client's stream for the session it opened, which becomes the
+        // message stream. Streams for any other session are ignored.
+        if is_server && self.messages.is_none() {
+            if let Some((STREAM_WEBTRANSPORT, kind_len)) = read_varint(&buffer) {
+                let Some((session, session_len)) = read_varint(&buffer[kind_len..]) else {
+                    self.incoming.insert(stream, buffer);
+                    return;
+                };
+                if self.session.map(u64::from) == Some(session) {
+                    buffer.drain(..kind_len + session_len);
+                    self.incoming.insert(stream, buffer);
+                    self.messages = Some(stream);
+                    self.connected = true;
+                    events.push(TransportEvent::Connected(self.id));
+                    self.parse(stream, is_server, events);
+                }
+                return;
+            }
+        }
+
+        loop {
+            let Some((kind, payload, len)) = read_frame(&buffer) else {
+                self.incoming.insert(stream, buffer);
+                return;
+            };
+            // Reserved frames may come first and mean nothing
+            if kind == FRAME_HEADERS {
+                let payload = payload.to_vec();
+                self.headers(stream, &payload, is_server, events);
+                // The session stream carries nothing else we use
+                return;
+            }
+            buffer.drain(..len);
+        }
+    }
+
+    fn headers(
+        &mut self,
+        stream: StreamId,
+        block: &[u8],
+        is_server: bool,
+        events: &mut Vec<TransportEvent>,
+    ) {
+        if is_server && self.session.is_none() {
+            // Only an extended CONNECT for WebTransport opens the session, the
+            // path isn't routed
+            let fields = read_header_block(block).unwrap_or_default();
+            let field = |name: &str| {
+                fields
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.as_str())
+            };
+            if field(":method") != Some("CONNECT") || field(":protocol") != Some("webtransport") {
+                let mut send = self.connection.send_stream(stream);
+                let _ = send.write(&request_refused());
+                let _ = send.finish();
+                return;
+            }
+            self.session = Some(stream);
+            let _ = self
+                .connection
+                .send_stream(stream)
+                .write(&session_accepted());
+            return;
+        }
+        if is_server || Some(stream) != self.session {
+            return;
+        }
+
+        // Our servers answer with the static table's 200
+        let messages = match block.get(2) {
+            Some(&STATUS_OK) => self.connection.streams().open(Dir::Bi),
+            _ => None,
+        };
+        let Some(messages) = messages else {
+            self.close();
+            return;
+        };
+        let mut header = Vec::new();
+        write_varint(&mut header, STREAM_WEBTRANSPORT);
+        write_varint(&mut header, u64::from(stream));
+        header.append(&mut self.outgoing);
+        self.outgoing = header;
+        self.messages = Some(messages);
+        self.incoming.insert(messages, Vec::new());
+        self.connected = true;
+        events.push(TransportEvent::Connected(self.id));
+        self.flush();
+    }
+
+    fn process(&mut self, is_server: bool, authority: &str, events: &mut Vec<TransportEvent>) {
+        while let Some(event) = self.connection.poll() {
+            match event {
+                Event::Connected => {
+                    self.open_control_stream();
+                    if !is_server {
+                        self.open_session(authority);
+                    }
+                }
+                Event::ConnectionLost { .. } => {
+                    if self.connected && !self.closing {
+                        events.push(TransportEvent::Disconnected(self.id));
+                    }
+                    self.connected = false;
+                }
+                Event::Stream(StreamEvent::Opened { dir }) => {
+                    while let Some(stream) = self.connection.streams().accept(dir) {
+                        if dir == Dir::Bi {
+                            self.incoming.insert(stream, Vec::new());
+                        }
+                        self.read(stream, is_server, events);
+                    }
+                }
+                Event::Stream(StreamEvent::Readable { id }) => {
+                    self.read(id, is_server, events);
+                }
+                Event::Stream(StreamEvent::Writable { .. }) => self.flush(),
+                Event::DatagramReceived => {
+                    while let Some(datagram) = self.connection.datagrams().recv() {
+                        // Datagrams lead with the session's quarter stream id
+                        let Some((_, len)) = read_varint(&datagram) else {
+                            continue;
+                        };
+                        if self.connected {
+                            events.push(TransportEvent::Message {
+                                from: self.id,
+                                payload: datagram[len..].to_vec(),
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// WebTransport server for browser clients, or a native client of one
+pub struct WebTransportTransport {
+    socket: UdpSocket,
+    endpoint: Endpoint,
+    peers: Vec<Peer>,
+    is_server: bool,
+    // Host clients ask for when opening their session
+    authority: String,
+    certificate_hash: Option<[u8; 32]>,
+    next_id: ConnectionId,
+}
+
+impl WebTransportTransport {
+    // Listens for clients on `addr`, under a freshly made certificate
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let (certificate, key) = self_signed_certificate()?;
+        let certificate_hash = Sha256::digest(&certificate).into();
+
+        let mut crypto = rustls::ServerConfig::builder_with_provider(provider())
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(io::Error::other)?
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate], key)
+            .map_err(io::Error::other)?;
+        crypto.alpn_protocols = vec![ALPN.to_vec()];
+        let crypto = QuicServerConfig::try_from(crypto).map_err(io::Error::other)?;
+        let mut config = ServerConfig::with_crypto(Arc::new(crypto));
+        config.transport_config(transport_config());
+
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            endpoint: Endpoint::new(
+                Arc::new(EndpointConfig::default()),
+                Some(Arc::new(config)),
+                false,
+                None,
+            ),
+            peers: Vec::new(),
+            is_server: true,
+            authority: String::new(),
+            certificate_hash: Some(certificate_hash),
+            next_id: SERVER_CONNECTION + 1,
+        })
+    }
+
+    // Starts a session with a server whose certificate has this SHA-256
+    // hash, `Connected` is reported once the session is open
+    pub fn connect(server: impl ToSocketAddrs, certificate_hash: [u8; 32]) -> io::Result<Self> {
+        let server = server
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no server address"))?;
+        let local: SocketAddr = if server.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+
+        let provider = provider();
+        let mut crypto = rustls::ClientConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(io::Error::other)?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedCertificate {
+                hash: certificate_hash,
+                provider,
+            }))
+            .with_no_client_auth();
+        crypto.alpn_protocols = vec![ALPN.to_vec()];
+        let crypto = QuicClientConfig::try_from(crypto).map_err(io::Error::other)?;
+        let mut config = ClientConfig::new(Arc::new(crypto));
+        config.transport_config(transport_config());
+
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        let mut endpoint = Endpoint::new(Arc::new(EndpointConfig::default()), None, false, None);
+        // The pinned hash is checked rather than the name
+        let (handle, connection) = endpoint
+            .connect(Instant::now(), config, server, "localhost")
+            .map_err(io::Error::other)?;
+
+        let mut transport = Self {
+            socket,
+            endpoint,
+            peers: vec![Peer::new(SERVER_CONNECTION, handle, connection)],
+            is_server: false,
+            authority: server.to_string(),
+            certificate_hash: None,
+            next_id: SERVER_CONNECTION + 1,
+        };
+        transport.transmit(0);
+        Ok(transport)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    // SHA-256 of a server's certificate, which browsers pass as
+    // `serverCertificateHashes` to trust it
+    pub fn certificate_hash(&self) -> Option<[u8; 32]> {
+        self.certificate_hash
+    }
+
+    // Sends whatever the connection has queued and lets the endpoint know
+    // what changed
+    fn transmit(&mut self, index: usize) {
+        let now = Instant::now();
+        let peer = &mut self.peers[index];
+        let mut buffer = Vec::new();
+        while let Some(transmit) = peer.connection.poll_transmit(now, 1, &mut buffer) {
+            // Losing a datagram is normal, QUIC resends what matters
+            let _ = self
+                .socket
+                .send_to(&buffer[..transmit.size], transmit.destination);
+            buffer.clear();
+        }
+        while let Some(event) = peer.connection.poll_endpoint_events() {
+            if let Some(event) = self.endpoint.handle_event(peer.handle, event) {
+                peer.connection.handle_event(event);
+            }
+        }
+    }
+
+    fn handle_datagram(&mut self, from: SocketAddr, data: &[u8]) {
+        let now = Instant::now();
+        let mut response = Vec::new();
+        match self
+            .endpoint
+            .handle(now, from, None, None, BytesMut::from(data), &mut response)
+        {
+            Some(DatagramEvent::ConnectionEvent(handle, event)) => {
+                if let Some(peer) = self.peers.iter_mut().find(|peer| peer.handle == handle) {
+                    peer.connection.handle_event(event);
+                }
+            }
+            Some(DatagramEvent::NewConnection(incoming)) => {
+                match self.endpoint.accept(incoming, now, &mut response, None) {
+                    Ok((handle, connection)) => {
+                        self.peers.push(Peer::new(self.next_id, handle, connection));
+                        self.next_id += 1;
+                    }
+                    Err(err) => {
+                        if let Some(transmit) = err.response {
+                            let _ = self.socket.send_to(&response[..transmit.size], from);
+                        }
+                    }
+                }
+            }
+            Some(DatagramEvent::Response(transmit)) => {
+                let _ = self
+                    .socket
+                    .send_to(&response[..transmit.size], transmit.destination);
+            }
+            None => {}
+        }
+    }
+}
+
+impl Transport for WebTransportTransport {
+    fn stats(&self, connection: ConnectionId) -> Option<ConnectionStats> {
+        let peer = self.peers.iter().find(|peer| peer.id == connection)?;
+        let path = peer.connection.stats().path;
+        Some(ConnectionStats {
+            rtt: Some(path.rtt),
+            loss: path.lost_packets as f32 / path.sent_packets.max(1) as f32,
+        })
+    }
+
+    fn send(&mut self, to: ConnectionId, channel: Channel, payload: &[u8]) -> io::Result<()> {
+        let Some(index) = self
+            .peers
+            .iter()
+            .position(|peer| peer.id == to && peer.connected && !peer.closing)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "unknown connection",
+            ));
+        };
+        if payload.len() > MAX_MESSAGE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "message too large",
+            ));
+        }
+
+        let peer = &mut self.peers[index];
+        let mut datagram = Vec::with_capacity(payload.len() + 8);
+        write_varint(&mut datagram, peer.session.map_or(0, u64::from) / 4);
+        datagram.extend_from_slice(payload);
+        // Messages too big for one datagram go reliably instead
+        let fits = peer
+            .connection
+            .datagrams()
+            .max_size()
+            .is_some_and(|max| datagram.len() <= max);
+        if channel == Channel::Unreliable && fits {
+            let _ = peer
+                .connection
+                .datagrams()
+                .send(Bytes::from(datagram), true);
+        } else {
+            peer.outgoing
+                .extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            peer.outgoing.extend_from_slice(payload);
+            peer.flush();
+        }
+        self.transmit(index);
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Vec<TransportEvent> {
+        let mut buffer = vec![0; 65_536];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, from)) => {
+                    let data = buffer[..len].to_vec();
+                    self.handle_datagram(from, &data);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                // ICMP errors from a peer that went away show up here on
+                // some platforms, the idle timeout deals with them
+                Err(_) => break,
+            }
+        }
+
+        let mut events = Vec::new();
+        let now = Instant::now();
+        for index in 0..self.peers.len() {
+            let peer = &mut self.peers[index];
+            if peer.connection.poll_timeout().is_some_and(|due| due <= now) {
+                peer.connection.handle_timeout(now);
+            }
+            peer.process(self.is_server, &self.authority, &mut events);
+            peer.flush();
+            self.transmit(index);
+        }
+
+        self.peers.retain(|peer| !peer.connection.is_drained());
+        events
+    }
+
+    fn disconnect(&mut self, connection: ConnectionId) {
+        if let Some(index) = self.peers.iter().position(|peer| peer.id == connection) {
+            self.peers[index].close();
+            self.transmit(index);
+        }
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        self.peers
+            .iter()
+            .filter(|peer| peer.connected && !peer.closing)
+            .map(|peer| peer.id)
+            .collect()
+    }
+}
@@ -0,0 +1,136 @@
+// paths.rs
+// Where the game keeps what it writes: settings, profile saves, stats and
+// telemetry captures. That's the platform's data directory unless the game
+// is run with `--portable`, which keeps everything next to the binary.
+use bevy::prelude::*;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Directory name under the platform's data directory
+const APP_DIR: &str = "landio";
+
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct Paths {
+    pub root: PathBuf,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Self::platform()
+    }
+}
+
+impl Paths {
+    pub fn in_dir(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    // Portable with `--portable` on the command line, the platform's data
+    // directory otherwise
+    pub fn from_args(args: &[String]) -> Self {
+        if args.iter().any(|arg| arg == "--portable") {
+            Self::portable()
+        } else {
+            Self::platform()
+        }
+    }
+
+    // Next to the binary, or the working directory if that can't be found
+    pub fn portable() -> Self {
+        let dir = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+            .unwrap_or_else(|| PathBuf::from("."));
+        Self::in_dir(dir)
+    }
+
+    // AppData on Windows, Application Support on macOS and the XDG data
+    // directory everywhere else. Falls back to portable without a home.
+    // Settings and saves left in the working directory by earlier versions
+    // are moved over on the first run.
+    pub fn platform() -> Self {
+        let var = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+        let base = if cfg!(target_os = "windows") {
+            var("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            var("HOME").map(|home| Path::new(&home).join("Library/Application Support"))
+        } else {
+            var("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .or_else(|| var("HOME").map(|home| Path::new(&home).join(".local/share")))
+        };
+        let Some(base) = base else {
+            return Self::portable();
+        };
+        let paths = Self::in_dir(base.join(APP_DIR));
+        if let Ok(dir) = env::current_dir() {
+            paths.migrate_from(&dir);
+        }
+        paths
+    }
+
+    // Moves the settings, saves and stats kept in `old_root` over, each one
+    // only if there isn't one here already
+    pub fn migrate_from(&self, old_root: &Path) {
+        for path in [self.config(), self.saves(), self.stats()] {
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            let old = old_root.join(name);
+            if path.exists() || !old.is_file() {
+                continue;
+            }
+            // Renaming fails across drives, where it takes a copy instead
+            let moved = create_parent_dir(&path).and_then(|_| {
+                fs::rename(&old, &path)
+                    .or_else(|_| fs::copy(&old, &path).and_then(|_| fs::remove_file(&old)))
+            });
+            match moved {
+                Ok(()) => println!("Moved {} to {}", old.display(), path.display()),
+                Err(err) => println!("Failed to move {}: {}", old.display(), err),
+            }
+        }
+    }
+
+    pub fn config(&self) -> PathBuf {
+        self.root.join("config.ron")
+    }
+
+    // Player profiles, with their XP, unlocks and ratings
+    pub fn saves(&self) -> PathBuf {
+        self.root.join("profiles.ron")
+    }
+
+    pub fn stats(&self) -> PathBuf {
+        self.root.join("stats.ron")
+    }
+
+    pub fn captures(&self) -> PathBuf {
+        self.root.join("captures")
+    }
+
+    // A capture file the user named, relative paths going in `captures`
+    pub fn capture(&self, name: &str) -> PathBuf {
+        let path = Path::new(name);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.captures().join(path)
+        }
+    }
+}
+
+// Writes a file, making the directories it goes in first
+pub fn write_file(path: &Path, contents: &str) -> io::Result<()> {
+    create_parent_dir(path)?;
+    fs::write(path, contents)
+}
+
+pub fn create_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::create_dir_all(dir),
+        _ => Ok(()),
+    }
+}
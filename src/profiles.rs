@@ -0,0 +1,261 @@
+// profiles.rs
+// Local player profiles, so people sharing a machine keep their own name,
+// look, controls and records. Stored as RON alongside the config.
+use crate::brain::BotDifficulty;
+use crate::paths::{write_file, Paths};
+use crate::progression::{level_for_xp, TrailStyle};
+use crate::systems::challenges::ChallengeRecord;
+use crate::systems::daily::DailyRecord;
+use crate::systems::input::KeyBindings;
+use crate::systems::quick_play::QuickPlaySetup;
+use crate::systems::rating::Rating;
+use crate::systems::stats::Award;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+// Results kept per difficulty, only the latest count for calibrating
+const RECENT_BOT_MATCHES: usize = 5;
+// Recent matches at a difficulty before calibration trusts them
+const CALIBRATION_MATCHES: usize = 3;
+// Winning at least this share of recent matches moves the bots up a step,
+// winning no more than the lower one moves them down
+const STEP_UP_WIN_RATE: f32 = 0.6;
+const STEP_DOWN_WIN_RATE: f32 = 0.25;
+
+// How matches against bots of one difficulty went
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotRecord {
+    pub played: u32,
+    pub won: u32,
+    // Whether each of the last few was won, oldest first
+    pub recent: Vec<bool>,
+}
+
+impl BotRecord {
+    pub fn add(&mut self, won: bool) {
+        self.played += 1;
+        self.won += won as u32;
+        self.recent.push(won);
+        if self.recent.len() > RECENT_BOT_MATCHES {
+            self.recent.remove(0);
+        }
+    }
+
+    pub fn recent_win_rate(&self) -> f32 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        self.recent.iter().filter(|&&won| won).count() as f32 / self.recent.len() as f32
+    }
+}
+
+// Bot difficulty picked from the record, and why, to show the player
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Calibration {
+    pub difficulty: BotDifficulty,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileStats {
+    pub matches_played: u32,
+    pub wins: u32,
+    pub best_score: u32,
+    // Times each end-of-match award was won
+    pub awards: BTreeMap<Award, u32>,
+    // Matches played against bots, by the bots' difficulty
+    pub bots: BTreeMap<BotDifficulty, BotRecord>,
+    // Difficulty of the last of them
+    pub last_bot_difficulty: Option<BotDifficulty>,
+}
+
+impl ProfileStats {
+    pub fn record_bot_match(&mut self, difficulty: BotDifficulty, won: bool) {
+        self.bots.entry(difficulty).or_default().add(won);
+        self.last_bot_difficulty = Some(difficulty);
+    }
+
+    // Difficulty that should make for a close match. Starts from the one
+    // played last and moves a step up after mostly wins there, or a step
+    // down after mostly losses.
+    pub fn calibrated_difficulty(&self) -> Calibration {
+        let Some(last) = self.last_bot_difficulty else {
+            return Calibration {
+                difficulty: BotDifficulty::Normal,
+                reason: "no matches against bots yet".to_string(),
+            };
+        };
+        let record = self.bots.get(&last).cloned().unwrap_or_default();
+        if record.recent.len() < CALIBRATION_MATCHES {
+            return Calibration {
+                difficulty: last,
+                reason: format!("still getting the measure of {}", last.name()),
+            };
+        }
+
+        let win_rate = record.recent_win_rate();
+        let (difficulty, reason) = if win_rate >= STEP_UP_WIN_RATE {
+            match last.harder() {
+                Some(harder) => (
+                    harder,
+                    format!(
+                        "you usually win vs {}, starting {}",
+                        last.name(),
+                        harder.name()
+                    ),
+                ),
+                None => (last, format!("you usually win vs {}", last.name())),
+            }
+        } else if win_rate <= STEP_DOWN_WIN_RATE {
+            match last.easier() {
+                Some(easier) => (
+                    easier,
+                    format!(
+                        "you usually lose vs {}, starting {}",
+                        last.name(),
+                        easier.name()
+                    ),
+                ),
+                None => (last, format!("you usually lose vs {}", last.name())),
+            }
+        } else {
+            (last, format!("you're evenly matched vs {}", last.name()))
+        };
+        Calibration { difficulty, reason }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    // Replaces the slot color when set
+    pub color: Option<Color>,
+    // Replaces the device's default keys when playing on a keyboard
+    pub key_bindings: Option<KeyBindings>,
+    pub stats: ProfileStats,
+    pub rating: Rating,
+    pub daily: DailyRecord,
+    // Best star count per puzzle level id
+    pub levels: HashMap<String, u8>,
+    // Earned over every match, the level unlocks cosmetics and maps
+    pub xp: u32,
+    pub trail_style: TrailStyle,
+    pub challenges: ChallengeRecord,
+    // Setup the last match from the join screen started with, None until
+    // there's been one
+    pub quick_play: Option<QuickPlaySetup>,
+}
+
+impl Profile {
+    pub fn level(&self) -> u32 {
+        level_for_xp(self.xp)
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: "Player".to_string(),
+            color: None,
+            key_bindings: None,
+            stats: ProfileStats::default(),
+            rating: Rating::default(),
+            daily: DailyRecord::default(),
+            levels: HashMap::new(),
+            xp: 0,
+            trail_style: TrailStyle::default(),
+            challenges: ChallengeRecord::default(),
+            quick_play: None,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileStore {
+    pub profiles: Vec<Profile>,
+}
+
+impl ProfileStore {
+    // Missing or unreadable profiles start from an empty list
+    pub fn load(paths: &Paths) -> Self {
+        Self::try_load(paths).unwrap_or_default()
+    }
+
+    // Missing profiles start from an empty list. A save that can't be read
+    // is moved aside, so the fresh one doesn't overwrite it, and reported.
+    pub fn try_load(paths: &Paths) -> Result<Self, String> {
+        let path = paths.saves();
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Ok(Self::default());
+        };
+
+        ron::from_str(&contents).map_err(|err| {
+            println!(
+                "Failed to parse {}: {}, starting fresh",
+                path.display(),
+                err
+            );
+            let backup = path.with_extension("ron.corrupt");
+            match fs::rename(&path, &backup) {
+                Ok(()) => format!("{}, kept a copy at {}", err, backup.display()),
+                Err(_) => err.to_string(),
+            }
+        })
+    }
+
+    // True once the profiles are on disk
+    pub fn save(&self, paths: &Paths) -> bool {
+        let contents = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(contents) => contents,
+            Err(err) => {
+                println!("Failed to serialize profiles: {}", err);
+                return false;
+            }
+        };
+
+        let path = paths.saves();
+        if let Err(err) = write_file(&path, &contents) {
+            println!("Failed to write {}: {}", path.display(), err);
+            return false;
+        }
+        true
+    }
+
+    // Adds a profile with a default name and returns its index
+    pub fn create(&mut self) -> usize {
+        self.profiles.push(Profile {
+            name: format!("Player {}", self.profiles.len() + 1),
+            ..default()
+        });
+        self.profiles.len() - 1
+    }
+}
+
+// Profile picked by each join slot, as an index into `ProfileStore::profiles`
+#[derive(Resource, Default)]
+pub struct ActiveProfiles {
+    pub slots: Vec<usize>,
+}
+
+impl ActiveProfiles {
+    pub fn profile<'a>(&self, store: &'a ProfileStore, slot: usize) -> Option<&'a Profile> {
+        self.slots
+            .get(slot)
+            .and_then(|&index| store.profiles.get(index))
+    }
+
+    // Next profile after the slot's current one that no other slot is using
+    pub fn next_free(&self, store: &ProfileStore, slot: usize) -> Option<usize> {
+        let count = store.profiles.len();
+        let current = self.slots.get(slot).copied().unwrap_or(count);
+        (1..=count)
+            .map(|offset| (current + offset) % count)
+            .find(|index| !self.slots.contains(index))
+    }
+}
@@ -0,0 +1,254 @@
+// Progression. Every match earns the player's profile XP off their score
+// and kills, and levels unlock colors, trail styles and maps to pick from on
+// the join screen.
+use crate::components::{GridSettings, MapShape, UpperFloor};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// XP for each kill and for winning, on top of a point per point scored
+pub const XP_PER_KILL: u32 = 25;
+pub const XP_FOR_WIN: u32 = 50;
+// XP between level 1 and 2, each level after needs that much more again
+const XP_PER_LEVEL_STEP: u32 = 50;
+
+pub fn xp_for_match(score: u32, kills: u32, won: bool) -> u32 {
+    score + kills * XP_PER_KILL + if won { XP_FOR_WIN } else { 0 }
+}
+
+// Total XP it takes to reach a level, 0 for level 1
+pub fn xp_for_level(level: u32) -> u32 {
+    let level = level.max(1);
+    XP_PER_LEVEL_STEP * level * (level - 1) / 2
+}
+
+pub fn level_for_xp(xp: u32) -> u32 {
+    let mut level = 1;
+    while xp_for_level(level + 1) <= xp {
+        level += 1;
+    }
+    level
+}
+
+pub struct UnlockableColor {
+    pub name: &'static str,
+    pub level: u32,
+    pub color: Color,
+}
+
+pub const COLORS: [UnlockableColor; 8] = [
+    UnlockableColor {
+        name: "Red",
+        level: 1,
+        color: Color::srgb(0.9, 0.2, 0.2),
+    },
+    UnlockableColor {
+        name: "Blue",
+        level: 1,
+        color: Color::srgb(0.2, 0.4, 0.9),
+    },
+    UnlockableColor {
+        name: "Green",
+        level: 1,
+        color: Color::srgb(0.2, 0.75, 0.3),
+    },
+    UnlockableColor {
+        name: "Orange",
+        level: 2,
+        color: Color::srgb(0.95, 0.55, 0.1),
+    },
+    UnlockableColor {
+        name: "Purple",
+        level: 3,
+        color: Color::srgb(0.6, 0.3, 0.85),
+    },
+    UnlockableColor {
+        name: "Teal",
+        level: 4,
+        color: Color::srgb(0.1, 0.7, 0.7),
+    },
+    UnlockableColor {
+        name: "Pink",
+        level: 6,
+        color: Color::srgb(0.95, 0.4, 0.7),
+    },
+    UnlockableColor {
+        name: "Gold",
+        level: 10,
+        color: Color::srgb(0.85, 0.7, 0.1),
+    },
+];
+
+pub fn unlocked_colors(level: u32) -> impl Iterator<Item = &'static UnlockableColor> {
+    COLORS
+        .iter()
+        .filter(move |unlockable| unlockable.level <= level)
+}
+
+// How a player's trail tiles are painted
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrailStyle {
+    #[default]
+    Solid,
+    // Every other pair of tiles faded
+    Dashed,
+    // Every other tile faded
+    Dotted,
+    // Brighter than the player's color
+    Glow,
+}
+
+impl TrailStyle {
+    pub const ALL: [TrailStyle; 4] = [
+        TrailStyle::Solid,
+        TrailStyle::Dashed,
+        TrailStyle::Dotted,
+        TrailStyle::Glow,
+    ];
+
+    pub fn unlock_level(self) -> u32 {
+        match self {
+            TrailStyle::Solid => 1,
+            TrailStyle::Dashed => 2,
+            TrailStyle::Dotted => 4,
+            TrailStyle::Glow => 7,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TrailStyle::Solid => "solid",
+            TrailStyle::Dashed => "dashed",
+            TrailStyle::Dotted => "dotted",
+            TrailStyle::Glow => "glow",
+        }
+    }
+
+    // Color of a trail tile in this style
+    pub fn tile_color(self, color: Color, (x, y): (i32, i32)) -> Color {
+        let faded = color.with_alpha(0.45);
+        match self {
+            TrailStyle::Solid => color.with_alpha(0.8),
+            TrailStyle::Dashed if ((x + y) / 2) % 2 == 0 => color.with_alpha(0.8),
+            TrailStyle::Dashed => faded,
+            TrailStyle::Dotted if (x + y) % 2 == 0 => color.with_alpha(0.8),
+            TrailStyle::Dotted => faded,
+            TrailStyle::Glow => color.lighter(0.2).with_alpha(0.95),
+        }
+    }
+}
+
+pub fn unlocked_trail_styles(level: u32) -> impl Iterator<Item = TrailStyle> {
+    TrailStyle::ALL
+        .into_iter()
+        .filter(move |style| style.unlock_level() <= level)
+}
+
+pub struct UnlockableMap {
+    pub name: &'static str,
+    pub level: u32,
+    pub grid_width: i32,
+    pub grid_height: i32,
+    // Bigger maps use smaller tiles to still fit the window
+    pub tile_size: f32,
+    pub shape: MapShape,
+    // Stairs up to a second floor the size of the first, if it has one
+    pub stairs: &'static [(i32, i32)],
+}
+
+impl UnlockableMap {
+    pub fn grid_settings(&self) -> GridSettings {
+        let grid_settings = GridSettings {
+            grid_width: self.grid_width,
+            grid_height: self.grid_height,
+            tile_size: self.tile_size,
+            shape: self.shape.clone(),
+            ..default()
+        };
+        if self.stairs.is_empty() {
+            return grid_settings;
+        }
+        grid_settings.with_upper_floor(UpperFloor {
+            stairs: self.stairs.to_vec(),
+        })
+    }
+}
+
+// The first is the default map everyone starts with
+pub const MAPS: [UnlockableMap; 8] = [
+    UnlockableMap {
+        name: "Classic",
+        level: 1,
+        grid_width: 40,
+        grid_height: 30,
+        tile_size: 20.0,
+        shape: MapShape::Rectangle,
+        stairs: &[],
+    },
+    UnlockableMap {
+        name: "Skirmish",
+        level: 3,
+        grid_width: 30,
+        grid_height: 22,
+        tile_size: 20.0,
+        shape: MapShape::Rectangle,
+        stairs: &[],
+    },
+    UnlockableMap {
+        name: "Wide",
+        level: 5,
+        grid_width: 56,
+        grid_height: 30,
+        tile_size: 14.0,
+        shape: MapShape::Rectangle,
+        stairs: &[],
+    },
+    UnlockableMap {
+        name: "Continent",
+        level: 8,
+        grid_width: 64,
+        grid_height: 48,
+        tile_size: 12.5,
+        shape: MapShape::Rectangle,
+        stairs: &[],
+    },
+    UnlockableMap {
+        name: "Round",
+        level: 4,
+        grid_width: 40,
+        grid_height: 30,
+        tile_size: 20.0,
+        shape: MapShape::Circle,
+        stairs: &[],
+    },
+    UnlockableMap {
+        name: "Crossroads",
+        level: 6,
+        grid_width: 44,
+        grid_height: 33,
+        tile_size: 18.0,
+        shape: MapShape::Cross,
+        stairs: &[],
+    },
+    UnlockableMap {
+        name: "Archipelago",
+        level: 9,
+        grid_width: 48,
+        grid_height: 36,
+        tile_size: 16.0,
+        shape: MapShape::Islands,
+        stairs: &[],
+    },
+    UnlockableMap {
+        name: "Townhouse",
+        level: 7,
+        grid_width: 28,
+        grid_height: 24,
+        tile_size: 14.0,
+        shape: MapShape::Rectangle,
+        stairs: &[(3, 3), (24, 3), (3, 20), (24, 20)],
+    },
+];
+
+pub fn unlocked_maps(level: u32) -> impl Iterator<Item = &'static UnlockableMap> {
+    MAPS.iter().filter(move |map| map.level <= level)
+}
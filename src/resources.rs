@@ -1,5 +1,12 @@
 // resources.rs
+use crate::territory::RegionSummary;
+use crate::win_condition::WinCondition;
 use bevy::prelude::*;
+use bevy::tasks::Task;
+use fixedbitset::FixedBitSet;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 #[derive(Resource)]
@@ -7,6 +14,8 @@ pub struct GameState {
     pub timer: Timer,
     pub player_scores: HashMap<Entity, u32>,
     pub game_running: bool,
+    // Set once the final minute starts
+    pub hurry_up: bool,
 }
 
 impl Default for GameState {
@@ -15,13 +24,654 @@ impl Default for GameState {
             timer: Timer::from_seconds(300.0, TimerMode::Once), // 5 minutes
             player_scores: HashMap::new(),
             game_running: false,
+            hurry_up: false,
         }
     }
 }
 
+// Seed for everything random about the next match's map, where hazard walls
+// come in and pickups turn up. A fresh one after every match unless a share
+// code picks it.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchSeed(pub u32);
+
+impl Default for MatchSeed {
+    fn default() -> Self {
+        Self(rand::rng().random())
+    }
+}
+
+// Random numbers for the map, from the match seed
+#[derive(Resource)]
+pub struct MatchRng(pub StdRng);
+
+impl MatchRng {
+    pub fn new(seed: MatchSeed) -> Self {
+        Self(StdRng::seed_from_u64(seed.0 as u64))
+    }
+}
+
+impl Default for MatchRng {
+    fn default() -> Self {
+        Self::new(MatchSeed::default())
+    }
+}
+
+// Result of a claim computed off the main thread
+pub struct ClaimResult {
+    pub player: Entity,
+    pub trail_tiles: Vec<(i32, i32)>,
+    pub pockets: Vec<Vec<(i32, i32)>>,
+}
+
+// Claim flood fills still running on the async compute pool. Each task is one
+// tick's batch of completed trails, with results already in priority order.
 #[derive(Resource, Default)]
-pub struct CompleteTrail {
-    pub player: Option<Entity>,
-    pub complete: bool,
-    pub entry_point: Option<(i32, i32)>,
+pub struct PendingClaims {
+    // Batches numbered in the order they were started, with the players whose
+    // loops are in each. Applied strictly in that order.
+    pub tasks: Vec<(u64, Vec<Entity>, Task<Vec<ClaimResult>>)>,
+    pub next_batch: u64,
+    // Players that died while a claim of theirs was being computed, with the
+    // first batch started after. Their results from earlier batches are
+    // dropped, later ones are from their new life.
+    pub cancelled: HashMap<Entity, u64>,
+}
+
+impl PendingClaims {
+    pub fn push(&mut self, players: Vec<Entity>, task: Task<Vec<ClaimResult>>) {
+        self.tasks.push((self.next_batch, players, task));
+        self.next_batch += 1;
+    }
+
+    // Drops the player's claims that are still being computed
+    pub fn cancel(&mut self, player: Entity) {
+        if !self.tasks.is_empty() {
+            self.cancelled.insert(player, self.next_batch);
+        }
+    }
+
+    // Whether the result from that batch came before the player died
+    pub fn is_cancelled(&self, player: Entity, batch: u64) -> bool {
+        self.cancelled
+            .get(&player)
+            .is_some_and(|&restarted| batch < restarted)
+    }
+}
+
+// How close (in tiles) an enemy has to get to your trail before it is flagged
+#[derive(Resource)]
+pub struct ProximitySettings {
+    pub warning_radius: i32,
+}
+
+impl Default for ProximitySettings {
+    fn default() -> Self {
+        Self { warning_radius: 5 }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TrailThreat {
+    pub enemy: Entity,
+    pub distance: i32,
+}
+
+// Enemies currently near the local player's trail, refreshed every frame
+#[derive(Resource, Default)]
+pub struct ProximityWarnings {
+    pub threats: Vec<TrailThreat>,
+}
+
+// How much trouble the local player is in, from 0.0 (safe) to 1.0 (about to
+// die). Drives the music intensity.
+#[derive(Resource, Default)]
+pub struct DangerScore {
+    pub value: f32,
+}
+
+// What a player loses when they die
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathPenalty {
+    // Lose everything and start over with fresh starting territory
+    FullReset,
+    // Lose only the trail that was being drawn
+    TrailOnly,
+    // Lose the trail plus this fraction (0.0 - 1.0) of territory, outermost ring first
+    ShrinkTerritory { fraction: f32 },
+    // Lose the trail plus the outer `rings` rings of territory
+    ErodeRings { rings: u32 },
+}
+
+// Where a player comes back after dying
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RespawnLocation {
+    // Their original spawn tile, or the closest owned tile to it
+    SpawnPoint,
+    // The owned tile closest to where they died
+    NearestToDeath,
+}
+
+// Match rules that can be tuned per mode
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+    pub death_penalty: DeathPenalty,
+    pub respawn_location: RespawnLocation,
+    // If set, every player's territory loses its outer ring this often (seconds)
+    pub territory_decay_interval: Option<f32>,
+    // Claims score double in the last minute
+    pub hurry_up_double_claims: bool,
+    // If set, bots are tuned within these bounds during single-player matches
+    pub dynamic_difficulty: Option<DifficultyBounds>,
+    // If set, pickups spawn during matches
+    pub pickups: Option<PickupRules>,
+    // Registered brain bots play with, the built-in one if unset
+    pub bot_brain: Option<String>,
+    // Running through someone else's trail kills them
+    pub trail_cuts: bool,
+    // If set, the first player to meet it wins without waiting for the clock,
+    // e.g. "territory_pct >= 0.5 || kills >= 5"
+    pub win_condition: Option<WinCondition>,
+    // If set, the playable area closes in a ring at a time
+    pub shrinking_zone: Option<ZoneRules>,
+    // If set, deadly walls sweep across the map on a schedule
+    pub hazard_walls: Option<HazardRules>,
+    // If set, rain, fog and wind come and go on a schedule
+    pub weather: Option<WeatherRules>,
+    // If set, players earn energy by claiming and spend it on abilities
+    pub energy: Option<EnergyRules>,
+    // If set, the leader carries a bounty whoever kills them collects
+    pub bounty: Option<BountyRules>,
+    // If set, the last placed player gets a shield and a little speed while
+    // they hold almost nothing
+    pub comeback: Option<ComebackRules>,
+    // If set, dead players sit out this many seconds watching their killer
+    // instead of just the kill cam, then come back somewhere safe
+    pub respawn_delay: Option<f32>,
+    pub game_speed: GameSpeed,
+    // How many points a drawn trail keeps, and how closely to its path
+    pub trail_points: TrailPointRules,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            death_penalty: DeathPenalty::FullReset,
+            respawn_location: RespawnLocation::SpawnPoint,
+            territory_decay_interval: None,
+            hurry_up_double_claims: false,
+            dynamic_difficulty: None,
+            pickups: None,
+            bot_brain: None,
+            trail_cuts: false,
+            win_condition: None,
+            shrinking_zone: None,
+            hazard_walls: None,
+            weather: None,
+            energy: None,
+            bounty: None,
+            comeback: None,
+            respawn_delay: None,
+            game_speed: GameSpeed::Normal,
+            trail_points: TrailPointRules::default(),
+        }
+    }
+}
+
+impl GameRules {
+    // Dying only costs the trail, and the player comes back inside their land
+    pub fn casual() -> Self {
+        Self {
+            death_penalty: DeathPenalty::TrailOnly,
+            respawn_location: RespawnLocation::NearestToDeath,
+            territory_decay_interval: None,
+            hurry_up_double_claims: false,
+            dynamic_difficulty: Some(DifficultyBounds::default()),
+            pickups: Some(PickupRules::default()),
+            bot_brain: None,
+            trail_cuts: false,
+            win_condition: None,
+            shrinking_zone: None,
+            hazard_walls: None,
+            weather: None,
+            energy: Some(EnergyRules::default()),
+            bounty: Some(BountyRules::default()),
+            comeback: Some(ComebackRules::default()),
+            respawn_delay: None,
+            game_speed: GameSpeed::Normal,
+            trail_points: TrailPointRules::default(),
+        }
+    }
+}
+
+// Pace of a match. Faster speeds run the whole simulation (movement, bots,
+// clocks) quicker and have players cover more ground per second of it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameSpeed {
+    Slow,
+    #[default]
+    Normal,
+    Blitz,
+}
+
+// Tiles per match second players start out moving at
+pub const PLAYER_SPEED: f32 = 5.0;
+
+impl GameSpeed {
+    pub const ALL: [GameSpeed; 3] = [GameSpeed::Slow, GameSpeed::Normal, GameSpeed::Blitz];
+
+    // Match seconds that pass in a second of real time, so how much each
+    // tick simulates. Movement and the clock both run on match time, so
+    // this is all it takes to speed a match up.
+    pub fn time_scale(self) -> f32 {
+        match self {
+            GameSpeed::Slow => 0.8,
+            GameSpeed::Normal => 1.0,
+            GameSpeed::Blitz => 1.25,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameSpeed::Slow => "slow",
+            GameSpeed::Normal => "normal",
+            GameSpeed::Blitz => "blitz",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&speed| speed == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+// How far the difficulty director may push bots, from its easiest setting
+// to its hardest
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultyBounds {
+    pub min_aggression: f32,
+    pub max_aggression: f32,
+    pub min_speed_scale: f32,
+    pub max_speed_scale: f32,
+}
+
+impl Default for DifficultyBounds {
+    fn default() -> Self {
+        Self {
+            min_aggression: 0.2,
+            max_aggression: 0.9,
+            min_speed_scale: 0.8,
+            max_speed_scale: 1.2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PickupRules {
+    // Seconds between spawns
+    pub interval: f32,
+    pub max_active: usize,
+    // Bias spawns towards trailing players and contested ground. Turn off for
+    // competitive play to get evenly random spawns.
+    pub directed: bool,
+}
+
+impl Default for PickupRules {
+    fn default() -> Self {
+        Self {
+            interval: 8.0,
+            max_active: 3,
+            directed: true,
+        }
+    }
+}
+
+// Schedule of the shrinking zone, in seconds of match time
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZoneRules {
+    // Until the first ring closes
+    pub first_shrink: f32,
+    // Between rings after that
+    pub interval: f32,
+    // The zone stops shrinking along a side once it's this many tiles across
+    pub min_size: i32,
+}
+
+impl Default for ZoneRules {
+    fn default() -> Self {
+        Self {
+            first_shrink: 30.0,
+            interval: 10.0,
+            min_size: 10,
+        }
+    }
+}
+
+// Schedule of the sweeping hazard walls, in seconds of match time
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HazardRules {
+    // Until the first wall starts moving
+    pub first_wall: f32,
+    // Between walls after that
+    pub interval: f32,
+    // Walls are announced this long before they start moving
+    pub warning: f32,
+    // Tiles a wall moves per second
+    pub speed: f32,
+    // Tiles along the wall
+    pub length: i32,
+}
+
+impl Default for HazardRules {
+    fn default() -> Self {
+        Self {
+            first_wall: 20.0,
+            interval: 25.0,
+            warning: 5.0,
+            speed: 4.0,
+            length: 12,
+        }
+    }
+}
+
+// Schedule of the weather, in seconds of match time, and how much each kind
+// changes the match while it lasts
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeatherRules {
+    // Until the first front sets in
+    pub first_front: f32,
+    // Between fronts setting in after that
+    pub interval: f32,
+    // Fronts are announced this long before they set in
+    pub warning: f32,
+    // How long a front lasts
+    pub duration: f32,
+    // Fraction of everyone's speed rain takes away
+    pub rain_slowdown: f32,
+    // Tiles local players can still see around themselves in fog
+    pub fog_radius: f32,
+    // Fraction of speed gained running with the wind, and lost running into it
+    pub wind_push: f32,
+}
+
+impl Default for WeatherRules {
+    fn default() -> Self {
+        Self {
+            first_front: 30.0,
+            interval: 45.0,
+            warning: 5.0,
+            duration: 20.0,
+            rain_slowdown: 0.1,
+            fog_radius: 6.0,
+            wind_push: 0.2,
+        }
+    }
+}
+
+// How energy is earned and what abilities cost
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyRules {
+    // Tiles claimed for each point of energy
+    pub tiles_per_energy: u32,
+    pub max_energy: u32,
+    pub bomb_cost: u32,
+    pub boost_cost: u32,
+}
+
+impl Default for EnergyRules {
+    fn default() -> Self {
+        Self {
+            tiles_per_energy: 5,
+            max_energy: 10,
+            bomb_cost: 6,
+            boost_cost: 3,
+        }
+    }
+}
+
+// Bonus on the leader's head
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BountyRules {
+    // Points the player who kills the leader gets on top of their score
+    pub points: u32,
+    // Score the leader needs before there's a bounty on them at all
+    pub min_score: u32,
+}
+
+impl Default for BountyRules {
+    fn default() -> Self {
+        Self {
+            points: 50,
+            min_score: 30,
+        }
+    }
+}
+
+// Points kept for drawing a trail. A point in line with the two before it
+// replaces the last one rather than adding to them, and past the cap the
+// oldest ones go.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrailPointRules {
+    pub max_points: usize,
+    // How far off the line (world units) a point can be and still count as on it
+    pub tolerance: f32,
+}
+
+impl Default for TrailPointRules {
+    fn default() -> Self {
+        Self {
+            max_points: 256,
+            tolerance: 0.5,
+        }
+    }
+}
+
+// Rubber-banding for whoever is furthest behind
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComebackRules {
+    // Share of the map's value below which the last placed player gets help
+    pub max_share: f32,
+    // Extra speed while they have it, 0.1 is 10% faster
+    pub speed_bonus: f32,
+}
+
+impl Default for ComebackRules {
+    fn default() -> Self {
+        Self {
+            max_share: 0.05,
+            speed_bonus: 0.1,
+        }
+    }
+}
+
+// Named rule sets that can be picked in the config or at match setup
+// instead of spelling out rules
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RulesPreset {
+    Classic,
+    Casual,
+    // Classic rules in an arena that closes in
+    ShrinkingZone,
+    // Fast, and kills pay: trails can be cut, the leader carries a bounty
+    // and dying starts a player over
+    Aggressive,
+    // Slow, and building pays: dying only costs part of the land, close to
+    // where it happened, and nobody's trail can be cut
+    Turtle,
+}
+
+// Seconds out after a death in the shrinking zone, where sitting out costs
+const SHRINKING_ZONE_RESPAWN_DELAY: f32 = 6.0;
+
+impl RulesPreset {
+    pub const ALL: [RulesPreset; 5] = [
+        RulesPreset::Classic,
+        RulesPreset::Aggressive,
+        RulesPreset::Turtle,
+        RulesPreset::Casual,
+        RulesPreset::ShrinkingZone,
+    ];
+
+    pub fn rules(self) -> GameRules {
+        match self {
+            RulesPreset::Classic => GameRules::default(),
+            RulesPreset::Casual => GameRules::casual(),
+            RulesPreset::ShrinkingZone => GameRules {
+                shrinking_zone: Some(ZoneRules::default()),
+                respawn_delay: Some(SHRINKING_ZONE_RESPAWN_DELAY),
+                ..GameRules::default()
+            },
+            RulesPreset::Aggressive => GameRules {
+                death_penalty: DeathPenalty::FullReset,
+                hurry_up_double_claims: true,
+                trail_cuts: true,
+                bounty: Some(BountyRules {
+                    points: 80,
+                    min_score: 20,
+                }),
+                game_speed: GameSpeed::Blitz,
+                ..GameRules::default()
+            },
+            RulesPreset::Turtle => GameRules {
+                death_penalty: DeathPenalty::ShrinkTerritory { fraction: 0.25 },
+                respawn_location: RespawnLocation::NearestToDeath,
+                trail_cuts: false,
+                comeback: Some(ComebackRules::default()),
+                game_speed: GameSpeed::Slow,
+                ..GameRules::default()
+            },
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RulesPreset::Classic => "Classic",
+            RulesPreset::Casual => "Casual",
+            RulesPreset::ShrinkingZone => "Shrinking zone",
+            RulesPreset::Aggressive => "Aggressive",
+            RulesPreset::Turtle => "Turtle",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&preset| preset == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+// Preset the match's rules came from, None for rules spelled out by hand or
+// read from a share code. Results and telemetry carry its name, so matches
+// played under different presets can be compared.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ActivePreset(pub Option<RulesPreset>);
+
+impl ActivePreset {
+    pub fn name(self) -> &'static str {
+        self.0.map_or("Custom", RulesPreset::name)
+    }
+}
+
+// Connected regions owned by one player, largest first
+#[derive(Clone, Debug, Default)]
+pub struct PlayerRegions {
+    pub regions: Vec<RegionSummary>,
+}
+
+impl PlayerRegions {
+    pub fn largest(&self) -> Option<&RegionSummary> {
+        self.regions.first()
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.regions.len()
+    }
+}
+
+// Per-player territory breakdown, rebuilt whenever tiles change hands
+#[derive(Resource, Default)]
+pub struct TerritoryAnalysis {
+    pub players: HashMap<Entity, PlayerRegions>,
+}
+
+impl TerritoryAnalysis {
+    pub fn get(&self, player: Entity) -> Option<&PlayerRegions> {
+        self.players.get(&player)
+    }
+}
+
+// Land of every player as a dense row-major bitset, kept in step with the tiles
+#[derive(Resource, Default)]
+pub struct OwnershipLayers {
+    pub width: i32,
+    pub height: i32,
+    pub layers: HashMap<Entity, FixedBitSet>,
+    // Points per tile in the same order, every tile counts 1 if empty
+    pub values: Vec<u32>,
+}
+
+impl OwnershipLayers {
+    pub fn layer(&self, player: Entity) -> Option<&FixedBitSet> {
+        self.layers.get(&player)
+    }
+
+    pub fn tile_count(&self, player: Entity) -> u32 {
+        self.layer(player)
+            .map_or(0, |layer| layer.count_ones(..) as u32)
+    }
+
+    // Points the player's land is worth
+    pub fn territory_value(&self, player: Entity) -> u32 {
+        let Some(layer) = self.layer(player) else {
+            return 0;
+        };
+        if self.values.is_empty() {
+            return layer.count_ones(..) as u32;
+        }
+        layer.ones().map(|index| self.values[index]).sum()
+    }
+
+    // Points the whole map is worth, for territory shares
+    pub fn total_value(&self) -> u32 {
+        if self.values.is_empty() {
+            return (self.width * self.height) as u32;
+        }
+        self.values.iter().sum()
+    }
+
+    // Moves a tile to `owner`'s layer, clearing it from everyone else's
+    pub fn set_owner(&mut self, x: i32, y: i32, owner: Option<Entity>) {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return;
+        }
+
+        let index = (y * self.width + x) as usize;
+        let cells = (self.width * self.height) as usize;
+
+        for layer in self.layers.values_mut() {
+            layer.set(index, false);
+        }
+
+        if let Some(owner) = owner {
+            self.layers
+                .entry(owner)
+                .or_insert_with(|| FixedBitSet::with_capacity(cells))
+                .insert(index);
+        }
+    }
 }
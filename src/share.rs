@@ -0,0 +1,277 @@
+// Share codes: a match setup written as a short code someone else can type
+// in on the join screen to play the same thing. A code carries the map, the
+// seed for everything random about the map, and the rules that make the
+// mode, along with what the map's edges do. Rules a code has no room for are
+// left at their defaults.
+//
+// The code is 11 bytes in base32, in the invite alphabet so it can be read
+// out loud. Its first byte is the version, its last a checksum.
+use crate::components::GridTopology;
+use crate::net::invite::CODE_ALPHABET;
+use crate::progression::MAPS;
+use crate::resources::{
+    BountyRules, ComebackRules, DeathPenalty, DifficultyBounds, EnergyRules, GameRules, GameSpeed,
+    HazardRules, PickupRules, RespawnLocation, WeatherRules, ZoneRules,
+};
+use std::fmt;
+
+pub const SHARE_CODE_VERSION: u8 = 1;
+const CODE_BYTES: usize = 11;
+// Characters in a code, leaving out the dashes between groups
+pub const SHARE_CODE_LENGTH: usize = (CODE_BYTES * 8).div_ceil(5);
+const GROUP_LENGTH: usize = 6;
+
+// Optional modes, a bit each
+const PICKUPS: u8 = 1 << 0;
+const ENERGY: u8 = 1 << 1;
+const BOUNTY: u8 = 1 << 2;
+const COMEBACK: u8 = 1 << 3;
+const SHRINKING_ZONE: u8 = 1 << 4;
+const HAZARD_WALLS: u8 = 1 << 5;
+const DYNAMIC_DIFFICULTY: u8 = 1 << 6;
+const HURRY_UP: u8 = 1 << 7;
+// And the rest, with the game speed and kind of death penalty in two bits
+// each
+const TRAIL_CUTS: u8 = 1 << 0;
+const RESPAWN_NEAREST: u8 = 1 << 1;
+const SPEED_SHIFT: u8 = 2;
+const PENALTY_SHIFT: u8 = 4;
+const MIRRORED_EDGES: u8 = 1 << 6;
+const WEATHER: u8 = 1 << 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShareCodeError {
+    Length(usize),
+    Character(char),
+    // From another version of the game
+    Version(u8),
+    Checksum,
+    Map(u8),
+    Rules,
+}
+
+impl fmt::Display for ShareCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShareCodeError::Length(length) => write!(
+                f,
+                "codes are {} characters, that's {}",
+                SHARE_CODE_LENGTH, length
+            ),
+            ShareCodeError::Character(c) => write!(f, "{} isn't used in codes", c),
+            ShareCodeError::Version(version) => write!(
+                f,
+                "code is version {}, this game reads version {}",
+                version, SHARE_CODE_VERSION
+            ),
+            ShareCodeError::Checksum => write!(f, "code doesn't check out, is there a typo?"),
+            ShareCodeError::Map(map) => write!(f, "no map {} in this version", map),
+            ShareCodeError::Rules => write!(f, "code has rules this version can't play"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ShareCode {
+    // Index into `progression::MAPS`
+    pub map: usize,
+    pub seed: u32,
+    pub topology: GridTopology,
+    pub rules: GameRules,
+}
+
+impl ShareCode {
+    // Just as much of the setup as a code can carry
+    pub fn new(map: usize, seed: u32, topology: GridTopology, rules: &GameRules) -> Self {
+        let code = Self {
+            map,
+            seed,
+            topology,
+            rules: rules.clone(),
+        };
+        Self::from_bytes(code.to_bytes()).unwrap_or(code)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ShareCodeError> {
+        let characters: Vec<char> = text
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if characters.len() != SHARE_CODE_LENGTH {
+            return Err(ShareCodeError::Length(characters.len()));
+        }
+
+        let mut bytes = [0; CODE_BYTES];
+        let (mut buffer, mut bits, mut filled) = (0u32, 0, 0);
+        for c in characters {
+            let value = CODE_ALPHABET
+                .iter()
+                .position(|&letter| letter as char == c)
+                .ok_or(ShareCodeError::Character(c))?;
+            buffer = (buffer << 5) | value as u32;
+            bits += 5;
+            if bits >= 8 && filled < CODE_BYTES {
+                bits -= 8;
+                bytes[filled] = (buffer >> bits) as u8;
+                filled += 1;
+            }
+        }
+        Self::from_bytes(bytes)
+    }
+
+    fn from_bytes(bytes: [u8; CODE_BYTES]) -> Result<Self, ShareCodeError> {
+        let [version, map, modes, flags, penalty_amount, respawn_delay, seed @ .., check] = bytes;
+        if checksum(&bytes[..CODE_BYTES - 1]) != check {
+            return Err(ShareCodeError::Checksum);
+        }
+        if version != SHARE_CODE_VERSION {
+            return Err(ShareCodeError::Version(version));
+        }
+        if map as usize >= MAPS.len() {
+            return Err(ShareCodeError::Map(map));
+        }
+
+        let game_speed = *GameSpeed::ALL
+            .get((flags >> SPEED_SHIFT & 0b11) as usize)
+            .ok_or(ShareCodeError::Rules)?;
+        let death_penalty = match flags >> PENALTY_SHIFT & 0b11 {
+            0 => DeathPenalty::FullReset,
+            1 => DeathPenalty::TrailOnly,
+            2 if penalty_amount <= 100 => DeathPenalty::ShrinkTerritory {
+                fraction: penalty_amount as f32 / 100.0,
+            },
+            3 => DeathPenalty::ErodeRings {
+                rings: penalty_amount as u32,
+            },
+            _ => return Err(ShareCodeError::Rules),
+        };
+        let has = |mode: u8| modes & mode != 0;
+
+        Ok(Self {
+            map: map as usize,
+            seed: u32::from_le_bytes(seed),
+            topology: if flags & MIRRORED_EDGES != 0 {
+                GridTopology::Mirrored
+            } else {
+                GridTopology::Clamp
+            },
+            rules: GameRules {
+                death_penalty,
+                respawn_location: if flags & RESPAWN_NEAREST != 0 {
+                    RespawnLocation::NearestToDeath
+                } else {
+                    RespawnLocation::SpawnPoint
+                },
+                hurry_up_double_claims: has(HURRY_UP),
+                dynamic_difficulty: has(DYNAMIC_DIFFICULTY).then(DifficultyBounds::default),
+                pickups: has(PICKUPS).then(PickupRules::default),
+                trail_cuts: flags & TRAIL_CUTS != 0,
+                shrinking_zone: has(SHRINKING_ZONE).then(ZoneRules::default),
+                hazard_walls: has(HAZARD_WALLS).then(HazardRules::default),
+                weather: (flags & WEATHER != 0).then(WeatherRules::default),
+                energy: has(ENERGY).then(EnergyRules::default),
+                bounty: has(BOUNTY).then(BountyRules::default),
+                comeback: has(COMEBACK).then(ComebackRules::default),
+                respawn_delay: (respawn_delay > 0).then_some(respawn_delay as f32),
+                game_speed,
+                ..GameRules::default()
+            },
+        })
+    }
+
+    fn to_bytes(&self) -> [u8; CODE_BYTES] {
+        let rules = &self.rules;
+        let mut modes = 0;
+        for (mode, on) in [
+            (PICKUPS, rules.pickups.is_some()),
+            (ENERGY, rules.energy.is_some()),
+            (BOUNTY, rules.bounty.is_some()),
+            (COMEBACK, rules.comeback.is_some()),
+            (SHRINKING_ZONE, rules.shrinking_zone.is_some()),
+            (HAZARD_WALLS, rules.hazard_walls.is_some()),
+            (DYNAMIC_DIFFICULTY, rules.dynamic_difficulty.is_some()),
+            (HURRY_UP, rules.hurry_up_double_claims),
+        ] {
+            if on {
+                modes |= mode;
+            }
+        }
+
+        let (penalty, penalty_amount) = match rules.death_penalty {
+            DeathPenalty::FullReset => (0, 0),
+            DeathPenalty::TrailOnly => (1, 0),
+            DeathPenalty::ShrinkTerritory { fraction } => {
+                (2, (fraction * 100.0).round().clamp(0.0, 100.0) as u8)
+            }
+            DeathPenalty::ErodeRings { rings } => (3, rings.min(u8::MAX as u32) as u8),
+        };
+        let speed = GameSpeed::ALL
+            .iter()
+            .position(|&speed| speed == rules.game_speed)
+            .unwrap_or(1) as u8;
+        let mut flags = speed << SPEED_SHIFT | penalty << PENALTY_SHIFT;
+        if rules.trail_cuts {
+            flags |= TRAIL_CUTS;
+        }
+        if rules.respawn_location == RespawnLocation::NearestToDeath {
+            flags |= RESPAWN_NEAREST;
+        }
+        if self.topology == GridTopology::Mirrored {
+            flags |= MIRRORED_EDGES;
+        }
+        if rules.weather.is_some() {
+            flags |= WEATHER;
+        }
+        // Whole seconds, but never rounded down to no delay at all
+        let respawn_delay = rules
+            .respawn_delay
+            .map_or(0, |delay| delay.round().clamp(1.0, u8::MAX as f32) as u8);
+
+        let mut bytes = [0; CODE_BYTES];
+        bytes[..6].copy_from_slice(&[
+            SHARE_CODE_VERSION,
+            self.map.min(u8::MAX as usize) as u8,
+            modes,
+            flags,
+            penalty_amount,
+            respawn_delay,
+        ]);
+        bytes[6..10].copy_from_slice(&self.seed.to_le_bytes());
+        bytes[10] = checksum(&bytes[..10]);
+        bytes
+    }
+}
+
+// Grouped with dashes, which parsing skips
+impl fmt::Display for ShareCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut characters = String::new();
+        let (mut buffer, mut bits) = (0u32, 0);
+        for byte in self.to_bytes() {
+            buffer = (buffer << 8) | byte as u32;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                characters.push(CODE_ALPHABET[(buffer >> bits & 0b11111) as usize] as char);
+            }
+        }
+        if bits > 0 {
+            characters.push(CODE_ALPHABET[(buffer << (5 - bits) & 0b11111) as usize] as char);
+        }
+
+        let groups: Vec<&str> = characters
+            .as_bytes()
+            .chunks(GROUP_LENGTH)
+            .map(|group| std::str::from_utf8(group).unwrap_or_default())
+            .collect();
+        write!(f, "{}", groups.join("-"))
+    }
+}
+
+// Order matters, so swapped characters are caught too
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0x5a, |check: u8, &byte| check.rotate_left(3) ^ byte)
+}
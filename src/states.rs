@@ -0,0 +1,52 @@
+// states.rs
+use bevy::prelude::*;
+
+// Top-level flow of the game
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AppState {
+    // Local players press a button on their device to take a slot
+    #[default]
+    Join,
+    Playing,
+    // Practice mode: no timer, instant respawns and a panel of knobs
+    Sandbox,
+    // Picking a puzzle level from the campaign
+    LevelSelect,
+    // Picking a server to join, from the LAN or the master server's list
+    ServerBrowser,
+    // Connected to a server, which runs the simulation for us
+    Online,
+    // Dedicated server between matches, while clients vote on the next map
+    Intermission,
+    // Bracket of a local tournament, between its matches
+    Tournament,
+}
+
+// Whether a local match is running or held, while the window is away or
+// for a photo
+#[derive(SubStates, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[source(AppState = AppState::Playing | AppState::Sandbox)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+    Photo,
+}
+
+// Stages of a gameplay frame, run in this order so every system sees the
+// tiles in a predictable state
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameSet {
+    // Read devices into direction intents
+    Input,
+    // Move players and lay down trail tiles
+    Movement,
+    // Record where players have been
+    TrailUpdate,
+    // Detect and resolve deaths
+    Collision,
+    // Turn closed loops into territory
+    Claim,
+    // Visuals and feedback that only read the settled state
+    Render,
+}
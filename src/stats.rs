@@ -0,0 +1,88 @@
+// stats.rs
+// Statistics gathered across matches, such as where players die and claim
+// land, for map design. Stored as RON alongside the config.
+use crate::paths::{write_file, Paths};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+
+// Count per tile of something that happened there
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TileCounts {
+    pub counts: HashMap<(i32, i32), u32>,
+}
+
+impl TileCounts {
+    pub fn add(&mut self, tile: (i32, i32)) {
+        *self.counts.entry(tile).or_insert(0) += 1;
+    }
+
+    pub fn get(&self, tile: (i32, i32)) -> u32 {
+        self.counts.get(&tile).copied().unwrap_or(0)
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.values().copied().max().unwrap_or(0)
+    }
+
+    // Count relative to the busiest tile, 0 to 1
+    pub fn intensity(&self, tile: (i32, i32)) -> f32 {
+        match self.max() {
+            0 => 0.0,
+            max => self.get(tile) as f32 / max as f32,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Heatmap {
+    pub deaths: TileCounts,
+    // Tiles taken by closing a loop, trail included
+    pub claims: TileCounts,
+}
+
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsStore {
+    pub heatmap: Heatmap,
+}
+
+impl StatsStore {
+    // Missing or unreadable stats start from nothing
+    pub fn load(paths: &Paths) -> Self {
+        let path = paths.stats();
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        match ron::from_str(&contents) {
+            Ok(store) => store,
+            Err(err) => {
+                println!(
+                    "Failed to parse {}: {}, starting fresh",
+                    path.display(),
+                    err
+                );
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, paths: &Paths) {
+        let contents = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(contents) => contents,
+            Err(err) => {
+                println!("Failed to serialize stats: {}", err);
+                return;
+            }
+        };
+
+        let path = paths.stats();
+        if let Err(err) = write_file(&path, &contents) {
+            println!("Failed to write {}: {}", path.display(), err);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// Abilities and the energy that pays for them. Claiming tiles fills a
+// player's energy meter, a point for every few tiles, and each ability
+// spends some of it. Costs and the cap come from the rules.
+use crate::components::{GridSettings, LocalPlayer, Player, Respawning};
+use crate::events::ClaimComputedEvent;
+use crate::resources::{EnergyRules, GameRules};
+use crate::systems::input::{InputDevice, InputSource};
+use crate::systems::pickups::{start_speed_boost, SpeedBoost};
+use bevy::prelude::*;
+
+// Tiles in each direction a bomb claims, a 3x3 square
+pub const BOMB_RADIUS: i32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ability {
+    // Claims the free tiles around the player without closing a loop. The
+    // claim goes through the same apply step as a loop, so scoring and claim
+    // rules treat it the same.
+    Bomb,
+    // The same burst of speed as the pickup
+    Boost,
+}
+
+impl Ability {
+    pub fn cost(self, rules: &EnergyRules) -> u32 {
+        match self {
+            Ability::Bomb => rules.bomb_cost,
+            Ability::Boost => rules.boost_cost,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct Energy {
+    pub amount: u32,
+    // Tiles claimed towards the next point
+    pub tiles: u32,
+    // Set by input, handled and cleared with the claims
+    pub requested: Option<Ability>,
+}
+
+impl Energy {
+    pub fn add_tiles(&mut self, tiles: u32, rules: &EnergyRules) {
+        let per_point = rules.tiles_per_energy.max(1);
+        self.tiles += tiles;
+        self.amount = (self.amount + self.tiles / per_point).min(rules.max_energy);
+        self.tiles %= per_point;
+        // Nothing builds up towards a point while the meter is full
+        if self.amount == rules.max_energy {
+            self.tiles = 0;
+        }
+    }
+
+    // Pays for an ability if there's enough energy
+    pub fn spend(&mut self, cost: u32) -> bool {
+        if self.amount < cost {
+            return false;
+        }
+        self.amount -= cost;
+        true
+    }
+}
+
+// Hands every player an energy meter while the rules have one
+pub fn grant_energy_system(
+    mut commands: Commands,
+    rules: Res<GameRules>,
+    player_query: Query<Entity, (With<Player>, Without<Energy>)>,
+) {
+    if rules.energy.is_none() {
+        return;
+    }
+    for entity in player_query.iter() {
+        commands.entity(entity).insert(Energy::default());
+    }
+}
+
+// Bomb on space, right shift or the south button; boost on left shift,
+// right control or the east button
+pub fn ability_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut query: Query<(&InputSource, &mut Energy)>,
+) {
+    for (source, mut energy) in query.iter_mut() {
+        let InputSource::Device(device) = source else {
+            continue;
+        };
+
+        let (bomb, boost) = match *device {
+            InputDevice::KeyboardWasd => (
+                keyboard_input.just_pressed(KeyCode::Space),
+                keyboard_input.just_pressed(KeyCode::ShiftLeft),
+            ),
+            InputDevice::KeyboardArrows => (
+                keyboard_input.just_pressed(KeyCode::ShiftRight),
+                keyboard_input.just_pressed(KeyCode::ControlRight),
+            ),
+            InputDevice::Gamepad(gamepad_entity) => match gamepads.get(gamepad_entity) {
+                Ok(gamepad) => (
+                    gamepad.just_pressed(GamepadButton::South),
+                    gamepad.just_pressed(GamepadButton::East),
+                ),
+                Err(_) => (false, false),
+            },
+        };
+        if bomb {
+            energy.requested = Some(Ability::Bomb);
+        } else if boost {
+            energy.requested = Some(Ability::Boost);
+        }
+    }
+}
+
+type AbilityUserQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Player,
+        &'static mut Energy,
+        Option<&'static mut SpeedBoost>,
+        Has<Respawning>,
+    ),
+>;
+
+// Uses requested abilities the player can afford
+pub fn use_abilities_system(
+    mut commands: Commands,
+    rules: Res<GameRules>,
+    grid_settings: Res<GridSettings>,
+    mut player_query: AbilityUserQuery,
+    mut claim_events: EventWriter<ClaimComputedEvent>,
+) {
+    let Some(energy_rules) = rules.energy else {
+        return;
+    };
+
+    for (entity, mut player, mut energy, boost, respawning) in player_query.iter_mut() {
+        let Some(ability) = energy.requested.take() else {
+            continue;
+        };
+        if respawning || !energy.spend(ability.cost(&energy_rules)) {
+            continue;
+        }
+
+        match ability {
+            Ability::Bomb => {
+                let (x, y) = player.last_tile_pos;
+                let blast = (-BOMB_RADIUS..=BOMB_RADIUS)
+                    .flat_map(|dy| (-BOMB_RADIUS..=BOMB_RADIUS).map(move |dx| (x + dx, y + dy)))
+                    .filter(|&(tx, ty)| {
+                        tx >= 0
+                            && tx < grid_settings.grid_width
+                            && ty >= 0
+                            && ty < grid_settings.grid_height
+                    })
+                    .collect();
+
+                println!("💣 Territory bomb set off at ({}, {})", x, y);
+                claim_events.send(ClaimComputedEvent {
+                    player: entity,
+                    trail_tiles: Vec::new(),
+                    pockets: vec![blast],
+                    from_bomb: true,
+                });
+            }
+            Ability::Boost => {
+                start_speed_boost(&mut commands, entity, &mut player, boost);
+            }
+        }
+    }
+}
+
+// Energy of each local player, bottom left
+#[derive(Component)]
+pub struct EnergyHud;
+
+pub fn setup_energy_hud(mut commands: Commands, rules: Res<GameRules>) {
+    if rules.energy.is_none() {
+        return;
+    }
+
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(16.0),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        },
+        EnergyHud,
+    ));
+}
+
+pub fn cleanup_energy_hud(mut commands: Commands, hud_query: Query<Entity, With<EnergyHud>>) {
+    for entity in hud_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// A bar of pips per local player, plus what they can afford right now
+pub fn update_energy_hud_system(
+    rules: Res<GameRules>,
+    local_query: Query<&Energy, With<LocalPlayer>>,
+    mut hud_query: Query<&mut Text, With<EnergyHud>>,
+) {
+    let Some(energy_rules) = rules.energy else {
+        return;
+    };
+
+    let lines: Vec<String> = local_query
+        .iter()
+        .map(|energy| {
+            let full = energy.amount.min(energy_rules.max_energy) as usize;
+            let empty = energy_rules.max_energy as usize - full;
+            let ready: Vec<&str> = [(Ability::Bomb, "bomb"), (Ability::Boost, "boost")]
+                .into_iter()
+                .filter(|(ability, _)| energy.amount >= ability.cost(&energy_rules))
+                .map(|(_, name)| name)
+                .collect();
+            format!(
+                "Energy [{}{}] {}",
+                "|".repeat(full),
+                ".".repeat(empty),
+                ready.join(" ")
+            )
+        })
+        .collect();
+
+    for mut text in hud_query.iter_mut() {
+        text.0 = lines.join("\n");
+    }
+}
@@ -0,0 +1,117 @@
+// Accessibility settings: a larger interface, and calmer visuals for anyone
+// bothered by the screen and tile flashes.
+use crate::config::GameConfig;
+use crate::paths::Paths;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Interface scales on offer
+pub const UI_SCALES: [f32; 3] = [1.0, 1.25, 1.5];
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub ui_scale: f32,
+    // No full-screen flashes on announcements and no flashing tiles on claims
+    pub reduce_flashing: bool,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            ui_scale: UI_SCALES[0],
+            reduce_flashing: false,
+        }
+    }
+}
+
+impl AccessibilitySettings {
+    pub fn next_ui_scale(&self) -> f32 {
+        let index = UI_SCALES.iter().position(|&scale| scale == self.ui_scale);
+        match index {
+            Some(index) => UI_SCALES[(index + 1) % UI_SCALES.len()],
+            None => UI_SCALES[0],
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum AccessibilityButton {
+    UiScale,
+    ReduceFlashing,
+}
+
+impl AccessibilityButton {
+    pub const ALL: [AccessibilityButton; 2] = [
+        AccessibilityButton::UiScale,
+        AccessibilityButton::ReduceFlashing,
+    ];
+
+    pub fn label(self, settings: &AccessibilitySettings) -> String {
+        match self {
+            AccessibilityButton::UiScale => {
+                format!("Interface size: {:.0}%", settings.ui_scale * 100.0)
+            }
+            AccessibilityButton::ReduceFlashing => format!(
+                "Reduce flashing: {}",
+                if settings.reduce_flashing {
+                    "on"
+                } else {
+                    "off"
+                }
+            ),
+        }
+    }
+}
+
+pub fn accessibility_button_system(
+    mut settings: ResMut<AccessibilitySettings>,
+    button_query: Query<(&Interaction, &AccessibilityButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            AccessibilityButton::UiScale => settings.ui_scale = settings.next_ui_scale(),
+            AccessibilityButton::ReduceFlashing => {
+                settings.reduce_flashing = !settings.reduce_flashing
+            }
+        }
+    }
+}
+
+pub fn update_accessibility_ui_system(
+    settings: Res<AccessibilitySettings>,
+    button_query: Query<(&AccessibilityButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (button, children) in button_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.0 = button.label(&settings);
+            }
+        }
+    }
+}
+
+pub fn apply_ui_scale_system(settings: Res<AccessibilitySettings>, mut ui_scale: ResMut<UiScale>) {
+    if settings.is_changed() && ui_scale.0 != settings.ui_scale {
+        ui_scale.0 = settings.ui_scale;
+    }
+}
+
+pub fn persist_accessibility_settings_system(
+    paths: Res<Paths>,
+    settings: Res<AccessibilitySettings>,
+    mut config: ResMut<GameConfig>,
+) {
+    if settings.is_changed() && !settings.is_added() && config.accessibility != *settings {
+        config.accessibility = *settings;
+        config.save(&paths);
+    }
+}
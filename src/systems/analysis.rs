@@ -0,0 +1,69 @@
+use crate::components::{GridSettings, Tiles};
+use crate::resources::{OwnershipLayers, PlayerRegions, TerritoryAnalysis};
+use crate::territory::{layer_regions, RegionSummary};
+use bevy::prelude::*;
+
+// Mirrors tile ownership changes into the per-player bitset layers. Trail
+// tiles don't count as land. The owners last mirrored are kept to tell
+// which tiles changed hands.
+pub fn sync_ownership_layers_system(
+    grid_settings: Res<GridSettings>,
+    mut layers: ResMut<OwnershipLayers>,
+    mut land_owners: Local<Vec<Option<Entity>>>,
+    tiles: Res<Tiles>,
+) {
+    if grid_settings.is_changed() {
+        layers.values = grid_settings.tile_values();
+    }
+
+    // A resized grid starts the layers over from every tile, since the
+    // owners last mirrored were for tiles somewhere else
+    if layers.width != tiles.width
+        || layers.height != tiles.height
+        || land_owners.len() != tiles.len()
+    {
+        layers.width = tiles.width;
+        layers.height = tiles.height;
+        layers.layers.clear();
+        land_owners.clear();
+        for tile in tiles.iter() {
+            layers.set_owner(tile.x, tile.y, tile.owner);
+            land_owners.push(tile.owner);
+        }
+        return;
+    }
+
+    if !tiles.is_changed() {
+        return;
+    }
+    for (tile, previous) in tiles.iter().zip(land_owners.iter_mut()) {
+        if tile.owner != *previous {
+            layers.set_owner(tile.x, tile.y, tile.owner);
+            *previous = tile.owner;
+        }
+    }
+}
+
+// Rebuilds the territory analysis from the ownership layers when they change
+pub fn update_territory_analysis_system(
+    layers: Res<OwnershipLayers>,
+    mut analysis: ResMut<TerritoryAnalysis>,
+) {
+    if !layers.is_changed() {
+        return;
+    }
+
+    analysis.players = layers
+        .layers
+        .iter()
+        .map(|(&player, layer)| {
+            let regions = PlayerRegions {
+                regions: layer_regions(layers.width, layers.height, layer)
+                    .iter()
+                    .map(|region| RegionSummary::of(region))
+                    .collect(),
+            };
+            (player, regions)
+        })
+        .collect();
+}
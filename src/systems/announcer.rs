@@ -0,0 +1,159 @@
+use crate::components::Player;
+use crate::events::{
+    BountyClaimedEvent, HazardWarningEvent, MatchTimerEvent, MultiKillEvent, PlaySoundEvent,
+    SoundEffect, TimerMilestone, WeatherWarningEvent,
+};
+use crate::systems::accessibility::AccessibilitySettings;
+use bevy::prelude::*;
+
+// Seconds an announcement (screen flash and banner) stays up
+const ANNOUNCEMENT_SECONDS: f32 = 1.5;
+
+// Full-screen flash plus banner text, faded out over its timer
+#[derive(Component)]
+pub struct Announcement {
+    pub timer: Timer,
+    pub flash_color: Color,
+}
+
+// Flashes the screen and puts up a banner over the arena
+fn spawn_announcement(commands: &mut Commands, text: &str, flash_color: Color) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                padding: UiRect::top(Val::Px(80.0)),
+                ..default()
+            },
+            BackgroundColor(flash_color.with_alpha(0.3)),
+            PickingBehavior::IGNORE,
+            Announcement {
+                timer: Timer::from_seconds(ANNOUNCEMENT_SECONDS, TimerMode::Once),
+                flash_color,
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(text),
+                TextFont::from_font_size(40.0),
+                TextColor(flash_color),
+            ));
+        });
+}
+
+// Flashes the HUD and plays the announcer sound at each timer milestone
+pub fn announce_timer_milestones_system(
+    mut commands: Commands,
+    mut timer_events: EventReader<MatchTimerEvent>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+) {
+    for event in timer_events.read() {
+        let flash_color = match event.milestone {
+            TimerMilestone::OneMinuteLeft => Color::srgb(1.0, 0.8, 0.2),
+            TimerMilestone::TenSecondsLeft => Color::srgb(1.0, 0.25, 0.2),
+        };
+
+        sound_events.send(PlaySoundEvent {
+            sound: SoundEffect::TimerWarning,
+        });
+
+        spawn_announcement(&mut commands, event.milestone.announcement(), flash_color);
+    }
+}
+
+// Warns everyone which side the next hazard wall will sweep in from
+pub fn announce_hazard_walls_system(
+    mut commands: Commands,
+    mut warning_events: EventReader<HazardWarningEvent>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+) {
+    for event in warning_events.read() {
+        sound_events.send(PlaySoundEvent {
+            sound: SoundEffect::TimerWarning,
+        });
+        spawn_announcement(
+            &mut commands,
+            &format!("Hazard wall from the {}!", event.wall.direction.from_side()),
+            Color::srgb(1.0, 0.45, 0.1),
+        );
+    }
+}
+
+// Warns everyone what weather is on the way
+pub fn announce_weather_system(
+    mut commands: Commands,
+    mut warning_events: EventReader<WeatherWarningEvent>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+) {
+    for event in warning_events.read() {
+        sound_events.send(PlaySoundEvent {
+            sound: SoundEffect::TimerWarning,
+        });
+        spawn_announcement(
+            &mut commands,
+            &event.front.kind.announcement(),
+            event.front.kind.color(),
+        );
+    }
+}
+
+// Calls out double and triple kills in the killer's color
+pub fn announce_multi_kills_system(
+    mut commands: Commands,
+    mut multi_kill_events: EventReader<MultiKillEvent>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+    player_query: Query<&Player>,
+) {
+    for event in multi_kill_events.read() {
+        let color = player_query
+            .get(event.player)
+            .map_or(Color::WHITE, |player| player.color);
+        sound_events.send(PlaySoundEvent {
+            sound: SoundEffect::TimerWarning,
+        });
+        spawn_announcement(&mut commands, &event.announcement(), color);
+    }
+}
+
+// Calls out the bounty being collected, in the collector's color
+pub fn announce_bounties_system(
+    mut commands: Commands,
+    mut bounty_events: EventReader<BountyClaimedEvent>,
+    player_query: Query<&Player>,
+) {
+    for event in bounty_events.read() {
+        let color = player_query
+            .get(event.killer)
+            .map_or(Color::WHITE, |player| player.color);
+        spawn_announcement(
+            &mut commands,
+            &format!("Bounty claimed! +{}", event.points),
+            color,
+        );
+    }
+}
+
+pub fn fade_announcements_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    accessibility: Res<AccessibilitySettings>,
+    mut announcement_query: Query<(Entity, &mut Announcement, &mut BackgroundColor)>,
+) {
+    for (entity, mut announcement, mut background) in announcement_query.iter_mut() {
+        if announcement.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // The flash fades much faster than the banner lingers
+        let flash = if accessibility.reduce_flashing {
+            0.0
+        } else {
+            (1.0 - announcement.timer.fraction() * 4.0).max(0.0)
+        };
+        background.0 = announcement.flash_color.with_alpha(0.3 * flash);
+    }
+}
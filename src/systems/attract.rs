@@ -0,0 +1,130 @@
+use crate::components::{GridSettings, Tiles};
+use crate::player_bundle;
+use crate::resources::PendingClaims;
+use crate::states::AppState;
+use crate::systems::bots::Bot;
+use crate::systems::input::{DirectionIntent, InputSource};
+use crate::systems::join::JoinedPlayers;
+use crate::territory::land_color;
+use bevy::prelude::*;
+
+// Seconds the join screen has to sit untouched before the demo starts
+const ATTRACT_IDLE_SECONDS: f32 = 8.0;
+// Length of a demo round before the map is wiped and it starts over
+const ATTRACT_ROUND_SECONDS: f32 = 45.0;
+const ATTRACT_BOTS: usize = 4;
+
+// Bot-only demo match played behind the join screen, like an arcade attract
+// screen
+#[derive(Resource)]
+pub struct AttractMode {
+    pub idle: Timer,
+    pub round: Timer,
+    pub active: bool,
+}
+
+impl Default for AttractMode {
+    fn default() -> Self {
+        Self {
+            idle: Timer::from_seconds(ATTRACT_IDLE_SECONDS, TimerMode::Once),
+            round: Timer::from_seconds(ATTRACT_ROUND_SECONDS, TimerMode::Once),
+            active: false,
+        }
+    }
+}
+
+// Run condition for the simulation: a real match, the sandbox, or the demo
+// behind the menu
+pub fn simulation_active(state: Res<State<AppState>>, attract: Res<AttractMode>) -> bool {
+    matches!(state.get(), AppState::Playing | AppState::Sandbox) || attract.active
+}
+
+// Removes the demo bots and hands every tile back
+fn clear_demo_match(
+    commands: &mut Commands,
+    bot_query: &Query<Entity, With<Bot>>,
+    tiles: &mut Tiles,
+    pending_claims: &mut PendingClaims,
+) {
+    for entity in bot_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (tile, tile_color) in tiles.iter_mut() {
+        if tile.owner.is_none() && tile.trail_owner.is_none() {
+            continue;
+        }
+
+        tile.owner = None;
+        tile.trail_owner = None;
+
+        // Back to the checkerboard, or void
+        tile_color.0 = land_color(tile, None);
+    }
+
+    pending_claims.tasks.clear();
+    pending_claims.cancelled.clear();
+}
+
+// Starts the demo once nobody has joined for a while, restarts it every
+// round, and clears it away as soon as someone joins
+#[allow(clippy::too_many_arguments)]
+pub fn attract_mode_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    grid_settings: Res<GridSettings>,
+    joined: Res<JoinedPlayers>,
+    mut attract: ResMut<AttractMode>,
+    mut pending_claims: ResMut<PendingClaims>,
+    bot_query: Query<Entity, With<Bot>>,
+    mut tiles: ResMut<Tiles>,
+) {
+    if !joined.devices.is_empty() {
+        if attract.active {
+            clear_demo_match(&mut commands, &bot_query, &mut tiles, &mut pending_claims);
+            attract.active = false;
+        }
+        attract.idle.reset();
+        return;
+    }
+
+    if attract.active {
+        if attract.round.tick(time.delta()).finished() {
+            // The idle timer is still finished, so a fresh round starts next frame
+            clear_demo_match(&mut commands, &bot_query, &mut tiles, &mut pending_claims);
+            attract.active = false;
+        }
+        return;
+    }
+
+    if !attract.idle.tick(time.delta()).finished() {
+        return;
+    }
+
+    for slot in 0..ATTRACT_BOTS {
+        commands.spawn((
+            player_bundle(&grid_settings, slot),
+            InputSource::External,
+            DirectionIntent::default(),
+            Bot::default(),
+        ));
+    }
+
+    attract.round.reset();
+    attract.active = true;
+}
+
+// Leaving the join screen for a real match wipes the demo
+pub fn stop_attract_mode_system(
+    mut commands: Commands,
+    mut attract: ResMut<AttractMode>,
+    mut pending_claims: ResMut<PendingClaims>,
+    bot_query: Query<Entity, With<Bot>>,
+    mut tiles: ResMut<Tiles>,
+) {
+    if attract.active {
+        clear_demo_match(&mut commands, &bot_query, &mut tiles, &mut pending_claims);
+    }
+    attract.active = false;
+    attract.idle.reset();
+}
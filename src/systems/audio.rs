@@ -0,0 +1,341 @@
+use crate::components::LocalPlayer;
+use crate::config::GameConfig;
+use crate::events::{
+    MultiKillEvent, PlaySoundEvent, PlayerDeathEvent, SoundEffect, TerritoryClaimedEvent,
+};
+use crate::paths::Paths;
+use crate::systems::countdown::MatchCountdown;
+use crate::systems::music::MusicLayer;
+use crate::systems::theme::Theme;
+use crate::themes::VoiceLine;
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Quiet kept after one voice line ends before the next may start
+const VOICE_GAP_SECONDS: f32 = 1.0;
+
+// Mixer channel a sound plays on, each with its own volume
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioBus {
+    Music,
+    Sfx,
+    Ui,
+}
+
+impl AudioBus {
+    pub const ALL: [AudioBus; 3] = [AudioBus::Music, AudioBus::Sfx, AudioBus::Ui];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AudioBus::Music => "Music",
+            AudioBus::Sfx => "SFX",
+            AudioBus::Ui => "UI",
+        }
+    }
+}
+
+// Per-bus volumes plus a global mute, persisted in the config file
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioMixer {
+    pub muted: bool,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub ui_volume: f32,
+}
+
+impl Default for AudioMixer {
+    fn default() -> Self {
+        Self {
+            muted: false,
+            music_volume: 0.6,
+            sfx_volume: 0.8,
+            ui_volume: 0.8,
+        }
+    }
+}
+
+impl AudioMixer {
+    pub fn volume(&self, bus: AudioBus) -> f32 {
+        match bus {
+            AudioBus::Music => self.music_volume,
+            AudioBus::Sfx => self.sfx_volume,
+            AudioBus::Ui => self.ui_volume,
+        }
+    }
+
+    pub fn set_volume(&mut self, bus: AudioBus, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        match bus {
+            AudioBus::Music => self.music_volume = volume,
+            AudioBus::Sfx => self.sfx_volume = volume,
+            AudioBus::Ui => self.ui_volume = volume,
+        }
+    }
+
+    // Volume a sink on this bus should actually play at
+    pub fn effective_volume(&self, bus: AudioBus) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume(bus)
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct SoundLibrary {
+    pub claim: Handle<AudioSource>,
+    pub death: Handle<AudioSource>,
+    pub click: Handle<AudioSource>,
+    pub warning: Handle<AudioSource>,
+}
+
+impl SoundLibrary {
+    pub fn load(asset_server: &AssetServer) -> Self {
+        let sound = |effect| asset_server.load(built_in_sound(effect));
+        Self {
+            claim: sound(SoundEffect::Claim),
+            death: sound(SoundEffect::Death),
+            click: sound(SoundEffect::UiClick),
+            warning: sound(SoundEffect::TimerWarning),
+        }
+    }
+
+    pub fn handle_mut(&mut self, sound: SoundEffect) -> &mut Handle<AudioSource> {
+        match sound {
+            SoundEffect::Claim => &mut self.claim,
+            SoundEffect::Death => &mut self.death,
+            SoundEffect::UiClick => &mut self.click,
+            SoundEffect::TimerWarning => &mut self.warning,
+        }
+    }
+
+    fn get(&self, sound: SoundEffect) -> (Handle<AudioSource>, AudioBus) {
+        match sound {
+            SoundEffect::Claim => (self.claim.clone(), AudioBus::Sfx),
+            SoundEffect::Death => (self.death.clone(), AudioBus::Sfx),
+            SoundEffect::UiClick => (self.click.clone(), AudioBus::Ui),
+            SoundEffect::TimerWarning => (self.warning.clone(), AudioBus::Sfx),
+        }
+    }
+}
+
+// Sound played for an effect when the theme doesn't replace it
+pub fn built_in_sound(sound: SoundEffect) -> &'static str {
+    match sound {
+        SoundEffect::Claim => "sounds/claim.wav",
+        SoundEffect::Death => "sounds/death.wav",
+        SoundEffect::UiClick => "sounds/click.wav",
+        SoundEffect::TimerWarning => "sounds/warning.wav",
+    }
+}
+
+pub fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SoundLibrary::load(&asset_server));
+}
+
+// Plays requested one-shot sounds on their bus
+pub fn play_sound_system(
+    mut commands: Commands,
+    mut sound_events: EventReader<PlaySoundEvent>,
+    library: Option<Res<SoundLibrary>>,
+    mixer: Res<AudioMixer>,
+) {
+    let Some(library) = library else {
+        sound_events.clear();
+        return;
+    };
+
+    for event in sound_events.read() {
+        let (source, bus) = library.get(event.sound);
+        commands.spawn((
+            AudioPlayer(source),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(mixer.effective_volume(bus))),
+            bus,
+        ));
+    }
+}
+
+// Seconds before the announcer will call out the same line again
+fn voice_cooldown(line: VoiceLine) -> f32 {
+    match line {
+        VoiceLine::Countdown | VoiceLine::Go => 0.0,
+        VoiceLine::MultiKill | VoiceLine::Kill => 4.0,
+        VoiceLine::Claim => 8.0,
+    }
+}
+
+// The countdown and GO cut off whatever is being said, everything else
+// waits its turn
+fn voice_interrupts(line: VoiceLine) -> bool {
+    matches!(line, VoiceLine::Countdown | VoiceLine::Go)
+}
+
+// When the announcer last spoke, overall and per line
+#[derive(Resource, Default)]
+pub struct VoiceCooldowns {
+    // Seconds the last line ended, or is still going
+    quiet_from: Option<f32>,
+    last: BTreeMap<VoiceLine, f32>,
+}
+
+impl VoiceCooldowns {
+    // Whether the line may be said at `seconds`, `speaking` if a line is
+    // still playing
+    pub fn ready(&self, line: VoiceLine, seconds: f32, speaking: bool) -> bool {
+        if voice_interrupts(line) {
+            return true;
+        }
+        let rested = self
+            .last
+            .get(&line)
+            .is_none_or(|&last| seconds - last >= voice_cooldown(line));
+        let quiet = self
+            .quiet_from
+            .is_none_or(|from| seconds - from >= VOICE_GAP_SECONDS);
+        rested && quiet && !speaking
+    }
+
+    pub fn said(&mut self, line: VoiceLine, seconds: f32) {
+        self.last.insert(line, seconds);
+        self.quiet_from = Some(seconds);
+    }
+}
+
+// A voice line playing right now
+#[derive(Component)]
+pub struct VoicePlayback;
+
+// Calls out the countdown, GO, and kills and claims by local players with
+// the theme's voice lines. When several come up at once the most important
+// is said and the rest dropped.
+#[allow(clippy::too_many_arguments)]
+pub fn announcer_voice_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    theme: Res<Theme>,
+    mixer: Res<AudioMixer>,
+    mut cooldowns: ResMut<VoiceCooldowns>,
+    countdown: Option<Res<MatchCountdown>>,
+    mut counting: Local<bool>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    mut multi_kill_events: EventReader<MultiKillEvent>,
+    mut claimed_events: EventReader<TerritoryClaimedEvent>,
+    local_query: Query<(), With<LocalPlayer>>,
+    voice_query: Query<Entity, With<VoicePlayback>>,
+) {
+    let mut lines = Vec::new();
+    match &countdown {
+        Some(countdown) if countdown.is_added() => lines.push(VoiceLine::Countdown),
+        None if *counting => lines.push(VoiceLine::Go),
+        _ => {}
+    }
+    *counting = countdown.is_some();
+
+    if multi_kill_events
+        .read()
+        .any(|event| local_query.contains(event.player))
+    {
+        lines.push(VoiceLine::MultiKill);
+    }
+    if death_events.read().any(|event| {
+        event
+            .killer
+            .is_some_and(|killer| killer != event.player_entity && local_query.contains(killer))
+    }) {
+        lines.push(VoiceLine::Kill);
+    }
+    if claimed_events
+        .read()
+        .any(|event| event.trail_tiles > 0 && local_query.contains(event.player))
+    {
+        lines.push(VoiceLine::Claim);
+    }
+
+    let Some(line) = lines.into_iter().min() else {
+        return;
+    };
+    let Some(source) = theme
+        .voice
+        .get(&line)
+        .and_then(|handles| handles.choose(&mut rand::rng()))
+    else {
+        return;
+    };
+    let seconds = time.elapsed_secs();
+    if !cooldowns.ready(line, seconds, !voice_query.is_empty()) {
+        return;
+    }
+
+    for entity in voice_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.spawn((
+        AudioPlayer(source.clone()),
+        PlaybackSettings::DESPAWN.with_volume(Volume::new(mixer.effective_volume(AudioBus::Sfx))),
+        AudioBus::Sfx,
+        VoicePlayback,
+    ));
+    cooldowns.said(line, seconds);
+}
+
+// Starts the quiet gap once a voice line has finished
+pub fn voice_finished_system(
+    time: Res<Time>,
+    mut cooldowns: ResMut<VoiceCooldowns>,
+    voice_query: Query<(), With<VoicePlayback>>,
+) {
+    if !voice_query.is_empty() {
+        cooldowns.quiet_from = Some(time.elapsed_secs());
+    }
+}
+
+// Global mute on M
+pub fn mute_hotkey_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut mixer: ResMut<AudioMixer>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyM) {
+        mixer.muted = !mixer.muted;
+        println!("Audio {}", if mixer.muted { "muted" } else { "unmuted" });
+    }
+}
+
+// Pushes mixer changes to sounds that are already playing, and picks up
+// sinks that only just started. Music layers are faded by the music system.
+pub fn apply_mixer_system(
+    mixer: Res<AudioMixer>,
+    sink_query: Query<(Ref<AudioSink>, &AudioBus), Without<MusicLayer>>,
+) {
+    for (sink, bus) in sink_query.iter() {
+        if mixer.is_changed() || sink.is_added() {
+            sink.set_volume(mixer.effective_volume(*bus));
+        }
+    }
+}
+
+// Writes mixer changes back to the config file once the user stops fiddling
+// with them, so dragging a slider doesn't rewrite the file every frame
+pub fn persist_mixer_system(
+    time: Res<Time>,
+    paths: Res<Paths>,
+    mixer: Res<AudioMixer>,
+    mut config: ResMut<GameConfig>,
+    mut pending_save: Local<Option<Timer>>,
+) {
+    if mixer.is_changed() && !mixer.is_added() && config.audio != *mixer {
+        config.audio = mixer.clone();
+        *pending_save = Some(Timer::from_seconds(0.5, TimerMode::Once));
+    }
+
+    if let Some(timer) = pending_save.as_mut() {
+        if timer.tick(time.delta()).finished() {
+            config.save(&paths);
+            *pending_save = None;
+        }
+    }
+}
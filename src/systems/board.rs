@@ -0,0 +1,246 @@
+// Draws the whole grid at once. A single quad covering the grid is shaded
+// tile by tile from a storage buffer with each tile's color and value. The
+// buffer goes to the GPU on frames where any tile looks different, so big
+// boards cost one entity to draw however many tiles they have.
+use crate::components::{GridSettings, Tile, Tiles};
+use crate::systems::floors::{ActiveFloor, OTHER_FLOOR_ALPHA};
+use crate::systems::theme::Theme;
+use crate::systems::weather::{Vision, FOG_COLOR, FOG_SHOW_THROUGH};
+use crate::themes::ThemeSlot;
+use bevy::prelude::*;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef};
+use bevy::render::storage::ShaderStorageBuffer;
+use bevy::sprite::{AlphaMode2d, Material2d};
+
+const BOARD_SHADER: &str = "shaders/board.wgsl";
+// Valuable tiles get a gold dot in the middle
+const VALUE_DOT_COLOR: Color = Color::srgb(0.95, 0.75, 0.1);
+// Stairs get a white one instead
+const STAIRS_DOT_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.85);
+// Same layer the tile sprites used to be on
+const BOARD_Z: f32 = -0.1;
+
+#[derive(Asset, TypePath, AsBindGroup, Clone, Debug)]
+pub struct BoardMaterial {
+    // Width and height in tiles
+    #[uniform(0)]
+    pub size: UVec2,
+    // Two entries a tile, see `BoardTile`
+    #[storage(1, read_only)]
+    pub tiles: Handle<ShaderStorageBuffer>,
+    // Theme textures for the checkerboard, tinted by each tile's color
+    #[texture(2)]
+    #[sampler(3)]
+    pub light_tile: Option<Handle<Image>>,
+    #[texture(4)]
+    #[sampler(5)]
+    pub dark_tile: Option<Handle<Image>>,
+}
+
+impl Material2d for BoardMaterial {
+    fn fragment_shader() -> ShaderRef {
+        BOARD_SHADER.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+}
+
+// A tile as the shader reads it, its color then its value dot's, both in
+// linear space
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoardTile {
+    pub color: Vec4,
+    pub value_dot: Vec4,
+}
+
+impl BoardTile {
+    pub fn new(tile: &Tile, color: Color) -> Self {
+        Self {
+            color: color.to_linear().to_vec4(),
+            value_dot: if tile.stairs {
+                STAIRS_DOT_COLOR
+            } else {
+                value_dot_color(tile.value)
+            }
+            .to_linear()
+            .to_vec4(),
+        }
+    }
+}
+
+// Gold dot in the middle of a valuable tile, bolder the more it's worth
+pub fn value_dot_color(value: u32) -> Color {
+    if value > 1 {
+        VALUE_DOT_COLOR.with_alpha((0.15 * value as f32).min(0.8))
+    } else {
+        Color::NONE
+    }
+}
+
+// What the board shows, row by row from the bottom. Empty while there are
+// no tiles.
+#[derive(Resource, Default)]
+pub struct Board {
+    pub width: i32,
+    pub height: i32,
+    pub tile_size: f32,
+    pub tiles: Vec<BoardTile>,
+}
+
+impl Board {
+    // What goes in the storage buffer
+    fn buffer_data(&self) -> Vec<Vec4> {
+        self.tiles
+            .iter()
+            .flat_map(|tile| [tile.color, tile.value_dot])
+            .collect()
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<&BoardTile> {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return None;
+        }
+        self.tiles.get((y * self.width + x) as usize)
+    }
+
+    // Whether the tile looked any different before
+    fn set(&mut self, tile: &Tile, color: Color) -> bool {
+        if tile.x < 0 || tile.x >= self.width || tile.y < 0 || tile.y >= self.height {
+            return false;
+        }
+        let board_tile = BoardTile::new(tile, color);
+        match self.tiles.get_mut((tile.y * self.width + tile.x) as usize) {
+            Some(slot) if *slot != board_tile => {
+                *slot = board_tile;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// The quad the board is drawn on, and the grid it was made for
+#[derive(Component)]
+pub struct BoardMesh {
+    pub width: i32,
+    pub height: i32,
+    pub tile_size: f32,
+}
+
+// Copies changed tiles into the board, fading the floor out of focus. A new
+// grid, or tiles appearing or going away, starts it over.
+pub fn update_board_system(
+    grid_settings: Res<GridSettings>,
+    active: Res<ActiveFloor>,
+    vision: Res<Vision>,
+    mut board: ResMut<Board>,
+    tiles: Res<Tiles>,
+) {
+    if board.width != tiles.width
+        || board.height != tiles.height
+        || board.tile_size != grid_settings.tile_size
+        || board.tiles.len() != tiles.len()
+    {
+        *board = Board {
+            width: tiles.width,
+            height: tiles.height,
+            tile_size: grid_settings.tile_size,
+            tiles: vec![BoardTile::default(); tiles.len()],
+        };
+    } else if !tiles.is_changed() && !active.is_changed() && !vision.is_changed() {
+        return;
+    }
+
+    let mut changed = false;
+    for (tile, tile_color) in tiles.iter_colored() {
+        let mut color = if active.fades(&grid_settings, tile.x) {
+            let alpha = tile_color.0.alpha() * OTHER_FLOOR_ALPHA;
+            tile_color.0.with_alpha(alpha)
+        } else {
+            tile_color.0
+        };
+        // Out of sight in fog, whoever's land it is hardly shows
+        if !vision.sees((tile.x, tile.y)) {
+            color = FOG_COLOR
+                .mix(&color, FOG_SHOW_THROUGH)
+                .with_alpha(color.alpha());
+        }
+        changed |= board.bypass_change_detection().set(tile, color);
+    }
+    if changed {
+        board.set_changed();
+    }
+}
+
+// Sends the board to the GPU, making a new quad when the grid's shape
+// changes and picking up the theme's tile textures
+pub fn draw_board_system(
+    mut commands: Commands,
+    board: Res<Board>,
+    theme: Res<Theme>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<BoardMaterial>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    mut board_query: Query<(
+        Entity,
+        &BoardMesh,
+        &MeshMaterial2d<BoardMaterial>,
+        &mut Visibility,
+    )>,
+) {
+    if !board.is_changed() && !theme.is_changed() {
+        return;
+    }
+
+    let existing = board_query.get_single_mut().ok();
+    let fits = existing.as_ref().is_some_and(|(_, mesh, _, _)| {
+        mesh.width == board.width
+            && mesh.height == board.height
+            && mesh.tile_size == board.tile_size
+    });
+    if let Some((entity, _, material, mut visibility)) = existing {
+        if fits {
+            *visibility = if board.tiles.is_empty() {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            };
+            // Touching the material rebuilds its bind group with the new buffer
+            let Some(material) = materials.get_mut(&material.0) else {
+                return;
+            };
+            if !board.tiles.is_empty() {
+                if let Some(buffer) = buffers.get_mut(&material.tiles) {
+                    buffer.set_data(board.buffer_data());
+                }
+            }
+            material.light_tile = theme.images.get(&ThemeSlot::LightTile).cloned();
+            material.dark_tile = theme.images.get(&ThemeSlot::DarkTile).cloned();
+            return;
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+    if board.tiles.is_empty() {
+        return;
+    }
+
+    let material = BoardMaterial {
+        size: UVec2::new(board.width as u32, board.height as u32),
+        tiles: buffers.add(ShaderStorageBuffer::from(board.buffer_data())),
+        light_tile: theme.images.get(&ThemeSlot::LightTile).cloned(),
+        dark_tile: theme.images.get(&ThemeSlot::DarkTile).cloned(),
+    };
+    let size = Vec2::new(board.width as f32, board.height as f32) * board.tile_size;
+    commands.spawn((
+        BoardMesh {
+            width: board.width,
+            height: board.height,
+            tile_size: board.tile_size,
+        },
+        Mesh2d(meshes.add(Rectangle::from_size(size))),
+        MeshMaterial2d(materials.add(material)),
+        Transform::from_xyz(0.0, 0.0, BOARD_Z),
+    ));
+}
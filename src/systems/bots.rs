@@ -0,0 +1,318 @@
+use crate::brain::{
+    BotBrain, BotDifficulty, BotTuning, BrainInput, BrainRegistry, PlayerView, WorldSnapshot,
+};
+use crate::components::{GridSettings, Player, Respawning, Tiles};
+use crate::resources::{GameRules, GameState};
+use crate::systems::input::DirectionIntent;
+use crate::systems::zone::SafeZone;
+use crate::territory::{TileMap, ZoneBounds};
+use bevy::prelude::*;
+use rand::Rng;
+use std::collections::VecDeque;
+
+// Computer-controlled player, steered by a pluggable brain
+#[derive(Component)]
+pub struct Bot {
+    pub brain: Box<dyn BotBrain>,
+    pub tuning: BotTuning,
+    // Speed scale already applied to the player, so tuning changes are
+    // applied relative to whatever else set the speed
+    pub applied_speed_scale: f32,
+    // Difficulty it was set up at, None for bots tuned some other way
+    pub difficulty: Option<BotDifficulty>,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self::with_brain(Box::new(LoopBrain::default()))
+    }
+}
+
+impl Bot {
+    pub fn with_brain(brain: Box<dyn BotBrain>) -> Self {
+        Self {
+            brain,
+            tuning: BotTuning::default(),
+            applied_speed_scale: 1.0,
+            difficulty: None,
+        }
+    }
+
+    // Default brain, tuned to the difficulty
+    pub fn with_difficulty(difficulty: BotDifficulty) -> Self {
+        Self {
+            tuning: difficulty.tuning(),
+            difficulty: Some(difficulty),
+            ..Self::default()
+        }
+    }
+}
+
+// The built-in brain. It plays rectangular loops out of its own territory,
+// each leg being a direction and how many tiles to follow it.
+#[derive(Default)]
+pub struct LoopBrain {
+    pub legs: VecDeque<(Vec2, u32)>,
+    pub last_tile: Option<(i32, i32)>,
+}
+
+impl BotBrain for LoopBrain {
+    fn decide(&mut self, input: &BrainInput) -> Vec2 {
+        let me = input.me;
+
+        // A stopped bot has just spawned or respawned, its old plan is void
+        if me.direction == Vec2::ZERO {
+            self.legs.clear();
+            self.last_tile = None;
+        }
+
+        // Count off a tile of the current leg each time the bot reaches a new tile
+        if self.last_tile != Some(me.tile) {
+            if self.last_tile.is_some() {
+                if let Some(leg) = self.legs.front_mut() {
+                    leg.1 = leg.1.saturating_sub(1);
+                    if leg.1 == 0 {
+                        self.legs.pop_front();
+                    }
+                }
+            }
+            self.last_tile = Some(me.tile);
+        }
+
+        // In the shrinking zone, play inside what will still be safe once
+        // the next ring closes, and drop the plan to get back in there
+        let map = &input.world.map;
+        let area = input
+            .world
+            .zone
+            .map_or(ZoneBounds::whole_map(map.width, map.height), |zone| {
+                zone.next
+            });
+        if !area.contains(me.tile) {
+            let inward = toward(me.tile, area.center(), me.direction);
+            if self.legs.front().map(|&(direction, _)| direction) != Some(inward) {
+                self.legs = VecDeque::from([(inward, 1)]);
+            }
+        }
+
+        if self.legs.is_empty() {
+            self.legs = plan_loop(
+                &mut rand::rng(),
+                me.tile,
+                me.direction,
+                input.tuning.aggression,
+                map,
+                me.entity,
+                area,
+            );
+        }
+
+        self.legs
+            .front()
+            .map_or(Vec2::ZERO, |&(direction, _)| direction)
+    }
+}
+
+const CARDINALS: [Vec2; 4] = [Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y];
+
+// Average value of the tiles a loop would add, judged on its bounding box
+fn loop_utility(map: &TileMap, me: Entity, from: (i32, i32), far: (i32, i32)) -> f32 {
+    let (min_x, max_x) = (from.0.min(far.0), from.0.max(far.0));
+    let (min_y, max_y) = (from.1.min(far.1), from.1.max(far.1));
+    let gained: Vec<(i32, i32)> = (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+        .filter(|&(x, y)| !map.is_territory_of(x, y, me))
+        .collect();
+    map.value_of(&gained) as f32 / gained.len().max(1) as f32
+}
+
+// Cardinal step from `from` towards `to`. Turns first where going straight
+// there would be a reversal, which the game ignores.
+fn toward(from: (i32, i32), to: (i32, i32), heading: Vec2) -> Vec2 {
+    let delta = Vec2::new((to.0 - from.0) as f32, (to.1 - from.1) as f32);
+    let sign = |value: f32| if value < 0.0 { -1.0 } else { 1.0 };
+    let (along, across) = if delta.x.abs() >= delta.y.abs() {
+        (Vec2::new(sign(delta.x), 0.0), Vec2::new(0.0, sign(delta.y)))
+    } else {
+        (Vec2::new(0.0, sign(delta.y)), Vec2::new(sign(delta.x), 0.0))
+    };
+    if along == -heading {
+        across
+    } else {
+        along
+    }
+}
+
+// Picks a rectangle that starts where the bot is, doesn't reverse its
+// current heading and stays inside `area`. Of the ones that fit, the one
+// over the most valuable tiles wins.
+fn plan_loop(
+    rng: &mut impl Rng,
+    from: (i32, i32),
+    heading: Vec2,
+    aggression: f32,
+    map: &TileMap,
+    me: Entity,
+    area: ZoneBounds,
+) -> VecDeque<(Vec2, u32)> {
+    // Loops grow with aggression, 3-7 by 2-6 tiles at the default of 0.5
+    let reach = (aggression.clamp(0.0, 1.0) * 8.0).round() as u32;
+    // Legs overshoot by a tile because turns wait for the next tile centre
+    let fits = |direction: Vec2, tiles: u32, (x, y): (i32, i32)| {
+        let end_x = x + direction.x as i32 * (tiles as i32 + 1);
+        let end_y = y + direction.y as i32 * (tiles as i32 + 1);
+        end_x > area.min.0 && end_x < area.max.0 && end_y > area.min.1 && end_y < area.max.1
+    };
+    // Nor does any leg run off the map, cross void, or pass next to stairs
+    // that would take the bot off to the other floor halfway round. Turns
+    // can come a tile early, so stairs get a tile of room.
+    let near_stairs = |(x, y): (i32, i32)| {
+        map.stairs(x, y) || map.neighbors8(x, y).any(|(x, y)| map.stairs(x, y))
+    };
+    let clear = |direction: Vec2, tiles: u32, (x, y): (i32, i32)| {
+        let steps = tiles as i32 + 1;
+        let step = direction.as_ivec2();
+        map.in_bounds(x + step.x * steps, y + step.y * steps)
+            && map
+                .first_hit_along((x, y), step, steps as u32, |tile, _| {
+                    !map.playable(tile.0, tile.1) || near_stairs(tile)
+                })
+                .is_none()
+    };
+
+    let mut best: Option<(f32, VecDeque<(Vec2, u32)>)> = None;
+    for _ in 0..8 {
+        let out = CARDINALS[rng.random_range(0..CARDINALS.len())];
+        if out == -heading {
+            continue;
+        }
+
+        let side = if rng.random_bool(0.5) {
+            Vec2::new(-out.y, out.x)
+        } else {
+            Vec2::new(out.y, -out.x)
+        };
+        let length = rng.random_range(3..=(3 + reach).max(3));
+        let width = rng.random_range(2..=(2 + reach).max(2));
+
+        let corner = (
+            from.0 + out.x as i32 * (length as i32 + 1),
+            from.1 + out.y as i32 * (length as i32 + 1),
+        );
+        let far = (
+            corner.0 + side.x as i32 * (width as i32 + 1),
+            corner.1 + side.y as i32 * (width as i32 + 1),
+        );
+        let back = (
+            from.0 + side.x as i32 * (width as i32 + 1),
+            from.1 + side.y as i32 * (width as i32 + 1),
+        );
+        let on_grid = fits(out, length, from) && fits(side, width, corner);
+        let over_land = clear(out, length, from)
+            && clear(side, width, corner)
+            && clear(-out, length, far)
+            && clear(-side, width, back);
+        if !(on_grid && over_land) {
+            continue;
+        }
+
+        let utility = loop_utility(map, me, from, far);
+        // Ties keep the earlier pick, so a map without value zones plays as before
+        if best.as_ref().is_none_or(|(top, _)| utility > *top) {
+            let legs =
+                VecDeque::from([(out, length), (side, width), (-out, length), (-side, width)]);
+            best = Some((utility, legs));
+        }
+    }
+    if let Some((_, legs)) = best {
+        return legs;
+    }
+
+    // Boxed in near an edge: head back towards the middle and try again
+    // there, or any other way that isn't into void
+    let inward = toward(from, area.center(), heading);
+    let direction = std::iter::once(inward)
+        .chain(CARDINALS)
+        .find(|&direction| direction != -heading && clear(direction, 1, from))
+        .unwrap_or(inward);
+    VecDeque::from([(direction, 2)])
+}
+
+// Hands every bot the same snapshot of the match and steers it where its
+// brain says
+pub fn bot_ai_system(
+    grid_settings: Res<GridSettings>,
+    game_state: Res<GameState>,
+    zone: Option<Res<SafeZone>>,
+    tiles: Res<Tiles>,
+    mut player_query: Query<(Entity, &mut Player, Option<&mut Bot>, Has<Respawning>)>,
+    mut intent_query: Query<&mut DirectionIntent, With<Bot>>,
+) {
+    if intent_query.is_empty() {
+        return;
+    }
+
+    let world = WorldSnapshot {
+        map: TileMap::from_tiles(
+            grid_settings.grid_width,
+            grid_settings.grid_height,
+            tiles.iter(),
+        ),
+        players: player_query
+            .iter()
+            .map(|(entity, player, bot, _)| PlayerView {
+                entity,
+                tile: player.last_tile_pos,
+                direction: player.direction,
+                drawing_trail: player.is_drawing_trail,
+                score: player.score,
+                is_bot: bot.is_some(),
+            })
+            .collect(),
+        time_left: game_state.timer.remaining_secs(),
+        zone: zone.map(|zone| zone.view(game_state.timer.elapsed_secs())),
+    };
+
+    for (entity, mut player, bot, respawning) in player_query.iter_mut() {
+        let (Some(mut bot), false) = (bot, respawning) else {
+            continue;
+        };
+        if bot.applied_speed_scale != bot.tuning.speed_scale {
+            player.speed *= bot.tuning.speed_scale / bot.applied_speed_scale;
+            bot.applied_speed_scale = bot.tuning.speed_scale;
+        }
+
+        let Some(me) = world.players.iter().find(|view| view.entity == entity) else {
+            continue;
+        };
+        let input = BrainInput {
+            me,
+            tuning: bot.tuning,
+            world: &world,
+        };
+        if let Ok(mut intent) = intent_query.get_mut(entity) {
+            intent.0 = bot.brain.decide(&input);
+        }
+    }
+}
+
+// Swaps in the brain picked by the rules for bots that just spawned
+pub fn assign_bot_brains_system(
+    rules: Res<GameRules>,
+    registry: Res<BrainRegistry>,
+    mut bot_query: Query<&mut Bot, Added<Bot>>,
+) {
+    let Some(name) = rules.bot_brain.as_deref() else {
+        return;
+    };
+
+    for mut bot in bot_query.iter_mut() {
+        match registry.create(name) {
+            Some(brain) => bot.brain = brain,
+            None => {
+                println!("Unknown bot brain '{}', keeping the default", name);
+                return;
+            }
+        }
+    }
+}
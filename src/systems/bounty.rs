@@ -0,0 +1,140 @@
+// Bounties. While the rules have them, the player out in front carries a
+// bonus on their head: a crown shows who it is, and whoever kills them
+// collects the bonus on top of their score.
+use crate::components::{GridSettings, Player, Upright};
+use crate::events::{BountyClaimedEvent, PlayerDeathEvent};
+use crate::resources::GameRules;
+use bevy::prelude::*;
+
+const CROWN_COLOR: Color = Color::srgb(1.0, 0.8, 0.1);
+
+// The current leader, there's at most one
+#[derive(Component)]
+pub struct BountyTarget;
+
+// Moves the bounty to whoever leads outright, or takes it away when nobody
+// does or the leader hasn't scored enough yet
+pub fn track_bounty_leader_system(
+    mut commands: Commands,
+    rules: Res<GameRules>,
+    player_query: Query<(Entity, &Player, Has<BountyTarget>)>,
+) {
+    let Some(bounty_rules) = rules.bounty else {
+        return;
+    };
+
+    let mut ranked: Vec<(Entity, u32)> = player_query
+        .iter()
+        .map(|(entity, player, _)| (entity, player.score))
+        .collect();
+    ranked.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    let leader = match ranked.as_slice() {
+        [(leader, score), rest @ ..]
+            if *score >= bounty_rules.min_score
+                && rest.first().is_none_or(|(_, second)| second < score) =>
+        {
+            Some(*leader)
+        }
+        _ => None,
+    };
+
+    for (entity, _, targeted) in player_query.iter() {
+        if Some(entity) == leader && !targeted {
+            println!("👑 Bounty of {} on the new leader", bounty_rules.points);
+            commands.entity(entity).insert(BountyTarget);
+        } else if Some(entity) != leader && targeted {
+            commands.entity(entity).remove::<BountyTarget>();
+        }
+    }
+}
+
+// Pays the bounty out to whoever kills the leader
+pub fn collect_bounty_system(
+    mut commands: Commands,
+    rules: Res<GameRules>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    target_query: Query<(), With<BountyTarget>>,
+    mut player_query: Query<&mut Player>,
+    mut bounty_events: EventWriter<BountyClaimedEvent>,
+) {
+    let Some(bounty_rules) = rules.bounty else {
+        death_events.clear();
+        return;
+    };
+
+    for event in death_events.read() {
+        let victim = event.player_entity;
+        let Some(killer) = event.killer.filter(|&killer| killer != victim) else {
+            continue;
+        };
+        if !target_query.contains(victim) {
+            continue;
+        }
+        let Ok(mut player) = player_query.get_mut(killer) else {
+            continue;
+        };
+
+        player.add_bonus(bounty_rules.points);
+        commands.entity(victim).remove::<BountyTarget>();
+        println!("💰 Bounty of {} collected", bounty_rules.points);
+        bounty_events.send(BountyClaimedEvent {
+            killer,
+            victim,
+            points: bounty_rules.points,
+        });
+    }
+}
+
+// Crown floating over the leader's head
+#[derive(Component)]
+pub struct BountyCrown;
+
+pub fn bounty_crown_system(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    target_query: Query<&Transform, (With<BountyTarget>, Without<BountyCrown>)>,
+    mut crown_query: Query<(Entity, &mut Transform), With<BountyCrown>>,
+) {
+    let Ok(target) = target_query.get_single() else {
+        for (entity, _) in crown_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+
+    let tile_size = grid_settings.tile_size;
+    let translation = target.translation.truncate() + Vec2::new(0.0, tile_size);
+    // Over the players and trails
+    let translation = translation.extend(0.7);
+
+    if let Ok((_, mut transform)) = crown_query.get_single_mut() {
+        transform.translation = translation;
+        return;
+    }
+
+    // A band with three points on top
+    let point = Vec2::splat(tile_size * 0.2);
+    commands
+        .spawn((
+            Sprite {
+                color: CROWN_COLOR,
+                custom_size: Some(Vec2::new(tile_size * 0.8, tile_size * 0.25)),
+                ..default()
+            },
+            Transform::from_translation(translation),
+            Upright,
+            BountyCrown,
+        ))
+        .with_children(|crown| {
+            for x in [-0.3, 0.0, 0.3] {
+                crown.spawn((
+                    Sprite {
+                        color: CROWN_COLOR,
+                        custom_size: Some(point),
+                        ..default()
+                    },
+                    Transform::from_xyz(x * tile_size, tile_size * 0.2, 0.0),
+                ));
+            }
+        });
+}
@@ -0,0 +1,70 @@
+// Camera modes. The default shows the whole map; chase mode zooms right in on
+// a lone local player and turns the map so they always head up the screen,
+// leaving the minimap as the only view of the wider game.
+use crate::components::{LocalPlayer, MainCamera, Player, Respawning};
+use crate::config::GameConfig;
+use crate::paths::Paths;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+// Zoom while chasing, tighter than the kill cam
+pub const CHASE_ZOOM: f32 = 0.35;
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CameraMode {
+    #[default]
+    Overview,
+    Chase,
+}
+
+// V switches between the overview and chase camera, saved with the settings
+pub fn toggle_camera_mode_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    paths: Res<Paths>,
+    mut mode: ResMut<CameraMode>,
+    mut config: ResMut<GameConfig>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyV) {
+        return;
+    }
+
+    *mode = match *mode {
+        CameraMode::Overview => CameraMode::Chase,
+        CameraMode::Chase => CameraMode::Overview,
+    };
+    println!("Camera: {:?}", *mode);
+    config.camera = *mode;
+    config.save(&paths);
+}
+
+// Camera rotation that points a movement direction up the screen
+pub fn chase_rotation(direction: Vec2) -> Quat {
+    Quat::from_rotation_z(direction.to_angle() - FRAC_PI_2)
+}
+
+pub type ChasePlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Transform, &'static Player, Has<Respawning>),
+    (With<LocalPlayer>, Without<MainCamera>),
+>;
+
+// Where the chase camera wants to be: over the only living local player,
+// turned to their heading (None while stopped, keeping the last turn).
+// Split screen and spectating keep the overview.
+pub fn chase_target(
+    mode: CameraMode,
+    player_query: &ChasePlayerQuery,
+) -> Option<(Vec2, Option<Quat>)> {
+    if mode != CameraMode::Chase {
+        return None;
+    }
+
+    let (transform, player, respawning) = player_query.get_single().ok()?;
+    if respawning {
+        return None;
+    }
+    let rotation = (player.direction != Vec2::ZERO).then(|| chase_rotation(player.direction));
+    Some((transform.translation.truncate(), rotation))
+}
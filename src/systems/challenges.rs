@@ -0,0 +1,306 @@
+// Weekly challenges. A few goals rotate in every week, picked from the week
+// number so everyone gets the same ones, and each profile works towards
+// them over every match it plays that week.
+use crate::components::LocalPlayer;
+use crate::events::{MatchEndedEvent, ToastEvent, ToastIcon};
+use crate::profiles::{ActiveProfiles, ProfileStore};
+use crate::systems::daily::today;
+use crate::systems::input::InputSource;
+use crate::systems::join::JoinedPlayers;
+use crate::systems::stats::MatchStats;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+// Mixed into the week number, like the daily challenge seed
+const WEEKLY_SALT: u64 = 0x3eed_7a11_c4a1_1e9e;
+pub const CHALLENGES_PER_WEEK: usize = 3;
+// Characters in a progress bar on the panel
+const BAR_WIDTH: usize = 20;
+
+// Weeks since the Unix epoch, so challenges rotate every seventh midnight UTC
+pub fn this_week() -> u64 {
+    today() / 7
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeeklyGoal {
+    // Win a match without dying once
+    WinWithoutDying,
+    // The rest add up over the week
+    ClaimTiles(u32),
+    Kills(u32),
+    Wins(u32),
+    MatchesPlayed(u32),
+    // Best single match
+    ScoreInMatch(u32),
+}
+
+const GOAL_POOL: [WeeklyGoal; 8] = [
+    WeeklyGoal::WinWithoutDying,
+    WeeklyGoal::ClaimTiles(500),
+    WeeklyGoal::ClaimTiles(1000),
+    WeeklyGoal::Kills(15),
+    WeeklyGoal::Wins(5),
+    WeeklyGoal::MatchesPlayed(10),
+    WeeklyGoal::ScoreInMatch(150),
+    WeeklyGoal::ScoreInMatch(300),
+];
+
+// What a profile's player did in one finished match
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchResult {
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub tiles_claimed: u32,
+    pub won: bool,
+}
+
+impl WeeklyGoal {
+    pub fn target(self) -> u32 {
+        match self {
+            WeeklyGoal::WinWithoutDying => 1,
+            WeeklyGoal::ClaimTiles(target)
+            | WeeklyGoal::Kills(target)
+            | WeeklyGoal::Wins(target)
+            | WeeklyGoal::MatchesPlayed(target)
+            | WeeklyGoal::ScoreInMatch(target) => target,
+        }
+    }
+
+    pub fn description(self) -> String {
+        match self {
+            WeeklyGoal::WinWithoutDying => "Win a match without dying".to_string(),
+            WeeklyGoal::ClaimTiles(target) => format!("Claim {} tiles", target),
+            WeeklyGoal::Kills(target) => format!("Get {} kills", target),
+            WeeklyGoal::Wins(target) => format!("Win {} matches", target),
+            WeeklyGoal::MatchesPlayed(target) => format!("Play {} matches", target),
+            WeeklyGoal::ScoreInMatch(target) => format!("Score {} in one match", target),
+        }
+    }
+
+    // Progress after a match, never past the target
+    pub fn advance(self, progress: u32, result: &MatchResult) -> u32 {
+        let progress = match self {
+            WeeklyGoal::WinWithoutDying => {
+                progress.max(u32::from(result.won && result.deaths == 0))
+            }
+            WeeklyGoal::ClaimTiles(_) => progress + result.tiles_claimed,
+            WeeklyGoal::Kills(_) => progress + result.kills,
+            WeeklyGoal::Wins(_) => progress + u32::from(result.won),
+            WeeklyGoal::MatchesPlayed(_) => progress + 1,
+            WeeklyGoal::ScoreInMatch(_) => progress.max(result.score),
+        };
+        progress.min(self.target())
+    }
+}
+
+// The week's goals. Like the daily challenge everything comes from the date.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct WeeklyChallenges {
+    pub week: u64,
+    pub goals: Vec<WeeklyGoal>,
+}
+
+impl WeeklyChallenges {
+    pub fn generate(week: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(week ^ WEEKLY_SALT);
+        let goals = GOAL_POOL
+            .choose_multiple(&mut rng, CHALLENGES_PER_WEEK)
+            .copied()
+            .collect();
+        Self { week, goals }
+    }
+}
+
+impl Default for WeeklyChallenges {
+    fn default() -> Self {
+        Self::generate(this_week())
+    }
+}
+
+// Per-profile progress on the current week's goals
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChallengeRecord {
+    // Week the progress belongs to, older progress is thrown away
+    pub week: u64,
+    // Progress per goal, in the week's order
+    pub progress: Vec<u32>,
+    // Goals finished over every week
+    pub completed: u32,
+}
+
+impl ChallengeRecord {
+    // Progress on the given week's goals, zero if it's from another week
+    pub fn progress_for(&self, challenges: &WeeklyChallenges) -> Vec<u32> {
+        if self.week == challenges.week && self.progress.len() == challenges.goals.len() {
+            self.progress.clone()
+        } else {
+            vec![0; challenges.goals.len()]
+        }
+    }
+
+    // Adds a match and returns the goals it finished
+    pub fn record(
+        &mut self,
+        challenges: &WeeklyChallenges,
+        result: &MatchResult,
+    ) -> Vec<WeeklyGoal> {
+        let before = self.progress_for(challenges);
+        let mut finished = Vec::new();
+        self.week = challenges.week;
+        self.progress = challenges
+            .goals
+            .iter()
+            .zip(before)
+            .map(|(&goal, progress)| {
+                let after = goal.advance(progress, result);
+                if progress < goal.target() && after >= goal.target() {
+                    finished.push(goal);
+                }
+                after
+            })
+            .collect();
+        self.completed += finished.len() as u32;
+        finished
+    }
+}
+
+// Counts each local player's finished match towards their profile's weekly
+// goals
+#[allow(clippy::too_many_arguments)]
+pub fn record_weekly_challenges_system(
+    mut match_end_events: EventReader<MatchEndedEvent>,
+    mut challenges: ResMut<WeeklyChallenges>,
+    match_stats: Res<MatchStats>,
+    joined: Res<JoinedPlayers>,
+    active: Res<ActiveProfiles>,
+    mut store: ResMut<ProfileStore>,
+    player_query: Query<&InputSource, With<LocalPlayer>>,
+    mut toast_events: EventWriter<ToastEvent>,
+) {
+    for event in match_end_events.read() {
+        // A session left running over the weekend moves on to the new goals
+        if challenges.week != this_week() {
+            *challenges = WeeklyChallenges::generate(this_week());
+        }
+
+        for standing in event.standings.iter() {
+            let Ok(InputSource::Device(device)) = player_query.get(standing.player) else {
+                continue;
+            };
+            let Some(&index) = joined
+                .slot_of(*device)
+                .and_then(|slot| active.slots.get(slot))
+            else {
+                continue;
+            };
+            let Some(profile) = store.profiles.get_mut(index) else {
+                continue;
+            };
+
+            let result = MatchResult {
+                score: standing.score,
+                kills: match_stats.kills(standing.player),
+                deaths: match_stats.deaths(standing.player),
+                tiles_claimed: match_stats.tiles_claimed(standing.player),
+                won: standing.placement == 0,
+            };
+            for goal in profile.challenges.record(&challenges, &result) {
+                println!(
+                    "🏅 {} finished a weekly challenge: {}",
+                    profile.name,
+                    goal.description()
+                );
+                toast_events.send(ToastEvent::new(
+                    ToastIcon::Achievement,
+                    format!("{} finished a weekly challenge", profile.name),
+                ));
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ChallengesPanel;
+
+#[derive(Component)]
+pub struct ChallengesPanelText;
+
+// This week's goals down the right of the join screen
+pub fn setup_challenges_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                top: Val::Px(10.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            ChallengesPanel,
+        ))
+        .with_child((
+            Text::new(""),
+            TextFont::from_font_size(14.0),
+            ChallengesPanelText,
+        ));
+}
+
+pub fn cleanup_challenges_panel(
+    mut commands: Commands,
+    panel_query: Query<Entity, With<ChallengesPanel>>,
+) {
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn progress_bar(progress: u32, target: u32) -> String {
+    let filled = (progress.min(target) as usize * BAR_WIDTH) / target.max(1) as usize;
+    format!(
+        "[{}{}] {}/{}",
+        "#".repeat(filled),
+        ".".repeat(BAR_WIDTH - filled),
+        progress,
+        target
+    )
+}
+
+// Every goal with a progress bar for each joined profile
+pub fn update_challenges_panel_system(
+    challenges: Res<WeeklyChallenges>,
+    store: Res<ProfileStore>,
+    active: Res<ActiveProfiles>,
+    mut text_query: Query<(&mut Text, Ref<ChallengesPanelText>)>,
+) {
+    let panel_added = text_query.iter().any(|(_, panel)| panel.is_added());
+    if !panel_added && !challenges.is_changed() && !store.is_changed() && !active.is_changed() {
+        return;
+    }
+
+    let profiles: Vec<_> = (0..active.slots.len())
+        .filter_map(|slot| active.profile(&store, slot))
+        .collect();
+    let mut lines = vec!["Weekly challenges".to_string()];
+    for (index, goal) in challenges.goals.iter().enumerate() {
+        lines.push(goal.description());
+        for profile in profiles.iter() {
+            let progress = profile.challenges.progress_for(&challenges)[index];
+            lines.push(format!(
+                "  {} {}",
+                progress_bar(progress, goal.target()),
+                profile.name
+            ));
+        }
+    }
+
+    for (mut text, _) in text_query.iter_mut() {
+        text.0 = lines.join("\n");
+    }
+}
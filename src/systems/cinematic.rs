@@ -0,0 +1,226 @@
+// Scripted camera moves. A tween plays a queue of shots, each easing the main
+// camera to a position and zoom, and takes over from the kill cam and chase
+// camera while it runs. The end of a match uses it to pull back over the
+// whole map and then sweep across the winner's land before the results.
+use crate::components::{GridSettings, MainCamera};
+use crate::events::MatchEndedEvent;
+use crate::grid::GridMath;
+use crate::resources::OwnershipLayers;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use std::collections::VecDeque;
+
+// Room left around whatever a shot frames
+const FRAME_MARGIN: f32 = 1.15;
+// Closest the sweep over the winner's land gets
+const MIN_SWEEP_ZOOM: f32 = 0.35;
+const ZOOM_OUT_SECONDS: f32 = 1.5;
+const HOLD_SECONDS: f32 = 1.0;
+const MOVE_SECONDS: f32 = 1.5;
+const SWEEP_SECONDS: f32 = 4.0;
+// How quickly the camera straightens up after chase mode
+const ROTATION_EASE: f32 = 4.0;
+
+// Where the camera should end up, and how it gets there
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraShot {
+    pub position: Vec2,
+    pub scale: f32,
+    pub seconds: f32,
+    pub ease: EaseFunction,
+}
+
+impl CameraShot {
+    pub fn new(position: Vec2, scale: f32, seconds: f32) -> Self {
+        Self {
+            position,
+            scale,
+            seconds,
+            ease: EaseFunction::CubicInOut,
+        }
+    }
+
+    pub fn with_ease(mut self, ease: EaseFunction) -> Self {
+        self.ease = ease;
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct CameraTween {
+    shots: VecDeque<CameraShot>,
+    // Position and zoom the current shot started from
+    from: Option<(Vec2, f32)>,
+    elapsed: f32,
+}
+
+impl CameraTween {
+    // Replaces whatever was playing
+    pub fn play(&mut self, shots: impl IntoIterator<Item = CameraShot>) {
+        self.shots = shots.into_iter().collect();
+        self.from = None;
+        self.elapsed = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.play([]);
+    }
+
+    pub fn active(&self) -> bool {
+        !self.shots.is_empty()
+    }
+
+    // Moves `seconds` further along from the camera's `current` position and
+    // zoom, returning where it should be now
+    pub fn advance(&mut self, current: (Vec2, f32), seconds: f32) -> Option<(Vec2, f32)> {
+        let mut pose = None;
+        self.elapsed += seconds;
+
+        while let Some(shot) = self.shots.front().copied() {
+            let (from_position, from_scale) = *self.from.get_or_insert(current);
+            if self.elapsed < shot.seconds {
+                let t = EasingCurve::new(0.0, 1.0, shot.ease)
+                    .sample_clamped(self.elapsed / shot.seconds);
+                return Some((
+                    from_position.lerp(shot.position, t),
+                    from_scale + (shot.scale - from_scale) * t,
+                ));
+            }
+
+            // Finished, the next shot starts where this one left the camera
+            self.elapsed -= shot.seconds;
+            self.shots.pop_front();
+            self.from = Some((shot.position, shot.scale));
+            pose = self.from;
+        }
+
+        self.from = None;
+        self.elapsed = 0.0;
+        pose
+    }
+}
+
+// Zoom that fits `size` world units on screen with some room around it
+pub fn fit_scale(size: Vec2, viewport: Vec2) -> f32 {
+    let ratio = size / viewport.max(Vec2::ONE);
+    ratio.x.max(ratio.y) * FRAME_MARGIN
+}
+
+// Corners of the tiles a player holds, None without any land
+pub fn territory_bounds(layers: &OwnershipLayers, grid: &GridMath, player: Entity) -> Option<Rect> {
+    let layer = layers.layer(player)?;
+    let width = layers.width.max(1) as usize;
+    let mut bounds: Option<Rect> = None;
+    for index in layer.ones() {
+        let (x, y) = ((index % width) as i32, (index / width) as i32);
+        let tile = Rect::from_corners(
+            grid.corner_of(x, y),
+            grid.corner_of(x, y) + Vec2::splat(grid.tile_size),
+        );
+        bounds = Some(bounds.map_or(tile, |bounds| bounds.union(tile)));
+    }
+    bounds
+}
+
+// Pulls back over the whole map, holds, then sweeps across `territory` along
+// its longer side, zoomed in as far as its shorter side allows
+pub fn end_of_match_shots(
+    grid: &GridMath,
+    territory: Option<Rect>,
+    viewport: Vec2,
+) -> Vec<CameraShot> {
+    let overview = fit_scale(grid.half_extent() * 2.0, viewport);
+    let mut shots = vec![
+        CameraShot::new(Vec2::ZERO, overview, ZOOM_OUT_SECONDS),
+        CameraShot::new(Vec2::ZERO, overview, HOLD_SECONDS),
+    ];
+
+    let Some(territory) = territory else {
+        return shots;
+    };
+
+    let ratio = territory.size() / viewport.max(Vec2::ONE);
+    let scale = (ratio.x.min(ratio.y) * FRAME_MARGIN).clamp(MIN_SWEEP_ZOOM, overview);
+    let visible = viewport * scale;
+
+    // Start and end of the sweep, centered on any side that fits on screen
+    let sweep = |min: f32, max: f32, visible: f32| {
+        let (start, end) = (min + visible / 2.0, max - visible / 2.0);
+        if start > end {
+            let center = (min + max) / 2.0;
+            (center, center)
+        } else {
+            (start, end)
+        }
+    };
+    let (start_x, end_x) = sweep(territory.min.x, territory.max.x, visible.x);
+    let (start_y, end_y) = sweep(territory.min.y, territory.max.y, visible.y);
+
+    shots.push(CameraShot::new(
+        Vec2::new(start_x, start_y),
+        scale,
+        MOVE_SECONDS,
+    ));
+    shots.push(
+        CameraShot::new(Vec2::new(end_x, end_y), scale, SWEEP_SECONDS)
+            .with_ease(EaseFunction::SineInOut),
+    );
+    shots
+}
+
+// Plans the end of match flyover around whoever came first
+pub fn start_end_of_match_camera_system(
+    mut match_end_events: EventReader<MatchEndedEvent>,
+    grid_settings: Res<GridSettings>,
+    layers: Res<OwnershipLayers>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut tween: ResMut<CameraTween>,
+) {
+    let Some(event) = match_end_events.read().last() else {
+        return;
+    };
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    let grid = GridMath::new(&grid_settings);
+    let territory = event
+        .standings
+        .iter()
+        .find(|standing| standing.placement == 0)
+        .and_then(|winner| territory_bounds(&layers, &grid, winner.player));
+    tween.play(end_of_match_shots(&grid, territory, window.size()));
+}
+
+// Any key cuts the flyover short
+pub fn skip_camera_tween_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut tween: ResMut<CameraTween>,
+) {
+    if tween.active() && keyboard_input.get_just_pressed().next().is_some() {
+        tween.stop();
+    }
+}
+
+pub fn camera_tween_system(
+    time: Res<Time>,
+    mut tween: ResMut<CameraTween>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    if !tween.active() {
+        return;
+    }
+    let Ok((mut transform, mut projection)) = camera_query.get_single_mut() else {
+        tween.stop();
+        return;
+    };
+
+    let current = (transform.translation.truncate(), projection.scale);
+    if let Some((position, scale)) = tween.advance(current, time.delta_secs()) {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        projection.scale = scale;
+    }
+    let blend = (ROTATION_EASE * time.delta_secs()).min(1.0);
+    transform.rotation = transform.rotation.slerp(Quat::IDENTITY, blend);
+}
@@ -0,0 +1,112 @@
+// Claim preview. While a local player is out on a trail, the area they'd take
+// by heading straight home is faintly tinted in their color. Only players on
+// this machine get one, so online opponents never see what you're after.
+use crate::components::{GridSettings, LocalPlayer, Player, Tiles};
+use crate::grid::GridMath;
+use crate::systems::coach::{claim_preview_task, CoachOverlay};
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, Task};
+use std::collections::HashMap;
+
+// Fainter than the coach overlay, which shows the same thing when on
+const PREVIEW_ALPHA: f32 = 0.12;
+
+// Preview being worked out for one player
+struct PendingPreview {
+    // Tile the last preview was started from
+    computed_from: Option<(i32, i32)>,
+    task: Option<Task<Vec<(i32, i32)>>>,
+}
+
+#[derive(Resource, Default)]
+pub struct ClaimPreviews {
+    pending: HashMap<Entity, PendingPreview>,
+}
+
+// One tinted tile of a player's preview
+#[derive(Component)]
+pub struct ClaimPreviewShade {
+    pub player: Entity,
+}
+
+fn clear_preview(
+    commands: &mut Commands,
+    shade_query: &Query<(Entity, &ClaimPreviewShade)>,
+    player: Entity,
+) {
+    for (entity, shade) in shade_query.iter() {
+        if shade.player == player {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Restarts a player's preview every time their trail reaches a new tile and
+// swaps in the tint once it's ready
+pub fn claim_preview_system(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    coach: Res<CoachOverlay>,
+    mut previews: ResMut<ClaimPreviews>,
+    player_query: Query<(Entity, &Player), With<LocalPlayer>>,
+    tiles: Res<Tiles>,
+    shade_query: Query<(Entity, &ClaimPreviewShade)>,
+) {
+    let grid = GridMath::new(&grid_settings);
+
+    // Anyone no longer out on a trail, or gone, loses their preview
+    let drawing: Vec<Entity> = player_query
+        .iter()
+        .filter(|(_, player)| player.is_drawing_trail && !coach.enabled)
+        .map(|(entity, _)| entity)
+        .collect();
+    previews.pending.retain(|player, _| {
+        let keep = drawing.contains(player);
+        if !keep {
+            clear_preview(&mut commands, &shade_query, *player);
+        }
+        keep
+    });
+
+    for (player_entity, player) in player_query.iter() {
+        if !drawing.contains(&player_entity) {
+            continue;
+        }
+
+        let pending = previews
+            .pending
+            .entry(player_entity)
+            .or_insert(PendingPreview {
+                computed_from: None,
+                task: None,
+            });
+
+        if let Some(task) = pending.task.as_mut() {
+            if let Some(tiles) = block_on(future::poll_once(task)) {
+                pending.task = None;
+                clear_preview(&mut commands, &shade_query, player_entity);
+
+                for (x, y) in tiles {
+                    commands.spawn((
+                        Sprite {
+                            color: player.color.with_alpha(PREVIEW_ALPHA),
+                            custom_size: Some(Vec2::splat(grid.tile_size)),
+                            ..default()
+                        },
+                        Transform::from_translation(grid.center_of(x, y).extend(-0.06)),
+                        ClaimPreviewShade {
+                            player: player_entity,
+                        },
+                    ));
+                }
+            }
+        }
+
+        let from = Some(player.last_tile_pos);
+        if pending.computed_from == from || pending.task.is_some() {
+            continue;
+        }
+        pending.computed_from = from;
+        pending.task = claim_preview_task(&grid_settings, &tiles, player_entity, player);
+    }
+}
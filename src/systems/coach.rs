@@ -0,0 +1,140 @@
+use crate::components::{Followed, GridSettings, LocalPlayer, Player, Tiles};
+use crate::grid::GridMath;
+use crate::territory::{claim_preview, manhattan_path, TileMap};
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
+use fixedbitset::FixedBitSet;
+
+// Practice/spectator overlay shading what the current trail would claim if
+// it headed straight home now. Toggled with F3.
+#[derive(Resource, Default)]
+pub struct CoachOverlay {
+    pub enabled: bool,
+    // Player and tile the last preview was started from
+    pub computed_from: Option<(Entity, (i32, i32))>,
+    pub task: Option<Task<Vec<(i32, i32)>>>,
+}
+
+// One shaded tile of the claim preview
+#[derive(Component)]
+pub struct CoachShade;
+
+fn clear_shades(commands: &mut Commands, shade_query: &Query<Entity, With<CoachShade>>) {
+    for entity in shade_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn toggle_coach_overlay_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<CoachOverlay>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+type FocusQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Player), Or<(With<LocalPlayer>, With<Followed>)>>;
+
+// Recomputes the preview on a background task every time the focused
+// player's trail grows, and swaps in the new shading once it's ready
+pub fn coach_overlay_system(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    mut overlay: ResMut<CoachOverlay>,
+    player_query: FocusQuery,
+    tiles: Res<Tiles>,
+    shade_query: Query<Entity, With<CoachShade>>,
+) {
+    if !overlay.enabled {
+        // Turned off, from the hotkey or the settings
+        if overlay.computed_from.is_some() || !shade_query.is_empty() {
+            overlay.task = None;
+            overlay.computed_from = None;
+            clear_shades(&mut commands, &shade_query);
+        }
+        return;
+    }
+
+    let grid = GridMath::new(&grid_settings);
+    let tile_size = grid.tile_size;
+
+    // Focus on the first local player, or whoever an observer follows
+    let focus = player_query.iter().next();
+
+    if let Some(task) = overlay.task.as_mut() {
+        if let Some(tiles) = block_on(future::poll_once(task)) {
+            overlay.task = None;
+            clear_shades(&mut commands, &shade_query);
+
+            let color = focus.map_or(Color::WHITE, |(_, player)| player.color);
+            for (x, y) in tiles {
+                commands.spawn((
+                    Sprite {
+                        color: color.with_alpha(0.25),
+                        custom_size: Some(Vec2::splat(tile_size)),
+                        ..default()
+                    },
+                    Transform::from_translation(grid.center_of(x, y).extend(-0.05)),
+                    CoachShade,
+                ));
+            }
+        }
+    }
+
+    let Some((player_entity, player)) = focus.filter(|(_, player)| player.is_drawing_trail) else {
+        if overlay.computed_from.is_some() {
+            overlay.computed_from = None;
+            overlay.task = None;
+            clear_shades(&mut commands, &shade_query);
+        }
+        return;
+    };
+
+    let from = (player_entity, player.last_tile_pos);
+    if overlay.computed_from == Some(from) || overlay.task.is_some() {
+        return;
+    }
+    overlay.computed_from = Some(from);
+    overlay.task = claim_preview_task(&grid_settings, &tiles, player_entity, player);
+}
+
+// Starts working out, on a background task, what the player's trail would
+// claim if it headed straight home from where they are now
+pub fn claim_preview_task(
+    grid_settings: &GridSettings,
+    tiles: &Tiles,
+    player_entity: Entity,
+    player: &Player,
+) -> Option<Task<Vec<(i32, i32)>>> {
+    let width = grid_settings.grid_width;
+    let height = grid_settings.grid_height;
+    let tile_map = TileMap::from_tiles(width, height, tiles.iter());
+
+    let home = tile_map.nearest_territory(player.last_tile_pos, player_entity)?;
+    let mut closing_path = manhattan_path(player.last_tile_pos, home);
+    closing_path.pop(); // The last step is already home
+
+    let mut blocked = FixedBitSet::with_capacity((width * height).max(0) as usize);
+    let mut trail = Vec::new();
+    for tile in tiles.iter() {
+        if tile.x < 0 || tile.x >= width || tile.y < 0 || tile.y >= height {
+            continue;
+        }
+        if tile.owner.is_some() || tile.trail_owner.is_some() {
+            blocked.insert((tile.y * width + tile.x) as usize);
+        }
+        if tile.trail_owner == Some(player_entity) {
+            trail.push((tile.x, tile.y));
+        }
+    }
+
+    // Void is never claimed
+    Some(AsyncComputeTaskPool::get().spawn(async move {
+        claim_preview(width, height, blocked, &trail, &closing_path)
+            .into_iter()
+            .filter(|&(x, y)| tile_map.playable(x, y))
+            .collect()
+    }))
+}
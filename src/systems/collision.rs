@@ -1,10 +1,20 @@
-use crate::components::{GridSettings, Player, Tile};
-use crate::events::{PlayerDeathEvent, PlayerDeathReason};
+use crate::components::{GridSettings, Player, Respawning, Tiles};
+use crate::events::{PlayerDeathEvent, PlayerDeathReason, ShieldBrokenEvent};
+use crate::grid::GridMath;
+use crate::resources::GameRules;
+use crate::systems::comeback::Comeback;
+use crate::systems::pickups::Ghost;
+use crate::territory::trail_length;
 use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+// Frames of trail history kept for rewinding, a third of a second at 60 fps.
+// Anyone further behind is judged as if they were this far behind.
+pub const MAX_REWIND_FRAMES: usize = 20;
 
 pub fn collision_detection_system(
-    player_query: Query<(Entity, &Transform, &Player)>,
-    tile_query: Query<(Entity, &Tile, &Sprite)>,
+    player_query: Query<(Entity, &Transform, &Player), Without<Ghost>>,
+    tiles: Res<Tiles>,
     grid_settings: Res<GridSettings>,
     mut death_events: EventWriter<PlayerDeathEvent>,
 ) {
@@ -23,18 +33,15 @@ pub fn collision_detection_system(
         );
 
         // Get the grid coordinates
-        let tile_size = grid_settings.tile_size;
-        let half_width = (grid_settings.grid_width as f32 * tile_size) / 2.0;
-        let half_height = (grid_settings.grid_height as f32 * tile_size) / 2.0;
-        let current_x = ((player_transform.translation.x + half_width) / tile_size).floor() as i32;
-        let current_y = ((player_transform.translation.y + half_height) / tile_size).floor() as i32;
+        let grid = GridMath::new(&grid_settings);
+        let (current_x, current_y) = grid.tile_of(player_pos);
 
         // Collect all trail tiles that could be collided with
         let mut trail_positions = Vec::new();
 
-        for (_, tile, _) in tile_query.iter() {
+        for tile in tiles.iter() {
             // Only consider collisions with the player's own trail
-            if tile.owner == Some(player_entity) && tile.is_trail {
+            if tile.trail_owner == Some(player_entity) {
                 // Skip the current tile and immediate neighbors (safe zone)
                 let dx = (tile.x - current_x).abs();
                 let dy = (tile.y - current_y).abs();
@@ -52,12 +59,10 @@ pub fn collision_detection_system(
 
         for &(tx, ty) in &trail_positions {
             // Calculate distance to this trail tile's center
-            let trail_center_x = (tx as f32 * tile_size) - half_width + (tile_size / 2.0);
-            let trail_center_y = (ty as f32 * tile_size) - half_height + (tile_size / 2.0);
-            let trail_pos = Vec2::new(trail_center_x, trail_center_y);
+            let trail_pos = grid.center_of(tx, ty);
 
             // Original collision threshold
-            let collision_threshold = tile_size * 0.7; // Slightly more forgiving
+            let collision_threshold = grid.tile_size * 0.7; // Slightly more forgiving
 
             if player_pos.distance(trail_pos) < collision_threshold {
                 collision_detected = true;
@@ -73,7 +78,128 @@ pub fn collision_detection_system(
             death_events.send(PlayerDeathEvent {
                 player_entity,
                 reason: PlayerDeathReason::TrailCollision,
+                killer: None,
+                tile: (current_x, current_y),
+                trail_length: trail_length(tiles.iter(), player_entity),
             });
         }
     }
 }
+
+// Trail tiles and their owners at the end of each recent frame, newest last.
+// Lets a cut be judged against the trails a lagging player could see.
+#[derive(Resource, Default)]
+pub struct TrailHistory {
+    frames: VecDeque<HashMap<(i32, i32), Entity>>,
+}
+
+impl TrailHistory {
+    // Owner of the trail on `tile` as of `frames_ago` recorded frames back,
+    // 0 being the most recent one
+    pub fn owner_at(&self, frames_ago: usize, tile: (i32, i32)) -> Option<Entity> {
+        let newest = self.frames.len().checked_sub(1)?;
+        let index = newest.saturating_sub(frames_ago.min(MAX_REWIND_FRAMES - 1));
+        self.frames[index].get(&tile).copied()
+    }
+}
+
+// How many frames behind the server a player sees the match. Cuts they make
+// are judged against the trails as they were that far back.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct LagCompensation {
+    pub frames_behind: usize,
+}
+
+pub fn record_trail_history_system(mut history: ResMut<TrailHistory>, tiles: Res<Tiles>) {
+    let trails = tiles
+        .iter()
+        .filter_map(|tile| tile.trail_owner.map(|owner| ((tile.x, tile.y), owner)))
+        .collect();
+    history.frames.push_back(trails);
+    while history.frames.len() > MAX_REWIND_FRAMES {
+        history.frames.pop_front();
+    }
+}
+
+type CutPlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Player,
+        Option<&'static LagCompensation>,
+        Has<Respawning>,
+        Has<Ghost>,
+        Option<&'static Comeback>,
+    ),
+>;
+
+// Kills whoever owns the trail a player just ran onto. Players with lag
+// compensation are judged against the trails they were seeing, so a cut
+// still counts if the victim closed their loop in the meantime. Ghosts pass
+// through trails, so they neither cut nor get cut.
+pub fn trail_cut_system(
+    rules: Res<GameRules>,
+    history: Res<TrailHistory>,
+    player_query: CutPlayerQuery,
+    tiles: Res<Tiles>,
+    mut last_tiles: Local<HashMap<Entity, (i32, i32)>>,
+    mut death_events: EventWriter<PlayerDeathEvent>,
+    mut shield_events: EventWriter<ShieldBrokenEvent>,
+) {
+    if !rules.trail_cuts {
+        return;
+    }
+    last_tiles.retain(|entity, _| player_query.contains(*entity));
+    let mut shields_broken = Vec::new();
+
+    for (attacker, player, lag, respawning, ghost, _) in player_query.iter() {
+        let tile = player.last_tile_pos;
+        let entered = last_tiles.insert(attacker, tile) != Some(tile);
+        if !entered || respawning || ghost {
+            continue;
+        }
+
+        let victim = match lag {
+            Some(lag) => history.owner_at(lag.frames_behind, tile),
+            None => tiles
+                .iter()
+                .find(|other| (other.x, other.y) == tile)
+                .and_then(|other| other.trail_owner),
+        };
+        let Some(victim) = victim.filter(|&victim| victim != attacker) else {
+            continue;
+        };
+        let Ok((_, victim_player, _, victim_respawning, victim_ghost, comeback)) =
+            player_query.get(victim)
+        else {
+            continue;
+        };
+        if victim_respawning || victim_ghost {
+            continue;
+        }
+
+        // A comeback shield takes the hit, and only the one
+        if comeback.is_some_and(|comeback| comeback.shielded) && !shields_broken.contains(&victim) {
+            println!(
+                "🛡️ Trail cut at ({},{}) blocked by a shield",
+                tile.0, tile.1
+            );
+            shields_broken.push(victim);
+            shield_events.send(ShieldBrokenEvent {
+                player: victim,
+                attacker,
+            });
+            continue;
+        }
+
+        println!("✂️ Trail cut at ({},{})", tile.0, tile.1);
+        death_events.send(PlayerDeathEvent {
+            player_entity: victim,
+            reason: PlayerDeathReason::TrailCut,
+            killer: Some(attacker),
+            tile: victim_player.last_tile_pos,
+            trail_length: trail_length(tiles.iter(), victim),
+        });
+    }
+}
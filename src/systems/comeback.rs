@@ -0,0 +1,150 @@
+// Comeback help. While the rules allow it, the last placed player gets a
+// shield that soaks up one trail cut and a little extra speed, for as long as
+// they hold almost none of the map. Both go once they've recovered.
+use crate::components::{GridSettings, Player, Tiles};
+use crate::events::ShieldBrokenEvent;
+use crate::resources::{GameRules, OwnershipLayers};
+use crate::territory::land_color;
+use bevy::prelude::*;
+
+const SHIELD_COLOR: Color = Color::srgba(0.3, 0.9, 1.0, 0.45);
+
+#[derive(Component)]
+pub struct Comeback {
+    // Still has the one hit the shield can take
+    pub shielded: bool,
+    // Speed multiplier applied, undone when the help goes
+    pub speed_factor: f32,
+}
+
+fn end_comeback(commands: &mut Commands, entity: Entity, player: &mut Player, comeback: &Comeback) {
+    player.speed /= comeback.speed_factor;
+    commands.entity(entity).remove::<Comeback>();
+}
+
+// Hands the help to the last placed player while their share is small, and
+// takes it back from anyone who has moved up or grown past it
+pub fn comeback_system(
+    mut commands: Commands,
+    rules: Res<GameRules>,
+    layers: Res<OwnershipLayers>,
+    mut player_query: Query<(Entity, &mut Player, Option<&Comeback>)>,
+) {
+    let Some(comeback_rules) = rules.comeback else {
+        for (entity, mut player, comeback) in player_query.iter_mut() {
+            if let Some(comeback) = comeback {
+                end_comeback(&mut commands, entity, &mut player, comeback);
+            }
+        }
+        return;
+    };
+
+    // Only someone alone at the bottom, a tie for last gets no help
+    let mut ranked: Vec<(Entity, u32)> = player_query
+        .iter()
+        .map(|(entity, player, _)| (entity, player.score))
+        .collect();
+    ranked.sort_by_key(|&(_, score)| score);
+    let map_value = layers.total_value().max(1) as f32;
+    let last_place = match ranked.as_slice() {
+        [(last, score), (_, next), ..]
+            if score < next
+                && (layers.territory_value(*last) as f32 / map_value)
+                    < comeback_rules.max_share =>
+        {
+            Some(*last)
+        }
+        _ => None,
+    };
+
+    for (entity, mut player, comeback) in player_query.iter_mut() {
+        match (Some(entity) == last_place, comeback) {
+            (true, None) => {
+                let speed_factor = 1.0 + comeback_rules.speed_bonus;
+                player.speed *= speed_factor;
+                println!("🛡️ Comeback shield for the player in last place");
+                commands.entity(entity).insert(Comeback {
+                    shielded: true,
+                    speed_factor,
+                });
+            }
+            (false, Some(comeback)) => {
+                println!("Comeback help ends, the player has recovered");
+                end_comeback(&mut commands, entity, &mut player, comeback);
+            }
+            _ => {}
+        }
+    }
+}
+
+// A broken shield costs the player their trail instead of their life
+pub fn break_comeback_shields_system(
+    mut shield_events: EventReader<ShieldBrokenEvent>,
+    mut player_query: Query<(&mut Player, Option<&mut Comeback>)>,
+    mut tiles: ResMut<Tiles>,
+) {
+    for event in shield_events.read() {
+        let Ok((mut player, Some(mut comeback))) = player_query.get_mut(event.player) else {
+            continue;
+        };
+        if !comeback.shielded {
+            continue;
+        }
+        comeback.shielded = false;
+        player.is_drawing_trail = false;
+        println!("🛡️ Comeback shield broke, the trail is lost");
+
+        for (tile, tile_color) in tiles.iter_mut() {
+            if tile.trail_owner != Some(event.player) {
+                continue;
+            }
+
+            tile.trail_owner = None;
+            let owner_color = tile
+                .owner
+                .and_then(|owner| player_query.get(owner).ok())
+                .map(|(owner, _)| owner.color);
+            tile_color.0 = land_color(tile, owner_color);
+        }
+    }
+}
+
+// Bubble drawn around a shielded player
+#[derive(Component)]
+pub struct ComebackShieldBubble {
+    pub player: Entity,
+}
+
+pub fn comeback_shield_bubble_system(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    shielded_query: Query<(Entity, &Transform, &Comeback), Without<ComebackShieldBubble>>,
+    mut bubble_query: Query<(Entity, &ComebackShieldBubble, &mut Transform)>,
+) {
+    let mut has_bubble = Vec::new();
+    for (entity, bubble, mut transform) in bubble_query.iter_mut() {
+        match shielded_query.get(bubble.player) {
+            Ok((_, target, comeback)) if comeback.shielded => {
+                // Between the tiles and the player so the bubble rings them
+                transform.translation = target.translation.truncate().extend(-0.02);
+                has_bubble.push(bubble.player);
+            }
+            _ => commands.entity(entity).despawn_recursive(),
+        }
+    }
+
+    for (entity, target, comeback) in shielded_query.iter() {
+        if !comeback.shielded || has_bubble.contains(&entity) {
+            continue;
+        }
+        commands.spawn((
+            Sprite {
+                color: SHIELD_COLOR,
+                custom_size: Some(Vec2::splat(grid_settings.tile_size * 1.5)),
+                ..default()
+            },
+            Transform::from_translation(target.translation.truncate().extend(-0.02)),
+            ComebackShieldBubble { player: entity },
+        ));
+    }
+}
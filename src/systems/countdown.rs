@@ -0,0 +1,134 @@
+use crate::resources::GameState;
+use bevy::prelude::*;
+
+// Length of the 3-2-1 countdown before a match
+pub const COUNTDOWN_SECONDS: f32 = 3.0;
+// How long "GO!" stays up once the countdown is over
+const GO_SECONDS: f32 = 0.8;
+
+// Present while a match is counting down. Input and movement are locked and
+// the match timer doesn't run until it's gone.
+#[derive(Resource)]
+pub struct MatchCountdown {
+    pub timer: Timer,
+}
+
+impl MatchCountdown {
+    // Number to show, 3 then 2 then 1
+    pub fn seconds_left(&self) -> u32 {
+        self.timer.remaining_secs().ceil() as u32
+    }
+}
+
+// Run condition for systems that must wait for GO
+pub fn countdown_finished(countdown: Option<Res<MatchCountdown>>) -> bool {
+    countdown.is_none()
+}
+
+pub fn start_countdown(mut commands: Commands) {
+    commands.insert_resource(MatchCountdown {
+        timer: Timer::from_seconds(COUNTDOWN_SECONDS, TimerMode::Once),
+    });
+}
+
+// Starts the match at GO
+pub fn countdown_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut game_state: ResMut<GameState>,
+    countdown: Option<ResMut<MatchCountdown>>,
+) {
+    let Some(mut countdown) = countdown else {
+        return;
+    };
+
+    if countdown.timer.tick(time.delta()).finished() {
+        commands.remove_resource::<MatchCountdown>();
+        game_state.game_running = true;
+    }
+}
+
+// Online the server says when it's GO, and starts the match itself. The
+// countdown here only runs down what's shown, starting whenever the server's
+// countdown message comes in.
+pub fn mirror_countdown_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    countdown: Option<ResMut<MatchCountdown>>,
+    text_query: Query<(), With<CountdownText>>,
+) {
+    let Some(mut countdown) = countdown else {
+        return;
+    };
+
+    if countdown.is_added() && text_query.is_empty() {
+        spawn_countdown_text(&mut commands);
+    }
+    if countdown.timer.tick(time.delta()).finished() {
+        commands.remove_resource::<MatchCountdown>();
+    }
+}
+
+// Drops a countdown still running when we leave the server
+pub fn cleanup_countdown(mut commands: Commands, text_query: Query<&Parent, With<CountdownText>>) {
+    commands.remove_resource::<MatchCountdown>();
+    for parent in text_query.iter() {
+        commands.entity(parent.get()).despawn_recursive();
+    }
+}
+
+// Big centred countdown number, turns into GO! and then goes away
+#[derive(Component)]
+pub struct CountdownText {
+    pub go_timer: Timer,
+}
+
+pub fn setup_countdown_text(mut commands: Commands) {
+    spawn_countdown_text(&mut commands);
+}
+
+fn spawn_countdown_text(commands: &mut Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            PickingBehavior::IGNORE,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont::from_font_size(96.0),
+                TextColor(Color::WHITE),
+                CountdownText {
+                    go_timer: Timer::from_seconds(GO_SECONDS, TimerMode::Once),
+                },
+            ));
+        });
+}
+
+pub fn update_countdown_text_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    countdown: Option<Res<MatchCountdown>>,
+    mut text_query: Query<(&mut Text, &mut CountdownText, &Parent)>,
+) {
+    for (mut text, mut countdown_text, parent) in text_query.iter_mut() {
+        match countdown.as_ref() {
+            Some(countdown) => {
+                text.0 = countdown.seconds_left().to_string();
+            }
+            None => {
+                text.0 = "GO!".to_string();
+                if countdown_text.go_timer.tick(time.delta()).finished() {
+                    commands.entity(parent.get()).despawn_recursive();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use crate::components::{GridSettings, LocalPlayer, MapShape, Player, ValueZone};
+use crate::events::MatchEndedEvent;
+use crate::profiles::{ActiveProfiles, ProfileStore};
+use crate::progression::{UnlockableMap, MAPS};
+use crate::resources::{GameState, OwnershipLayers, PendingClaims};
+use crate::states::AppState;
+use crate::systems::bots::Bot;
+use crate::systems::input::{DirectionIntent, InputSource};
+use crate::systems::join::SelectedMap;
+use crate::systems::stats::MatchStats;
+use crate::{player_bundle_at, rank_standings, spawn_grid};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Mixed into the day number so the seed isn't just 0, 1, 2...
+const CHALLENGE_SALT: u64 = 0x1a4d_10c4_a11e_46e5;
+// Closest a challenge bot may start to the player or to another bot
+const MIN_SPAWN_GAP: i32 = 8;
+// Bots need room for their starting territory plus a loop before the edge
+const SPAWN_MARGIN: i32 = 4;
+
+const GOAL_PERCENTS: [u32; 4] = [10, 15, 20, 25];
+const TIME_LIMITS: [f32; 3] = [120.0, 180.0, 240.0];
+// Width, height and tile size of the day's map, each filling the window
+const MAP_SIZES: [(i32, i32, f32); 4] = [
+    (32, 24, 25.0),
+    (40, 30, 20.0),
+    (48, 36, 16.0),
+    (56, 42, 14.0),
+];
+const MAP_SHAPES: [MapShape; 4] = [
+    MapShape::Rectangle,
+    MapShape::Circle,
+    MapShape::Cross,
+    MapShape::Islands,
+];
+
+// Days since the Unix epoch, so the challenge changes at midnight UTC
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChallengeBot {
+    pub tile: (i32, i32),
+    pub speed: f32,
+}
+
+// Share of the map to hold before the time runs out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChallengeGoal {
+    pub territory_percent: u32,
+    pub time_limit: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChallengeOutcome {
+    Completed,
+    Failed,
+}
+
+// The day's challenge. Everything about it comes from the date, so everyone
+// playing on the same day gets the same map, bots and goal.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct DailyChallenge {
+    pub day: u64,
+    // Played with the edges picked on the join screen
+    pub map: GridSettings,
+    pub bots: Vec<ChallengeBot>,
+    pub goal: ChallengeGoal,
+    pub outcome: Option<ChallengeOutcome>,
+}
+
+impl DailyChallenge {
+    pub fn generate(day: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(day ^ CHALLENGE_SALT);
+        let map = generate_map(&mut rng);
+        let (w, h) = (map.grid_width, map.grid_height);
+
+        // The player starts as near the middle as the map allows
+        let mut taken = vec![map.spawn_tile_near((w / 2, h / 2))];
+        let mut bots = Vec::new();
+        let bot_count = rng.random_range(1..=4);
+
+        for _ in 0..100 {
+            if bots.len() == bot_count {
+                break;
+            }
+
+            let tile = (
+                rng.random_range(SPAWN_MARGIN..w - SPAWN_MARGIN),
+                rng.random_range(SPAWN_MARGIN..h - SPAWN_MARGIN),
+            );
+            let crowded = taken.iter().any(|&(x, y)| {
+                (x - tile.0).abs() < MIN_SPAWN_GAP && (y - tile.1).abs() < MIN_SPAWN_GAP
+            });
+            // Starting territory all on the map
+            let roomy =
+                (-2..=2).all(|dy| (-2..=2).all(|dx| map.playable(tile.0 + dx, tile.1 + dy)));
+            if crowded || !roomy {
+                continue;
+            }
+
+            taken.push(tile);
+            bots.push(ChallengeBot {
+                tile,
+                // Half-tile steps either side of the player's 5 tiles/s
+                speed: rng.random_range(8..=11) as f32 * 0.5,
+            });
+        }
+
+        Self {
+            day,
+            map,
+            bots,
+            goal: ChallengeGoal {
+                territory_percent: GOAL_PERCENTS[rng.random_range(0..GOAL_PERCENTS.len())],
+                time_limit: TIME_LIMITS[rng.random_range(0..TIME_LIMITS.len())],
+            },
+            outcome: None,
+        }
+    }
+
+    pub fn description(&self) -> String {
+        let seconds = self.goal.time_limit as u32;
+        format!(
+            "Claim {}% of the map in {}:{:02} against {} bot{}",
+            self.goal.territory_percent,
+            seconds / 60,
+            seconds % 60,
+            self.bots.len(),
+            if self.bots.len() == 1 { "" } else { "s" }
+        )
+    }
+}
+
+// Size, outline and valuable spots of the day's map
+fn generate_map(rng: &mut StdRng) -> GridSettings {
+    let (grid_width, grid_height, tile_size) = MAP_SIZES[rng.random_range(0..MAP_SIZES.len())];
+    let mut map = GridSettings {
+        grid_width,
+        grid_height,
+        tile_size,
+        shape: MAP_SHAPES[rng.random_range(0..MAP_SHAPES.len())].clone(),
+        ..default()
+    };
+
+    for _ in 0..rng.random_range(0..=2) {
+        let center = (
+            rng.random_range(SPAWN_MARGIN..grid_width - SPAWN_MARGIN),
+            rng.random_range(SPAWN_MARGIN..grid_height - SPAWN_MARGIN),
+        );
+        let zone = ValueZone {
+            center: map.spawn_tile_near(center),
+            radius: rng.random_range(2..=4),
+            value: rng.random_range(2..=3),
+        };
+        map.value_zones.push(zone);
+    }
+    map
+}
+
+// Per-profile record of finished daily challenges
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailyRecord {
+    pub last_completed: Option<u64>,
+    pub streak: u32,
+    pub best_streak: u32,
+    pub completed: u32,
+}
+
+impl DailyRecord {
+    pub fn complete(&mut self, day: u64) {
+        if self.last_completed == Some(day) {
+            return;
+        }
+
+        self.streak = match self.last_completed {
+            Some(last) if last + 1 == day => self.streak + 1,
+            _ => 1,
+        };
+        self.best_streak = self.best_streak.max(self.streak);
+        self.last_completed = Some(day);
+        self.completed += 1;
+    }
+
+    // A streak survives until a whole day goes by without a completion
+    pub fn current_streak(&self, today: u64) -> u32 {
+        match self.last_completed {
+            Some(last) if last + 1 >= today => self.streak,
+            _ => 0,
+        }
+    }
+}
+
+// Adds the day's bots and time limit to a match started as a daily challenge
+pub fn start_daily_challenge(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    challenge: Option<Res<DailyChallenge>>,
+    mut game_state: ResMut<GameState>,
+) {
+    let Some(challenge) = challenge else {
+        return;
+    };
+
+    for (i, bot) in challenge.bots.iter().enumerate() {
+        let speed = bot.speed;
+        commands
+            .spawn((
+                player_bundle_at(&grid_settings, i + 1, bot.tile),
+                InputSource::External,
+                DirectionIntent::default(),
+                Bot::default(),
+            ))
+            .entry::<Player>()
+            .and_modify(move |mut player| player.speed = speed);
+    }
+
+    // A fresh clock, whatever the last match left behind
+    *game_state = GameState {
+        timer: Timer::from_seconds(challenge.goal.time_limit, TimerMode::Once),
+        ..default()
+    };
+    println!("Daily challenge: {}", challenge.description());
+}
+
+// Leaving the daily match ends the challenge and lays the join screen's map
+// back out
+pub fn end_daily_challenge(
+    mut commands: Commands,
+    challenge: Option<Res<DailyChallenge>>,
+    selected: Res<SelectedMap>,
+    mut grid_settings: ResMut<GridSettings>,
+) {
+    if challenge.is_none() {
+        return;
+    }
+    commands.remove_resource::<DailyChallenge>();
+
+    let map = selected
+        .0
+        .and_then(|index| MAPS.get(index))
+        .map_or_else(GridSettings::default, UnlockableMap::grid_settings);
+    *grid_settings = GridSettings {
+        topology: grid_settings.topology,
+        ..map
+    };
+    spawn_grid(&mut commands, &grid_settings);
+}
+
+// Once the challenge is won or lost, Enter goes back to the join screen
+pub fn leave_daily_challenge_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    challenge: Option<Res<DailyChallenge>>,
+    mut game_state: ResMut<GameState>,
+    mut pending_claims: ResMut<PendingClaims>,
+    mut next_state: ResMut<NextState<AppState>>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    let Some(challenge) = challenge else {
+        return;
+    };
+    if challenge.outcome.is_none() || !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+
+    // The map itself is laid out again when the challenge ends
+    for entity in player_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    pending_claims.tasks.clear();
+    pending_claims.cancelled.clear();
+    *game_state = GameState::default();
+    next_state.set(AppState::Join);
+}
+
+// Finishes the challenge as soon as the goal is met, ending the match there,
+// or fails it when the clock runs out first
+#[allow(clippy::too_many_arguments)]
+pub fn daily_challenge_goal_system(
+    challenge: Option<ResMut<DailyChallenge>>,
+    layers: Res<OwnershipLayers>,
+    mut game_state: ResMut<GameState>,
+    stats: Res<MatchStats>,
+    local_query: Query<Entity, With<LocalPlayer>>,
+    player_query: Query<(Entity, &Player)>,
+    mut match_end_events: EventWriter<MatchEndedEvent>,
+) {
+    let Some(mut challenge) = challenge else {
+        return;
+    };
+    if challenge.outcome.is_some() {
+        return;
+    }
+
+    let total = layers.total_value();
+    let held = local_query
+        .iter()
+        .map(|entity| layers.territory_value(entity))
+        .max()
+        .unwrap_or(0);
+
+    if held * 100 >= challenge.goal.territory_percent * total {
+        challenge.outcome = Some(ChallengeOutcome::Completed);
+        game_state.game_running = false;
+
+        let winners: Vec<Entity> = local_query.iter().collect();
+        let scores = player_query
+            .iter()
+            .map(|(entity, player)| (entity, player.score));
+        let standings = rank_standings(scores, &winners);
+        match_end_events.send(MatchEndedEvent {
+            awards: stats.awards(&standings, game_state.timer.elapsed_secs()),
+            standings,
+        });
+        println!("Daily challenge complete!");
+    } else if game_state.timer.finished() {
+        challenge.outcome = Some(ChallengeOutcome::Failed);
+        println!("Daily challenge failed");
+    }
+}
+
+// Goal and progress shown during a daily challenge
+#[derive(Component)]
+pub struct DailyChallengeHud;
+
+pub fn setup_daily_challenge_hud(mut commands: Commands, challenge: Option<Res<DailyChallenge>>) {
+    if challenge.is_none() {
+        return;
+    }
+
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(16.0),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        },
+        DailyChallengeHud,
+    ));
+}
+
+pub fn update_daily_challenge_hud_system(
+    challenge: Option<Res<DailyChallenge>>,
+    layers: Res<OwnershipLayers>,
+    local_query: Query<Entity, With<LocalPlayer>>,
+    mut hud_query: Query<&mut Text, With<DailyChallengeHud>>,
+) {
+    let Some(challenge) = challenge else {
+        return;
+    };
+
+    let total = layers.total_value() as f32;
+    let held = local_query
+        .iter()
+        .map(|entity| layers.territory_value(entity))
+        .max()
+        .unwrap_or(0);
+    let percent = held as f32 / total * 100.0;
+
+    let status = match challenge.outcome {
+        Some(ChallengeOutcome::Completed) => "Complete!".to_string(),
+        Some(ChallengeOutcome::Failed) => "Failed".to_string(),
+        None => format!("{:.0}% / {}%", percent, challenge.goal.territory_percent),
+    };
+
+    for mut text in hud_query.iter_mut() {
+        text.0 = format!("Daily: {}", status);
+    }
+}
+
+// Counts a completed challenge towards P1's streak
+pub fn record_daily_challenge_system(
+    challenge: Option<Res<DailyChallenge>>,
+    active: Res<ActiveProfiles>,
+    mut store: ResMut<ProfileStore>,
+) {
+    let Some(challenge) = challenge else {
+        return;
+    };
+    if !challenge.is_changed() || challenge.outcome != Some(ChallengeOutcome::Completed) {
+        return;
+    }
+
+    let Some(profile) = active
+        .slots
+        .first()
+        .and_then(|&index| store.profiles.get_mut(index))
+    else {
+        return;
+    };
+
+    profile.daily.complete(challenge.day);
+    println!(
+        "{} is on a {} day streak",
+        profile.name, profile.daily.streak
+    );
+}
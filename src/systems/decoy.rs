@@ -0,0 +1,210 @@
+// Decoys. A pickup that sends a copy of the collector wandering off on its
+// own for a few seconds, drawing a trail that looks real but never claims
+// anything. It isn't a player, so it never scores and nobody scores off it:
+// running into it or its trail just pops it.
+use crate::components::{GridSettings, Player, Respawning, Tiles, Upright};
+use crate::grid::GridMath;
+use bevy::prelude::*;
+use rand::seq::IndexedRandom;
+use rand::Rng;
+
+pub const DECOY_SECONDS: f32 = 5.0;
+// Chance a decoy turns at a tile when it could carry straight on
+const DECOY_TURN_CHANCE: f64 = 0.25;
+
+#[derive(Component)]
+pub struct Decoy {
+    pub owner: Entity,
+    pub timer: Timer,
+    // Tile it last reached and the way it's heading from there
+    pub tile: (i32, i32),
+    pub heading: IVec2,
+    // Tiles a second, copied from the owner
+    pub speed: f32,
+    // How far it is along to the next tile, 0 to 1
+    pub progress: f32,
+    // Tiles its fake trail covers
+    pub trail: Vec<(i32, i32)>,
+}
+
+// One tile of a decoy's fake trail
+#[derive(Component)]
+pub struct DecoyTrail {
+    pub decoy: Entity,
+}
+
+fn step((x, y): (i32, i32), heading: IVec2) -> (i32, i32) {
+    (x + heading.x, y + heading.y)
+}
+
+// Wanders like a player would: mostly straight, sometimes turning, never
+// reversing, and keeping on the map and off its own trail while it can
+fn pick_heading(grid_settings: &GridSettings, decoy: &Decoy) -> IVec2 {
+    let playable = |(x, y): (i32, i32)| grid_settings.playable(x, y);
+    let heading = decoy.heading;
+    let turns = [heading, heading.perp(), -heading.perp()];
+    let open: Vec<IVec2> = turns
+        .into_iter()
+        .filter(|&turn| {
+            let next = step(decoy.tile, turn);
+            playable(next) && !decoy.trail.contains(&next)
+        })
+        .collect();
+    let open = if open.is_empty() {
+        turns
+            .into_iter()
+            .filter(|&turn| playable(step(decoy.tile, turn)))
+            .collect()
+    } else {
+        open
+    };
+
+    let mut rng = rand::rng();
+    if open.contains(&heading) && !rng.random_bool(DECOY_TURN_CHANCE) {
+        return heading;
+    }
+    open.choose(&mut rng).copied().unwrap_or(-heading)
+}
+
+// Sends a copy of the player peeling off to one side of where they're going
+pub fn spawn_decoy(
+    commands: &mut Commands,
+    grid_settings: &GridSettings,
+    owner: Entity,
+    player: &Player,
+) {
+    let grid = GridMath::new(grid_settings);
+    let forward = player.direction.round().as_ivec2();
+    let sides = if forward == IVec2::ZERO {
+        vec![IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+    } else {
+        vec![forward.perp(), -forward.perp()]
+    };
+    let open: Vec<IVec2> = sides
+        .into_iter()
+        .filter(|&side| {
+            let (x, y) = step(player.last_tile_pos, side);
+            grid_settings.playable(x, y)
+        })
+        .collect();
+    let heading = open.choose(&mut rand::rng()).copied().unwrap_or(forward);
+
+    commands.spawn((
+        Sprite {
+            color: player.color,
+            custom_size: Some(Vec2::splat(grid_settings.tile_size * 0.8)),
+            ..default()
+        },
+        Transform::from_translation(
+            grid.center_of(player.last_tile_pos.0, player.last_tile_pos.1)
+                .extend(0.0),
+        ),
+        Decoy {
+            owner,
+            timer: Timer::from_seconds(DECOY_SECONDS, TimerMode::Once),
+            tile: player.last_tile_pos,
+            heading,
+            speed: player.speed,
+            progress: 0.0,
+            trail: Vec::new(),
+        },
+        Upright,
+    ));
+}
+
+fn despawn_decoy(
+    commands: &mut Commands,
+    decoy: Entity,
+    trail_query: &Query<(Entity, &DecoyTrail)>,
+) {
+    commands.entity(decoy).despawn();
+    for (trail_entity, trail) in trail_query.iter() {
+        if trail.decoy == decoy {
+            commands.entity(trail_entity).despawn();
+        }
+    }
+}
+
+// Walks decoys along, leaving fake trail off their owner's land, until they
+// run out of time
+pub fn move_decoys_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    grid_settings: Res<GridSettings>,
+    mut decoy_query: Query<(Entity, &mut Decoy, &mut Transform)>,
+    trail_query: Query<(Entity, &DecoyTrail)>,
+    player_query: Query<&Player>,
+    tiles: Res<Tiles>,
+) {
+    let grid = GridMath::new(&grid_settings);
+    for (entity, mut decoy, mut transform) in decoy_query.iter_mut() {
+        let color = player_query.get(decoy.owner).map(|owner| owner.color);
+        if decoy.timer.tick(time.delta()).finished() || color.is_err() {
+            despawn_decoy(&mut commands, entity, &trail_query);
+            continue;
+        }
+
+        decoy.progress += decoy.speed * time.delta_secs();
+        while decoy.progress >= 1.0 {
+            decoy.progress -= 1.0;
+            decoy.tile = step(decoy.tile, decoy.heading);
+            decoy.heading = pick_heading(&grid_settings, &decoy);
+
+            // A real trail only starts once it's off the owner's land
+            let on_own_land = tiles
+                .iter()
+                .any(|tile| (tile.x, tile.y) == decoy.tile && tile.owner == Some(decoy.owner));
+            if on_own_land || decoy.trail.contains(&decoy.tile) {
+                continue;
+            }
+            let tile = decoy.tile;
+            decoy.trail.push(tile);
+            commands.spawn((
+                Sprite {
+                    color: color.unwrap_or(Color::WHITE).with_alpha(0.8),
+                    custom_size: Some(Vec2::splat(grid_settings.tile_size)),
+                    ..default()
+                },
+                // Over the tiles, under the zone overlay
+                Transform::from_translation(grid.center_of(tile.0, tile.1).extend(-0.05)),
+                DecoyTrail { decoy: entity },
+            ));
+        }
+
+        let (next_x, next_y) = step(decoy.tile, decoy.heading);
+        let from = grid.center_of(decoy.tile.0, decoy.tile.1);
+        let to = grid.center_of(next_x, next_y);
+        transform.translation = from.lerp(to, decoy.progress).extend(0.0);
+    }
+}
+
+// Anyone but the owner running into a decoy or its trail pops it. Nobody
+// died, so there's no kill and nothing to score.
+pub fn pop_decoys_system(
+    mut commands: Commands,
+    decoy_query: Query<(Entity, &Decoy)>,
+    trail_query: Query<(Entity, &DecoyTrail)>,
+    player_query: Query<(Entity, &Player), Without<Respawning>>,
+) {
+    for (entity, decoy) in decoy_query.iter() {
+        let popped = player_query.iter().any(|(player_entity, player)| {
+            player_entity != decoy.owner
+                && (player.last_tile_pos == decoy.tile
+                    || decoy.trail.contains(&player.last_tile_pos))
+        });
+        if popped {
+            println!("Popped a decoy at ({}, {})", decoy.tile.0, decoy.tile.1);
+            despawn_decoy(&mut commands, entity, &trail_query);
+        }
+    }
+}
+
+pub fn cleanup_decoys(
+    mut commands: Commands,
+    decoy_query: Query<Entity, With<Decoy>>,
+    trail_query: Query<Entity, With<DecoyTrail>>,
+) {
+    for entity in decoy_query.iter().chain(trail_query.iter()) {
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,133 @@
+// Bot difficulty for local matches. Unless one is picked on the join screen
+// it's calibrated from how P1's past matches against bots went, so whoever
+// keeps winning finds the bots a step tougher next time.
+use crate::brain::BotDifficulty;
+use crate::components::LocalPlayer;
+use crate::events::MatchEndedEvent;
+use crate::profiles::{ActiveProfiles, Calibration, ProfileStats, ProfileStore};
+use crate::systems::bots::Bot;
+use crate::systems::input::InputSource;
+use crate::systems::join::JoinedPlayers;
+use bevy::prelude::*;
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct BotDifficultySetting {
+    // Picked on the join screen, None to go with the calibration
+    pub chosen: Option<BotDifficulty>,
+    pub calibration: Calibration,
+}
+
+impl BotDifficultySetting {
+    // What bots spawned for a local match play at
+    pub fn difficulty(&self) -> BotDifficulty {
+        self.chosen.unwrap_or(self.calibration.difficulty)
+    }
+
+    pub fn describe(&self) -> String {
+        match self.chosen {
+            Some(chosen) => format!("Bots: {} (E to change)", chosen.name()),
+            None => format!(
+                "Bots: {}, {} (E to pick one)",
+                self.calibration.difficulty.name(),
+                self.calibration.reason
+            ),
+        }
+    }
+}
+
+// Reruns the calibration whenever P1's record changes, or P1 switches
+// profile. Nobody joined yet calibrates from an empty record.
+pub fn calibrate_bot_difficulty_system(
+    store: Res<ProfileStore>,
+    active: Res<ActiveProfiles>,
+    mut setting: ResMut<BotDifficultySetting>,
+) {
+    if !store.is_changed() && !active.is_changed() {
+        return;
+    }
+    let calibration = active.profile(&store, 0).map_or_else(
+        || ProfileStats::default().calibrated_difficulty(),
+        |profile| profile.stats.calibrated_difficulty(),
+    );
+    if setting.calibration != calibration {
+        setting.calibration = calibration;
+    }
+}
+
+// E steps through the difficulties and back round to the calibrated one
+pub fn cycle_bot_difficulty_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut setting: ResMut<BotDifficultySetting>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyE) {
+        return;
+    }
+    setting.chosen = match setting.chosen {
+        None => Some(BotDifficulty::ALL[0]),
+        Some(chosen) => chosen.harder(),
+    };
+    println!("Bots: {}", setting.difficulty().name());
+}
+
+// Puts how a match against bots went on each local player's profile, once
+// per match. Only counts when every bot was set up at the same difficulty.
+pub fn record_bot_matches_system(
+    mut match_end_events: EventReader<MatchEndedEvent>,
+    joined: Res<JoinedPlayers>,
+    active: Res<ActiveProfiles>,
+    mut store: ResMut<ProfileStore>,
+    local_query: Query<&InputSource, With<LocalPlayer>>,
+    bot_query: Query<&Bot>,
+) {
+    let Some(event) = match_end_events.read().last() else {
+        return;
+    };
+    let mut difficulties = bot_query.iter().map(|bot| bot.difficulty);
+    let Some(Some(difficulty)) = difficulties.next() else {
+        return;
+    };
+    if difficulties.any(|other| other != Some(difficulty)) {
+        return;
+    }
+
+    for standing in event.standings.iter() {
+        let Ok(InputSource::Device(device)) = local_query.get(standing.player) else {
+            continue;
+        };
+        let Some(&index) = joined
+            .slot_of(*device)
+            .and_then(|slot| active.slots.get(slot))
+        else {
+            continue;
+        };
+        let Some(profile) = store.profiles.get_mut(index) else {
+            continue;
+        };
+
+        // Sharing first place counts as a win
+        let won = standing.placement == 0;
+        profile.stats.record_bot_match(difficulty, won);
+        println!(
+            "{} {} against {} bots",
+            profile.name,
+            if won { "won" } else { "lost" },
+            difficulty.name()
+        );
+    }
+}
+
+// Line on the join screen saying what the bots will play at
+#[derive(Component)]
+pub struct BotDifficultyText;
+
+pub fn update_bot_difficulty_text_system(
+    setting: Res<BotDifficultySetting>,
+    mut text_query: Query<&mut Text, With<BotDifficultyText>>,
+) {
+    for mut text in text_query.iter_mut() {
+        let describe = setting.describe();
+        if text.0 != describe {
+            text.0 = describe;
+        }
+    }
+}
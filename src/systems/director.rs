@@ -0,0 +1,108 @@
+use crate::components::LocalPlayer;
+use crate::events::PlayerDeathEvent;
+use crate::resources::{GameRules, OwnershipLayers};
+use crate::systems::bots::Bot;
+use bevy::prelude::*;
+
+// Seconds between difficulty adjustments
+const DIRECTOR_INTERVAL: f32 = 3.0;
+// How far the human's territory share may drift from an even split before
+// the bots are retuned
+const SHARE_TOLERANCE: f32 = 0.08;
+// How much the difficulty moves per adjustment, out of the whole range
+const DIFFICULTY_STEP: f32 = 0.1;
+// Share a kill (or a death, negatively) is worth when judging the human
+const KILL_WEIGHT: f32 = 0.05;
+
+// Keeps single-player matches close by making bots tougher while the human
+// is ahead and easing off while they're behind
+#[derive(Resource)]
+pub struct DifficultyDirector {
+    // 0 is the easy end of the configured bounds, 1 the hard end
+    pub level: f32,
+    pub timer: Timer,
+    // Since the last adjustment
+    pub kills: i32,
+    pub deaths: i32,
+}
+
+impl Default for DifficultyDirector {
+    fn default() -> Self {
+        Self {
+            level: 0.5,
+            timer: Timer::from_seconds(DIRECTOR_INTERVAL, TimerMode::Repeating),
+            kills: 0,
+            deaths: 0,
+        }
+    }
+}
+
+// Every match starts from the middle of the range
+pub fn reset_difficulty_director(mut commands: Commands) {
+    commands.insert_resource(DifficultyDirector::default());
+}
+
+pub fn difficulty_director_system(
+    time: Res<Time>,
+    rules: Res<GameRules>,
+    layers: Res<OwnershipLayers>,
+    mut director: ResMut<DifficultyDirector>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    local_query: Query<Entity, With<LocalPlayer>>,
+    mut bot_query: Query<(Entity, &mut Bot)>,
+) {
+    let Some(bounds) = rules.dynamic_difficulty else {
+        death_events.clear();
+        return;
+    };
+
+    // Only one human against bots counts as single-player
+    let Ok(human) = local_query.get_single() else {
+        death_events.clear();
+        return;
+    };
+    if bot_query.is_empty() {
+        death_events.clear();
+        return;
+    }
+
+    for event in death_events.read() {
+        if event.player_entity == human {
+            director.deaths += 1;
+        } else if event.killer == Some(human) {
+            director.kills += 1;
+        }
+    }
+
+    if !director.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let human_tiles = layers.tile_count(human) as f32;
+    let bot_tiles: f32 = bot_query
+        .iter()
+        .map(|(entity, _)| layers.tile_count(entity) as f32)
+        .sum();
+    let total = human_tiles + bot_tiles;
+    if total == 0.0 {
+        return;
+    }
+
+    let even_share = 1.0 / (bot_query.iter().count() + 1) as f32;
+    let performance =
+        human_tiles / total - even_share + KILL_WEIGHT * (director.kills - director.deaths) as f32;
+    director.kills = 0;
+    director.deaths = 0;
+
+    if performance > SHARE_TOLERANCE {
+        director.level = (director.level + DIFFICULTY_STEP).min(1.0);
+    } else if performance < -SHARE_TOLERANCE {
+        director.level = (director.level - DIFFICULTY_STEP).max(0.0);
+    }
+
+    let level = director.level;
+    for (_, mut bot) in bot_query.iter_mut() {
+        bot.tuning.aggression = bounds.min_aggression.lerp(bounds.max_aggression, level);
+        bot.tuning.speed_scale = bounds.min_speed_scale.lerp(bounds.max_speed_scale, level);
+    }
+}
@@ -0,0 +1,480 @@
+// Display settings: window mode and size, a frame cap, vsync, and a
+// low-power mode that only redraws menus a few times a second until there's
+// input to react to. Changes that could leave the screen unusable revert on
+// their own unless they're kept.
+use crate::config::GameConfig;
+use crate::paths::Paths;
+use crate::states::{AppState, PauseState};
+use crate::systems::projection::ViewProjection;
+use bevy::prelude::*;
+use bevy::window::{MonitorSelection, PresentMode, WindowMode};
+use bevy::winit::{UpdateMode, WinitSettings};
+use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often an idle menu is redrawn in low-power mode
+const LOW_POWER_WAIT: Duration = Duration::from_millis(100);
+// Seconds to confirm a risky change before it's undone
+pub const REVERT_SECONDS: f32 = 10.0;
+// Window sizes offered in windowed mode
+pub const RESOLUTIONS: [(u32, u32); 4] = [(800, 600), (1280, 720), (1600, 900), (1920, 1080)];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FrameCap {
+    Fps30,
+    #[default]
+    Fps60,
+    Fps120,
+    Uncapped,
+}
+
+impl FrameCap {
+    pub const ALL: [FrameCap; 4] = [
+        FrameCap::Fps30,
+        FrameCap::Fps60,
+        FrameCap::Fps120,
+        FrameCap::Uncapped,
+    ];
+
+    // Shortest a frame may take, None when uncapped
+    pub fn frame_time(self) -> Option<Duration> {
+        let fps = match self {
+            FrameCap::Fps30 => 30,
+            FrameCap::Fps60 => 60,
+            FrameCap::Fps120 => 120,
+            FrameCap::Uncapped => return None,
+        };
+        Some(Duration::from_secs(1) / fps)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FrameCap::Fps30 => "30",
+            FrameCap::Fps60 => "60",
+            FrameCap::Fps120 => "120",
+            FrameCap::Uncapped => "uncapped",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&cap| cap == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl WindowModeSetting {
+    pub const ALL: [WindowModeSetting; 3] = [
+        WindowModeSetting::Windowed,
+        WindowModeSetting::Borderless,
+        WindowModeSetting::Fullscreen,
+    ];
+
+    pub fn window_mode(self) -> WindowMode {
+        match self {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::Borderless => {
+                WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+            }
+            WindowModeSetting::Fullscreen => WindowMode::Fullscreen(MonitorSelection::Current),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WindowModeSetting::Windowed => "windowed",
+            WindowModeSetting::Borderless => "borderless",
+            WindowModeSetting::Fullscreen => "fullscreen",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+// Saved with the rest of the settings
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub window_mode: WindowModeSetting,
+    // Window size in windowed mode
+    pub resolution: (u32, u32),
+    pub frame_cap: FrameCap,
+    pub vsync: bool,
+    // Menus are redrawn only on input or every `LOW_POWER_WAIT`
+    pub low_power: bool,
+    pub projection: ViewProjection,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            window_mode: WindowModeSetting::Windowed,
+            resolution: RESOLUTIONS[0],
+            frame_cap: FrameCap::Fps60,
+            vsync: true,
+            low_power: false,
+            projection: ViewProjection::TopDown,
+        }
+    }
+}
+
+impl DisplaySettings {
+    pub fn next_resolution(&self) -> (u32, u32) {
+        let index = RESOLUTIONS
+            .iter()
+            .position(|&resolution| resolution == self.resolution);
+        match index {
+            Some(index) => RESOLUTIONS[(index + 1) % RESOLUTIONS.len()],
+            None => RESOLUTIONS[0],
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    // Matches keep going flat out, menus idle when low power is on
+    pub fn update_mode(&self, in_menu: bool) -> UpdateMode {
+        if self.low_power && in_menu {
+            UpdateMode::reactive_low_power(LOW_POWER_WAIT)
+        } else {
+            UpdateMode::Continuous
+        }
+    }
+}
+
+// A change that's undone after `REVERT_SECONDS` unless the player keeps it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PendingRevert {
+    pub previous: DisplaySettings,
+    pub seconds_left: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct DisplayRevert {
+    pub pending: Option<PendingRevert>,
+}
+
+impl DisplayRevert {
+    // Starts the countdown, or restarts it while still going back to the
+    // settings from before the first unconfirmed change
+    pub fn start(&mut self, previous: DisplaySettings) {
+        let previous = self.pending.map_or(previous, |pending| pending.previous);
+        self.pending = Some(PendingRevert {
+            previous,
+            seconds_left: REVERT_SECONDS,
+        });
+    }
+
+    pub fn keep(&mut self) {
+        self.pending = None;
+    }
+
+    // Settings to go back to, once the countdown has run out
+    pub fn tick(&mut self, seconds: f32) -> Option<DisplaySettings> {
+        let pending = self.pending.as_mut()?;
+        pending.seconds_left -= seconds;
+        if pending.seconds_left > 0.0 {
+            return None;
+        }
+        self.pending.take().map(|pending| pending.previous)
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayButton {
+    WindowMode,
+    Resolution,
+    FrameCap,
+    Vsync,
+    LowPower,
+    Projection,
+}
+
+impl DisplayButton {
+    pub const ALL: [DisplayButton; 6] = [
+        DisplayButton::WindowMode,
+        DisplayButton::Resolution,
+        DisplayButton::FrameCap,
+        DisplayButton::Vsync,
+        DisplayButton::LowPower,
+        DisplayButton::Projection,
+    ];
+
+    // Changes that could leave the game unreadable, or off screen
+    pub fn risky(self) -> bool {
+        matches!(self, DisplayButton::WindowMode | DisplayButton::Resolution)
+    }
+
+    pub fn label(self, settings: &DisplaySettings) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        match self {
+            DisplayButton::WindowMode => format!("Window: {}", settings.window_mode.label()),
+            DisplayButton::Resolution => {
+                let (width, height) = settings.resolution;
+                format!("Resolution: {}x{}", width, height)
+            }
+            DisplayButton::FrameCap => format!("Frame cap: {}", settings.frame_cap.label()),
+            DisplayButton::Vsync => format!("Vsync: {}", on_off(settings.vsync)),
+            DisplayButton::LowPower => format!("Low power menus: {}", on_off(settings.low_power)),
+            DisplayButton::Projection => format!("View: {}", settings.projection.label()),
+        }
+    }
+}
+
+pub fn display_button_system(
+    mut settings: ResMut<DisplaySettings>,
+    mut revert: ResMut<DisplayRevert>,
+    button_query: Query<(&Interaction, &DisplayButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if button.risky() {
+            revert.start(*settings);
+        }
+        match button {
+            DisplayButton::WindowMode => settings.window_mode = settings.window_mode.next(),
+            DisplayButton::Resolution => settings.resolution = settings.next_resolution(),
+            DisplayButton::FrameCap => settings.frame_cap = settings.frame_cap.next(),
+            DisplayButton::Vsync => settings.vsync = !settings.vsync,
+            DisplayButton::LowPower => settings.low_power = !settings.low_power,
+            DisplayButton::Projection => settings.projection = settings.projection.next(),
+        }
+    }
+}
+
+pub fn update_display_settings_ui_system(
+    settings: Res<DisplaySettings>,
+    button_query: Query<(&DisplayButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (button, children) in button_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.0 = button.label(&settings);
+            }
+        }
+    }
+}
+
+pub fn apply_window_settings_system(
+    settings: Res<DisplaySettings>,
+    mut window_query: Query<&mut Window>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut window in window_query.iter_mut() {
+        window.present_mode = settings.present_mode();
+        window.mode = settings.window_mode.window_mode();
+        let (width, height) = settings.resolution;
+        window.resolution.set(width as f32, height as f32);
+    }
+}
+
+// Counts down an unconfirmed change in real time, so it still reverts
+// while a match is paused
+pub fn display_revert_system(
+    time: Res<Time<Real>>,
+    mut revert: ResMut<DisplayRevert>,
+    mut settings: ResMut<DisplaySettings>,
+) {
+    if let Some(previous) = revert.tick(time.delta_secs()) {
+        println!("Display change not kept, reverting");
+        *settings = previous;
+    }
+}
+
+#[derive(Component)]
+pub struct RevertPrompt;
+
+#[derive(Component)]
+pub struct RevertCountdown;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum RevertButton {
+    Keep,
+    Revert,
+}
+
+// Asks to keep a risky change while its countdown runs
+pub fn revert_prompt_system(
+    mut commands: Commands,
+    revert: Res<DisplayRevert>,
+    prompt_query: Query<Entity, With<RevertPrompt>>,
+    mut countdown_query: Query<&mut Text, With<RevertCountdown>>,
+) {
+    let Some(pending) = revert.pending else {
+        for entity in prompt_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+
+    let countdown = format!("Reverting in {}s", pending.seconds_left.ceil() as u32);
+    if !prompt_query.is_empty() {
+        for mut text in countdown_query.iter_mut() {
+            text.0 = countdown.clone();
+        }
+        return;
+    }
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            GlobalZIndex(9),
+            PickingBehavior::IGNORE,
+            RevertPrompt,
+        ))
+        .with_children(|screen| {
+            screen
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(10.0),
+                        padding: UiRect::all(Val::Px(16.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.1, 0.1, 0.1, 0.95)),
+                ))
+                .with_children(|prompt| {
+                    prompt.spawn((
+                        Text::new("Keep these display settings?"),
+                        TextFont::from_font_size(20.0),
+                    ));
+                    prompt.spawn((
+                        Text::new(countdown),
+                        TextFont::from_font_size(14.0),
+                        RevertCountdown,
+                    ));
+                    prompt
+                        .spawn(Node {
+                            column_gap: Val::Px(12.0),
+                            ..default()
+                        })
+                        .with_children(|buttons| {
+                            for (button, label) in [
+                                (RevertButton::Keep, "Keep"),
+                                (RevertButton::Revert, "Revert"),
+                            ] {
+                                buttons
+                                    .spawn((
+                                        Node {
+                                            padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+                                            ..default()
+                                        },
+                                        BackgroundColor(Color::srgb(0.3, 0.3, 0.3)),
+                                        Button,
+                                        button,
+                                    ))
+                                    .with_children(|button| {
+                                        button.spawn((
+                                            Text::new(label),
+                                            TextFont::from_font_size(16.0),
+                                        ));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+pub fn revert_button_system(
+    mut revert: ResMut<DisplayRevert>,
+    mut settings: ResMut<DisplaySettings>,
+    button_query: Query<(&Interaction, &RevertButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            RevertButton::Keep => revert.keep(),
+            RevertButton::Revert => {
+                if let Some(pending) = revert.pending.take() {
+                    *settings = pending.previous;
+                }
+            }
+        }
+    }
+}
+
+// Switches winit between running flat out and waiting for input as the game
+// moves between matches and menus
+pub fn apply_update_mode_system(
+    settings: Res<DisplaySettings>,
+    state: Res<State<AppState>>,
+    pause: Option<Res<State<PauseState>>>,
+    mut winit: ResMut<WinitSettings>,
+) {
+    let paused = pause
+        .as_ref()
+        .is_some_and(|pause| *pause.get() == PauseState::Paused);
+    let in_menu = paused
+        || matches!(
+            state.get(),
+            AppState::Join | AppState::LevelSelect | AppState::ServerBrowser | AppState::Tournament
+        );
+    let mode = settings.update_mode(in_menu);
+    if winit.focused_mode != mode {
+        winit.focused_mode = mode;
+        winit.unfocused_mode = mode;
+    }
+}
+
+// Sleeps off whatever is left of the frame's budget. Runs last so the whole
+// frame counts against it.
+pub fn frame_limiter_system(
+    settings: Res<DisplaySettings>,
+    mut frame_start: Local<Option<Instant>>,
+) {
+    if let (Some(frame_time), Some(start)) = (settings.frame_cap.frame_time(), *frame_start) {
+        let elapsed = start.elapsed();
+        if elapsed < frame_time {
+            thread::sleep(frame_time - elapsed);
+        }
+    }
+    *frame_start = Some(Instant::now());
+}
+
+// Unconfirmed changes wait until they're kept
+pub fn persist_display_settings_system(
+    paths: Res<Paths>,
+    settings: Res<DisplaySettings>,
+    revert: Res<DisplayRevert>,
+    mut config: ResMut<GameConfig>,
+) {
+    if revert.pending.is_none() && config.display != *settings {
+        config.display = *settings;
+        config.save(&paths);
+    }
+}
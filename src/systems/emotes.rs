@@ -0,0 +1,357 @@
+// Short speech bubbles over players. Bots pipe up now and then when
+// something happens to them, and people pick one from a small wheel on Q.
+// Emotes are events like any other, so online they go through the server
+// and everyone sees them.
+use crate::components::{LocalPlayer, MainCamera, Player};
+use crate::events::{EmoteEvent, PlayerDeathEvent, PlayerDeathReason, TerritoryClaimedEvent};
+use crate::resources::GameState;
+use crate::systems::bots::Bot;
+use crate::systems::input::{InputDevice, InputSource};
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// How long a bubble stays up, the last bit of it fading out
+const BUBBLE_SECONDS: f32 = 2.5;
+const BUBBLE_FADE_SECONDS: f32 = 0.5;
+// Screen pixels between a player and the bubble over them
+const BUBBLE_OFFSET: f32 = 28.0;
+// A claim this big is worth bragging about
+const BRAG_TILES: u32 = 20;
+
+const WHEEL_KEYS: [KeyCode; 4] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Emote {
+    Hello,
+    NiceCut,
+    UhOh,
+    Gotcha,
+    AllMine,
+    GoodGame,
+}
+
+impl Emote {
+    // What people can pick from, on keys 1-4
+    pub const WHEEL: [Emote; 4] = [Emote::Hello, Emote::NiceCut, Emote::UhOh, Emote::GoodGame];
+
+    pub fn text(self) -> &'static str {
+        match self {
+            Emote::Hello => "Hi!",
+            Emote::NiceCut => "Nice cut!",
+            Emote::UhOh => "Uh oh",
+            Emote::Gotcha => "Gotcha!",
+            Emote::AllMine => "All mine!",
+            Emote::GoodGame => "GG",
+        }
+    }
+}
+
+// How chatty bots are: the chance they say something when it's their turn,
+// and how long each keeps quiet after speaking
+#[derive(Resource)]
+pub struct BotChatter {
+    pub chance: f32,
+    pub cooldown: f32,
+    // Match seconds each bot last spoke
+    last_spoke: HashMap<Entity, f32>,
+}
+
+impl Default for BotChatter {
+    fn default() -> Self {
+        Self {
+            chance: 0.35,
+            cooldown: 6.0,
+            last_spoke: HashMap::new(),
+        }
+    }
+}
+
+impl BotChatter {
+    // Whether the bot gets to speak now, noting it if it does
+    fn speak(&mut self, bot: Entity, seconds: f32, roll: f32) -> bool {
+        let rested = self
+            .last_spoke
+            .get(&bot)
+            .is_none_or(|&last| seconds - last >= self.cooldown);
+        if !rested || roll >= self.chance {
+            return false;
+        }
+        self.last_spoke.insert(bot, seconds);
+        true
+    }
+}
+
+// Bots react to deaths and claims involving them: a compliment when their
+// trail is cut, a yelp when they die some other way, a taunt after a kill
+// and a brag after a big claim
+pub fn bot_chatter_system(
+    game_state: Res<GameState>,
+    mut chatter: ResMut<BotChatter>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    mut claimed_events: EventReader<TerritoryClaimedEvent>,
+    bot_query: Query<(), With<Bot>>,
+    mut emote_events: EventWriter<EmoteEvent>,
+) {
+    let mut lines = Vec::new();
+    for event in death_events.read() {
+        let victim = event.player_entity;
+        if bot_query.contains(victim) {
+            let emote = match event.reason {
+                PlayerDeathReason::TrailCut => Emote::NiceCut,
+                _ => Emote::UhOh,
+            };
+            lines.push((victim, emote));
+        }
+        if let Some(killer) = event
+            .killer
+            .filter(|&killer| killer != victim && bot_query.contains(killer))
+        {
+            lines.push((killer, Emote::Gotcha));
+        }
+    }
+    for event in claimed_events.read() {
+        let size = event.trail_tiles + event.pockets.iter().sum::<u32>();
+        if size >= BRAG_TILES && bot_query.contains(event.player) {
+            lines.push((event.player, Emote::AllMine));
+        }
+    }
+
+    let seconds = game_state.timer.elapsed_secs();
+    let mut rng = rand::rng();
+    for (player, emote) in lines {
+        if chatter.speak(player, seconds, rng.random()) {
+            emote_events.send(EmoteEvent { player, emote });
+        }
+    }
+}
+
+// Whether the emote wheel is up
+#[derive(Resource, Default)]
+pub struct EmoteWheel {
+    pub open: bool,
+}
+
+// The wheel belongs to whoever plays on the WASD keys, which is also the
+// player in an online match
+pub fn emote_wheel_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut wheel: ResMut<EmoteWheel>,
+    local_query: Query<(Entity, &InputSource), With<LocalPlayer>>,
+    mut emote_events: EventWriter<EmoteEvent>,
+) {
+    let Some(player) = local_query
+        .iter()
+        .find(|(_, source)| matches!(source, InputSource::Device(InputDevice::KeyboardWasd)))
+        .map(|(entity, _)| entity)
+    else {
+        wheel.open = false;
+        return;
+    };
+
+    if keyboard_input.just_pressed(KeyCode::KeyQ) {
+        wheel.open = !wheel.open;
+        return;
+    }
+    if !wheel.open {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        wheel.open = false;
+        return;
+    }
+    if let Some(index) = WHEEL_KEYS
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key))
+    {
+        emote_events.send(EmoteEvent {
+            player,
+            emote: Emote::WHEEL[index],
+        });
+        wheel.open = false;
+    }
+}
+
+#[derive(Component)]
+pub struct EmoteWheelPanel;
+
+// Four choices in a diamond at the bottom of the screen, numbered clockwise
+// from the top
+pub fn emote_wheel_ui_system(
+    mut commands: Commands,
+    wheel: Res<EmoteWheel>,
+    panel_query: Query<Entity, With<EmoteWheelPanel>>,
+) {
+    if !wheel.is_changed() {
+        return;
+    }
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !wheel.open {
+        return;
+    }
+
+    let spots = [
+        (Val::Px(60.0), Val::Px(0.0)),
+        (Val::Px(120.0), Val::Px(60.0)),
+        (Val::Px(60.0), Val::Px(120.0)),
+        (Val::Px(0.0), Val::Px(60.0)),
+    ];
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Px(60.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            PickingBehavior::IGNORE,
+            EmoteWheelPanel,
+        ))
+        .with_children(|panel| {
+            panel
+                .spawn(Node {
+                    width: Val::Px(200.0),
+                    height: Val::Px(150.0),
+                    ..default()
+                })
+                .with_children(|wheel| {
+                    for (index, (emote, (left, top))) in Emote::WHEEL.iter().zip(spots).enumerate()
+                    {
+                        wheel.spawn((
+                            Text::new(format!("{} {}", index + 1, emote.text())),
+                            TextFont::from_font_size(16.0),
+                            Node {
+                                position_type: PositionType::Absolute,
+                                left,
+                                top,
+                                padding: UiRect::axes(Val::Px(6.0), Val::Px(3.0)),
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+                        ));
+                    }
+                });
+        });
+}
+
+// A bubble over a player, kept over them until it times out
+#[derive(Component)]
+pub struct EmoteBubble {
+    pub player: Entity,
+    pub timer: Timer,
+}
+
+// Puts up a bubble for each emote, replacing any the player already has
+pub fn spawn_emote_bubbles_system(
+    mut commands: Commands,
+    mut emote_events: EventReader<EmoteEvent>,
+    player_query: Query<&Player>,
+    bubble_query: Query<(Entity, &EmoteBubble)>,
+) {
+    for event in emote_events.read() {
+        let Ok(player) = player_query.get(event.player) else {
+            continue;
+        };
+        for (entity, bubble) in bubble_query.iter() {
+            if bubble.player == event.player {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        commands.spawn((
+            Text::new(event.emote.text()),
+            TextFont::from_font_size(14.0),
+            TextColor(Color::BLACK),
+            Node {
+                position_type: PositionType::Absolute,
+                padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)),
+                border: UiRect::all(Val::Px(2.0)),
+                ..default()
+            },
+            BackgroundColor(Color::WHITE),
+            BorderColor(player.color.with_alpha(1.0)),
+            BorderRadius::all(Val::Px(8.0)),
+            Visibility::Hidden,
+            PickingBehavior::IGNORE,
+            EmoteBubble {
+                player: event.player,
+                timer: Timer::from_seconds(BUBBLE_SECONDS, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+type BubbleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut EmoteBubble,
+        &'static mut Node,
+        &'static mut Visibility,
+        &'static ComputedNode,
+        &'static mut TextColor,
+        &'static mut BackgroundColor,
+    ),
+>;
+
+// Keeps bubbles over their players on screen, fading them out at the end.
+// They go with their player, and hide while the player is.
+pub fn place_emote_bubbles_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    player_query: Query<(&GlobalTransform, &ViewVisibility), With<Player>>,
+    mut bubble_query: BubbleQuery,
+) {
+    let camera = camera_query.get_single().ok();
+    for (entity, mut bubble, mut node, mut visibility, computed, mut text_color, mut background) in
+        bubble_query.iter_mut()
+    {
+        let player = player_query.get(bubble.player).ok();
+        if player.is_none() || bubble.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let Some((player_transform, player_visibility)) = player else {
+            continue;
+        };
+
+        let screen = camera.and_then(|(camera, camera_transform)| {
+            camera
+                .world_to_viewport(camera_transform, player_transform.translation())
+                .ok()
+        });
+        let Some(screen) = screen.filter(|_| player_visibility.get()) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        let size = computed.size() * computed.inverse_scale_factor();
+        node.left = Val::Px(screen.x - size.x / 2.0);
+        node.top = Val::Px(screen.y - BUBBLE_OFFSET - size.y);
+        *visibility = Visibility::Inherited;
+
+        let alpha = (bubble.timer.remaining_secs() / BUBBLE_FADE_SECONDS).min(1.0);
+        text_color.0 = text_color.0.with_alpha(alpha);
+        background.0 = background.0.with_alpha(alpha);
+    }
+}
+
+pub fn cleanup_emotes(
+    mut commands: Commands,
+    mut wheel: ResMut<EmoteWheel>,
+    bubble_query: Query<Entity, With<EmoteBubble>>,
+) {
+    wheel.open = false;
+    for entity in bubble_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,201 @@
+// Error reporting. Recoverable failures are sent as `GameError` events from
+// wherever they happen: the ones that can be tried again get a dialog with
+// Retry and Dismiss, the rest a toast. Problems found while starting up,
+// before there's anything to show them on, wait in `StartupErrors`.
+use crate::events::{GameError, GameErrorKind, RetryAction, RetryEvent, ToastEvent};
+use crate::levels::Campaign;
+use crate::paths::Paths;
+use crate::profiles::ProfileStore;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+const DIALOG_COLOR: Color = Color::srgb(0.15, 0.12, 0.12);
+const BUTTON_COLOR: Color = Color::srgb(0.3, 0.3, 0.3);
+
+// Errors from before the first frame, reported once the app is running
+#[derive(Resource, Default)]
+pub struct StartupErrors(pub Vec<GameError>);
+
+pub fn report_startup_errors_system(
+    mut startup_errors: ResMut<StartupErrors>,
+    mut error_events: EventWriter<GameError>,
+) {
+    error_events.send_batch(startup_errors.0.drain(..));
+}
+
+// Tries again whatever the player asked to. Reconnecting is up to the net
+// client.
+pub fn retry_system(
+    mut retry_events: EventReader<RetryEvent>,
+    paths: Res<Paths>,
+    store: Res<ProfileStore>,
+    mut campaign: ResMut<Campaign>,
+    mut error_events: EventWriter<GameError>,
+) {
+    for event in retry_events.read() {
+        match event.action {
+            RetryAction::ReloadLevels => {
+                let selected = campaign.selected;
+                *campaign = Campaign::load();
+                campaign.selected = selected.min(campaign.levels.len().saturating_sub(1));
+                println!("Reloaded {} levels", campaign.levels.len());
+                error_events.send_batch(campaign.errors());
+            }
+            RetryAction::SaveProfiles => {
+                if !store.save(&paths) {
+                    error_events.send(profiles_not_saved(&paths));
+                }
+            }
+            RetryAction::Reconnect => {}
+        }
+    }
+}
+
+pub fn profiles_not_saved(paths: &Paths) -> GameError {
+    GameError::new(
+        GameErrorKind::SaveFailed,
+        format!(
+            "profiles couldn't be written to {}",
+            paths.saves().display()
+        ),
+    )
+    .with_retry(RetryAction::SaveProfiles)
+}
+
+// Errors waiting for the player to retry or dismiss them, oldest first
+#[derive(Resource, Default)]
+pub struct ErrorDialogs {
+    pub waiting: VecDeque<GameError>,
+}
+
+#[derive(Component)]
+pub struct ErrorDialog;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorDialogButton {
+    Retry,
+    Dismiss,
+}
+
+// Retryable errors go to a dialog, the rest are only worth a toast
+pub fn route_game_errors_system(
+    mut error_events: EventReader<GameError>,
+    mut dialogs: ResMut<ErrorDialogs>,
+    mut toast_events: EventWriter<ToastEvent>,
+) {
+    for error in error_events.read() {
+        println!("{}", error.message());
+        if error.retry.is_none() {
+            toast_events.send(error.toast());
+        } else if !dialogs.waiting.contains(error) {
+            dialogs.waiting.push_back(error.clone());
+        }
+    }
+}
+
+// Puts up the oldest waiting error, one dialog at a time
+pub fn show_error_dialog_system(
+    mut commands: Commands,
+    dialogs: Res<ErrorDialogs>,
+    dialog_query: Query<(), With<ErrorDialog>>,
+) {
+    let Some(error) = dialogs.waiting.front() else {
+        return;
+    };
+    if !dialog_query.is_empty() {
+        return;
+    }
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+            // Over everything but the toasts
+            GlobalZIndex(9),
+            ErrorDialog,
+        ))
+        .with_children(|screen| {
+            screen
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(10.0),
+                        padding: UiRect::all(Val::Px(16.0)),
+                        max_width: Val::Px(480.0),
+                        ..default()
+                    },
+                    BackgroundColor(DIALOG_COLOR),
+                ))
+                .with_children(|dialog| {
+                    dialog.spawn((
+                        Text::new(error.kind.title()),
+                        TextFont::from_font_size(24.0),
+                        TextColor(Color::srgb(1.0, 0.4, 0.35)),
+                    ));
+                    dialog.spawn((
+                        Text::new(error.detail.clone()),
+                        TextFont::from_font_size(14.0),
+                        TextLayout::new_with_justify(JustifyText::Center),
+                    ));
+                    dialog
+                        .spawn(Node {
+                            column_gap: Val::Px(12.0),
+                            ..default()
+                        })
+                        .with_children(|buttons| {
+                            for (button, label) in [
+                                (ErrorDialogButton::Retry, "Retry"),
+                                (ErrorDialogButton::Dismiss, "Dismiss"),
+                            ] {
+                                buttons
+                                    .spawn((
+                                        Node {
+                                            padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+                                            ..default()
+                                        },
+                                        BackgroundColor(BUTTON_COLOR),
+                                        Button,
+                                        button,
+                                    ))
+                                    .with_children(|button| {
+                                        button.spawn((
+                                            Text::new(label),
+                                            TextFont::from_font_size(16.0),
+                                        ));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+pub fn error_dialog_button_system(
+    mut commands: Commands,
+    mut dialogs: ResMut<ErrorDialogs>,
+    button_query: Query<(&Interaction, &ErrorDialogButton), Changed<Interaction>>,
+    dialog_query: Query<Entity, With<ErrorDialog>>,
+    mut retry_events: EventWriter<RetryEvent>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(error) = dialogs.waiting.pop_front() else {
+            continue;
+        };
+        if let (ErrorDialogButton::Retry, Some(action)) = (button, error.retry) {
+            retry_events.send(RetryEvent { action });
+        }
+        for entity in dialog_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Maps with an upper floor lay both floors out side by side. The floor the
+// local player is on is the one in focus: the other fades on the board,
+// players on it fade too, and the minimap shows only the active one.
+use crate::components::{GridSettings, LocalPlayer, Player};
+use crate::systems::pickups::Ghost;
+use bevy::prelude::*;
+
+// How much of a tile or player on the other floor still shows
+pub const OTHER_FLOOR_ALPHA: f32 = 0.3;
+
+// Floor in focus, None on single floor maps or with nobody local to follow
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ActiveFloor(pub Option<u8>);
+
+impl ActiveFloor {
+    // Columns of the grid in view
+    pub fn columns(&self, grid_settings: &GridSettings) -> std::ops::Range<i32> {
+        match self.0 {
+            Some(floor) => grid_settings.floor_columns(floor),
+            None => 0..grid_settings.grid_width,
+        }
+    }
+
+    // Whether a tile in that column is on a floor out of focus
+    pub fn fades(&self, grid_settings: &GridSettings, x: i32) -> bool {
+        self.0.is_some() && grid_settings.floor_of(x) != self.0
+    }
+}
+
+// Follows the first local player up and down the stairs
+pub fn active_floor_system(
+    grid_settings: Res<GridSettings>,
+    mut active: ResMut<ActiveFloor>,
+    player_query: Query<&Player, With<LocalPlayer>>,
+) {
+    let floor = grid_settings
+        .upper_floor
+        .as_ref()
+        .and_then(|_| player_query.iter().next())
+        .and_then(|player| grid_settings.floor_of(player.last_tile_pos.0));
+    active.set_if_neq(ActiveFloor(floor));
+}
+
+// Players on the other floor show faintly. Ghosts already fade themselves.
+pub fn floor_fade_system(
+    grid_settings: Res<GridSettings>,
+    active: Res<ActiveFloor>,
+    mut player_query: Query<(&Player, &mut Sprite), Without<Ghost>>,
+) {
+    // Nothing to do on one floor, once everyone's back to full
+    if grid_settings.upper_floor.is_none() && !active.is_changed() {
+        return;
+    }
+    for (player, mut sprite) in player_query.iter_mut() {
+        let alpha = if active.fades(&grid_settings, player.last_tile_pos.0) {
+            OTHER_FLOOR_ALPHA
+        } else {
+            1.0
+        };
+        if sprite.color.alpha() != alpha {
+            sprite.color.set_alpha(alpha);
+        }
+    }
+}
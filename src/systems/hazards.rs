@@ -0,0 +1,229 @@
+// Hazard walls. On a schedule a line of deadly tiles is announced at one side
+// of the map, then sweeps across it, killing whoever it touches and wiping
+// any trail in its way. Territory it passes over is left alone.
+use crate::components::{GridSettings, Player, Respawning, Tiles};
+use crate::events::{HazardWarningEvent, PlayerDeathEvent, PlayerDeathReason};
+use crate::grid::GridMath;
+use crate::resources::{GameRules, GameState, HazardRules, MatchRng};
+use crate::territory::{land_color, trail_length, HazardWall, SweepDirection, ZoneBounds};
+use bevy::prelude::*;
+use rand::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScheduledWall {
+    pub wall: HazardWall,
+    // Last line it has swept, None until it starts moving
+    pub swept: Option<i32>,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct HazardSchedule {
+    pub rules: HazardRules,
+    // Announced walls that haven't left the map yet, oldest first
+    pub walls: Vec<ScheduledWall>,
+    // Match seconds the next wall starts moving at
+    pub next_wall: f32,
+    // Walls announced so far, picks the side the next one comes from
+    pub announced: usize,
+}
+
+impl HazardSchedule {
+    pub fn new(rules: HazardRules) -> Self {
+        Self {
+            rules,
+            walls: Vec::new(),
+            next_wall: rules.first_wall,
+            announced: 0,
+        }
+    }
+
+    // Lays out the next wall somewhere random along its side
+    fn announce_wall(&mut self, width: i32, height: i32, rng: &mut impl Rng) -> HazardWall {
+        let direction = SweepDirection::CYCLE[self.announced % SweepDirection::CYCLE.len()];
+        let span = match direction {
+            SweepDirection::East | SweepDirection::West => height,
+            SweepDirection::North | SweepDirection::South => width,
+        };
+        let length = self.rules.length.clamp(1, span);
+        let wall = HazardWall {
+            direction,
+            offset: rng.random_range(0..=span - length),
+            length,
+            starts_at: self.next_wall,
+            speed: self.rules.speed.max(0.1),
+        };
+        self.walls.push(ScheduledWall { wall, swept: None });
+        self.announced += 1;
+        self.next_wall += self.rules.interval.max(self.rules.warning);
+        wall
+    }
+}
+
+pub fn setup_hazard_schedule(mut commands: Commands, rules: Res<GameRules>) {
+    match rules.hazard_walls {
+        Some(hazard_rules) => commands.insert_resource(HazardSchedule::new(hazard_rules)),
+        None => commands.remove_resource::<HazardSchedule>(),
+    }
+}
+
+pub fn cleanup_hazard_schedule(mut commands: Commands) {
+    commands.remove_resource::<HazardSchedule>();
+}
+
+// Announces walls as their warning comes up, moves them along and applies
+// whatever they cross
+#[allow(clippy::too_many_arguments)]
+pub fn sweep_hazard_walls_system(
+    game_state: Res<GameState>,
+    grid_settings: Res<GridSettings>,
+    mut schedule: ResMut<HazardSchedule>,
+    mut rng: ResMut<MatchRng>,
+    player_query: Query<(Entity, &Player, Has<Respawning>)>,
+    mut tiles: ResMut<Tiles>,
+    mut death_events: EventWriter<PlayerDeathEvent>,
+    mut warning_events: EventWriter<HazardWarningEvent>,
+) {
+    if !game_state.game_running {
+        return;
+    }
+
+    let elapsed = game_state.timer.elapsed_secs();
+    let (width, height) = (grid_settings.grid_width, grid_settings.grid_height);
+
+    if elapsed >= schedule.next_wall - schedule.rules.warning {
+        let wall = schedule.announce_wall(width, height, &mut rng.0);
+        println!(
+            "Hazard wall incoming from the {}!",
+            wall.direction.from_side()
+        );
+        warning_events.send(HazardWarningEvent { wall });
+    }
+
+    // Everything crossed since last frame counts, so fast walls can't skip
+    // over anyone
+    let mut swept: Vec<ZoneBounds> = Vec::new();
+    for scheduled in schedule.walls.iter_mut() {
+        let Some(line) = scheduled.wall.line_at(elapsed, width, height) else {
+            continue;
+        };
+        let from = scheduled.swept.map_or(0, |last| (last + 1).min(line));
+        swept.push(scheduled.wall.swept_bounds(from, line, width, height));
+        scheduled.swept = Some(line);
+    }
+    schedule
+        .walls
+        .retain(|scheduled| !scheduled.wall.finished(elapsed, width, height));
+
+    if swept.is_empty() {
+        return;
+    }
+
+    for (entity, player, respawning) in player_query.iter() {
+        if respawning
+            || !swept
+                .iter()
+                .any(|bounds| bounds.contains(player.last_tile_pos))
+        {
+            continue;
+        }
+        death_events.send(PlayerDeathEvent {
+            player_entity: entity,
+            reason: PlayerDeathReason::HazardWall,
+            killer: None,
+            tile: player.last_tile_pos,
+            trail_length: trail_length(tiles.iter(), entity),
+        });
+    }
+
+    for (tile, tile_color) in tiles.iter_mut() {
+        if tile.trail_owner.is_none()
+            || !swept.iter().any(|bounds| bounds.contains((tile.x, tile.y)))
+        {
+            continue;
+        }
+
+        // Walls sweep trails away, the land underneath stays put
+        tile.trail_owner = None;
+        let owner_color = tile
+            .owner
+            .and_then(|owner| player_query.get(owner).ok())
+            .map(|(_, owner, _)| owner.color);
+        tile_color.0 = land_color(tile, owner_color);
+    }
+}
+
+// Bar drawn over the tiles a wall covers
+#[derive(Component)]
+pub struct HazardWallSprite {
+    pub starts_at: f32,
+}
+
+// Shows announced walls as a faint flashing bar on the side they'll come
+// from, and moving walls as a solid one
+pub fn hazard_wall_render_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    grid_settings: Res<GridSettings>,
+    schedule: Option<Res<HazardSchedule>>,
+    mut sprite_query: Query<(Entity, &HazardWallSprite, &mut Sprite, &mut Transform)>,
+) {
+    let walls = schedule
+        .as_ref()
+        .map_or(&[][..], |schedule| &schedule.walls);
+    for (entity, wall_sprite, _, _) in sprite_query.iter() {
+        if !walls
+            .iter()
+            .any(|scheduled| scheduled.wall.starts_at == wall_sprite.starts_at)
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let grid = GridMath::new(&grid_settings);
+    let elapsed = game_state.timer.elapsed_secs();
+    let pulse = 0.5 + 0.5 * (time.elapsed_secs() * 10.0).sin();
+
+    for scheduled in walls {
+        let wall = scheduled.wall;
+        let line = wall.line_at(elapsed, grid_settings.grid_width, grid_settings.grid_height);
+        let bounds = wall.bounds(
+            line.unwrap_or(0),
+            grid_settings.grid_width,
+            grid_settings.grid_height,
+        );
+        let color = match line {
+            Some(_) => Color::srgba(0.9, 0.1, 0.1, 0.9),
+            None => Color::srgba(1.0, 0.45, 0.1, 0.2 + 0.4 * pulse),
+        };
+
+        let size = Vec2::new(bounds.width() as f32, bounds.height() as f32) * grid.tile_size;
+        let center = grid.corner_of(bounds.min.0, bounds.min.1) + size / 2.0;
+        // Over everything, nothing should hide a wall
+        let translation = center.extend(0.2);
+
+        match sprite_query
+            .iter_mut()
+            .find(|(_, wall_sprite, _, _)| wall_sprite.starts_at == wall.starts_at)
+        {
+            Some((_, _, mut sprite, mut transform)) => {
+                sprite.color = color;
+                sprite.custom_size = Some(size);
+                transform.translation = translation;
+            }
+            None => {
+                commands.spawn((
+                    Sprite {
+                        color,
+                        custom_size: Some(size),
+                        ..default()
+                    },
+                    Transform::from_translation(translation),
+                    HazardWallSprite {
+                        starts_at: wall.starts_at,
+                    },
+                ));
+            }
+        }
+    }
+}
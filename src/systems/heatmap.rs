@@ -0,0 +1,128 @@
+use crate::components::GridSettings;
+use crate::events::{ClaimComputedEvent, MatchEndedEvent, PlayerDeathEvent};
+use crate::grid::GridMath;
+use crate::paths::Paths;
+use crate::stats::{StatsStore, TileCounts};
+use bevy::prelude::*;
+
+// Which heatmap is drawn over the grid on the join screen, cycled with H
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeatmapView {
+    #[default]
+    Off,
+    Deaths,
+    Claims,
+}
+
+impl HeatmapView {
+    fn next(self) -> Self {
+        match self {
+            HeatmapView::Off => HeatmapView::Deaths,
+            HeatmapView::Deaths => HeatmapView::Claims,
+            HeatmapView::Claims => HeatmapView::Off,
+        }
+    }
+
+    fn counts(self, store: &StatsStore) -> Option<&TileCounts> {
+        match self {
+            HeatmapView::Off => None,
+            HeatmapView::Deaths => Some(&store.heatmap.deaths),
+            HeatmapView::Claims => Some(&store.heatmap.claims),
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            HeatmapView::Claims => Color::srgb(0.1, 0.8, 0.3),
+            _ => Color::srgb(0.95, 0.15, 0.1),
+        }
+    }
+}
+
+// One tinted square of the heatmap overlay
+#[derive(Component)]
+pub struct HeatmapCell;
+
+// Adds every death and claimed tile of the match to the heatmap, and saves it
+// when the match is over
+pub fn record_heatmap_system(
+    paths: Res<Paths>,
+    mut store: ResMut<StatsStore>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    mut claim_events: EventReader<ClaimComputedEvent>,
+    mut match_end_events: EventReader<MatchEndedEvent>,
+) {
+    for event in death_events.read() {
+        store.heatmap.deaths.add(event.tile);
+    }
+
+    for event in claim_events.read() {
+        for &tile in event
+            .trail_tiles
+            .iter()
+            .chain(event.pockets.iter().flatten())
+        {
+            store.heatmap.claims.add(tile);
+        }
+    }
+
+    if match_end_events.read().count() > 0 {
+        store.save(&paths);
+    }
+}
+
+// Catches matches that were left before the clock ran out
+pub fn save_stats(paths: Res<Paths>, store: Res<StatsStore>) {
+    store.save(&paths);
+}
+
+pub fn heatmap_hotkey_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut view: ResMut<HeatmapView>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyH) {
+        *view = view.next();
+    }
+}
+
+pub fn hide_heatmap(mut view: ResMut<HeatmapView>) {
+    *view = HeatmapView::Off;
+}
+
+// Rebuilds the overlay whenever the view or the data changes
+pub fn heatmap_overlay_system(
+    mut commands: Commands,
+    view: Res<HeatmapView>,
+    store: Res<StatsStore>,
+    grid_settings: Res<GridSettings>,
+    cell_query: Query<Entity, With<HeatmapCell>>,
+) {
+    if !view.is_changed() && !store.is_changed() {
+        return;
+    }
+
+    for entity in cell_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let Some(counts) = view.counts(&store) else {
+        return;
+    };
+
+    let grid = GridMath::new(&grid_settings);
+
+    for &(x, y) in counts.counts.keys() {
+        // Keep even rare spots visible
+        let alpha = 0.15 + 0.75 * counts.intensity((x, y));
+
+        commands.spawn((
+            Sprite {
+                color: view.color().with_alpha(alpha),
+                custom_size: Some(Vec2::splat(grid.tile_size)),
+                ..default()
+            },
+            Transform::from_translation(grid.center_of(x, y).extend(0.4)),
+            HeatmapCell,
+        ));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::components::{GridSettings, LocalPlayer, Player, Tiles};
+use crate::territory::TileMap;
+use bevy::prelude::*;
+
+// Arrow shown next to a player while they are away from home
+#[derive(Component)]
+pub struct HomeArrow {
+    pub player: Entity,
+    // Tile the search was last run from, and what it found
+    pub searched_from: Option<(i32, i32)>,
+    pub target: Option<(i32, i32)>,
+}
+
+// Gives every newly spawned local player an arrow pointing home
+pub fn spawn_home_arrows_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    grid_settings: Res<GridSettings>,
+    player_query: Query<Entity, Added<LocalPlayer>>,
+) {
+    let tile_size = grid_settings.tile_size;
+
+    for player_entity in player_query.iter() {
+        // Triangle pointing along +X, rotated towards home every frame
+        let arrow = Triangle2d::new(
+            Vec2::new(tile_size * 0.35, 0.0),
+            Vec2::new(-tile_size * 0.2, tile_size * 0.25),
+            Vec2::new(-tile_size * 0.2, -tile_size * 0.25),
+        );
+
+        commands.spawn((
+            Mesh2d(meshes.add(arrow)),
+            MeshMaterial2d(materials.add(Color::srgba(1.0, 1.0, 1.0, 0.9))),
+            Transform::from_translation(Vec3::new(0.0, 0.0, 0.5)),
+            Visibility::Hidden,
+            HomeArrow {
+                player: player_entity,
+                searched_from: None,
+                target: None,
+            },
+        ));
+    }
+}
+
+// Points the arrow at the nearest tile of the player's own territory while
+// they are drawing a trail, hides it otherwise
+pub fn home_arrow_system(
+    grid_settings: Res<GridSettings>,
+    player_query: Query<(&Transform, &Player), With<LocalPlayer>>,
+    tiles: Res<Tiles>,
+    mut arrow_query: Query<(&mut HomeArrow, &mut Transform, &mut Visibility), Without<Player>>,
+) {
+    for (mut arrow, mut arrow_transform, mut visibility) in arrow_query.iter_mut() {
+        let Ok((player_transform, player)) = player_query.get(arrow.player) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        if !player.is_drawing_trail {
+            arrow.searched_from = None;
+            arrow.target = None;
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        // Only search again once the player has reached a new tile
+        if arrow.searched_from != Some(player.last_tile_pos) {
+            let tile_map = TileMap::from_tiles(
+                grid_settings.grid_width,
+                grid_settings.grid_height,
+                tiles.iter(),
+            );
+            arrow.searched_from = Some(player.last_tile_pos);
+            arrow.target = tile_map.nearest_territory(player.last_tile_pos, arrow.player);
+        }
+
+        let Some((target_x, target_y)) = arrow.target else {
+            // No territory left to go home to
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        let direction = Vec2::new(
+            (target_x - player.last_tile_pos.0) as f32,
+            (target_y - player.last_tile_pos.1) as f32,
+        )
+        .normalize_or_zero();
+
+        if direction == Vec2::ZERO {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        let offset = direction * grid_settings.tile_size * 0.9;
+        arrow_transform.translation.x = player_transform.translation.x + offset.x;
+        arrow_transform.translation.y = player_transform.translation.y + offset.y;
+        arrow_transform.rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
+        *visibility = Visibility::Visible;
+    }
+}
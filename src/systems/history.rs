@@ -0,0 +1,28 @@
+use crate::components::{Player, PositionHistory};
+use bevy::prelude::*;
+
+// How far back the rolling position history goes
+pub const HISTORY_SECONDS: f32 = 3.0;
+
+// Records every player's position each frame, dropping samples older than
+// HISTORY_SECONDS
+pub fn record_position_history_system(
+    time: Res<Time>,
+    mut query: Query<(&Transform, &mut PositionHistory), With<Player>>,
+) {
+    let now = time.elapsed_secs();
+
+    for (transform, mut history) in query.iter_mut() {
+        history
+            .samples
+            .push_back((now, transform.translation.truncate()));
+
+        while history
+            .samples
+            .front()
+            .is_some_and(|&(t, _)| now - t > HISTORY_SECONDS)
+        {
+            history.samples.pop_front();
+        }
+    }
+}
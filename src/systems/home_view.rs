@@ -0,0 +1,133 @@
+// Picture-in-picture of home. While the local player is out on a long trail a
+// second camera renders their territory into a texture shown in the corner,
+// so an enemy moving in on it doesn't go unnoticed.
+use crate::components::{GridSettings, LocalPlayer, Player, Tiles};
+use crate::grid::GridMath;
+use crate::territory::trail_length;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+
+// Size of the view in pixels
+const VIEW_WIDTH: u32 = 200;
+const VIEW_HEIGHT: u32 = 150;
+// Tiles across the view
+const VIEW_TILES: f32 = 12.0;
+// Trail tiles out before home is shown
+const MIN_TRAIL_LENGTH: u32 = 8;
+
+// Renders the home region into the corner image
+#[derive(Component)]
+pub struct HomeViewCamera;
+
+#[derive(Component)]
+pub struct HomeViewPanel;
+
+pub fn setup_home_view(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let size = Extent3d {
+        width: VIEW_WIDTH,
+        height: VIEW_HEIGHT,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Bgra8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    let handle = images.add(image);
+
+    // Before the main camera, and idle until there's something to show
+    commands.spawn((
+        Camera2d,
+        Camera {
+            target: RenderTarget::Image(handle.clone()),
+            order: -1,
+            is_active: false,
+            ..default()
+        },
+        HomeViewCamera,
+    ));
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                border: UiRect::all(Val::Px(1.0)),
+                ..default()
+            },
+            BorderColor(Color::srgba(1.0, 1.0, 1.0, 0.6)),
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Visibility::Hidden,
+            HomeViewPanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((Text::new("Home"), TextFont::from_font_size(12.0)));
+            panel.spawn((
+                ImageNode::new(handle),
+                Node {
+                    width: Val::Px(VIEW_WIDTH as f32),
+                    height: Val::Px(VIEW_HEIGHT as f32),
+                    ..default()
+                },
+            ));
+        });
+}
+
+// Points the camera at the middle of the lone local player's territory while
+// they're far out on a trail, and hides the view once they're back
+pub fn home_view_system(
+    grid_settings: Res<GridSettings>,
+    player_query: Query<(Entity, &Player), With<LocalPlayer>>,
+    tiles: Res<Tiles>,
+    mut camera_query: Query<
+        (&mut Camera, &mut Transform, &mut OrthographicProjection),
+        With<HomeViewCamera>,
+    >,
+    mut panel_query: Query<&mut Visibility, With<HomeViewPanel>>,
+) {
+    let home = player_query
+        .get_single()
+        .ok()
+        .filter(|(entity, player)| {
+            player.is_drawing_trail && trail_length(tiles.iter(), *entity) >= MIN_TRAIL_LENGTH
+        })
+        .and_then(|(entity, _)| {
+            let (sum, count) = tiles
+                .iter()
+                .filter(|tile| tile.owner == Some(entity))
+                .fold((Vec2::ZERO, 0), |(sum, count), tile| {
+                    (sum + Vec2::new(tile.x as f32, tile.y as f32), count + 1)
+                });
+            (count > 0).then(|| sum / count as f32)
+        });
+
+    for mut visibility in panel_query.iter_mut() {
+        visibility.set_if_neq(if home.is_some() {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        });
+    }
+
+    for (mut camera, mut transform, mut projection) in camera_query.iter_mut() {
+        camera.is_active = home.is_some();
+        let Some(home) = home else {
+            continue;
+        };
+
+        // The middle of the land can fall between tiles
+        let grid = GridMath::new(&grid_settings);
+        let center = grid.corner_of(0, 0) + (home + 0.5) * grid.tile_size;
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+        projection.scale = VIEW_TILES * grid.tile_size / VIEW_WIDTH as f32;
+    }
+}
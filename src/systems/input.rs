@@ -1,55 +1,304 @@
-use crate::components::Player;
+use crate::components::{Player, Respawning};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-pub fn player_input_system(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<&mut Player>,
-) {
-    if let Ok(mut player) = query.get_single_mut() {
-        // Start with no direction
-        let mut new_direction = Vec2::ZERO;
+// Where a local player's directions come from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputDevice {
+    KeyboardWasd,
+    KeyboardArrows,
+    Gamepad(Entity),
+}
+
+impl InputDevice {
+    pub fn label(self) -> &'static str {
+        match self {
+            InputDevice::KeyboardWasd => "Keyboard (WASD)",
+            InputDevice::KeyboardArrows => "Keyboard (Arrows)",
+            InputDevice::Gamepad(_) => "Gamepad",
+        }
+    }
+}
+
+// Keys a keyboard player steers with. Players without one use their
+// device's default layout.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBindings {
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+}
+
+impl KeyBindings {
+    pub const WASD: KeyBindings = KeyBindings {
+        up: KeyCode::KeyW,
+        down: KeyCode::KeyS,
+        left: KeyCode::KeyA,
+        right: KeyCode::KeyD,
+    };
+
+    pub const ARROWS: KeyBindings = KeyBindings {
+        up: KeyCode::ArrowUp,
+        down: KeyCode::ArrowDown,
+        left: KeyCode::ArrowLeft,
+        right: KeyCode::ArrowRight,
+    };
+
+    pub fn key(&self, direction: BindDirection) -> KeyCode {
+        match direction {
+            BindDirection::Up => self.up,
+            BindDirection::Down => self.down,
+            BindDirection::Left => self.left,
+            BindDirection::Right => self.right,
+        }
+    }
+
+    pub fn set_key(&mut self, direction: BindDirection, key: KeyCode) {
+        match direction {
+            BindDirection::Up => self.up = key,
+            BindDirection::Down => self.down = key,
+            BindDirection::Left => self.left = key,
+            BindDirection::Right => self.right = key,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
 
-        // Process only cardinal directions - no diagonals allowed
-        // Priority order: right > left > down > up (later ones override earlier ones)
-        // You can change this order if you prefer a different priority
+impl BindDirection {
+    pub const ALL: [BindDirection; 4] = [
+        BindDirection::Up,
+        BindDirection::Down,
+        BindDirection::Left,
+        BindDirection::Right,
+    ];
 
-        if keyboard_input.pressed(KeyCode::KeyW) || keyboard_input.pressed(KeyCode::ArrowUp) {
-            new_direction = Vec2::new(0.0, 1.0);
+    pub fn label(self) -> &'static str {
+        match self {
+            BindDirection::Up => "Up",
+            BindDirection::Down => "Down",
+            BindDirection::Left => "Left",
+            BindDirection::Right => "Right",
         }
+    }
+}
+
+// Keys of the two keyboard layouts, rebindable from the settings. A
+// profile's own bindings still win over these.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    pub wasd: KeyBindings,
+    pub arrows: KeyBindings,
+}
 
-        if keyboard_input.pressed(KeyCode::KeyS) || keyboard_input.pressed(KeyCode::ArrowDown) {
-            new_direction = Vec2::new(0.0, -1.0);
+impl Default for ControlSettings {
+    fn default() -> Self {
+        Self {
+            wasd: KeyBindings::WASD,
+            arrows: KeyBindings::ARROWS,
         }
+    }
+}
 
-        if keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::ArrowLeft) {
-            new_direction = Vec2::new(-1.0, 0.0);
+impl ControlSettings {
+    pub fn bindings(&self, device: InputDevice) -> Option<KeyBindings> {
+        match device {
+            InputDevice::KeyboardWasd => Some(self.wasd),
+            InputDevice::KeyboardArrows => Some(self.arrows),
+            InputDevice::Gamepad(_) => None,
         }
+    }
 
-        if keyboard_input.pressed(KeyCode::KeyD) || keyboard_input.pressed(KeyCode::ArrowRight) {
-            new_direction = Vec2::new(1.0, 0.0);
+    pub fn bindings_mut(&mut self, device: InputDevice) -> Option<&mut KeyBindings> {
+        match device {
+            InputDevice::KeyboardWasd => Some(&mut self.wasd),
+            InputDevice::KeyboardArrows => Some(&mut self.arrows),
+            InputDevice::Gamepad(_) => None,
         }
+    }
+}
 
-        // Only update direction if there's input
-        if new_direction != Vec2::ZERO {
-            // Check if the new direction is opposite to the current direction
-            let current_dir = player.direction;
-            let is_opposite = (current_dir.x != 0.0 && new_direction.x == -current_dir.x)
-                || (current_dir.y != 0.0 && new_direction.y == -current_dir.y);
+// Readable name of a key, "W" rather than "KeyW"
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    ["Key", "Digit"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .map_or(name.clone(), str::to_string)
+}
 
-            // Don't allow direct reversals
-            if is_opposite {
-                // Ignore the reversal attempt
-                return;
-            }
+// Timed list of directions, e.g. a recorded demo or a test script. Each
+// step holds its direction from its start time until the next step.
+#[derive(Clone, Debug, Default)]
+pub struct InputScript {
+    pub steps: Vec<(f32, Vec2)>,
+    pub elapsed: f32,
+}
 
-            // If the player is currently moving to the next tile, buffer the direction change
-            if player.is_moving_to_next_tile && current_dir != Vec2::ZERO {
-                player.buffered_direction = Some(new_direction);
-            } else {
-                // Otherwise, apply the direction immediately
-                player.direction = new_direction;
-                player.buffered_direction = None;
-            }
+impl InputScript {
+    pub fn new(steps: Vec<(f32, Vec2)>) -> Self {
+        Self {
+            steps,
+            elapsed: 0.0,
         }
     }
+
+    pub fn direction(&self) -> Vec2 {
+        self.steps
+            .iter()
+            .take_while(|(start, _)| *start <= self.elapsed)
+            .last()
+            .map_or(Vec2::ZERO, |&(_, direction)| direction)
+    }
+}
+
+// Where a player's direction intents come from. Movement only ever looks at
+// the `DirectionIntent`, so any source can drive any player.
+#[derive(Component, Clone, Debug)]
+pub enum InputSource {
+    // A local keyboard or gamepad
+    Device(InputDevice),
+    // A recorded or hand-written script
+    Script(InputScript),
+    // Written straight into `DirectionIntent` by another system (AI, network)
+    External,
+}
+
+// Direction a player wants to go this frame, zero for no input
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct DirectionIntent(pub Vec2);
+
+// Stick deflection needed before it counts as a direction
+const STICK_DEADZONE: f32 = 0.5;
+
+fn keyboard_direction(keyboard_input: &ButtonInput<KeyCode>, bindings: KeyBindings) -> Vec2 {
+    let KeyBindings {
+        up,
+        down,
+        left,
+        right,
+    } = bindings;
+
+    // Process only cardinal directions - no diagonals allowed
+    // Priority order: right > left > down > up (later ones override earlier ones)
+    let mut new_direction = Vec2::ZERO;
+
+    if keyboard_input.pressed(up) {
+        new_direction = Vec2::new(0.0, 1.0);
+    }
+
+    if keyboard_input.pressed(down) {
+        new_direction = Vec2::new(0.0, -1.0);
+    }
+
+    if keyboard_input.pressed(left) {
+        new_direction = Vec2::new(-1.0, 0.0);
+    }
+
+    if keyboard_input.pressed(right) {
+        new_direction = Vec2::new(1.0, 0.0);
+    }
+
+    new_direction
+}
+
+fn gamepad_direction(gamepad: &Gamepad) -> Vec2 {
+    // D-pad wins over the stick when both are used
+    let dpad = gamepad.dpad();
+    let raw = if dpad != Vec2::ZERO {
+        dpad
+    } else {
+        gamepad.left_stick()
+    };
+
+    // Snap to the dominant axis so diagonals never reach the player
+    if raw.x.abs() >= raw.y.abs() && raw.x.abs() > STICK_DEADZONE {
+        Vec2::new(raw.x.signum(), 0.0)
+    } else if raw.y.abs() > STICK_DEADZONE {
+        Vec2::new(0.0, raw.y.signum())
+    } else {
+        Vec2::ZERO
+    }
+}
+
+pub fn apply_direction(player: &mut Player, new_direction: Vec2) {
+    // Only update direction if there's input
+    if new_direction == Vec2::ZERO {
+        return;
+    }
+
+    // Check if the new direction is opposite to the current direction
+    let current_dir = player.direction;
+    let is_opposite = (current_dir.x != 0.0 && new_direction.x == -current_dir.x)
+        || (current_dir.y != 0.0 && new_direction.y == -current_dir.y);
+
+    // Don't allow direct reversals
+    if is_opposite {
+        return;
+    }
+
+    // A moving player turns once they reach the next tile center, until then
+    // the change is buffered
+    if current_dir != Vec2::ZERO {
+        player.buffered_direction = Some(new_direction);
+    } else {
+        // Otherwise, apply the direction immediately
+        player.direction = new_direction;
+        player.buffered_direction = None;
+    }
+}
+
+// Reads local devices into the intents of the players bound to them
+pub fn device_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    controls: Res<ControlSettings>,
+    gamepads: Query<&Gamepad>,
+    mut query: Query<(&InputSource, Option<&KeyBindings>, &mut DirectionIntent)>,
+) {
+    for (source, custom_bindings, mut intent) in query.iter_mut() {
+        let InputSource::Device(device) = source else {
+            continue;
+        };
+
+        intent.0 = match *device {
+            InputDevice::Gamepad(gamepad_entity) => gamepads
+                .get(gamepad_entity)
+                .map(gamepad_direction)
+                .unwrap_or(Vec2::ZERO),
+            keyboard => match custom_bindings.copied().or(controls.bindings(keyboard)) {
+                Some(bindings) => keyboard_direction(&keyboard_input, bindings),
+                None => Vec2::ZERO,
+            },
+        };
+    }
+}
+
+// Plays input scripts forward
+pub fn scripted_input_system(
+    time: Res<Time>,
+    mut query: Query<(&mut InputSource, &mut DirectionIntent)>,
+) {
+    for (mut source, mut intent) in query.iter_mut() {
+        let InputSource::Script(script) = source.as_mut() else {
+            continue;
+        };
+
+        script.elapsed += time.delta_secs();
+        intent.0 = script.direction();
+    }
+}
+
+// Turns intents into player direction changes, whatever produced them
+pub fn player_input_system(mut query: Query<(&DirectionIntent, &mut Player), Without<Respawning>>) {
+    for (intent, mut player) in query.iter_mut() {
+        apply_direction(&mut player, intent.0);
+    }
 }
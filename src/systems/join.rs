@@ -0,0 +1,514 @@
+use crate::components::GridSettings;
+use crate::net::invite::copy_to_clipboard;
+use crate::profiles::{ActiveProfiles, ProfileStore};
+use crate::progression::{unlocked_maps, MAPS};
+use crate::resources::{ActivePreset, GameRules, MatchRng, MatchSeed, RulesPreset};
+use crate::share::{ShareCode, SHARE_CODE_LENGTH};
+use crate::spawn_grid;
+use crate::states::AppState;
+use crate::systems::daily::{today, DailyChallenge};
+use crate::systems::difficulty::BotDifficultyText;
+use crate::systems::input::InputDevice;
+use crate::systems::quick_play::QuickPlayText;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+
+pub const MAX_LOCAL_PLAYERS: usize = 4;
+
+// Devices that have taken a player slot, indexed by slot
+#[derive(Resource, Default)]
+pub struct JoinedPlayers {
+    pub devices: Vec<InputDevice>,
+}
+
+impl JoinedPlayers {
+    pub fn slot_of(&self, device: InputDevice) -> Option<usize> {
+        self.devices.iter().position(|&joined| joined == device)
+    }
+}
+
+// Map picked on the join screen as an index into `progression::MAPS`, None
+// keeps whatever map is loaded
+#[derive(Resource, Default)]
+pub struct SelectedMap(pub Option<usize>);
+
+// A share code being typed in on the join screen
+#[derive(Resource, Default)]
+pub struct ShareCodeEntry {
+    // What's typed so far, None while nobody's typing
+    pub text: Option<String>,
+    // Why the last code didn't work
+    pub error: Option<String>,
+}
+
+// The join screen's hotkeys are letters too, so they're off while typing
+pub fn entering_share_code(entry: Res<ShareCodeEntry>) -> bool {
+    entry.text.is_some()
+}
+
+// Code for the match the join screen is set up for, or that was just played.
+// Codes name a map on the list, so there's none for any other map.
+pub fn current_share_code(
+    selected: &SelectedMap,
+    grid_settings: &GridSettings,
+    seed: MatchSeed,
+    rules: &GameRules,
+) -> Option<ShareCode> {
+    let map = selected.0.or_else(|| {
+        MAPS.iter().position(|map| {
+            same_layout(&map.grid_settings(), grid_settings) && grid_settings.value_zones.is_empty()
+        })
+    })?;
+    Some(ShareCode::new(map, seed.0, grid_settings.topology, rules))
+}
+
+// Whether two maps are laid out the same, whatever their edges do
+fn same_layout(a: &GridSettings, b: &GridSettings) -> bool {
+    a.grid_width == b.grid_width
+        && a.grid_height == b.grid_height
+        && a.tile_size == b.tile_size
+        && a.shape == b.shape
+        && a.upper_floor == b.upper_floor
+}
+
+#[derive(Component)]
+pub struct JoinScreen;
+
+#[derive(Component)]
+pub struct MapPickerText;
+
+#[derive(Component)]
+pub struct JoinSlotText {
+    pub slot: usize,
+}
+
+pub fn setup_join_screen(mut commands: Commands) {
+    let daily = DailyChallenge::generate(today());
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(10.0),
+                ..default()
+            },
+            // See-through so the attract mode demo shows behind it
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.45)),
+            JoinScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Text::new("Press a button to join"),
+                TextFont::from_font_size(32.0),
+            ));
+
+            for slot in 0..MAX_LOCAL_PLAYERS {
+                screen.spawn((
+                    Text::new(format!("P{}: ---", slot + 1)),
+                    TextFont::from_font_size(20.0),
+                    JoinSlotText { slot },
+                ));
+            }
+
+            screen.spawn((
+                Text::new(
+                    "Enter / Start to play, Q for quick play, P for the practice sandbox, L for puzzle levels, T for a tournament, B to browse servers, 1-4 to switch profile, F1-F4 for color, F5-F8 for trail style, N to pick the map, R for the rules, E for the bot difficulty, G for the game speed, O for the edges, H for heatmaps, V for the chase camera",
+                ),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+
+            screen.spawn((
+                Text::new(""),
+                TextFont::from_font_size(16.0),
+                MapPickerText,
+            ));
+
+            screen.spawn((
+                Text::new(""),
+                TextFont::from_font_size(16.0),
+                BotDifficultyText,
+            ));
+
+            screen.spawn((
+                Text::new(""),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.5, 0.85, 1.0)),
+                QuickPlayText,
+            ));
+
+            screen.spawn((
+                Text::new(format!(
+                    "C for the daily challenge: {}",
+                    daily.description()
+                )),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.95, 0.8, 0.3)),
+            ));
+        });
+}
+
+pub fn cleanup_join_screen(mut commands: Commands, screen_query: Query<Entity, With<JoinScreen>>) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Gives a slot to any device that presses something, and starts the match
+// once at least one player has joined
+pub fn join_detection_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    mut joined: ResMut<JoinedPlayers>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let mut pressed = Vec::new();
+
+    if keyboard_input.any_just_pressed([
+        KeyCode::KeyW,
+        KeyCode::KeyA,
+        KeyCode::KeyS,
+        KeyCode::KeyD,
+        KeyCode::Space,
+    ]) {
+        pressed.push(InputDevice::KeyboardWasd);
+    }
+
+    if keyboard_input.any_just_pressed([
+        KeyCode::ArrowUp,
+        KeyCode::ArrowDown,
+        KeyCode::ArrowLeft,
+        KeyCode::ArrowRight,
+    ]) {
+        pressed.push(InputDevice::KeyboardArrows);
+    }
+
+    let mut start_pressed = keyboard_input.just_pressed(KeyCode::Enter);
+
+    for (gamepad_entity, gamepad) in gamepads.iter() {
+        if gamepad.just_pressed(GamepadButton::Start) {
+            start_pressed = true;
+        } else if gamepad.get_just_pressed().next().is_some() {
+            pressed.push(InputDevice::Gamepad(gamepad_entity));
+        }
+    }
+
+    for device in pressed {
+        if joined.devices.contains(&device) || joined.devices.len() >= MAX_LOCAL_PLAYERS {
+            continue;
+        }
+
+        println!(
+            "Player {} joined with {}",
+            joined.devices.len() + 1,
+            device.label()
+        );
+        joined.devices.push(device);
+    }
+
+    if start_pressed && !joined.devices.is_empty() {
+        next_state.set(AppState::Playing);
+    }
+
+    // The sandbox can be entered straight away, on WASD if nobody joined yet
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        if joined.devices.is_empty() {
+            joined.devices.push(InputDevice::KeyboardWasd);
+        }
+        next_state.set(AppState::Sandbox);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        next_state.set(AppState::LevelSelect);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        next_state.set(AppState::Tournament);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyB) {
+        next_state.set(AppState::ServerBrowser);
+    }
+
+    // The daily challenge is a solo match for P1
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        if joined.devices.is_empty() {
+            joined.devices.push(InputDevice::KeyboardWasd);
+        }
+        joined.devices.truncate(1);
+        commands.insert_resource(DailyChallenge::generate(today()));
+        next_state.set(AppState::Playing);
+    }
+}
+
+// N steps through the maps unlocked by the highest level profile that has
+// joined
+pub fn cycle_map_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    store: Res<ProfileStore>,
+    active: Res<ActiveProfiles>,
+    mut selected: ResMut<SelectedMap>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyN) {
+        return;
+    }
+
+    let level = (0..active.slots.len())
+        .filter_map(|slot| active.profile(&store, slot))
+        .map(|profile| profile.level())
+        .max()
+        .unwrap_or(1);
+    let unlocked: Vec<usize> = unlocked_maps(level)
+        .map(|map| {
+            MAPS.iter()
+                .position(|other| other.name == map.name)
+                .unwrap_or(0)
+        })
+        .collect();
+    let current = selected
+        .0
+        .and_then(|index| unlocked.iter().position(|&unlocked| unlocked == index));
+    let next = match current {
+        Some(position) => unlocked[(position + 1) % unlocked.len()],
+        None => unlocked[0],
+    };
+    println!("Map: {}", MAPS[next].name);
+    selected.0 = Some(next);
+}
+
+// G steps through the game speeds. The rules no longer match any preset
+// after, so the match counts as custom.
+pub fn cycle_game_speed_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut rules: ResMut<GameRules>,
+    mut preset: ResMut<ActivePreset>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        rules.game_speed = rules.game_speed.next();
+        preset.0 = None;
+    }
+}
+
+// R steps through the rules presets, starting over from the preset's rules
+pub fn cycle_rules_preset_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut preset: ResMut<ActivePreset>,
+    mut rules: ResMut<GameRules>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        let next = preset.0.map_or(RulesPreset::Classic, RulesPreset::next);
+        preset.0 = Some(next);
+        *rules = next.rules();
+        println!("Rules: {}", next.name());
+    }
+}
+
+// O steps through what the map's edges do
+pub fn cycle_topology_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut grid_settings: ResMut<GridSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        grid_settings.topology = grid_settings.topology.next();
+        println!("Edges: {}", grid_settings.topology.name());
+    }
+}
+
+// K starts typing a share code, Enter plays it and Esc gives up. Y copies
+// the code for the current setup.
+#[allow(clippy::too_many_arguments)]
+pub fn share_code_entry_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut key_events: EventReader<KeyboardInput>,
+    mut entry: ResMut<ShareCodeEntry>,
+    mut selected: ResMut<SelectedMap>,
+    mut seed: ResMut<MatchSeed>,
+    mut rules: ResMut<GameRules>,
+    mut preset: ResMut<ActivePreset>,
+    mut grid_settings: ResMut<GridSettings>,
+) {
+    let Some(text) = entry.text.as_mut() else {
+        key_events.clear();
+        if keyboard_input.just_pressed(KeyCode::KeyK) {
+            entry.text = Some(String::new());
+            entry.error = None;
+        }
+        if keyboard_input.just_pressed(KeyCode::KeyY) {
+            match current_share_code(&selected, &grid_settings, *seed, &rules) {
+                Some(code) => {
+                    let code = code.to_string();
+                    // Printed either way, so it can still be copied from the
+                    // console
+                    println!("Share code: {}", code);
+                    if let Err(err) = copy_to_clipboard(&code) {
+                        println!("Failed to copy the share code: {}", err);
+                    }
+                }
+                None => println!("This map can't be shared"),
+            }
+        }
+        return;
+    };
+
+    let mut submitted = false;
+    for event in key_events.read().filter(|event| event.state.is_pressed()) {
+        match &event.logical_key {
+            Key::Character(typed) => {
+                for c in typed
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+                {
+                    // Room for the dashes between groups
+                    if text.len() < SHARE_CODE_LENGTH * 2 {
+                        text.push(c.to_ascii_uppercase());
+                    }
+                }
+            }
+            Key::Backspace => {
+                text.pop();
+            }
+            Key::Enter => submitted = true,
+            Key::Escape => {
+                entry.text = None;
+                return;
+            }
+            _ => {}
+        }
+    }
+    if !submitted {
+        return;
+    }
+
+    match ShareCode::parse(text) {
+        Ok(code) => {
+            println!("Playing shared setup {} on {}", code, MAPS[code.map].name);
+            selected.0 = Some(code.map);
+            *seed = MatchSeed(code.seed);
+            grid_settings.topology = code.topology;
+            *rules = code.rules;
+            // Codes carry the rules themselves, not where they came from
+            *preset = ActivePreset(None);
+            *entry = ShareCodeEntry::default();
+        }
+        Err(err) => entry.error = Some(err.to_string()),
+    }
+}
+
+// Puts the map's random numbers back at the start of the match's seed
+pub fn seed_match_rng(mut commands: Commands, seed: Res<MatchSeed>) {
+    commands.insert_resource(MatchRng::new(*seed));
+}
+
+pub fn next_match_seed(mut seed: ResMut<MatchSeed>) {
+    *seed = MatchSeed::default();
+}
+
+// Lays out the picked map when leaving the join screen, if it isn't loaded
+// already. The daily challenge brings a map of its own.
+pub fn apply_selected_map(
+    mut commands: Commands,
+    selected: Res<SelectedMap>,
+    challenge: Option<Res<DailyChallenge>>,
+    mut grid_settings: ResMut<GridSettings>,
+) {
+    let map = match challenge {
+        Some(challenge) => challenge.map.clone(),
+        None => match selected.0.and_then(|index| MAPS.get(index)) {
+            Some(map) => map.grid_settings(),
+            None => return,
+        },
+    };
+    if same_layout(&map, &grid_settings) && map.value_zones == grid_settings.value_zones {
+        return;
+    }
+
+    // The edges picked on the join screen go with the new map
+    *grid_settings = GridSettings {
+        topology: grid_settings.topology,
+        ..map
+    };
+    spawn_grid(&mut commands, &grid_settings);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_join_screen_system(
+    joined: Res<JoinedPlayers>,
+    store: Res<ProfileStore>,
+    active: Res<ActiveProfiles>,
+    selected: Res<SelectedMap>,
+    grid_settings: Res<GridSettings>,
+    rules: Res<GameRules>,
+    preset: Res<ActivePreset>,
+    seed: Res<MatchSeed>,
+    entry: Res<ShareCodeEntry>,
+    mut slot_query: Query<(&JoinSlotText, &mut Text), Without<MapPickerText>>,
+    mut map_query: Query<&mut Text, With<MapPickerText>>,
+) {
+    if !joined.is_changed()
+        && !store.is_changed()
+        && !active.is_changed()
+        && !selected.is_changed()
+        && !rules.is_changed()
+        && !preset.is_changed()
+        && !seed.is_changed()
+        && !entry.is_changed()
+    {
+        return;
+    }
+
+    for mut text in map_query.iter_mut() {
+        let map = match selected.0.and_then(|index| MAPS.get(index)) {
+            Some(map) if !map.stairs.is_empty() => format!(
+                "Map: {} ({}x{}, two floors)",
+                map.name, map.grid_width, map.grid_height
+            ),
+            Some(map) => format!("Map: {} ({}x{})", map.name, map.grid_width, map.grid_height),
+            None => format!(
+                "Map: current ({}x{})",
+                grid_settings.grid_width, grid_settings.grid_height
+            ),
+        };
+        let code = match current_share_code(&selected, &grid_settings, *seed, &rules) {
+            Some(code) => format!("share code {} (Y to copy, K to enter one)", code),
+            None => "K to enter a share code".to_string(),
+        };
+        text.0 = match (&entry.text, &entry.error) {
+            (Some(typed), Some(error)) => format!("Share code: {}_ ({})", typed, error),
+            (Some(typed), None) => {
+                format!("Share code: {}_ (Enter to play it, Esc to cancel)", typed)
+            }
+            (None, _) => format!(
+                "{}, {} rules, {} speed, {} edges - {}",
+                map,
+                preset.name(),
+                rules.game_speed.name(),
+                grid_settings.topology.name(),
+                code
+            ),
+        };
+    }
+
+    for (slot_text, mut text) in slot_query.iter_mut() {
+        let slot = slot_text.slot;
+        text.0 = match (joined.devices.get(slot), active.profile(&store, slot)) {
+            (Some(device), Some(profile)) => format!(
+                "P{}: {} - {} (level {}, {} XP, {} trail, rating {:.0}, daily streak {})",
+                slot + 1,
+                device.label(),
+                profile.name,
+                profile.level(),
+                profile.xp,
+                profile.trail_style.name(),
+                profile.rating.value,
+                profile.daily.current_streak(today())
+            ),
+            (Some(device), None) => format!("P{}: {}", slot + 1, device.label()),
+            (None, _) => format!("P{}: ---", slot + 1),
+        };
+    }
+}
@@ -0,0 +1,270 @@
+use crate::components::{LocalPlayer, MainCamera, Player, PositionHistory, Respawning, Spectating};
+use crate::events::PlayerDeathEvent;
+use crate::systems::camera::{chase_target, CameraMode, ChasePlayerQuery, CHASE_ZOOM};
+use crate::systems::cinematic::CameraTween;
+use bevy::prelude::*;
+
+// Replay runs slower than real time so the death is easy to follow
+const REPLAY_SPEED: f32 = 0.75;
+// Zoom applied to the camera while the kill cam is focused on a death
+const KILL_CAM_ZOOM: f32 = 0.6;
+// How quickly the camera eases towards its target
+const CAMERA_EASE: f32 = 4.0;
+
+// Seconds a player spends dead before respawning, long enough for the replay
+pub fn respawn_delay(history_seconds: f32) -> f32 {
+    history_seconds / REPLAY_SPEED
+}
+
+// Ghost sprite replaying a dead player's last few seconds of movement
+#[derive(Component)]
+pub struct KillCamReplay {
+    pub samples: Vec<(f32, Vec2)>,
+    pub elapsed: f32,
+}
+
+// Recap line explaining the death, removed with the replay
+#[derive(Component)]
+pub struct KillCamRecap {
+    pub timer: Timer,
+}
+
+// Where the camera should be looking, None means the whole map
+#[derive(Resource, Default)]
+pub struct KillCamFocus {
+    pub target: Option<Vec2>,
+}
+
+impl KillCamReplay {
+    fn duration(&self) -> f32 {
+        match (self.samples.first(), self.samples.last()) {
+            (Some(first), Some(last)) => last.0 - first.0,
+            _ => 0.0,
+        }
+    }
+
+    // Interpolated position at `elapsed` seconds into the recording
+    fn position_at(&self, elapsed: f32) -> Option<Vec2> {
+        let start = self.samples.first()?.0;
+        let t = start + elapsed;
+
+        let next = self.samples.iter().position(|&(time, _)| time >= t);
+        match next {
+            Some(0) => Some(self.samples[0].1),
+            Some(i) => {
+                let (t0, p0) = self.samples[i - 1];
+                let (t1, p1) = self.samples[i];
+                let span = (t1 - t0).max(f32::EPSILON);
+                Some(p0.lerp(p1, (t - t0) / span))
+            }
+            None => self.samples.last().map(|&(_, p)| p),
+        }
+    }
+}
+
+// Starts a replay and recap for local players that just died. Must run before
+// the death handler teleports them back to their spawn point.
+pub fn start_kill_cam_system(
+    mut commands: Commands,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    mut focus: ResMut<KillCamFocus>,
+    player_query: Query<(&Player, &PositionHistory, &Transform), With<LocalPlayer>>,
+    local_count: Query<(), With<LocalPlayer>>,
+) {
+    for event in death_events.read() {
+        let Ok((player, history, transform)) = player_query.get(event.player_entity) else {
+            continue;
+        };
+
+        let samples: Vec<(f32, Vec2)> = history.samples.iter().copied().collect();
+        let death_position = transform.translation.truncate();
+
+        let replay = KillCamReplay {
+            samples,
+            elapsed: 0.0,
+        };
+        let duration = replay.duration() / REPLAY_SPEED;
+
+        commands.spawn((
+            Sprite {
+                color: player.color.with_alpha(0.6),
+                custom_size: Some(Vec2::splat(14.0)),
+                ..default()
+            },
+            Transform::from_translation(death_position.extend(0.6)),
+            replay,
+        ));
+
+        let mut recap = event.reason.description().to_string();
+        if event.killer.is_some() {
+            recap.push_str(" - taken out by an opponent");
+        }
+        if event.trail_length > 0 {
+            recap.push_str(&format!(" ({} trail tiles lost)", event.trail_length));
+        }
+
+        commands.spawn((
+            Text::new(recap),
+            TextFont::from_font_size(24.0),
+            TextColor(player.color),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(40.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+            KillCamRecap {
+                timer: Timer::from_seconds(duration.max(1.0), TimerMode::Once),
+            },
+        ));
+
+        // Only take over the shared camera when nobody else is playing on it
+        if local_count.iter().count() == 1 {
+            focus.target = Some(death_position);
+        }
+    }
+}
+
+pub fn kill_cam_playback_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut focus: ResMut<KillCamFocus>,
+    mut replay_query: Query<(Entity, &mut KillCamReplay, &mut Transform)>,
+    mut recap_query: Query<(Entity, &mut KillCamRecap)>,
+) {
+    for (entity, mut replay, mut transform) in replay_query.iter_mut() {
+        replay.elapsed += time.delta_secs() * REPLAY_SPEED;
+
+        if replay.elapsed >= replay.duration() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        if let Some(position) = replay.position_at(replay.elapsed) {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+
+    for (entity, mut recap) in recap_query.iter_mut() {
+        if recap.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    if replay_query.is_empty() && recap_query.is_empty() {
+        focus.target = None;
+    }
+}
+
+// Countdown shown to a local player sitting out a respawn delay
+#[derive(Component)]
+pub struct SpectateCountdown {
+    pub player: Entity,
+}
+
+// Shows local spectators how long until they're back, and points the camera
+// at whoever killed them while the shared camera is theirs alone
+pub fn spectate_killer_system(
+    mut commands: Commands,
+    mut focus: ResMut<KillCamFocus>,
+    mut following: Local<bool>,
+    spectator_query: Query<(Entity, &Spectating, &Respawning, &Player), With<LocalPlayer>>,
+    killer_query: Query<&Transform, (With<Player>, Without<Respawning>)>,
+    mut countdown_query: Query<(Entity, &SpectateCountdown, &mut Text)>,
+    local_count: Query<(), With<LocalPlayer>>,
+) {
+    for (entity, countdown, _) in countdown_query.iter() {
+        if !spectator_query.contains(countdown.player) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let mut killer_position = None;
+    for (entity, spectating, respawning, player) in spectator_query.iter() {
+        let killer = spectating
+            .killer
+            .and_then(|killer| killer_query.get(killer).ok());
+        if let Some(transform) = killer {
+            killer_position = Some(transform.translation.truncate());
+        }
+
+        let text = format!(
+            "Respawning in {}{}",
+            respawning.timer.remaining_secs().ceil() as u32,
+            if killer.is_some() {
+                " - watching your killer"
+            } else {
+                ""
+            }
+        );
+        match countdown_query
+            .iter_mut()
+            .find(|(_, countdown, _)| countdown.player == entity)
+        {
+            Some((_, _, mut countdown_text)) => countdown_text.0 = text,
+            None => {
+                commands.spawn((
+                    Text::new(text),
+                    TextFont::from_font_size(28.0),
+                    TextColor(player.color),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        bottom: Val::Px(60.0),
+                        width: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    TextLayout::new_with_justify(JustifyText::Center),
+                    SpectateCountdown { player: entity },
+                ));
+            }
+        }
+    }
+
+    // Only take over the shared camera when nobody else is playing on it
+    if local_count.iter().count() == 1 && killer_position.is_some() {
+        focus.target = killer_position;
+        *following = true;
+    } else if *following {
+        focus.target = None;
+        *following = false;
+    }
+}
+
+// Eases the camera towards the kill cam focus, the chase camera's player, or
+// back to the full map. Scripted camera moves take priority.
+pub fn kill_cam_camera_system(
+    time: Res<Time>,
+    focus: Res<KillCamFocus>,
+    mode: Res<CameraMode>,
+    tween: Res<CameraTween>,
+    player_query: ChasePlayerQuery,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    if tween.active() {
+        return;
+    }
+
+    let chase = chase_target(*mode, &player_query);
+    let (target_position, target_scale, target_rotation) = match (focus.target, chase) {
+        (Some(position), _) => (position, KILL_CAM_ZOOM, Some(Quat::IDENTITY)),
+        (None, Some((position, rotation))) => (position, CHASE_ZOOM, rotation),
+        (None, None) => (Vec2::ZERO, 1.0, Some(Quat::IDENTITY)),
+    };
+
+    let blend = (CAMERA_EASE * time.delta_secs()).min(1.0);
+
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        let current = transform.translation.truncate();
+        let next = current.lerp(target_position, blend);
+        transform.translation.x = next.x;
+        transform.translation.y = next.y;
+        projection.scale += (target_scale - projection.scale) * blend;
+        if let Some(rotation) = target_rotation {
+            transform.rotation = transform.rotation.slerp(rotation, blend);
+        }
+    }
+}
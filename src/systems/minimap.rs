@@ -0,0 +1,445 @@
+use crate::components::{GridSettings, LocalPlayer, Player, Tiles};
+use crate::resources::{GameState, ProximityWarnings};
+use crate::systems::bounty::BountyTarget;
+use crate::systems::camera::CameraMode;
+use crate::systems::floors::ActiveFloor;
+use crate::systems::hazards::HazardSchedule;
+use crate::systems::weather::Vision;
+use bevy::color::ColorToPacked;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+// On-screen size of the minimap in pixels
+const MINIMAP_WIDTH: f32 = 160.0;
+// The chase camera hides most of the map, so the minimap grows to make up
+const CHASE_MINIMAP_WIDTH: f32 = 240.0;
+const EMPTY_COLOR: [u8; 4] = [40, 40, 40, 220];
+// Off the map's shape, left see-through
+const VOID_COLOR: [u8; 4] = [0, 0, 0, 0];
+const STAIRS_COLOR: [u8; 4] = [230, 230, 230, 230];
+
+// Handle to the texture the minimap draws the grid into (one pixel per tile)
+#[derive(Resource)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+}
+
+// Root UI node of the minimap, markers and pings are its children
+#[derive(Component)]
+pub struct MinimapRoot;
+
+#[derive(Component)]
+pub struct MinimapPlayerMarker {
+    pub player: Entity,
+}
+
+// Pulsing red dot for an enemy close to the local player's trail
+#[derive(Component)]
+pub struct MinimapPing {
+    pub enemy: Entity,
+}
+
+// Bar over the ground a hazard wall covers, flashing until it starts moving
+#[derive(Component)]
+pub struct MinimapHazardMarker {
+    pub starts_at: f32,
+}
+
+// Gold ring around the leader with a bounty on them
+#[derive(Component)]
+pub struct MinimapCrownMarker;
+
+pub fn setup_minimap(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let size = Extent3d {
+        width: grid_settings.grid_width as u32,
+        height: grid_settings.grid_height as u32,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &EMPTY_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    // Keep tiles crisp when the texture is scaled up
+    image.sampler = ImageSampler::nearest();
+    let handle = images.add(image);
+
+    let aspect = grid_settings.grid_height as f32 / grid_settings.grid_width as f32;
+
+    commands.spawn((
+        ImageNode::new(handle.clone()),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            width: Val::Px(MINIMAP_WIDTH),
+            height: Val::Px(MINIMAP_WIDTH * aspect),
+            border: UiRect::all(Val::Px(1.0)),
+            ..default()
+        },
+        BorderColor(Color::srgba(1.0, 1.0, 1.0, 0.6)),
+        MinimapRoot,
+    ));
+
+    commands.insert_resource(Minimap { image: handle });
+}
+
+// Sizes the minimap for the camera mode and the shape of the map, or of
+// the floor in focus
+pub fn minimap_layout_system(
+    mode: Res<CameraMode>,
+    grid_settings: Res<GridSettings>,
+    active: Res<ActiveFloor>,
+    mut root_query: Query<&mut Node, With<MinimapRoot>>,
+) {
+    if !mode.is_changed() && !grid_settings.is_changed() && !active.is_changed() {
+        return;
+    }
+
+    let width = match *mode {
+        CameraMode::Overview => MINIMAP_WIDTH,
+        CameraMode::Chase => CHASE_MINIMAP_WIDTH,
+    };
+    let columns = active.columns(&grid_settings).len();
+    let aspect = grid_settings.grid_height as f32 / columns.max(1) as f32;
+    for mut node in root_query.iter_mut() {
+        node.width = Val::Px(width);
+        node.height = Val::Px(width * aspect);
+    }
+}
+
+// Repaints the minimap texture whenever tile ownership changes, showing
+// only the floor in focus on a map with two
+pub fn update_minimap_texture_system(
+    minimap: Option<Res<Minimap>>,
+    grid_settings: Res<GridSettings>,
+    active: Res<ActiveFloor>,
+    mut images: ResMut<Assets<Image>>,
+    tiles: Res<Tiles>,
+    player_query: Query<&Player>,
+) {
+    let Some(minimap) = minimap else {
+        return;
+    };
+
+    if !tiles.is_changed() && !active.is_changed() {
+        return;
+    }
+
+    let Some(image) = images.get_mut(&minimap.image) else {
+        return;
+    };
+
+    let columns = active.columns(&grid_settings);
+    let width = columns.len() as i32;
+    let height = grid_settings.grid_height;
+
+    // A different map was laid out, start again from a blank texture its size
+    if image.width() != width as u32 || image.height() != height as u32 {
+        image.resize(Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        });
+        for pixel in image.data.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&EMPTY_COLOR);
+        }
+    }
+
+    for tile in tiles.iter() {
+        if !columns.contains(&tile.x) || tile.y < 0 || tile.y >= height {
+            continue;
+        }
+
+        let color_of = |owner: Option<Entity>| owner.and_then(|owner| player_query.get(owner).ok());
+        let pixel = match (color_of(tile.trail_owner), color_of(tile.owner)) {
+            (Some(trailer), _) => trailer.color.to_srgba().to_u8_array(),
+            (None, Some(owner)) => owner.color.to_srgba().with_alpha(0.6).to_u8_array(),
+            (None, None) if tile.void => VOID_COLOR,
+            (None, None) if tile.stairs => STAIRS_COLOR,
+            (None, None) => EMPTY_COLOR,
+        };
+
+        // Image rows go top to bottom, tile rows go bottom to top
+        let row = (height - 1 - tile.y) as usize;
+        let offset = (row * width as usize + (tile.x - columns.start) as usize) * 4;
+        image.data[offset..offset + 4].copy_from_slice(&pixel);
+    }
+}
+
+// Where a tile is on the minimap, None while it's on the floor out of view
+fn minimap_position(
+    grid_settings: &GridSettings,
+    active: &ActiveFloor,
+    tile: (i32, i32),
+) -> Option<(Val, Val)> {
+    let columns = active.columns(grid_settings);
+    if !columns.contains(&tile.0) {
+        return None;
+    }
+    let left = (tile.0 - columns.start) as f32 + 0.5;
+    let left = left / columns.len() as f32 * 100.0;
+    let bottom = (tile.1 as f32 + 0.5) / grid_settings.grid_height as f32 * 100.0;
+    Some((Val::Percent(left), Val::Percent(bottom)))
+}
+
+// Places a marker over a tile, hiding it while the tile is out of view
+fn place_marker(node: &mut Node, position: Option<(Val, Val)>) {
+    match position {
+        Some((left, bottom)) => {
+            node.left = left;
+            node.bottom = bottom;
+            node.display = Display::Flex;
+        }
+        None => node.display = Display::None,
+    }
+}
+
+// Keeps a dot on the minimap for every player
+#[allow(clippy::too_many_arguments)]
+pub fn update_minimap_markers_system(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    active: Res<ActiveFloor>,
+    vision: Res<Vision>,
+    root_query: Query<Entity, With<MinimapRoot>>,
+    new_players: Query<(Entity, &Player, Has<LocalPlayer>), Added<Player>>,
+    player_query: Query<&Player>,
+    mut marker_query: Query<(Entity, &MinimapPlayerMarker, &mut Node)>,
+) {
+    let Ok(root) = root_query.get_single() else {
+        return;
+    };
+
+    for (player_entity, player, is_local) in new_players.iter() {
+        // Local players get a white outline so they stand out from enemies
+        let outline = if is_local { Color::WHITE } else { player.color };
+
+        commands.entity(root).with_children(|parent| {
+            parent.spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    width: Val::Px(6.0),
+                    height: Val::Px(6.0),
+                    margin: UiRect::new(Val::Px(-3.0), Val::ZERO, Val::ZERO, Val::Px(-3.0)),
+                    border: UiRect::all(Val::Px(1.0)),
+                    ..default()
+                },
+                BackgroundColor(player.color),
+                BorderColor(outline),
+                MinimapPlayerMarker {
+                    player: player_entity,
+                },
+            ));
+        });
+    }
+
+    for (marker_entity, marker, mut node) in marker_query.iter_mut() {
+        let Ok(player) = player_query.get(marker.player) else {
+            commands.entity(marker_entity).despawn_recursive();
+            continue;
+        };
+
+        // Nobody shows up on the minimap from inside the fog either
+        let position = minimap_position(&grid_settings, &active, player.last_tile_pos)
+            .filter(|_| vision.sees(player.last_tile_pos));
+        place_marker(&mut node, position);
+    }
+}
+
+// Pings enemies on the minimap while they are near the local player's trail
+#[allow(clippy::too_many_arguments)]
+pub fn minimap_ping_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    grid_settings: Res<GridSettings>,
+    active: Res<ActiveFloor>,
+    warnings: Res<ProximityWarnings>,
+    root_query: Query<Entity, With<MinimapRoot>>,
+    player_query: Query<&Player>,
+    mut ping_query: Query<(Entity, &MinimapPing, &mut Node, &mut BackgroundColor)>,
+) {
+    let Ok(root) = root_query.get_single() else {
+        return;
+    };
+
+    let mut pinged = Vec::new();
+
+    for (ping_entity, ping, mut node, mut background) in ping_query.iter_mut() {
+        let threat = warnings.threats.iter().find(|t| t.enemy == ping.enemy);
+        let (Some(threat), Ok(enemy)) = (threat, player_query.get(ping.enemy)) else {
+            commands.entity(ping_entity).despawn_recursive();
+            continue;
+        };
+
+        // Pulse between 8 and 14 pixels, faster the closer the enemy is
+        let rate = 4.0 + 8.0 / (threat.distance.max(1) as f32);
+        let pulse = (time.elapsed_secs() * rate).sin() * 0.5 + 0.5;
+        let size = 8.0 + pulse * 6.0;
+
+        let position = minimap_position(&grid_settings, &active, enemy.last_tile_pos);
+        place_marker(&mut node, position);
+        node.width = Val::Px(size);
+        node.height = Val::Px(size);
+        node.margin = UiRect::new(
+            Val::Px(-size / 2.0),
+            Val::ZERO,
+            Val::ZERO,
+            Val::Px(-size / 2.0),
+        );
+        background.0 = Color::srgba(1.0, 0.1, 0.1, 1.0 - pulse * 0.6);
+        pinged.push(ping.enemy);
+    }
+
+    for threat in warnings.threats.iter() {
+        if pinged.contains(&threat.enemy) {
+            continue;
+        }
+        pinged.push(threat.enemy);
+
+        commands.entity(root).with_children(|parent| {
+            parent.spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                BorderRadius::MAX,
+                BackgroundColor(Color::srgb(1.0, 0.1, 0.1)),
+                MinimapPing {
+                    enemy: threat.enemy,
+                },
+            ));
+        });
+    }
+}
+
+// Telegraphs hazard walls on the minimap, flashing on their starting side
+// once announced, then solid red as they sweep across
+#[allow(clippy::too_many_arguments)]
+pub fn minimap_hazard_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    grid_settings: Res<GridSettings>,
+    active: Res<ActiveFloor>,
+    schedule: Option<Res<HazardSchedule>>,
+    root_query: Query<Entity, With<MinimapRoot>>,
+    mut marker_query: Query<(
+        Entity,
+        &MinimapHazardMarker,
+        &mut Node,
+        &mut BackgroundColor,
+    )>,
+) {
+    let walls = schedule
+        .as_ref()
+        .map_or(&[][..], |schedule| &schedule.walls);
+    let (width, height) = (grid_settings.grid_width, grid_settings.grid_height);
+    // Walls sweep the whole grid, the minimap may only show a floor of it
+    let columns = active.columns(&grid_settings);
+    let elapsed = game_state.timer.elapsed_secs();
+    let pulse = 0.5 + 0.5 * (time.elapsed_secs() * 10.0).sin();
+
+    for (marker_entity, marker, mut node, mut background) in marker_query.iter_mut() {
+        let Some(scheduled) = walls
+            .iter()
+            .find(|scheduled| scheduled.wall.starts_at == marker.starts_at)
+        else {
+            commands.entity(marker_entity).despawn_recursive();
+            continue;
+        };
+
+        let line = scheduled.wall.line_at(elapsed, width, height);
+        let bounds = scheduled.wall.bounds(line.unwrap_or(0), width, height);
+        let shown = columns.len() as f32;
+        node.left = Val::Percent((bounds.min.0 - columns.start) as f32 / shown * 100.0);
+        node.bottom = Val::Percent(bounds.min.1 as f32 / height as f32 * 100.0);
+        node.width = Val::Percent(bounds.width() as f32 / shown * 100.0);
+        node.height = Val::Percent(bounds.height() as f32 / height as f32 * 100.0);
+        background.0 = match line {
+            Some(_) => Color::srgb(1.0, 0.1, 0.1),
+            None => Color::srgba(1.0, 0.45, 0.1, 0.3 + 0.7 * pulse),
+        };
+    }
+
+    let Ok(root) = root_query.get_single() else {
+        return;
+    };
+    for scheduled in walls {
+        if marker_query
+            .iter()
+            .any(|(_, marker, _, _)| marker.starts_at == scheduled.wall.starts_at)
+        {
+            continue;
+        }
+
+        // Placed and coloured from the next frame on
+        commands.entity(root).with_children(|parent| {
+            parent.spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                BackgroundColor(Color::NONE),
+                MinimapHazardMarker {
+                    starts_at: scheduled.wall.starts_at,
+                },
+            ));
+        });
+    }
+}
+
+// Rings the bounty target on the minimap
+pub fn minimap_bounty_system(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    active: Res<ActiveFloor>,
+    root_query: Query<Entity, With<MinimapRoot>>,
+    target_query: Query<&Player, With<BountyTarget>>,
+    mut marker_query: Query<(Entity, &mut Node), With<MinimapCrownMarker>>,
+) {
+    let Ok(target) = target_query.get_single() else {
+        for (entity, _) in marker_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+
+    let position = minimap_position(&grid_settings, &active, target.last_tile_pos);
+    if let Ok((_, mut node)) = marker_query.get_single_mut() {
+        place_marker(&mut node, position);
+        return;
+    }
+    let Some((left, bottom)) = position else {
+        return;
+    };
+
+    let Ok(root) = root_query.get_single() else {
+        return;
+    };
+    commands.entity(root).with_children(|parent| {
+        parent.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left,
+                bottom,
+                width: Val::Px(12.0),
+                height: Val::Px(12.0),
+                margin: UiRect::new(Val::Px(-6.0), Val::ZERO, Val::ZERO, Val::Px(-6.0)),
+                border: UiRect::all(Val::Px(2.0)),
+                ..default()
+            },
+            BorderRadius::MAX,
+            BorderColor(Color::srgb(1.0, 0.8, 0.1)),
+            MinimapCrownMarker,
+        ));
+    });
+}
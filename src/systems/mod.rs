@@ -1,5 +1,60 @@
+pub mod abilities;
+pub mod accessibility;
+pub mod analysis;
+pub mod announcer;
+pub mod attract;
+pub mod audio;
+pub mod board;
+pub mod bots;
+pub mod bounty;
+pub mod camera;
+pub mod challenges;
+pub mod cinematic;
+pub mod claim_preview;
+pub mod coach;
 pub mod collision;
+pub mod comeback;
+pub mod countdown;
+pub mod daily;
+pub mod decoy;
+pub mod difficulty;
+pub mod director;
+pub mod display;
+pub mod emotes;
+pub mod errors;
+pub mod floors;
+pub mod hazards;
+pub mod heatmap;
+pub mod hints;
+pub mod history;
+pub mod home_view;
 pub mod input;
+pub mod join;
+pub mod killcam;
+pub mod minimap;
 pub mod movement;
+pub mod music;
+pub mod pause;
+pub mod photo;
+pub mod pickups;
 pub mod player;
+pub mod profiles;
+pub mod projection;
+pub mod proximity;
+pub mod puzzle;
+pub mod quick_play;
+pub mod rating;
+pub mod results;
+pub mod sandbox;
+pub mod score_hud;
+pub mod settings;
+pub mod split_screen;
+pub mod stats;
+pub mod telemetry;
+pub mod theme;
+pub mod tile_effects;
+pub mod toasts;
+pub mod tournament;
 pub mod trails;
+pub mod weather;
+pub mod zone;
@@ -1,109 +1,107 @@
 // In src/systems/movement.rs
-use crate::components::{GridSettings, Player, Tile};
-use crate::events::{PlayerDeathEvent, PlayerDeathReason};
-use crate::resources::CompleteTrail;
+use crate::components::{GridSettings, GridTopology, Player, Tiles};
+use crate::events::{PlayerDeathEvent, PlayerDeathReason, TrailCompletedEvent};
+use crate::grid::GridMath;
+use crate::progression::TrailStyle;
+use crate::resources::{GameRules, GameState};
+use crate::states::AppState;
+use crate::systems::pickups::Ghost;
+use crate::systems::weather::WeatherSchedule;
+use crate::territory::trail_length;
 use bevy::prelude::*;
 
+type MovingPlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static mut Player,
+        Has<Ghost>,
+        Option<&'static TrailStyle>,
+    ),
+>;
+
+#[allow(clippy::too_many_arguments)]
 pub fn player_movement_system(
     time: Res<Time>,
+    game_state: Res<GameState>,
     grid_settings: Res<GridSettings>,
-    mut commands: Commands,
-    mut query: Query<(Entity, &mut Transform, &mut Player)>,
-    mut tile_query: Query<(Entity, &mut Tile, &mut Sprite)>,
+    weather: Option<Res<WeatherSchedule>>,
+    mut query: MovingPlayerQuery,
+    mut tiles: ResMut<Tiles>,
     mut death_events: EventWriter<PlayerDeathEvent>,
+    mut trail_events: EventWriter<TrailCompletedEvent>,
 ) {
-    let tile_size = grid_settings.tile_size;
-    let half_width = (grid_settings.grid_width as f32 * tile_size) / 2.0;
-    let half_height = (grid_settings.grid_height as f32 * tile_size) / 2.0;
-
-    for (entity, mut transform, mut player) in query.iter_mut() {
-        if player.direction.length_squared() > 0.0 {
-            // Calculate current grid position
-            let current_x = ((transform.translation.x + half_width) / tile_size).floor() as i32;
-            let current_y = ((transform.translation.y + half_height) / tile_size).floor() as i32;
-            let current_pos = (current_x, current_y);
-
-            // Calculate tile center position
-            let tile_center_x = (current_x as f32 * tile_size) - half_width + (tile_size / 2.0);
-            let tile_center_y = (current_y as f32 * tile_size) - half_height + (tile_size / 2.0);
-            let tile_center = Vec2::new(tile_center_x, tile_center_y);
-
-            // Calculate distance to tile center
-            let distance_to_center =
-                Vec2::new(transform.translation.x, transform.translation.y).distance(tile_center);
-
-            // If we're at a tile center or just starting movement
-            if distance_to_center < 0.5
-                || (!player.is_moving_to_next_tile && current_pos != player.last_tile_pos)
-            {
-                // We've reached a new tile center
-                player.is_moving_to_next_tile = false;
-                player.last_tile_pos = current_pos;
-
-                // Apply any buffered direction change now that we're at a tile center
-                if let Some(new_dir) = player.buffered_direction {
-                    player.direction = new_dir;
-                    player.buffered_direction = None;
-                    println!("Applied buffered direction: {:?}", player.direction);
-                }
+    let grid = GridMath::new(&grid_settings);
 
-                // Mark that we're starting movement to the next tile
-                player.is_moving_to_next_tile = true;
+    'players: for (entity, mut transform, mut player, ghost, trail_style) in query.iter_mut() {
+        // A long frame or a fast player can cover more than one tile, and
+        // each tile passed gets its logic run
+        let mut delta_secs = time.delta_secs();
+        while delta_secs > 0.0 && player.direction.length_squared() > 0.0 {
+            let (heading, retracing) = (player.direction, player.retracing);
+            if let Some(arrived) = arrive_at_tile(&mut player) {
+                let bounced = bounce_off_edge(&mut player, &grid_settings);
+                let current_pos = take_stairs(&mut player, &grid_settings).unwrap_or(arrived);
+                let (current_x, current_y) = current_pos;
 
                 // CRITICAL CHECK: First determine what type of tile we're on BEFORE changing it
                 let mut on_trail = false;
                 let mut on_territory = false;
                 let mut on_empty = false;
 
-                for (_, tile, _) in tile_query.iter() {
-                    if tile.x == current_x && tile.y == current_y {
-                        if tile.owner == Some(entity) {
-                            if tile.is_trail {
-                                on_trail = true;
-                            } else {
-                                on_territory = true;
-                            }
-                        } else if tile.owner.is_none() {
-                            on_empty = true;
-                        }
-                        break;
+                // Trails run over anyone's land, so only the trail layer
+                // decides whether the tile is free to lay one on
+                if let Some(tile) = tiles.get(current_x, current_y) {
+                    if tile.trail_owner == Some(entity) {
+                        on_trail = true;
+                    } else if tile.owner == Some(entity) {
+                        on_territory = true;
+                    } else if tile.trail_owner.is_none() {
+                        on_empty = true;
                     }
                 }
 
+                // Stepping off the map's shape is deadly, ghost or not
+                if tiles
+                    .get(current_x, current_y)
+                    .is_some_and(|tile| tile.void)
+                {
+                    death_events.send(PlayerDeathEvent {
+                        player_entity: entity,
+                        reason: PlayerDeathReason::OutOfBounds,
+                        killer: None,
+                        tile: current_pos,
+                        trail_length: trail_length(tiles.iter(), entity),
+                    });
+                    continue 'players;
+                }
+
                 // CASE 1: If we're on our own trail and drawing a trail, that's a collision!
-                if on_trail && player.is_drawing_trail {
+                // Ghosts pass straight through, and so does a player retracing
+                // it after a bounce
+                if on_trail && player.is_drawing_trail && !ghost && !retracing {
                     println!("⚠️ PLAYER HIT THEIR OWN TRAIL! GAME OVER! ⚠️");
                     death_events.send(PlayerDeathEvent {
                         player_entity: entity,
                         reason: PlayerDeathReason::TrailCollision,
+                        killer: None,
+                        tile: current_pos,
+                        trail_length: trail_length(tiles.iter(), entity),
                     });
-                    continue; // Skip the rest of the movement processing
+                    continue 'players; // Skip the rest of the movement processing
                 }
 
                 // Determine next tile state based on current direction
-                let next_dir = player.direction.normalize();
-                let next_x = current_x + next_dir.x.round() as i32;
-                let next_y = current_y + next_dir.y.round() as i32;
+                let (next_x, next_y) = next_tile(&player);
 
                 // Check if next tile is in bounds
-                if next_x >= 0
-                    && next_x < grid_settings.grid_width
-                    && next_y >= 0
-                    && next_y < grid_settings.grid_height
-                {
+                if grid_settings.same_floor(current_pos, (next_x, next_y)) {
                     // Check if next tile is player's territory
-                    let mut next_is_territory = false;
-
-                    for (_, tile, _) in tile_query.iter() {
-                        if tile.x == next_x && tile.y == next_y {
-                            if tile.owner == Some(entity) {
-                                if !tile.is_trail {
-                                    next_is_territory = true;
-                                }
-                            }
-                            break;
-                        }
-                    }
+                    let next_is_territory = tiles
+                        .get(next_x, next_y)
+                        .is_some_and(|tile| tile.owner == Some(entity));
 
                     // CASE 2: Currently on territory, about to leave territory
                     // Mark that we'll start drawing trail at the NEXT tile, not this one
@@ -120,7 +118,7 @@ pub fn player_movement_system(
 
                 // Process current tile (not the next one)
                 // Only make changes AFTER checking what type it is
-                for (_, mut tile, mut sprite) in tile_query.iter_mut() {
+                for (tile, tile_color) in tiles.iter_mut() {
                     if tile.x == current_x && tile.y == current_y {
                         // If we're on our own territory and we're drawing a trail
                         // and it's not the tile we just started drawing from
@@ -131,47 +129,159 @@ pub fn player_movement_system(
                                 "Player returned to their territory - claiming enclosed area!"
                             );
 
-                            commands.insert_resource(CompleteTrail {
-                                player: Some(entity),
-                                complete: true,
-                                entry_point: Some((current_x, current_y)),
+                            trail_events.send(TrailCompletedEvent {
+                                player: entity,
+                                entry_point: (current_x, current_y),
                             });
                         }
-                        // Mark as part of trail if drawing and NOT the player's territory
-                        else if player.is_drawing_trail && (on_empty || on_trail) {
-                            tile.is_trail = true;
-                            tile.owner = Some(entity);
+                        // Mark as part of trail if drawing and NOT the player's territory,
+                        // leaving whoever owns the land underneath as they are. Trail
+                        // being retraced is already laid.
+                        else if player.is_drawing_trail && (on_empty || on_trail && !retracing) {
+                            tile.trail_owner = Some(entity);
 
-                            // Keep consistent trail color
-                            sprite.color = player.color.with_alpha(0.8);
+                            // Keep consistent trail color, in the player's style
+                            tile_color.0 = trail_style
+                                .copied()
+                                .unwrap_or_default()
+                                .tile_color(player.color, current_pos);
                         }
                         break;
                     }
                 }
-            }
 
-            // Apply movement (smooth)
-            let normalized_dir = player.direction.normalize();
-            let movement = normalized_dir * player.speed * time.delta_secs();
-            transform.translation.x += movement.x * tile_size;
-            transform.translation.y += movement.y * tile_size;
-
-            // Calculate new grid position
-            let new_x = ((transform.translation.x + half_width) / tile_size).floor() as i32;
-            let new_y = ((transform.translation.y + half_height) / tile_size).floor() as i32;
-
-            // Constrain to grid boundaries
-            let constrained_x = new_x.clamp(0, grid_settings.grid_width - 1);
-            let constrained_y = new_y.clamp(0, grid_settings.grid_height - 1);
-
-            // If we've gone beyond the grid boundaries, snap back
-            if constrained_x != new_x || constrained_y != new_y {
-                transform.translation.x =
-                    (constrained_x as f32 * tile_size) - half_width + (tile_size / 2.0);
-                transform.translation.y =
-                    (constrained_y as f32 * tile_size) - half_height + (tile_size / 2.0);
-                player.is_moving_to_next_tile = false; // We've snapped to a tile center
+                // Retracing starts with a bounce off a tile of trail and lasts
+                // as long as the player keeps straight on over it
+                let laid = tiles
+                    .get(current_x, current_y)
+                    .is_some_and(|tile| tile.trail_owner == Some(entity));
+                player.retracing = laid && (bounced || retracing && player.direction == heading);
             }
+
+            // Rain and wind change how far everyone gets this frame
+            let scale = weather.as_ref().map_or(1.0, |weather| {
+                weather.speed_scale(game_state.timer.elapsed_secs(), player.direction)
+            });
+            delta_secs = advance_player(&mut player, delta_secs * scale, &grid_settings) / scale;
         }
+
+        // Drawn from the logical position, whether moving or not
+        let position = player_position(&player, &grid);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+// Where a player is drawn: the center of their tile, plus however far they've
+// got towards the next one
+pub fn player_position(player: &Player, grid: &GridMath) -> Vec2 {
+    let (x, y) = player.last_tile_pos;
+    grid.center_of(x, y) + player.direction * player.progress * grid.tile_size
+}
+
+// Tile a player is heading into
+pub fn next_tile(player: &Player) -> (i32, i32) {
+    let (x, y) = player.last_tile_pos;
+    (
+        x + player.direction.x.round() as i32,
+        y + player.direction.y.round() as i32,
+    )
+}
+
+// A player stood on a tile center (just arrived, or starting out) takes the
+// turn they had buffered. Returns the tile they're on when it's time to run
+// the tile logic, which happens once per tile.
+pub fn arrive_at_tile(player: &mut Player) -> Option<(i32, i32)> {
+    if player.progress > 0.0 {
+        return None;
+    }
+
+    // Apply any buffered direction change now that we're at a tile center
+    if let Some(new_dir) = player.buffered_direction.take() {
+        player.direction = new_dir;
+        println!("Applied buffered direction: {:?}", player.direction);
+    }
+    Some(player.last_tile_pos)
+}
+
+// On a mirrored map, a player stood on the edge tile and heading off it is
+// turned back. Returns whether they were.
+pub fn bounce_off_edge(player: &mut Player, grid_settings: &GridSettings) -> bool {
+    if grid_settings.topology != GridTopology::Mirrored {
+        return false;
+    }
+    let (next_x, next_y) = next_tile(player);
+    let (x, y) = player.last_tile_pos;
+    if grid_settings.same_floor((x, y), (next_x, next_y)) {
+        return false;
+    }
+
+    if !grid_settings.same_floor((x, y), (next_x, y)) {
+        player.direction.x = -player.direction.x;
+    }
+    if !grid_settings.same_floor((x, y), (x, next_y)) {
+        player.direction.y = -player.direction.y;
+    }
+    true
+}
+
+// A player arriving on stairs carries straight on from the same tile on the
+// other floor. Stairs coming out against an edge are skipped, so a player
+// held there can't bounce between floors. Returns where they came out.
+pub fn take_stairs(player: &mut Player, grid_settings: &GridSettings) -> Option<(i32, i32)> {
+    let (x, y) = player.last_tile_pos;
+    let (out_x, out_y) = grid_settings.stairs_from(x, y)?;
+    let step = (
+        out_x + player.direction.x.round() as i32,
+        out_y + player.direction.y.round() as i32,
+    );
+    if player.direction == Vec2::ZERO || !grid_settings.same_floor((out_x, out_y), step) {
+        return None;
+    }
+    player.last_tile_pos = (out_x, out_y);
+    Some((out_x, out_y))
+}
+
+// Moves a player towards the next tile. Reaching it puts them exactly on its
+// center and returns the time they had left over, so the caller can run the
+// tile logic before moving on with it. No tile is skipped and no distance is
+// lost, however the frame times line up.
+pub fn advance_player(player: &mut Player, delta_secs: f32, grid_settings: &GridSettings) -> f32 {
+    if player.direction == Vec2::ZERO {
+        return 0.0;
+    }
+
+    // Held on the edge tile rather than walking off the grid, or its floor
+    let (next_x, next_y) = next_tile(player);
+    if !grid_settings.same_floor(player.last_tile_pos, (next_x, next_y)) {
+        player.progress = 0.0;
+        return 0.0;
+    }
+
+    player.progress += player.speed * delta_secs;
+    if player.progress < 1.0 {
+        return 0.0;
+    }
+    let left_over = (player.progress - 1.0) / player.speed;
+    player.last_tile_pos = (next_x, next_y);
+    player.progress = 0.0;
+    left_over
+}
+
+// Runs matches at the pace the rules ask for by stretching or squeezing the
+// time each tick covers. Menus and everything else between matches keep to
+// real time.
+pub fn game_speed_system(
+    rules: Res<GameRules>,
+    state: Res<State<AppState>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    let scale = match state.get() {
+        AppState::Playing | AppState::Online => rules.game_speed.time_scale(),
+        _ => 1.0,
+    };
+    if time.relative_speed() != scale {
+        time.set_relative_speed(scale);
+        println!("Game speed: {}x", scale);
     }
 }
@@ -0,0 +1,56 @@
+use crate::resources::DangerScore;
+use crate::systems::audio::{AudioBus, AudioMixer};
+use bevy::audio::Volume;
+use bevy::prelude::*;
+
+// Which layer of the soundtrack a looping sink plays. Both layers run in
+// lockstep and are crossfaded by the current danger.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MusicLayer {
+    Calm,
+    Intense,
+}
+
+// Smoothed blend between the calm (0.0) and intense (1.0) layers
+#[derive(Resource, Default)]
+pub struct MusicIntensity {
+    pub value: f32,
+}
+
+pub fn start_music(mut commands: Commands, asset_server: Res<AssetServer>) {
+    for (layer, path) in [
+        (MusicLayer::Calm, "sounds/music_calm.wav"),
+        (MusicLayer::Intense, "sounds/music_intense.wav"),
+    ] {
+        commands.spawn((
+            AudioPlayer::<AudioSource>(asset_server.load(path)),
+            PlaybackSettings::LOOP.with_volume(Volume::new(0.0)),
+            AudioBus::Music,
+            layer,
+        ));
+    }
+}
+
+pub fn music_crossfade_system(
+    time: Res<Time>,
+    danger: Res<DangerScore>,
+    mixer: Res<AudioMixer>,
+    mut intensity: ResMut<MusicIntensity>,
+    sink_query: Query<(&AudioSink, &MusicLayer)>,
+) {
+    // Ramp up quickly when things get dangerous, calm down slowly
+    let target = danger.value.clamp(0.0, 1.0);
+    let rate = if target > intensity.value { 1.5 } else { 0.4 };
+    let max_step = rate * time.delta_secs();
+    intensity.value += (target - intensity.value).clamp(-max_step, max_step);
+
+    let bus_volume = mixer.effective_volume(AudioBus::Music);
+
+    for (sink, layer) in sink_query.iter() {
+        let weight = match layer {
+            MusicLayer::Calm => 1.0 - intensity.value,
+            MusicLayer::Intense => intensity.value,
+        };
+        sink.set_volume(bus_volume * weight);
+    }
+}
@@ -0,0 +1,168 @@
+// Pausing when the window goes away. A local match holds still while the
+// window is unfocused or minimized and waits for a key once it's back. Online
+// the server keeps going, so all we can do is flag that our input isn't
+// reaching the game.
+use crate::components::LocalPlayer;
+use crate::states::{AppState, PauseState};
+use bevy::prelude::*;
+use bevy::window::{WindowFocused, WindowOccluded};
+
+// Whether the window can take input, kept up to date by the client. Stays
+// focused without a window.
+#[derive(Resource)]
+pub struct WindowFocus {
+    pub focused: bool,
+    // Minimized or completely covered
+    pub occluded: bool,
+}
+
+impl Default for WindowFocus {
+    fn default() -> Self {
+        Self {
+            focused: true,
+            occluded: false,
+        }
+    }
+}
+
+impl WindowFocus {
+    pub fn away(&self) -> bool {
+        !self.focused || self.occluded
+    }
+}
+
+#[derive(Component)]
+pub struct PauseOverlay;
+
+// Shown online while the window is away
+#[derive(Component)]
+pub struct InputLostIndicator;
+
+pub fn track_window_focus_system(
+    mut focus: ResMut<WindowFocus>,
+    mut focused_events: EventReader<WindowFocused>,
+    mut occluded_events: EventReader<WindowOccluded>,
+) {
+    for event in focused_events.read() {
+        focus.focused = event.focused;
+    }
+    for event in occluded_events.read() {
+        focus.occluded = event.occluded;
+    }
+}
+
+// Pauses as soon as the window goes away, and resumes on the first key or
+// button pressed after it's back
+pub fn auto_pause_system(
+    focus: Res<WindowFocus>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    state: Res<State<PauseState>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    match state.get() {
+        PauseState::Running if focus.away() => {
+            println!("Paused while the window is away");
+            next_state.set(PauseState::Paused);
+        }
+        PauseState::Paused if !focus.away() => {
+            let pressed = keyboard_input.get_just_pressed().next().is_some()
+                || gamepads
+                    .iter()
+                    .any(|gamepad| gamepad.get_just_pressed().next().is_some());
+            if pressed {
+                println!("Resumed");
+                next_state.set(PauseState::Running);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Only a solo match pauses by itself. With several players on one machine
+// the others are still playing while one of them looks away.
+pub fn one_local_player(local_query: Query<(), With<LocalPlayer>>) -> bool {
+    local_query.iter().count() == 1
+}
+
+// Whether a local match is held, for whatever reason
+pub fn match_held(state: Option<Res<State<PauseState>>>) -> bool {
+    state.is_some_and(|state| *state.get() != PauseState::Running)
+}
+
+// Stops the clock, so timers and anything else driven by it hold still too
+pub fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+pub fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn spawn_centered_notice(commands: &mut Commands, message: &str, marker: impl Bundle) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+            PickingBehavior::IGNORE,
+            marker,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(message),
+                TextFont::from_font_size(32.0),
+                TextColor(Color::WHITE),
+                TextLayout::new_with_justify(JustifyText::Center),
+            ));
+        });
+}
+
+pub fn setup_pause_overlay(mut commands: Commands) {
+    spawn_centered_notice(
+        &mut commands,
+        "Paused\nPress any key to resume",
+        PauseOverlay,
+    );
+}
+
+pub fn cleanup_pause_overlay(
+    mut commands: Commands,
+    overlay_query: Query<Entity, With<PauseOverlay>>,
+) {
+    for entity in overlay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Online there's no pausing, the match carries on without our input
+pub fn input_lost_indicator_system(
+    mut commands: Commands,
+    focus: Res<WindowFocus>,
+    state: Res<State<AppState>>,
+    indicator_query: Query<Entity, With<InputLostIndicator>>,
+) {
+    let show = focus.away() && *state.get() == AppState::Online;
+    let shown = !indicator_query.is_empty();
+    if show == shown {
+        return;
+    }
+
+    if show {
+        spawn_centered_notice(
+            &mut commands,
+            "Disconnected from input\nThe match goes on without you",
+            InputLostIndicator,
+        );
+    } else {
+        for entity in indicator_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,457 @@
+// Photo mode. F9 holds a local match and frees the camera: WASD or the arrows
+// pan, - and = zoom, F steps through the filters, F10 hides the UI and 2-4
+// pick how many times the window's size a photo is. Enter renders the view
+// at that size into an image of its own and saves it with the captures.
+use crate::components::MainCamera;
+use crate::paths::{create_parent_dir, Paths};
+use crate::states::PauseState;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::view::screenshot::{save_to_disk, Screenshot, ScreenshotCaptured};
+use bevy::render::view::{ColorGrading, ColorGradingGlobal, ColorGradingSection};
+use bevy::window::PrimaryWindow;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// World units a second the camera pans at full zoom
+const PAN_SPEED: f32 = 400.0;
+// Zoom change a second while a zoom key is held, and per wheel notch
+const ZOOM_SPEED: f32 = 1.5;
+const WHEEL_ZOOM: f32 = 0.1;
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 2.0;
+// Photos are this many times the window's size
+pub const MIN_PHOTO_SCALE: u32 = 2;
+pub const MAX_PHOTO_SCALE: u32 = 4;
+
+const SCALE_KEYS: [(KeyCode, u32); 3] = [
+    (KeyCode::Digit2, 2),
+    (KeyCode::Digit3, 3),
+    (KeyCode::Digit4, 4),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PhotoFilter {
+    #[default]
+    None,
+    Mono,
+    Warm,
+    Vivid,
+    Noir,
+}
+
+impl PhotoFilter {
+    pub const ALL: [PhotoFilter; 5] = [
+        PhotoFilter::None,
+        PhotoFilter::Mono,
+        PhotoFilter::Warm,
+        PhotoFilter::Vivid,
+        PhotoFilter::Noir,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PhotoFilter::None => "none",
+            PhotoFilter::Mono => "mono",
+            PhotoFilter::Warm => "warm",
+            PhotoFilter::Vivid => "vivid",
+            PhotoFilter::Noir => "noir",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&filter| filter == self);
+        Self::ALL[index.map_or(0, |index| (index + 1) % Self::ALL.len())]
+    }
+
+    // Grading that makes the filter, None for the game's own colors
+    pub fn grading(self) -> Option<ColorGrading> {
+        let (global, section) = match self {
+            PhotoFilter::None => return None,
+            PhotoFilter::Mono => (
+                ColorGradingGlobal {
+                    post_saturation: 0.0,
+                    ..default()
+                },
+                ColorGradingSection::default(),
+            ),
+            PhotoFilter::Warm => (
+                ColorGradingGlobal {
+                    temperature: 0.4,
+                    tint: 0.1,
+                    ..default()
+                },
+                ColorGradingSection::default(),
+            ),
+            PhotoFilter::Vivid => (
+                ColorGradingGlobal {
+                    post_saturation: 1.5,
+                    ..default()
+                },
+                ColorGradingSection {
+                    contrast: 1.15,
+                    ..default()
+                },
+            ),
+            PhotoFilter::Noir => (
+                ColorGradingGlobal {
+                    post_saturation: 0.0,
+                    exposure: -0.3,
+                    ..default()
+                },
+                ColorGradingSection {
+                    contrast: 1.6,
+                    ..default()
+                },
+            ),
+        };
+        Some(ColorGrading::with_identical_sections(global, section))
+    }
+}
+
+// Where the photo camera is and how photos come out. Only there while in
+// photo mode.
+#[derive(Resource)]
+pub struct PhotoMode {
+    pub position: Vec2,
+    pub zoom: f32,
+    pub filter: PhotoFilter,
+    pub hide_ui: bool,
+    scale: u32,
+    // UI hidden for the photo, with how it was shown before
+    hidden: Vec<(Entity, Visibility)>,
+}
+
+impl PhotoMode {
+    pub fn new(position: Vec2, zoom: f32) -> Self {
+        Self {
+            position,
+            zoom,
+            filter: PhotoFilter::None,
+            hide_ui: false,
+            scale: MIN_PHOTO_SCALE,
+            hidden: Vec::new(),
+        }
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: u32) {
+        self.scale = scale.clamp(MIN_PHOTO_SCALE, MAX_PHOTO_SCALE);
+    }
+
+    // Pixels in a photo of a window this many physical pixels across
+    pub fn photo_size(&self, window: UVec2) -> UVec2 {
+        window.max(UVec2::ONE) * self.scale
+    }
+}
+
+#[derive(Component)]
+pub struct PhotoPanel;
+
+// Renders a single photo, gone once it's saved
+#[derive(Component)]
+pub struct PhotoCamera;
+
+// F9 goes in and out of photo mode, Esc also gets out
+pub fn toggle_photo_mode_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<PauseState>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    match state.get() {
+        PauseState::Running if keyboard_input.just_pressed(KeyCode::F9) => {
+            println!("Photo mode");
+            next_state.set(PauseState::Photo);
+        }
+        PauseState::Photo if keyboard_input.any_just_pressed([KeyCode::F9, KeyCode::Escape]) => {
+            next_state.set(PauseState::Running);
+        }
+        _ => {}
+    }
+}
+
+// Starts the photo camera where the game's camera is
+pub fn enter_photo_mode(
+    mut commands: Commands,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let (position, zoom) = camera_query
+        .get_single()
+        .map_or((Vec2::ZERO, 1.0), |(transform, projection)| {
+            (transform.translation.truncate(), projection.scale)
+        });
+    commands.insert_resource(PhotoMode::new(position, zoom));
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(16.0),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        PickingBehavior::IGNORE,
+        PhotoPanel,
+    ));
+}
+
+// Puts the UI and the camera's colors back. The game's camera eases back
+// to where it was on its own.
+pub fn exit_photo_mode(
+    mut commands: Commands,
+    photo: Option<Res<PhotoMode>>,
+    panel_query: Query<Entity, With<PhotoPanel>>,
+    mut camera_query: Query<(Entity, &mut Camera), With<MainCamera>>,
+    mut visibility_query: Query<&mut Visibility>,
+) {
+    if let Some(photo) = photo {
+        for &(entity, shown) in photo.hidden.iter() {
+            if let Ok(mut visibility) = visibility_query.get_mut(entity) {
+                *visibility = shown;
+            }
+        }
+    }
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (entity, mut camera) in camera_query.iter_mut() {
+        camera.hdr = false;
+        set_filter(&mut commands, entity, PhotoFilter::None);
+    }
+    commands.remove_resource::<PhotoMode>();
+}
+
+// Grading only runs on HDR cameras with tonemapping, so cameras go back to
+// plain rendering without a filter
+fn set_filter(commands: &mut Commands, camera: Entity, filter: PhotoFilter) {
+    match filter.grading() {
+        Some(grading) => {
+            commands
+                .entity(camera)
+                .insert((grading, Tonemapping::TonyMcMapface));
+        }
+        None => {
+            commands
+                .entity(camera)
+                .insert((ColorGrading::default(), Tonemapping::None));
+        }
+    }
+}
+
+pub fn photo_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    time: Res<Time<Real>>,
+    mut photo: ResMut<PhotoMode>,
+) {
+    // The game's clock is stopped, so the camera goes by the real one
+    let delta = time.delta_secs();
+
+    let mut pan = Vec2::ZERO;
+    if keyboard_input.any_pressed([KeyCode::KeyW, KeyCode::ArrowUp]) {
+        pan.y += 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyS, KeyCode::ArrowDown]) {
+        pan.y -= 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyA, KeyCode::ArrowLeft]) {
+        pan.x -= 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyD, KeyCode::ArrowRight]) {
+        pan.x += 1.0;
+    }
+    if pan != Vec2::ZERO {
+        let step = pan.normalize() * PAN_SPEED * photo.zoom * delta;
+        photo.position += step;
+    }
+
+    let mut zoom = 0.0;
+    if keyboard_input.pressed(KeyCode::Minus) {
+        zoom += ZOOM_SPEED * delta;
+    }
+    if keyboard_input.pressed(KeyCode::Equal) {
+        zoom -= ZOOM_SPEED * delta;
+    }
+    zoom -= wheel_events.read().map(|event| event.y).sum::<f32>() * WHEEL_ZOOM;
+    if zoom != 0.0 {
+        photo.zoom = (photo.zoom * (1.0 + zoom)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        photo.filter = photo.filter.next();
+    }
+    if keyboard_input.just_pressed(KeyCode::F10) {
+        photo.hide_ui = !photo.hide_ui;
+    }
+    if let Some(&(_, scale)) = SCALE_KEYS
+        .iter()
+        .find(|(key, _)| keyboard_input.just_pressed(*key))
+    {
+        photo.set_scale(scale);
+    }
+}
+
+// Holds the game's camera where the photo camera is. Runs after the kill
+// cam, which would otherwise pull it back.
+pub fn photo_camera_system(
+    mut commands: Commands,
+    photo: Res<PhotoMode>,
+    mut camera_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut OrthographicProjection,
+            &mut Camera,
+        ),
+        With<MainCamera>,
+    >,
+) {
+    for (entity, mut transform, mut projection, mut camera) in camera_query.iter_mut() {
+        transform.translation.x = photo.position.x;
+        transform.translation.y = photo.position.y;
+        transform.rotation = Quat::IDENTITY;
+        projection.scale = photo.zoom;
+        if photo.is_changed() {
+            camera.hdr = photo.filter.grading().is_some();
+            set_filter(&mut commands, entity, photo.filter);
+        }
+    }
+}
+
+type RootUiQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static mut Visibility),
+    (With<Node>, Without<Parent>, Without<PhotoPanel>),
+>;
+
+// Hides everything on screen but the panel while the UI is off, and the
+// panel too once it's been read. What was hidden comes back as it was.
+pub fn photo_ui_system(
+    mut photo: ResMut<PhotoMode>,
+    mut root_query: RootUiQuery,
+    mut panel_query: Query<(&mut Text, &mut Visibility), With<PhotoPanel>>,
+) {
+    if photo.hide_ui {
+        let hidden: Vec<(Entity, Visibility)> = root_query
+            .iter_mut()
+            .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+            .map(|(entity, mut visibility)| {
+                (
+                    entity,
+                    std::mem::replace(&mut *visibility, Visibility::Hidden),
+                )
+            })
+            .collect();
+        if !hidden.is_empty() {
+            photo.hidden.extend(hidden);
+        }
+    } else if !photo.hidden.is_empty() {
+        for (entity, shown) in std::mem::take(&mut photo.hidden) {
+            if let Ok((_, mut visibility)) = root_query.get_mut(entity) {
+                *visibility = shown;
+            }
+        }
+    }
+
+    if !photo.is_changed() {
+        return;
+    }
+    for (mut text, mut visibility) in panel_query.iter_mut() {
+        visibility.set_if_neq(if photo.hide_ui {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+        text.0 = format!(
+            "Photo mode - WASD pan, -/= zoom, F filter: {}, F10 hide UI, 2-4 size: {}x, Enter save, F9 back",
+            photo.filter.name(),
+            photo.scale
+        );
+    }
+}
+
+// Enter renders the view again, bigger, into an image that's saved and
+// thrown away. The UI isn't in it, it only draws on the game's camera.
+pub fn take_photo_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    paths: Res<Paths>,
+    photo: Res<PhotoMode>,
+    mut images: ResMut<Assets<Image>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    let photo_size = photo.photo_size(window.physical_size());
+    let size = Extent3d {
+        width: photo_size.x,
+        height: photo_size.y,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Bgra8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::COPY_SRC | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    let handle = images.add(image);
+
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = paths.captures().join(format!("photo-{}.png", seconds));
+    if let Err(err) = create_parent_dir(&path) {
+        println!("Couldn't save the photo to {}: {}", path.display(), err);
+        return;
+    }
+
+    // Same view as the window, just more pixels of it
+    let camera = commands
+        .spawn((
+            Camera2d,
+            Camera {
+                target: RenderTarget::Image(handle.clone()),
+                order: -2,
+                hdr: photo.filter.grading().is_some(),
+                ..default()
+            },
+            Transform::from_translation(photo.position.extend(0.0)),
+            OrthographicProjection {
+                scale: photo.zoom * window.width() / photo_size.x as f32,
+                ..OrthographicProjection::default_2d()
+            },
+            PhotoCamera,
+        ))
+        .id();
+    set_filter(&mut commands, camera, photo.filter);
+
+    println!("Saving photo to {}", path.display());
+    commands
+        .spawn(Screenshot::image(handle.clone()))
+        .observe(save_to_disk(path))
+        .observe(
+            move |_: Trigger<ScreenshotCaptured>,
+                  mut commands: Commands,
+                  mut images: ResMut<Assets<Image>>| {
+                commands.entity(camera).despawn_recursive();
+                images.remove(&handle);
+            },
+        );
+}
@@ -0,0 +1,357 @@
+use crate::components::{GridSettings, Player, Respawning, Tiles};
+use crate::events::{PlaySoundEvent, SoundEffect};
+use crate::grid::GridMath;
+use crate::resources::{GameRules, MatchRng};
+use crate::systems::decoy::spawn_decoy;
+use crate::territory::TileMap;
+use bevy::prelude::*;
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const SPEED_BOOST_FACTOR: f32 = 1.5;
+const SPEED_BOOST_SECONDS: f32 = 4.0;
+const GHOST_SECONDS: f32 = 3.0;
+// Ghosts are rare and decoys uncommon, most pickups are boosts or land grabs
+const GHOST_CHANCE: f64 = 0.1;
+const DECOY_CHANCE: f64 = 0.15;
+// How see-through a ghost is drawn, it flickers back in for its last second
+const GHOST_ALPHA: f32 = 0.35;
+// Free tiles within this many steps of the pickup are handed to the collector
+const LAND_GRAB_RADIUS: i32 = 1;
+
+// Extra spawn weight for tiles next to two or more players' land
+const CONTESTED_WEIGHT: f32 = 3.0;
+// Extra spawn weight right next to the trailing player's land, fading out
+// over `UNDERDOG_REACH` tiles
+const UNDERDOG_WEIGHT: f32 = 4.0;
+const UNDERDOG_REACH: u32 = 6;
+// The smallest territory only counts as trailing below this share of the largest
+const UNDERDOG_THRESHOLD: f32 = 0.75;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickupKind {
+    // Temporary burst of speed
+    SpeedBoost,
+    // Claims the free tiles around the pickup
+    LandGrab,
+    // Trails are passed through for a few seconds, so nobody cuts or is cut
+    Ghost,
+    // Sends a fake copy of the collector wandering off
+    Decoy,
+}
+
+impl PickupKind {
+    fn color(self) -> Color {
+        match self {
+            PickupKind::SpeedBoost => Color::srgb(0.3, 0.9, 1.0),
+            PickupKind::LandGrab => Color::srgb(1.0, 0.85, 0.2),
+            PickupKind::Ghost => Color::srgb(0.85, 0.85, 1.0),
+            PickupKind::Decoy => Color::srgb(0.9, 0.4, 0.9),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub tile: (i32, i32),
+}
+
+// Active speed boost, undone when the timer runs out
+#[derive(Component)]
+pub struct SpeedBoost {
+    pub timer: Timer,
+}
+
+// Active ghost state, the player ignores trails until the timer runs out
+#[derive(Component)]
+pub struct Ghost {
+    pub timer: Timer,
+}
+
+// Decides when and where pickups appear
+#[derive(Resource, Default)]
+pub struct PickupDirector {
+    pub timer: Option<Timer>,
+}
+
+// Spawn weight for every free tile. Undirected spawns weigh every free tile
+// the same; directed ones favour contested ground and the trailing player's
+// doorstep so they have something to fight back with.
+pub fn pickup_spawn_weights(
+    map: &TileMap,
+    underdog: Option<Entity>,
+    directed: bool,
+) -> Vec<((i32, i32), f32)> {
+    let distances = match underdog.filter(|_| directed) {
+        Some(player) => distances_from_territory(map, player),
+        None => HashMap::new(),
+    };
+
+    let mut weights = Vec::new();
+    for y in 0..map.height {
+        for x in 0..map.width {
+            if !map.playable(x, y) || map.get(x, y).is_some_and(|state| state.owner.is_some()) {
+                continue;
+            }
+
+            let mut weight = 1.0;
+            if directed {
+                let mut owners = HashSet::new();
+                for dy in -2..=2 {
+                    for dx in -2..=2 {
+                        if let Some(owner) = map.get(x + dx, y + dy).and_then(|state| state.owner) {
+                            owners.insert(owner);
+                        }
+                    }
+                }
+                if owners.len() >= 2 {
+                    weight += CONTESTED_WEIGHT;
+                }
+
+                if let Some(&distance) = distances.get(&(x, y)) {
+                    if distance <= UNDERDOG_REACH {
+                        let closeness = 1.0 - distance as f32 / (UNDERDOG_REACH + 1) as f32;
+                        weight += UNDERDOG_WEIGHT * closeness;
+                    }
+                }
+            }
+            weights.push(((x, y), weight));
+        }
+    }
+    weights
+}
+
+// Steps from the player's territory to every tile within reach
+fn distances_from_territory(map: &TileMap, player: Entity) -> HashMap<(i32, i32), u32> {
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    for tile in map.territory_tiles(player) {
+        distances.insert(tile, 0);
+        queue.push_back(tile);
+    }
+
+    while let Some((x, y)) = queue.pop_front() {
+        let distance = distances[&(x, y)];
+        if distance >= UNDERDOG_REACH {
+            continue;
+        }
+        for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if map.in_bounds(nx, ny) && !distances.contains_key(&(nx, ny)) {
+                distances.insert((nx, ny), distance + 1);
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    distances
+}
+
+// Player with clearly the least land, if anyone is falling behind
+fn underdog(map: &TileMap, players: &[Entity]) -> Option<Entity> {
+    let sizes: Vec<(Entity, usize)> = players
+        .iter()
+        .map(|&player| (player, map.territory_tiles(player).len()))
+        .collect();
+    let largest = sizes.iter().map(|&(_, size)| size).max()?;
+    let (player, smallest) = sizes.into_iter().min_by_key(|&(_, size)| size)?;
+
+    (largest > 0 && (smallest as f32) < largest as f32 * UNDERDOG_THRESHOLD).then_some(player)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pickup_director_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    rules: Res<GameRules>,
+    grid_settings: Res<GridSettings>,
+    mut director: ResMut<PickupDirector>,
+    mut rng: ResMut<MatchRng>,
+    player_query: Query<Entity, With<Player>>,
+    pickup_query: Query<&Pickup>,
+    tiles: Res<Tiles>,
+) {
+    let Some(pickup_rules) = rules.pickups else {
+        director.timer = None;
+        return;
+    };
+
+    let timer = director
+        .timer
+        .get_or_insert_with(|| Timer::from_seconds(pickup_rules.interval, TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    if pickup_query.iter().count() >= pickup_rules.max_active {
+        return;
+    }
+
+    let map = TileMap::from_tiles(
+        grid_settings.grid_width,
+        grid_settings.grid_height,
+        tiles.iter(),
+    );
+    let players: Vec<Entity> = player_query.iter().collect();
+    let taken: HashSet<(i32, i32)> = pickup_query.iter().map(|pickup| pickup.tile).collect();
+
+    let weights: Vec<((i32, i32), f32)> =
+        pickup_spawn_weights(&map, underdog(&map, &players), pickup_rules.directed)
+            .into_iter()
+            .filter(|(tile, _)| !taken.contains(tile))
+            .collect();
+    let total: f32 = weights.iter().map(|&(_, weight)| weight).sum();
+    if total <= 0.0 {
+        return;
+    }
+
+    let rng = &mut rng.0;
+    let mut roll = rng.random_range(0.0..total);
+    let Some(&(tile, _)) = weights.iter().find(|&&(_, weight)| {
+        roll -= weight;
+        roll < 0.0
+    }) else {
+        return;
+    };
+
+    let roll: f64 = rng.random();
+    let kind = if roll < GHOST_CHANCE {
+        PickupKind::Ghost
+    } else if roll < GHOST_CHANCE + DECOY_CHANCE {
+        PickupKind::Decoy
+    } else if rng.random_bool(0.5) {
+        PickupKind::SpeedBoost
+    } else {
+        PickupKind::LandGrab
+    };
+
+    let grid = GridMath::new(&grid_settings);
+    let position = grid.center_of(tile.0, tile.1).extend(0.3);
+
+    commands.spawn((
+        Sprite {
+            color: kind.color(),
+            custom_size: Some(Vec2::splat(grid.tile_size * 0.45)),
+            ..default()
+        },
+        Transform::from_translation(position)
+            .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+        Pickup { kind, tile },
+    ));
+}
+
+// Speeds the player up, or extends the boost they already have
+pub fn start_speed_boost(
+    commands: &mut Commands,
+    entity: Entity,
+    player: &mut Player,
+    boost: Option<Mut<SpeedBoost>>,
+) {
+    match boost {
+        Some(mut boost) => boost.timer.reset(),
+        None => {
+            player.speed *= SPEED_BOOST_FACTOR;
+            commands.entity(entity).insert(SpeedBoost {
+                timer: Timer::from_seconds(SPEED_BOOST_SECONDS, TimerMode::Once),
+            });
+        }
+    }
+}
+
+type CollectorQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Player,
+        Option<&'static mut SpeedBoost>,
+        Option<&'static mut Ghost>,
+    ),
+    Without<Respawning>,
+>;
+
+// Players pick up whatever sits on the tile they just reached
+pub fn collect_pickups_system(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    mut player_query: CollectorQuery,
+    pickup_query: Query<(Entity, &Pickup)>,
+    mut tiles: ResMut<Tiles>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+) {
+    for (pickup_entity, pickup) in pickup_query.iter() {
+        let Some((player_entity, mut player, boost, ghost)) = player_query
+            .iter_mut()
+            .find(|(_, player, _, _)| player.last_tile_pos == pickup.tile)
+        else {
+            continue;
+        };
+
+        match pickup.kind {
+            PickupKind::SpeedBoost => {
+                start_speed_boost(&mut commands, player_entity, &mut player, boost);
+            }
+            PickupKind::LandGrab => {
+                for (tile, tile_color) in tiles.iter_mut() {
+                    let near = (tile.x - pickup.tile.0).abs() <= LAND_GRAB_RADIUS
+                        && (tile.y - pickup.tile.1).abs() <= LAND_GRAB_RADIUS;
+                    if near && tile.owner.is_none() && !tile.void {
+                        tile.owner = Some(player_entity);
+                        if tile.trail_owner.is_none() {
+                            tile_color.0 = player.color.with_alpha(0.5);
+                        }
+                        player.score += tile.value;
+                    }
+                }
+            }
+            PickupKind::Ghost => match ghost {
+                Some(mut ghost) => ghost.timer.reset(),
+                None => {
+                    commands.entity(player_entity).insert(Ghost {
+                        timer: Timer::from_seconds(GHOST_SECONDS, TimerMode::Once),
+                    });
+                }
+            },
+            PickupKind::Decoy => {
+                spawn_decoy(&mut commands, &grid_settings, player_entity, &player);
+            }
+        }
+
+        sound_events.send(PlaySoundEvent {
+            sound: SoundEffect::Claim,
+        });
+        commands.entity(pickup_entity).despawn_recursive();
+    }
+}
+
+pub fn speed_boost_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Player, &mut SpeedBoost)>,
+) {
+    for (entity, mut player, mut boost) in query.iter_mut() {
+        if boost.timer.tick(time.delta()).finished() {
+            player.speed /= SPEED_BOOST_FACTOR;
+            commands.entity(entity).remove::<SpeedBoost>();
+        }
+    }
+}
+
+// Fades ghosts out while the effect lasts, flickering them back in over the
+// last second so nobody is caught out when it ends
+pub fn ghost_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &Player, &mut Ghost, &mut Sprite)>,
+) {
+    for (entity, player, mut ghost, mut sprite) in query.iter_mut() {
+        if ghost.timer.tick(time.delta()).finished() {
+            sprite.color = player.color;
+            commands.entity(entity).remove::<Ghost>();
+            continue;
+        }
+
+        let flicker = ghost.timer.remaining_secs() < 1.0
+            && (ghost.timer.remaining_secs() * 10.0) as i32 % 2 == 0;
+        let alpha = if flicker { 1.0 } else { GHOST_ALPHA };
+        sprite.color = player.color.with_alpha(alpha);
+    }
+}
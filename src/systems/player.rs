@@ -1,35 +1,48 @@
-use crate::components::{GridSettings, Player, Tile};
-use crate::events::{PlayerDeathEvent, PlayerDeathReason};
-use crate::CompleteTrail;
+use crate::components::{GridSettings, Player, Respawning, Spectating, Tiles};
+use crate::events::{PlaySoundEvent, PlayerDeathEvent, PlayerDeathReason, SoundEffect};
+use crate::grid::GridMath;
+use crate::resources::{DeathPenalty, GameRules, PendingClaims, RespawnLocation};
+use crate::systems::history::HISTORY_SECONDS;
+use crate::systems::killcam::respawn_delay;
+use crate::systems::stats::MatchStats;
+use crate::systems::zone::SafeZone;
+use crate::territory::{king_distance, land_color, TileMap};
 use bevy::prelude::*;
+use std::collections::HashSet;
+
+// Closest an opponent may be to a spectator's respawn tile before they're
+// moved somewhere safer
+const SAFE_RESPAWN_DISTANCE: i32 = 5;
 
 // System that handles player death events
+#[allow(clippy::too_many_arguments)]
 pub fn handle_player_death(
     mut commands: Commands,
     mut death_events: EventReader<PlayerDeathEvent>,
     mut player_query: Query<&mut Player>,
-    mut tile_query: Query<(Entity, &mut Tile, &mut Sprite)>,
+    mut tiles: ResMut<Tiles>,
     grid_settings: Res<GridSettings>,
-    // Add this to cancel any pending territory claiming
-    mut complete_trail: Option<ResMut<CompleteTrail>>,
+    rules: Res<GameRules>,
+    zone: Option<Res<SafeZone>>,
+    mut pending_claims: ResMut<PendingClaims>,
+    mut match_stats: ResMut<MatchStats>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
 ) {
     // Skip if no death events
     if death_events.is_empty() {
         return;
     }
 
-    // First, explicitly cancel any pending territory claiming operations
-    if let Some(mut trail_info) = complete_trail {
-        // Clear the complete trail resource to cancel any territory claiming
-        trail_info.complete = false;
-        trail_info.player = None;
-        trail_info.entry_point = None;
-        println!("Cancelled any pending territory claims due to player death");
-    }
-
     for event in death_events.read() {
         let player_entity = event.player_entity;
 
+        // A claim still computing in the background would land after the reset
+        pending_claims.cancel(player_entity);
+
+        sound_events.send(PlaySoundEvent {
+            sound: SoundEffect::Death,
+        });
+
         match event.reason {
             PlayerDeathReason::TrailCollision => {
                 println!("⚠️ PLAYER HIT THEIR OWN TRAIL! GAME OVER! ⚠️");
@@ -43,100 +56,111 @@ pub fn handle_player_death(
             PlayerDeathReason::HitOtherPlayer => {
                 println!("PLAYER HIT ANOTHER PLAYER - PLAYER DIES!");
             }
+            PlayerDeathReason::TrailCut => {
+                println!("PLAYER'S TRAIL WAS CUT - PLAYER DIES!");
+            }
+            PlayerDeathReason::ZoneClosed => {
+                println!("PLAYER WAS CAUGHT OUTSIDE THE ZONE - PLAYER DIES!");
+            }
+            PlayerDeathReason::HazardWall => {
+                println!("PLAYER WAS HIT BY A HAZARD WALL - PLAYER DIES!");
+            }
         }
 
+        println!(
+            "Died at ({}, {}) with a {} tile trail{}",
+            event.tile.0,
+            event.tile.1,
+            event.trail_length,
+            if event.killer.is_some() {
+                ", killed by another player"
+            } else {
+                ""
+            }
+        );
+
         // Reset player
-        let player_color = if let Ok(player) = player_query.get(player_entity) {
-            player.color
-        } else {
-            Color::srgba(0.2, 0.7, 0.9, 1.0) // Default color
+        let (player_color, spawn_tile) = match player_query.get(player_entity) {
+            Ok(player) => (player.color, player.spawn_tile),
+            Err(_) => (
+                Color::srgba(0.2, 0.7, 0.9, 1.0), // Default color
+                (grid_settings.grid_width / 2, grid_settings.grid_height / 2),
+            ),
         };
 
         if let Ok(mut player) = player_query.get_mut(player_entity) {
             // Stop drawing trail immediately
             player.is_drawing_trail = false;
+            player.retracing = false;
             player.buffered_direction = None;
 
             // Set direction to zero to stop movement
             player.direction = Vec2::ZERO;
-
-            // Reset score to ZERO - lose all points!
-            player.score = 0;
+            player.progress = 0.0;
         }
 
-        // Reset player position to center of grid
-        let center_tile_x = grid_settings.grid_width / 2;
-        let center_tile_y = grid_settings.grid_height / 2;
-        let tile_size = grid_settings.tile_size;
-        let half_width = (grid_settings.grid_width as f32 * tile_size) / 2.0;
-        let half_height = (grid_settings.grid_height as f32 * tile_size) / 2.0;
-
-        let center_x = (center_tile_x as f32 * tile_size) - half_width + (tile_size / 2.0);
-        let center_y = (center_tile_y as f32 * tile_size) - half_height + (tile_size / 2.0);
+        // Work out which of the player's tiles are lost under the current rules
+        let mut tile_map = TileMap::from_tiles(
+            grid_settings.grid_width,
+            grid_settings.grid_height,
+            tiles.iter(),
+        );
 
-        // Update player transform and position
-        commands
-            .entity(player_entity)
-            .insert(Transform::from_translation(Vec3::new(
-                center_x, center_y, 0.0,
-            )));
+        // The whole trail always goes, land it crossed stays with its owner
+        let lost_trail: HashSet<(i32, i32)> = tiles
+            .iter()
+            .filter(|tile| tile.trail_owner == Some(player_entity))
+            .map(|tile| (tile.x, tile.y))
+            .collect();
 
-        // Also update player.last_tile_pos to the center tile
-        if let Ok(mut player) = player_query.get_mut(player_entity) {
-            player.last_tile_pos = (center_tile_x, center_tile_y);
-        }
+        let lost_land: HashSet<(i32, i32)> = match rules.death_penalty {
+            DeathPenalty::FullReset => tile_map
+                .territory_tiles(player_entity)
+                .into_iter()
+                .collect(),
+            DeathPenalty::TrailOnly => HashSet::new(),
+            DeathPenalty::ShrinkTerritory { fraction } => tile_map
+                .shrink_territory(player_entity, fraction)
+                .into_iter()
+                .collect(),
+            DeathPenalty::ErodeRings { rings } => tile_map
+                .erode_territory(player_entity, rings)
+                .into_iter()
+                .collect(),
+        };
 
-        let mut grid = vec![
-            vec![false; grid_settings.grid_width as usize];
-            grid_settings.grid_height as usize
-        ];
-
-        // First mark all tiles that are owned by this player in the grid
-        for (_, tile, _) in tile_query.iter() {
-            if tile.x >= 0
-                && tile.x < grid_settings.grid_width
-                && tile.y >= 0
-                && tile.y < grid_settings.grid_height
-                && tile.owner == Some(player_entity)
-            {
-                grid[tile.y as usize][tile.x as usize] = true;
-            }
+        for &(x, y) in lost_land.iter() {
+            tile_map.clear_owner(x, y);
         }
 
-        // Now reset ALL player tiles based on the grid
-        let mut territory_count = 0;
-        let mut trail_count = 0;
-
-        for (_, mut tile, mut sprite) in tile_query.iter_mut() {
-            if tile.x >= 0
-                && tile.x < grid_settings.grid_width
-                && tile.y >= 0
-                && tile.y < grid_settings.grid_height
-            {
-                let x = tile.x as usize;
-                let y = tile.y as usize;
-
-                if grid[y][x] {
-                    // Count what we're removing
-                    if tile.is_trail {
-                        trail_count += 1;
-                    } else {
-                        territory_count += 1;
-                    }
+        // Now reset the lost tiles
+        let territory_count = lost_land.len();
+        let trail_count = lost_trail.len();
+        *match_stats.tiles_lost.entry(player_entity).or_default() += territory_count as u32;
 
-                    // Reset ownership and appearance
-                    tile.owner = None;
-                    tile.is_trail = false;
+        for (tile, tile_color) in tiles.iter_mut() {
+            let position = (tile.x, tile.y);
+            let trail_lost = lost_trail.contains(&position);
+            let land_lost = lost_land.contains(&position);
+            if !trail_lost && !land_lost {
+                continue;
+            }
+            if trail_lost {
+                tile.trail_owner = None;
+            }
+            if land_lost {
+                tile.owner = None;
+            }
 
-                    // Reset to original color (checkerboard pattern)
-                    let is_dark = (tile.x + tile.y) % 2 == 0;
-                    sprite.color = if is_dark {
-                        Color::srgb(0.8, 0.8, 0.8) // Light gray
-                    } else {
-                        Color::srgb(0.9, 0.9, 0.9) // Lighter gray
-                    };
-                }
+            // Someone else's trail still covers the tile
+            if tile.trail_owner.is_some() {
+                continue;
             }
+            let owner_color = tile
+                .owner
+                .and_then(|owner| player_query.get(owner).ok())
+                .map(|owner| owner.color);
+            tile_color.0 = land_color(tile, owner_color);
         }
 
         println!(
@@ -144,47 +168,219 @@ pub fn handle_player_death(
             territory_count, trail_count
         );
 
-        // Pause briefly to ensure all tiles are reset
-        // This is just a safety measure and doesn't actually pause execution
-
-        // Give player initial territory just like at first spawn
-        let territory_radius = 2; // Creates a 5x5 area (2 tiles in each direction from center)
-        let mut initial_territory_count = 0;
-
-        for (_, mut tile, mut sprite) in tile_query.iter_mut() {
-            let dx = (tile.x - center_tile_x).abs();
-            let dy = (tile.y - center_tile_y).abs();
-
-            if dx <= territory_radius && dy <= territory_radius {
-                // Double check that this tile is NOT still owned
-                // (This is a sanity check that should never fail if the above code works)
-                if tile.owner.is_none() {
-                    // Mark as player territory
-                    tile.owner = Some(player_entity);
-                    tile.is_trail = false;
-                    sprite.color = player_color.with_alpha(0.5);
-                    initial_territory_count += 1;
-                } else {
-                    // Print warning if we find a tile still owned by someone
-                    println!(
-                        "WARNING: Tile at ({}, {}) is still owned during respawn!",
-                        tile.x, tile.y
-                    );
+        let remaining_tiles = tile_map.territory_tiles(player_entity);
+        let mut remaining_territory = remaining_tiles.len() as u32;
+        let mut remaining_value = tile_map.value_of(&remaining_tiles);
+
+        // Respawn on the chosen anchor tile if it's still ours, otherwise on the
+        // closest tile of whatever territory is left
+        let anchor = match rules.respawn_location {
+            RespawnLocation::SpawnPoint => spawn_tile,
+            RespawnLocation::NearestToDeath => event.tile,
+        };
+        let mut respawn = if tile_map.is_territory_of(anchor.0, anchor.1, player_entity) {
+            anchor
+        } else {
+            tile_map
+                .nearest_territory(anchor, player_entity)
+                .unwrap_or(spawn_tile)
+        };
+        // Never back outside the zone, with room for starting territory
+        if let Some(zone) = zone.as_ref().filter(|zone| !zone.bounds.contains(respawn)) {
+            respawn = zone.bounds.clamp(respawn, 2);
+        }
+        // Nor off the map's shape
+        if !tile_map.playable(respawn.0, respawn.1) {
+            respawn = grid_settings.spawn_tile_near(respawn);
+        }
+        let (respawn_x, respawn_y) = respawn;
+
+        if remaining_territory == 0 {
+            // Nothing left - give player initial territory just like at first spawn
+            let territory_radius = 2; // Creates a 5x5 area (2 tiles in each direction from center)
+
+            for (tile, tile_color) in tiles.iter_mut() {
+                let dx = (tile.x - respawn_x).abs();
+                let dy = (tile.y - respawn_y).abs();
+
+                if dx <= territory_radius && dy <= territory_radius && !tile.void {
+                    if tile.owner.is_none() {
+                        // Mark as player territory, under any trail crossing it
+                        tile.owner = Some(player_entity);
+                        if tile.trail_owner.is_none() {
+                            tile_color.0 = player_color.with_alpha(0.5);
+                        }
+                        remaining_territory += 1;
+                        remaining_value += tile.value;
+                    } else {
+                        // Print warning if we find a tile still owned by someone
+                        println!(
+                            "WARNING: Tile at ({}, {}) is still owned during respawn!",
+                            tile.x, tile.y
+                        );
+                    }
                 }
             }
         }
 
-        // Update player score based on initial territory
+        let respawn_world = GridMath::new(&grid_settings).center_of(respawn_x, respawn_y);
+
+        // Update player transform and position, and keep them hidden and
+        // frozen while the kill cam plays, or while they watch their killer
+        // when the rules make them sit out
+        let delay = rules
+            .respawn_delay
+            .unwrap_or_else(|| respawn_delay(HISTORY_SECONDS));
+        commands.entity(player_entity).insert((
+            Transform::from_translation(respawn_world.extend(0.0)),
+            Visibility::Hidden,
+            Respawning {
+                timer: Timer::from_seconds(delay, TimerMode::Once),
+            },
+        ));
+        if rules.respawn_delay.is_some() {
+            commands.entity(player_entity).insert(Spectating {
+                killer: event.killer,
+            });
+        }
+
+        // Score follows the territory that's left
         if let Ok(mut player) = player_query.get_mut(player_entity) {
-            player.score = initial_territory_count;
+            player.last_tile_pos = (respawn_x, respawn_y);
+            player.set_land_value(remaining_value);
         }
 
         println!(
-            "Player respawned at center with {} initial territory tiles.",
-            initial_territory_count
+            "Player respawned at ({}, {}) with {} territory tiles.",
+            respawn_x, respawn_y, remaining_territory
         );
     }
 
     // Clear death events to ensure they don't process again
     death_events.clear();
 }
+
+type RespawningQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Respawning,
+        &'static mut Visibility,
+        &'static mut Player,
+        &'static mut Transform,
+        Has<Spectating>,
+    ),
+>;
+
+// Brings dead players back once their respawn delay is over. Anyone who sat
+// out a longer delay is moved to the part of their land furthest from
+// opponents if one has wandered close in the meantime.
+pub fn respawn_timer_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    grid_settings: Res<GridSettings>,
+    mut query: RespawningQuery,
+    tiles: Res<Tiles>,
+    active_query: Query<(Entity, &Player), Without<Respawning>>,
+) {
+    for (entity, mut respawning, mut visibility, mut player, mut transform, spectating) in
+        query.iter_mut()
+    {
+        if !respawning.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        *visibility = Visibility::Visible;
+        commands.entity(entity).remove::<(Respawning, Spectating)>();
+        if !spectating {
+            continue;
+        }
+
+        let threats: Vec<(i32, i32)> = active_query
+            .iter()
+            .filter(|(other, _)| *other != entity)
+            .map(|(_, other)| other.last_tile_pos)
+            .collect();
+        let tile_map = TileMap::from_tiles(
+            grid_settings.grid_width,
+            grid_settings.grid_height,
+            tiles.iter(),
+        );
+        let threatened = threats
+            .iter()
+            .any(|&threat| king_distance(threat, player.last_tile_pos) < SAFE_RESPAWN_DISTANCE);
+        let (x, y) = player.last_tile_pos;
+        if !threatened && tile_map.is_territory_of(x, y, entity) {
+            continue;
+        }
+        let Some((safe_x, safe_y)) = tile_map.safest_territory(entity, &threats) else {
+            continue;
+        };
+
+        let safe = GridMath::new(&grid_settings).center_of(safe_x, safe_y);
+        transform.translation.x = safe.x;
+        transform.translation.y = safe.y;
+        player.last_tile_pos = (safe_x, safe_y);
+        player.progress = 0.0;
+        println!("Respawned somewhere safer at ({}, {})", safe_x, safe_y);
+    }
+}
+
+// Optional decay rule: periodically erodes the outer ring of every player's
+// territory so idle land doesn't last forever
+pub fn territory_decay_system(
+    time: Res<Time>,
+    rules: Res<GameRules>,
+    grid_settings: Res<GridSettings>,
+    mut decay_timer: Local<Option<Timer>>,
+    mut player_query: Query<(Entity, &mut Player)>,
+    mut tiles: ResMut<Tiles>,
+) {
+    let Some(interval) = rules.territory_decay_interval else {
+        *decay_timer = None;
+        return;
+    };
+
+    let timer =
+        decay_timer.get_or_insert_with(|| Timer::from_seconds(interval, TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let mut tile_map = TileMap::from_tiles(
+        grid_settings.grid_width,
+        grid_settings.grid_height,
+        tiles.iter(),
+    );
+
+    let mut decayed = HashSet::new();
+    // Decay wears land down but never takes a living player's last ring
+    for (player_entity, _) in player_query.iter() {
+        if tile_map.last_ring(player_entity) {
+            continue;
+        }
+        decayed.extend(tile_map.erode_territory(player_entity, 1));
+    }
+
+    if decayed.is_empty() {
+        return;
+    }
+
+    for (tile, tile_color) in tiles.iter_mut() {
+        if !decayed.contains(&(tile.x, tile.y)) {
+            continue;
+        }
+
+        tile.owner = None;
+        if tile.trail_owner.is_none() {
+            tile_color.0 = land_color(tile, None);
+        }
+    }
+
+    for (player_entity, mut player) in player_query.iter_mut() {
+        player.set_land_value(tile_map.value_of(&tile_map.territory_tiles(player_entity)));
+    }
+
+    println!("Territory decay removed {} tiles", decayed.len());
+}
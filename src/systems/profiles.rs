@@ -0,0 +1,202 @@
+use crate::components::{LocalPlayer, Player};
+use crate::events::{GameError, MatchEndedEvent, ToastEvent, ToastIcon, ToastPriority};
+use crate::paths::Paths;
+use crate::profiles::{ActiveProfiles, ProfileStore};
+use crate::progression::{unlocked_colors, unlocked_trail_styles, xp_for_match};
+use crate::systems::errors::profiles_not_saved;
+use crate::systems::input::InputSource;
+use crate::systems::join::JoinedPlayers;
+use crate::systems::stats::MatchStats;
+use bevy::prelude::*;
+
+// Keys that switch the profile of join slots 1-4
+const PROFILE_KEYS: [KeyCode; 4] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+];
+// Keys that cycle the color, then the trail style, of join slots 1-4
+const COLOR_KEYS: [KeyCode; 4] = [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4];
+const TRAIL_STYLE_KEYS: [KeyCode; 4] = [KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8];
+
+// Gives every newly joined slot a profile nobody else is using, making a
+// fresh one when they are all taken
+pub fn assign_profiles_system(
+    joined: Res<JoinedPlayers>,
+    mut store: ResMut<ProfileStore>,
+    mut active: ResMut<ActiveProfiles>,
+) {
+    while active.slots.len() < joined.devices.len() {
+        let slot = active.slots.len();
+        let index = match active.next_free(&store, slot) {
+            Some(index) => index,
+            None => store.create(),
+        };
+        active.slots.push(index);
+    }
+}
+
+// Number keys cycle a joined slot through the free profiles
+pub fn cycle_profile_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    store: Res<ProfileStore>,
+    mut active: ResMut<ActiveProfiles>,
+) {
+    for (slot, key) in PROFILE_KEYS.iter().enumerate() {
+        if !keyboard_input.just_pressed(*key) || slot >= active.slots.len() {
+            continue;
+        }
+
+        if let Some(index) = active.next_free(&store, slot) {
+            active.slots[slot] = index;
+        }
+    }
+}
+
+// Function keys cycle a joined slot's color and trail style through the ones
+// its profile has unlocked
+pub fn cycle_cosmetics_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    active: Res<ActiveProfiles>,
+    mut store: ResMut<ProfileStore>,
+) {
+    for (slot, &index) in active.slots.iter().enumerate().take(COLOR_KEYS.len()) {
+        let Some(profile) = store.profiles.get_mut(index) else {
+            continue;
+        };
+        let level = profile.level();
+
+        if keyboard_input.just_pressed(COLOR_KEYS[slot]) {
+            let colors: Vec<Color> = unlocked_colors(level)
+                .map(|unlockable| unlockable.color)
+                .collect();
+            let next = match colors
+                .iter()
+                .position(|&color| Some(color) == profile.color)
+            {
+                Some(current) => colors[(current + 1) % colors.len()],
+                None => colors[0],
+            };
+            profile.color = Some(next);
+        }
+
+        if keyboard_input.just_pressed(TRAIL_STYLE_KEYS[slot]) {
+            let styles: Vec<_> = unlocked_trail_styles(level).collect();
+            let current = styles
+                .iter()
+                .position(|&style| style == profile.trail_style)
+                .unwrap_or(0);
+            profile.trail_style = styles[(current + 1) % styles.len()];
+        }
+    }
+}
+
+// Applies the profile's color, trail style and key bindings to freshly
+// spawned local players. Runs before their starting territory is painted.
+pub fn apply_profile_system(
+    mut commands: Commands,
+    joined: Res<JoinedPlayers>,
+    store: Res<ProfileStore>,
+    active: Res<ActiveProfiles>,
+    mut player_query: Query<(Entity, &InputSource, &mut Player, &mut Sprite), Added<LocalPlayer>>,
+) {
+    for (entity, source, mut player, mut sprite) in player_query.iter_mut() {
+        let InputSource::Device(device) = source else {
+            continue;
+        };
+        let Some(profile) = joined
+            .slot_of(*device)
+            .and_then(|slot| active.profile(&store, slot))
+        else {
+            continue;
+        };
+
+        if let Some(color) = profile.color {
+            player.color = color;
+            sprite.color = color;
+        }
+
+        commands.entity(entity).insert(profile.trail_style);
+
+        if let Some(bindings) = profile.key_bindings {
+            commands.entity(entity).insert(bindings);
+        }
+    }
+}
+
+// Adds the finished match to each local player's record, along with the XP
+// it earned
+pub fn record_profile_stats_system(
+    mut match_end_events: EventReader<MatchEndedEvent>,
+    match_stats: Res<MatchStats>,
+    joined: Res<JoinedPlayers>,
+    active: Res<ActiveProfiles>,
+    mut store: ResMut<ProfileStore>,
+    player_query: Query<&InputSource, With<LocalPlayer>>,
+    mut toast_events: EventWriter<ToastEvent>,
+) {
+    for event in match_end_events.read() {
+        for standing in event.standings.iter() {
+            let Ok(InputSource::Device(device)) = player_query.get(standing.player) else {
+                continue;
+            };
+            let Some(&index) = joined
+                .slot_of(*device)
+                .and_then(|slot| active.slots.get(slot))
+            else {
+                continue;
+            };
+            let Some(profile) = store.profiles.get_mut(index) else {
+                continue;
+            };
+
+            profile.stats.matches_played += 1;
+            if standing.placement == 0 {
+                profile.stats.wins += 1;
+            }
+            profile.stats.best_score = profile.stats.best_score.max(standing.score);
+            for award in event.awards.iter() {
+                if award.player == standing.player {
+                    *profile.stats.awards.entry(award.award).or_default() += 1;
+                }
+            }
+
+            let level = profile.level();
+            profile.xp += xp_for_match(
+                standing.score,
+                match_stats.kills(standing.player),
+                standing.placement == 0,
+            );
+            if profile.level() > level {
+                println!("⭐ {} reached level {}!", profile.name, profile.level());
+                toast_events.send(
+                    ToastEvent::new(
+                        ToastIcon::Achievement,
+                        format!("{} reached level {}!", profile.name, profile.level()),
+                    )
+                    .with_priority(ToastPriority::High),
+                );
+            }
+        }
+    }
+}
+
+// Writes profile changes back to disk
+pub fn persist_profiles_system(
+    paths: Res<Paths>,
+    store: Res<ProfileStore>,
+    mut toast_events: EventWriter<ToastEvent>,
+    mut error_events: EventWriter<GameError>,
+) {
+    if !store.is_changed() || store.is_added() {
+        return;
+    }
+    if store.save(&paths) {
+        toast_events.send(
+            ToastEvent::new(ToastIcon::Save, "Progress saved").with_priority(ToastPriority::Low),
+        );
+    } else {
+        error_events.send(profiles_not_saved(&paths));
+    }
+}
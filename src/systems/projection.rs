@@ -0,0 +1,153 @@
+// How the map is drawn. The simulation only knows the flat square grid, and
+// transforms stay in its coordinates; the projection is applied to global
+// transforms just before drawing. Isometric turns the grid 45 degrees and
+// squashes it to half height, so tiles become diamonds, while players and
+// anything else `Upright` keep their shape and are drawn nearest last.
+use crate::components::Upright;
+use crate::systems::display::DisplaySettings;
+use bevy::math::Affine3A;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Height of the isometric view relative to its width
+const ISOMETRIC_SQUASH: f32 = 0.5;
+// Depth added per world unit towards the bottom of the screen, small enough
+// that upright things never cross into the layers above or below them
+const DEPTH_PER_UNIT: f32 = 0.00001;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViewProjection {
+    #[default]
+    TopDown,
+    Isometric,
+}
+
+impl ViewProjection {
+    pub const ALL: [ViewProjection; 2] = [ViewProjection::TopDown, ViewProjection::Isometric];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ViewProjection::TopDown => "top-down",
+            ViewProjection::Isometric => "isometric",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&view| view == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // From the grid's plane to the screen's
+    pub fn matrix(self) -> Mat2 {
+        match self {
+            ViewProjection::TopDown => Mat2::IDENTITY,
+            ViewProjection::Isometric => {
+                Mat2::from_diagonal(Vec2::new(1.0, ISOMETRIC_SQUASH))
+                    * Mat2::from_angle(std::f32::consts::FRAC_PI_4)
+            }
+        }
+    }
+
+    pub fn project(self, position: Vec2) -> Vec2 {
+        self.matrix() * position
+    }
+
+    // Back from the screen to the grid, e.g. for what's under the cursor
+    pub fn unproject(self, position: Vec2) -> Vec2 {
+        self.matrix().inverse() * position
+    }
+
+    fn affine(self) -> Affine3A {
+        let matrix = self.matrix();
+        Affine3A::from_mat3(Mat3::from_cols(
+            matrix.x_axis.extend(0.0),
+            matrix.y_axis.extend(0.0),
+            Vec3::Z,
+        ))
+    }
+}
+
+// Something drawn this frame, where it sits in the hierarchy
+pub type ProjectedQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut GlobalTransform,
+        Has<Upright>,
+        Option<&'static Parent>,
+    ),
+    (
+        Or<(With<Sprite>, With<Mesh2d>)>,
+        Changed<GlobalTransform>,
+        Without<Camera2d>,
+    ),
+>;
+
+// Whatever the projection is applied to
+pub type DrawnTransformQuery<'w, 's> =
+    Query<'w, 's, &'static mut Transform, Or<(With<Sprite>, With<Mesh2d>, With<Camera2d>)>>;
+
+// Switching projection redraws everything, not just what moved
+pub fn reproject_on_change_system(
+    settings: Res<DisplaySettings>,
+    mut transform_query: DrawnTransformQuery,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut transform in transform_query.iter_mut() {
+        transform.set_changed();
+    }
+}
+
+// Runs after transforms are propagated, on whatever they just updated.
+// Children of something projected this frame move with their parent; ones
+// whose parent didn't move were worked out from its projected transform
+// already. Sprites are never nested more than one level deep.
+pub fn project_view_system(
+    settings: Res<DisplaySettings>,
+    mut projected_query: ProjectedQuery,
+    mut camera_query: Query<&mut GlobalTransform, (With<Camera2d>, Changed<GlobalTransform>)>,
+) {
+    let projection = settings.projection;
+    if projection == ViewProjection::TopDown {
+        return;
+    }
+
+    let flat = projection.affine();
+    let mut applied: HashMap<Entity, Affine3A> = HashMap::new();
+    for (entity, mut global, upright, parent) in projected_query.iter_mut() {
+        if parent.is_some() {
+            continue;
+        }
+        let adjustment = if upright {
+            let position = global.translation();
+            let screen = projection.project(position.truncate());
+            let offset = screen - position.truncate();
+            Affine3A::from_translation(offset.extend(-screen.y * DEPTH_PER_UNIT))
+        } else {
+            flat
+        };
+        *global = GlobalTransform::from(adjustment * global.affine());
+        applied.insert(entity, adjustment);
+    }
+    for (_, mut global, _, parent) in projected_query.iter_mut() {
+        let Some(adjustment) = parent.and_then(|parent| applied.get(&parent.get())) else {
+            continue;
+        };
+        *global = GlobalTransform::from(*adjustment * global.affine());
+    }
+
+    // Cameras look at the projected spot but keep their own turn and zoom
+    for mut global in camera_query.iter_mut() {
+        let (scale, rotation, translation) = global.to_scale_rotation_translation();
+        let screen = projection.project(translation.truncate());
+        *global = GlobalTransform::from(Transform {
+            translation: screen.extend(translation.z),
+            rotation,
+            scale,
+        });
+    }
+}
@@ -0,0 +1,95 @@
+use crate::components::{LocalPlayer, Player, Tiles};
+use crate::resources::{DangerScore, GameState, ProximitySettings, ProximityWarnings, TrailThreat};
+use bevy::prelude::*;
+
+// Finds enemies that are close enough to a local player's trail to cut it.
+// Other local players count as enemies too.
+pub fn proximity_warning_system(
+    settings: Res<ProximitySettings>,
+    mut warnings: ResMut<ProximityWarnings>,
+    local_query: Query<(Entity, &Player), With<LocalPlayer>>,
+    enemy_query: Query<(Entity, &Player)>,
+    tiles: Res<Tiles>,
+) {
+    warnings.threats.clear();
+
+    for (local_entity, local_player) in local_query.iter() {
+        if !local_player.is_drawing_trail {
+            continue;
+        }
+
+        let trail_tiles: Vec<(i32, i32)> = tiles
+            .iter()
+            .filter(|tile| tile.trail_owner == Some(local_entity))
+            .map(|tile| (tile.x, tile.y))
+            .collect();
+
+        if trail_tiles.is_empty() {
+            continue;
+        }
+
+        for (enemy_entity, enemy) in enemy_query.iter() {
+            if enemy_entity == local_entity {
+                continue;
+            }
+
+            let (ex, ey) = enemy.last_tile_pos;
+
+            // Chebyshev distance so diagonals count the same as straight lines
+            let distance = trail_tiles
+                .iter()
+                .map(|&(tx, ty)| (tx - ex).abs().max((ty - ey).abs()))
+                .min()
+                .unwrap_or(i32::MAX);
+
+            if distance <= settings.warning_radius {
+                warnings.threats.push(TrailThreat {
+                    enemy: enemy_entity,
+                    distance,
+                });
+            }
+        }
+    }
+}
+
+// Trail length (in tiles) at which the trail alone counts as fully dangerous
+const DANGEROUS_TRAIL_LENGTH: f32 = 30.0;
+// Seconds left on the match timer when the final-stretch tension kicks in
+const FINAL_STRETCH_SECONDS: f32 = 30.0;
+
+// Rates the local player's danger from their trail length, nearby enemies
+// and how close the match is to ending
+pub fn danger_score_system(
+    settings: Res<ProximitySettings>,
+    warnings: Res<ProximityWarnings>,
+    game_state: Res<GameState>,
+    mut danger: ResMut<DangerScore>,
+    local_query: Query<(Entity, &Player), With<LocalPlayer>>,
+    tiles: Res<Tiles>,
+) {
+    let mut trail_danger: f32 = 0.0;
+
+    for (local_entity, local_player) in local_query.iter() {
+        if !local_player.is_drawing_trail {
+            continue;
+        }
+
+        let trail_length = tiles
+            .iter()
+            .filter(|tile| tile.trail_owner == Some(local_entity))
+            .count() as f32;
+        trail_danger = trail_danger.max((trail_length / DANGEROUS_TRAIL_LENGTH).min(1.0));
+    }
+
+    let enemy_danger = warnings
+        .threats
+        .iter()
+        .map(|threat| 1.0 - threat.distance as f32 / (settings.warning_radius + 1) as f32)
+        .fold(0.0, f32::max);
+
+    let remaining = game_state.timer.remaining_secs();
+    let final_stretch = game_state.game_running && remaining <= FINAL_STRETCH_SECONDS;
+    let time_danger = if final_stretch { 0.5 } else { 0.0 };
+
+    danger.value = (trail_danger * 0.5 + enemy_danger * 0.6 + time_danger).clamp(0.0, 1.0);
+}
@@ -0,0 +1,424 @@
+use crate::components::{GridSettings, LocalPlayer, Player, Tiles};
+use crate::events::{PlayerDeathEvent, TrailCompletedEvent};
+use crate::grid::GridMath;
+use crate::levels::{Campaign, Level};
+use crate::profiles::{ActiveProfiles, ProfileStore};
+use crate::resources::{GameState, OwnershipLayers, PendingClaims};
+use crate::states::AppState;
+use crate::systems::input::InputDevice;
+use crate::systems::join::JoinedPlayers;
+use crate::territory::land_color;
+use bevy::prelude::*;
+
+pub(crate) const ENEMY_COLOR: Color = Color::srgb(0.55, 0.2, 0.25);
+// Normal match length, used by levels without a time limit
+const UNTIMED_LEVEL_SECONDS: f32 = 300.0;
+
+// Owner of a level's pre-placed territory and trails. It never moves.
+#[derive(Component)]
+pub struct LevelEnemy;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LevelOutcome {
+    Completed { stars: u8 },
+    Failed { reason: &'static str },
+}
+
+// The puzzle level being played, with what the player has used up so far
+#[derive(Resource, Clone, Debug)]
+pub struct ActiveLevel {
+    pub level: Level,
+    pub loops: u32,
+    pub deaths: u32,
+    pub outcome: Option<LevelOutcome>,
+}
+
+impl ActiveLevel {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            loops: 0,
+            deaths: 0,
+            outcome: None,
+        }
+    }
+}
+
+// Lays out the level's enemy land and trails and moves the player to the
+// level's start before their starting territory is handed out
+pub fn start_level(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    level: Option<Res<ActiveLevel>>,
+    mut game_state: ResMut<GameState>,
+    mut player_query: Query<(&mut Player, &mut Transform), With<LocalPlayer>>,
+    mut tiles: ResMut<Tiles>,
+) {
+    let Some(level) = level else {
+        return;
+    };
+
+    let layout = level.level.tiles(&grid_settings);
+    let enemy = commands.spawn(LevelEnemy).id();
+
+    for (tile, tile_color) in tiles.iter_mut() {
+        let position = (tile.x, tile.y);
+        if layout.enemy_land.contains(&position) {
+            tile.owner = Some(enemy);
+            tile_color.0 = ENEMY_COLOR.with_alpha(0.5);
+        } else if layout.enemy_trail.contains(&position) {
+            tile.trail_owner = Some(enemy);
+            tile_color.0 = ENEMY_COLOR.with_alpha(0.8);
+        }
+    }
+
+    if let Some((start_x, start_y)) = layout.start {
+        let start = GridMath::new(&grid_settings).center_of(start_x, start_y);
+
+        for (mut player, mut transform) in player_query.iter_mut() {
+            player.spawn_tile = (start_x, start_y);
+            player.last_tile_pos = (start_x, start_y);
+            player.progress = 0.0;
+            transform.translation.x = start.x;
+            transform.translation.y = start.y;
+        }
+    }
+
+    let seconds = level.level.time_limit.unwrap_or(UNTIMED_LEVEL_SECONDS);
+    game_state.timer = Timer::from_seconds(seconds, TimerMode::Once);
+    println!(
+        "Level: {} - {}",
+        level.level.name,
+        level.level.rules_summary()
+    );
+}
+
+// Counts loops and deaths against the level's limits and decides the outcome
+pub fn level_goal_system(
+    level: Option<ResMut<ActiveLevel>>,
+    mut trail_events: EventReader<TrailCompletedEvent>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    layers: Res<OwnershipLayers>,
+    mut game_state: ResMut<GameState>,
+    local_query: Query<Entity, With<LocalPlayer>>,
+) {
+    let Some(mut level) = level else {
+        trail_events.clear();
+        death_events.clear();
+        return;
+    };
+    if level.outcome.is_some() {
+        return;
+    }
+
+    let is_local = |entity: Entity| local_query.contains(entity);
+    let loops = trail_events
+        .read()
+        .filter(|event| is_local(event.player))
+        .count() as u32;
+    let deaths = death_events
+        .read()
+        .filter(|event| is_local(event.player_entity))
+        .count() as u32;
+    if loops > 0 || deaths > 0 {
+        level.loops += loops;
+        level.deaths += deaths;
+    }
+
+    let held = local_query
+        .iter()
+        .map(|entity| layers.tile_count(entity))
+        .max()
+        .unwrap_or(0);
+
+    let outcome = if held >= level.level.goal_tiles {
+        Some(LevelOutcome::Completed {
+            stars: level.level.stars(game_state.timer.elapsed_secs()),
+        })
+    } else if level.level.max_loops.is_some_and(|max| level.loops > max) {
+        Some(LevelOutcome::Failed {
+            reason: "Too many loops",
+        })
+    } else if level.level.max_deaths.is_some_and(|max| level.deaths > max) {
+        Some(LevelOutcome::Failed {
+            reason: "Too many deaths",
+        })
+    } else if game_state.timer.finished() {
+        Some(LevelOutcome::Failed {
+            reason: "Out of time",
+        })
+    } else {
+        None
+    };
+
+    if let Some(outcome) = outcome {
+        println!("Level {}: {:?}", level.level.name, outcome);
+        game_state.game_running = false;
+        level.outcome = Some(outcome);
+    }
+}
+
+// Once a level is over, Enter goes back to the level select with a clean map
+#[allow(clippy::too_many_arguments)]
+pub fn leave_level_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    level: Option<Res<ActiveLevel>>,
+    mut game_state: ResMut<GameState>,
+    mut pending_claims: ResMut<PendingClaims>,
+    mut next_state: ResMut<NextState<AppState>>,
+    player_query: Query<Entity, With<Player>>,
+    enemy_query: Query<Entity, With<LevelEnemy>>,
+    mut tiles: ResMut<Tiles>,
+) {
+    let Some(level) = level else {
+        return;
+    };
+    if level.outcome.is_none() || !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+
+    for entity in player_query.iter().chain(enemy_query.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (tile, tile_color) in tiles.iter_mut() {
+        tile.owner = None;
+        tile.trail_owner = None;
+
+        // Back to the checkerboard, or void
+        tile_color.0 = land_color(tile, None);
+    }
+
+    pending_claims.tasks.clear();
+    pending_claims.cancelled.clear();
+    *game_state = GameState::default();
+    commands.remove_resource::<ActiveLevel>();
+    next_state.set(AppState::LevelSelect);
+}
+
+// Level list on the level select screen
+#[derive(Component)]
+pub struct LevelSelectScreen;
+
+#[derive(Component)]
+pub struct LevelSelectRow {
+    pub index: usize,
+}
+
+// Goal and limits shown while a level is played
+#[derive(Component)]
+pub struct LevelHud;
+
+fn star_text(stars: u8) -> String {
+    (0..3)
+        .map(|star| if star < stars { '*' } else { '-' })
+        .collect()
+}
+
+pub fn setup_level_select(mut commands: Commands, campaign: Res<Campaign>) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+            LevelSelectScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((Text::new("Puzzle levels"), TextFont::from_font_size(32.0)));
+
+            if campaign.levels.is_empty() {
+                screen.spawn((
+                    Text::new("No levels found in assets/levels"),
+                    TextFont::from_font_size(18.0),
+                ));
+            }
+
+            for index in 0..campaign.levels.len() {
+                screen.spawn((
+                    Text::new(""),
+                    TextFont::from_font_size(18.0),
+                    LevelSelectRow { index },
+                ));
+            }
+
+            screen.spawn((
+                Text::new("Up / Down to pick, Enter to play, Esc to go back"),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+        });
+}
+
+pub fn cleanup_level_select(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<LevelSelectScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn level_select_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut campaign: ResMut<Campaign>,
+    mut joined: ResMut<JoinedPlayers>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let count = campaign.levels.len();
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(AppState::Join);
+        return;
+    }
+    if count == 0 {
+        return;
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::ArrowUp, KeyCode::KeyW]) {
+        campaign.selected = (campaign.selected + count - 1) % count;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::ArrowDown, KeyCode::KeyS]) {
+        campaign.selected = (campaign.selected + 1) % count;
+    }
+
+    // Levels are solo, played by P1
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        if joined.devices.is_empty() {
+            joined.devices.push(InputDevice::KeyboardWasd);
+        }
+        joined.devices.truncate(1);
+        let level = campaign.levels[campaign.selected].clone();
+        commands.insert_resource(ActiveLevel::new(level));
+        next_state.set(AppState::Playing);
+    }
+}
+
+pub fn update_level_select_system(
+    campaign: Res<Campaign>,
+    store: Res<ProfileStore>,
+    active: Res<ActiveProfiles>,
+    mut row_query: Query<(&LevelSelectRow, &mut Text, &mut TextColor)>,
+) {
+    let profile = active.profile(&store, 0);
+
+    for (row, mut text, mut color) in row_query.iter_mut() {
+        let Some(level) = campaign.levels.get(row.index) else {
+            continue;
+        };
+        let stars = profile
+            .and_then(|profile| profile.levels.get(&level.id))
+            .copied()
+            .unwrap_or(0);
+
+        text.0 = format!(
+            "{}. {}  [{}]  {}",
+            row.index + 1,
+            level.name,
+            star_text(stars),
+            level.rules_summary()
+        );
+        color.0 = if row.index == campaign.selected {
+            Color::srgb(1.0, 0.85, 0.3)
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+pub fn setup_level_hud(mut commands: Commands, level: Option<Res<ActiveLevel>>) {
+    if level.is_none() {
+        return;
+    }
+
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(16.0),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        },
+        LevelHud,
+    ));
+}
+
+pub fn cleanup_level_hud(mut commands: Commands, hud_query: Query<Entity, With<LevelHud>>) {
+    for entity in hud_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn update_level_hud_system(
+    level: Option<Res<ActiveLevel>>,
+    layers: Res<OwnershipLayers>,
+    local_query: Query<Entity, With<LocalPlayer>>,
+    mut hud_query: Query<&mut Text, With<LevelHud>>,
+) {
+    let Some(level) = level else {
+        return;
+    };
+
+    let held = local_query
+        .iter()
+        .map(|entity| layers.tile_count(entity))
+        .max()
+        .unwrap_or(0);
+
+    let status = match &level.outcome {
+        Some(LevelOutcome::Completed { stars }) => {
+            format!("Complete! [{}] - Enter to continue", star_text(*stars))
+        }
+        Some(LevelOutcome::Failed { reason }) => format!("{} - Enter to continue", reason),
+        None => {
+            let loops = match level.level.max_loops {
+                Some(max) => format!(", loops {}/{}", level.loops, max),
+                None => String::new(),
+            };
+            format!("{}/{} tiles{}", held, level.level.goal_tiles, loops)
+        }
+    };
+
+    for mut text in hud_query.iter_mut() {
+        text.0 = format!("{}: {}", level.level.name, status);
+    }
+}
+
+// Keeps P1's best star count for each level
+pub fn record_level_stars_system(
+    level: Option<Res<ActiveLevel>>,
+    active: Res<ActiveProfiles>,
+    mut store: ResMut<ProfileStore>,
+) {
+    let Some(level) = level else {
+        return;
+    };
+    if !level.is_changed() {
+        return;
+    }
+    let Some(LevelOutcome::Completed { stars }) = level.outcome else {
+        return;
+    };
+
+    let Some(profile) = active
+        .slots
+        .first()
+        .and_then(|&index| store.profiles.get_mut(index))
+    else {
+        return;
+    };
+
+    let best = profile.levels.entry(level.level.id.clone()).or_insert(0);
+    if stars > *best {
+        *best = stars;
+    }
+}
@@ -0,0 +1,222 @@
+// Quick play. One key on the join screen starts a match against bots set up
+// the way P1 last played, or with sensible defaults the first time, without
+// going through the map, rules and bot pickers. Whatever a match is started
+// with from the join screen is remembered in P1's profile for next time.
+use crate::brain::BotDifficulty;
+use crate::components::{GridSettings, GridTopology};
+use crate::config::GameConfig;
+use crate::player_bundle;
+use crate::profiles::{ActiveProfiles, Profile, ProfileStore};
+use crate::progression::MAPS;
+use crate::resources::{ActivePreset, GameRules, GameSpeed, RulesPreset};
+use crate::states::AppState;
+use crate::systems::bots::Bot;
+use crate::systems::daily::DailyChallenge;
+use crate::systems::difficulty::BotDifficultySetting;
+use crate::systems::input::{DirectionIntent, InputDevice, InputSource};
+use crate::systems::join::{JoinedPlayers, SelectedMap};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Setup a quick play match starts with, kept per profile
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuickPlaySetup {
+    // Name of the map in `progression::MAPS`, None for whatever is loaded
+    pub map: Option<String>,
+    // None for the rules in the config
+    pub preset: Option<RulesPreset>,
+    pub game_speed: GameSpeed,
+    pub topology: GridTopology,
+    pub bots: usize,
+    // None to go with the calibrated difficulty
+    pub difficulty: Option<BotDifficulty>,
+}
+
+impl Default for QuickPlaySetup {
+    fn default() -> Self {
+        Self {
+            map: None,
+            preset: Some(RulesPreset::Classic),
+            game_speed: GameSpeed::Normal,
+            topology: GridTopology::Clamp,
+            bots: 3,
+            difficulty: None,
+        }
+    }
+}
+
+impl QuickPlaySetup {
+    // The profile's last setup, or the defaults before they've played
+    pub fn for_profile(profile: Option<&Profile>) -> Self {
+        profile
+            .and_then(|profile| profile.quick_play.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn describe(&self, difficulty: &BotDifficultySetting) -> String {
+        let difficulty = self.difficulty.unwrap_or(difficulty.calibration.difficulty);
+        format!(
+            "Q for quick play: {}, {} rules, {} speed, {} {} bot{}",
+            self.map.as_deref().unwrap_or("current map"),
+            ActivePreset(self.preset).name(),
+            self.game_speed.name(),
+            self.bots,
+            difficulty.name(),
+            if self.bots == 1 { "" } else { "s" }
+        )
+    }
+}
+
+// Bots to add to the match, while one started with quick play is on
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct QuickPlay {
+    pub bots: usize,
+}
+
+// Profile P1 plays as, or would once they join
+fn first_profile<'a>(active: &ActiveProfiles, store: &'a ProfileStore) -> Option<&'a Profile> {
+    active.profile(store, 0).or_else(|| {
+        active
+            .next_free(store, 0)
+            .and_then(|index| store.profiles.get(index))
+    })
+}
+
+// Q puts P1's last setup back and starts the match, joining P1 on WASD if
+// nobody has joined yet
+#[allow(clippy::too_many_arguments)]
+pub fn quick_play_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    store: Res<ProfileStore>,
+    active: Res<ActiveProfiles>,
+    config: Res<GameConfig>,
+    mut joined: ResMut<JoinedPlayers>,
+    mut selected: ResMut<SelectedMap>,
+    mut preset: ResMut<ActivePreset>,
+    mut rules: ResMut<GameRules>,
+    mut grid_settings: ResMut<GridSettings>,
+    mut difficulty: ResMut<BotDifficultySetting>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyQ) {
+        return;
+    }
+
+    let setup = QuickPlaySetup::for_profile(first_profile(&active, &store));
+    if let Some(index) = setup
+        .map
+        .as_ref()
+        .and_then(|name| MAPS.iter().position(|map| map.name == *name))
+    {
+        selected.0 = Some(index);
+    }
+    preset.0 = setup.preset;
+    let base = setup
+        .preset
+        .map_or_else(|| config.rules.clone(), RulesPreset::rules);
+    *rules = GameRules {
+        game_speed: setup.game_speed,
+        ..base
+    };
+    grid_settings.topology = setup.topology;
+    difficulty.chosen = setup.difficulty;
+
+    if joined.devices.is_empty() {
+        joined.devices.push(InputDevice::KeyboardWasd);
+    }
+    // Not a replay of today's challenge, if that was played last
+    commands.remove_resource::<DailyChallenge>();
+    commands.insert_resource(QuickPlay { bots: setup.bots });
+    println!("{}", setup.describe(&difficulty));
+    next_state.set(AppState::Playing);
+}
+
+// Adds the bots to a match started with quick play, after the local players
+pub fn start_quick_play(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    joined: Res<JoinedPlayers>,
+    difficulty: Res<BotDifficultySetting>,
+    quick_play: Option<Res<QuickPlay>>,
+) {
+    let Some(quick_play) = quick_play else {
+        return;
+    };
+    let difficulty = difficulty.difficulty();
+    for slot in joined.devices.len()..joined.devices.len() + quick_play.bots {
+        commands.spawn((
+            player_bundle(&grid_settings, slot),
+            InputSource::External,
+            DirectionIntent::default(),
+            Bot::with_difficulty(difficulty),
+        ));
+    }
+    println!(
+        "Quick play against {} {} bots",
+        quick_play.bots,
+        difficulty.name()
+    );
+}
+
+// Back on the join screen, the next match is set up as usual
+pub fn end_quick_play(mut commands: Commands) {
+    commands.remove_resource::<QuickPlay>();
+}
+
+// Saves the setup a match from the join screen starts with as P1's quick
+// play setup. Matches started without quick play keep the bot count from
+// the last one that was.
+#[allow(clippy::too_many_arguments)]
+pub fn remember_quick_play_system(
+    active: Res<ActiveProfiles>,
+    mut store: ResMut<ProfileStore>,
+    selected: Res<SelectedMap>,
+    preset: Res<ActivePreset>,
+    rules: Res<GameRules>,
+    grid_settings: Res<GridSettings>,
+    difficulty: Res<BotDifficultySetting>,
+    quick_play: Option<Res<QuickPlay>>,
+) {
+    let Some(profile) = active.profile(&store, 0) else {
+        return;
+    };
+
+    let last = QuickPlaySetup::for_profile(Some(profile));
+    let setup = QuickPlaySetup {
+        map: selected
+            .0
+            .and_then(|index| MAPS.get(index))
+            .map(|map| map.name.to_string()),
+        preset: preset.0,
+        game_speed: rules.game_speed,
+        topology: grid_settings.topology,
+        bots: quick_play.map_or(last.bots, |quick_play| quick_play.bots),
+        difficulty: difficulty.chosen,
+    };
+    // Only written when it's changed, so starting the same match again
+    // doesn't save the profiles
+    if profile.quick_play.as_ref() != Some(&setup) {
+        store.profiles[active.slots[0]].quick_play = Some(setup);
+    }
+}
+
+// Line on the join screen saying what quick play will start
+#[derive(Component)]
+pub struct QuickPlayText;
+
+pub fn update_quick_play_text_system(
+    store: Res<ProfileStore>,
+    active: Res<ActiveProfiles>,
+    difficulty: Res<BotDifficultySetting>,
+    mut text_query: Query<&mut Text, With<QuickPlayText>>,
+) {
+    if !store.is_changed() && !active.is_changed() && !difficulty.is_changed() {
+        return;
+    }
+    let setup = QuickPlaySetup::for_profile(first_profile(&active, &store));
+    for mut text in text_query.iter_mut() {
+        text.0 = setup.describe(&difficulty);
+    }
+}
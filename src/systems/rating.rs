@@ -0,0 +1,117 @@
+use crate::components::Player;
+use crate::events::{MatchEndedEvent, Standing};
+use crate::profiles::{ActiveProfiles, ProfileStore};
+use crate::systems::bots::Bot;
+use crate::systems::input::InputSource;
+use crate::systems::join::JoinedPlayers;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Where new players start, and the fixed strength bots are assumed to play at
+pub const STARTING_RATING: f32 = 1000.0;
+pub const BOT_RATING: f32 = 1000.0;
+// Most a single match can move a rating by
+const K_FACTOR: f32 = 32.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rating {
+    pub value: f32,
+    pub matches: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            value: STARTING_RATING,
+            matches: 0,
+        }
+    }
+}
+
+// Chance of beating an opponent, standard Elo curve
+pub fn expected_score(rating: f32, opponent: f32) -> f32 {
+    1.0 / (1.0 + 10f32.powf((opponent - rating) / 400.0))
+}
+
+// Free-for-all Elo: every pair of players counts as a head-to-head decided by
+// placement, with K split across the field so bigger matches don't swing more.
+// Takes (rating, placement) per player and returns each player's change.
+pub fn rating_changes(field: &[(f32, usize)]) -> Vec<f32> {
+    if field.len() < 2 {
+        return vec![0.0; field.len()];
+    }
+
+    let k = K_FACTOR / (field.len() - 1) as f32;
+    field
+        .iter()
+        .enumerate()
+        .map(|(i, &(rating, placement))| {
+            field
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, &(opponent, opponent_placement))| {
+                    let actual = match placement.cmp(&opponent_placement) {
+                        std::cmp::Ordering::Less => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Greater => 0.0,
+                    };
+                    k * (actual - expected_score(rating, opponent))
+                })
+                .sum()
+        })
+        .collect()
+}
+
+// Rates local players' profiles on how they placed against everyone else in
+// the match
+pub fn update_ratings_system(
+    mut match_end_events: EventReader<MatchEndedEvent>,
+    joined: Res<JoinedPlayers>,
+    active: Res<ActiveProfiles>,
+    mut store: ResMut<ProfileStore>,
+    player_query: Query<(Option<&InputSource>, Has<Bot>), With<Player>>,
+) {
+    for event in match_end_events.read() {
+        // Profile of each standing, None for bots and other non-local players
+        let profiles: Vec<Option<usize>> = event
+            .standings
+            .iter()
+            .map(|standing| match player_query.get(standing.player) {
+                Ok((Some(InputSource::Device(device)), false)) => joined
+                    .slot_of(*device)
+                    .and_then(|slot| active.slots.get(slot).copied())
+                    .filter(|&index| index < store.profiles.len()),
+                _ => None,
+            })
+            .collect();
+
+        if profiles.iter().all(Option::is_none) {
+            continue;
+        }
+
+        let field: Vec<(f32, usize)> = event
+            .standings
+            .iter()
+            .zip(&profiles)
+            .map(|(&Standing { placement, .. }, profile)| match profile {
+                Some(index) => (store.profiles[*index].rating.value, placement),
+                None => (BOT_RATING, placement),
+            })
+            .collect();
+
+        for (change, profile) in rating_changes(&field).into_iter().zip(&profiles) {
+            let Some(index) = *profile else {
+                continue;
+            };
+            let profile = &mut store.profiles[index];
+            profile.rating.value += change;
+            profile.rating.matches += 1;
+            println!(
+                "{} rating {:.0} ({:+.0})",
+                profile.name, profile.rating.value, change
+            );
+        }
+    }
+}
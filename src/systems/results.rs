@@ -0,0 +1,221 @@
+use crate::components::{GridSettings, Player};
+use crate::events::MatchEndedEvent;
+use crate::resources::{ActivePreset, GameRules, MatchSeed};
+use crate::systems::cinematic::CameraTween;
+use crate::systems::daily::DailyChallenge;
+use crate::systems::join::{current_share_code, SelectedMap};
+use crate::systems::puzzle::ActiveLevel;
+use crate::systems::stats::MatchStats;
+use bevy::prelude::*;
+
+const GRAPH_WIDTH: f32 = 360.0;
+const GRAPH_HEIGHT: f32 = 160.0;
+const LINE_WIDTH: f32 = 2.0;
+
+// End of match overlay with the standings and the territory timeline
+#[derive(Component)]
+pub struct ResultsScreen;
+
+// Results of a match that just ended, held back until the camera's done
+#[derive(Resource, Default)]
+pub struct PendingResults(pub Option<MatchEndedEvent>);
+
+// The setup's share code goes along the bottom, so a good match can be
+// passed on. Puzzle levels and the daily challenge can't be.
+#[allow(clippy::too_many_arguments)]
+pub fn show_results_system(
+    mut commands: Commands,
+    mut match_end_events: EventReader<MatchEndedEvent>,
+    mut pending: ResMut<PendingResults>,
+    tween: Res<CameraTween>,
+    stats: Res<MatchStats>,
+    selected: Res<SelectedMap>,
+    grid_settings: Res<GridSettings>,
+    seed: Res<MatchSeed>,
+    rules: Res<GameRules>,
+    preset: Res<ActivePreset>,
+    level: Option<Res<ActiveLevel>>,
+    challenge: Option<Res<DailyChallenge>>,
+    player_query: Query<&Player>,
+) {
+    if let Some(event) = match_end_events.read().last() {
+        pending.0 = Some(event.clone());
+    }
+    if tween.active() {
+        return;
+    }
+    let Some(event) = pending.0.take() else {
+        return;
+    };
+
+    let code = (level.is_none() && challenge.is_none())
+        .then(|| current_share_code(&selected, &grid_settings, *seed, &rules))
+        .flatten();
+    // Leave some headroom above the best line
+    let peak = (stats.peak_share() * 1.1).max(0.05);
+    let last_sample = stats.samples.len().saturating_sub(1).max(1) as f32;
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            ResultsScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((Text::new("Results"), TextFont::from_font_size(32.0)));
+            screen.spawn((
+                Text::new(format!("{} rules", preset.name())),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+
+            for standing in event.standings.iter() {
+                let color = player_query
+                    .get(standing.player)
+                    .map_or(Color::WHITE, |player| player.color);
+                screen.spawn((
+                    Text::new(format!(
+                        "#{} - {} points, {} kills, {} assists",
+                        standing.placement + 1,
+                        standing.score,
+                        stats.kills(standing.player),
+                        stats.assists(standing.player)
+                    )),
+                    TextFont::from_font_size(18.0),
+                    TextColor(color),
+                ));
+            }
+
+            // Awards, each with its badge and who won it
+            for award in event.awards.iter() {
+                let color = player_query
+                    .get(award.player)
+                    .map_or(Color::WHITE, |player| player.color);
+                let (glyph, badge_color) = award.award.badge();
+                screen
+                    .spawn(Node {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.0),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            Node {
+                                width: Val::Px(20.0),
+                                height: Val::Px(20.0),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(badge_color),
+                        ))
+                        .with_children(|badge| {
+                            badge.spawn((
+                                Text::new(glyph),
+                                TextFont::from_font_size(14.0),
+                                TextColor(Color::BLACK),
+                            ));
+                        });
+                        row.spawn((
+                            Text::new(format!(
+                                "{} - {}",
+                                award.award.name(),
+                                award.award.describe(award.amount)
+                            )),
+                            TextFont::from_font_size(16.0),
+                            TextColor(color),
+                        ));
+                    });
+            }
+
+            screen.spawn((
+                Text::new(format!("Territory over time (peak {:.0}%)", peak * 100.0)),
+                TextFont::from_font_size(14.0),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+
+            // A line per player, joining up their samples
+            screen
+                .spawn((
+                    Node {
+                        width: Val::Px(GRAPH_WIDTH),
+                        height: Val::Px(GRAPH_HEIGHT),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.1)),
+                ))
+                .with_children(|graph| {
+                    for standing in event.standings.iter() {
+                        let color = player_query
+                            .get(standing.player)
+                            .map_or(Color::WHITE, |player| player.color);
+
+                        let points: Vec<Vec2> = stats
+                            .territory_timeline(standing.player)
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, share)| {
+                                Vec2::new(
+                                    i as f32 / last_sample * GRAPH_WIDTH,
+                                    share / peak * GRAPH_HEIGHT,
+                                )
+                            })
+                            .collect();
+
+                        // Each segment is a thin bar laid between two samples
+                        // and turned to meet both
+                        for pair in points.windows(2) {
+                            let (from, to) = (pair[0], pair[1]);
+                            let middle = (from + to) / 2.0;
+                            let length = from.distance(to);
+                            graph.spawn((
+                                Node {
+                                    position_type: PositionType::Absolute,
+                                    left: Val::Px(middle.x - length / 2.0),
+                                    bottom: Val::Px(middle.y - LINE_WIDTH / 2.0),
+                                    width: Val::Px(length),
+                                    height: Val::Px(LINE_WIDTH),
+                                    ..default()
+                                },
+                                // UI space runs downwards, so the turn is
+                                // the other way round
+                                Transform::from_rotation(Quat::from_rotation_z(
+                                    -(to - from).to_angle(),
+                                )),
+                                BackgroundColor(color),
+                            ));
+                        }
+                    }
+                });
+
+            if let Some(code) = code {
+                screen.spawn((
+                    Text::new(format!("Share code: {}", code)),
+                    TextFont::from_font_size(14.0),
+                    TextColor(Color::srgb(0.7, 0.7, 0.7)),
+                ));
+            }
+        });
+}
+
+pub fn cleanup_results_screen(
+    mut commands: Commands,
+    mut pending: ResMut<PendingResults>,
+    mut tween: ResMut<CameraTween>,
+    screen_query: Query<Entity, With<ResultsScreen>>,
+) {
+    pending.0 = None;
+    tween.stop();
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
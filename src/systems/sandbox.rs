@@ -0,0 +1,634 @@
+use crate::components::{
+    GridSettings, LocalPlayer, MainCamera, Player, Respawning, Tile, TileColor, Tiles,
+};
+use crate::events::{PlaySoundEvent, SoundEffect};
+use crate::grid::GridMath;
+use crate::levels::LevelTiles;
+use crate::player_bundle;
+use crate::resources::{DeathPenalty, GameRules, PendingClaims};
+use crate::systems::bots::Bot;
+use crate::systems::display::DisplaySettings;
+use crate::systems::input::{DirectionIntent, InputSource};
+use crate::systems::join::JoinedPlayers;
+use crate::systems::puzzle::{LevelEnemy, ENEMY_COLOR};
+use crate::territory::land_color;
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_SANDBOX_BOTS: usize = 6;
+const MIN_SPEED: f32 = 1.0;
+const MAX_SPEED: f32 = 12.0;
+// Decay interval used when the decay rule is switched on from the panel
+const SANDBOX_DECAY_INTERVAL: f32 = 10.0;
+
+// Knobs for the practice sandbox, changed from its panel
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxSettings {
+    pub player_speed: f32,
+    pub bot_count: usize,
+    #[serde(skip)]
+    pub painting: bool,
+    // Player colors in joining order, bots after the local players. Anyone
+    // past the end keeps their usual color.
+    pub colors: Vec<Color>,
+}
+
+impl Default for SandboxSettings {
+    fn default() -> Self {
+        Self {
+            player_speed: 5.0,
+            bot_count: 0,
+            painting: false,
+            colors: Vec::new(),
+        }
+    }
+}
+
+// Map edits requested from the sandbox panel
+#[derive(Event)]
+pub enum SandboxEvent {
+    // Wipe every tile and put players back on fresh starting territory
+    ClearMap,
+    // Hand a tile to the first local player
+    Paint { tile: (i32, i32) },
+    // Clear the map and lay a level's enemy land and trails out on it
+    Layout(LevelTiles),
+}
+
+fn reset_tile(tile: &mut Tile, tile_color: &mut TileColor) {
+    tile.owner = None;
+    tile.trail_owner = None;
+
+    // Back to the checkerboard, or void
+    tile_color.0 = land_color(tile, None);
+}
+
+pub fn sandbox_speed_system(settings: Res<SandboxSettings>, mut player_query: Query<&mut Player>) {
+    for mut player in player_query.iter_mut() {
+        if settings.is_changed() || player.is_added() {
+            player.speed = settings.player_speed;
+        }
+    }
+}
+
+// Spawns or removes bots until there are as many as the slider asks for
+pub fn sandbox_bot_count_system(
+    mut commands: Commands,
+    settings: Res<SandboxSettings>,
+    grid_settings: Res<GridSettings>,
+    joined: Res<JoinedPlayers>,
+    bot_query: Query<Entity, With<Bot>>,
+    mut tiles: ResMut<Tiles>,
+) {
+    let bots: Vec<Entity> = bot_query.iter().collect();
+
+    for slot in bots.len()..settings.bot_count {
+        commands.spawn((
+            player_bundle(&grid_settings, joined.devices.len() + slot),
+            InputSource::External,
+            DirectionIntent::default(),
+            Bot::default(),
+        ));
+    }
+
+    for &bot in bots.iter().skip(settings.bot_count) {
+        commands.entity(bot).despawn_recursive();
+        for (tile, tile_color) in tiles.iter_mut() {
+            if tile.owner == Some(bot) || tile.trail_owner == Some(bot) {
+                reset_tile(tile, tile_color);
+            }
+        }
+    }
+}
+
+// Nobody waits to respawn in the sandbox
+pub fn sandbox_instant_respawn_system(mut query: Query<&mut Respawning>) {
+    for mut respawning in query.iter_mut() {
+        let duration = respawning.timer.duration();
+        respawning.timer.set_elapsed(duration);
+    }
+}
+
+// Players the sandbox panel moves around, and whether they're local
+pub type SandboxPlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Player,
+        &'static mut Transform,
+        Has<LocalPlayer>,
+    ),
+>;
+
+// Wipes every tile and puts players back on fresh starting territory
+fn clear_map(
+    grid: &GridMath,
+    player_query: &mut SandboxPlayerQuery,
+    tiles: &mut Tiles,
+    pending_claims: &mut PendingClaims,
+) {
+    // Claims still being computed were for the map that's gone
+    pending_claims.tasks.clear();
+    pending_claims.cancelled.clear();
+
+    for (tile, tile_color) in tiles.iter_mut() {
+        reset_tile(tile, tile_color);
+    }
+
+    for (entity, mut player, mut transform, _) in player_query.iter_mut() {
+        let (spawn_x, spawn_y) = player.spawn_tile;
+
+        let mut spawn_value = 0;
+        for (tile, tile_color) in tiles.iter_mut() {
+            if (tile.x - spawn_x).abs() <= 2 && (tile.y - spawn_y).abs() <= 2 && !tile.void {
+                tile.owner = Some(entity);
+                tile_color.0 = player.color.with_alpha(0.5);
+                spawn_value += tile.value;
+            }
+        }
+
+        let spawn = grid.center_of(spawn_x, spawn_y);
+        transform.translation.x = spawn.x;
+        transform.translation.y = spawn.y;
+        player.direction = Vec2::ZERO;
+        player.buffered_direction = None;
+        player.is_drawing_trail = false;
+        player.retracing = false;
+        player.progress = 0.0;
+        player.last_tile_pos = (spawn_x, spawn_y);
+        player.bonus = 0;
+        player.score = spawn_value;
+    }
+}
+
+pub fn sandbox_event_system(
+    mut commands: Commands,
+    mut sandbox_events: EventReader<SandboxEvent>,
+    grid_settings: Res<GridSettings>,
+    mut player_query: SandboxPlayerQuery,
+    mut tiles: ResMut<Tiles>,
+    mut pending_claims: ResMut<PendingClaims>,
+    enemy_query: Query<Entity, With<LevelEnemy>>,
+) {
+    let grid = GridMath::new(&grid_settings);
+
+    for event in sandbox_events.read() {
+        match event {
+            SandboxEvent::ClearMap => {
+                clear_map(&grid, &mut player_query, &mut tiles, &mut pending_claims)
+            }
+            SandboxEvent::Paint { tile: (x, y) } => {
+                let Some((entity, mut player, _, _)) = player_query
+                    .iter_mut()
+                    .find(|(_, _, _, is_local)| *is_local)
+                else {
+                    continue;
+                };
+
+                let Some((tile, tile_color)) = tiles.get_mut(*x, *y) else {
+                    continue;
+                };
+                if tile.owner == Some(entity) {
+                    continue;
+                }
+                let previous = tile.owner;
+                tile.owner = Some(entity);
+                tile.trail_owner = None;
+                tile_color.0 = player.color.with_alpha(0.5);
+                player.score += tile.value;
+
+                // Painted over someone else's land, which they lose the points for
+                let value = tile.value;
+                if let Some(Ok((_, mut previous, _, _))) =
+                    previous.map(|previous| player_query.get_mut(previous))
+                {
+                    previous.score = previous.score.saturating_sub(value);
+                }
+            }
+            SandboxEvent::Layout(layout) => {
+                for entity in enemy_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+                if let Some(start) = layout.start {
+                    for (_, mut player, _, is_local) in player_query.iter_mut() {
+                        if is_local {
+                            player.spawn_tile = start;
+                        }
+                    }
+                }
+                clear_map(&grid, &mut player_query, &mut tiles, &mut pending_claims);
+
+                // Players' starting land stays theirs
+                let enemy = commands.spawn(LevelEnemy).id();
+                for (tile, tile_color) in tiles.iter_mut() {
+                    if tile.owner.is_some() {
+                        continue;
+                    }
+                    let position = (tile.x, tile.y);
+                    if layout.enemy_land.contains(&position) {
+                        tile.owner = Some(enemy);
+                        tile_color.0 = ENEMY_COLOR.with_alpha(0.5);
+                    } else if layout.enemy_trail.contains(&position) {
+                        tile.trail_owner = Some(enemy);
+                        tile_color.0 = ENEMY_COLOR.with_alpha(0.8);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Repaints players and their land in the colors the settings ask for, once
+// they change and whenever someone joins
+pub fn sandbox_color_system(
+    settings: Res<SandboxSettings>,
+    joined: Res<JoinedPlayers>,
+    mut player_query: Query<(Entity, &mut Player, &mut Sprite, &InputSource)>,
+    mut tiles: ResMut<Tiles>,
+) {
+    let added = player_query
+        .iter_mut()
+        .any(|(_, player, _, _)| player.is_added());
+    if settings.colors.is_empty() || !(settings.is_changed() || added) {
+        return;
+    }
+
+    // Local players by slot, bots in the order they were spawned
+    let mut players: Vec<_> = player_query
+        .iter()
+        .map(|(entity, _, _, source)| {
+            let slot = match source {
+                InputSource::Device(device) => joined.slot_of(*device),
+                _ => None,
+            };
+            (slot.unwrap_or(usize::MAX), entity.index(), entity)
+        })
+        .collect();
+    players.sort();
+
+    for (&(_, _, entity), &color) in players.iter().zip(settings.colors.iter()) {
+        let Ok((_, mut player, mut sprite, _)) = player_query.get_mut(entity) else {
+            continue;
+        };
+        if player.color == color {
+            continue;
+        }
+        player.color = color;
+        sprite.color = color;
+        for (tile, tile_color) in tiles.iter_mut() {
+            if tile.trail_owner == Some(entity) {
+                tile_color.0 = color.with_alpha(0.8);
+            } else if tile.owner == Some(entity) {
+                tile_color.0 = color.with_alpha(0.5);
+            }
+        }
+    }
+}
+
+// Sandbox panel
+
+#[derive(Component)]
+pub struct SandboxPanel;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SandboxSliderKind {
+    Speed,
+    Bots,
+}
+
+#[derive(Component)]
+pub struct SandboxSlider {
+    pub kind: SandboxSliderKind,
+}
+
+#[derive(Component)]
+pub struct SandboxSliderFill {
+    pub kind: SandboxSliderKind,
+}
+
+#[derive(Component)]
+pub struct SandboxValueText {
+    pub kind: SandboxSliderKind,
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SandboxButton {
+    Penalty,
+    Decay,
+    ClearMap,
+    Paint,
+}
+
+const PANEL_COLOR: Color = Color::srgba(0.1, 0.1, 0.1, 0.85);
+const TRACK_COLOR: Color = Color::srgb(0.3, 0.3, 0.3);
+const FILL_COLOR: Color = Color::srgb(0.9, 0.7, 0.2);
+
+impl SandboxSliderKind {
+    fn label(self) -> &'static str {
+        match self {
+            SandboxSliderKind::Speed => "Speed",
+            SandboxSliderKind::Bots => "Bots",
+        }
+    }
+
+    // Position of the current value along the track, 0.0 - 1.0
+    fn fraction(self, settings: &SandboxSettings) -> f32 {
+        match self {
+            SandboxSliderKind::Speed => {
+                (settings.player_speed - MIN_SPEED) / (MAX_SPEED - MIN_SPEED)
+            }
+            SandboxSliderKind::Bots => settings.bot_count as f32 / MAX_SANDBOX_BOTS as f32,
+        }
+    }
+
+    fn value_text(self, settings: &SandboxSettings) -> String {
+        match self {
+            SandboxSliderKind::Speed => format!("{:.1}", settings.player_speed),
+            SandboxSliderKind::Bots => settings.bot_count.to_string(),
+        }
+    }
+
+    fn set(self, settings: &mut SandboxSettings, fraction: f32) {
+        let fraction = fraction.clamp(0.0, 1.0);
+        match self {
+            SandboxSliderKind::Speed => {
+                let speed = MIN_SPEED + fraction * (MAX_SPEED - MIN_SPEED);
+                settings.player_speed = (speed * 2.0).round() / 2.0;
+            }
+            SandboxSliderKind::Bots => {
+                settings.bot_count = (fraction * MAX_SANDBOX_BOTS as f32).round() as usize;
+            }
+        }
+    }
+}
+
+fn penalty_label(penalty: &DeathPenalty) -> &'static str {
+    match penalty {
+        DeathPenalty::FullReset => "Full reset",
+        DeathPenalty::TrailOnly => "Trail only",
+        DeathPenalty::ShrinkTerritory { .. } => "Shrink half",
+        DeathPenalty::ErodeRings { .. } => "Erode 2 rings",
+    }
+}
+
+fn next_penalty(penalty: &DeathPenalty) -> DeathPenalty {
+    match penalty {
+        DeathPenalty::FullReset => DeathPenalty::TrailOnly,
+        DeathPenalty::TrailOnly => DeathPenalty::ShrinkTerritory { fraction: 0.5 },
+        DeathPenalty::ShrinkTerritory { .. } => DeathPenalty::ErodeRings { rings: 2 },
+        DeathPenalty::ErodeRings { .. } => DeathPenalty::FullReset,
+    }
+}
+
+impl SandboxButton {
+    fn text(self, settings: &SandboxSettings, rules: &GameRules) -> String {
+        match self {
+            SandboxButton::Penalty => format!("Death: {}", penalty_label(&rules.death_penalty)),
+            SandboxButton::Decay => match rules.territory_decay_interval {
+                Some(_) => "Decay: on".to_string(),
+                None => "Decay: off".to_string(),
+            },
+            SandboxButton::ClearMap => "Clear map".to_string(),
+            SandboxButton::Paint => match settings.painting {
+                true => "Paint: on".to_string(),
+                false => "Paint: off".to_string(),
+            },
+        }
+    }
+}
+
+pub fn setup_sandbox_panel(
+    mut commands: Commands,
+    settings: Res<SandboxSettings>,
+    rules: Res<GameRules>,
+) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            BackgroundColor(PANEL_COLOR),
+            RelativeCursorPosition::default(),
+            SandboxPanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((Text::new("Sandbox"), TextFont::from_font_size(16.0)));
+
+            for kind in [SandboxSliderKind::Speed, SandboxSliderKind::Bots] {
+                panel
+                    .spawn(Node {
+                        column_gap: Val::Px(8.0),
+                        align_items: AlignItems::Center,
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            Text::new(kind.label()),
+                            TextFont::from_font_size(14.0),
+                            Node {
+                                width: Val::Px(50.0),
+                                ..default()
+                            },
+                        ));
+
+                        row.spawn((
+                            Node {
+                                width: Val::Px(120.0),
+                                height: Val::Px(12.0),
+                                ..default()
+                            },
+                            BackgroundColor(TRACK_COLOR),
+                            Button,
+                            RelativeCursorPosition::default(),
+                            SandboxSlider { kind },
+                        ))
+                        .with_children(|track| {
+                            track.spawn((
+                                Node {
+                                    width: Val::Percent(kind.fraction(&settings) * 100.0),
+                                    height: Val::Percent(100.0),
+                                    ..default()
+                                },
+                                BackgroundColor(FILL_COLOR),
+                                SandboxSliderFill { kind },
+                            ));
+                        });
+
+                        row.spawn((
+                            Text::new(kind.value_text(&settings)),
+                            TextFont::from_font_size(14.0),
+                            SandboxValueText { kind },
+                        ));
+                    });
+            }
+
+            for button in [
+                SandboxButton::Penalty,
+                SandboxButton::Decay,
+                SandboxButton::ClearMap,
+                SandboxButton::Paint,
+            ] {
+                panel
+                    .spawn((
+                        Node {
+                            padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        BackgroundColor(TRACK_COLOR),
+                        Button,
+                        button,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new(button.text(&settings, &rules)),
+                            TextFont::from_font_size(14.0),
+                        ));
+                    });
+            }
+        });
+}
+
+pub fn cleanup_sandbox_panel(
+    mut commands: Commands,
+    panel_query: Query<Entity, With<SandboxPanel>>,
+) {
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn sandbox_slider_system(
+    mut settings: ResMut<SandboxSettings>,
+    slider_query: Query<(&Interaction, &RelativeCursorPosition, &SandboxSlider)>,
+) {
+    for (interaction, cursor, slider) in slider_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        if let Some(position) = cursor.normalized {
+            // Only touch the settings when the value actually moves
+            let mut updated = settings.clone();
+            slider.kind.set(&mut updated, position.x);
+
+            if updated.player_speed != settings.player_speed
+                || updated.bot_count != settings.bot_count
+            {
+                *settings = updated;
+            }
+        }
+    }
+}
+
+pub fn sandbox_button_system(
+    mut settings: ResMut<SandboxSettings>,
+    mut rules: ResMut<GameRules>,
+    mut sandbox_events: EventWriter<SandboxEvent>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+    button_query: Query<(&Interaction, &SandboxButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            SandboxButton::Penalty => rules.death_penalty = next_penalty(&rules.death_penalty),
+            SandboxButton::Decay => {
+                rules.territory_decay_interval = match rules.territory_decay_interval {
+                    Some(_) => None,
+                    None => Some(SANDBOX_DECAY_INTERVAL),
+                };
+            }
+            SandboxButton::ClearMap => {
+                sandbox_events.send(SandboxEvent::ClearMap);
+            }
+            SandboxButton::Paint => settings.painting = !settings.painting,
+        }
+
+        sound_events.send(PlaySoundEvent {
+            sound: SoundEffect::UiClick,
+        });
+    }
+}
+
+// Keeps the panel's fills and labels in sync with the settings and rules
+pub fn update_sandbox_panel_system(
+    settings: Res<SandboxSettings>,
+    rules: Res<GameRules>,
+    mut fill_query: Query<(&mut Node, &SandboxSliderFill)>,
+    mut value_query: Query<(&mut Text, &SandboxValueText)>,
+    button_query: Query<(&SandboxButton, &Children)>,
+    mut text_query: Query<&mut Text, Without<SandboxValueText>>,
+) {
+    if !settings.is_changed() && !rules.is_changed() {
+        return;
+    }
+
+    for (mut node, fill) in fill_query.iter_mut() {
+        node.width = Val::Percent(fill.kind.fraction(&settings) * 100.0);
+    }
+
+    for (mut text, value) in value_query.iter_mut() {
+        text.0 = value.kind.value_text(&settings);
+    }
+
+    for (button, children) in button_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.0 = button.text(&settings, &rules);
+            }
+        }
+    }
+}
+
+// While painting is on, holding the left mouse button over the map hands the
+// tiles under the cursor to the first local player
+#[allow(clippy::too_many_arguments)]
+pub fn sandbox_paint_system(
+    settings: Res<SandboxSettings>,
+    display: Res<DisplaySettings>,
+    grid_settings: Res<GridSettings>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    panel_query: Query<&RelativeCursorPosition, With<SandboxPanel>>,
+    mut sandbox_events: EventWriter<SandboxEvent>,
+) {
+    if !settings.painting || !mouse_input.pressed(MouseButton::Left) {
+        return;
+    }
+
+    // Clicks on the panel itself aren't paint strokes
+    if panel_query.iter().any(|cursor| cursor.mouse_over()) {
+        return;
+    }
+
+    let Some(cursor) = window_query.iter().next().and_then(|w| w.cursor_position()) else {
+        return;
+    };
+    let Some((camera, camera_transform)) = camera_query.iter().next() else {
+        return;
+    };
+    let Ok(world) = camera.viewport_to_world_2d(camera_transform, cursor) else {
+        return;
+    };
+
+    let grid = GridMath::new(&grid_settings);
+    let (x, y) = grid.tile_of(display.projection.unproject(world));
+
+    // Void can't be painted
+    if grid_settings.playable(x, y) {
+        sandbox_events.send(SandboxEvent::Paint { tile: (x, y) });
+    }
+}
@@ -0,0 +1,216 @@
+// Each local player's score in the top left. The number counts up to the
+// real score rather than jumping, points just earned pop up next to it as
+// "+N", and loops closed in a row without dying show as a streak.
+use crate::components::{LocalPlayer, Player};
+use crate::events::{BountyClaimedEvent, TerritoryClaimedEvent};
+use crate::systems::stats::MatchStats;
+use bevy::prelude::*;
+
+// How quickly the shown score catches up with the real one
+const SCORE_EASE: f32 = 8.0;
+// How long a "+N" stays up after the last points that went into it
+const DELTA_SECONDS: f32 = 1.5;
+// Loops in a row before the streak is worth showing
+const MIN_STREAK: u32 = 2;
+
+#[derive(Component)]
+pub struct ScoreHud;
+
+// One local player's line in the HUD
+#[derive(Component)]
+pub struct ScoreHudRow {
+    pub player: Entity,
+}
+
+// The score as shown, on its way to the real one
+#[derive(Component)]
+pub struct ScoreCounter {
+    pub player: Entity,
+    pub shown: f32,
+}
+
+// Points just earned. Points coming in while it's still up add to it.
+#[derive(Component)]
+pub struct ScoreDelta {
+    pub player: Entity,
+    pub points: u32,
+    pub timer: Timer,
+}
+
+#[derive(Component)]
+pub struct StreakCounter {
+    pub player: Entity,
+}
+
+pub fn setup_score_hud(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            top: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        PickingBehavior::IGNORE,
+        ScoreHud,
+    ));
+}
+
+pub fn cleanup_score_hud(mut commands: Commands, hud_query: Query<Entity, With<ScoreHud>>) {
+    for entity in hud_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// A line for each local player, in the order they joined, and none for
+// players who are gone
+pub fn sync_score_hud_system(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<ScoreHud>>,
+    row_query: Query<(Entity, &ScoreHudRow)>,
+    local_query: Query<(Entity, &Player), With<LocalPlayer>>,
+) {
+    let Ok(hud) = hud_query.get_single() else {
+        return;
+    };
+
+    for (entity, row) in row_query.iter() {
+        if !local_query.contains(row.player) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let mut missing: Vec<(Entity, &Player)> = local_query
+        .iter()
+        .filter(|&(player, _)| !row_query.iter().any(|(_, row)| row.player == player))
+        .collect();
+    missing.sort_by_key(|&(player, _)| player);
+    for (player, player_data) in missing {
+        let row = commands
+            .spawn((
+                Node {
+                    column_gap: Val::Px(8.0),
+                    align_items: AlignItems::Baseline,
+                    ..default()
+                },
+                ScoreHudRow { player },
+            ))
+            .with_children(|row| {
+                row.spawn((
+                    Text::new(format!("Score: {}", player_data.score)),
+                    TextFont::from_font_size(20.0),
+                    TextColor(player_data.color.with_alpha(1.0)),
+                    ScoreCounter {
+                        player,
+                        shown: player_data.score as f32,
+                    },
+                ));
+                row.spawn((
+                    Text::new(""),
+                    TextFont::from_font_size(16.0),
+                    TextColor(Color::WHITE),
+                    ScoreDelta {
+                        player,
+                        points: 0,
+                        timer: Timer::from_seconds(DELTA_SECONDS, TimerMode::Once),
+                    },
+                ));
+                row.spawn((
+                    Text::new(""),
+                    TextFont::from_font_size(16.0),
+                    TextColor(Color::srgb(1.0, 0.8, 0.2)),
+                    StreakCounter { player },
+                ));
+            })
+            .id();
+        commands.entity(hud).add_child(row);
+    }
+}
+
+// Eases each counter towards its player's score, snapping once it's close
+pub fn tween_score_counters_system(
+    time: Res<Time>,
+    player_query: Query<&Player>,
+    mut counter_query: Query<(&mut ScoreCounter, &mut Text)>,
+) {
+    let blend = (SCORE_EASE * time.delta_secs()).min(1.0);
+    for (mut counter, mut text) in counter_query.iter_mut() {
+        let Ok(player) = player_query.get(counter.player) else {
+            continue;
+        };
+        let target = player.score as f32;
+        if counter.shown == target {
+            continue;
+        }
+        counter.shown += (target - counter.shown) * blend;
+        if (target - counter.shown).abs() < 0.5 {
+            counter.shown = target;
+        }
+        text.0 = format!("Score: {}", counter.shown.round() as u32);
+    }
+}
+
+// Puts up "+N" for claims and bounties, fading it out once nothing more
+// comes in
+pub fn show_score_deltas_system(
+    time: Res<Time>,
+    mut claimed_events: EventReader<TerritoryClaimedEvent>,
+    mut bounty_events: EventReader<BountyClaimedEvent>,
+    mut delta_query: Query<(&mut ScoreDelta, &mut Text, &mut TextColor)>,
+) {
+    let earned: Vec<(Entity, u32)> = claimed_events
+        .read()
+        .map(|event| (event.player, event.points))
+        .chain(
+            bounty_events
+                .read()
+                .map(|event| (event.killer, event.points)),
+        )
+        .filter(|&(_, points)| points > 0)
+        .collect();
+
+    for (mut delta, mut text, mut color) in delta_query.iter_mut() {
+        let points: u32 = earned
+            .iter()
+            .filter(|&&(player, _)| player == delta.player)
+            .map(|&(_, points)| points)
+            .sum();
+        if points > 0 {
+            if delta.timer.finished() {
+                delta.points = 0;
+            }
+            delta.points += points;
+            delta.timer.reset();
+            text.0 = format!("+{}", delta.points);
+        } else if delta.timer.finished() {
+            continue;
+        }
+
+        delta.timer.tick(time.delta());
+        if delta.timer.finished() {
+            text.0.clear();
+        }
+        color.0 = color.0.with_alpha(1.0 - delta.timer.fraction());
+    }
+}
+
+pub fn update_streak_counters_system(
+    stats: Res<MatchStats>,
+    mut streak_query: Query<(&StreakCounter, &mut Text)>,
+) {
+    if !stats.is_changed() {
+        return;
+    }
+    for (counter, mut text) in streak_query.iter_mut() {
+        let streak = stats.claim_streak(counter.player);
+        let label = if streak >= MIN_STREAK {
+            format!("{} loop streak", streak)
+        } else {
+            String::new()
+        };
+        if text.0 != label {
+            text.0 = label;
+        }
+    }
+}
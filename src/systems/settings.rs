@@ -0,0 +1,548 @@
+// Settings screen, toggled with F2. Everything on it applies as soon as it's
+// changed and is saved to the config, risky display changes only once
+// they've been kept.
+use crate::config::GameConfig;
+use crate::events::{PlaySoundEvent, SoundEffect};
+use crate::paths::Paths;
+use crate::systems::accessibility::{AccessibilityButton, AccessibilitySettings};
+use crate::systems::audio::{AudioBus, AudioMixer};
+use crate::systems::camera::CameraMode;
+use crate::systems::coach::CoachOverlay;
+use crate::systems::display::{DisplayButton, DisplaySettings};
+use crate::systems::input::{key_name, BindDirection, ControlSettings, InputDevice};
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+
+// Root of the settings screen
+#[derive(Component)]
+pub struct SettingsPanel;
+
+// Which page of the settings is showing
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SettingsTab {
+    #[default]
+    Video,
+    Audio,
+    Controls,
+    Gameplay,
+    Accessibility,
+}
+
+impl SettingsTab {
+    pub const ALL: [SettingsTab; 5] = [
+        SettingsTab::Video,
+        SettingsTab::Audio,
+        SettingsTab::Controls,
+        SettingsTab::Gameplay,
+        SettingsTab::Accessibility,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SettingsTab::Video => "Video",
+            SettingsTab::Audio => "Audio",
+            SettingsTab::Controls => "Controls",
+            SettingsTab::Gameplay => "Gameplay",
+            SettingsTab::Accessibility => "Accessibility",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SettingsTabButton(pub SettingsTab);
+
+// Everything on one page, hidden unless its tab is picked
+#[derive(Component)]
+pub struct SettingsPage(pub SettingsTab);
+
+// Clickable track of a volume slider
+#[derive(Component)]
+pub struct VolumeSlider {
+    pub bus: AudioBus,
+}
+
+// Filled part of a volume slider, its width is the bus volume
+#[derive(Component)]
+pub struct VolumeSliderFill {
+    pub bus: AudioBus,
+}
+
+#[derive(Component)]
+pub struct MuteButton;
+
+// One key of a keyboard layout, click then press the new key
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RebindButton {
+    pub device: InputDevice,
+    pub direction: BindDirection,
+}
+
+#[derive(Component)]
+pub struct ResetControlsButton;
+
+// Key waiting for a new binding
+#[derive(Resource, Default)]
+pub struct Rebinding(pub Option<RebindButton>);
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum GameplayButton {
+    CameraMode,
+    CoachOverlay,
+}
+
+impl GameplayButton {
+    pub const ALL: [GameplayButton; 2] = [GameplayButton::CameraMode, GameplayButton::CoachOverlay];
+
+    pub fn label(self, camera: CameraMode, coach: &CoachOverlay) -> String {
+        match self {
+            GameplayButton::CameraMode => format!(
+                "Camera: {} (V)",
+                match camera {
+                    CameraMode::Overview => "overview",
+                    CameraMode::Chase => "chase",
+                }
+            ),
+            GameplayButton::CoachOverlay => format!(
+                "Coach overlay: {} (F3)",
+                if coach.enabled { "on" } else { "off" }
+            ),
+        }
+    }
+}
+
+// Keyboard layouts that can be rebound, as named on the join screen
+const LAYOUTS: [(InputDevice, &str); 2] = [
+    (InputDevice::KeyboardWasd, "Keyboard 1"),
+    (InputDevice::KeyboardArrows, "Keyboard 2"),
+];
+
+const PANEL_COLOR: Color = Color::srgba(0.1, 0.1, 0.1, 0.92);
+const TRACK_COLOR: Color = Color::srgb(0.3, 0.3, 0.3);
+const FILL_COLOR: Color = Color::srgb(0.2, 0.7, 0.9);
+const TAB_COLOR: Color = Color::srgb(0.2, 0.2, 0.2);
+
+fn spawn_setting_button(parent: &mut ChildBuilder, label: String, marker: impl Bundle) {
+    parent
+        .spawn((
+            Node {
+                padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(TRACK_COLOR),
+            Button,
+            marker,
+        ))
+        .with_children(|button| {
+            button.spawn((Text::new(label), TextFont::from_font_size(14.0)));
+        });
+}
+
+fn rebind_label(button: RebindButton, controls: &ControlSettings, rebinding: &Rebinding) -> String {
+    if rebinding.0 == Some(button) {
+        return format!("{}: press a key", button.direction.label());
+    }
+    let key = controls
+        .bindings(button.device)
+        .map_or("-".to_string(), |bindings| {
+            key_name(bindings.key(button.direction))
+        });
+    format!("{}: {}", button.direction.label(), key)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn setup_settings_panel(
+    mut commands: Commands,
+    mixer: Res<AudioMixer>,
+    display: Res<DisplaySettings>,
+    controls: Res<ControlSettings>,
+    accessibility: Res<AccessibilitySettings>,
+    camera: Res<CameraMode>,
+    coach: Res<CoachOverlay>,
+    tab: Res<SettingsTab>,
+) {
+    let page_node = |shown: bool| Node {
+        flex_direction: FlexDirection::Column,
+        row_gap: Val::Px(6.0),
+        display: if shown { Display::Flex } else { Display::None },
+        ..default()
+    };
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                top: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(8.0),
+                padding: UiRect::all(Val::Px(10.0)),
+                min_width: Val::Px(320.0),
+                ..default()
+            },
+            BackgroundColor(PANEL_COLOR),
+            Visibility::Hidden,
+            SettingsPanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((Text::new("Settings (F2)"), TextFont::from_font_size(18.0)));
+
+            panel
+                .spawn(Node {
+                    column_gap: Val::Px(4.0),
+                    ..default()
+                })
+                .with_children(|tabs| {
+                    for page in SettingsTab::ALL {
+                        spawn_setting_button(
+                            tabs,
+                            page.label().to_string(),
+                            SettingsTabButton(page),
+                        );
+                    }
+                });
+
+            panel
+                .spawn((
+                    page_node(*tab == SettingsTab::Video),
+                    SettingsPage(SettingsTab::Video),
+                ))
+                .with_children(|page| {
+                    for kind in DisplayButton::ALL {
+                        spawn_setting_button(page, kind.label(&display), kind);
+                    }
+                });
+
+            panel
+                .spawn((
+                    page_node(*tab == SettingsTab::Audio),
+                    SettingsPage(SettingsTab::Audio),
+                ))
+                .with_children(|page| {
+                    for bus in AudioBus::ALL {
+                        page.spawn(Node {
+                            column_gap: Val::Px(8.0),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            row.spawn((
+                                Text::new(bus.label()),
+                                TextFont::from_font_size(14.0),
+                                Node {
+                                    width: Val::Px(50.0),
+                                    ..default()
+                                },
+                            ));
+
+                            row.spawn((
+                                Node {
+                                    width: Val::Px(120.0),
+                                    height: Val::Px(12.0),
+                                    ..default()
+                                },
+                                BackgroundColor(TRACK_COLOR),
+                                Button,
+                                RelativeCursorPosition::default(),
+                                VolumeSlider { bus },
+                            ))
+                            .with_children(|track| {
+                                track.spawn((
+                                    Node {
+                                        width: Val::Percent(mixer.volume(bus) * 100.0),
+                                        height: Val::Percent(100.0),
+                                        ..default()
+                                    },
+                                    BackgroundColor(FILL_COLOR),
+                                    VolumeSliderFill { bus },
+                                ));
+                            });
+                        });
+                    }
+
+                    spawn_setting_button(page, "Mute (M)".to_string(), MuteButton);
+                });
+
+            panel
+                .spawn((
+                    page_node(*tab == SettingsTab::Controls),
+                    SettingsPage(SettingsTab::Controls),
+                ))
+                .with_children(|page| {
+                    for (device, name) in LAYOUTS {
+                        page.spawn((Text::new(name), TextFont::from_font_size(16.0)));
+                        page.spawn(Node {
+                            column_gap: Val::Px(4.0),
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            for direction in BindDirection::ALL {
+                                let button = RebindButton { device, direction };
+                                spawn_setting_button(
+                                    row,
+                                    rebind_label(button, &controls, &Rebinding::default()),
+                                    button,
+                                );
+                            }
+                        });
+                    }
+                    spawn_setting_button(
+                        page,
+                        "Reset to defaults".to_string(),
+                        ResetControlsButton,
+                    );
+                });
+
+            panel
+                .spawn((
+                    page_node(*tab == SettingsTab::Gameplay),
+                    SettingsPage(SettingsTab::Gameplay),
+                ))
+                .with_children(|page| {
+                    for kind in GameplayButton::ALL {
+                        spawn_setting_button(page, kind.label(*camera, &coach), kind);
+                    }
+                });
+
+            panel
+                .spawn((
+                    page_node(*tab == SettingsTab::Accessibility),
+                    SettingsPage(SettingsTab::Accessibility),
+                ))
+                .with_children(|page| {
+                    for kind in AccessibilityButton::ALL {
+                        spawn_setting_button(page, kind.label(&accessibility), kind);
+                    }
+                });
+        });
+}
+
+pub fn toggle_settings_panel_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut panel_query: Query<&mut Visibility, With<SettingsPanel>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    for mut visibility in panel_query.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+// Dragging along a slider sets that bus's volume
+pub fn volume_slider_system(
+    mut mixer: ResMut<AudioMixer>,
+    slider_query: Query<(&Interaction, &RelativeCursorPosition, &VolumeSlider)>,
+) {
+    for (interaction, cursor, slider) in slider_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        if let Some(position) = cursor.normalized {
+            let volume = position.x.clamp(0.0, 1.0);
+            if mixer.volume(slider.bus) != volume {
+                mixer.set_volume(slider.bus, volume);
+            }
+        }
+    }
+}
+
+pub fn mute_button_system(
+    mut mixer: ResMut<AudioMixer>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+    button_query: Query<&Interaction, (Changed<Interaction>, With<MuteButton>)>,
+) {
+    for interaction in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            mixer.muted = !mixer.muted;
+            sound_events.send(PlaySoundEvent {
+                sound: SoundEffect::UiClick,
+            });
+        }
+    }
+}
+
+// Keeps slider fills and the mute label in sync with the mixer
+pub fn update_audio_settings_ui_system(
+    mixer: Res<AudioMixer>,
+    mut fill_query: Query<(&mut Node, &VolumeSliderFill)>,
+    mute_query: Query<&Children, With<MuteButton>>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !mixer.is_changed() {
+        return;
+    }
+
+    for (mut node, fill) in fill_query.iter_mut() {
+        node.width = Val::Percent(mixer.volume(fill.bus) * 100.0);
+    }
+
+    for children in mute_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.0 = if mixer.muted {
+                    "Unmute (M)".to_string()
+                } else {
+                    "Mute (M)".to_string()
+                };
+            }
+        }
+    }
+}
+
+pub fn settings_tab_system(
+    mut tab: ResMut<SettingsTab>,
+    button_query: Query<(&Interaction, &SettingsTabButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction == Interaction::Pressed && *tab != button.0 {
+            *tab = button.0;
+        }
+    }
+}
+
+// Shows the picked page and highlights its tab
+pub fn update_settings_tabs_system(
+    tab: Res<SettingsTab>,
+    mut page_query: Query<(&mut Node, &SettingsPage)>,
+    mut tab_query: Query<(&mut BackgroundColor, &SettingsTabButton)>,
+) {
+    if !tab.is_changed() {
+        return;
+    }
+
+    for (mut node, page) in page_query.iter_mut() {
+        node.display = if page.0 == *tab {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    for (mut background, button) in tab_query.iter_mut() {
+        background.0 = if button.0 == *tab {
+            FILL_COLOR
+        } else {
+            TAB_COLOR
+        };
+    }
+}
+
+pub fn rebind_button_system(
+    mut rebinding: ResMut<Rebinding>,
+    mut controls: ResMut<ControlSettings>,
+    rebind_query: Query<(&Interaction, &RebindButton), Changed<Interaction>>,
+    reset_query: Query<&Interaction, (Changed<Interaction>, With<ResetControlsButton>)>,
+) {
+    for (interaction, button) in rebind_query.iter() {
+        if *interaction == Interaction::Pressed {
+            rebinding.0 = Some(*button);
+        }
+    }
+    for interaction in reset_query.iter() {
+        if *interaction == Interaction::Pressed {
+            *controls = ControlSettings::default();
+            rebinding.0 = None;
+        }
+    }
+}
+
+// Takes the next key pressed as the new binding, Escape gives up
+pub fn capture_rebind_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut controls: ResMut<ControlSettings>,
+) {
+    let Some(button) = rebinding.0 else {
+        return;
+    };
+    let Some(&key) = keyboard_input.get_just_pressed().next() else {
+        return;
+    };
+
+    rebinding.0 = None;
+    if key == KeyCode::Escape {
+        return;
+    }
+    if let Some(bindings) = controls.bindings_mut(button.device) {
+        bindings.set_key(button.direction, key);
+    }
+}
+
+pub fn update_controls_ui_system(
+    controls: Res<ControlSettings>,
+    rebinding: Res<Rebinding>,
+    button_query: Query<(&RebindButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !controls.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+
+    for (button, children) in button_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.0 = rebind_label(*button, &controls, &rebinding);
+            }
+        }
+    }
+}
+
+pub fn persist_controls_system(
+    paths: Res<Paths>,
+    controls: Res<ControlSettings>,
+    mut config: ResMut<GameConfig>,
+) {
+    if controls.is_changed() && !controls.is_added() && config.controls != *controls {
+        config.controls = *controls;
+        config.save(&paths);
+    }
+}
+
+pub fn gameplay_button_system(
+    paths: Res<Paths>,
+    mut camera: ResMut<CameraMode>,
+    mut coach: ResMut<CoachOverlay>,
+    mut config: ResMut<GameConfig>,
+    button_query: Query<(&Interaction, &GameplayButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            GameplayButton::CameraMode => {
+                *camera = match *camera {
+                    CameraMode::Overview => CameraMode::Chase,
+                    CameraMode::Chase => CameraMode::Overview,
+                };
+                config.camera = *camera;
+                config.save(&paths);
+            }
+            GameplayButton::CoachOverlay => coach.enabled = !coach.enabled,
+        }
+    }
+}
+
+pub fn update_gameplay_ui_system(
+    camera: Res<CameraMode>,
+    coach: Res<CoachOverlay>,
+    button_query: Query<(&GameplayButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !camera.is_changed() && !coach.is_changed() {
+        return;
+    }
+
+    for (button, children) in button_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.0 = button.label(*camera, &coach);
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// Split screen. Two players on one machine playing a map too big to take in
+// at once each get half the window following them, with their own score in
+// their half. The shared camera stays on top to draw the rest of the UI.
+use crate::components::{GridSettings, LocalPlayer, MainCamera, Player};
+use crate::progression::MAPS;
+use crate::systems::input::InputSource;
+use crate::systems::join::JoinedPlayers;
+use bevy::prelude::*;
+use bevy::render::camera::Viewport;
+use bevy::render::view::RenderLayers;
+
+// Zoom of each half, close enough to see what's around the player
+const SPLIT_ZOOM: f32 = 0.8;
+const FOLLOW_EASE: f32 = 6.0;
+// Nothing is drawn on this layer, so while split the shared camera only
+// draws UI over the two halves
+const UI_ONLY_LAYER: usize = 31;
+
+#[derive(Component)]
+pub struct SplitScreenCamera {
+    pub player: Entity,
+}
+
+// Score in the corner of a player's half
+#[derive(Component)]
+pub struct SplitScreenHud {
+    pub player: Entity,
+}
+
+// Only a pair of local players gets split screen, and only on maps bigger
+// than the classic one that fits the window
+pub fn wants_split_screen(grid_settings: &GridSettings, local_players: usize) -> bool {
+    let classic = &MAPS[0];
+    local_players == 2
+        && grid_settings.grid_width * grid_settings.grid_height
+            > classic.grid_width * classic.grid_height
+}
+
+// Local players left to right in join order
+fn split_order(
+    joined: &JoinedPlayers,
+    player_query: &Query<(Entity, &Transform, &InputSource), With<LocalPlayer>>,
+) -> Vec<(Entity, Vec2)> {
+    let mut players: Vec<_> = player_query
+        .iter()
+        .map(|(entity, transform, source)| {
+            let slot = match source {
+                InputSource::Device(device) => joined.slot_of(*device),
+                _ => None,
+            };
+            (
+                slot.unwrap_or(usize::MAX),
+                entity,
+                transform.translation.truncate(),
+            )
+        })
+        .collect();
+    players.sort_by_key(|&(slot, entity, _)| (slot, entity));
+    players
+        .into_iter()
+        .map(|(_, entity, position)| (entity, position))
+        .collect()
+}
+
+// Sets up or tears down the two halves, lays them out over the window and
+// keeps each following its player
+pub fn split_screen_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    grid_settings: Res<GridSettings>,
+    joined: Res<JoinedPlayers>,
+    window_query: Query<&Window>,
+    player_query: Query<(Entity, &Transform, &InputSource), With<LocalPlayer>>,
+    mut split_query: Query<
+        (Entity, &SplitScreenCamera, &mut Camera, &mut Transform),
+        Without<LocalPlayer>,
+    >,
+) {
+    let players = split_order(&joined, &player_query);
+    let split = wants_split_screen(&grid_settings, players.len());
+    let window = window_query.get_single().ok();
+
+    // Halves whose player is gone, or everything when no longer split
+    for (entity, split_camera, _, _) in split_query.iter() {
+        if !split
+            || !players
+                .iter()
+                .any(|&(player, _)| player == split_camera.player)
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    if !split {
+        return;
+    }
+
+    let size = window
+        .map(|window| UVec2::new(window.physical_width(), window.physical_height()))
+        .unwrap_or(UVec2::new(800, 600));
+    let half_width = (size.x / 2).max(1);
+    let blend = (FOLLOW_EASE * time.delta_secs()).min(1.0);
+
+    for (index, &(player, position)) in players.iter().enumerate() {
+        let viewport = Viewport {
+            physical_position: UVec2::new(half_width * index as u32, 0),
+            physical_size: UVec2::new(half_width, size.y.max(1)),
+            ..default()
+        };
+
+        let existing = split_query
+            .iter_mut()
+            .find(|(_, split_camera, _, _)| split_camera.player == player);
+        if let Some((_, _, mut camera, mut transform)) = existing {
+            let laid_out = camera.viewport.as_ref().is_some_and(|current| {
+                current.physical_position == viewport.physical_position
+                    && current.physical_size == viewport.physical_size
+            });
+            if !laid_out {
+                camera.viewport = Some(viewport);
+            }
+            let next = transform.translation.truncate().lerp(position, blend);
+            transform.translation.x = next.x;
+            transform.translation.y = next.y;
+            continue;
+        }
+
+        // Drawn before the shared camera so its UI ends up on top
+        let camera = commands
+            .spawn((
+                Camera2d,
+                Camera {
+                    order: index as isize - 2,
+                    viewport: Some(viewport),
+                    ..default()
+                },
+                OrthographicProjection {
+                    scale: SPLIT_ZOOM,
+                    ..OrthographicProjection::default_2d()
+                },
+                Transform::from_translation(position.extend(0.0)),
+                SplitScreenCamera { player },
+            ))
+            .id();
+        commands.spawn((
+            Text::new(""),
+            TextFont::from_font_size(20.0),
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                ..default()
+            },
+            TargetCamera(camera),
+            SplitScreenHud { player },
+        ));
+    }
+}
+
+// While split the shared camera stops drawing the game and just puts the UI
+// over both halves
+pub fn split_screen_main_camera_system(
+    mut commands: Commands,
+    split_query: Query<(), With<SplitScreenCamera>>,
+    mut main_query: Query<(Entity, &mut Camera), With<MainCamera>>,
+) {
+    let split = !split_query.is_empty();
+    for (entity, mut camera) in main_query.iter_mut() {
+        let ui_only = matches!(camera.clear_color, ClearColorConfig::None);
+        if split && !ui_only {
+            camera.clear_color = ClearColorConfig::None;
+            commands
+                .entity(entity)
+                .insert(RenderLayers::layer(UI_ONLY_LAYER));
+        } else if !split && ui_only {
+            camera.clear_color = ClearColorConfig::Default;
+            commands.entity(entity).remove::<RenderLayers>();
+        }
+    }
+}
+
+pub fn split_screen_hud_system(
+    mut commands: Commands,
+    player_query: Query<&Player>,
+    split_query: Query<&SplitScreenCamera>,
+    mut hud_query: Query<(Entity, &SplitScreenHud, &mut Text, &mut TextColor)>,
+) {
+    for (entity, hud, mut text, mut color) in hud_query.iter_mut() {
+        let split = split_query
+            .iter()
+            .any(|split_camera| split_camera.player == hud.player);
+        let Some(player) = player_query.get(hud.player).ok().filter(|_| split) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        text.0 = format!("Score: {}", player.score);
+        color.0 = player.color;
+    }
+}
@@ -0,0 +1,371 @@
+use crate::components::{Player, Respawning};
+use crate::events::{MultiKillEvent, PlayerDeathEvent, Standing, TerritoryClaimedEvent};
+use crate::resources::{GameState, OwnershipLayers};
+use crate::territory::king_distance;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Seconds between territory samples
+const SAMPLE_SECONDS: f32 = 1.0;
+// Tiles from someone that count as chasing them
+const CHASE_DISTANCE: i32 = 3;
+// A chaser gets an assist if someone else kills their target this soon after
+pub const ASSIST_SECONDS: f32 = 2.0;
+// Kills this close together chain into a double, triple...
+pub const MULTI_KILL_SECONDS: f32 = 4.0;
+
+// Fun titles handed out at the end of a match, each to whoever did the most
+// of something
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Award {
+    MostAggressive, // Most kills
+    LandBaron,      // Biggest single claim
+    Survivor,       // Longest time without dying
+    OpenHouse,      // Most tiles lost
+}
+
+impl Award {
+    pub const ALL: [Award; 4] = [
+        Award::MostAggressive,
+        Award::LandBaron,
+        Award::Survivor,
+        Award::OpenHouse,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Award::MostAggressive => "Most Aggressive",
+            Award::LandBaron => "Land Baron",
+            Award::Survivor => "Survivor",
+            Award::OpenHouse => "Open House",
+        }
+    }
+
+    // Glyph and color of the badge shown next to it
+    pub fn badge(self) -> (&'static str, Color) {
+        match self {
+            Award::MostAggressive => ("x", Color::srgb(1.0, 0.35, 0.3)),
+            Award::LandBaron => ("#", Color::srgb(1.0, 0.8, 0.2)),
+            Award::Survivor => ("+", Color::srgb(0.45, 0.85, 0.4)),
+            Award::OpenHouse => ("o", Color::srgb(0.6, 0.6, 0.9)),
+        }
+    }
+
+    // What the winner did to earn it
+    pub fn describe(self, amount: u32) -> String {
+        match self {
+            Award::MostAggressive => format!("{} kills", amount),
+            Award::LandBaron => format!("{} tiles in one claim", amount),
+            Award::Survivor => format!("{}s without dying", amount),
+            Award::OpenHouse => format!("{} tiles lost", amount),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchAward {
+    pub award: Award,
+    pub player: Entity,
+    pub amount: u32,
+}
+
+// Each player's share of the map at one point in the match
+#[derive(Clone, Debug)]
+pub struct TerritorySample {
+    pub seconds: f32,
+    pub shares: Vec<(Entity, f32)>,
+}
+
+// Running record of the match in progress, read by the results screen
+#[derive(Resource)]
+pub struct MatchStats {
+    pub samples: Vec<TerritorySample>,
+    // Kills and deaths per player this match
+    pub kills: HashMap<Entity, u32>,
+    pub deaths: HashMap<Entity, u32>,
+    // Tiles each player turned into territory this match
+    pub claimed: HashMap<Entity, u32>,
+    pub assists: HashMap<Entity, u32>,
+    // Most kills each player chained together
+    pub best_multi_kill: HashMap<Entity, u32>,
+    // Loops each player has closed since they last died, and the most in a row
+    pub claim_streaks: HashMap<Entity, u32>,
+    pub best_claim_streak: HashMap<Entity, u32>,
+    // Most tiles each player took in one claim
+    pub largest_claim: HashMap<Entity, u32>,
+    // Territory each player lost, to deaths or to other players' trails
+    pub tiles_lost: HashMap<Entity, u32>,
+    // Match seconds each player last died, and their longest stretch alive
+    // before that
+    last_death: HashMap<Entity, f32>,
+    longest_life: HashMap<Entity, f32>,
+    // Match seconds each (chaser, target) pair were last close
+    chases: HashMap<(Entity, Entity), f32>,
+    // Match seconds of each player's last kill and the chain it's part of
+    kill_chains: HashMap<Entity, (f32, u32)>,
+    timer: Timer,
+}
+
+impl Default for MatchStats {
+    fn default() -> Self {
+        Self {
+            samples: Vec::new(),
+            kills: HashMap::new(),
+            deaths: HashMap::new(),
+            claimed: HashMap::new(),
+            assists: HashMap::new(),
+            best_multi_kill: HashMap::new(),
+            claim_streaks: HashMap::new(),
+            best_claim_streak: HashMap::new(),
+            largest_claim: HashMap::new(),
+            tiles_lost: HashMap::new(),
+            last_death: HashMap::new(),
+            longest_life: HashMap::new(),
+            chases: HashMap::new(),
+            kill_chains: HashMap::new(),
+            timer: Timer::from_seconds(SAMPLE_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+impl MatchStats {
+    // One player's share over time, zero wherever they weren't around yet
+    pub fn territory_timeline(&self, player: Entity) -> Vec<f32> {
+        self.samples
+            .iter()
+            .map(|sample| {
+                sample
+                    .shares
+                    .iter()
+                    .find(|(entity, _)| *entity == player)
+                    .map_or(0.0, |&(_, share)| share)
+            })
+            .collect()
+    }
+
+    pub fn kills(&self, player: Entity) -> u32 {
+        self.kills.get(&player).copied().unwrap_or(0)
+    }
+
+    pub fn deaths(&self, player: Entity) -> u32 {
+        self.deaths.get(&player).copied().unwrap_or(0)
+    }
+
+    pub fn tiles_claimed(&self, player: Entity) -> u32 {
+        self.claimed.get(&player).copied().unwrap_or(0)
+    }
+
+    pub fn assists(&self, player: Entity) -> u32 {
+        self.assists.get(&player).copied().unwrap_or(0)
+    }
+
+    pub fn claim_streak(&self, player: Entity) -> u32 {
+        self.claim_streaks.get(&player).copied().unwrap_or(0)
+    }
+
+    pub fn largest_claim(&self, player: Entity) -> u32 {
+        self.largest_claim.get(&player).copied().unwrap_or(0)
+    }
+
+    pub fn tiles_lost(&self, player: Entity) -> u32 {
+        self.tiles_lost.get(&player).copied().unwrap_or(0)
+    }
+
+    // Ends the player's current stretch alive
+    pub fn died(&mut self, player: Entity, seconds: f32) {
+        let life = self.longest_life(player, seconds);
+        self.longest_life.insert(player, life);
+        self.last_death.insert(player, seconds);
+    }
+
+    // Longest the player went without dying, counting the stretch they're
+    // on now. Lives are timed from the start of the match or the last death.
+    pub fn longest_life(&self, player: Entity, seconds: f32) -> f32 {
+        let current = seconds - self.last_death.get(&player).copied().unwrap_or(0.0);
+        self.longest_life
+            .get(&player)
+            .copied()
+            .unwrap_or(0.0)
+            .max(current)
+    }
+
+    // Each award goes to whoever did the most of it. Ties go to the better
+    // placed player, and nobody gets one for doing none of it.
+    pub fn awards(&self, standings: &[Standing], seconds: f32) -> Vec<MatchAward> {
+        Award::ALL
+            .into_iter()
+            .filter_map(|award| {
+                let mut best: Option<MatchAward> = None;
+                for standing in standings {
+                    let player = standing.player;
+                    let amount = match award {
+                        Award::MostAggressive => self.kills(player),
+                        Award::LandBaron => self.largest_claim(player),
+                        Award::Survivor => self.longest_life(player, seconds) as u32,
+                        Award::OpenHouse => self.tiles_lost(player),
+                    };
+                    if amount > best.map_or(0, |best| best.amount) {
+                        best = Some(MatchAward {
+                            award,
+                            player,
+                            amount,
+                        });
+                    }
+                }
+                best
+            })
+            .collect()
+    }
+
+    pub fn chased(&mut self, chaser: Entity, target: Entity, seconds: f32) {
+        self.chases.insert((chaser, target), seconds);
+    }
+
+    // Whoever was on the victim most recently, other than the killer, if it
+    // was recent enough
+    pub fn assister(&self, victim: Entity, killer: Entity, seconds: f32) -> Option<Entity> {
+        self.chases
+            .iter()
+            .filter(|(&(chaser, target), &last)| {
+                target == victim
+                    && chaser != killer
+                    && chaser != victim
+                    && seconds - last <= ASSIST_SECONDS
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(&(chaser, _), _)| chaser)
+    }
+
+    // Counts a kill and returns how many the killer has now chained together
+    pub fn chain_kill(&mut self, killer: Entity, seconds: f32) -> u32 {
+        let chain = match self.kill_chains.get(&killer) {
+            Some(&(last, chain)) if seconds - last <= MULTI_KILL_SECONDS => chain + 1,
+            _ => 1,
+        };
+        self.kill_chains.insert(killer, (seconds, chain));
+        let best = self.best_multi_kill.entry(killer).or_default();
+        *best = (*best).max(chain);
+        chain
+    }
+
+    // Highest share anyone reached, for scaling graphs
+    pub fn peak_share(&self) -> f32 {
+        self.samples
+            .iter()
+            .flat_map(|sample| sample.shares.iter().map(|&(_, share)| share))
+            .fold(0.0, f32::max)
+    }
+}
+
+pub fn reset_match_stats(mut stats: ResMut<MatchStats>) {
+    *stats = MatchStats::default();
+}
+
+// Notes who is close on whose heels, for crediting assists
+pub fn track_chases_system(
+    game_state: Res<GameState>,
+    player_query: Query<(Entity, &Player), Without<Respawning>>,
+    mut stats: ResMut<MatchStats>,
+) {
+    if !game_state.game_running {
+        return;
+    }
+
+    let seconds = game_state.timer.elapsed_secs();
+    for (chaser, chaser_player) in player_query.iter() {
+        for (target, target_player) in player_query.iter() {
+            if chaser != target
+                && king_distance(chaser_player.last_tile_pos, target_player.last_tile_pos)
+                    <= CHASE_DISTANCE
+            {
+                stats.chased(chaser, target, seconds);
+            }
+        }
+    }
+}
+
+// Tallies deaths, kills for whoever caused them and assists for whoever was
+// chasing the victim, and calls out kills chained close together
+pub fn count_match_deaths_system(
+    game_state: Res<GameState>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    mut stats: ResMut<MatchStats>,
+    mut multi_kill_events: EventWriter<MultiKillEvent>,
+) {
+    let seconds = game_state.timer.elapsed_secs();
+    for event in death_events.read() {
+        // Dying ends the run of loops, whenever it happens
+        stats.claim_streaks.remove(&event.player_entity);
+        if !game_state.game_running {
+            continue;
+        }
+        stats.died(event.player_entity, seconds);
+        *stats.deaths.entry(event.player_entity).or_default() += 1;
+        let Some(killer) = event.killer.filter(|&killer| killer != event.player_entity) else {
+            continue;
+        };
+        *stats.kills.entry(killer).or_default() += 1;
+
+        if let Some(assister) = stats.assister(event.player_entity, killer, seconds) {
+            *stats.assists.entry(assister).or_default() += 1;
+            println!("Assist credited for chasing the victim down");
+        }
+
+        let kills = stats.chain_kill(killer, seconds);
+        if kills > 1 {
+            multi_kill_events.send(MultiKillEvent {
+                player: killer,
+                kills,
+            });
+        }
+    }
+}
+
+// Notes the biggest claim each player made, and counts loops closed in a row
+// without dying. Bombs claim without a trail and don't count towards streaks.
+pub fn count_claim_streaks_system(
+    mut claimed_events: EventReader<TerritoryClaimedEvent>,
+    mut stats: ResMut<MatchStats>,
+) {
+    for event in claimed_events.read() {
+        let size = event.trail_tiles + event.pockets.iter().sum::<u32>();
+        let largest = stats.largest_claim.entry(event.player).or_default();
+        *largest = (*largest).max(size);
+
+        if event.trail_tiles == 0 {
+            continue;
+        }
+        let streak = stats.claim_streaks.entry(event.player).or_default();
+        *streak += 1;
+        let streak = *streak;
+        let best = stats.best_claim_streak.entry(event.player).or_default();
+        *best = (*best).max(streak);
+    }
+}
+
+// Samples territory once a second while the clock is running
+pub fn sample_match_stats_system(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    layers: Res<OwnershipLayers>,
+    mut stats: ResMut<MatchStats>,
+) {
+    if !game_state.game_running || !stats.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // Shares are of what the map is worth, valuable tiles count for more
+    let map_value = layers.total_value() as f32;
+    let mut shares: Vec<(Entity, f32)> = layers
+        .layers
+        .keys()
+        .map(|&player| (player, layers.territory_value(player) as f32 / map_value))
+        .collect();
+    shares.sort_by_key(|&(player, _)| player);
+
+    stats.samples.push(TerritorySample {
+        seconds: game_state.timer.elapsed_secs(),
+        shares,
+    });
+}
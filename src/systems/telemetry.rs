@@ -0,0 +1,214 @@
+use crate::components::Player;
+use crate::events::{MatchTimerEvent, PlayerDeathEvent, TrailCompletedEvent};
+use crate::paths::{create_parent_dir, Paths};
+use crate::resources::{ActivePreset, GameState, OwnershipLayers};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TelemetryFormat {
+    // One JSON object per tick
+    #[default]
+    JsonLines,
+    // One row per player or event per tick
+    Csv,
+}
+
+// Where per-tick match telemetry goes, persisted in the config file. Off
+// unless a path is set, relative paths going in the captures directory.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetrySettings {
+    pub path: Option<String>,
+    pub format: TelemetryFormat,
+}
+
+const CSV_HEADER: &str = "tick,seconds_left,kind,player,x,y,score,tiles,detail";
+
+#[derive(Serialize)]
+struct PlayerSample {
+    player: u32,
+    x: i32,
+    y: i32,
+    score: u32,
+    tiles: u32,
+    drawing_trail: bool,
+}
+
+#[derive(Serialize)]
+struct EventSample {
+    kind: &'static str,
+    player: Option<u32>,
+    x: i32,
+    y: i32,
+    detail: String,
+}
+
+#[derive(Serialize)]
+struct TickSample {
+    tick: u64,
+    seconds_left: f32,
+    players: Vec<PlayerSample>,
+    events: Vec<EventSample>,
+}
+
+impl TickSample {
+    fn write_csv(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for player in self.players.iter() {
+            writeln!(
+                out,
+                "{},{:.3},player,{},{},{},{},{},{}",
+                self.tick,
+                self.seconds_left,
+                player.player,
+                player.x,
+                player.y,
+                player.score,
+                player.tiles,
+                if player.drawing_trail { "trail" } else { "" }
+            )?;
+        }
+        for event in self.events.iter() {
+            writeln!(
+                out,
+                "{},{:.3},{},{},{},{},,,{}",
+                self.tick,
+                self.seconds_left,
+                event.kind,
+                event
+                    .player
+                    .map_or(String::new(), |player| player.to_string()),
+                event.x,
+                event.y,
+                event.detail
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Open telemetry file for the match in progress
+#[derive(Resource)]
+pub struct TelemetryWriter {
+    out: BufWriter<File>,
+    format: TelemetryFormat,
+    tick: u64,
+    // Written with the first tick, so captures of different presets can be
+    // told apart
+    preset: &'static str,
+}
+
+pub fn start_telemetry(
+    mut commands: Commands,
+    paths: Res<Paths>,
+    settings: Res<TelemetrySettings>,
+    preset: Res<ActivePreset>,
+) {
+    let Some(path) = settings.path.as_ref() else {
+        return;
+    };
+    let path = paths.capture(path);
+    let format = settings.format;
+
+    let opened = create_parent_dir(&path).and_then(|_| File::create(&path));
+    let mut out = match opened {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => {
+            println!("Failed to open telemetry file {}: {}", path.display(), err);
+            return;
+        }
+    };
+    if format == TelemetryFormat::Csv && writeln!(out, "{}", CSV_HEADER).is_err() {
+        return;
+    }
+
+    commands.insert_resource(TelemetryWriter {
+        out,
+        format,
+        tick: 0,
+        preset: preset.name(),
+    });
+}
+
+pub fn stop_telemetry(mut commands: Commands, writer: Option<ResMut<TelemetryWriter>>) {
+    if let Some(mut writer) = writer {
+        let _ = writer.out.flush();
+        commands.remove_resource::<TelemetryWriter>();
+    }
+}
+
+// Appends one sample of the match per frame
+pub fn record_telemetry_system(
+    mut writer: ResMut<TelemetryWriter>,
+    game_state: Res<GameState>,
+    layers: Res<OwnershipLayers>,
+    player_query: Query<(Entity, &Player)>,
+    mut death_events: EventReader<PlayerDeathEvent>,
+    mut trail_events: EventReader<TrailCompletedEvent>,
+    mut timer_events: EventReader<MatchTimerEvent>,
+) {
+    let mut events: Vec<EventSample> = death_events
+        .read()
+        .map(|event| EventSample {
+            kind: "death",
+            player: Some(event.player_entity.index()),
+            x: event.tile.0,
+            y: event.tile.1,
+            detail: format!("{:?}", event.reason),
+        })
+        .collect();
+    events.extend(trail_events.read().map(|event| EventSample {
+        kind: "claim",
+        player: Some(event.player.index()),
+        x: event.entry_point.0,
+        y: event.entry_point.1,
+        detail: String::new(),
+    }));
+    if writer.tick == 0 {
+        events.push(EventSample {
+            kind: "preset",
+            player: None,
+            x: 0,
+            y: 0,
+            detail: writer.preset.to_string(),
+        });
+    }
+    events.extend(timer_events.read().map(|event| EventSample {
+        kind: "milestone",
+        player: None,
+        x: 0,
+        y: 0,
+        detail: format!("{:?}", event.milestone),
+    }));
+
+    let sample = TickSample {
+        tick: writer.tick,
+        seconds_left: game_state.timer.remaining_secs(),
+        players: player_query
+            .iter()
+            .map(|(entity, player)| PlayerSample {
+                player: entity.index(),
+                x: player.last_tile_pos.0,
+                y: player.last_tile_pos.1,
+                score: player.score,
+                tiles: layers.tile_count(entity),
+                drawing_trail: player.is_drawing_trail,
+            })
+            .collect(),
+        events,
+    };
+    writer.tick += 1;
+
+    let writer = &mut *writer;
+    let written = match writer.format {
+        TelemetryFormat::JsonLines => serde_json::to_writer(&mut writer.out, &sample)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writeln!(writer.out)),
+        TelemetryFormat::Csv => sample.write_csv(&mut writer.out),
+    };
+    if let Err(err) = written {
+        println!("Failed to write telemetry: {}", err);
+    }
+}
@@ -0,0 +1,232 @@
+// Applies the theme picked in the config. Its manifest comes in through the
+// asset server; once it's there its files are checked, loaded and put on the
+// board, players, UI, sound library and announcer. Anything that fails along
+// the way is reported and left to the built-in look.
+use crate::components::Player;
+use crate::config::GameConfig;
+use crate::events::{GameError, GameErrorKind};
+use crate::systems::audio::{built_in_sound, SoundLibrary};
+use crate::themes::{theme_dir, ThemeManifest, ThemeSlot, UiSkin, VoiceLine, MANIFEST_FILE};
+use bevy::asset::io::file::FileAssetReader;
+use bevy::asset::UntypedAssetId;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+// The look in use. Empty is the built-in theme.
+#[derive(Resource, Default)]
+pub struct Theme {
+    pub name: Option<String>,
+    // Manifest on its way in, and the directory it's in
+    loading: Option<(Handle<ThemeManifest>, String)>,
+    pub ui: UiSkin,
+    pub images: BTreeMap<ThemeSlot, Handle<Image>>,
+    pub font: Option<Handle<Font>>,
+    pub sounds: BTreeMap<ThemeSlot, Handle<AudioSource>>,
+    // The built-in theme has no announcer
+    pub voice: BTreeMap<VoiceLine, Vec<Handle<AudioSource>>>,
+}
+
+impl Theme {
+    pub fn image(&self, slot: ThemeSlot) -> Handle<Image> {
+        self.images.get(&slot).cloned().unwrap_or_default()
+    }
+}
+
+fn theme_error(detail: String) -> GameError {
+    GameError::new(GameErrorKind::ThemeFallback, detail)
+}
+
+pub fn load_theme_system(
+    config: Res<GameConfig>,
+    asset_server: Res<AssetServer>,
+    mut theme: ResMut<Theme>,
+    mut error_events: EventWriter<GameError>,
+) {
+    let Some(name) = config.theme.as_ref() else {
+        return;
+    };
+    let Some(dir) = theme_dir(name) else {
+        error_events.send(theme_error(format!("no theme called {}", name)));
+        return;
+    };
+    let manifest = asset_server.load(format!("{}/{}", dir, MANIFEST_FILE));
+    theme.loading = Some((manifest, dir));
+}
+
+// Checks the manifest once it's loaded and starts loading the files that
+// passed
+pub fn resolve_theme_system(
+    mut theme: ResMut<Theme>,
+    asset_server: Res<AssetServer>,
+    manifests: Res<Assets<ThemeManifest>>,
+    library: Option<ResMut<SoundLibrary>>,
+    mut error_events: EventWriter<GameError>,
+) {
+    let Some((handle, dir)) = theme.loading.clone() else {
+        return;
+    };
+    if asset_server.load_state(&handle).is_failed() {
+        theme.loading = None;
+        error_events.send(theme_error(format!(
+            "{}/{} couldn't be read",
+            dir, MANIFEST_FILE
+        )));
+        return;
+    }
+    let Some(manifest) = manifests.get(&handle) else {
+        return;
+    };
+
+    let assets = FileAssetReader::get_base_path().join("assets");
+    let (files, mut problems) = manifest.validate(&assets, &dir);
+    let (voice, voice_problems) = manifest.validate_voice(&assets, &dir);
+    problems.extend(voice_problems);
+    for problem in problems {
+        error_events.send(theme_error(problem));
+    }
+
+    let mut themed = Theme {
+        name: Some(if manifest.name.is_empty() {
+            dir.clone()
+        } else {
+            manifest.name.clone()
+        }),
+        ui: manifest.ui.clone(),
+        ..default()
+    };
+    for (slot, path) in files {
+        match slot {
+            ThemeSlot::Font => themed.font = Some(asset_server.load(path)),
+            _ if slot.sound().is_some() => {
+                themed.sounds.insert(slot, asset_server.load(path));
+            }
+            _ => {
+                themed.images.insert(slot, asset_server.load(path));
+            }
+        }
+    }
+    for (line, paths) in voice {
+        let handles = paths.into_iter().map(|path| asset_server.load(path));
+        themed.voice.insert(line, handles.collect());
+    }
+
+    if let Some(mut library) = library {
+        for (slot, sound) in themed.sounds.iter() {
+            if let Some(effect) = slot.sound() {
+                *library.handle_mut(effect) = sound.clone();
+            }
+        }
+    }
+    println!("Theme: {}", themed.name.as_deref().unwrap_or_default());
+    *theme = themed;
+}
+
+// Files that passed the checks can still fail to decode. Those go back to
+// the built-in look.
+pub fn theme_fallback_system(
+    mut theme: ResMut<Theme>,
+    asset_server: Res<AssetServer>,
+    mut library: Option<ResMut<SoundLibrary>>,
+    mut error_events: EventWriter<GameError>,
+) {
+    let failed = |id: UntypedAssetId| asset_server.load_state(id).is_failed();
+    let mut broken: Vec<ThemeSlot> = theme
+        .images
+        .iter()
+        .filter(|(_, handle)| failed(handle.id().untyped()))
+        .map(|(&slot, _)| slot)
+        .chain(
+            theme
+                .sounds
+                .iter()
+                .filter(|(_, handle)| failed(handle.id().untyped()))
+                .map(|(&slot, _)| slot),
+        )
+        .collect();
+    if theme
+        .font
+        .as_ref()
+        .is_some_and(|font| failed(font.id().untyped()))
+    {
+        broken.push(ThemeSlot::Font);
+    }
+
+    // A voice file that won't decode is dropped, its line keeps the rest
+    let broken_voice: Vec<VoiceLine> = theme
+        .voice
+        .iter()
+        .filter(|(_, handles)| handles.iter().any(|handle| failed(handle.id().untyped())))
+        .map(|(&line, _)| line)
+        .collect();
+    for line in broken_voice {
+        error_events.send(theme_error(format!(
+            "{} voice line couldn't be loaded",
+            line.name()
+        )));
+        if let Some(handles) = theme.voice.get_mut(&line) {
+            handles.retain(|handle| !failed(handle.id().untyped()));
+        }
+        theme.voice.retain(|_, handles| !handles.is_empty());
+    }
+
+    if broken.is_empty() {
+        return;
+    }
+
+    for slot in broken {
+        error_events.send(theme_error(format!("{} couldn't be loaded", slot.name())));
+        theme.images.remove(&slot);
+        theme.sounds.remove(&slot);
+        if slot == ThemeSlot::Font {
+            theme.font = None;
+        }
+        if let (Some(effect), Some(library)) = (slot.sound(), library.as_mut()) {
+            *library.handle_mut(effect) = asset_server.load(built_in_sound(effect));
+        }
+    }
+}
+
+// The player texture goes on new players, and on all of them when the
+// theme changes. Their colors tint it. Tile textures are the board's.
+pub fn apply_theme_to_world_system(
+    theme: Res<Theme>,
+    mut player_query: Query<(Ref<Player>, &mut Sprite)>,
+) {
+    for (player, mut sprite) in player_query.iter_mut() {
+        if theme.is_changed() || player.is_added() {
+            sprite.image = theme.image(ThemeSlot::Player);
+        }
+    }
+}
+
+// Font and plain text color for new text, button color for new buttons,
+// and the same for everything already up when the theme changes
+pub fn apply_theme_to_ui_system(
+    theme: Res<Theme>,
+    mut text_query: Query<(&mut TextFont, &mut TextColor)>,
+    mut button_query: Query<(Ref<Button>, &mut BackgroundColor)>,
+) {
+    for (mut font, mut color) in text_query.iter_mut() {
+        if !theme.is_changed() && !font.is_added() {
+            continue;
+        }
+        let themed_font = theme.font.clone().unwrap_or_default();
+        if font.font != themed_font {
+            font.font = themed_font;
+        }
+        if let Some(text) = theme.ui.text {
+            if color.0 == Color::WHITE {
+                color.0 = text;
+            }
+        }
+    }
+
+    let Some(button_color) = theme.ui.button else {
+        return;
+    };
+    for (button, mut background) in button_query.iter_mut() {
+        if theme.is_changed() || button.is_added() {
+            background.0 = button_color;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::components::Tiles;
+use crate::systems::accessibility::AccessibilitySettings;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+// How long a freshly claimed or stolen tile flashes before settling
+const TILE_FLASH_SECONDS: f32 = 0.35;
+
+// Short-lived tween on a tile whose land owner just changed
+pub struct TileFlash {
+    pub timer: Timer,
+    pub flash_color: Color,
+    pub settle_color: Color,
+}
+
+// Tiles flashing right now, by position
+#[derive(Resource, Default)]
+pub struct TileFlashes(pub HashMap<(i32, i32), TileFlash>);
+
+// Watches tiles for land ownership changes and starts a flash on them.
+// Unowned tiles flash white when first claimed, stolen tiles flash in their
+// new owner's full color. Nothing flashes with reduced flashing on.
+pub fn detect_tile_ownership_change_system(
+    accessibility: Res<AccessibilitySettings>,
+    mut land_owners: Local<Vec<Option<Entity>>>,
+    mut flashes: ResMut<TileFlashes>,
+    tiles: Res<Tiles>,
+) {
+    if !tiles.is_changed() {
+        return;
+    }
+    // A new map starts everyone over without flashing
+    if land_owners.len() != tiles.len() {
+        *land_owners = tiles.iter().map(|tile| tile.owner).collect();
+        flashes.0.clear();
+        return;
+    }
+
+    for ((tile, tile_color), previous) in tiles.iter_colored().zip(land_owners.iter_mut()) {
+        // Trails laid over the tile don't change whose land it is
+        let land_owner = tile.owner;
+        let previous_owner = std::mem::replace(previous, land_owner);
+
+        if land_owner == previous_owner {
+            continue;
+        }
+
+        if land_owner.is_none() {
+            // Land was lost (death reset), drop any flash so it can't repaint the tile
+            flashes.0.remove(&(tile.x, tile.y));
+            continue;
+        }
+        if accessibility.reduce_flashing {
+            continue;
+        }
+
+        let flash_color = if previous_owner.is_none() {
+            Color::WHITE
+        } else {
+            tile_color.0.with_alpha(1.0)
+        };
+
+        flashes.0.insert(
+            (tile.x, tile.y),
+            TileFlash {
+                timer: Timer::from_seconds(TILE_FLASH_SECONDS, TimerMode::Once),
+                flash_color,
+                settle_color: tile_color.0,
+            },
+        );
+    }
+}
+
+// Fades flashing tiles back to their owner's territory color
+pub fn animate_tile_flash_system(
+    time: Res<Time>,
+    mut flashes: ResMut<TileFlashes>,
+    mut tiles: ResMut<Tiles>,
+) {
+    if flashes.0.is_empty() {
+        return;
+    }
+    flashes.0.retain(|&(x, y), flash| {
+        flash.timer.tick(time.delta());
+        let Some((_, tile_color)) = tiles.get_mut(x, y) else {
+            return false;
+        };
+
+        if flash.timer.finished() {
+            tile_color.0 = flash.settle_color;
+            false
+        } else {
+            tile_color.0 = flash
+                .flash_color
+                .mix(&flash.settle_color, flash.timer.fraction());
+            true
+        }
+    });
+}
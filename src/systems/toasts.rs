@@ -0,0 +1,234 @@
+// Toasts: short notices stacked in the bottom right corner. Anything can send
+// a `ToastEvent`; they wait their turn in a queue, highest priority first, and
+// only a few are up at once.
+use crate::events::{ToastEvent, ToastIcon, ToastPriority};
+use bevy::prelude::*;
+
+// Toasts on screen at once, the rest wait
+pub const MAX_VISIBLE_TOASTS: usize = 3;
+// Seconds a toast takes to fade out at the end of its time
+const FADE_SECONDS: f32 = 0.4;
+const TOAST_BACKGROUND: Color = Color::srgba(0.08, 0.08, 0.1, 0.85);
+
+impl ToastIcon {
+    fn badge(self) -> (&'static str, Color) {
+        match self {
+            ToastIcon::Info => ("i", Color::srgb(0.4, 0.6, 1.0)),
+            ToastIcon::Achievement => ("*", Color::srgb(1.0, 0.8, 0.2)),
+            ToastIcon::Network => ("~", Color::srgb(0.3, 0.85, 0.85)),
+            ToastIcon::Save => ("s", Color::srgb(0.45, 0.85, 0.4)),
+            ToastIcon::Error => ("!", Color::srgb(1.0, 0.3, 0.25)),
+        }
+    }
+}
+
+// Toasts waiting for room on screen
+#[derive(Resource, Default)]
+pub struct ToastQueue {
+    waiting: Vec<ToastEvent>,
+}
+
+impl ToastQueue {
+    // Queues a toast unless the same message is already waiting
+    pub fn push(&mut self, toast: ToastEvent) {
+        if !self
+            .waiting
+            .iter()
+            .any(|waiting| waiting.message == toast.message)
+        {
+            self.waiting.push(toast);
+        }
+    }
+
+    // Highest priority toast, oldest first among equals
+    pub fn pop(&mut self) -> Option<ToastEvent> {
+        let index = self
+            .waiting
+            .iter()
+            .enumerate()
+            .max_by(|(a_index, a), (b_index, b)| {
+                a.priority.cmp(&b.priority).then(b_index.cmp(a_index))
+            })
+            .map(|(index, _)| index)?;
+        Some(self.waiting.remove(index))
+    }
+
+    pub fn highest_priority(&self) -> Option<ToastPriority> {
+        self.waiting.iter().map(|toast| toast.priority).max()
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiting.is_empty()
+    }
+}
+
+// Column the toasts are stacked in
+#[derive(Component)]
+pub struct ToastStack;
+
+#[derive(Component)]
+pub struct Toast {
+    pub timer: Timer,
+    pub priority: ToastPriority,
+    pub message: String,
+}
+
+pub fn setup_toast_stack(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(16.0),
+            right: Val::Px(16.0),
+            flex_direction: FlexDirection::ColumnReverse,
+            align_items: AlignItems::FlexEnd,
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        // Above menus and overlays
+        GlobalZIndex(10),
+        PickingBehavior::IGNORE,
+        ToastStack,
+    ));
+}
+
+pub fn queue_toasts_system(
+    mut toast_events: EventReader<ToastEvent>,
+    mut queue: ResMut<ToastQueue>,
+    toast_query: Query<&Toast>,
+) {
+    for event in toast_events.read() {
+        // Nothing gained by showing the same thing twice
+        if toast_query
+            .iter()
+            .any(|toast| toast.message == event.message)
+        {
+            continue;
+        }
+        queue.push(event.clone());
+    }
+}
+
+// Puts waiting toasts up while there's room. A high priority toast cuts the
+// lowest priority one on screen short when there isn't.
+pub fn show_toasts_system(
+    mut commands: Commands,
+    mut queue: ResMut<ToastQueue>,
+    stack_query: Query<Entity, With<ToastStack>>,
+    mut toast_query: Query<&mut Toast>,
+) {
+    let Ok(stack) = stack_query.get_single() else {
+        return;
+    };
+
+    let visible = toast_query
+        .iter()
+        .filter(|toast| !toast.timer.finished())
+        .count();
+    if visible >= MAX_VISIBLE_TOASTS {
+        let Some(waiting) = queue.highest_priority() else {
+            return;
+        };
+        let lowest = toast_query
+            .iter_mut()
+            .filter(|toast| !toast.timer.finished() && toast.priority < waiting)
+            .min_by_key(|toast| toast.priority);
+        if let Some(mut toast) = lowest {
+            let duration = toast.timer.duration();
+            toast.timer.set_elapsed(duration);
+        }
+        return;
+    }
+
+    for _ in visible..MAX_VISIBLE_TOASTS {
+        let Some(toast) = queue.pop() else {
+            break;
+        };
+        let (glyph, badge_color) = toast.icon.badge();
+        commands.entity(stack).with_children(|stack| {
+            stack
+                .spawn((
+                    Node {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.0),
+                        padding: UiRect::axes(Val::Px(10.0), Val::Px(6.0)),
+                        ..default()
+                    },
+                    BackgroundColor(TOAST_BACKGROUND),
+                    Toast {
+                        timer: Timer::from_seconds(toast.seconds, TimerMode::Once),
+                        priority: toast.priority,
+                        message: toast.message.clone(),
+                    },
+                ))
+                .with_children(|row| {
+                    row.spawn((
+                        Node {
+                            width: Val::Px(20.0),
+                            height: Val::Px(20.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(badge_color),
+                    ))
+                    .with_children(|badge| {
+                        badge.spawn((
+                            Text::new(glyph),
+                            TextFont::from_font_size(14.0),
+                            TextColor(Color::BLACK),
+                        ));
+                    });
+                    row.spawn((
+                        Text::new(toast.message),
+                        TextFont::from_font_size(16.0),
+                        TextColor(Color::WHITE),
+                    ));
+                });
+        });
+    }
+}
+
+// Badges and text inside a toast
+pub type ToastPartQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static mut TextColor>,
+        Option<&'static mut BackgroundColor>,
+    ),
+    Without<Toast>,
+>;
+
+// Counts real time, so toasts still go away while the game is paused. Toasts
+// fade out over their last moments.
+pub fn expire_toasts_system(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut toast_query: Query<(Entity, &mut Toast, &mut BackgroundColor, &Children)>,
+    mut part_query: ToastPartQuery,
+) {
+    for (entity, mut toast, mut background, children) in toast_query.iter_mut() {
+        if toast.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let alpha = (toast.timer.remaining_secs() / FADE_SECONDS).min(1.0);
+        background.0 = TOAST_BACKGROUND.with_alpha(TOAST_BACKGROUND.alpha() * alpha);
+        for &child in children.iter() {
+            let Ok((text_color, badge_color)) = part_query.get_mut(child) else {
+                continue;
+            };
+            if let Some(mut color) = text_color {
+                color.0 = color.0.with_alpha(alpha);
+            }
+            if let Some(mut color) = badge_color {
+                color.0 = color.0.with_alpha(alpha);
+            }
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use crate::components::{GridSettings, LocalPlayer, Player, Tiles};
+use crate::events::MatchEndedEvent;
+use crate::player_bundle;
+use crate::resources::{GameState, PendingClaims};
+use crate::states::AppState;
+use crate::systems::bots::Bot;
+use crate::systems::difficulty::BotDifficultySetting;
+use crate::systems::input::{DirectionIntent, InputDevice, InputSource};
+use crate::systems::join::JoinedPlayers;
+use crate::territory::land_color;
+use crate::tournament::{Entrant, Tournament, BRACKET_SIZES};
+use bevy::prelude::*;
+
+const WINNER_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+const PENDING_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+
+// The bracket match being played, with the players on each side once spawned
+#[derive(Resource, Clone, Debug)]
+pub struct TournamentMatch {
+    pub round: usize,
+    pub index: usize,
+    pub players: Vec<Entity>,
+    pub decided: bool,
+}
+
+impl TournamentMatch {
+    pub fn new(round: usize, index: usize) -> Self {
+        Self {
+            round,
+            index,
+            players: Vec::new(),
+            decided: false,
+        }
+    }
+}
+
+// Spawns the two sides of the bracket match, humans on their own device
+// and bots for everyone else
+pub fn start_tournament_match(
+    mut commands: Commands,
+    grid_settings: Res<GridSettings>,
+    joined: Res<JoinedPlayers>,
+    difficulty: Res<BotDifficultySetting>,
+    tournament: Option<Res<Tournament>>,
+    bracket_match: Option<ResMut<TournamentMatch>>,
+    mut game_state: ResMut<GameState>,
+) {
+    let (Some(tournament), Some(mut bracket_match)) = (tournament, bracket_match) else {
+        return;
+    };
+    let Some(sides) = tournament
+        .rounds
+        .get(bracket_match.round)
+        .and_then(|matches| matches.get(bracket_match.index))
+        .map(|bracket_match| bracket_match.sides)
+    else {
+        return;
+    };
+
+    bracket_match.players.clear();
+    for (slot, side) in sides.into_iter().enumerate() {
+        let entrant = side
+            .and_then(|index| tournament.participants.get(index))
+            .map_or(Entrant::Bot, |participant| participant.entrant);
+        let device = match entrant {
+            Entrant::Human { slot } => joined.devices.get(slot).copied(),
+            Entrant::Bot => None,
+        };
+        let mut player = commands.spawn((
+            player_bundle(&grid_settings, slot),
+            DirectionIntent::default(),
+        ));
+        // A human whose device has gone is played by a bot
+        match device {
+            Some(device) => player.insert((LocalPlayer, InputSource::Device(device))),
+            None => player.insert((
+                InputSource::External,
+                Bot::with_difficulty(difficulty.difficulty()),
+            )),
+        };
+        bracket_match.players.push(player.id());
+    }
+
+    game_state.timer = Timer::from_seconds(tournament.match_seconds, TimerMode::Once);
+    println!(
+        "{}: {} vs {}",
+        tournament.round_name(bracket_match.round),
+        tournament.name(sides[0]),
+        tournament.name(sides[1])
+    );
+}
+
+// Puts the final scores into the bracket
+pub fn record_tournament_match_system(
+    mut match_end_events: EventReader<MatchEndedEvent>,
+    tournament: Option<ResMut<Tournament>>,
+    bracket_match: Option<ResMut<TournamentMatch>>,
+) {
+    let Some(event) = match_end_events.read().last() else {
+        return;
+    };
+    let (Some(mut tournament), Some(mut bracket_match)) = (tournament, bracket_match) else {
+        return;
+    };
+    if bracket_match.decided {
+        return;
+    }
+
+    let score_of = |side: usize| {
+        bracket_match
+            .players
+            .get(side)
+            .and_then(|&player| {
+                event
+                    .standings
+                    .iter()
+                    .find(|standing| standing.player == player)
+            })
+            .map_or(0, |standing| standing.score)
+    };
+    let scores = [score_of(0), score_of(1)];
+    let winner = tournament.record(bracket_match.round, bracket_match.index, scores);
+    bracket_match.decided = true;
+    println!(
+        "{} wins {} to {}",
+        tournament.name(winner),
+        scores[0].max(scores[1]),
+        scores[0].min(scores[1])
+    );
+}
+
+// Once the match is decided, Enter goes back to the bracket with a clean map
+#[allow(clippy::too_many_arguments)]
+pub fn leave_tournament_match_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bracket_match: Option<Res<TournamentMatch>>,
+    mut game_state: ResMut<GameState>,
+    mut pending_claims: ResMut<PendingClaims>,
+    mut next_state: ResMut<NextState<AppState>>,
+    player_query: Query<Entity, With<Player>>,
+    mut tiles: ResMut<Tiles>,
+) {
+    let Some(bracket_match) = bracket_match else {
+        return;
+    };
+    if !bracket_match.decided || !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+
+    for entity in player_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for (tile, tile_color) in tiles.iter_mut() {
+        tile.owner = None;
+        tile.trail_owner = None;
+
+        // Back to the checkerboard, or void
+        tile_color.0 = land_color(tile, None);
+    }
+
+    pending_claims.tasks.clear();
+    pending_claims.cancelled.clear();
+    *game_state = GameState::default();
+    commands.remove_resource::<TournamentMatch>();
+    next_state.set(AppState::Tournament);
+}
+
+// Bracket screen between tournament matches
+#[derive(Component)]
+pub struct TournamentScreen;
+
+#[derive(Component)]
+pub struct BracketView;
+
+#[derive(Component)]
+pub struct TournamentFooter;
+
+// Draws a fresh bracket for whoever joined, unless one is under way
+pub fn setup_tournament_screen(
+    mut commands: Commands,
+    mut joined: ResMut<JoinedPlayers>,
+    tournament: Option<Res<Tournament>>,
+) {
+    if tournament.is_none() {
+        if joined.devices.is_empty() {
+            joined.devices.push(InputDevice::KeyboardWasd);
+        }
+        if let Some(tournament) = Tournament::random_draw(joined.devices.len(), BRACKET_SIZES[0]) {
+            commands.insert_resource(tournament);
+        }
+    }
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+            TournamentScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((Text::new("Tournament"), TextFont::from_font_size(32.0)));
+            screen.spawn((
+                Node {
+                    flex_direction: FlexDirection::Row,
+                    column_gap: Val::Px(24.0),
+                    ..default()
+                },
+                BracketView,
+            ));
+            screen.spawn((
+                Text::new(""),
+                TextFont::from_font_size(16.0),
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+                TournamentFooter,
+            ));
+        });
+}
+
+pub fn cleanup_tournament_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<TournamentScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn tournament_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    joined: Res<JoinedPlayers>,
+    tournament: Option<ResMut<Tournament>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let Some(mut tournament) = tournament else {
+        return;
+    };
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        commands.remove_resource::<Tournament>();
+        next_state.set(AppState::Join);
+        return;
+    }
+
+    // The field can be resized until the first match is played
+    if !tournament.started() {
+        let size = tournament.participants.len();
+        let current = BRACKET_SIZES.iter().position(|&s| s == size).unwrap_or(0);
+        let count = BRACKET_SIZES.len();
+        let step = if keyboard_input.any_just_pressed([KeyCode::ArrowRight, KeyCode::KeyD]) {
+            Some(current + 1)
+        } else if keyboard_input.any_just_pressed([KeyCode::ArrowLeft, KeyCode::KeyA]) {
+            Some(current + count - 1)
+        } else {
+            None
+        };
+        if let Some(drawn) = step.and_then(|step| {
+            Tournament::random_draw(joined.devices.len(), BRACKET_SIZES[step % count])
+        }) {
+            *tournament = drawn;
+        }
+    }
+
+    if !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+    match tournament.next_match() {
+        Some((round, index)) => {
+            commands.insert_resource(TournamentMatch::new(round, index));
+            next_state.set(AppState::Playing);
+        }
+        // Over, back to the join screen
+        None => {
+            commands.remove_resource::<Tournament>();
+            next_state.set(AppState::Join);
+        }
+    }
+}
+
+// Redraws the bracket, one column per round
+pub fn update_bracket_system(
+    mut commands: Commands,
+    tournament: Option<Res<Tournament>>,
+    view_query: Query<(Entity, Ref<BracketView>)>,
+    mut footer_query: Query<&mut Text, With<TournamentFooter>>,
+) {
+    let (Some(tournament), Ok((view, added))) = (tournament, view_query.get_single()) else {
+        return;
+    };
+    // Drawn when the screen opens, and again whenever the bracket changes
+    if !tournament.is_changed() && !added.is_added() {
+        return;
+    }
+
+    let next = tournament.next_match();
+    commands
+        .entity(view)
+        .despawn_descendants()
+        .with_children(|view| {
+            for (round, matches) in tournament.rounds.iter().enumerate() {
+                view.spawn(Node {
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceAround,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                })
+                .with_children(|column| {
+                    column.spawn((
+                        Text::new(tournament.round_name(round)),
+                        TextFont::from_font_size(16.0),
+                        TextColor(Color::srgb(0.7, 0.7, 0.7)),
+                    ));
+                    for (index, bracket_match) in matches.iter().enumerate() {
+                        let border = if next == Some((round, index)) {
+                            WINNER_COLOR
+                        } else {
+                            Color::srgba(1.0, 1.0, 1.0, 0.2)
+                        };
+                        column
+                            .spawn((
+                                Node {
+                                    flex_direction: FlexDirection::Column,
+                                    padding: UiRect::all(Val::Px(4.0)),
+                                    border: UiRect::all(Val::Px(1.0)),
+                                    min_width: Val::Px(110.0),
+                                    ..default()
+                                },
+                                BorderColor(border),
+                            ))
+                            .with_children(|card| {
+                                for (side, participant) in bracket_match.sides.iter().enumerate() {
+                                    let score =
+                                        bracket_match.scores.map_or(String::new(), |scores| {
+                                            format!("  {}", scores[side])
+                                        });
+                                    let color = match (participant, bracket_match.winner) {
+                                        (None, _) => PENDING_COLOR,
+                                        (Some(participant), Some(winner))
+                                            if *participant == winner =>
+                                        {
+                                            WINNER_COLOR
+                                        }
+                                        (Some(_), Some(_)) => PENDING_COLOR,
+                                        (Some(_), None) => Color::WHITE,
+                                    };
+                                    card.spawn((
+                                        Text::new(format!(
+                                            "{}{}",
+                                            tournament.name(*participant),
+                                            score
+                                        )),
+                                        TextFont::from_font_size(14.0),
+                                        TextColor(color),
+                                    ));
+                                }
+                            });
+                    }
+                });
+            }
+        });
+
+    let footer = match (tournament.champion(), next) {
+        (Some(champion), _) => format!("{} wins the tournament! Enter to finish", champion.name),
+        (None, Some((round, index))) => {
+            let sides = tournament.rounds[round][index].sides;
+            let resize = if tournament.started() {
+                ""
+            } else {
+                "Left / Right for 4, 8 or 16 entrants, "
+            };
+            format!(
+                "Next: {} vs {} - {}Enter to play, Esc to leave",
+                tournament.name(sides[0]),
+                tournament.name(sides[1]),
+                resize
+            )
+        }
+        (None, None) => "Esc to leave".to_string(),
+    };
+    for mut text in footer_query.iter_mut() {
+        text.0 = footer.clone();
+    }
+}
+
+// Who's playing who, shown during a tournament match
+#[derive(Component)]
+pub struct TournamentHud;
+
+pub fn setup_tournament_hud(mut commands: Commands, bracket_match: Option<Res<TournamentMatch>>) {
+    if bracket_match.is_none() {
+        return;
+    }
+
+    commands.spawn((
+        Text::new(""),
+        TextFont::from_font_size(16.0),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        },
+        TournamentHud,
+    ));
+}
+
+pub fn cleanup_tournament_hud(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<TournamentHud>>,
+) {
+    for entity in hud_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn update_tournament_hud_system(
+    tournament: Option<Res<Tournament>>,
+    bracket_match: Option<Res<TournamentMatch>>,
+    mut hud_query: Query<&mut Text, With<TournamentHud>>,
+) {
+    let (Some(tournament), Some(bracket_match)) = (tournament, bracket_match) else {
+        return;
+    };
+    let Some(played) = tournament
+        .rounds
+        .get(bracket_match.round)
+        .and_then(|matches| matches.get(bracket_match.index))
+    else {
+        return;
+    };
+
+    let status = match played.winner {
+        Some(winner) if bracket_match.decided => {
+            format!(
+                "{} goes through - Enter for the bracket",
+                tournament.name(Some(winner))
+            )
+        }
+        _ => format!(
+            "{} vs {}",
+            tournament.name(played.sides[0]),
+            tournament.name(played.sides[1])
+        ),
+    };
+    for mut text in hud_query.iter_mut() {
+        text.0 = format!("{}: {}", tournament.round_name(bracket_match.round), status);
+    }
+}
@@ -1,60 +1,47 @@
-use crate::components::{GridSettings, Player, Tile, Trail};
-use crate::resources::CompleteTrail;
+use crate::components::{GridSettings, Player, Tiles, Trail};
+use crate::events::{
+    ClaimComputedEvent, PlaySoundEvent, SoundEffect, TerritoryClaimedEvent, TrailCompletedEvent,
+};
+use crate::grid::GridMath;
+use crate::progression::TrailStyle;
+use crate::resources::{ClaimResult, GameRules, GameState, PendingClaims};
+use crate::systems::abilities::Energy;
+use crate::systems::stats::MatchStats;
+use crate::territory::{enclosed_cells, pockets_touching};
 use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool};
+use fixedbitset::FixedBitSet;
+use std::collections::{HashMap, HashSet};
 
 pub fn start_trail_system(
     grid_settings: Res<GridSettings>,
-    mut player_query: Query<(Entity, &Transform, &mut Player)>,
-    mut tile_query: Query<(Entity, &mut Tile, &mut Sprite)>,
+    mut player_query: Query<(Entity, &Transform, &mut Player, Option<&TrailStyle>)>,
+    mut tiles: ResMut<Tiles>,
 ) {
-    let tile_size = grid_settings.tile_size;
-    let half_width = (grid_settings.grid_width as f32 * tile_size) / 2.0;
-    let half_height = (grid_settings.grid_height as f32 * tile_size) / 2.0;
+    let grid = GridMath::new(&grid_settings);
 
-    for (player_entity, transform, mut player) in player_query.iter_mut() {
+    for (player_entity, transform, mut player, trail_style) in player_query.iter_mut() {
         // Skip if player is not moving
         if player.direction.length_squared() == 0.0 {
             continue;
         }
 
         // Calculate current grid position
-        let current_x = ((transform.translation.x + half_width) / tile_size).floor() as i32;
-        let current_y = ((transform.translation.y + half_height) / tile_size).floor() as i32;
+        let (current_x, current_y) = grid.tile_of(transform.translation.truncate());
 
         // Calculate the next tile based on player direction
         let next_dir = player.direction.normalize();
         let next_x = current_x + next_dir.x.round() as i32;
         let next_y = current_y + next_dir.y.round() as i32;
 
-        // Check if current tile is territory (owned by player, not a trail)
-        let mut current_is_territory = false;
-        let mut next_is_territory = false;
-
-        // Check current tile
-        for (_, tile, _) in tile_query.iter() {
-            if tile.x == current_x && tile.y == current_y {
-                if tile.owner == Some(player_entity) && !tile.is_trail {
-                    current_is_territory = true;
-                }
-                break;
-            }
-        }
-
-        // Check next tile
-        if next_x >= 0
-            && next_x < grid_settings.grid_width
-            && next_y >= 0
-            && next_y < grid_settings.grid_height
-        {
-            for (_, tile, _) in tile_query.iter() {
-                if tile.x == next_x && tile.y == next_y {
-                    if tile.owner == Some(player_entity) && !tile.is_trail {
-                        next_is_territory = true;
-                    }
-                    break;
-                }
-            }
-        }
+        // Check if current and next tiles are territory (owned by player, not a trail)
+        let is_territory = |x: i32, y: i32| {
+            tiles
+                .get(x, y)
+                .is_some_and(|tile| tile.owner == Some(player_entity))
+        };
+        let current_is_territory = is_territory(current_x, current_y);
+        let next_is_territory = is_territory(next_x, next_y);
 
         // CASE 1: Player is on territory and about to leave territory
         if current_is_territory && !next_is_territory && !player.is_drawing_trail {
@@ -71,11 +58,13 @@ pub fn start_trail_system(
             player.is_drawing_trail = true;
 
             // Immediately mark the current tile as a trail
-            for (_, mut tile, mut sprite) in tile_query.iter_mut() {
+            for (tile, tile_color) in tiles.iter_mut() {
                 if tile.x == current_x && tile.y == current_y {
-                    tile.is_trail = true;
-                    tile.owner = Some(player_entity);
-                    sprite.color = player.color.with_alpha(0.8);
+                    tile.trail_owner = Some(player_entity);
+                    tile_color.0 = trail_style
+                        .copied()
+                        .unwrap_or_default()
+                        .tile_color(player.color, (current_x, current_y));
 
                     println!(
                         "Started trail at current position ({}, {})",
@@ -90,6 +79,7 @@ pub fn start_trail_system(
 
 // Add points to the trail as player moves
 pub fn update_trail_system(
+    rules: Res<GameRules>,
     query: Query<(Entity, &Transform, &Player)>,
     mut trail_query: Query<&mut Trail>,
 ) {
@@ -100,11 +90,11 @@ pub fn update_trail_system(
             // Find the active trail belonging to this player
             for mut trail in trail_query.iter_mut() {
                 if trail.owner == entity && trail.is_active {
-                    let last_point = trail.points.last().unwrap_or(&Vec2::ZERO);
+                    let last_point = trail.points.back().unwrap_or(&Vec2::ZERO);
 
                     // Only add points if we've moved far enough (prevents too many points)
                     if last_point.distance(player_pos) > 5.0 {
-                        trail.points.push(player_pos);
+                        trail.push(player_pos, &rules.trail_points);
                     }
 
                     break;
@@ -114,10 +104,10 @@ pub fn update_trail_system(
     }
 }
 
-// Render the trails
+// Render the trails that changed since last frame
 pub fn render_trail_system(
     mut commands: Commands,
-    trail_query: Query<(Entity, &Trail)>,
+    trail_query: Query<(Entity, &Trail), Changed<Trail>>,
     player_query: Query<&Player>,
 ) {
     for (trail_entity, trail) in trail_query.iter() {
@@ -132,7 +122,7 @@ pub fn render_trail_system(
             };
 
             // First clear any existing children
-            commands.entity(trail_entity).clear_children();
+            commands.entity(trail_entity).despawn_descendants();
 
             // Then add new children using with_children
             commands.entity(trail_entity).with_children(|parent| {
@@ -170,196 +160,276 @@ pub fn render_trail_system(
     }
 }
 
-// The main territory claiming system - uses flood fill to accurately determine
-// which tiles are inside the enclosed area
+// The main territory claiming system - snapshots the grid into a bitset and
+// works out which tiles each completed loop encloses on the async compute
+// pool, so huge grids don't stall the frame. Every loop closed in the same
+// tick goes into one batch so they resolve in a fixed order: the tighter loop
+// (fewer enclosed tiles) claims contested tiles first, ties go to the lower
+// entity. The results are applied once they come back.
 pub fn claim_territory_system(
     grid_settings: Res<GridSettings>,
-    complete_trail: Option<ResMut<CompleteTrail>>,
-    mut player_query: Query<(Entity, &mut Player)>,
-    mut tile_query: Query<(Entity, &mut Tile, &mut Sprite)>,
+    mut trail_events: EventReader<TrailCompletedEvent>,
+    mut pending_claims: ResMut<PendingClaims>,
+    tiles: Res<Tiles>,
 ) {
-    // Only process if we have a completed trail
-    if let Some(mut trail_info) = complete_trail {
-        if !trail_info.complete || trail_info.player.is_none() {
-            return;
-        }
-
-        let player_entity = trail_info.player.unwrap();
-        let entry_point = trail_info.entry_point;
-
-        // Reset the flag to prevent processing multiple times
-        trail_info.complete = false;
-        trail_info.player = None;
-        trail_info.entry_point = None;
-
-        // We must have an entry point for territory claiming
-        if entry_point.is_none() {
-            println!("No entry point specified for territory claiming, aborting.");
-            return;
-        }
-
-        let (entry_x, entry_y) = entry_point.unwrap();
-        println!("============ TERRITORY CLAIMING STARTED ============");
+    let mut completed: Vec<Entity> = Vec::new();
+    for event in trail_events.read() {
         println!(
             "Player completed loop by returning to territory at ({}, {})",
-            entry_x, entry_y
+            event.entry_point.0, event.entry_point.1
         );
-
-        let grid_width = grid_settings.grid_width as usize;
-        let grid_height = grid_settings.grid_height as usize;
-
-        // Step 1: Create grid representation
-        #[derive(Clone, Copy, PartialEq)]
-        enum CellType {
-            Empty,
-            PlayerTerritory,
-            PlayerTrail,
-            Other,
+        if !completed.contains(&event.player) {
+            completed.push(event.player);
         }
+    }
 
-        let mut grid = vec![vec![CellType::Empty; grid_width]; grid_height];
-        let mut tile_entities = vec![vec![None; grid_width]; grid_height];
+    if completed.is_empty() {
+        return;
+    }
 
-        // Fill the grid with current tile state
-        for (tile_entity, tile, _) in tile_query.iter() {
-            if tile.x >= 0
-                && tile.x < grid_settings.grid_width
-                && tile.y >= 0
-                && tile.y < grid_settings.grid_height
-            {
-                let x = tile.x as usize;
-                let y = tile.y as usize;
+    let width = grid_settings.grid_width;
+    let height = grid_settings.grid_height;
 
-                tile_entities[y][x] = Some(tile_entity);
+    // Any owned tile (anyone's land or trail) blocks the fill. Void is left
+    // open, so it counts as outside and is never claimed.
+    let mut blocked = FixedBitSet::with_capacity((width * height).max(0) as usize);
+    let mut void = blocked.clone();
+    let mut trails: Vec<(Entity, Vec<(i32, i32)>)> = completed
+        .iter()
+        .map(|&player| (player, Vec::new()))
+        .collect();
 
-                if tile.owner == Some(player_entity) {
-                    if tile.is_trail {
-                        grid[y][x] = CellType::PlayerTrail;
-                    } else {
-                        grid[y][x] = CellType::PlayerTerritory;
-                    }
-                } else if tile.owner.is_some() {
-                    grid[y][x] = CellType::Other;
-                }
-            }
+    for tile in tiles.iter() {
+        if tile.x < 0 || tile.x >= width || tile.y < 0 || tile.y >= height {
+            continue;
         }
 
-        // Step 2: Convert all trail tiles to territory
-        let mut trail_count = 0;
-
-        for y in 0..grid_height {
-            for x in 0..grid_width {
-                if grid[y][x] == CellType::PlayerTrail {
-                    grid[y][x] = CellType::PlayerTerritory;
-                    trail_count += 1;
-                }
-            }
+        if tile.owner.is_some() || tile.trail_owner.is_some() {
+            blocked.insert((tile.y * width + tile.x) as usize);
         }
+        if tile.void {
+            void.insert((tile.y * width + tile.x) as usize);
+        }
+        let Some(trail_owner) = tile.trail_owner else {
+            continue;
+        };
+        if let Some((_, trail)) = trails.iter_mut().find(|(player, _)| *player == trail_owner) {
+            trail.push((tile.x, tile.y));
+        }
+    }
 
-        println!("Converting {} trail tiles to territory", trail_count);
-
-        // Step 3: Find all potentially enclosed areas
-        let mut fill_grid = vec![vec![false; grid_width]; grid_height];
-
-        // Mark all non-empty cells as visited
-        for y in 0..grid_height {
-            for x in 0..grid_width {
-                if grid[y][x] != CellType::Empty {
-                    fill_grid[y][x] = true;
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let enclosed = enclosed_cells(width, height, &blocked);
+
+        // Each loop only claims the enclosed pockets its own trail borders
+        let mut results: Vec<ClaimResult> = trails
+            .into_iter()
+            .map(|(player, trail_tiles)| {
+                let pockets = pockets_touching(width, height, &enclosed, &trail_tiles)
+                    .into_iter()
+                    .map(|pocket| {
+                        pocket
+                            .into_iter()
+                            .filter(|&(x, y)| !void[(y * width + x) as usize])
+                            .collect::<Vec<_>>()
+                    })
+                    .filter(|pocket| !pocket.is_empty())
+                    .collect();
+                ClaimResult {
+                    player,
+                    trail_tiles,
+                    pockets,
                 }
-            }
-        }
+            })
+            .collect();
 
-        // Flood fill from the edges to mark outside areas
-        let mut queue = Vec::new();
+        results.sort_by_key(|result| {
+            let enclosed: usize = result.pockets.iter().map(Vec::len).sum();
+            (enclosed, result.player.to_bits())
+        });
+        results
+    });
 
-        // Start from edges
-        for x in 0..grid_width {
-            if !fill_grid[0][x] {
-                queue.push((x, 0));
-                fill_grid[0][x] = true;
-            }
-            if !fill_grid[grid_height - 1][x] {
-                queue.push((x, grid_height - 1));
-                fill_grid[grid_height - 1][x] = true;
-            }
-        }
+    pending_claims.push(completed, task);
+}
 
-        for y in 1..grid_height - 1 {
-            if !fill_grid[y][0] {
-                queue.push((0, y));
-                fill_grid[y][0] = true;
-            }
-            if !fill_grid[y][grid_width - 1] {
-                queue.push((grid_width - 1, y));
-                fill_grid[y][grid_width - 1] = true;
-            }
-        }
+// Hands finished claim batches over to the apply step. Batches go in the
+// order they were started so overlapping claims from different ticks always
+// resolve the same way; a later batch waits for an earlier one still running.
+pub fn poll_claim_tasks_system(
+    mut pending_claims: ResMut<PendingClaims>,
+    mut claim_events: EventWriter<ClaimComputedEvent>,
+) {
+    // Batches with nothing but cancelled claims in them hold nothing up
+    let dropped: Vec<u64> = pending_claims
+        .tasks
+        .iter()
+        .filter(|(batch, players, _)| {
+            players
+                .iter()
+                .all(|&player| pending_claims.is_cancelled(player, *batch))
+        })
+        .map(|(batch, _, _)| *batch)
+        .collect();
+    pending_claims
+        .tasks
+        .retain(|(batch, _, _)| !dropped.contains(batch));
+
+    let mut finished = Vec::new();
+    while let Some((_, _, task)) = pending_claims.tasks.first_mut() {
+        let Some(results) = block_on(future::poll_once(task)) else {
+            break;
+        };
+        let (batch, _, _) = pending_claims.tasks.remove(0);
+        finished.extend(results.into_iter().map(|result| (batch, result)));
+    }
 
-        // Perform flood fill
-        while let Some((x, y)) = queue.pop() {
-            let neighbors = [
-                (x.wrapping_add(1), y),
-                (x.wrapping_sub(1), y),
-                (x, y.wrapping_add(1)),
-                (x, y.wrapping_sub(1)),
-            ];
-
-            for (nx, ny) in neighbors {
-                if nx < grid_width && ny < grid_height && !fill_grid[ny][nx] {
-                    fill_grid[ny][nx] = true;
-                    queue.push((nx, ny));
-                }
-            }
+    for (batch, result) in finished {
+        if pending_claims.is_cancelled(result.player, batch) {
+            println!("Cancelled pending territory claim due to player death");
+            continue;
         }
 
-        // Step 4: Collect all enclosed tiles
-        let mut enclosed_tiles = Vec::new();
+        claim_events.send(ClaimComputedEvent {
+            player: result.player,
+            trail_tiles: result.trail_tiles,
+            pockets: result.pockets,
+            from_bomb: false,
+        });
+    }
 
-        for y in 0..grid_height {
-            for x in 0..grid_width {
-                if grid[y][x] == CellType::Empty && !fill_grid[y][x] {
-                    enclosed_tiles.push((x, y));
-                }
-            }
-        }
+    // Forget deaths once every batch from before them is done
+    let oldest = pending_claims.tasks.first().map(|(batch, _, _)| *batch);
+    pending_claims
+        .cancelled
+        .retain(|_, restarted| oldest.is_some_and(|oldest| oldest < *restarted));
+}
 
-        println!("Found {} enclosed tiles", enclosed_tiles.len());
+// Turns the closing trail into territory and claims the enclosed tiles
+#[allow(clippy::too_many_arguments)]
+pub fn apply_claim_system(
+    rules: Res<GameRules>,
+    game_state: Res<GameState>,
+    mut claim_events: EventReader<ClaimComputedEvent>,
+    mut match_stats: ResMut<MatchStats>,
+    mut player_query: Query<(&mut Player, Option<&mut Energy>)>,
+    mut tiles: ResMut<Tiles>,
+    mut sound_events: EventWriter<PlaySoundEvent>,
+    mut claimed_events: EventWriter<TerritoryClaimedEvent>,
+) {
+    // Hurry-up phase: enclosed tiles are worth double
+    let claim_multiplier = if rules.hurry_up_double_claims && game_state.hurry_up {
+        2
+    } else {
+        1
+    };
+
+    for event in claim_events.read() {
+        let player_entity = event.player;
+        let trail: HashSet<(i32, i32)> = event.trail_tiles.iter().copied().collect();
+        // Pocket each enclosed tile belongs to
+        let enclosed: HashMap<(i32, i32), usize> = event
+            .pockets
+            .iter()
+            .enumerate()
+            .flat_map(|(pocket, tiles)| tiles.iter().map(move |&tile| (tile, pocket)))
+            .collect();
+        let mut pocket_counts = vec![0; event.pockets.len()];
+
+        // If the trail is gone the player died while the claim was computed.
+        // Bombs have no trail and go off the moment they're triggered.
+        let trail_still_there = tiles.iter().any(|tile| {
+            tile.trail_owner == Some(player_entity) && trail.contains(&(tile.x, tile.y))
+        });
+        if !trail_still_there && !event.from_bomb {
+            println!("Discarding stale territory claim");
+            continue;
+        }
 
-        // Step 5: Claim enclosed tiles and update trail tiles
         let player_color = player_query
             .get(player_entity)
-            .map_or(Color::srgba(0.5, 0.5, 0.5, 1.0), |(_, p)| p.color);
+            .map_or(Color::srgba(0.5, 0.5, 0.5, 1.0), |(p, _)| p.color);
 
         let territory_color = player_color.with_alpha(0.5);
+        let mut trail_count = 0;
         let mut claimed_count = 0;
-
-        for (_, mut tile, mut sprite) in tile_query.iter_mut() {
-            // First, convert all trail tiles to territory
-            if tile.owner == Some(player_entity) && tile.is_trail {
-                tile.is_trail = false;
-                sprite.color = territory_color;
+        let mut trail_value = 0;
+        let mut claimed_value = 0;
+        // Land the trail ran over that belonged to someone else
+        let mut taken: HashMap<Entity, u32> = HashMap::new();
+
+        for (tile, tile_color) in tiles.iter_mut() {
+            let tile_pos = (tile.x, tile.y);
+
+            // First, convert the closing trail to territory, taking the land
+            // under it from whoever held it
+            if tile.trail_owner == Some(player_entity) && trail.contains(&tile_pos) {
+                if let Some(previous) = tile.owner.filter(|&owner| owner != player_entity) {
+                    *taken.entry(previous).or_default() += tile.value;
+                    *match_stats.tiles_lost.entry(previous).or_default() += 1;
+                }
+                tile.owner = Some(player_entity);
+                tile.trail_owner = None;
+                tile_color.0 = territory_color;
+                trail_count += 1;
+                trail_value += tile.value;
             }
 
-            // Then claim enclosed tiles
-            let tile_pos = (tile.x as usize, tile.y as usize);
-            if enclosed_tiles.contains(&tile_pos) {
+            // Then claim enclosed tiles nobody took in the meantime, never void
+            if let Some(&pocket) = enclosed
+                .get(&tile_pos)
+                .filter(|_| tile.owner.is_none() && !tile.void)
+            {
                 tile.owner = Some(player_entity);
-                tile.is_trail = false;
-                sprite.color = territory_color;
+                tile_color.0 = territory_color;
+                pocket_counts[pocket] += 1;
                 claimed_count += 1;
+                claimed_value += tile.value;
             }
         }
 
-        // Update player score
-        if let Ok((_, mut player)) = player_query.get_mut(player_entity) {
-            player.score += claimed_count;
+        for (previous, value) in taken {
+            if let Ok((mut player, _)) = player_query.get_mut(previous) {
+                player.score = player.score.saturating_sub(value);
+            }
+        }
+
+        println!("Converted {} trail tiles to territory", trail_count);
+        if pocket_counts.len() > 1 {
+            println!(
+                "Loop closed off {} pockets: {:?}",
+                pocket_counts.len(),
+                pocket_counts
+            );
+        }
+        // Tiles count for what they're worth, the hurry-up extra is a bonus
+        // that outlasts the land
+        let bonus = claimed_value * (claim_multiplier - 1);
+        let points = trail_value + claimed_value + bonus;
+        claimed_events.send(TerritoryClaimedEvent {
+            player: player_entity,
+            trail_tiles: trail_count,
+            pockets: pocket_counts,
+            points,
+        });
+        *match_stats.claimed.entry(player_entity).or_default() += trail_count + claimed_count;
+
+        if let Ok((mut player, energy)) = player_query.get_mut(player_entity) {
+            player.score += trail_value + claimed_value;
+            player.add_bonus(bonus);
             println!(
                 "Player claimed {} tiles. Total score: {}",
                 claimed_count, player.score
             );
+
+            // Every tile claimed counts towards energy, whatever it's worth
+            if let (Some(mut energy), Some(energy_rules)) = (energy, rules.energy) {
+                energy.add_tiles(trail_count + claimed_count, &energy_rules);
+            }
         }
 
-        println!("============ TERRITORY CLAIMING ENDED ============");
+        sound_events.send(PlaySoundEvent {
+            sound: SoundEffect::Claim,
+        });
     }
 }
@@ -0,0 +1,352 @@
+// Weather. On a schedule a front of rain, fog or wind is announced, then
+// covers the whole map for a while. Fronts are global modifiers: movement asks
+// the schedule how fast everyone goes, and the client asks it how far local
+// players can see. Rain slows everyone down, wind speeds up whoever runs with
+// it and holds back whoever runs into it, and fog hides the map and enemies
+// outside a small radius.
+use crate::components::{GridSettings, LocalPlayer, Player, Respawning};
+use crate::events::WeatherWarningEvent;
+use crate::grid::GridMath;
+use crate::resources::{GameRules, GameState, MatchRng, WeatherRules};
+use crate::territory::SweepDirection;
+use bevy::prelude::*;
+use rand::Rng;
+
+// Tiles out of sight in fog are washed out towards this
+pub const FOG_COLOR: Color = Color::srgb(0.62, 0.64, 0.68);
+// How much of a tile's own color still shows through fog
+pub const FOG_SHOW_THROUGH: f32 = 0.15;
+// Rain or wind streaks drawn over the map
+const STREAKS: usize = 48;
+// Tiles a second the streaks move
+const RAIN_FALL_SPEED: f32 = 14.0;
+const WIND_STREAK_SPEED: f32 = 10.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeatherKind {
+    Rain,
+    Fog,
+    // Blowing the way it points
+    Wind(SweepDirection),
+}
+
+impl WeatherKind {
+    pub fn announcement(self) -> String {
+        match self {
+            WeatherKind::Rain => "Rain is on the way!".to_string(),
+            WeatherKind::Fog => "Fog is rolling in!".to_string(),
+            WeatherKind::Wind(direction) => {
+                format!("Wind coming from the {}!", direction.from_side())
+            }
+        }
+    }
+
+    // Banner flash, and the tint laid over the map while it lasts
+    pub fn color(self) -> Color {
+        match self {
+            WeatherKind::Rain => Color::srgb(0.3, 0.45, 0.85),
+            WeatherKind::Fog => FOG_COLOR,
+            WeatherKind::Wind(_) => Color::srgb(0.8, 0.9, 0.95),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeatherFront {
+    pub kind: WeatherKind,
+    // Match seconds it sets in and clears up at
+    pub starts_at: f32,
+    pub ends_at: f32,
+}
+
+impl WeatherFront {
+    pub fn active(&self, elapsed: f32) -> bool {
+        elapsed >= self.starts_at && elapsed < self.ends_at
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct WeatherSchedule {
+    pub rules: WeatherRules,
+    // Announced front that hasn't cleared up yet
+    pub front: Option<WeatherFront>,
+    // Match seconds the next front sets in at
+    pub next_front: f32,
+}
+
+impl WeatherSchedule {
+    pub fn new(rules: WeatherRules) -> Self {
+        Self {
+            rules,
+            front: None,
+            next_front: rules.first_front,
+        }
+    }
+
+    // Weather in effect right now, None between fronts or while one is
+    // only announced
+    pub fn active(&self, elapsed: f32) -> Option<WeatherKind> {
+        self.front
+            .filter(|front| front.active(elapsed))
+            .map(|front| front.kind)
+    }
+
+    // How much faster than usual someone heading that way moves
+    pub fn speed_scale(&self, elapsed: f32, direction: Vec2) -> f32 {
+        let scale = match self.active(elapsed) {
+            Some(WeatherKind::Rain) => 1.0 - self.rules.rain_slowdown,
+            Some(WeatherKind::Wind(blowing)) => {
+                1.0 + self.rules.wind_push * direction.dot(blowing.vector())
+            }
+            Some(WeatherKind::Fog) | None => 1.0,
+        };
+        // However strong the weather, nobody is blown to a standstill
+        scale.max(0.1)
+    }
+
+    // Tiles local players see around themselves, None when nothing is in
+    // the way
+    pub fn vision_radius(&self, elapsed: f32) -> Option<f32> {
+        (self.active(elapsed) == Some(WeatherKind::Fog)).then_some(self.rules.fog_radius)
+    }
+
+    fn announce_front(&mut self, rng: &mut impl Rng) -> WeatherFront {
+        let kind = match rng.random_range(0..3) {
+            0 => WeatherKind::Rain,
+            1 => WeatherKind::Fog,
+            _ => WeatherKind::Wind(
+                SweepDirection::CYCLE[rng.random_range(0..SweepDirection::CYCLE.len())],
+            ),
+        };
+        let front = WeatherFront {
+            kind,
+            starts_at: self.next_front,
+            ends_at: self.next_front + self.rules.duration.max(0.0),
+        };
+        self.front = Some(front);
+        // The next one is never announced before this one clears up
+        self.next_front += self
+            .rules
+            .interval
+            .max(self.rules.duration + self.rules.warning);
+        front
+    }
+}
+
+pub fn setup_weather_schedule(mut commands: Commands, rules: Res<GameRules>) {
+    match rules.weather {
+        Some(weather_rules) => commands.insert_resource(WeatherSchedule::new(weather_rules)),
+        None => commands.remove_resource::<WeatherSchedule>(),
+    }
+}
+
+pub fn cleanup_weather_schedule(mut commands: Commands) {
+    commands.remove_resource::<WeatherSchedule>();
+}
+
+// Announces fronts as their warning comes up and clears them once they're
+// over
+pub fn weather_system(
+    game_state: Res<GameState>,
+    mut schedule: ResMut<WeatherSchedule>,
+    mut rng: ResMut<MatchRng>,
+    mut warning_events: EventWriter<WeatherWarningEvent>,
+) {
+    if !game_state.game_running {
+        return;
+    }
+
+    let elapsed = game_state.timer.elapsed_secs();
+    if schedule.front.is_some_and(|front| elapsed >= front.ends_at) {
+        println!("The weather clears up");
+        schedule.front = None;
+    }
+    if schedule.front.is_none() && elapsed >= schedule.next_front - schedule.rules.warning {
+        let front = schedule.announce_front(&mut rng.0);
+        println!("Weather incoming: {:?}", front.kind);
+        warning_events.send(WeatherWarningEvent { front });
+    }
+}
+
+// Where local players can see, the whole map unless there's fog
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct Vision {
+    pub radius: Option<f32>,
+    // Tiles the local players are on
+    pub centers: Vec<(i32, i32)>,
+}
+
+impl Vision {
+    pub fn sees(&self, (x, y): (i32, i32)) -> bool {
+        self.radius.is_none_or(|radius| {
+            self.centers.iter().any(|&(center_x, center_y)| {
+                let offset = Vec2::new((x - center_x) as f32, (y - center_y) as f32);
+                offset.length_squared() <= radius * radius
+            })
+        })
+    }
+}
+
+// Follows the local players around while there's fog. With nobody local to
+// follow, spectators see everything.
+pub fn vision_system(
+    game_state: Res<GameState>,
+    schedule: Option<Res<WeatherSchedule>>,
+    player_query: Query<&Player, With<LocalPlayer>>,
+    mut vision: ResMut<Vision>,
+) {
+    let radius =
+        schedule.and_then(|schedule| schedule.vision_radius(game_state.timer.elapsed_secs()));
+    let centers: Vec<(i32, i32)> = match radius {
+        Some(_) => player_query
+            .iter()
+            .map(|player| player.last_tile_pos)
+            .collect(),
+        None => Vec::new(),
+    };
+    vision.set_if_neq(Vision {
+        radius: radius.filter(|_| !centers.is_empty()),
+        centers,
+    });
+}
+
+// Enemy hidden because they're lost in the fog, rather than dead
+#[derive(Component)]
+pub struct HiddenByFog;
+
+type FoggedPlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Player,
+        &'static mut Visibility,
+        Has<HiddenByFog>,
+    ),
+    (Without<LocalPlayer>, Without<Respawning>),
+>;
+
+// Hides enemies out of sight, and brings them back once they're in it again
+pub fn fog_visibility_system(
+    mut commands: Commands,
+    vision: Res<Vision>,
+    mut player_query: FoggedPlayerQuery,
+) {
+    for (entity, player, mut visibility, hidden) in player_query.iter_mut() {
+        if !vision.sees(player.last_tile_pos) {
+            visibility.set_if_neq(Visibility::Hidden);
+            if !hidden {
+                commands.entity(entity).insert(HiddenByFog);
+            }
+        } else if hidden {
+            *visibility = Visibility::Inherited;
+            commands.entity(entity).remove::<HiddenByFog>();
+        }
+    }
+}
+
+// Tint over the whole map while the weather lasts
+#[derive(Component)]
+pub struct WeatherOverlay;
+
+// A streak of rain or gust of wind, starting somewhere across the map
+#[derive(Component)]
+pub struct WeatherStreak {
+    pub phase: Vec2,
+}
+
+// Tints the map for rain and wind, with streaks falling or blowing across it.
+// Fog is drawn by the board instead.
+pub fn weather_overlay_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    grid_settings: Res<GridSettings>,
+    schedule: Option<Res<WeatherSchedule>>,
+    mut overlay_query: Query<(Entity, &mut Sprite, &mut Transform), With<WeatherOverlay>>,
+    mut streak_query: Query<
+        (Entity, &WeatherStreak, &mut Sprite, &mut Transform),
+        Without<WeatherOverlay>,
+    >,
+) {
+    let kind = schedule
+        .and_then(|schedule| schedule.active(game_state.timer.elapsed_secs()))
+        .filter(|&kind| kind != WeatherKind::Fog);
+    let Some(kind) = kind else {
+        for (entity, ..) in overlay_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        for (entity, ..) in streak_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+
+    let grid = GridMath::new(&grid_settings);
+    let size = Vec2::new(
+        grid_settings.grid_width as f32,
+        grid_settings.grid_height as f32,
+    ) * grid.tile_size;
+    let corner = grid.corner_of(0, 0);
+    let tint = kind.color().with_alpha(0.15);
+    // Just under hazard walls
+    let translation = (corner + size / 2.0).extend(0.15);
+    match overlay_query.get_single_mut() {
+        Ok((_, mut sprite, mut transform)) => {
+            sprite.color = tint;
+            sprite.custom_size = Some(size);
+            transform.translation = translation;
+        }
+        Err(_) => {
+            commands.spawn((
+                Sprite {
+                    color: tint,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                Transform::from_translation(translation),
+                WeatherOverlay,
+            ));
+        }
+    }
+
+    // Streak size and speed in tiles
+    let (streak_size, velocity) = match kind {
+        WeatherKind::Wind(blowing) if blowing.vector().x != 0.0 => {
+            (Vec2::new(0.9, 0.06), blowing.vector() * WIND_STREAK_SPEED)
+        }
+        WeatherKind::Wind(blowing) => (Vec2::new(0.06, 0.9), blowing.vector() * WIND_STREAK_SPEED),
+        _ => (
+            Vec2::new(0.06, 0.7),
+            Vec2::new(0.05, -1.0) * RAIN_FALL_SPEED,
+        ),
+    };
+    let travelled = velocity * grid.tile_size * time.elapsed_secs();
+    let place = |phase: Vec2| {
+        let position = (phase * size + travelled).rem_euclid(size);
+        (corner + position).extend(0.16)
+    };
+
+    if streak_query.is_empty() {
+        for i in 0..STREAKS {
+            // Spread out evenly rather than at random, so they don't clump
+            let phase = Vec2::new(
+                (i as f32 * 0.618_034).fract(),
+                (i as f32 * 0.754_877_7).fract(),
+            );
+            commands.spawn((
+                Sprite {
+                    color: Color::srgba(1.0, 1.0, 1.0, 0.35),
+                    custom_size: Some(streak_size * grid.tile_size),
+                    ..default()
+                },
+                Transform::from_translation(place(phase)),
+                WeatherStreak { phase },
+            ));
+        }
+    }
+    for (_, streak, mut sprite, mut transform) in streak_query.iter_mut() {
+        sprite.custom_size = Some(streak_size * grid.tile_size);
+        transform.translation = place(streak.phase);
+    }
+}
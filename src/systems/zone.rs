@@ -0,0 +1,187 @@
+// Shrinking-zone mode. The safe area starts as the whole map and loses its
+// outer ring on a schedule, and anyone caught outside dies. The ring about to
+// close pulses for a few seconds first, and bots are told where it will be.
+use crate::brain::ZoneView;
+use crate::components::{GridSettings, Player, Respawning, Tiles};
+use crate::events::{PlayerDeathEvent, PlayerDeathReason};
+use crate::grid::GridMath;
+use crate::resources::{GameRules, GameState, ZoneRules};
+use crate::territory::{trail_length, ZoneBounds};
+use bevy::prelude::*;
+
+// How long before a ring closes it starts flashing
+pub const ZONE_WARNING_SECONDS: f32 = 5.0;
+
+#[derive(Resource, Clone, Debug)]
+pub struct SafeZone {
+    pub rules: ZoneRules,
+    pub bounds: ZoneBounds,
+    // Match seconds the next ring closes at
+    pub next_shrink: f32,
+}
+
+impl SafeZone {
+    pub fn new(rules: ZoneRules, grid_settings: &GridSettings) -> Self {
+        Self {
+            rules,
+            bounds: ZoneBounds::whole_map(grid_settings.grid_width, grid_settings.grid_height),
+            next_shrink: rules.first_shrink,
+        }
+    }
+
+    // What's left once the next ring closes, the same bounds once the zone
+    // has stopped shrinking
+    pub fn next_bounds(&self) -> ZoneBounds {
+        self.bounds.shrunk(self.rules.min_size)
+    }
+
+    pub fn seconds_to_shrink(&self, elapsed: f32) -> Option<f32> {
+        (self.next_bounds() != self.bounds).then(|| (self.next_shrink - elapsed).max(0.0))
+    }
+
+    // The bounds after the next shrink, once it's close enough to warn about
+    pub fn warning(&self, elapsed: f32) -> Option<ZoneBounds> {
+        self.seconds_to_shrink(elapsed)
+            .filter(|&seconds| seconds <= ZONE_WARNING_SECONDS)
+            .map(|_| self.next_bounds())
+    }
+
+    pub fn view(&self, elapsed: f32) -> ZoneView {
+        ZoneView {
+            bounds: self.bounds,
+            next: self.next_bounds(),
+            seconds_to_shrink: self.seconds_to_shrink(elapsed),
+        }
+    }
+}
+
+pub fn setup_safe_zone(
+    mut commands: Commands,
+    rules: Res<GameRules>,
+    grid_settings: Res<GridSettings>,
+) {
+    match rules.shrinking_zone {
+        Some(zone_rules) => commands.insert_resource(SafeZone::new(zone_rules, &grid_settings)),
+        None => commands.remove_resource::<SafeZone>(),
+    }
+}
+
+pub fn cleanup_safe_zone(mut commands: Commands) {
+    commands.remove_resource::<SafeZone>();
+}
+
+// Closes rings as their time comes and kills whoever is outside
+pub fn shrink_zone_system(
+    game_state: Res<GameState>,
+    mut zone: ResMut<SafeZone>,
+    player_query: Query<(Entity, &Player), Without<Respawning>>,
+    tiles: Res<Tiles>,
+    mut death_events: EventWriter<PlayerDeathEvent>,
+) {
+    if !game_state.game_running {
+        return;
+    }
+
+    let elapsed = game_state.timer.elapsed_secs();
+    if zone
+        .seconds_to_shrink(elapsed)
+        .is_some_and(|seconds| seconds <= 0.0)
+    {
+        zone.bounds = zone.next_bounds();
+        zone.next_shrink += zone.rules.interval;
+        println!(
+            "The zone closed in to {}x{}",
+            zone.bounds.width(),
+            zone.bounds.height()
+        );
+    }
+
+    for (entity, player) in player_query.iter() {
+        if zone.bounds.contains(player.last_tile_pos) {
+            continue;
+        }
+        death_events.send(PlayerDeathEvent {
+            player_entity: entity,
+            reason: PlayerDeathReason::ZoneClosed,
+            killer: None,
+            tile: player.last_tile_pos,
+            trail_length: trail_length(tiles.iter(), entity),
+        });
+    }
+}
+
+// Shade over a tile outside the zone, or in the ring about to close
+#[derive(Component)]
+pub struct ZoneOverlay {
+    pub tile: (i32, i32),
+}
+
+// Darkens the closed area and pulses the next ring from amber to red as it
+// gets closer to closing
+pub fn zone_overlay_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    grid_settings: Res<GridSettings>,
+    zone: Option<Res<SafeZone>>,
+    mut overlay_query: Query<(Entity, &ZoneOverlay, &mut Sprite)>,
+    mut covered: Local<Option<ZoneBounds>>,
+) {
+    let Some(zone) = zone else {
+        for (entity, _, _) in overlay_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        *covered = None;
+        return;
+    };
+
+    // Overlays cover everything outside the area that will still be safe
+    let elapsed = game_state.timer.elapsed_secs();
+    let warning = zone.warning(elapsed);
+    let area = warning.unwrap_or(zone.bounds);
+    if *covered != Some(area) {
+        for (entity, _, _) in overlay_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        *covered = Some(area);
+
+        let grid = GridMath::new(&grid_settings);
+        let tile_size = grid.tile_size;
+        for y in 0..grid_settings.grid_height {
+            for x in 0..grid_settings.grid_width {
+                if area.contains((x, y)) {
+                    continue;
+                }
+                commands.spawn((
+                    Sprite {
+                        color: Color::NONE,
+                        custom_size: Some(Vec2::new(tile_size, tile_size)),
+                        ..default()
+                    },
+                    // Over the tiles, under trails and players
+                    Transform::from_translation(grid.center_of(x, y).extend(-0.04)),
+                    ZoneOverlay { tile: (x, y) },
+                ));
+            }
+        }
+    }
+
+    // 0 when the warning starts, 1 as the ring closes
+    let urgency = zone
+        .seconds_to_shrink(elapsed)
+        .map_or(0.0, |seconds| 1.0 - seconds / ZONE_WARNING_SECONDS)
+        .clamp(0.0, 1.0);
+    // Pulses speed up as the ring gets closer to closing
+    let pulse = 0.5 + 0.5 * (time.elapsed_secs() * (4.0 + urgency * 8.0)).sin();
+    let warning_color = Color::srgb(1.0, 0.75, 0.1)
+        .mix(&Color::srgb(0.9, 0.1, 0.1), urgency)
+        .with_alpha((0.2 + 0.35 * urgency) * (0.5 + 0.5 * pulse));
+
+    for (_, overlay, mut sprite) in overlay_query.iter_mut() {
+        sprite.color = if zone.bounds.contains(overlay.tile) {
+            warning_color
+        } else {
+            Color::srgba(0.25, 0.05, 0.05, 0.55)
+        };
+    }
+}
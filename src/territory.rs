@@ -0,0 +1,748 @@
+// territory.rs
+// Pure grid-level territory logic. Nothing in here touches the ECS directly,
+// systems take a snapshot of the tiles and ask questions about it.
+use crate::components::Tile;
+use crate::grid::GridMath;
+use bevy::prelude::*;
+use fixedbitset::FixedBitSet;
+use std::collections::{HashSet, VecDeque};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TileState {
+    // Land and trail owners, as on `Tile`
+    pub owner: Option<Entity>,
+    pub trail_owner: Option<Entity>,
+}
+
+// Size and centre of mass (in tile coordinates) of one connected region
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegionSummary {
+    pub size: u32,
+    pub centroid: Vec2,
+}
+
+impl RegionSummary {
+    pub fn of(tiles: &[(i32, i32)]) -> Self {
+        let sum = tiles.iter().fold(Vec2::ZERO, |sum, &(x, y)| {
+            sum + Vec2::new(x as f32, y as f32)
+        });
+
+        Self {
+            size: tiles.len() as u32,
+            centroid: sum / tiles.len().max(1) as f32,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TileMap {
+    pub width: i32,
+    pub height: i32,
+    cells: Vec<TileState>,
+    // Points each tile is worth, kept apart from the state so clearing a
+    // tile doesn't touch it
+    values: Vec<u32>,
+    // Tiles off the map's shape
+    void: FixedBitSet,
+    // Tiles with stairs to the other floor
+    stairs: FixedBitSet,
+}
+
+impl TileMap {
+    pub fn new(width: i32, height: i32) -> Self {
+        let cells = (width.max(0) * height.max(0)) as usize;
+        Self {
+            width,
+            height,
+            cells: vec![TileState::default(); cells],
+            values: vec![1; cells],
+            void: FixedBitSet::with_capacity(cells),
+            stairs: FixedBitSet::with_capacity(cells),
+        }
+    }
+
+    // Snapshot the current tiles into a grid of their own
+    pub fn from_tiles<'a>(
+        width: i32,
+        height: i32,
+        tiles: impl IntoIterator<Item = &'a Tile>,
+    ) -> Self {
+        let mut map = Self::new(width, height);
+        for tile in tiles {
+            if let Some(i) = map.index(tile.x, tile.y) {
+                map.values[i] = tile.value;
+                map.void.set(i, tile.void);
+                map.stairs.set(i, tile.stairs);
+            }
+            map.set(
+                tile.x,
+                tile.y,
+                TileState {
+                    owner: tile.owner,
+                    trail_owner: tile.trail_owner,
+                },
+            );
+        }
+        map
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        self.in_bounds(x, y).then(|| (y * self.width + x) as usize)
+    }
+
+    // On the map and not void
+    pub fn playable(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| !self.void[i])
+    }
+
+    pub fn stairs(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| self.stairs[i])
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<TileState> {
+        self.index(x, y).map(|i| self.cells[i])
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, state: TileState) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = state;
+        }
+    }
+
+    pub fn value(&self, x: i32, y: i32) -> u32 {
+        self.index(x, y).map_or(0, |i| self.values[i])
+    }
+
+    pub fn set_value(&mut self, x: i32, y: i32, value: u32) {
+        if let Some(i) = self.index(x, y) {
+            self.values[i] = value;
+        }
+    }
+
+    // The map's grid, one world unit to a tile, for walking it with the
+    // grid helpers
+    pub fn grid(&self) -> GridMath {
+        GridMath {
+            width: self.width,
+            height: self.height,
+            tile_size: 1.0,
+        }
+    }
+
+    // Tiles on the map sharing an edge with (x, y)
+    pub fn neighbors4(&self, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> {
+        self.grid().neighbors(x, y)
+    }
+
+    // Tiles on the map sharing an edge or a corner with (x, y)
+    pub fn neighbors8(&self, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> {
+        self.grid()
+            .around(x, y, 1)
+            .filter(move |&tile| tile != (x, y))
+    }
+
+    // Steps from `from` along `direction` for up to `max_steps` tiles and
+    // returns the first tile `hit` picks out, None if the ray reaches the
+    // map edge or runs out first. `from` itself is never hit.
+    pub fn first_hit_along(
+        &self,
+        from: (i32, i32),
+        direction: IVec2,
+        max_steps: u32,
+        hit: impl Fn((i32, i32), TileState) -> bool,
+    ) -> Option<(i32, i32)> {
+        if direction == IVec2::ZERO {
+            return None;
+        }
+        (1..=max_steps as i32)
+            .map(|steps| (from.0 + direction.x * steps, from.1 + direction.y * steps))
+            .map_while(|(x, y)| self.get(x, y).map(|state| ((x, y), state)))
+            .find(|&(tile, state)| hit(tile, state))
+            .map(|(tile, _)| tile)
+    }
+
+    // What a set of tiles is worth added up
+    pub fn value_of(&self, tiles: &[(i32, i32)]) -> u32 {
+        tiles.iter().map(|&(x, y)| self.value(x, y)).sum()
+    }
+
+    // True if the tile is land owned by the player, whoever's trail is on it
+    pub fn is_territory_of(&self, x: i32, y: i32, player: Entity) -> bool {
+        self.get(x, y)
+            .is_some_and(|state| state.owner == Some(player))
+    }
+
+    // Takes the land off its owner, leaving any trail on it
+    pub fn clear_owner(&mut self, x: i32, y: i32) {
+        if let Some(state) = self.get(x, y) {
+            self.set(
+                x,
+                y,
+                TileState {
+                    owner: None,
+                    ..state
+                },
+            );
+        }
+    }
+
+    // Breadth-first search outwards from `from` for the closest tile of the
+    // player's territory, measured in 4-connected steps
+    pub fn nearest_territory(&self, from: (i32, i32), player: Entity) -> Option<(i32, i32)> {
+        let start = self.index(from.0, from.1)?;
+        let mut visited = vec![false; self.cells.len()];
+        let mut queue = VecDeque::new();
+
+        visited[start] = true;
+        queue.push_back(from);
+
+        while let Some((x, y)) = queue.pop_front() {
+            if self.is_territory_of(x, y, player) {
+                return Some((x, y));
+            }
+
+            for (nx, ny) in self.neighbors4(x, y) {
+                let i = (ny * self.width + nx) as usize;
+                if !visited[i] {
+                    visited[i] = true;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        None
+    }
+
+    // All land tiles owned by the player
+    pub fn territory_tiles(&self, player: Entity) -> Vec<(i32, i32)> {
+        let mut tiles = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.is_territory_of(x, y, player) {
+                    tiles.push((x, y));
+                }
+            }
+        }
+        tiles
+    }
+
+    // Land tile of the player furthest from the closest threat, measured in
+    // king moves
+    pub fn safest_territory(&self, player: Entity, threats: &[(i32, i32)]) -> Option<(i32, i32)> {
+        self.territory_tiles(player)
+            .into_iter()
+            .max_by_key(|&tile| {
+                threats
+                    .iter()
+                    .map(|&threat| king_distance(tile, threat))
+                    .min()
+                    .unwrap_or(i32::MAX)
+            })
+    }
+
+    // Land tiles of the player that touch something that isn't their land
+    // (including the map edge)
+    pub fn border_tiles(&self, player: Entity) -> Vec<(i32, i32)> {
+        self.territory_tiles(player)
+            .into_iter()
+            .filter(|&(x, y)| {
+                [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+                    .iter()
+                    .any(|&(nx, ny)| !self.is_territory_of(nx, ny, player))
+            })
+            .collect()
+    }
+
+    // Removes roughly `fraction` of the player's land, peeling the outermost
+    // ring first. Within a ring, tiles furthest from the territory's center
+    // go first. Returns the tiles that were removed.
+    pub fn shrink_territory(&mut self, player: Entity, fraction: f32) -> Vec<(i32, i32)> {
+        let territory = self.territory_tiles(player);
+        let target = (territory.len() as f32 * fraction.clamp(0.0, 1.0)).ceil() as usize;
+        if territory.is_empty() || target == 0 {
+            return Vec::new();
+        }
+
+        let center = RegionSummary::of(&territory).centroid;
+
+        let mut removed = Vec::new();
+
+        while removed.len() < target {
+            let mut ring = self.border_tiles(player);
+            if ring.is_empty() {
+                break;
+            }
+
+            ring.sort_by(|a, b| {
+                let da = Vec2::new(a.0 as f32, a.1 as f32).distance_squared(center);
+                let db = Vec2::new(b.0 as f32, b.1 as f32).distance_squared(center);
+                db.total_cmp(&da)
+            });
+
+            for (x, y) in ring.into_iter().take(target - removed.len()) {
+                self.clear_owner(x, y);
+                removed.push((x, y));
+            }
+        }
+
+        removed
+    }
+
+    // Whether the player's land is all border, so one more ring clears it
+    pub fn last_ring(&self, player: Entity) -> bool {
+        self.border_tiles(player).len() == self.territory_tiles(player).len()
+    }
+
+    // 4-connected regions of the player's land, largest first
+    pub fn regions(&self, player: Entity) -> Vec<Vec<(i32, i32)>> {
+        layer_regions(self.width, self.height, &self.territory_layer(player))
+    }
+
+    // The player's land as a row-major bitset
+    pub fn territory_layer(&self, player: Entity) -> FixedBitSet {
+        let mut layer = FixedBitSet::with_capacity(self.cells.len());
+        for (i, cell) in self.cells.iter().enumerate() {
+            if cell.owner == Some(player) {
+                layer.insert(i);
+            }
+        }
+        layer
+    }
+
+    // Strips the outer `rings` rings of the player's land. The territory is
+    // kept in one piece: fragments cut off from the largest region by the
+    // erosion are removed too. More rings than the territory is deep clear
+    // it. Returns the tiles that were removed.
+    pub fn erode_territory(&mut self, player: Entity, rings: u32) -> Vec<(i32, i32)> {
+        let mut removed = Vec::new();
+
+        for _ in 0..rings {
+            let ring = self.border_tiles(player);
+            if ring.is_empty() {
+                break;
+            }
+
+            for &(x, y) in ring.iter() {
+                self.clear_owner(x, y);
+            }
+            removed.extend(ring);
+
+            for fragment in self.regions(player).into_iter().skip(1) {
+                for &(x, y) in fragment.iter() {
+                    self.clear_owner(x, y);
+                }
+                removed.extend(fragment);
+            }
+        }
+
+        removed
+    }
+}
+
+// Tiles between two positions counting diagonal steps as one
+pub fn king_distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
+// Void off the edge of the map's shape
+pub const VOID_COLOR: Color = Color::srgb(0.12, 0.13, 0.16);
+
+// How a tile looks with no trail on it: its owner's land, the empty
+// checkerboard, or void
+pub fn land_color(tile: &Tile, owner_color: Option<Color>) -> Color {
+    if tile.void {
+        return VOID_COLOR;
+    }
+    if let Some(color) = owner_color.filter(|_| tile.owner.is_some()) {
+        return color.with_alpha(0.5);
+    }
+
+    // Original color (checkerboard pattern)
+    let is_dark = (tile.x + tile.y) % 2 == 0;
+    if is_dark {
+        Color::srgb(0.8, 0.8, 0.8) // Light gray
+    } else {
+        Color::srgb(0.9, 0.9, 0.9) // Lighter gray
+    }
+}
+
+// Number of trail tiles currently laid down by the player
+pub fn trail_length<'a>(tiles: impl IntoIterator<Item = &'a Tile>, player: Entity) -> u32 {
+    tiles
+        .into_iter()
+        .filter(|tile| tile.trail_owner == Some(player))
+        .count() as u32
+}
+
+// Cells of a `width` x `height` grid (row-major bitset, set = blocked) that
+// are closed off from the rest of the map. Any open area that doesn't reach
+// the map edge is enclosed. The edge also counts as a wall, so a loop that
+// uses the border as one of its sides encloses the strip it hugs: of the
+// areas that do reach the edge, whichever is biggest is the outside and never
+// enclosed. Areas tied for biggest all count as outside, so a line cutting the
+// map into equal halves claims neither.
+pub fn enclosed_cells(width: i32, height: i32, blocked: &FixedBitSet) -> Vec<(i32, i32)> {
+    if width <= 0 || height <= 0 {
+        return Vec::new();
+    }
+
+    let mut open = blocked.clone();
+    open.grow((width * height) as usize);
+    open.toggle_range(..);
+
+    let mut unvisited = open.clone();
+    let mut enclosed = FixedBitSet::with_capacity(open.len());
+    let mut edge_areas = Vec::new();
+
+    for start in open.ones() {
+        if !unvisited[start] {
+            continue;
+        }
+        let runs = fill_runs(width, height, &mut unvisited, start);
+        let on_edge = runs
+            .iter()
+            .any(|&(y, left, right)| y == 0 || y == height - 1 || left == 0 || right == width - 1);
+        if on_edge {
+            edge_areas.push(runs);
+        } else {
+            set_runs(&mut enclosed, width, &runs);
+        }
+    }
+
+    let outside = edge_areas
+        .iter()
+        .map(|runs| runs_len(runs))
+        .max()
+        .unwrap_or(0);
+    for runs in edge_areas.iter().filter(|runs| runs_len(runs) < outside) {
+        set_runs(&mut enclosed, width, runs);
+    }
+
+    enclosed
+        .ones()
+        .map(|i| (i as i32 % width, i as i32 / width))
+        .collect()
+}
+
+// 4-connected regions of the set cells in a row-major layer, largest first
+pub fn layer_regions(width: i32, height: i32, layer: &FixedBitSet) -> Vec<Vec<(i32, i32)>> {
+    let mut unvisited = layer.clone();
+    let mut regions: Vec<Vec<(i32, i32)>> = Vec::new();
+
+    for start in layer.ones() {
+        if !unvisited[start] {
+            continue;
+        }
+        let runs = fill_runs(width, height, &mut unvisited, start);
+        regions.push(
+            runs.into_iter()
+                .flat_map(|(y, left, right)| (left..=right).map(move |x| (x, y)))
+                .collect(),
+        );
+    }
+
+    regions.sort_by_key(|region| std::cmp::Reverse(region.len()));
+    regions
+}
+
+// Scanline fill of the area of set cells around `start`, clearing it from
+// `unvisited` as it goes. Large grids only push one seed per horizontal run.
+// Returns the area as its runs, each (y, first x, last x).
+fn fill_runs(
+    width: i32,
+    height: i32,
+    unvisited: &mut FixedBitSet,
+    start: usize,
+) -> Vec<(i32, i32, i32)> {
+    let index = |x: i32, y: i32| (y * width + x) as usize;
+    let mut runs = Vec::new();
+    let mut seeds = vec![(start as i32 % width, start as i32 / width)];
+
+    while let Some((x, y)) = seeds.pop() {
+        if !unvisited[index(x, y)] {
+            continue;
+        }
+
+        // Grow the run left and right as far as it goes
+        let mut left = x;
+        while left > 0 && unvisited[index(left - 1, y)] {
+            left -= 1;
+        }
+        let mut right = x;
+        while right < width - 1 && unvisited[index(right + 1, y)] {
+            right += 1;
+        }
+
+        unvisited.set_range(index(left, y)..index(right, y) + 1, false);
+        runs.push((y, left, right));
+
+        // Seed the start of every open run directly above and below
+        for ny in [y - 1, y + 1] {
+            if ny < 0 || ny >= height {
+                continue;
+            }
+
+            let mut in_run = false;
+            for nx in left..=right {
+                let open = unvisited[index(nx, ny)];
+                if open && !in_run {
+                    seeds.push((nx, ny));
+                }
+                in_run = open;
+            }
+        }
+    }
+
+    runs
+}
+
+fn runs_len(runs: &[(i32, i32, i32)]) -> usize {
+    runs.iter()
+        .map(|&(_, left, right)| (right - left + 1) as usize)
+        .sum()
+}
+
+fn set_runs(layer: &mut FixedBitSet, width: i32, runs: &[(i32, i32, i32)]) {
+    for &(y, left, right) in runs {
+        layer.set_range(
+            (y * width + left) as usize..(y * width + right + 1) as usize,
+            true,
+        );
+    }
+}
+
+// The pockets of enclosed cells bordering any of `boundary`'s tiles, so a loop
+// doesn't pick up holes closed off by someone else. One trail can border
+// several separate pockets, each is returned on its own, largest first.
+pub fn pockets_touching(
+    width: i32,
+    height: i32,
+    enclosed: &[(i32, i32)],
+    boundary: &[(i32, i32)],
+) -> Vec<Vec<(i32, i32)>> {
+    let mut layer = FixedBitSet::with_capacity((width * height).max(0) as usize);
+    for &(x, y) in enclosed {
+        layer.insert((y * width + x) as usize);
+    }
+
+    let boundary: HashSet<(i32, i32)> = boundary.iter().copied().collect();
+
+    layer_regions(width, height, &layer)
+        .into_iter()
+        .filter(|pocket| {
+            pocket.iter().any(|&(x, y)| {
+                [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]
+                    .iter()
+                    .any(|neighbour| boundary.contains(neighbour))
+            })
+        })
+        .collect()
+}
+
+// Straight-line route between two tiles, horizontal leg first, excluding
+// `from` and including `to`
+pub fn manhattan_path(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut path = Vec::new();
+    let (mut x, mut y) = from;
+
+    while x != to.0 {
+        x += (to.0 - x).signum();
+        path.push((x, y));
+    }
+    while y != to.1 {
+        y += (to.1 - y).signum();
+        path.push((x, y));
+    }
+
+    path
+}
+
+// Tiles a loop would claim if its trail were closed along `closing_path`
+// right now: the closing path itself plus the pockets the trail encloses
+pub fn claim_preview(
+    width: i32,
+    height: i32,
+    mut blocked: FixedBitSet,
+    trail: &[(i32, i32)],
+    closing_path: &[(i32, i32)],
+) -> Vec<(i32, i32)> {
+    for &(x, y) in closing_path {
+        if x >= 0 && x < width && y >= 0 && y < height {
+            blocked.insert((y * width + x) as usize);
+        }
+    }
+
+    let boundary: Vec<(i32, i32)> = trail.iter().chain(closing_path).copied().collect();
+    let enclosed = enclosed_cells(width, height, &blocked);
+
+    let mut preview = pockets_touching(width, height, &enclosed, &boundary).concat();
+    preview.extend_from_slice(closing_path);
+    preview
+}
+
+// Inclusive rectangle of tiles, the safe area in the shrinking-zone mode and
+// the ground a hazard wall covers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ZoneBounds {
+    pub min: (i32, i32),
+    pub max: (i32, i32),
+}
+
+impl ZoneBounds {
+    pub fn whole_map(width: i32, height: i32) -> Self {
+        Self {
+            min: (0, 0),
+            max: (width - 1, height - 1),
+        }
+    }
+
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        x >= self.min.0 && x <= self.max.0 && y >= self.min.1 && y <= self.max.1
+    }
+
+    pub fn width(&self) -> i32 {
+        self.max.0 - self.min.0 + 1
+    }
+
+    pub fn height(&self) -> i32 {
+        self.max.1 - self.min.1 + 1
+    }
+
+    // One ring smaller on every side that is still wider than `min_size`
+    pub fn shrunk(&self, min_size: i32) -> Self {
+        let mut bounds = *self;
+        if self.width() - 2 >= min_size {
+            bounds.min.0 += 1;
+            bounds.max.0 -= 1;
+        }
+        if self.height() - 2 >= min_size {
+            bounds.min.1 += 1;
+            bounds.max.1 -= 1;
+        }
+        bounds
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        ((self.min.0 + self.max.0) / 2, (self.min.1 + self.max.1) / 2)
+    }
+
+    // Closest tile at least `margin` tiles in from the edge, or the centre
+    // where the bounds are too small for the margin
+    pub fn clamp(&self, (x, y): (i32, i32), margin: i32) -> (i32, i32) {
+        let axis = |value: i32, min: i32, max: i32| {
+            if max - min < margin * 2 {
+                (min + max) / 2
+            } else {
+                value.clamp(min + margin, max - margin)
+            }
+        };
+        (
+            axis(x, self.min.0, self.max.0),
+            axis(y, self.min.1, self.max.1),
+        )
+    }
+}
+
+// Which way a hazard wall travels across the map
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepDirection {
+    East,
+    North,
+    West,
+    South,
+}
+
+impl SweepDirection {
+    // Walls take turns coming in from each side
+    pub const CYCLE: [SweepDirection; 4] = [
+        SweepDirection::East,
+        SweepDirection::North,
+        SweepDirection::West,
+        SweepDirection::South,
+    ];
+
+    // One tile in the direction of travel
+    pub fn vector(self) -> Vec2 {
+        match self {
+            SweepDirection::East => Vec2::X,
+            SweepDirection::North => Vec2::Y,
+            SweepDirection::West => Vec2::NEG_X,
+            SweepDirection::South => Vec2::NEG_Y,
+        }
+    }
+
+    // Side of the map the wall comes in from
+    pub fn from_side(self) -> &'static str {
+        match self {
+            SweepDirection::East => "west",
+            SweepDirection::North => "south",
+            SweepDirection::West => "east",
+            SweepDirection::South => "north",
+        }
+    }
+}
+
+// A line of deadly tiles that crosses the map a row or column at a time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HazardWall {
+    pub direction: SweepDirection,
+    // First tile along the wall, and how many tiles it covers
+    pub offset: i32,
+    pub length: i32,
+    // Match seconds it starts moving at, and tiles a second after that
+    pub starts_at: f32,
+    pub speed: f32,
+}
+
+impl HazardWall {
+    // Rows or columns it has to cross to leave the map
+    pub fn lines(&self, width: i32, height: i32) -> i32 {
+        match self.direction {
+            SweepDirection::East | SweepDirection::West => width,
+            SweepDirection::North | SweepDirection::South => height,
+        }
+    }
+
+    // Lines crossed so far, None before it starts or once it's off the map
+    pub fn line_at(&self, elapsed: f32, width: i32, height: i32) -> Option<i32> {
+        if elapsed < self.starts_at {
+            return None;
+        }
+        let line = ((elapsed - self.starts_at) * self.speed).floor() as i32;
+        (line < self.lines(width, height)).then_some(line)
+    }
+
+    pub fn finished(&self, elapsed: f32, width: i32, height: i32) -> bool {
+        elapsed >= self.starts_at && self.line_at(elapsed, width, height).is_none()
+    }
+
+    // Tiles covered on the way from one line to another, counted from the
+    // side it comes in from
+    pub fn swept_bounds(&self, from: i32, to: i32, width: i32, height: i32) -> ZoneBounds {
+        let across = |line: i32| match self.direction {
+            SweepDirection::East | SweepDirection::North => line,
+            SweepDirection::West => width - 1 - line,
+            SweepDirection::South => height - 1 - line,
+        };
+        let (low, high) = (across(from).min(across(to)), across(from).max(across(to)));
+        let (start, end) = (self.offset, self.offset + self.length - 1);
+        match self.direction {
+            SweepDirection::East | SweepDirection::West => ZoneBounds {
+                min: (low, start),
+                max: (high, end),
+            },
+            SweepDirection::North | SweepDirection::South => ZoneBounds {
+                min: (start, low),
+                max: (end, high),
+            },
+        }
+    }
+
+    pub fn bounds(&self, line: i32, width: i32, height: i32) -> ZoneBounds {
+        self.swept_bounds(line, line, width, height)
+    }
+}
@@ -0,0 +1,264 @@
+// themes.rs
+// Themes restyle the game: tile textures, the player sprite, UI colors and
+// font, the sound effects and the announcer's voice lines. Each one is a
+// directory under assets/themes with a theme.ron manifest naming its files
+// relative to that directory.
+// Whatever is missing or unusable falls back to the built-in look, so a
+// broken community theme can't stop the game.
+use crate::events::SoundEffect;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Component, Path};
+
+// Relative to the assets directory, as the asset server sees it
+pub const THEMES_DIR: &str = "themes";
+pub const MANIFEST_FILE: &str = "theme.ron";
+
+#[derive(Asset, TypePath, Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ThemeManifest {
+    pub name: String,
+    pub tiles: TileTextures,
+    // Drawn tinted in each player's color, so best kept white
+    pub player: Option<String>,
+    pub ui: UiSkin,
+    pub sounds: ThemeSounds,
+    // Files for each announcer line, one picked at random each time
+    pub voice: BTreeMap<VoiceLine, Vec<String>>,
+}
+
+// The checkerboard's two kinds of tile
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TileTextures {
+    pub light: Option<String>,
+    pub dark: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct UiSkin {
+    pub button: Option<Color>,
+    // Replaces plain white text, colored text keeps its color
+    pub text: Option<Color>,
+    pub font: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ThemeSounds {
+    pub claim: Option<String>,
+    pub death: Option<String>,
+    pub click: Option<String>,
+    pub warning: Option<String>,
+}
+
+// What the announcer calls out, in order of importance when several come up
+// at once
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoiceLine {
+    Countdown,
+    Go,
+    MultiKill,
+    Kill,
+    Claim,
+}
+
+impl VoiceLine {
+    pub fn name(self) -> &'static str {
+        match self {
+            VoiceLine::Countdown => "countdown",
+            VoiceLine::Go => "go",
+            VoiceLine::MultiKill => "multi-kill",
+            VoiceLine::Kill => "kill",
+            VoiceLine::Claim => "claim",
+        }
+    }
+}
+
+// Voice lines a theme can actually play, as asset paths
+pub type VoiceFiles = BTreeMap<VoiceLine, Vec<String>>;
+
+const VOICE_EXTENSIONS: &[&str] = &["wav", "ogg"];
+
+// Every file a theme can replace
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ThemeSlot {
+    LightTile,
+    DarkTile,
+    Player,
+    Font,
+    Claim,
+    Death,
+    Click,
+    Warning,
+}
+
+impl ThemeSlot {
+    pub const ALL: [ThemeSlot; 8] = [
+        ThemeSlot::LightTile,
+        ThemeSlot::DarkTile,
+        ThemeSlot::Player,
+        ThemeSlot::Font,
+        ThemeSlot::Claim,
+        ThemeSlot::Death,
+        ThemeSlot::Click,
+        ThemeSlot::Warning,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemeSlot::LightTile => "light tile",
+            ThemeSlot::DarkTile => "dark tile",
+            ThemeSlot::Player => "player sprite",
+            ThemeSlot::Font => "font",
+            ThemeSlot::Claim => "claim sound",
+            ThemeSlot::Death => "death sound",
+            ThemeSlot::Click => "click sound",
+            ThemeSlot::Warning => "warning sound",
+        }
+    }
+
+    // File types the game is built to read for this slot
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            ThemeSlot::LightTile | ThemeSlot::DarkTile | ThemeSlot::Player => &["png"],
+            ThemeSlot::Font => &["ttf", "otf"],
+            ThemeSlot::Claim | ThemeSlot::Death | ThemeSlot::Click | ThemeSlot::Warning => {
+                &["wav", "ogg"]
+            }
+        }
+    }
+
+    pub fn sound(self) -> Option<SoundEffect> {
+        match self {
+            ThemeSlot::Claim => Some(SoundEffect::Claim),
+            ThemeSlot::Death => Some(SoundEffect::Death),
+            ThemeSlot::Click => Some(SoundEffect::UiClick),
+            ThemeSlot::Warning => Some(SoundEffect::TimerWarning),
+            _ => None,
+        }
+    }
+}
+
+// A theme's usable files, as asset paths. Slots without one use the
+// built-in look.
+pub type ThemeFiles = BTreeMap<ThemeSlot, String>;
+
+impl ThemeManifest {
+    pub fn file(&self, slot: ThemeSlot) -> Option<&String> {
+        match slot {
+            ThemeSlot::LightTile => self.tiles.light.as_ref(),
+            ThemeSlot::DarkTile => self.tiles.dark.as_ref(),
+            ThemeSlot::Player => self.player.as_ref(),
+            ThemeSlot::Font => self.ui.font.as_ref(),
+            ThemeSlot::Claim => self.sounds.claim.as_ref(),
+            ThemeSlot::Death => self.sounds.death.as_ref(),
+            ThemeSlot::Click => self.sounds.click.as_ref(),
+            ThemeSlot::Warning => self.sounds.warning.as_ref(),
+        }
+    }
+
+    // Checks the files the theme in `dir` names against what's on disk under
+    // `assets`. Returns the ones that can be loaded, and a line for each one
+    // that can't.
+    pub fn validate(&self, assets: &Path, dir: &str) -> (ThemeFiles, Vec<String>) {
+        let mut files = ThemeFiles::new();
+        let mut problems = Vec::new();
+        for slot in ThemeSlot::ALL {
+            let Some(file) = self.file(slot) else {
+                continue;
+            };
+            match check_file(assets, dir, file, slot.extensions()) {
+                Some(problem) => problems.push(format!("{} {} {}", slot.name(), file, problem)),
+                None => {
+                    files.insert(slot, format!("{}/{}", dir, file));
+                }
+            }
+        }
+        (files, problems)
+    }
+
+    // Same checks for the voice lines. Lines left without a usable file
+    // aren't called out.
+    pub fn validate_voice(&self, assets: &Path, dir: &str) -> (VoiceFiles, Vec<String>) {
+        let mut files = VoiceFiles::new();
+        let mut problems = Vec::new();
+        for (&line, line_files) in self.voice.iter() {
+            for file in line_files {
+                match check_file(assets, dir, file, VOICE_EXTENSIONS) {
+                    Some(problem) => {
+                        problems.push(format!("{} voice line {} {}", line.name(), file, problem))
+                    }
+                    None => files
+                        .entry(line)
+                        .or_default()
+                        .push(format!("{}/{}", dir, file)),
+                }
+            }
+        }
+        (files, problems)
+    }
+}
+
+// What's wrong with a file a theme names, if anything
+fn check_file(assets: &Path, dir: &str, file: &str, extensions: &[&str]) -> Option<String> {
+    let path = Path::new(file);
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+
+    // Themes stay inside their own directory
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        Some("isn't inside the theme's directory".to_string())
+    } else if !extension.is_some_and(|ext| extensions.contains(&ext.as_str())) {
+        Some(format!("should be {}", extensions.join(" or ")))
+    } else if !assets.join(dir).join(path).is_file() {
+        Some("is missing".to_string())
+    } else {
+        None
+    }
+}
+
+// Asset path of a theme's directory, None for names that aren't a single
+// directory
+pub fn theme_dir(name: &str) -> Option<String> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Some(format!("{}/{}", THEMES_DIR, name)),
+        _ => None,
+    }
+}
+
+// Reads theme.ron manifests for the asset server
+#[derive(Default)]
+pub struct ThemeManifestLoader;
+
+impl AssetLoader for ThemeManifestLoader {
+    type Asset = ThemeManifest;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<ThemeManifest, io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        ron::de::from_bytes(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["theme.ron"]
+    }
+}
@@ -0,0 +1,147 @@
+// Local single elimination tournaments. Every match is one on one, the
+// winner moves on to the next round until a single champion is left.
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+// Field sizes a bracket can be drawn for
+pub const BRACKET_SIZES: [usize; 3] = [4, 8, 16];
+// Tournament matches are shorter than a normal match, a 16 player bracket
+// is fifteen of them
+pub const TOURNAMENT_MATCH_SECONDS: f32 = 120.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Entrant {
+    // Played from the device that joined this slot
+    Human { slot: usize },
+    Bot,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Participant {
+    pub name: String,
+    pub entrant: Entrant,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BracketMatch {
+    // Participants by index, None until the match feeding the side is decided
+    pub sides: [Option<usize>; 2],
+    pub scores: Option<[u32; 2]>,
+    pub winner: Option<usize>,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct Tournament {
+    pub participants: Vec<Participant>,
+    // First round first, the final last
+    pub rounds: Vec<Vec<BracketMatch>>,
+    pub match_seconds: f32,
+}
+
+impl Tournament {
+    // Draws the bracket in the order given, first against second and so on.
+    // None unless the field is one of `BRACKET_SIZES`.
+    pub fn new(participants: Vec<Participant>) -> Option<Self> {
+        if !BRACKET_SIZES.contains(&participants.len()) {
+            return None;
+        }
+
+        let mut rounds = Vec::new();
+        let first = (0..participants.len() / 2)
+            .map(|index| BracketMatch {
+                sides: [Some(index * 2), Some(index * 2 + 1)],
+                ..default()
+            })
+            .collect();
+        rounds.push(first);
+        let mut matches = participants.len() / 4;
+        while matches > 0 {
+            rounds.push(vec![BracketMatch::default(); matches]);
+            matches /= 2;
+        }
+
+        Some(Self {
+            participants,
+            rounds,
+            match_seconds: TOURNAMENT_MATCH_SECONDS,
+        })
+    }
+
+    // The joined players plus enough bots to fill the field, in a random draw
+    pub fn random_draw(humans: usize, size: usize) -> Option<Self> {
+        let mut participants: Vec<Participant> = (0..humans.min(size))
+            .map(|slot| Participant {
+                name: format!("P{}", slot + 1),
+                entrant: Entrant::Human { slot },
+            })
+            .chain((1..).map(|bot| Participant {
+                name: format!("Bot {}", bot),
+                entrant: Entrant::Bot,
+            }))
+            .take(size)
+            .collect();
+        participants.shuffle(&mut rand::rng());
+        Self::new(participants)
+    }
+
+    // Whether any match has been played yet
+    pub fn started(&self) -> bool {
+        self.rounds
+            .iter()
+            .flatten()
+            .any(|bracket_match| bracket_match.winner.is_some())
+    }
+
+    // Round and index of the next match to play, earliest rounds first
+    pub fn next_match(&self) -> Option<(usize, usize)> {
+        self.rounds.iter().enumerate().find_map(|(round, matches)| {
+            matches
+                .iter()
+                .position(|bracket_match| {
+                    bracket_match.winner.is_none()
+                        && bracket_match.sides.iter().all(Option::is_some)
+                })
+                .map(|index| (round, index))
+        })
+    }
+
+    // Settles a match on its scores and moves the winner on. A tie goes to
+    // the first side, who was drawn higher.
+    pub fn record(&mut self, round: usize, index: usize, scores: [u32; 2]) -> Option<usize> {
+        let bracket_match = self.rounds.get_mut(round)?.get_mut(index)?;
+        let side = if scores[1] > scores[0] { 1 } else { 0 };
+        let winner = bracket_match.sides[side]?;
+        bracket_match.scores = Some(scores);
+        bracket_match.winner = Some(winner);
+
+        if let Some(next) = self
+            .rounds
+            .get_mut(round + 1)
+            .and_then(|matches| matches.get_mut(index / 2))
+        {
+            next.sides[index % 2] = Some(winner);
+        }
+        Some(winner)
+    }
+
+    // Winner of the final, once it's been played
+    pub fn champion(&self) -> Option<&Participant> {
+        let winner = self.rounds.last()?.first()?.winner?;
+        self.participants.get(winner)
+    }
+
+    pub fn name(&self, participant: Option<usize>) -> &str {
+        participant
+            .and_then(|index| self.participants.get(index))
+            .map_or("---", |participant| participant.name.as_str())
+    }
+
+    pub fn round_name(&self, round: usize) -> String {
+        match self.rounds.len().saturating_sub(round) {
+            1 => "Final".to_string(),
+            2 => "Semifinals".to_string(),
+            3 => "Quarterfinals".to_string(),
+            _ => format!("Round {}", round + 1),
+        }
+    }
+}
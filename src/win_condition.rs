@@ -0,0 +1,411 @@
+// Win conditions written as small expressions in the rules, checked for
+// every player each tick, e.g. "territory_pct >= 0.5 || kills >= 5".
+//
+// Numbers, the variables below, arithmetic (+ - * /), comparisons
+// (< <= > >= == !=), && || ! and parentheses. Anything non-zero is true.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// What an expression can ask about a player
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WinVariables {
+    // Tiles held
+    pub territory: f32,
+    // Share of what the map is worth held, 0 to 1
+    pub territory_pct: f32,
+    pub score: f32,
+    pub kills: f32,
+    pub deaths: f32,
+    // Seconds since GO, and left on the clock
+    pub elapsed: f32,
+    pub time_left: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Variable {
+    Territory,
+    TerritoryPct,
+    Score,
+    Kills,
+    Deaths,
+    Elapsed,
+    TimeLeft,
+}
+
+impl Variable {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "territory" => Variable::Territory,
+            "territory_pct" => Variable::TerritoryPct,
+            "score" => Variable::Score,
+            "kills" => Variable::Kills,
+            "deaths" => Variable::Deaths,
+            "elapsed" => Variable::Elapsed,
+            "time_left" => Variable::TimeLeft,
+            _ => return None,
+        })
+    }
+
+    fn value(self, variables: &WinVariables) -> f32 {
+        match self {
+            Variable::Territory => variables.territory,
+            Variable::TerritoryPct => variables.territory_pct,
+            Variable::Score => variables.score,
+            Variable::Kills => variables.kills,
+            Variable::Deaths => variables.deaths,
+            Variable::Elapsed => variables.elapsed,
+            Variable::TimeLeft => variables.time_left,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOp {
+    Or,
+    And,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f32),
+    Variable(Variable),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+fn truth(value: bool) -> f32 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl Expr {
+    fn eval(&self, variables: &WinVariables) -> f32 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Variable(variable) => variable.value(variables),
+            Expr::Not(inner) => truth(inner.eval(variables) == 0.0),
+            Expr::Negate(inner) => -inner.eval(variables),
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(variables);
+                // Short-circuits, like it reads
+                match op {
+                    BinaryOp::Or if left != 0.0 => return 1.0,
+                    BinaryOp::And if left == 0.0 => return 0.0,
+                    _ => {}
+                }
+                let right = right.eval(variables);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => truth(right != 0.0),
+                    BinaryOp::Less => truth(left < right),
+                    BinaryOp::LessEqual => truth(left <= right),
+                    BinaryOp::Greater => truth(left > right),
+                    BinaryOp::GreaterEqual => truth(left >= right),
+                    BinaryOp::Equal => truth(left == right),
+                    BinaryOp::NotEqual => truth(left != right),
+                    BinaryOp::Add => left + right,
+                    BinaryOp::Subtract => left - right,
+                    BinaryOp::Multiply => left * right,
+                    // Dividing by zero gives zero rather than infinity
+                    BinaryOp::Divide if right == 0.0 => 0.0,
+                    BinaryOp::Divide => left / right,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WinConditionError {
+    pub message: String,
+    // Byte offset into the expression
+    pub position: usize,
+}
+
+impl fmt::Display for WinConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for WinConditionError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+// Longest first, so ">=" isn't read as ">" then "="
+const OPERATORS: [&str; 14] = [
+    "||", "&&", "<=", ">=", "==", "!=", "<", ">", "!", "+", "-", "*", "/", "=",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, WinConditionError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let position = source.len() - rest.len();
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let (token, len) = if c.is_ascii_digit() || c == '.' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let value = rest[..len].parse().map_err(|_| WinConditionError {
+                message: format!("bad number '{}'", &rest[..len]),
+                position,
+            })?;
+            (Token::Number(value), len)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (Token::Ident(rest[..len].to_string()), len)
+        } else if c == '(' {
+            (Token::Open, 1)
+        } else if c == ')' {
+            (Token::Close, 1)
+        } else {
+            let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+                return Err(WinConditionError {
+                    message: format!("unexpected '{}'", c),
+                    position,
+                });
+            };
+            // A lone "=" is almost certainly a typo for "=="
+            if *op == "=" {
+                return Err(WinConditionError {
+                    message: "'=' should be '=='".to_string(),
+                    position,
+                });
+            }
+            (Token::Op(op), op.len())
+        };
+        tokens.push((position, token));
+        rest = &rest[len..];
+    }
+    Ok(tokens)
+}
+
+// Deepest an expression may nest, counting brackets, operators and the
+// operands they chain, so a hostile config can't overflow the stack
+const MAX_DEPTH: usize = 64;
+
+// A parsed subexpression and how deep it nests
+type Parsed = Result<(Expr, usize), WinConditionError>;
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize,
+    // Brackets and unary operators the parser is currently inside
+    nesting: usize,
+}
+
+impl Parser {
+    fn peek_op(&self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.get(self.next) {
+            Some((_, Token::Op(op))) if ops.contains(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn error(&self, message: &str) -> WinConditionError {
+        WinConditionError {
+            message: message.to_string(),
+            position: self
+                .tokens
+                .get(self.next)
+                .map_or(self.end, |(position, _)| *position),
+        }
+    }
+
+    // A node `depth` levels deep, unless that's too deep
+    fn node(&self, expr: Expr, depth: usize) -> Parsed {
+        if depth > MAX_DEPTH {
+            return Err(self.error("expression nested too deeply"));
+        }
+        Ok((expr, depth))
+    }
+
+    // Steps into a bracket or unary operator, refusing to go too deep before
+    // the parser recurses any further
+    fn nested(&mut self, inner: fn(&mut Self) -> Parsed) -> Parsed {
+        if self.nesting >= MAX_DEPTH {
+            return Err(self.error("expression nested too deeply"));
+        }
+        self.nesting += 1;
+        let parsed = inner(self);
+        self.nesting -= 1;
+        parsed
+    }
+
+    // One level of left-associative binary operators
+    fn binary(
+        &mut self,
+        ops: &[(&'static str, BinaryOp)],
+        operand: fn(&mut Self) -> Parsed,
+    ) -> Parsed {
+        let names: Vec<&'static str> = ops.iter().map(|(name, _)| *name).collect();
+        let (mut left, mut depth) = operand(self)?;
+        while let Some(op) = self
+            .peek_op(&names)
+            .and_then(|name| ops.iter().find(|(op, _)| *op == name).map(|(_, op)| *op))
+        {
+            self.next += 1;
+            let (right, right_depth) = operand(self)?;
+            (left, depth) = self.node(
+                Expr::Binary(op, Box::new(left), Box::new(right)),
+                depth.max(right_depth) + 1,
+            )?;
+        }
+        Ok((left, depth))
+    }
+
+    fn or(&mut self) -> Parsed {
+        self.binary(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Parsed {
+        self.binary(&[("&&", BinaryOp::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Parsed {
+        self.binary(
+            &[
+                ("<", BinaryOp::Less),
+                ("<=", BinaryOp::LessEqual),
+                (">", BinaryOp::Greater),
+                (">=", BinaryOp::GreaterEqual),
+                ("==", BinaryOp::Equal),
+                ("!=", BinaryOp::NotEqual),
+            ],
+            Self::sum,
+        )
+    }
+
+    fn sum(&mut self) -> Parsed {
+        self.binary(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Subtract)],
+            Self::product,
+        )
+    }
+
+    fn product(&mut self) -> Parsed {
+        self.binary(
+            &[("*", BinaryOp::Multiply), ("/", BinaryOp::Divide)],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Parsed {
+        let op = match self.peek_op(&["!", "-"]) {
+            Some("!") => Expr::Not,
+            Some(_) => Expr::Negate,
+            None => return self.primary(),
+        };
+        self.next += 1;
+        let (inner, depth) = self.nested(Self::unary)?;
+        self.node(op(Box::new(inner)), depth + 1)
+    }
+
+    fn primary(&mut self) -> Parsed {
+        let Some((position, token)) = self.tokens.get(self.next).cloned() else {
+            return Err(self.error("expression ends early"));
+        };
+        self.next += 1;
+        match token {
+            Token::Number(value) => Ok((Expr::Number(value), 1)),
+            Token::Ident(name) => Variable::parse(&name)
+                .map(|variable| (Expr::Variable(variable), 1))
+                .ok_or(WinConditionError {
+                    message: format!("unknown variable '{}'", name),
+                    position,
+                }),
+            Token::Open => {
+                let inner = self.nested(Self::or)?;
+                match self.tokens.get(self.next) {
+                    Some((_, Token::Close)) => {
+                        self.next += 1;
+                        Ok(inner)
+                    }
+                    _ => Err(self.error("missing ')'")),
+                }
+            }
+            Token::Close | Token::Op(_) => {
+                self.next -= 1;
+                Err(self.error("expected a number or variable"))
+            }
+        }
+    }
+}
+
+// A parsed win condition. Kept as its source in config files.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct WinCondition {
+    source: String,
+    expr: Expr,
+}
+
+impl WinCondition {
+    pub fn parse(source: &str) -> Result<Self, WinConditionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            next: 0,
+            end: source.len(),
+            nesting: 0,
+        };
+        let (expr, _) = parser.or()?;
+        if parser.next < parser.tokens.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // Whether a player with these numbers has won
+    pub fn met(&self, variables: &WinVariables) -> bool {
+        self.expr.eval(variables) != 0.0
+    }
+}
+
+impl TryFrom<String> for WinCondition {
+    type Error = WinConditionError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Self::parse(&source)
+    }
+}
+
+impl From<WinCondition> for String {
+    fn from(condition: WinCondition) -> Self {
+        condition.source
+    }
+}
@@ -0,0 +1,4097 @@
+// Drives the simulation headless: no window, renderer or audio, just
+// `GamePlugin` stepped frame by frame with synthetic keyboard input.
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::tasks::{futures_lite::future, AsyncComputeTaskPool, Task};
+use bevy::time::TimeUpdateStrategy;
+use bevy::winit::UpdateMode;
+use fixedbitset::FixedBitSet;
+use landio::balance::BalanceReport;
+use landio::brain::{
+    BotBrain, BotDifficulty, BotTuning, BrainInput, PlayerView, RegisterBotBrain, WorldSnapshot,
+    ZoneView,
+};
+use landio::components::{
+    GridSettings, GridTopology, LocalPlayer, MapShape, Player, Respawning, Spectating, Tile, Tiles,
+    Trail, UpperFloor, Upright, ValueZone,
+};
+use landio::config::GameConfig;
+use landio::events::{
+    BountyClaimedEvent, ClaimComputedEvent, EmoteEvent, MatchEndedEvent, MatchTimerEvent,
+    MultiKillEvent, PlayerDeathEvent, PlayerDeathReason, Standing, TerritoryClaimedEvent,
+    TimerMilestone, ToastEvent, ToastIcon, ToastPriority,
+};
+use landio::grid::GridMath;
+use landio::headless::{run_batch, HeadlessMatch, MatchSetup, MatchSummary};
+use landio::levels::Campaign;
+use landio::paths::Paths;
+use landio::profiles::{ActiveProfiles, Profile, ProfileStats, ProfileStore};
+use landio::progression::{
+    level_for_xp, unlocked_colors, unlocked_maps, unlocked_trail_styles, xp_for_level,
+    xp_for_match, TrailStyle,
+};
+use landio::resources::{
+    ActivePreset, BountyRules, ClaimResult, ComebackRules, DeathPenalty, DifficultyBounds,
+    EnergyRules, GameRules, GameSpeed, GameState, HazardRules, MatchSeed, OwnershipLayers,
+    PendingClaims, RespawnLocation, RulesPreset, TerritoryAnalysis, TrailPointRules, WeatherRules,
+    ZoneRules, PLAYER_SPEED,
+};
+use landio::share::{ShareCode, ShareCodeError, SHARE_CODE_LENGTH};
+use landio::states::{AppState, PauseState};
+use landio::stats::{StatsStore, TileCounts};
+use landio::systems::abilities::{Ability, Energy};
+use landio::systems::analysis::sync_ownership_layers_system;
+use landio::systems::audio::VoiceCooldowns;
+use landio::systems::board::{update_board_system, value_dot_color, Board};
+use landio::systems::bots::{Bot, LoopBrain};
+use landio::systems::bounty::BountyTarget;
+use landio::systems::camera::CameraMode;
+use landio::systems::challenges::{ChallengeRecord, MatchResult, WeeklyChallenges, WeeklyGoal};
+use landio::systems::cinematic::{end_of_match_shots, territory_bounds, CameraShot, CameraTween};
+use landio::systems::collision::{LagCompensation, TrailHistory};
+use landio::systems::comeback::Comeback;
+use landio::systems::countdown::{MatchCountdown, COUNTDOWN_SECONDS};
+use landio::systems::daily::{ChallengeOutcome, DailyChallenge};
+use landio::systems::decoy::{Decoy, DecoyTrail};
+use landio::systems::difficulty::{
+    calibrate_bot_difficulty_system, record_bot_matches_system, BotDifficultySetting,
+};
+use landio::systems::director::DifficultyDirector;
+use landio::systems::display::{
+    DisplayRevert, DisplaySettings, FrameCap, WindowModeSetting, REVERT_SECONDS,
+};
+use landio::systems::emotes::{BotChatter, Emote};
+use landio::systems::floors::ActiveFloor;
+use landio::systems::hazards::HazardSchedule;
+use landio::systems::input::{
+    key_name, BindDirection, ControlSettings, DirectionIntent, InputDevice, InputScript,
+    InputSource, KeyBindings,
+};
+use landio::systems::join::{
+    current_share_code, cycle_game_speed_system, JoinedPlayers, SelectedMap,
+};
+use landio::systems::pause::WindowFocus;
+use landio::systems::photo::{PhotoFilter, PhotoMode, MAX_PHOTO_SCALE, MIN_PHOTO_SCALE};
+use landio::systems::pickups::{pickup_spawn_weights, Ghost, Pickup, PickupKind, SpeedBoost};
+use landio::systems::projection::{
+    project_view_system, reproject_on_change_system, ViewProjection,
+};
+use landio::systems::puzzle::{ActiveLevel, LevelEnemy};
+use landio::systems::quick_play::{quick_play_system, QuickPlay, QuickPlaySetup};
+use landio::systems::rating::rating_changes;
+use landio::systems::sandbox::{SandboxEvent, SandboxSettings};
+use landio::systems::stats::{Award, MatchAward, MatchStats, MULTI_KILL_SECONDS};
+use landio::systems::telemetry::{TelemetryFormat, TelemetrySettings};
+use landio::systems::toasts::ToastQueue;
+use landio::systems::tournament::TournamentMatch;
+use landio::systems::trails::poll_claim_tasks_system;
+use landio::systems::weather::{Vision, WeatherFront, WeatherKind, WeatherSchedule};
+use landio::systems::zone::SafeZone;
+use landio::territory::{
+    enclosed_cells, pockets_touching, SweepDirection, TileMap, TileState, ZoneBounds,
+};
+use landio::themes::{theme_dir, ThemeManifest, ThemeSlot, VoiceLine};
+use landio::tournament::{Entrant, Participant, Tournament};
+use landio::win_condition::{WinCondition, WinVariables};
+use landio::GamePlugin;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const FRAME: Duration = Duration::from_millis(1000 / 60);
+
+// App sitting on the join screen
+fn join_screen_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin, GamePlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .init_resource::<ButtonInput<KeyCode>>();
+    app.update();
+    app
+}
+
+// App with one WASD player in a match that's past its countdown
+fn headless_app() -> App {
+    let mut app = join_screen_app();
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    app.update();
+
+    // Countdown plus a frame for the match to start
+    let frames = (COUNTDOWN_SECONDS / FRAME.as_secs_f32()).ceil() as usize + 1;
+    for _ in 0..frames {
+        app.update();
+    }
+    app
+}
+
+fn player_entity(app: &mut App) -> Entity {
+    let world = app.world_mut();
+    world.query_filtered::<Entity, With<Player>>().single(world)
+}
+
+// The one player that isn't a bot
+fn human_entity(app: &mut App) -> Entity {
+    let world = app.world_mut();
+    world
+        .query_filtered::<Entity, (With<Player>, Without<Bot>)>()
+        .single(world)
+}
+
+fn player(app: &mut App) -> &Player {
+    let entity = player_entity(app);
+    app.world().get::<Player>(entity).unwrap()
+}
+
+fn owned_tiles(app: &mut App, owner: Entity) -> (usize, usize) {
+    let (mut land, mut trail) = (0, 0);
+    for tile in app.world().resource::<Tiles>().iter() {
+        if tile.owner == Some(owner) {
+            land += 1;
+        }
+        if tile.trail_owner == Some(owner) {
+            trail += 1;
+        }
+    }
+    (land, trail)
+}
+
+// Holds `key` until the player's tile satisfies `reached`
+fn steer(app: &mut App, key: KeyCode, reached: impl Fn((i32, i32)) -> bool) {
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(key);
+
+    for _ in 0..600 {
+        app.update();
+        if reached(player(app).last_tile_pos) {
+            break;
+        }
+    }
+
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .release(key);
+}
+
+fn run_frames(app: &mut App, frames: usize) {
+    for _ in 0..frames {
+        app.update();
+    }
+}
+
+#[test]
+fn players_start_with_a_five_by_five_territory() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+
+    assert_eq!(owned_tiles(&mut app, entity), (25, 0));
+    assert_eq!(player(&mut app).score, 25);
+}
+
+#[test]
+fn players_are_locked_in_place_until_the_countdown_ends() {
+    let mut app = join_screen_app();
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyD);
+    run_frames(&mut app, 60);
+
+    assert!(app.world().contains_resource::<MatchCountdown>());
+    assert!(!app.world().resource::<GameState>().game_running);
+    assert_eq!(player(&mut app).last_tile_pos, (20, 15));
+
+    run_frames(&mut app, 150);
+
+    assert!(!app.world().contains_resource::<MatchCountdown>());
+    assert!(app.world().resource::<GameState>().game_running);
+    assert!(player(&mut app).last_tile_pos.0 > 20);
+}
+
+#[test]
+fn leaving_territory_lays_down_a_trail() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+
+    // The starting 5x5 block spans x 18..=22 around the centre tile
+    steer(&mut app, KeyCode::KeyD, |(x, _)| x >= 25);
+
+    let (land, trail) = owned_tiles(&mut app, entity);
+    assert_eq!(land, 25);
+    assert!(
+        trail >= 2,
+        "expected a trail outside the territory, got {trail}"
+    );
+}
+
+#[test]
+fn players_step_tile_by_tile_and_only_turn_on_arrival() {
+    let mut app = headless_app();
+    let grid = GridMath::new(app.world().resource::<GridSettings>());
+
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyD);
+    let mut visited = vec![player(&mut app).last_tile_pos];
+    let mut turned_at = None;
+    for frame in 0..120 {
+        app.update();
+        let entity = player_entity(&mut app);
+        let translation = app.world().get::<Transform>(entity).unwrap().translation;
+        let player = player(&mut app);
+        let (tile, progress, direction) = (player.last_tile_pos, player.progress, player.direction);
+        assert!((0.0..1.0).contains(&progress));
+
+        // Drawn where the logical state says, never between two rows
+        let expected = grid.center_of(tile.0, tile.1) + direction * progress * grid.tile_size;
+        assert!(translation.truncate().distance(expected) < 1e-3);
+
+        if visited.last() != Some(&tile) {
+            visited.push(tile);
+        }
+
+        // Ask to go up partway between tiles
+        if frame == 30 {
+            assert!(progress > 0.0);
+            let mut input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            input.release(KeyCode::KeyD);
+            input.press(KeyCode::KeyW);
+        }
+        if turned_at.is_none() && direction == Vec2::Y {
+            assert!(progress < 0.5);
+            turned_at = Some(tile);
+        }
+    }
+
+    // Every tile on the way was stood on once, each a single step on
+    let turned_at = turned_at.expect("the buffered turn was never taken");
+    for pair in visited.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        assert_eq!((a.0 - b.0).abs() + (a.1 - b.1).abs(), 1, "{visited:?}");
+    }
+    assert!(visited.contains(&turned_at));
+    assert!(visited.iter().all(|&(x, y)| y == 15 || x == turned_at.0));
+}
+
+#[test]
+fn fast_players_keep_the_distance_past_each_tile_and_lay_every_one() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+    // Well over a tile a frame
+    app.world_mut().get_mut::<Player>(entity).unwrap().speed = 100.0;
+    let start = player(&mut app).last_tile_pos;
+
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyD);
+    run_frames(&mut app, 8);
+
+    let player = player(&mut app);
+    let covered = (player.last_tile_pos.0 - start.0) as f32 + player.progress;
+    let expected = 100.0 * 8.0 * FRAME.as_secs_f32();
+    assert!((covered - expected).abs() < 1e-3, "{covered} {expected}");
+
+    let (x, y) = player.last_tile_pos;
+    let tiles = app.world().resource::<Tiles>();
+    assert!((start.0 + 3..x).all(|x| tiles
+        .get(x, y)
+        .is_some_and(|tile| tile.trail_owner == Some(entity))));
+}
+
+#[test]
+fn territory_analysis_counts_and_measures_each_region() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+    let (cx, cy) = player(&mut app).spawn_tile;
+
+    // A ring of land off in the corner, around a tile that isn't ours
+    for (tile, _) in app.world_mut().resource_mut::<Tiles>().iter_mut() {
+        let ring = (2..=4).contains(&tile.x) && (2..=4).contains(&tile.y);
+        if ring && (tile.x, tile.y) != (3, 3) {
+            tile.owner = Some(entity);
+        }
+    }
+    run_frames(&mut app, 2);
+
+    let analysis = app.world().resource::<TerritoryAnalysis>();
+    let regions = analysis.get(entity).expect("analysed");
+    assert_eq!(regions.region_count(), 2);
+
+    // The starting block, largest first, centred on the spawn
+    let start = regions.largest().unwrap();
+    assert_eq!(start.size, 25);
+    assert!(start.centroid.distance(Vec2::new(cx as f32, cy as f32)) < 1e-4);
+
+    // The hole doesn't split the ring or count towards it
+    let ring = regions.regions[1];
+    assert_eq!(ring.size, 8);
+    assert!(ring.centroid.distance(Vec2::new(3.0, 3.0)) < 1e-4);
+}
+
+// Match seconds and tiles covered by the lone player heading right for a
+// real second at the given speed
+fn one_second_at(speed: GameSpeed) -> (f32, f32) {
+    let mut app = join_screen_app();
+    app.world_mut().resource_mut::<GameRules>().game_speed = speed;
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    while app.world().contains_resource::<MatchCountdown>()
+        || !app.world().resource::<GameState>().game_running
+    {
+        app.update();
+    }
+
+    assert_eq!(player(&mut app).speed, PLAYER_SPEED);
+    let elapsed = app.world().resource::<GameState>().timer.elapsed_secs();
+    let start = player(&mut app).last_tile_pos.0;
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyD);
+    run_frames(&mut app, 60);
+    let covered = (
+        app.world().resource::<GameState>().timer.elapsed_secs() - elapsed,
+        (player(&mut app).last_tile_pos.0 - start) as f32 + player(&mut app).progress,
+    );
+
+    // Back on the join screen everything runs at real time again
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Join);
+    run_frames(&mut app, 2);
+    assert_eq!(
+        app.world().resource::<Time<Virtual>>().relative_speed(),
+        1.0
+    );
+    covered
+}
+
+#[test]
+fn game_speed_sets_the_pace_of_movement_and_the_clock() {
+    let (slow_clock, slow_tiles) = one_second_at(GameSpeed::Slow);
+    let (normal_clock, normal_tiles) = one_second_at(GameSpeed::Normal);
+    let (blitz_clock, blitz_tiles) = one_second_at(GameSpeed::Blitz);
+
+    for (clock, speed) in [
+        (slow_clock, GameSpeed::Slow),
+        (normal_clock, GameSpeed::Normal),
+        (blitz_clock, GameSpeed::Blitz),
+    ] {
+        assert!(
+            (clock - speed.time_scale()).abs() < 0.05,
+            "{speed:?}: {clock}"
+        );
+    }
+    assert!(slow_tiles < normal_tiles && normal_tiles < blitz_tiles);
+    // The faster clock alone speeds players up, nothing on top of it
+    for (clock, tiles) in [
+        (slow_clock, slow_tiles),
+        (normal_clock, normal_tiles),
+        (blitz_clock, blitz_tiles),
+    ] {
+        assert!(
+            (tiles - PLAYER_SPEED * clock).abs() < 0.2,
+            "{tiles} tiles in {clock}s"
+        );
+    }
+}
+
+#[test]
+fn closing_a_loop_claims_the_enclosed_tiles() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+
+    // Out to the right, up over the top of the territory, left, and back down
+    // into it
+    steer(&mut app, KeyCode::KeyD, |(x, _)| x >= 25);
+    steer(&mut app, KeyCode::KeyW, |(_, y)| y >= 19);
+    steer(&mut app, KeyCode::KeyA, |(x, _)| x <= 21);
+    steer(&mut app, KeyCode::KeyS, |(_, y)| y <= 17);
+    run_frames(&mut app, 10);
+
+    let (land, trail) = owned_tiles(&mut app, entity);
+    assert_eq!(trail, 0);
+    assert!(land > 25, "expected the loop to add territory, got {land}");
+    assert_eq!(player(&mut app).score as usize, land);
+}
+
+#[test]
+fn losing_window_focus_pauses_a_local_match_until_a_key_is_pressed() {
+    let mut app = headless_app();
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyD);
+    run_frames(&mut app, 10);
+    // Nothing clears the keys between frames here, so D isn't new any more
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .clear();
+
+    app.world_mut().resource_mut::<WindowFocus>().focused = false;
+    run_frames(&mut app, 2);
+    let paused_at = player(&mut app).last_tile_pos;
+    let clock = app.world().resource::<GameState>().timer.elapsed_secs();
+    run_frames(&mut app, 60);
+
+    assert_eq!(
+        *app.world().resource::<State<PauseState>>().get(),
+        PauseState::Paused
+    );
+    assert_eq!(player(&mut app).last_tile_pos, paused_at);
+    assert_eq!(
+        app.world().resource::<GameState>().timer.elapsed_secs(),
+        clock
+    );
+
+    // Coming back isn't enough, it takes a key
+    app.world_mut().resource_mut::<WindowFocus>().focused = true;
+    run_frames(&mut app, 10);
+    assert_eq!(player(&mut app).last_tile_pos, paused_at);
+
+    let mut input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    input.release(KeyCode::KeyD);
+    input.clear();
+    input.press(KeyCode::KeyD);
+    run_frames(&mut app, 30);
+    assert_eq!(
+        *app.world().resource::<State<PauseState>>().get(),
+        PauseState::Running
+    );
+    assert!(player(&mut app).last_tile_pos.0 > paused_at.0);
+}
+
+#[test]
+fn losing_window_focus_leaves_a_match_with_several_local_players_running() {
+    let mut app = join_screen_app();
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd, InputDevice::KeyboardArrows],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    run_frames(&mut app, 3);
+
+    app.world_mut().resource_mut::<WindowFocus>().focused = false;
+    run_frames(&mut app, 5);
+    assert_eq!(
+        *app.world().resource::<State<PauseState>>().get(),
+        PauseState::Running
+    );
+}
+
+#[test]
+fn photo_mode_holds_the_match_and_sizes_photos_up_from_the_window() {
+    let mut app = headless_app();
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyD);
+    run_frames(&mut app, 10);
+
+    app.world_mut()
+        .resource_mut::<NextState<PauseState>>()
+        .set(PauseState::Photo);
+    run_frames(&mut app, 2);
+    let held_at = player(&mut app).last_tile_pos;
+    let clock = app.world().resource::<GameState>().timer.elapsed_secs();
+    run_frames(&mut app, 60);
+    assert_eq!(player(&mut app).last_tile_pos, held_at);
+    assert_eq!(
+        app.world().resource::<GameState>().timer.elapsed_secs(),
+        clock
+    );
+
+    app.world_mut()
+        .resource_mut::<NextState<PauseState>>()
+        .set(PauseState::Running);
+    run_frames(&mut app, 30);
+    assert!(player(&mut app).last_tile_pos.0 > held_at.0);
+
+    let mut photo = PhotoMode::new(Vec2::ZERO, 1.0);
+    assert_eq!(
+        photo.photo_size(UVec2::new(1280, 720)),
+        UVec2::new(2560, 1440)
+    );
+    photo.set_scale(8);
+    assert_eq!(photo.scale(), MAX_PHOTO_SCALE);
+    photo.set_scale(1);
+    assert_eq!(photo.scale(), MIN_PHOTO_SCALE);
+    // Filters come round again, and only the plain one leaves colors alone
+    let mut filter = PhotoFilter::None;
+    for _ in PhotoFilter::ALL {
+        filter = filter.next();
+        assert_eq!(filter.grading().is_none(), filter == PhotoFilter::None);
+    }
+    assert_eq!(filter, PhotoFilter::None);
+}
+
+#[test]
+fn running_into_your_own_trail_kills_you() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+
+    // A tight clockwise spiral outside the territory that crosses itself
+    steer(&mut app, KeyCode::KeyD, |(x, _)| x >= 26);
+    steer(&mut app, KeyCode::KeyW, |(_, y)| y >= 17);
+    steer(&mut app, KeyCode::KeyA, |(x, _)| x <= 25);
+    steer(&mut app, KeyCode::KeyS, |(_, y)| y <= 15);
+    run_frames(&mut app, 30);
+
+    // Dead players wait out the respawn delay with their trail wiped
+    assert!(app.world().get::<Respawning>(entity).is_some());
+    assert_eq!(owned_tiles(&mut app, entity), (25, 0));
+}
+
+// Gives the player every tile in the rectangle
+fn give_land(
+    map: &mut TileMap,
+    player: Entity,
+    xs: std::ops::Range<i32>,
+    ys: std::ops::Range<i32>,
+) {
+    for y in ys {
+        for x in xs.clone() {
+            map.set(
+                x,
+                y,
+                TileState {
+                    owner: Some(player),
+                    trail_owner: None,
+                },
+            );
+        }
+    }
+}
+
+#[test]
+fn shrinking_territory_peels_the_outermost_ring_first() {
+    let player = Entity::from_raw(1);
+    let five_by_five = || {
+        let mut map = TileMap::new(12, 12);
+        give_land(&mut map, player, 2..7, 2..7);
+        map
+    };
+
+    // Corners stick out furthest, so they go before the rest of the ring
+    let mut map = five_by_five();
+    let mut removed = map.shrink_territory(player, 0.16);
+    removed.sort();
+    assert_eq!(removed, vec![(2, 2), (2, 6), (6, 2), (6, 6)]);
+
+    // Half the land all comes off the outer ring, leaving the 3x3 inside
+    let mut map = five_by_five();
+    let removed = map.shrink_territory(player, 0.5);
+    assert_eq!(removed.len(), 13);
+    assert!(removed
+        .iter()
+        .all(|&(x, y)| x == 2 || x == 6 || y == 2 || y == 6));
+    for y in 3..6 {
+        for x in 3..6 {
+            assert!(map.is_territory_of(x, y, player));
+        }
+    }
+
+    // Nothing goes at 0, everything at 1, and fractions past it are clamped
+    let mut map = five_by_five();
+    assert!(map.shrink_territory(player, 0.0).is_empty());
+    assert_eq!(map.territory_tiles(player).len(), 25);
+    assert_eq!(map.shrink_territory(player, 1.0).len(), 25);
+    assert!(map.territory_tiles(player).is_empty());
+    let mut map = five_by_five();
+    assert_eq!(map.shrink_territory(player, 1.5).len(), 25);
+    assert!(map.shrink_territory(player, 0.5).is_empty());
+}
+
+#[test]
+fn eroding_territory_strips_rings_and_keeps_it_in_one_piece() {
+    let player = Entity::from_raw(1);
+
+    // A 5x5 is three rings deep: 16 tiles, then 8, then the center
+    let mut map = TileMap::new(12, 12);
+    give_land(&mut map, player, 2..7, 2..7);
+    assert_eq!(map.erode_territory(player, 1).len(), 16);
+    assert_eq!(map.erode_territory(player, 1).len(), 8);
+    assert_eq!(map.territory_tiles(player), vec![(4, 4)]);
+    assert!(map.last_ring(player));
+
+    // More rings than that clear it
+    let mut map = TileMap::new(12, 12);
+    give_land(&mut map, player, 2..7, 2..7);
+    assert_eq!(map.erode_territory(player, 5).len(), 25);
+    assert!(map.territory_tiles(player).is_empty());
+    assert!(map.erode_territory(player, 1).is_empty());
+
+    // A 5x5 and a 4x4 joined by a bridge: the bridge erodes away, and what's
+    // left of the smaller block goes with it as a cut off fragment. The tile
+    // the bridge joined the 5x5 at wasn't on its border, so it stays.
+    let mut map = TileMap::new(20, 10);
+    give_land(&mut map, player, 1..6, 1..6);
+    give_land(&mut map, player, 6..9, 3..4);
+    give_land(&mut map, player, 9..13, 1..5);
+    let removed = map.erode_territory(player, 1);
+    assert_eq!(removed.len(), 25 + 3 + 16 - 10);
+    let mut left = map.territory_tiles(player);
+    left.sort();
+    let mut core: Vec<_> = (2..5).flat_map(|x| (2..5).map(move |y| (x, y))).collect();
+    core.sort();
+    let mut joined = core.clone();
+    joined.push((5, 3));
+    assert_eq!(left, joined);
+
+    // Land already in separate regions is eroded down to the largest one
+    let mut map = TileMap::new(20, 10);
+    give_land(&mut map, player, 1..6, 1..6);
+    give_land(&mut map, player, 10..13, 1..4);
+    map.erode_territory(player, 1);
+    let mut left = map.territory_tiles(player);
+    left.sort();
+    assert_eq!(left, core);
+    assert_eq!(map.regions(player).len(), 1);
+}
+
+#[test]
+fn mirrored_edges_bounce_players_back_over_their_trail() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+    app.world_mut().resource_mut::<GridSettings>().topology = GridTopology::Mirrored;
+
+    // Out to the right edge and let go, holding right wouldn't turn them
+    // around again anyway
+    steer(&mut app, KeyCode::KeyD, |(x, _)| x >= 39);
+    let mut longest_trail = 0;
+    for _ in 0..300 {
+        app.update();
+        longest_trail = longest_trail.max(owned_tiles(&mut app, entity).1);
+        if player(&mut app).last_tile_pos.0 <= 22 {
+            break;
+        }
+    }
+    run_frames(&mut app, 10);
+
+    // Back home alive, the way back laying nothing new, and the trail out
+    // claimed as a line
+    assert_eq!(player(&mut app).direction, Vec2::NEG_X);
+    assert!(!player(&mut app).retracing);
+    assert!(app.world().get::<Respawning>(entity).is_none());
+    assert_eq!(longest_trail, 17);
+    assert_eq!(owned_tiles(&mut app, entity), (25 + 17, 0));
+
+    // Solid edges never turn the player around
+    let mut app = headless_app();
+    steer(&mut app, KeyCode::KeyD, |(x, _)| x >= 39);
+    for _ in 0..30 {
+        app.update();
+        assert_ne!(player(&mut app).direction, Vec2::NEG_X);
+    }
+}
+
+#[test]
+fn scripted_input_drives_the_player_like_a_device() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+
+    // Right for a second, then up
+    let script = InputScript::new(vec![(0.0, Vec2::X), (1.0, Vec2::Y)]);
+    app.world_mut()
+        .entity_mut(entity)
+        .insert(InputSource::Script(script));
+    run_frames(&mut app, 90);
+
+    let (x, y) = player(&mut app).last_tile_pos;
+    assert!(
+        x > 22,
+        "expected the script to move the player right, at {x}"
+    );
+    assert!(y > 15, "expected the script to turn the player up, at {y}");
+}
+
+#[test]
+fn idle_join_screen_plays_a_bot_demo_until_someone_joins() {
+    let mut app = join_screen_app();
+    run_frames(&mut app, 60 * 12);
+
+    let world = app.world_mut();
+    let bots = world.query_filtered::<(), With<Bot>>().iter(world).count();
+    let owned = world
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| tile.owner.is_some())
+        .count();
+    assert!(bots > 0, "expected demo bots after idling");
+    assert!(owned >= bots * 25, "expected the bots to hold territory");
+
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    run_frames(&mut app, 2);
+
+    let world = app.world_mut();
+    assert_eq!(world.query::<&Bot>().iter(world).count(), 0);
+    assert!(world
+        .resource::<Tiles>()
+        .iter()
+        .all(|tile| tile.owner.is_none()));
+}
+
+#[test]
+fn sandbox_spawns_the_requested_bots_and_never_starts_the_timer() {
+    let mut app = join_screen_app();
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Sandbox);
+    app.world_mut().resource_mut::<SandboxSettings>().bot_count = 2;
+    run_frames(&mut app, 5);
+
+    let world = app.world_mut();
+    assert_eq!(world.query::<&Bot>().iter(world).count(), 2);
+    assert_eq!(world.query::<&Player>().iter(world).count(), 3);
+    assert!(!app.world().resource::<GameState>().game_running);
+}
+
+#[test]
+fn sandbox_painting_takes_the_points_and_clearing_drops_pending_claims() {
+    let mut app = join_screen_app();
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd, InputDevice::KeyboardArrows],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Sandbox);
+    run_frames(&mut app, 3);
+
+    // The first local player paints over the middle of the other's land
+    let world = app.world_mut();
+    let players: Vec<(Entity, (i32, i32), u32)> = world
+        .query::<(Entity, &Player)>()
+        .iter(world)
+        .map(|(entity, player)| (entity, player.spawn_tile, player.score))
+        .collect();
+    let tile = players[1].1;
+    let owner = app
+        .world()
+        .resource::<Tiles>()
+        .get(tile.0, tile.1)
+        .unwrap()
+        .owner;
+    let (owner, painter) = if owner == Some(players[1].0) {
+        (players[1], players[0])
+    } else {
+        (players[0], players[1])
+    };
+    let tile = owner.1;
+    app.world_mut().send_event(SandboxEvent::Paint { tile });
+    app.update();
+    let score = |app: &App, entity: Entity| app.world().get::<Player>(entity).unwrap().score;
+    assert_eq!(
+        app.world()
+            .resource::<Tiles>()
+            .get(tile.0, tile.1)
+            .unwrap()
+            .owner,
+        Some(painter.0)
+    );
+    assert_eq!(score(&app, painter.0), painter.2 + 1);
+    assert_eq!(score(&app, owner.0), owner.2 - 1);
+
+    // A claim still computing when the map is cleared never lands on it
+    let claim = AsyncComputeTaskPool::get().spawn(future::pending::<Vec<ClaimResult>>());
+    app.world_mut()
+        .resource_mut::<PendingClaims>()
+        .push(vec![owner.0], claim);
+    app.world_mut().send_event(SandboxEvent::ClearMap);
+    app.update();
+    assert!(app.world().resource::<PendingClaims>().tasks.is_empty());
+    assert_eq!(score(&app, owner.0), 25);
+    let tiles = app.world().resource::<Tiles>();
+    assert_eq!(tiles.get(tile.0, tile.1).unwrap().owner, Some(owner.0));
+}
+
+#[test]
+fn one_minute_left_publishes_a_milestone_and_starts_hurry_up() {
+    let mut app = headless_app();
+    let elapsed = Duration::from_secs_f32(300.0 - 60.02);
+    app.world_mut()
+        .resource_mut::<GameState>()
+        .timer
+        .set_elapsed(elapsed);
+    // Events only live for two updates, so stop just past the milestone
+    run_frames(&mut app, 2);
+
+    let events = app.world().resource::<Events<MatchTimerEvent>>();
+    let milestones: Vec<TimerMilestone> = events
+        .get_cursor()
+        .read(events)
+        .map(|event| event.milestone)
+        .collect();
+    assert_eq!(milestones, vec![TimerMilestone::OneMinuteLeft]);
+    assert!(app.world().resource::<GameState>().hurry_up);
+}
+
+#[test]
+fn placing_above_equal_rated_opponents_raises_your_rating() {
+    let changes = rating_changes(&[(1000.0, 0), (1000.0, 1), (1000.0, 1)]);
+
+    assert!(changes[0] > 0.0);
+    assert!(changes[1] < 0.0);
+    assert_eq!(changes[1], changes[2]);
+    assert!(changes.iter().sum::<f32>().abs() < 1e-3);
+}
+
+#[test]
+fn custom_key_bindings_replace_the_device_layout() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+    app.world_mut().entity_mut(entity).insert(KeyBindings {
+        up: KeyCode::KeyI,
+        down: KeyCode::KeyK,
+        left: KeyCode::KeyJ,
+        right: KeyCode::KeyL,
+    });
+
+    let start = player(&mut app).last_tile_pos;
+    steer(&mut app, KeyCode::KeyD, |_| false);
+    assert_eq!(
+        player(&mut app).last_tile_pos,
+        start,
+        "WASD should be unbound"
+    );
+
+    steer(&mut app, KeyCode::KeyL, |(x, _)| x > start.0 + 1);
+    assert!(player(&mut app).last_tile_pos.0 > start.0 + 1);
+}
+
+#[test]
+fn daily_challenge_is_the_same_all_day_and_sets_up_the_match() {
+    let challenge = DailyChallenge::generate(20_000);
+    assert_eq!(challenge, DailyChallenge::generate(20_000));
+    assert!((1..=4).contains(&challenge.bots.len()));
+
+    // Each day brings its own map, with every bot starting on it
+    let maps: Vec<GridSettings> = (20_000..20_010)
+        .map(|day| DailyChallenge::generate(day).map)
+        .collect();
+    assert!(maps.iter().any(|map| *map != maps[0]));
+    for day in 20_000..20_010 {
+        let challenge = DailyChallenge::generate(day);
+        assert!(challenge
+            .bots
+            .iter()
+            .all(|bot| challenge.map.playable(bot.tile.0, bot.tile.1)));
+    }
+
+    let mut app = join_screen_app();
+    app.insert_resource(challenge.clone());
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    run_frames(&mut app, 2);
+
+    let world = app.world_mut();
+    assert_eq!(
+        world.query::<&Bot>().iter(world).count(),
+        challenge.bots.len()
+    );
+    let timer = &app.world().resource::<GameState>().timer;
+    assert_eq!(timer.duration().as_secs_f32(), challenge.goal.time_limit);
+    assert_eq!(*app.world().resource::<GridSettings>(), challenge.map);
+
+    // Leaving ends the challenge and puts the usual map back
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Join);
+    run_frames(&mut app, 2);
+    assert!(!app.world().contains_resource::<DailyChallenge>());
+    assert_eq!(
+        *app.world().resource::<GridSettings>(),
+        GridSettings::default()
+    );
+    let tiles = app.world().resource::<Tiles>();
+    assert_eq!(tiles.len(), 40 * 30);
+}
+
+#[test]
+fn meeting_the_daily_goal_ends_the_match_and_enter_leaves() {
+    let mut app = join_screen_app();
+    // Whatever the last match left on the clock doesn't carry over
+    app.world_mut().resource_mut::<GameState>().hurry_up = true;
+    app.insert_resource(DailyChallenge::generate(20_000));
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    run_frames(&mut app, 2);
+    assert!(!app.world().resource::<GameState>().hurry_up);
+
+    let frames = (COUNTDOWN_SECONDS / FRAME.as_secs_f32()).ceil() as usize + 1;
+    run_frames(&mut app, frames);
+    let human = human_entity(&mut app);
+    for (tile, _) in app.world_mut().resource_mut::<Tiles>().iter_mut() {
+        if tile.owner.is_none() {
+            tile.owner = Some(human);
+        }
+    }
+    run_frames(&mut app, 2);
+
+    let world = app.world();
+    assert_eq!(
+        world.resource::<DailyChallenge>().outcome,
+        Some(ChallengeOutcome::Completed)
+    );
+    let events = world.resource::<Events<MatchEndedEvent>>();
+    let mut cursor = events.get_cursor();
+    let ended = cursor.read(events).last().expect("the match ended");
+    assert_eq!(ended.standings[0].player, human);
+
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::Enter);
+    run_frames(&mut app, 2);
+    assert_eq!(
+        *app.world().resource::<State<AppState>>().get(),
+        AppState::Join
+    );
+    assert!(!app.world().contains_resource::<DailyChallenge>());
+    let world = app.world_mut();
+    assert_eq!(world.query::<&Player>().iter(world).count(), 0);
+}
+
+#[test]
+fn puzzle_levels_load_and_lay_out_their_enemy_territory() {
+    let campaign = Campaign::load();
+    assert!(campaign.levels.len() >= 3, "expected the bundled levels");
+
+    let level = campaign
+        .levels
+        .iter()
+        .find(|level| level.id == "02_around_the_wall")
+        .expect("bundled level")
+        .clone();
+    let tiles = level.tiles(&GridSettings::default());
+
+    let mut app = join_screen_app();
+    app.insert_resource(ActiveLevel::new(level));
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    run_frames(&mut app, 2);
+
+    assert_eq!(Some(player(&mut app).spawn_tile), tiles.start);
+
+    let world = app.world_mut();
+    let enemy = world
+        .query_filtered::<Entity, With<LevelEnemy>>()
+        .single(world);
+    let (land, _) = owned_tiles(&mut app, enemy);
+    assert_eq!(land, tiles.enemy_land.len());
+}
+
+#[test]
+fn difficulty_director_toughens_bots_while_the_human_is_ahead() {
+    let mut app = join_screen_app();
+    app.insert_resource(GameRules {
+        dynamic_difficulty: Some(DifficultyBounds::default()),
+        ..GameRules::default()
+    });
+    app.insert_resource(DailyChallenge::generate(20_000));
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    run_frames(&mut app, 2);
+
+    // Hand the human the whole bottom of the map
+    let human = human_entity(&mut app);
+    let world = app.world_mut();
+    for (tile, _) in world.resource_mut::<Tiles>().iter_mut() {
+        if tile.y < 6 && tile.owner.is_none() {
+            tile.owner = Some(human);
+        }
+    }
+    run_frames(&mut app, 4 * 60);
+
+    let level = app.world().resource::<DifficultyDirector>().level;
+    assert!(
+        level > 0.5,
+        "expected the director to raise difficulty, at {level}"
+    );
+    let world = app.world_mut();
+    assert!(world
+        .query::<&Bot>()
+        .iter(world)
+        .all(|bot| bot.tuning.aggression > 0.55));
+}
+
+#[test]
+fn directed_pickups_favour_the_trailing_player() {
+    let (leader, underdog) = (Entity::from_raw(1), Entity::from_raw(2));
+    let mut map = TileMap::new(30, 10);
+    for y in 0..10 {
+        for x in 20..30 {
+            map.set(
+                x,
+                y,
+                TileState {
+                    owner: Some(leader),
+                    trail_owner: None,
+                },
+            );
+        }
+    }
+    for tile in [(2, 5), (17, 0)] {
+        map.set(
+            tile.0,
+            tile.1,
+            TileState {
+                owner: Some(underdog),
+                trail_owner: None,
+            },
+        );
+    }
+
+    let undirected = pickup_spawn_weights(&map, Some(underdog), false);
+    assert!(undirected.iter().all(|&(_, weight)| weight == 1.0));
+
+    let directed = pickup_spawn_weights(&map, Some(underdog), true);
+    let weight_at = |tile: (i32, i32)| {
+        directed
+            .iter()
+            .find(|&&(at, _)| at == tile)
+            .map(|&(_, weight)| weight)
+            .unwrap()
+    };
+    assert!(weight_at((3, 5)) > weight_at((10, 5)));
+    assert!(weight_at((18, 2)) > weight_at((18, 8)), "contested border");
+}
+
+// Test brain that only ever heads right
+struct EastBrain;
+
+impl BotBrain for EastBrain {
+    fn decide(&mut self, _input: &BrainInput) -> Vec2 {
+        Vec2::X
+    }
+}
+
+#[test]
+fn bots_play_with_the_brain_named_in_the_rules() {
+    let mut app = join_screen_app();
+    app.register_bot_brain("east", || EastBrain);
+    app.insert_resource(GameRules {
+        bot_brain: Some("east".to_string()),
+        ..GameRules::default()
+    });
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Sandbox);
+    app.world_mut().resource_mut::<SandboxSettings>().bot_count = 1;
+    run_frames(&mut app, 30);
+
+    let world = app.world_mut();
+    let bot = world.query_filtered::<&Player, With<Bot>>().single(world);
+    assert_eq!(bot.direction, Vec2::X);
+}
+
+#[test]
+fn batched_headless_matches_run_in_parallel() {
+    let setups: Vec<MatchSetup> = (1..=3)
+        .map(|bots| MatchSetup {
+            bots,
+            match_seconds: 2.0,
+            ..MatchSetup::default()
+        })
+        .collect();
+
+    let summaries = run_batch(&setups, 2);
+    assert_eq!(summaries.len(), 3);
+    for (bots, summary) in (1..=3).zip(summaries.iter()) {
+        assert_eq!(summary.scores.len(), bots);
+        assert!(summary.seconds >= 2.0);
+        assert!(summary.winner_share() > 0.0);
+    }
+}
+
+#[test]
+fn balance_report_summarises_matches() {
+    let summary = |winner, kills, seconds| MatchSummary {
+        scores: vec![winner, 25],
+        map_tiles: 1200,
+        seconds,
+        deaths: kills + 1,
+        kills,
+    };
+    let report = BalanceReport::from_summaries(&[
+        summary(300, 2, 300.0),
+        summary(600, 4, 300.0),
+        summary(120, 0, 45.0),
+    ]);
+
+    assert_eq!(report.matches, 3);
+    assert!((report.average_winner_share - 0.85 / 3.0).abs() < 1e-4);
+    assert_eq!(report.total_kills, 6);
+    assert_eq!(report.average_deaths, 3.0);
+    assert_eq!(report.match_lengths.len(), 11);
+    assert_eq!(report.match_lengths[1].matches, 1);
+    assert_eq!(report.match_lengths[10].matches, 2);
+    assert!(report.to_csv().starts_with("matches,"));
+    assert!(report.to_json().contains("\"total_kills\": 6"));
+}
+
+#[test]
+fn telemetry_streams_a_line_per_tick() {
+    let path = std::env::temp_dir().join(format!("landio-telemetry-{}.jsonl", std::process::id()));
+    let mut app = join_screen_app();
+    app.insert_resource(TelemetrySettings {
+        path: Some(path.to_string_lossy().into_owned()),
+        format: TelemetryFormat::JsonLines,
+    });
+    app.insert_resource(ActivePreset(Some(RulesPreset::Turtle)));
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    run_frames(&mut app, 10);
+
+    // Leaving the match flushes the file
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Join);
+    app.update();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<serde_json::Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(lines.len() >= 9, "got {} ticks", lines.len());
+    assert_eq!(lines[0]["tick"], 0);
+    assert_eq!(lines[0]["players"][0]["tiles"], 25);
+    // Tagged with the preset once, up front
+    assert_eq!(lines[0]["events"][0]["kind"], "preset");
+    assert_eq!(lines[0]["events"][0]["detail"], "Turtle");
+    assert!(lines[1..]
+        .iter()
+        .all(|line| line["events"].as_array().unwrap().is_empty()));
+}
+
+// Sets the player heading `direction` and steps until their tile satisfies
+// `reached` or they die, returning the frames it took
+fn walk(
+    game: &mut HeadlessMatch,
+    player: Entity,
+    direction: Vec2,
+    reached: impl Fn((i32, i32)) -> bool,
+) -> usize {
+    let world = game.app_mut().world_mut();
+    world.get_mut::<DirectionIntent>(player).unwrap().0 = direction;
+    for frame in 1..=600 {
+        game.step();
+        let world = game.app().world();
+        if world.get::<Respawning>(player).is_some()
+            || reached(world.get::<Player>(player).unwrap().last_tile_pos)
+        {
+            return frame;
+        }
+    }
+    600
+}
+
+// Whether the first player kills the second by walking across their trail
+// under the preset
+fn preset_cuts(preset: RulesPreset) -> bool {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: preset.rules(),
+        external_players: 2,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let (attacker, victim) = (game.external_players()[0], game.external_players()[1]);
+    let (x, _) = game
+        .app()
+        .world()
+        .get::<Player>(attacker)
+        .unwrap()
+        .spawn_tile;
+    let (_, row) = game.app().world().get::<Player>(victim).unwrap().spawn_tile;
+
+    // The second player trails out along their row past the first's column,
+    // then the first heads down across it
+    walk(&mut game, victim, Vec2::X, |(at, _)| at > x);
+    walk(&mut game, attacker, Vec2::NEG_Y, |(_, at)| at < row);
+    game.app().world().get::<Respawning>(victim).is_some()
+}
+
+// How a player running into their own trail fared under a preset
+struct PresetDeath {
+    frames: usize,
+    death: (i32, i32),
+    respawn: (i32, i32),
+    spawn: (i32, i32),
+    land: usize,
+}
+
+fn preset_death(preset: RulesPreset) -> PresetDeath {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: preset.rules(),
+        external_players: 1,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let player = game.external_players()[0];
+
+    // A tight clockwise spiral outside the territory that crosses itself
+    let mut frames = walk(&mut game, player, Vec2::X, |(x, _)| x >= 26);
+    frames += walk(&mut game, player, Vec2::Y, |(_, y)| y >= 17);
+    frames += walk(&mut game, player, Vec2::NEG_X, |(x, _)| x <= 25);
+    let death = (25, 15);
+    frames += walk(&mut game, player, Vec2::NEG_Y, |tile| tile == death);
+    while game.app().world().get::<Respawning>(player).is_none() {
+        game.step();
+        frames += 1;
+    }
+
+    let world = game.app().world();
+    let state = world.get::<Player>(player).unwrap();
+    let land = world
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| tile.owner == Some(player))
+        .count();
+    PresetDeath {
+        frames,
+        death,
+        respawn: state.last_tile_pos,
+        spawn: state.spawn_tile,
+        land,
+    }
+}
+
+// Where a casual player dying at `death` comes back, and how much land
+// they're left with, after `prepare` has had its way with the map
+fn casual_respawn(death: (i32, i32), prepare: impl Fn(&mut Tile, Entity)) -> ((i32, i32), usize) {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: GameRules::casual(),
+        external_players: 1,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let player = game.external_players()[0];
+
+    let world = game.app_mut().world_mut();
+    for (tile, _) in world.resource_mut::<Tiles>().iter_mut() {
+        prepare(tile, player);
+    }
+    world.send_event(PlayerDeathEvent {
+        player_entity: player,
+        reason: PlayerDeathReason::TrailCollision,
+        killer: None,
+        tile: death,
+        trail_length: 0,
+    });
+    game.step();
+
+    let world = game.app().world();
+    let land = world
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| tile.owner == Some(player))
+        .count();
+    (world.get::<Player>(player).unwrap().last_tile_pos, land)
+}
+
+#[test]
+fn casual_players_respawn_on_their_land_nearest_where_they_died() {
+    // A patch of land off to the side, much closer to the death than the
+    // starting block is
+    let patch = |tile: &mut Tile, player| {
+        if (30..=31).contains(&tile.x) && (5..=6).contains(&tile.y) {
+            tile.owner = Some(player);
+        }
+    };
+    let (respawn, land) = casual_respawn((34, 4), patch);
+    assert_eq!(respawn, (31, 5));
+    assert_eq!(land, 25 + 4);
+
+    // Dying on their own land brings them back right there
+    let (respawn, _) = casual_respawn((21, 14), patch);
+    assert_eq!(respawn, (21, 14));
+
+    // With no land left they start over with a fresh block at their spawn
+    let wiped = |tile: &mut Tile, _| tile.owner = None;
+    let (respawn, land) = casual_respawn((34, 4), wiped);
+    assert_eq!(respawn, (20, 15));
+    assert_eq!(land, 25);
+}
+
+#[test]
+fn balance_presets_tune_penalties_speeds_and_cuts() {
+    let distance = |(ax, ay): (i32, i32), (bx, by): (i32, i32)| (ax - bx).abs() + (ay - by).abs();
+
+    // Only Aggressive lets a player cut someone else's trail
+    assert!(!preset_cuts(RulesPreset::Classic));
+    assert!(preset_cuts(RulesPreset::Aggressive));
+    assert!(!preset_cuts(RulesPreset::Turtle));
+
+    let classic = preset_death(RulesPreset::Classic);
+    let aggressive = preset_death(RulesPreset::Aggressive);
+    let turtle = preset_death(RulesPreset::Turtle);
+
+    // Aggressive plays fastest and Turtle slowest
+    assert!(
+        aggressive.frames < classic.frames && classic.frames < turtle.frames,
+        "{} {} {}",
+        aggressive.frames,
+        classic.frames,
+        turtle.frames
+    );
+
+    // Classic and Aggressive wipe a dead player back to a fresh start at
+    // their spawn
+    for wiped in [&classic, &aggressive] {
+        assert_eq!(wiped.respawn, wiped.spawn);
+        assert_eq!(wiped.land, 25);
+    }
+
+    // Turtle only peels some land off, and brings them back on what's left
+    // nearest to where they died
+    assert!((1..25).contains(&turtle.land), "{}", turtle.land);
+    assert!(
+        distance(turtle.respawn, turtle.death) < distance(turtle.spawn, turtle.death),
+        "{:?}",
+        turtle.respawn
+    );
+
+    // Cycling at match setup visits every preset once
+    let mut preset = RulesPreset::Classic;
+    let mut seen = vec![preset];
+    for _ in 1..RulesPreset::ALL.len() {
+        preset = preset.next();
+        assert!(!seen.contains(&preset));
+        seen.push(preset);
+    }
+    assert_eq!(preset.next(), RulesPreset::Classic);
+    assert_eq!(
+        ActivePreset(Some(RulesPreset::Aggressive)).name(),
+        "Aggressive"
+    );
+    assert_eq!(ActivePreset::default().name(), "Custom");
+
+    // Changing the speed after picking a preset makes the rules custom
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(RulesPreset::Turtle.rules())
+        .insert_resource(ActivePreset(Some(RulesPreset::Turtle)))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .add_systems(Update, cycle_game_speed_system);
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyG);
+    app.update();
+    assert_eq!(
+        app.world().resource::<GameRules>().game_speed,
+        GameSpeed::Slow.next()
+    );
+    assert_eq!(*app.world().resource::<ActivePreset>(), ActivePreset(None));
+
+    // Presets pick up where a match is set up from the config
+    let config: GameConfig = ron::from_str("(preset: Some(Turtle))").unwrap();
+    assert_eq!(config.game_rules().game_speed, GameSpeed::Slow);
+}
+
+#[test]
+fn match_stats_sample_territory_every_second() {
+    let mut app = headless_app();
+    let human = player_entity(&mut app);
+    run_frames(&mut app, 125);
+
+    let stats = app.world().resource::<MatchStats>();
+    assert_eq!(stats.samples.len(), 2);
+    let timeline = stats.territory_timeline(human);
+    assert_eq!(timeline, vec![25.0 / 1200.0; 2]);
+    assert_eq!(stats.peak_share(), 25.0 / 1200.0);
+}
+
+#[test]
+fn quick_play_starts_the_last_setup_against_bots() {
+    // Smart defaults before a profile has played, then whatever it last did
+    let mut profile = Profile::default();
+    let setup = QuickPlaySetup::for_profile(Some(&profile));
+    assert_eq!(setup.preset, Some(RulesPreset::Classic));
+    assert_eq!(setup.bots, 3);
+    profile.quick_play = Some(QuickPlaySetup {
+        map: Some("Arena".to_string()),
+        preset: Some(RulesPreset::Turtle),
+        game_speed: GameSpeed::Blitz,
+        bots: 1,
+        difficulty: Some(BotDifficulty::Hard),
+        ..default()
+    });
+    let saved = ron::to_string(&profile).unwrap();
+    let loaded: Profile = ron::from_str(&saved).unwrap();
+    assert_eq!(loaded.quick_play, profile.quick_play);
+    assert_eq!(
+        QuickPlaySetup::for_profile(Some(&loaded)).describe(&BotDifficultySetting::default()),
+        "Q for quick play: Arena, Turtle rules, blitz speed, 1 Hard bot"
+    );
+
+    // Bots join after the local players, at the chosen difficulty
+    let mut app = join_screen_app();
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    })
+    .insert_resource(QuickPlay { bots: 2 });
+    app.world_mut()
+        .resource_mut::<BotDifficultySetting>()
+        .chosen = Some(BotDifficulty::Expert);
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    app.update();
+
+    let world = app.world_mut();
+    let difficulties: Vec<_> = world
+        .query::<&Bot>()
+        .iter(world)
+        .map(|bot| bot.difficulty)
+        .collect();
+    assert_eq!(difficulties, vec![Some(BotDifficulty::Expert); 2]);
+    assert_eq!(world.query::<&Player>().iter(world).count(), 3);
+
+    // Back on the join screen the next match is set up as usual
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Join);
+    app.update();
+    assert!(!app.world().contains_resource::<QuickPlay>());
+
+    // A setup played under custom rules goes back to the config's rules,
+    // whatever preset was picked since
+    let mut config = GameConfig::default();
+    config.rules.trail_cuts = true;
+    let mut app = join_screen_app();
+    app.insert_resource(ProfileStore {
+        profiles: vec![Profile {
+            quick_play: Some(QuickPlaySetup {
+                preset: None,
+                game_speed: GameSpeed::Slow,
+                ..default()
+            }),
+            ..default()
+        }],
+    })
+    .insert_resource(ActiveProfiles { slots: vec![0] })
+    .insert_resource(config)
+    .insert_resource(RulesPreset::Aggressive.rules())
+    .insert_resource(ActivePreset(Some(RulesPreset::Aggressive)))
+    .add_systems(Update, quick_play_system);
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyQ);
+    app.update();
+    assert_eq!(*app.world().resource::<ActivePreset>(), ActivePreset(None));
+    let rules = app.world().resource::<GameRules>();
+    assert_eq!(rules.game_speed, GameSpeed::Slow);
+    assert!(rules.trail_cuts && rules.bounty.is_none());
+}
+
+#[test]
+fn bot_difficulty_is_calibrated_from_matches_against_bots() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(ProfileStore {
+            profiles: vec![Profile::default(), Profile::default()],
+        })
+        .insert_resource(ActiveProfiles { slots: vec![0, 1] })
+        .insert_resource(JoinedPlayers {
+            devices: vec![InputDevice::KeyboardWasd, InputDevice::KeyboardArrows],
+        })
+        .init_resource::<BotDifficultySetting>()
+        .add_event::<MatchEndedEvent>()
+        .add_systems(
+            Update,
+            (record_bot_matches_system, calibrate_bot_difficulty_system).chain(),
+        );
+    let human = app
+        .world_mut()
+        .spawn((LocalPlayer, InputSource::Device(InputDevice::KeyboardWasd)))
+        .id();
+    let bot = app
+        .world_mut()
+        .spawn(Bot::with_difficulty(BotDifficulty::Normal))
+        .id();
+    assert_eq!(
+        app.world().get::<Bot>(bot).unwrap().tuning,
+        BotDifficulty::Normal.tuning()
+    );
+    let play = |app: &mut App, winner: Entity, loser: Entity| {
+        app.world_mut().send_event(MatchEndedEvent {
+            standings: vec![
+                Standing {
+                    player: winner,
+                    score: 40,
+                    placement: 0,
+                },
+                Standing {
+                    player: loser,
+                    score: 10,
+                    placement: 1,
+                },
+            ],
+            awards: Vec::new(),
+        });
+        app.update();
+    };
+    let stats = |app: &App, index: usize| {
+        app.world().resource::<ProfileStore>().profiles[index]
+            .stats
+            .clone()
+    };
+
+    // Nothing to go on yet, then too little
+    assert_eq!(
+        ProfileStats::default().calibrated_difficulty().difficulty,
+        BotDifficulty::Normal
+    );
+    play(&mut app, human, bot);
+    play(&mut app, human, bot);
+    assert_eq!(stats(&app, 0).bots[&BotDifficulty::Normal].won, 2);
+    assert_eq!(
+        stats(&app, 0).calibrated_difficulty().difficulty,
+        BotDifficulty::Normal
+    );
+
+    // Mostly winning moves the bots up a step
+    play(&mut app, bot, human);
+    play(&mut app, human, bot);
+    let calibration = &app.world().resource::<BotDifficultySetting>().calibration;
+    assert_eq!(calibration.difficulty, BotDifficulty::Hard);
+    assert_eq!(
+        calibration.reason,
+        "you usually win vs Normal, starting Hard"
+    );
+
+    // The record stays with the profile that played, P2's is untouched
+    assert!(stats(&app, 1).bots.is_empty());
+
+    // Then losing most of the last few at Hard brings them back down
+    let mut record = stats(&app, 0);
+    for won in [false, false, true, false] {
+        record.record_bot_match(BotDifficulty::Hard, won);
+    }
+    assert_eq!(
+        record.calibrated_difficulty().difficulty,
+        BotDifficulty::Normal
+    );
+    for _ in 0..5 {
+        record.record_bot_match(BotDifficulty::Expert, true);
+    }
+    assert_eq!(
+        record.calibrated_difficulty().difficulty,
+        BotDifficulty::Expert
+    );
+    assert_eq!(record.bots[&BotDifficulty::Expert].recent.len(), 5);
+
+    // Picking one at match setup wins over the calibration
+    let mut setting = BotDifficultySetting {
+        chosen: None,
+        calibration: record.calibrated_difficulty(),
+    };
+    assert_eq!(setting.difficulty(), BotDifficulty::Expert);
+    setting.chosen = Some(BotDifficulty::Easy);
+    assert_eq!(setting.difficulty(), BotDifficulty::Easy);
+
+    // Calibration follows whichever profile P1 plays as
+    app.world_mut().resource_mut::<ActiveProfiles>().slots = vec![1, 0];
+    app.update();
+    assert_eq!(
+        app.world()
+            .resource::<BotDifficultySetting>()
+            .calibration
+            .reason,
+        "no matches against bots yet"
+    );
+
+    // Matches where the bots weren't set to a difficulty don't count
+    app.world_mut().entity_mut(bot).insert(Bot::default());
+    play(&mut app, human, bot);
+    assert_eq!(stats(&app, 0).bots[&BotDifficulty::Normal].played, 4);
+    assert!(stats(&app, 1).bots.is_empty());
+}
+
+#[test]
+fn heatmap_intensity_is_relative_to_the_busiest_tile() {
+    let mut deaths = TileCounts::default();
+    assert_eq!(deaths.intensity((0, 0)), 0.0);
+
+    for tile in [(3, 4), (3, 4), (3, 4), (3, 4), (10, 2)] {
+        deaths.add(tile);
+    }
+    assert_eq!(deaths.get((3, 4)), 4);
+    assert_eq!(deaths.intensity((3, 4)), 1.0);
+    assert_eq!(deaths.intensity((10, 2)), 0.25);
+    assert_eq!(deaths.intensity((0, 0)), 0.0);
+}
+
+// Lays a trail tile for the second player, turns it into land a few frames
+// later, then walks the first player onto it
+fn late_cut(lag: Option<LagCompensation>) -> bool {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: GameRules {
+            trail_cuts: true,
+            ..GameRules::default()
+        },
+        external_players: 2,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let (attacker, victim) = (game.external_players()[0], game.external_players()[1]);
+    let cut_tile = (30, 25);
+
+    let set_tile = |game: &mut HeadlessMatch, is_trail: bool| {
+        let world = game.app_mut().world_mut();
+        for (tile, _) in world.resource_mut::<Tiles>().iter_mut() {
+            if (tile.x, tile.y) == cut_tile {
+                // Trail until the loop closes, land after
+                tile.owner = (!is_trail).then_some(victim);
+                tile.trail_owner = is_trail.then_some(victim);
+            }
+        }
+        world.get_mut::<Player>(victim).unwrap().is_drawing_trail = is_trail;
+    };
+    set_tile(&mut game, true);
+    for _ in 0..3 {
+        game.step();
+    }
+    set_tile(&mut game, false);
+    game.step();
+
+    let world = game.app_mut().world_mut();
+    if let Some(lag) = lag {
+        world.entity_mut(attacker).insert(lag);
+    }
+    world.get_mut::<Player>(attacker).unwrap().last_tile_pos = cut_tile;
+    game.step();
+    game.app().world().get::<Respawning>(victim).is_some()
+}
+
+#[test]
+fn lagging_players_can_cut_trails_they_were_shown() {
+    // Without rewinding, the trail was already land by the time they got there
+    assert!(!late_cut(None));
+    assert!(late_cut(Some(LagCompensation { frames_behind: 2 })));
+}
+
+#[test]
+fn steering_across_a_live_trail_cuts_it() {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: GameRules {
+            trail_cuts: true,
+            ..GameRules::default()
+        },
+        external_players: 2,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let (attacker, victim) = (game.external_players()[0], game.external_players()[1]);
+    let steer = |game: &mut HeadlessMatch, player: Entity, direction: Vec2| {
+        let world = game.app_mut().world_mut();
+        world.get_mut::<DirectionIntent>(player).unwrap().0 = direction;
+    };
+    let trail_at = |game: &HeadlessMatch, at: (i32, i32)| {
+        game.app()
+            .world()
+            .resource::<Tiles>()
+            .iter()
+            .find(|tile| (tile.x, tile.y) == at)
+            .and_then(|tile| tile.trail_owner)
+    };
+
+    // The second player draws a trail along their row, out past the first
+    let row = game
+        .app()
+        .world()
+        .get::<Player>(victim)
+        .unwrap()
+        .last_tile_pos
+        .1;
+    let x = game
+        .app()
+        .world()
+        .get::<Player>(attacker)
+        .unwrap()
+        .last_tile_pos
+        .0;
+    steer(&mut game, victim, Vec2::X);
+    for _ in 0..600 {
+        if trail_at(&game, (x, row)) == Some(victim) {
+            break;
+        }
+        game.step();
+    }
+    assert_eq!(trail_at(&game, (x, row)), Some(victim));
+
+    // No lag compensation, so the cut is judged against the trails as they
+    // are now, with the recorded history there all along
+    assert!(game
+        .app()
+        .world()
+        .get::<LagCompensation>(attacker)
+        .is_none());
+    assert!(game.app().world().contains_resource::<TrailHistory>());
+    steer(&mut game, attacker, Vec2::NEG_Y);
+    for _ in 0..600 {
+        if game.app().world().get::<Respawning>(victim).is_some() {
+            break;
+        }
+        game.step();
+    }
+    assert!(game.app().world().get::<Respawning>(victim).is_some());
+    assert!(game.app().world().get::<Respawning>(attacker).is_none());
+    assert_eq!(game.summary().kills, 1);
+}
+
+// Walks the first player onto a trail tile of the second, with either of
+// them ghosted
+fn ghost_cut(attacker_ghost: bool, victim_ghost: bool) -> bool {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: GameRules {
+            trail_cuts: true,
+            ..GameRules::default()
+        },
+        external_players: 2,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let (attacker, victim) = (game.external_players()[0], game.external_players()[1]);
+    let cut_tile = (30, 25);
+
+    let world = game.app_mut().world_mut();
+    for (tile, _) in world.resource_mut::<Tiles>().iter_mut() {
+        if (tile.x, tile.y) == cut_tile {
+            tile.trail_owner = Some(victim);
+        }
+    }
+    world.get_mut::<Player>(victim).unwrap().is_drawing_trail = true;
+    for (player, ghosted) in [(attacker, attacker_ghost), (victim, victim_ghost)] {
+        if ghosted {
+            world.entity_mut(player).insert(Ghost {
+                timer: Timer::from_seconds(3.0, TimerMode::Once),
+            });
+        }
+    }
+    world.get_mut::<Player>(attacker).unwrap().last_tile_pos = cut_tile;
+    game.step();
+    game.app().world().get::<Respawning>(victim).is_some()
+}
+
+#[test]
+fn ghosts_neither_cut_trails_nor_get_cut() {
+    assert!(ghost_cut(false, false));
+    assert!(!ghost_cut(true, false));
+    assert!(!ghost_cut(false, true));
+
+    // Picking one up ghosts the player for three seconds
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        external_players: 1,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let player = game.external_players()[0];
+    let tile = game
+        .app()
+        .world()
+        .get::<Player>(player)
+        .unwrap()
+        .last_tile_pos;
+    game.app_mut().world_mut().spawn(Pickup {
+        kind: PickupKind::Ghost,
+        tile,
+    });
+    game.step();
+    assert!(game.app().world().get::<Ghost>(player).is_some());
+    for _ in 0..200 {
+        game.step();
+    }
+    assert!(game.app().world().get::<Ghost>(player).is_none());
+}
+
+#[test]
+fn win_conditions_parse_from_the_rules_and_end_the_match() {
+    let condition = WinCondition::parse("territory_pct >= 0.5 || kills >= 5").unwrap();
+    let variables = WinVariables {
+        territory_pct: 0.2,
+        kills: 5.0,
+        ..WinVariables::default()
+    };
+    assert!(condition.met(&variables));
+    assert!(!condition.met(&WinVariables::default()));
+    assert!(WinCondition::parse("1 + 2 * 3 == 7 && !(deaths > 0)")
+        .unwrap()
+        .met(&WinVariables::default()));
+    assert!(WinCondition::parse("wins > 1").is_err());
+    assert!(WinCondition::parse("kills >=").is_err());
+    assert!(WinCondition::parse("kills = 1").is_err());
+    let bracketed = |depth| format!("{}kills{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(WinCondition::parse(&bracketed(32)).is_ok());
+    assert!(WinCondition::parse(&bracketed(100_000)).is_err());
+    assert!(WinCondition::parse(&format!("{}1", "!".repeat(100_000))).is_err());
+    assert!(WinCondition::parse(&vec!["kills"; 100].join(" + ")).is_err());
+
+    let rules: GameRules = ron::from_str(r#"(win_condition: Some("kills >= 1"))"#).unwrap();
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules,
+        external_players: 2,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let (killer, victim) = (game.external_players()[0], game.external_players()[1]);
+    game.step();
+    assert!(!game.finished());
+
+    game.app_mut().world_mut().send_event(PlayerDeathEvent {
+        player_entity: victim,
+        reason: PlayerDeathReason::TrailCut,
+        killer: Some(killer),
+        tile: (0, 0),
+        trail_length: 0,
+    });
+    game.step();
+    game.step();
+    assert!(game.finished());
+
+    let world = game.app().world();
+    let events = world.resource::<Events<MatchEndedEvent>>();
+    let mut cursor = events.get_cursor();
+    let ended = cursor.read(events).last().unwrap();
+    assert_eq!(ended.standings[0].player, killer);
+    assert_eq!(ended.standings[0].placement, 0);
+    assert_eq!(ended.standings[1].placement, 1);
+}
+
+#[test]
+fn value_zones_make_their_tiles_worth_more() {
+    let setup = MatchSetup {
+        external_players: 1,
+        bots: 0,
+        ..MatchSetup::default()
+    };
+    let mut plain = HeadlessMatch::new(&setup);
+    let player = plain.external_players()[0];
+    let spawn = plain
+        .app()
+        .world()
+        .get::<Player>(player)
+        .unwrap()
+        .spawn_tile;
+
+    // A 3x3 patch worth 3 a tile in the middle of the starting territory
+    let mut setup = setup.clone();
+    setup.grid.value_zones = vec![ValueZone {
+        center: spawn,
+        radius: 1,
+        value: 3,
+    }];
+    let mut game = HeadlessMatch::new(&setup);
+    let player = game.external_players()[0];
+    game.step();
+
+    let world = game.app().world();
+    assert_eq!(world.get::<Player>(player).unwrap().score, 25 + 9 * 2);
+    let layers = world.resource::<OwnershipLayers>();
+    assert_eq!(layers.tile_count(player), 25);
+    assert_eq!(layers.territory_value(player), 43);
+    assert_eq!(layers.total_value(), 1200 + 9 * 2);
+    assert_eq!(plain.summary().map_tiles, 1200);
+    assert_eq!(game.summary().map_tiles, 1218);
+}
+
+#[test]
+fn masked_maps_leave_void_unclaimable_uncounted_and_deadly() {
+    // The built-in shapes keep the middle and leave the corners void
+    for shape in [MapShape::Circle, MapShape::Cross, MapShape::Islands] {
+        assert!(shape.playable(20, 15, 40, 30), "{:?}", shape);
+        assert!(!shape.playable(0, 0, 40, 30), "{:?}", shape);
+    }
+
+    // Everything right of column 20 is void, which crowds the middle spawn
+    let mask = vec![format!("{}{}", "#".repeat(21), ".".repeat(19)); 30];
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        grid: GridSettings {
+            shape: MapShape::Mask(mask),
+            ..default()
+        },
+        external_players: 1,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let player = game.external_players()[0];
+    game.step();
+
+    // Moved over so the starting territory is all on the map, and void
+    // isn't worth anything
+    let world = game.app().world();
+    assert_eq!(world.get::<Player>(player).unwrap().spawn_tile, (18, 15));
+    assert_eq!(world.resource::<OwnershipLayers>().tile_count(player), 25);
+    assert_eq!(game.summary().map_tiles, 21 * 30);
+    let tiles = game.app().world().resource::<Tiles>();
+    assert!(tiles.get(21, 15).unwrap().void && tiles.get(21, 15).unwrap().value == 0);
+
+    // Heading right walks off the edge of the shape
+    let world = game.app_mut().world_mut();
+    world.get_mut::<DirectionIntent>(player).unwrap().0 = Vec2::X;
+    for _ in 0..60 {
+        game.step();
+        if game.app().world().get::<Respawning>(player).is_some() {
+            break;
+        }
+    }
+    let world = game.app().world();
+    assert!(world.get::<Respawning>(player).is_some());
+    assert!(world
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| tile.void)
+        .all(|tile| tile.owner.is_none() && tile.trail_owner.is_none()));
+    assert_eq!(game.summary().deaths, 1);
+}
+
+#[test]
+fn stairs_carry_players_between_floors_that_claim_on_their_own() {
+    let grid = GridSettings::default().with_upper_floor(UpperFloor {
+        stairs: vec![(26, 15), (27, 19)],
+    });
+    // Side by side with a column of void between them
+    assert_eq!((grid.grid_width, grid.floor_width()), (81, 40));
+    assert_eq!(grid.floor_of(39), Some(0));
+    assert_eq!(grid.floor_of(40), None);
+    assert_eq!(grid.floor_of(41), Some(1));
+    assert!(!grid.playable(40, 15));
+    assert_eq!(grid.stairs_from(26, 15), Some((67, 15)));
+    assert_eq!(grid.stairs_from(68, 19), Some((27, 19)));
+    assert_eq!(grid.stairs_from(25, 15), None);
+
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        grid,
+        external_players: 1,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let player = game.external_players()[0];
+    game.step();
+    assert_eq!(game.summary().map_tiles, 2 * 40 * 30);
+
+    // Steers until the player's tile passes the test
+    let walk = |game: &mut HeadlessMatch, direction: Vec2, until: &dyn Fn((i32, i32)) -> bool| {
+        let world = game.app_mut().world_mut();
+        world.get_mut::<DirectionIntent>(player).unwrap().0 = direction;
+        for _ in 0..300 {
+            game.step();
+            if until(
+                game.app()
+                    .world()
+                    .get::<Player>(player)
+                    .unwrap()
+                    .last_tile_pos,
+            ) {
+                return;
+            }
+        }
+        panic!("never got there heading {:?}", direction);
+    };
+
+    // Out of the starting land and up the stairs, drawing all the way
+    walk(&mut game, Vec2::X, &|(x, _)| x > 40);
+    let tiles = game.app().world().resource::<Tiles>();
+    assert_eq!(tiles.get(25, 15).unwrap().trail_owner, Some(player));
+    assert_eq!(tiles.get(26, 15).unwrap().trail_owner, None);
+    assert_eq!(tiles.get(67, 15).unwrap().trail_owner, Some(player));
+
+    // Back down the other stairs and home. The trail rings ground floor
+    // tiles only with the help of upstairs, so nothing on either floor is
+    // enclosed.
+    walk(&mut game, Vec2::Y, &|(x, _)| x < 40);
+    walk(&mut game, Vec2::NEG_X, &|(x, _)| x <= 22);
+    walk(&mut game, Vec2::NEG_Y, &|(_, y)| y <= 17);
+    for _ in 0..5 {
+        game.step();
+    }
+    let world = game.app().world();
+    assert!(world.get::<Respawning>(player).is_none());
+    assert!(!world.get::<Player>(player).unwrap().is_drawing_trail);
+    let tiles = world.resource::<Tiles>();
+    // The trail itself is land now, on whichever floor it was laid
+    assert_eq!(tiles.get(25, 20).unwrap().owner, Some(player));
+    assert_eq!(tiles.get(68, 17).unwrap().owner, Some(player));
+    assert_eq!(tiles.get(24, 17).unwrap().owner, None);
+    assert_eq!(tiles.get(66, 17).unwrap().owner, None);
+    assert!(tiles.iter().all(|tile| tile.trail_owner.is_none()));
+}
+
+#[test]
+fn the_shrinking_zone_warns_then_kills_whoever_is_left_outside() {
+    let mut rules = RulesPreset::ShrinkingZone.rules();
+    rules.shrinking_zone = Some(ZoneRules {
+        first_shrink: 0.5,
+        ..ZoneRules::default()
+    });
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules,
+        external_players: 1,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let player = game.external_players()[0];
+
+    let ring = ZoneBounds {
+        min: (1, 1),
+        max: (38, 28),
+    };
+    let zone = game.app().world().resource::<SafeZone>();
+    assert_eq!(zone.bounds, ZoneBounds::whole_map(40, 30));
+    // Already inside the warning time, the first ring closes at 0.5s
+    assert_eq!(zone.warning(0.0), Some(ring));
+
+    // Standing in the outer ring when it closes
+    let world = game.app_mut().world_mut();
+    world.get_mut::<Player>(player).unwrap().last_tile_pos = (0, 10);
+    for _ in 0..40 {
+        game.step();
+    }
+
+    let world = game.app().world();
+    assert_eq!(world.resource::<SafeZone>().bounds, ring);
+    assert!(world.get::<Respawning>(player).is_some());
+    assert!(ring.contains(world.get::<Player>(player).unwrap().last_tile_pos));
+    assert_eq!(world.resource::<MatchStats>().deaths(player), 1);
+}
+
+#[test]
+fn bots_head_back_into_the_zone_before_it_closes() {
+    let bounds = ZoneBounds::whole_map(40, 30);
+    let next = bounds.shrunk(10).shrunk(10);
+    let decide = |direction: Vec2| {
+        let me = PlayerView {
+            entity: Entity::from_raw(1),
+            tile: (1, 10),
+            direction,
+            drawing_trail: false,
+            score: 25,
+            is_bot: true,
+        };
+        let world = WorldSnapshot {
+            map: TileMap::new(40, 30),
+            players: vec![me],
+            time_left: 60.0,
+            zone: Some(ZoneView {
+                bounds,
+                next,
+                seconds_to_shrink: Some(3.0),
+            }),
+        };
+        LoopBrain::default().decide(&BrainInput {
+            me: &me,
+            tuning: BotTuning::default(),
+            world: &world,
+        })
+    };
+
+    assert_eq!(decide(Vec2::NEG_Y), Vec2::X);
+    // Turning back would be ignored, so it turns towards the middle first
+    assert_eq!(decide(Vec2::NEG_X), Vec2::Y);
+}
+
+#[test]
+fn share_codes_carry_the_setup_and_read_back_the_same() {
+    let rules = GameRules {
+        respawn_delay: Some(6.0),
+        trail_cuts: true,
+        game_speed: GameSpeed::Blitz,
+        weather: Some(WeatherRules::default()),
+        ..GameRules::casual()
+    };
+    let code = ShareCode::new(2, 0xdead_beef, GridTopology::Mirrored, &rules);
+    let text = code.to_string();
+    assert_eq!(text.len(), SHARE_CODE_LENGTH + 2, "{}", text);
+
+    // Typed in lower case with the dashes left out is fine
+    let read = ShareCode::parse(&text.replace('-', "").to_lowercase()).unwrap();
+    assert_eq!(read.to_string(), text);
+    assert_eq!((read.map, read.seed), (2, 0xdead_beef));
+    assert_eq!(read.topology, GridTopology::Mirrored);
+    assert_eq!(read.rules.death_penalty, DeathPenalty::TrailOnly);
+    assert_eq!(read.rules.respawn_location, RespawnLocation::NearestToDeath);
+    assert_eq!(read.rules.respawn_delay, Some(6.0));
+    assert_eq!(read.rules.game_speed, GameSpeed::Blitz);
+    assert!(read.rules.trail_cuts && read.rules.pickups.is_some() && read.rules.energy.is_some());
+    assert!(read.rules.shrinking_zone.is_none() && read.rules.hazard_walls.is_none());
+    assert_eq!(read.rules.weather, Some(WeatherRules::default()));
+
+    // A slip of the finger is caught rather than played
+    let mut typo: Vec<char> = text.chars().collect();
+    typo[0] = if typo[0] == 'A' { 'B' } else { 'A' };
+    assert_eq!(
+        ShareCode::parse(&typo.into_iter().collect::<String>()).unwrap_err(),
+        ShareCodeError::Checksum
+    );
+    assert_eq!(
+        ShareCode::parse(&text.replace(&text[..1], "0")).unwrap_err(),
+        ShareCodeError::Character('0')
+    );
+    assert_eq!(
+        ShareCode::parse(&text[..10]).unwrap_err(),
+        ShareCodeError::Length(9)
+    );
+
+    // Only maps on the list have a code, the day's map doesn't
+    let seed = MatchSeed(7);
+    let listed = current_share_code(&SelectedMap(None), &GridSettings::default(), seed, &rules);
+    assert_eq!(listed.map(|code| code.map), Some(0));
+    let daily = DailyChallenge::generate(20_000).map;
+    assert!(current_share_code(&SelectedMap(None), &daily, seed, &rules).is_none());
+
+    // The same seed lays out the same hazard walls
+    let first_wall = |seed| {
+        let game = HeadlessMatch::new(&MatchSetup {
+            rules: GameRules {
+                hazard_walls: Some(HazardRules {
+                    first_wall: 0.5,
+                    ..HazardRules::default()
+                }),
+                ..GameRules::default()
+            },
+            bots: 0,
+            seed: Some(seed),
+            ..MatchSetup::default()
+        });
+        let schedule = game.app().world().resource::<HazardSchedule>();
+        schedule.walls[0].wall.offset
+    };
+    assert_eq!(first_wall(7), first_wall(7));
+}
+
+#[test]
+fn hazard_walls_are_announced_then_sweep_away_players_and_trails() {
+    let rules = GameRules {
+        hazard_walls: Some(HazardRules {
+            first_wall: 0.5,
+            warning: 0.5,
+            speed: 30.0,
+            ..HazardRules::default()
+        }),
+        ..GameRules::default()
+    };
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules,
+        external_players: 2,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let (caught, clear) = (game.external_players()[0], game.external_players()[1]);
+
+    // The first wall comes in from the west along a random stretch of rows
+    let schedule = game.app().world().resource::<HazardSchedule>();
+    assert_eq!(schedule.announced, 1);
+    let wall = schedule.walls[0].wall;
+    assert_eq!(wall.direction, SweepDirection::East);
+    assert_eq!(wall.length, 12);
+    let row = wall.offset;
+    let outside = if row + 12 < 30 { row + 12 } else { row - 1 };
+
+    let world = game.app_mut().world_mut();
+    world.get_mut::<Player>(caught).unwrap().last_tile_pos = (5, row);
+    world.get_mut::<Player>(clear).unwrap().last_tile_pos = (20, outside);
+    for (tile, _) in world.resource_mut::<Tiles>().iter_mut() {
+        if (tile.x, tile.y) == (10, row) {
+            tile.owner = None;
+            tile.trail_owner = Some(clear);
+        } else if (tile.x, tile.y) == (12, row) {
+            tile.owner = Some(clear);
+        }
+    }
+
+    // Across all 40 columns in well under two seconds
+    for _ in 0..120 {
+        game.step();
+    }
+
+    let world = game.app_mut().world_mut();
+    assert!(world.resource::<HazardSchedule>().walls.is_empty());
+    assert_eq!(world.resource::<MatchStats>().deaths(caught), 1);
+    assert_eq!(world.resource::<MatchStats>().deaths(clear), 0);
+    let at = |x, y| {
+        world
+            .resource::<Tiles>()
+            .iter()
+            .find(|tile| (tile.x, tile.y) == (x, y))
+            .map(|tile| (tile.owner, tile.trail_owner))
+            .unwrap()
+    };
+    // Trails are wiped, territory is left alone
+    assert_eq!(at(10, row), (None, None));
+    assert_eq!(at(12, row), (Some(clear), None));
+}
+
+#[test]
+fn weather_is_announced_ahead_then_slows_and_pushes_everyone() {
+    let rules = GameRules {
+        weather: Some(WeatherRules {
+            first_front: 0.5,
+            warning: 0.5,
+            duration: 60.0,
+            ..WeatherRules::default()
+        }),
+        ..GameRules::default()
+    };
+
+    // Tiles covered in a second by a player heading east and one heading
+    // west, under whatever weather the front is turned into
+    let covered = |kind: Option<WeatherKind>| {
+        let mut game = HeadlessMatch::new(&MatchSetup {
+            rules: rules.clone(),
+            external_players: 2,
+            bots: 0,
+            ..MatchSetup::default()
+        });
+        let (east, west) = (game.external_players()[0], game.external_players()[1]);
+
+        // Announced on the first frame, well before it sets in
+        let world = game.app_mut().world_mut();
+        let mut schedule = world.resource_mut::<WeatherSchedule>();
+        let front = schedule.front.expect("front announced");
+        assert_eq!((front.starts_at, front.ends_at), (0.5, 60.5));
+        assert_eq!(schedule.active(0.0), None);
+        match kind {
+            Some(kind) => schedule.front.as_mut().unwrap().kind = kind,
+            None => schedule.front = None,
+        }
+
+        for (player, tile, direction) in [(east, (10, 8), Vec2::X), (west, (30, 22), Vec2::NEG_X)] {
+            world.get_mut::<Player>(player).unwrap().last_tile_pos = tile;
+            world.get_mut::<DirectionIntent>(player).unwrap().0 = direction;
+        }
+        // Past the warning, then a second of weather
+        for _ in 0..90 {
+            game.step();
+        }
+        let world = game.app().world();
+        let x = |player| {
+            let player = world.get::<Player>(player).unwrap();
+            player.last_tile_pos.0 as f32 + player.direction.x * player.progress
+        };
+        (x(east) - 10.0, 30.0 - x(west))
+    };
+
+    let (calm, _) = covered(None);
+    let (rain_east, rain_west) = covered(Some(WeatherKind::Rain));
+    let (with_wind, into_wind) = covered(Some(WeatherKind::Wind(SweepDirection::East)));
+    let (fog_east, _) = covered(Some(WeatherKind::Fog));
+
+    assert!(calm > 5.0, "{}", calm);
+    assert!(
+        rain_east < calm && rain_west < calm,
+        "{} {}",
+        rain_east,
+        calm
+    );
+    assert!(
+        with_wind > calm && into_wind < calm,
+        "{} {}",
+        with_wind,
+        into_wind
+    );
+    assert_eq!(fog_east, calm);
+
+    // Fog only hides what's outside the local players' radius
+    let schedule = WeatherSchedule {
+        front: Some(WeatherFront {
+            kind: WeatherKind::Fog,
+            starts_at: 0.0,
+            ends_at: 10.0,
+        }),
+        ..WeatherSchedule::new(WeatherRules::default())
+    };
+    assert_eq!(schedule.vision_radius(5.0), Some(6.0));
+    assert_eq!(schedule.vision_radius(10.0), None);
+    let vision = Vision {
+        radius: schedule.vision_radius(5.0),
+        centers: vec![(10, 10)],
+    };
+    assert!(vision.sees((14, 14)) && !vision.sees((15, 15)));
+    assert!(Vision::default().sees((15, 15)));
+}
+
+#[test]
+fn claiming_earns_energy_that_pays_for_bombs_and_boosts() {
+    let energy_rules = EnergyRules {
+        tiles_per_energy: 3,
+        max_energy: 10,
+        bomb_cost: 3,
+        boost_cost: 2,
+    };
+    let mut capped = Energy::default();
+    capped.add_tiles(100, &energy_rules);
+    assert_eq!((capped.amount, capped.tiles), (10, 0));
+
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: GameRules {
+            energy: Some(energy_rules),
+            ..GameRules::default()
+        },
+        external_players: 1,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let player = game.external_players()[0];
+    game.step();
+
+    let request = |game: &mut HeadlessMatch, ability: Ability| {
+        let world = game.app_mut().world_mut();
+        world.get_mut::<Player>(player).unwrap().last_tile_pos = (30, 20);
+        world.get_mut::<Energy>(player).unwrap().requested = Some(ability);
+        let score = world.get::<Player>(player).unwrap().score;
+        game.step();
+        let world = game.app().world();
+        (
+            world.get::<Player>(player).unwrap().score - score,
+            world.get::<Energy>(player).unwrap().amount,
+        )
+    };
+
+    // Nothing to spend yet, the request is dropped
+    assert_eq!(request(&mut game, Ability::Bomb), (0, 0));
+    assert!(game
+        .app()
+        .world()
+        .get::<Energy>(player)
+        .unwrap()
+        .requested
+        .is_none());
+
+    // The bomb's nine tiles earn back all three points it cost
+    game.app_mut()
+        .world_mut()
+        .get_mut::<Energy>(player)
+        .unwrap()
+        .amount = 3;
+    assert_eq!(request(&mut game, Ability::Bomb), (9, 3));
+    let world = game.app_mut().world_mut();
+    let claimed = world
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| (tile.x - 30).abs() <= 1 && (tile.y - 20).abs() <= 1)
+        .all(|tile| tile.owner == Some(player) && tile.trail_owner.is_none());
+    assert!(claimed);
+
+    assert_eq!(request(&mut game, Ability::Boost), (0, 1));
+    assert!(game.app().world().get::<SpeedBoost>(player).is_some());
+    assert_eq!(request(&mut game, Ability::Bomb), (0, 1));
+}
+
+#[test]
+fn decoys_wander_off_leaving_a_fake_trail_and_pop_without_a_kill() {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        external_players: 2,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let (owner, rival) = (game.external_players()[0], game.external_players()[1]);
+    // Out in the open, so its trail starts straight away
+    let tile = (30, 20);
+    let world = game.app_mut().world_mut();
+    world.get_mut::<Player>(owner).unwrap().last_tile_pos = tile;
+    world.spawn(Pickup {
+        kind: PickupKind::Decoy,
+        tile,
+    });
+    for _ in 0..60 {
+        game.step();
+    }
+
+    let world = game.app_mut().world_mut();
+    let trail = {
+        let mut decoys = world.query::<&Decoy>();
+        let decoy = decoys.single(world);
+        assert_eq!(decoy.owner, owner);
+        decoy.trail.clone()
+    };
+    assert!(!trail.is_empty());
+    assert_eq!(
+        world.query::<&DecoyTrail>().iter(world).count(),
+        trail.len()
+    );
+    // The fake trail is only drawn, the tiles under it are untouched
+    let tiles_touched = world.resource::<Tiles>().iter().any(|tile| {
+        tile.trail_owner.is_some()
+            || (tile.owner == Some(owner) && trail.contains(&(tile.x, tile.y)))
+    });
+    assert!(!tiles_touched);
+
+    let scores: Vec<u32> = [owner, rival]
+        .iter()
+        .map(|&player| world.get::<Player>(player).unwrap().score)
+        .collect();
+    world.get_mut::<Player>(rival).unwrap().last_tile_pos = trail[0];
+    game.step();
+
+    let world = game.app_mut().world_mut();
+    assert_eq!(world.query::<&Decoy>().iter(world).count(), 0);
+    assert_eq!(world.query::<&DecoyTrail>().iter(world).count(), 0);
+    assert_eq!(game.summary().deaths, 0);
+    assert_eq!(game.summary().kills, 0);
+    let world = game.app().world();
+    for (player, score) in [owner, rival].into_iter().zip(scores) {
+        assert_eq!(world.get::<Player>(player).unwrap().score, score);
+    }
+}
+
+#[cfg(feature = "gym")]
+#[test]
+fn gym_steps_agents_and_rewards_claims() {
+    use landio::gym::{Action, GymConfig, GymEnv, OBSERVATION_CHANNELS, OWN_HEAD, OWN_LAND};
+
+    let mut env = GymEnv::new(GymConfig::default());
+    let observations = env.reset();
+    assert_eq!(observations.len(), 1);
+    assert_eq!(observations[0].shape(), [OBSERVATION_CHANNELS, 30, 40]);
+    assert_eq!(observations[0].get(OWN_HEAD, 20, 15), 1.0);
+    assert_eq!(observations[0].get(OWN_LAND, 22, 17), 1.0);
+
+    // Out of the territory, up, back in: a closed loop claims land
+    let mut total = 0.0;
+    for action in [Action::Right, Action::Up, Action::Left, Action::Down] {
+        for _ in 0..15 {
+            let (_, rewards, done) = env.step(&[action]);
+            assert!(!done);
+            total += rewards[0];
+        }
+    }
+    assert!(total > 0.0, "claiming should pay out, got {total}");
+}
+
+#[test]
+fn tournament_brackets_move_winners_on_to_a_champion() {
+    let field = |size: usize| {
+        (0..size)
+            .map(|index| Participant {
+                name: format!("Bot {}", index + 1),
+                entrant: Entrant::Bot,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert!(Tournament::new(field(6)).is_none());
+
+    let mut tournament = Tournament::new(field(4)).unwrap();
+    assert_eq!(tournament.rounds.len(), 2);
+    assert_eq!(tournament.next_match(), Some((0, 0)));
+    assert_eq!(tournament.record(0, 0, [10, 30]), Some(1));
+    // Ties go to the side drawn first
+    assert_eq!(tournament.record(0, 1, [25, 25]), Some(2));
+    assert_eq!(tournament.rounds[1][0].sides, [Some(1), Some(2)]);
+    assert_eq!(tournament.round_name(1), "Final");
+    assert_eq!(tournament.next_match(), Some((1, 0)));
+    tournament.record(1, 0, [40, 12]);
+    assert_eq!(tournament.next_match(), None);
+    assert_eq!(tournament.champion().unwrap().name, "Bot 2");
+
+    // Everyone who joined gets a place, bots make up the rest
+    let drawn = Tournament::random_draw(2, 8).unwrap();
+    let humans = drawn
+        .participants
+        .iter()
+        .filter(|participant| matches!(participant.entrant, Entrant::Human { .. }))
+        .count();
+    assert_eq!((drawn.participants.len(), humans), (8, 2));
+    assert_eq!(drawn.round_name(0), "Quarterfinals");
+}
+
+#[test]
+fn tournament_matches_pit_the_two_sides_and_fill_in_the_bracket() {
+    let mut app = join_screen_app();
+    let mut tournament = Tournament::random_draw(0, 4).unwrap();
+    tournament.match_seconds = 1.0;
+    app.insert_resource(tournament)
+        .insert_resource(TournamentMatch::new(0, 1));
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    app.update();
+
+    let world = app.world_mut();
+    assert_eq!(world.query::<&Player>().iter(world).count(), 2);
+
+    let frames = ((COUNTDOWN_SECONDS + 1.0) / FRAME.as_secs_f32()).ceil() as usize + 2;
+    run_frames(&mut app, frames);
+    assert!(app.world().resource::<TournamentMatch>().decided);
+    let tournament = app.world().resource::<Tournament>();
+    let winner = tournament.rounds[0][1].winner.unwrap();
+    assert_eq!(tournament.rounds[1][0].sides, [None, Some(winner)]);
+    assert_eq!(tournament.next_match(), Some((0, 0)));
+
+    // Enter goes back to the bracket with the map cleared
+    app.world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::Enter);
+    run_frames(&mut app, 2);
+    assert_eq!(
+        *app.world().resource::<State<AppState>>().get(),
+        AppState::Tournament
+    );
+    let world = app.world_mut();
+    assert_eq!(world.query::<&Player>().iter(world).count(), 0);
+    assert!(world
+        .resource::<Tiles>()
+        .iter()
+        .all(|tile| tile.owner.is_none()));
+}
+
+#[test]
+fn match_xp_levels_profiles_up_and_unlocks_cosmetics_and_maps() {
+    assert_eq!(xp_for_match(40, 2, true), 40 + 50 + 50);
+    assert_eq!(level_for_xp(0), 1);
+    assert_eq!(level_for_xp(xp_for_level(3) - 1), 2);
+    assert_eq!(level_for_xp(xp_for_level(3)), 3);
+
+    assert_eq!(unlocked_colors(1).count(), 3);
+    assert_eq!(
+        unlocked_trail_styles(1).collect::<Vec<_>>(),
+        vec![TrailStyle::Solid]
+    );
+    assert!(unlocked_trail_styles(2).any(|style| style == TrailStyle::Dashed));
+    assert_eq!(unlocked_maps(1).count(), 1);
+
+    // A picked map gets laid out when the match starts
+    let mut app = join_screen_app();
+    app.insert_resource(SelectedMap(Some(1)));
+    app.insert_resource(JoinedPlayers {
+        devices: vec![InputDevice::KeyboardWasd],
+    });
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    app.update();
+
+    let grid = app.world().resource::<GridSettings>();
+    assert_eq!((grid.grid_width, grid.grid_height), (30, 22));
+    let world = app.world_mut();
+    assert_eq!(world.resource::<Tiles>().len(), 30 * 22);
+}
+
+#[test]
+fn weekly_challenges_rotate_by_week_and_track_progress_per_profile() {
+    let challenges = WeeklyChallenges::generate(2900);
+    assert_eq!(challenges, WeeklyChallenges::generate(2900));
+    assert_eq!(challenges.goals.len(), 3);
+    assert!((2900..2910).any(|week| WeeklyChallenges::generate(week).goals != challenges.goals));
+
+    let challenges = WeeklyChallenges {
+        week: 2900,
+        goals: vec![WeeklyGoal::WinWithoutDying, WeeklyGoal::ClaimTiles(500)],
+    };
+    let mut record = ChallengeRecord::default();
+
+    let lost = MatchResult {
+        tiles_claimed: 300,
+        deaths: 0,
+        ..MatchResult::default()
+    };
+    assert!(record.record(&challenges, &lost).is_empty());
+    assert_eq!(record.progress_for(&challenges), vec![0, 300]);
+
+    let won_after_dying = MatchResult {
+        tiles_claimed: 300,
+        deaths: 1,
+        won: true,
+        ..MatchResult::default()
+    };
+    assert_eq!(
+        record.record(&challenges, &won_after_dying),
+        vec![WeeklyGoal::ClaimTiles(500)]
+    );
+    assert_eq!(record.progress_for(&challenges), vec![0, 500]);
+
+    let clean_win = MatchResult {
+        won: true,
+        ..MatchResult::default()
+    };
+    assert_eq!(
+        record.record(&challenges, &clean_win),
+        vec![WeeklyGoal::WinWithoutDying]
+    );
+    assert_eq!(record.completed, 2);
+
+    // A new week starts everyone from zero
+    let next_week = WeeklyChallenges {
+        week: 2901,
+        ..challenges
+    };
+    assert_eq!(record.progress_for(&next_week), vec![0, 0]);
+}
+
+#[test]
+fn display_settings_cap_frames_and_idle_menus_in_low_power() {
+    assert_eq!(
+        FrameCap::Fps60.frame_time(),
+        Some(Duration::from_secs(1) / 60)
+    );
+    assert_eq!(FrameCap::Uncapped.frame_time(), None);
+    assert_eq!(FrameCap::Uncapped.next(), FrameCap::Fps30);
+
+    let mut settings = DisplaySettings::default();
+    assert_eq!(settings.update_mode(true), UpdateMode::Continuous);
+    settings.low_power = true;
+    assert_eq!(settings.update_mode(false), UpdateMode::Continuous);
+    assert!(matches!(
+        settings.update_mode(true),
+        UpdateMode::Reactive { .. }
+    ));
+}
+
+#[test]
+fn settings_saves_and_captures_go_under_one_data_directory() {
+    let root = std::env::temp_dir().join(format!("landio-paths-{}", std::process::id()));
+    let paths = Paths::in_dir(&root);
+
+    let mut store = ProfileStore::default();
+    store.create();
+    store.save(&paths);
+    let config = GameConfig {
+        camera: CameraMode::Chase,
+        ..default()
+    };
+    config.save(&paths);
+    StatsStore::default().save(&paths);
+
+    for file in [paths.saves(), paths.config(), paths.stats()] {
+        assert!(
+            file.starts_with(&root) && file.exists(),
+            "{}",
+            file.display()
+        );
+    }
+    assert_eq!(ProfileStore::load(&paths).profiles.len(), 1);
+    assert_eq!(GameConfig::load(&paths).camera, CameraMode::Chase);
+
+    // Relative capture names go in captures, absolute ones are left alone
+    assert_eq!(paths.capture("run.jsonl"), root.join("captures/run.jsonl"));
+    let elsewhere = std::env::temp_dir().join("run.jsonl");
+    assert_eq!(paths.capture(&elsewhere.to_string_lossy()), elsewhere);
+
+    let args = ["landio".to_string(), "--portable".to_string()];
+    assert_eq!(Paths::from_args(&args), Paths::portable());
+
+    // Files an earlier version left in the working directory move in, but
+    // never over ones already here
+    let old = root.join("old");
+    std::fs::create_dir_all(&old).unwrap();
+    for name in ["config.ron", "profiles.ron", "stats.ron"] {
+        std::fs::write(old.join(name), "()").unwrap();
+    }
+    std::fs::remove_file(paths.stats()).unwrap();
+    paths.migrate_from(&old);
+    assert_eq!(std::fs::read_to_string(paths.stats()).unwrap(), "()");
+    assert!(!old.join("stats.ron").exists());
+    assert_eq!(GameConfig::load(&paths).camera, CameraMode::Chase);
+    assert!(old.join("config.ron").exists() && old.join("profiles.ron").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+// Hands column 24 to someone else, right of the starting territory
+fn give_column_away(app: &mut App) -> Entity {
+    let world = app.world_mut();
+    let rival = world.spawn_empty().id();
+    for (tile, _) in world.resource_mut::<Tiles>().iter_mut() {
+        if tile.x == 24 {
+            tile.owner = Some(rival);
+        }
+    }
+    rival
+}
+
+// Land and trail owners of a tile
+fn owners_at(app: &mut App, at: (i32, i32)) -> (Option<Entity>, Option<Entity>) {
+    let world = app.world_mut();
+    world
+        .resource::<Tiles>()
+        .iter()
+        .find(|tile| (tile.x, tile.y) == at)
+        .map(|tile| (tile.owner, tile.trail_owner))
+        .unwrap()
+}
+
+#[test]
+fn trails_cross_other_players_land_without_taking_it_until_claimed() {
+    // Dying leaves the land the trail ran over with its owner
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+    let rival = give_column_away(&mut app);
+    steer(&mut app, KeyCode::KeyD, |(x, _)| x >= 25);
+    let row = player(&mut app).last_tile_pos.1;
+
+    assert_eq!(owners_at(&mut app, (24, row)), (Some(rival), Some(entity)));
+
+    app.world_mut().send_event(PlayerDeathEvent {
+        player_entity: entity,
+        reason: PlayerDeathReason::HazardWall,
+        killer: None,
+        tile: (25, row),
+        trail_length: 3,
+    });
+    run_frames(&mut app, 2);
+    assert_eq!(owners_at(&mut app, (24, row)), (Some(rival), None));
+
+    // Closing the loop takes just the land the trail crossed
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+    let rival = give_column_away(&mut app);
+    steer(&mut app, KeyCode::KeyD, |(x, _)| x >= 25);
+    let row = player(&mut app).last_tile_pos.1;
+    steer(&mut app, KeyCode::KeyW, |(_, y)| y >= 19);
+    steer(&mut app, KeyCode::KeyA, |(x, _)| x <= 21);
+    steer(&mut app, KeyCode::KeyS, |(_, y)| y <= 17);
+    run_frames(&mut app, 10);
+
+    assert_eq!(owned_tiles(&mut app, entity).1, 0);
+    assert_eq!(owners_at(&mut app, (24, row)), (Some(entity), None));
+    assert_eq!(owners_at(&mut app, (24, 0)), (Some(rival), None));
+}
+
+#[test]
+fn respawn_delays_spectate_the_killer_then_respawn_away_from_opponents() {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: GameRules {
+            respawn_delay: Some(2.0),
+            ..GameRules::default()
+        },
+        external_players: 2,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let (killer, victim) = (game.external_players()[0], game.external_players()[1]);
+    game.step();
+
+    game.app_mut().world_mut().send_event(PlayerDeathEvent {
+        player_entity: victim,
+        reason: PlayerDeathReason::TrailCut,
+        killer: Some(killer),
+        tile: (0, 0),
+        trail_length: 0,
+    });
+    game.step();
+
+    let world = game.app().world();
+    assert_eq!(
+        world.get::<Spectating>(victim).unwrap().killer,
+        Some(killer)
+    );
+    assert_eq!(
+        world.get::<Respawning>(victim).unwrap().timer.duration(),
+        Duration::from_secs(2)
+    );
+
+    // The killer parks right on the victim's respawn tile
+    let respawn = world.get::<Player>(victim).unwrap().last_tile_pos;
+    game.app_mut()
+        .world_mut()
+        .get_mut::<Player>(killer)
+        .unwrap()
+        .last_tile_pos = respawn;
+
+    for _ in 0..(2.0 / FRAME.as_secs_f32()) as usize + 2 {
+        game.step();
+    }
+
+    let world = game.app().world();
+    assert!(world.get::<Respawning>(victim).is_none());
+    assert!(world.get::<Spectating>(victim).is_none());
+    let back_at = world.get::<Player>(victim).unwrap().last_tile_pos;
+    assert_eq!(
+        (back_at.0 - respawn.0)
+            .abs()
+            .max((back_at.1 - respawn.1).abs()),
+        2,
+        "should come back in the corner of the starting territory furthest from the killer"
+    );
+}
+
+#[test]
+fn chasers_get_assists_and_quick_kills_chain_into_multi_kills() {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        external_players: 4,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let players = game.external_players().to_vec();
+    let (killer, chaser, victim, other) = (players[0], players[1], players[2], players[3]);
+    game.step();
+
+    // The chaser sits on the victim's heels while the killer finishes them
+    let victim_tile = game
+        .app()
+        .world()
+        .get::<Player>(victim)
+        .unwrap()
+        .last_tile_pos;
+    game.app_mut()
+        .world_mut()
+        .get_mut::<Player>(chaser)
+        .unwrap()
+        .last_tile_pos = victim_tile;
+    game.step();
+
+    let kill = |game: &mut HeadlessMatch, player_entity| {
+        game.app_mut().world_mut().send_event(PlayerDeathEvent {
+            player_entity,
+            reason: PlayerDeathReason::TrailCut,
+            killer: Some(killer),
+            tile: (0, 0),
+            trail_length: 0,
+        });
+        game.step();
+    };
+    kill(&mut game, victim);
+    kill(&mut game, other);
+
+    let world = game.app().world();
+    let stats = world.resource::<MatchStats>();
+    assert_eq!(stats.assists(chaser), 1);
+    assert_eq!(stats.assists(killer), 0);
+    assert_eq!(stats.best_multi_kill[&killer], 2);
+    let events = world.resource::<Events<MultiKillEvent>>();
+    let called_out: Vec<u32> = events
+        .get_cursor()
+        .read(events)
+        .map(|event| event.kills)
+        .collect();
+    assert_eq!(called_out, vec![2]);
+
+    // Long after the chase, and the chain, there's no credit for either
+    game.app_mut()
+        .world_mut()
+        .get_mut::<Player>(chaser)
+        .unwrap()
+        .last_tile_pos = (0, 0);
+    for _ in 0..(MULTI_KILL_SECONDS / FRAME.as_secs_f32()) as usize + 2 {
+        game.step();
+    }
+    kill(&mut game, victim);
+
+    let stats = game.app().world().resource::<MatchStats>();
+    assert_eq!(stats.assists(chaser), 1);
+    assert_eq!(stats.best_multi_kill[&killer], 2);
+    assert_eq!(stats.kills(killer), 3);
+}
+
+#[test]
+fn the_leader_carries_a_bounty_their_killer_collects() {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: GameRules {
+            bounty: Some(BountyRules {
+                points: 50,
+                min_score: 30,
+            }),
+            ..GameRules::default()
+        },
+        external_players: 3,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let players = game.external_players().to_vec();
+    let (leader, hunter) = (players[0], players[1]);
+    game.step();
+
+    // Everyone starts level, so nobody has a bounty on them yet
+    let targets = |game: &mut HeadlessMatch| {
+        let world = game.app_mut().world_mut();
+        world
+            .query_filtered::<Entity, With<BountyTarget>>()
+            .iter(world)
+            .collect::<Vec<_>>()
+    };
+    assert!(targets(&mut game).is_empty());
+
+    game.app_mut()
+        .world_mut()
+        .get_mut::<Player>(leader)
+        .unwrap()
+        .score = 100;
+    game.step();
+    assert_eq!(targets(&mut game), vec![leader]);
+
+    let hunter_score = game.app().world().get::<Player>(hunter).unwrap().score;
+    game.app_mut().world_mut().send_event(PlayerDeathEvent {
+        player_entity: leader,
+        reason: PlayerDeathReason::TrailCut,
+        killer: Some(hunter),
+        tile: (0, 0),
+        trail_length: 0,
+    });
+    game.step();
+
+    let world = game.app().world();
+    assert_eq!(
+        world.get::<Player>(hunter).unwrap().score,
+        hunter_score + 50
+    );
+    let events = world.resource::<Events<BountyClaimedEvent>>();
+    let claimed: Vec<_> = events.get_cursor().read(events).copied().collect();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!((claimed[0].killer, claimed[0].victim), (hunter, leader));
+
+    // The bounty outlasts the hunter's own death, which only costs land
+    game.app_mut().world_mut().send_event(PlayerDeathEvent {
+        player_entity: hunter,
+        reason: PlayerDeathReason::OutOfBounds,
+        killer: None,
+        tile: (0, 0),
+        trail_length: 0,
+    });
+    game.step();
+    let world = game.app().world();
+    let land: u32 = world
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| tile.owner == Some(hunter))
+        .map(|tile| tile.value)
+        .sum();
+    let hunter = world.get::<Player>(hunter).unwrap();
+    assert_eq!(hunter.bonus, 50);
+    assert_eq!(hunter.score, land + 50);
+}
+
+#[test]
+fn last_place_gets_a_comeback_shield_that_soaks_up_one_cut() {
+    let mut game = HeadlessMatch::new(&MatchSetup {
+        rules: GameRules {
+            trail_cuts: true,
+            comeback: Some(ComebackRules::default()),
+            ..GameRules::default()
+        },
+        external_players: 3,
+        bots: 0,
+        ..MatchSetup::default()
+    });
+    let players = game.external_players().to_vec();
+    let (trailing, attacker) = (players[0], players[1]);
+    game.step();
+
+    // Nobody is behind yet
+    let shielded = |game: &HeadlessMatch| {
+        game.app()
+            .world()
+            .get::<Comeback>(trailing)
+            .map(|comeback| comeback.shielded)
+    };
+    assert_eq!(shielded(&game), None);
+
+    let base_speed = game.app().world().get::<Player>(trailing).unwrap().speed;
+    for &player in players.iter().skip(1) {
+        game.app_mut()
+            .world_mut()
+            .get_mut::<Player>(player)
+            .unwrap()
+            .score = 80;
+    }
+    game.step();
+    assert_eq!(shielded(&game), Some(true));
+    let speed = game.app().world().get::<Player>(trailing).unwrap().speed;
+    assert!((speed - base_speed * 1.1).abs() < 0.01);
+
+    // The first cut breaks the shield and the trail, not the player
+    let cut_tile = (30, 25);
+    let world = game.app_mut().world_mut();
+    for (tile, _) in world.resource_mut::<Tiles>().iter_mut() {
+        if (tile.x, tile.y) == cut_tile {
+            tile.trail_owner = Some(trailing);
+        }
+    }
+    world.get_mut::<Player>(trailing).unwrap().is_drawing_trail = true;
+    world.get_mut::<Player>(attacker).unwrap().last_tile_pos = cut_tile;
+    game.step();
+
+    let world = game.app_mut().world_mut();
+    assert!(world.get::<Respawning>(trailing).is_none());
+    assert!(!world.get::<Comeback>(trailing).unwrap().shielded);
+    assert!(!world.get::<Player>(trailing).unwrap().is_drawing_trail);
+    assert!(!world
+        .resource::<Tiles>()
+        .iter()
+        .any(|tile| tile.trail_owner == Some(trailing)));
+
+    // Catching up takes the help away again
+    world.get_mut::<Player>(trailing).unwrap().score = 200;
+    game.step();
+    assert_eq!(shielded(&game), None);
+    let speed = game.app().world().get::<Player>(trailing).unwrap().speed;
+    assert!((speed - base_speed).abs() < 0.01);
+}
+
+#[test]
+fn grid_math_converts_between_world_positions_and_tiles() {
+    let grid = GridMath::new(&GridSettings::default());
+
+    // 40x30 tiles of 20px centered on the origin
+    assert_eq!(grid.center_of(0, 0), Vec2::new(-390.0, -290.0));
+    assert_eq!(grid.center_of(39, 29), Vec2::new(390.0, 290.0));
+    assert_eq!(grid.corner_of(20, 15), Vec2::ZERO);
+    for (x, y) in [(0, 0), (7, 3), (39, 29)] {
+        assert_eq!(grid.tile_of(grid.center_of(x, y)), (x, y));
+    }
+    // Edges belong to the tile above and to the right
+    assert_eq!(grid.tile_of(Vec2::ZERO), (20, 15));
+    assert_eq!(grid.tile_of(Vec2::new(-0.1, -0.1)), (19, 14));
+    assert_eq!(grid.tile_of(Vec2::new(-401.0, 0.0)), (-1, 15));
+
+    assert!(grid.in_bounds(39, 29));
+    assert!(!grid.in_bounds(40, 0));
+    assert!(!grid.in_bounds(0, -1));
+    assert_eq!(grid.clamp(-3, 31), (0, 29));
+
+    let mut corner: Vec<_> = grid.neighbors(0, 0).collect();
+    corner.sort();
+    assert_eq!(corner, vec![(0, 1), (1, 0)]);
+    assert_eq!(grid.neighbors(5, 5).count(), 4);
+    assert_eq!(grid.around(5, 5, 1).count(), 9);
+    assert_eq!(grid.around(0, 0, 2).count(), 9);
+}
+
+#[test]
+fn tile_map_neighborhoods_and_rays_stay_on_the_map() {
+    let player = Entity::from_raw(1);
+    let mut map = TileMap::new(10, 8);
+
+    let mut corner: Vec<_> = map.neighbors4(0, 0).collect();
+    corner.sort();
+    assert_eq!(corner, vec![(0, 1), (1, 0)]);
+    assert_eq!(map.neighbors8(0, 0).count(), 3);
+    assert_eq!(map.neighbors8(4, 4).count(), 8);
+
+    map.set(
+        6,
+        3,
+        TileState {
+            owner: None,
+            trail_owner: Some(player),
+        },
+    );
+    let is_trail = |_: (i32, i32), state: TileState| state.trail_owner.is_some();
+    assert_eq!(
+        map.first_hit_along((1, 3), IVec2::X, 10, is_trail),
+        Some((6, 3))
+    );
+    // Too short, pointing away, or standing on it
+    assert_eq!(map.first_hit_along((1, 3), IVec2::X, 4, is_trail), None);
+    assert_eq!(
+        map.first_hit_along((1, 3), IVec2::NEG_X, 10, is_trail),
+        None
+    );
+    assert_eq!(map.first_hit_along((6, 3), IVec2::X, 10, is_trail), None);
+}
+
+// Turns a tile on a 10x10 map a quarter turn anticlockwise `turns` times
+fn turned((x, y): (i32, i32), turns: usize) -> (i32, i32) {
+    (0..turns).fold((x, y), |(x, y), _| (9 - y, x))
+}
+
+// Enclosed and claimed cells for a loop given in bottom-left coordinates on a
+// 10x10 map, turned to each of the four sides in turn, checked against the
+// tiles it should claim
+fn assert_claims_on_every_side(land: &[(i32, i32)], trail: &[(i32, i32)], claims: &[(i32, i32)]) {
+    for turns in 0..4 {
+        let mut blocked = FixedBitSet::with_capacity(100);
+        for &tile in land.iter().chain(trail) {
+            let (x, y) = turned(tile, turns);
+            blocked.insert((y * 10 + x) as usize);
+        }
+        let trail: Vec<_> = trail.iter().map(|&tile| turned(tile, turns)).collect();
+        let mut expected: Vec<_> = claims.iter().map(|&tile| turned(tile, turns)).collect();
+        expected.sort();
+
+        let mut enclosed = enclosed_cells(10, 10, &blocked);
+        enclosed.sort();
+        let mut claimed = pockets_touching(10, 10, &enclosed, &trail).concat();
+        claimed.sort();
+        assert_eq!(claimed, expected, "turned {turns} times");
+        // Nothing past the loop, the rest of the map stays outside
+        assert_eq!(enclosed, expected, "turned {turns} times");
+    }
+}
+
+#[test]
+fn loops_along_the_map_edge_claim_the_strip_they_hug() {
+    // Out of land on the edge, along a row and back down to the edge, closing
+    // off a strip with the border as its fourth side
+    let land: Vec<_> = (2..=3).flat_map(|x| (0..=2).map(move |y| (x, y))).collect();
+    let trail = [(4, 2), (5, 2), (6, 2), (6, 1), (6, 0)];
+    assert_claims_on_every_side(&land, &trail, &[(4, 0), (4, 1), (5, 0), (5, 1)]);
+
+    // Tucked into a corner the loop closes off the corner square
+    let land = [(0, 3), (1, 3), (0, 4), (1, 4)];
+    let trail = [(2, 3), (3, 3), (3, 2), (3, 1), (3, 0)];
+    let corner: Vec<_> = (0..=2).flat_map(|x| (0..=2).map(move |y| (x, y))).collect();
+    assert_claims_on_every_side(&land, &trail, &corner);
+
+    // A wall across the map closes off the smaller side, and neither of two
+    // equal halves
+    let mut wall = FixedBitSet::with_capacity(100);
+    for y in 0..10 {
+        wall.insert((y * 10 + 6) as usize);
+    }
+    assert_eq!(enclosed_cells(10, 10, &wall).len(), 30);
+    let mut halves = FixedBitSet::with_capacity(110);
+    for y in 0..10 {
+        halves.insert((y * 11 + 5) as usize);
+    }
+    assert!(enclosed_cells(11, 10, &halves).is_empty());
+}
+
+#[test]
+fn loops_enclosing_most_of_the_map_claim_the_inside() {
+    // A loop one tile in from the edge of a 40x30 map leaves a thin strip
+    // outside it, much smaller than the 36x26 inside
+    let (width, height) = (40, 30);
+    let mut blocked = FixedBitSet::with_capacity((width * height) as usize);
+    for x in 1..=38 {
+        for y in 1..=28 {
+            if x == 1 || x == 38 || y == 1 || y == 28 {
+                blocked.insert((y * width + x) as usize);
+            }
+        }
+    }
+
+    let mut enclosed = enclosed_cells(width, height, &blocked);
+    enclosed.sort();
+    let inside: Vec<_> = (2..=37)
+        .flat_map(|x| (2..=27).map(move |y| (x, y)))
+        .collect();
+    assert_eq!(enclosed, inside);
+}
+
+// Claim fixture drawn top row first: `#` land, `+` the closing trail, `.`
+// outside, digits the pockets the trail should claim and `x` holes it
+// encloses without bordering
+fn assert_claim_fixture(rows: &[&str]) {
+    let height = rows.len() as i32;
+    let width = rows[0].len() as i32;
+    let mut blocked = FixedBitSet::with_capacity((width * height) as usize);
+    let mut trail = Vec::new();
+    let mut holes = Vec::new();
+    let mut expected: Vec<Vec<(i32, i32)>> = vec![Vec::new(); 10];
+    for (row, line) in rows.iter().enumerate() {
+        let y = height - 1 - row as i32;
+        for (x, cell) in line.chars().enumerate() {
+            let tile = (x as i32, y);
+            match cell {
+                '#' | '+' => blocked.insert((y * width + tile.0) as usize),
+                'x' => holes.push(tile),
+                '.' => {}
+                digit => expected[digit.to_digit(10).unwrap() as usize].push(tile),
+            }
+            if cell == '+' {
+                trail.push(tile);
+            }
+        }
+    }
+    let mut expected: Vec<_> = expected
+        .into_iter()
+        .filter(|pocket| !pocket.is_empty())
+        .collect();
+    expected.iter_mut().for_each(|pocket| pocket.sort());
+    expected.sort();
+
+    let mut enclosed = enclosed_cells(width, height, &blocked);
+    enclosed.sort();
+    let mut all_enclosed = expected.concat();
+    all_enclosed.extend(holes);
+    all_enclosed.sort();
+    assert_eq!(enclosed, all_enclosed);
+
+    let mut pockets = pockets_touching(width, height, &enclosed, &trail);
+    pockets.iter_mut().for_each(|pocket| pocket.sort());
+    pockets.sort();
+    assert_eq!(pockets, expected);
+}
+
+#[test]
+fn one_trail_claims_every_pocket_it_closes_off() {
+    // An S bending through a box of land
+    assert_claim_fixture(&[
+        "............",
+        ".##########.",
+        ".#111+2222#.",
+        ".#111+2222#.",
+        ".#++++++22#.",
+        ".#33333+++#.",
+        ".##########.",
+        "............",
+    ]);
+    // A comb laid against the land, next to a hole it doesn't border
+    assert_claim_fixture(&[
+        "...........",
+        ".#########.",
+        ".#1+22+33#.",
+        ".#1+22+33#.",
+        ".#+++++++#.",
+        ".#########.",
+        ".####x####.",
+        ".#########.",
+        "...........",
+    ]);
+
+    // Applying the claim reports what each pocket actually gave
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+    let world = app.world_mut();
+    let rival = world.spawn_empty().id();
+    for (tile, _) in world.resource_mut::<Tiles>().iter_mut() {
+        match (tile.x, tile.y) {
+            (30, 5) => tile.trail_owner = Some(entity),
+            (31, 5) => tile.owner = Some(rival),
+            _ => {}
+        }
+    }
+    world.send_event(ClaimComputedEvent {
+        player: entity,
+        trail_tiles: vec![(30, 5)],
+        pockets: vec![vec![(31, 5), (32, 5), (33, 5)], vec![(30, 6)]],
+        from_bomb: false,
+    });
+    app.update();
+
+    let world = app.world();
+    let events = world.resource::<Events<TerritoryClaimedEvent>>();
+    let claimed: Vec<_> = events.get_cursor().read(events).cloned().collect();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].trail_tiles, 1);
+    assert_eq!(claimed[0].pockets, vec![2, 1]);
+}
+
+// Polls the pending claims until they are all in (or a hundred frames pass)
+// and returns whose claims came through, in order
+fn claimed_players(app: &mut App) -> Vec<Entity> {
+    let mut claimed = Vec::new();
+    for _ in 0..100 {
+        app.update();
+        let events = app.world().resource::<Events<ClaimComputedEvent>>();
+        claimed.extend(events.get_cursor().read(events).map(|event| event.player));
+        app.world_mut()
+            .resource_mut::<Events<ClaimComputedEvent>>()
+            .clear();
+        if app.world().resource::<PendingClaims>().tasks.is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    claimed
+}
+
+fn claim_polling_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<PendingClaims>()
+        .add_event::<ClaimComputedEvent>()
+        .add_systems(Update, poll_claim_tasks_system);
+    app
+}
+
+fn one_tile_claim(player: Entity) -> ClaimResult {
+    ClaimResult {
+        player,
+        trail_tiles: vec![(0, 0)],
+        pockets: Vec::new(),
+    }
+}
+
+// A claim batch that only finishes once `release` is set
+fn held_claim_batch(release: &Arc<AtomicBool>, players: Vec<Entity>) -> Task<Vec<ClaimResult>> {
+    let released = release.clone();
+    AsyncComputeTaskPool::get().spawn(async move {
+        while !released.load(Ordering::Acquire) {
+            future::yield_now().await;
+        }
+        players.into_iter().map(one_tile_claim).collect()
+    })
+}
+
+#[test]
+fn a_death_only_drops_the_claims_computed_before_it() {
+    let (died, other) = (Entity::from_raw(1), Entity::from_raw(2));
+    let mut app = claim_polling_app();
+
+    // A slow batch with a claim from each, still running when one dies
+    let release = Arc::new(AtomicBool::new(false));
+    let slow = held_claim_batch(&release, vec![died, other]);
+    let mut pending = app.world_mut().resource_mut::<PendingClaims>();
+    pending.push(vec![died, other], slow);
+    pending.cancel(died);
+
+    // Respawned and closed a new loop before the slow batch is done, which
+    // waits behind it
+    let fresh = AsyncComputeTaskPool::get().spawn(async move { vec![one_tile_claim(died)] });
+    app.world_mut()
+        .resource_mut::<PendingClaims>()
+        .push(vec![died], fresh);
+    assert!(claimed_players(&mut app).is_empty());
+
+    // The claim from before the death is dropped, everyone else's goes through
+    release.store(true, Ordering::Release);
+    assert_eq!(claimed_players(&mut app), vec![other, died]);
+    assert!(app.world().resource::<PendingClaims>().cancelled.is_empty());
+}
+
+#[test]
+fn claim_batches_apply_in_the_order_they_started() {
+    let (first, second, gone) = (
+        Entity::from_raw(1),
+        Entity::from_raw(2),
+        Entity::from_raw(3),
+    );
+    let mut app = claim_polling_app();
+
+    // The later batch finishes first but still lands after the earlier one
+    let release = Arc::new(AtomicBool::new(false));
+    let slow = held_claim_batch(&release, vec![first]);
+    let fast = AsyncComputeTaskPool::get().spawn(async move { vec![one_tile_claim(second)] });
+    let mut pending = app.world_mut().resource_mut::<PendingClaims>();
+    pending.push(vec![first], slow);
+    pending.push(vec![second], fast);
+    assert!(claimed_players(&mut app).is_empty());
+    assert_eq!(app.world().resource::<PendingClaims>().tasks.len(), 2);
+
+    release.store(true, Ordering::Release);
+    assert_eq!(claimed_players(&mut app), vec![first, second]);
+
+    // A batch whose every claim was cancelled holds nothing up
+    let never = Arc::new(AtomicBool::new(false));
+    let stuck = held_claim_batch(&never, vec![gone]);
+    let fast = AsyncComputeTaskPool::get().spawn(async move { vec![one_tile_claim(second)] });
+    let mut pending = app.world_mut().resource_mut::<PendingClaims>();
+    pending.push(vec![gone], stuck);
+    pending.cancel(gone);
+    pending.push(vec![second], fast);
+    assert_eq!(claimed_players(&mut app), vec![second]);
+}
+
+// Closes a one-tile loop at `x` for the player and returns what it reported
+fn close_small_loop(app: &mut App, entity: Entity, x: i32) -> TerritoryClaimedEvent {
+    let world = app.world_mut();
+    if let Some((tile, _)) = world.resource_mut::<Tiles>().get_mut(x, 5) {
+        tile.trail_owner = Some(entity);
+    }
+    world.send_event(ClaimComputedEvent {
+        player: entity,
+        trail_tiles: vec![(x, 5)],
+        pockets: vec![vec![(x, 6)]],
+        from_bomb: false,
+    });
+    app.update();
+
+    let events = app.world().resource::<Events<TerritoryClaimedEvent>>();
+    events.iter_current_update_events().last().cloned().unwrap()
+}
+
+#[test]
+fn hurry_up_doubles_claims_for_good() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+    {
+        let mut rules = app.world_mut().resource_mut::<GameRules>();
+        rules.hurry_up_double_claims = true;
+        rules.territory_decay_interval = Some(0.05);
+    }
+    app.world_mut().resource_mut::<GameState>().hurry_up = true;
+
+    let claimed = close_small_loop(&mut app, entity, 30);
+    let enclosed = app.world().resource::<Tiles>().get(30, 6).unwrap().value;
+    assert_eq!(claimed.points, 1 + enclosed * 2);
+    assert_eq!(player(&mut app).bonus, enclosed);
+
+    // Decay recounts the land but leaves the doubled part alone
+    run_frames(&mut app, 10);
+    let land: u32 = app
+        .world()
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| tile.owner == Some(entity))
+        .map(|tile| tile.value)
+        .sum();
+    assert!(land < 26 + enclosed);
+    assert_eq!(player(&mut app).score, land + enclosed);
+}
+
+#[test]
+fn claims_report_their_points_and_loops_in_a_row_build_a_streak() {
+    let mut app = headless_app();
+    let entity = player_entity(&mut app);
+
+    let before = player(&mut app).score;
+    let claimed = close_small_loop(&mut app, entity, 30);
+    assert!(claimed.points >= 2);
+    assert_eq!(player(&mut app).score, before + claimed.points);
+
+    close_small_loop(&mut app, entity, 33);
+    let stats = app.world().resource::<MatchStats>();
+    assert_eq!(stats.claim_streak(entity), 2);
+
+    // Dying starts the streak over but the best one is kept
+    app.world_mut().send_event(PlayerDeathEvent {
+        player_entity: entity,
+        reason: PlayerDeathReason::OutOfBounds,
+        killer: None,
+        tile: (0, 0),
+        trail_length: 0,
+    });
+    app.update();
+    let stats = app.world().resource::<MatchStats>();
+    assert_eq!(stats.claim_streak(entity), 0);
+    assert_eq!(stats.best_claim_streak.get(&entity), Some(&2));
+}
+
+#[test]
+fn awards_go_to_whoever_did_the_most_of_each_thing() {
+    let mut world = World::new();
+    let first = world.spawn_empty().id();
+    let second = world.spawn_empty().id();
+    let standings = [
+        Standing {
+            player: first,
+            score: 100,
+            placement: 0,
+        },
+        Standing {
+            player: second,
+            score: 50,
+            placement: 1,
+        },
+    ];
+
+    let mut stats = MatchStats::default();
+    stats.kills.insert(second, 3);
+    stats.largest_claim.insert(first, 40);
+    stats.largest_claim.insert(second, 40);
+    stats.tiles_lost.insert(second, 25);
+    // Second died twice, first never did
+    stats.died(second, 30.0);
+    stats.died(second, 90.0);
+    assert_eq!(stats.longest_life(second, 100.0), 60.0);
+
+    let awards = stats.awards(&standings, 100.0);
+    let award = |award: Award| awards.iter().find(|won| won.award == award).copied();
+    assert_eq!(
+        award(Award::MostAggressive),
+        Some(MatchAward {
+            award: Award::MostAggressive,
+            player: second,
+            amount: 3,
+        })
+    );
+    // Tied, so it goes to the better placed player
+    assert_eq!(award(Award::LandBaron).unwrap().player, first);
+    assert_eq!(award(Award::Survivor).unwrap().amount, 100);
+    assert_eq!(award(Award::OpenHouse).unwrap().player, second);
+
+    // Nobody did anything, nobody gets anything
+    assert!(MatchStats::default().awards(&standings, 0.0).is_empty());
+}
+
+#[test]
+fn bots_speak_up_when_things_happen_to_them_but_not_every_time() {
+    let mut app = headless_app();
+    let human = player_entity(&mut app);
+    let world = app.world_mut();
+    world.resource_mut::<BotChatter>().chance = 1.0;
+    let bot = world.spawn(Bot::default()).id();
+
+    let cut = |player_entity, killer| PlayerDeathEvent {
+        player_entity,
+        reason: PlayerDeathReason::TrailCut,
+        killer: Some(killer),
+        tile: (0, 0),
+        trail_length: 3,
+    };
+    let emotes = |app: &App| -> Vec<EmoteEvent> {
+        let events = app.world().resource::<Events<EmoteEvent>>();
+        events.iter_current_update_events().copied().collect()
+    };
+
+    // Cut by the human, the bot is a good sport about it
+    app.world_mut().send_event(cut(bot, human));
+    app.update();
+    assert_eq!(
+        emotes(&app),
+        vec![EmoteEvent {
+            player: bot,
+            emote: Emote::NiceCut,
+        }]
+    );
+
+    // Then keeps quiet for a while, even after getting a kill of its own
+    app.world_mut().send_event(cut(human, bot));
+    app.update();
+    assert!(emotes(&app).is_empty());
+}
+
+#[test]
+fn end_of_match_camera_frames_the_map_then_sweeps_the_winners_land() {
+    let mut tween = CameraTween::default();
+    tween.play([
+        CameraShot::new(Vec2::new(100.0, 0.0), 2.0, 1.0),
+        CameraShot::new(Vec2::new(100.0, 50.0), 1.0, 1.0),
+    ]);
+    let start = (Vec2::ZERO, 1.0);
+
+    // Eases out of the starting pose and hands over between shots
+    let (position, scale) = tween.advance(start, 0.5).unwrap();
+    assert!((position.x - 50.0).abs() < 0.01 && (scale - 1.5).abs() < 0.01);
+    let (position, _) = tween.advance(start, 0.6).unwrap();
+    assert!(position.x == 100.0 && position.y > 0.0 && position.y < 5.0);
+    assert_eq!(
+        tween.advance(start, 5.0),
+        Some((Vec2::new(100.0, 50.0), 1.0))
+    );
+    assert!(!tween.active());
+
+    let grid = GridMath {
+        width: 40,
+        height: 20,
+        tile_size: 20.0,
+    };
+    let winner = Entity::from_raw(1);
+    let mut layers = OwnershipLayers {
+        width: 40,
+        height: 20,
+        ..default()
+    };
+    for x in 0..30 {
+        for y in 0..4 {
+            layers.set_owner(x, y, Some(winner));
+        }
+    }
+    let bounds = territory_bounds(&layers, &grid, winner).unwrap();
+    assert_eq!(bounds.min, grid.corner_of(0, 0));
+    assert_eq!(bounds.max, grid.corner_of(30, 4));
+
+    // The whole 800x400 map fits an 800x600 window, then a wide strip of land
+    // is swept from left to right
+    let viewport = Vec2::new(800.0, 600.0);
+    let shots = end_of_match_shots(&grid, Some(bounds), viewport);
+    assert_eq!(shots.len(), 4);
+    assert!(shots[0].scale > 1.0 && shots[0].position == Vec2::ZERO);
+    let (sweep_from, sweep_to) = (shots[2], shots[3]);
+    assert!(sweep_from.scale < shots[0].scale);
+    assert!(sweep_from.position.x < sweep_to.position.x);
+    assert_eq!(sweep_from.position.y, sweep_to.position.y);
+    assert_eq!(end_of_match_shots(&grid, None, viewport).len(), 2);
+}
+
+#[test]
+fn toasts_wait_their_turn_by_priority() {
+    let mut queue = ToastQueue::default();
+    queue
+        .push(ToastEvent::new(ToastIcon::Save, "Progress saved").with_priority(ToastPriority::Low));
+    queue.push(ToastEvent::new(ToastIcon::Info, "First"));
+    queue.push(ToastEvent::new(ToastIcon::Info, "Second"));
+    queue.push(ToastEvent::new(ToastIcon::Info, "First"));
+    queue.push(
+        ToastEvent::new(ToastIcon::Error, "Lost the connection to the server")
+            .with_priority(ToastPriority::High),
+    );
+    assert_eq!(queue.len(), 4);
+    assert_eq!(queue.highest_priority(), Some(ToastPriority::High));
+
+    let order: Vec<String> = std::iter::from_fn(|| queue.pop())
+        .map(|toast| toast.message)
+        .collect();
+    assert_eq!(
+        order,
+        [
+            "Lost the connection to the server",
+            "First",
+            "Second",
+            "Progress saved"
+        ]
+    );
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn a_corrupt_save_is_reported_and_kept_aside() {
+    let root = std::env::temp_dir().join(format!("landio-corrupt-{}", std::process::id()));
+    let paths = Paths::in_dir(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(paths.saves(), "(profiles: [oops").unwrap();
+
+    let err = ProfileStore::try_load(&paths).unwrap_err();
+    let backup = paths.saves().with_extension("ron.corrupt");
+    assert!(err.contains("kept a copy"), "{}", err);
+    assert!(backup.exists() && !paths.saves().exists());
+
+    // A fresh save no longer clobbers what couldn't be read
+    assert!(ProfileStore::default().save(&paths));
+    assert_eq!(
+        std::fs::read_to_string(&backup).unwrap(),
+        "(profiles: [oops"
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn rebound_keyboard_layouts_steer_players_and_display_changes_revert() {
+    let mut app = headless_app();
+    app.world_mut()
+        .resource_mut::<ControlSettings>()
+        .wasd
+        .set_key(BindDirection::Right, KeyCode::KeyL);
+    assert_eq!(key_name(KeyCode::KeyL), "L");
+    assert_eq!(key_name(KeyCode::ArrowUp), "ArrowUp");
+
+    let start = player(&mut app).last_tile_pos;
+    steer(&mut app, KeyCode::KeyL, |(x, _)| x > start.0 + 1);
+    assert!(player(&mut app).last_tile_pos.0 > start.0 + 1);
+
+    // Going back to the settings from before the first unkept change
+    let original = DisplaySettings::default();
+    let mut revert = DisplayRevert::default();
+    revert.start(original);
+    revert.start(DisplaySettings {
+        window_mode: WindowModeSetting::Fullscreen,
+        ..original
+    });
+    assert_eq!(revert.tick(REVERT_SECONDS - 1.0), None);
+    assert_eq!(revert.tick(1.0), Some(original));
+    assert!(revert.pending.is_none());
+
+    revert.start(original);
+    revert.keep();
+    assert_eq!(revert.tick(REVERT_SECONDS * 2.0), None);
+}
+
+#[cfg(feature = "dev")]
+#[test]
+fn edited_config_and_levels_reach_a_running_sandbox() {
+    use landio::dev::{DevPlugin, HotReload};
+
+    let root = std::env::temp_dir().join(format!("landio-dev-{}", std::process::id()));
+    let levels = root.join("levels");
+    std::fs::create_dir_all(&levels).unwrap();
+    let paths = Paths::in_dir(&root);
+
+    let mut app = App::new();
+    app.insert_resource(paths.clone())
+        .insert_resource(HotReload::new(&levels).with_poll_seconds(0.0))
+        .add_plugins((MinimalPlugins, StatesPlugin, GamePlugin, DevPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .insert_resource(JoinedPlayers {
+            devices: vec![InputDevice::KeyboardWasd],
+        });
+    app.update();
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Sandbox);
+    run_frames(&mut app, 3);
+
+    let red = Color::srgb(1.0, 0.0, 0.0);
+    let mut config = GameConfig::default();
+    config.rules.trail_cuts = true;
+    config.sandbox.player_speed = 9.0;
+    config.sandbox.colors = vec![red];
+    config.save(&paths);
+    run_frames(&mut app, 3);
+
+    assert!(app.world().resource::<GameRules>().trail_cuts);
+    assert_eq!(player(&mut app).speed, 9.0);
+    assert_eq!(player(&mut app).color, red);
+
+    // Half an edit is left alone
+    std::fs::write(paths.config(), "(rules: (trail_cuts: fal").unwrap();
+    run_frames(&mut app, 3);
+    assert!(app.world().resource::<GameRules>().trail_cuts);
+
+    // A new level is laid out on the sandbox map
+    std::fs::copy(
+        "assets/levels/02_around_the_wall.ron",
+        levels.join("02_around_the_wall.ron"),
+    )
+    .unwrap();
+    run_frames(&mut app, 3);
+
+    let campaign = app.world().resource::<Campaign>();
+    assert_eq!(campaign.levels.len(), 1);
+    let tiles = campaign.levels[0].tiles(&GridSettings::default());
+    assert_eq!(Some(player(&mut app).spawn_tile), tiles.start);
+    let world = app.world_mut();
+    let enemy = world
+        .query_filtered::<Entity, With<LevelEnemy>>()
+        .single(world);
+    let (land, _) = owned_tiles(&mut app, enemy);
+    assert!(land > 0);
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn theme_manifests_keep_only_the_files_that_can_be_loaded() {
+    let bundled: ThemeManifest =
+        ron::from_str(&std::fs::read_to_string("assets/themes/night/theme.ron").unwrap()).unwrap();
+    let (files, problems) = bundled.validate(Path::new("assets"), "themes/night");
+    assert!(problems.is_empty(), "{:?}", problems);
+    assert_eq!(
+        files.get(&ThemeSlot::DarkTile).map(String::as_str),
+        Some("themes/night/tile_dark.png")
+    );
+    assert!(bundled.ui.button.is_some());
+
+    let root = std::env::temp_dir().join(format!("landio-theme-{}", std::process::id()));
+    let dir = root.join("themes/broken");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("light.png"), []).unwrap();
+    let broken: ThemeManifest = ron::from_str(
+        r#"(
+            tiles: (light: Some("light.png"), dark: Some("dark.png")),
+            player: Some("../../night/player.png"),
+            sounds: (claim: Some("claim.mp3")),
+        )"#,
+    )
+    .unwrap();
+    let (files, problems) = broken.validate(&root, "themes/broken");
+    std::fs::remove_dir_all(&root).unwrap();
+
+    // Everything else is left to the built-in look
+    assert_eq!(
+        files.keys().copied().collect::<Vec<_>>(),
+        [ThemeSlot::LightTile]
+    );
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+    assert!(problems
+        .iter()
+        .any(|problem| problem.contains("dark.png is missing")));
+    assert_eq!(theme_dir("night").as_deref(), Some("themes/night"));
+    assert_eq!(theme_dir("../night"), None);
+}
+
+#[test]
+fn theme_voice_lines_are_checked_and_spaced_out() {
+    let root = std::env::temp_dir().join(format!("landio-voice-{}", std::process::id()));
+    let dir = root.join("themes/loud");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("go.wav"), []).unwrap();
+    std::fs::write(dir.join("nice.ogg"), []).unwrap();
+    let manifest: ThemeManifest = ron::from_str(
+        r#"(
+            voice: {
+                go: ["go.wav"],
+                claim: ["nice.ogg", "mine.ogg", "wow.mp3"],
+                kill: ["../kill.wav"],
+            },
+        )"#,
+    )
+    .unwrap();
+    let (files, problems) = manifest.validate_voice(&root, "themes/loud");
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(
+        files.get(&VoiceLine::Claim),
+        Some(&vec!["themes/loud/nice.ogg".to_string()])
+    );
+    assert!(files.contains_key(&VoiceLine::Go));
+    assert!(!files.contains_key(&VoiceLine::Kill));
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+
+    let mut cooldowns = VoiceCooldowns::default();
+    assert!(cooldowns.ready(VoiceLine::Claim, 0.0, false));
+    cooldowns.said(VoiceLine::Claim, 0.0);
+    // Nothing talks over a line or straight after it, and the same line
+    // waits longer still
+    assert!(!cooldowns.ready(VoiceLine::Kill, 0.5, true));
+    assert!(!cooldowns.ready(VoiceLine::Kill, 0.5, false));
+    assert!(cooldowns.ready(VoiceLine::Kill, 2.0, false));
+    assert!(!cooldowns.ready(VoiceLine::Claim, 2.0, false));
+    assert!(cooldowns.ready(VoiceLine::Claim, 9.0, false));
+    // Except the countdown, which cuts in
+    assert!(cooldowns.ready(VoiceLine::Go, 0.5, true));
+}
+
+#[test]
+fn isometric_view_draws_tiles_as_diamonds_without_moving_the_simulation() {
+    let iso = ViewProjection::Isometric;
+    let point = Vec2::new(30.0, -10.0);
+    assert!((iso.unproject(iso.project(point)) - point).length() < 0.001);
+    // Corners of a tile along both axes end up level, half as tall as wide
+    let (right, up) = (iso.project(Vec2::X), iso.project(Vec2::Y));
+    assert!((right.x + up.x).abs() < 0.001 && (right.y - up.y).abs() < 0.001);
+    assert!((right.x / right.y - 2.0).abs() < 0.001);
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TransformPlugin))
+        .insert_resource(DisplaySettings {
+            projection: ViewProjection::Isometric,
+            ..default()
+        })
+        .add_systems(
+            PostUpdate,
+            (
+                reproject_on_change_system.before(TransformSystem::TransformPropagate),
+                project_view_system.after(TransformSystem::TransformPropagate),
+            ),
+        );
+    let tile = app
+        .world_mut()
+        .spawn((Sprite::default(), Transform::from_xyz(20.0, 0.0, -0.1)))
+        .id();
+    let near = app
+        .world_mut()
+        .spawn((
+            Sprite::default(),
+            Transform::from_xyz(0.0, -20.0, 0.0),
+            Upright,
+        ))
+        .id();
+    let far = app
+        .world_mut()
+        .spawn((
+            Sprite::default(),
+            Transform::from_xyz(0.0, 20.0, 0.0),
+            Upright,
+        ))
+        .id();
+
+    // Projected once, however many frames go by
+    for _ in 0..3 {
+        app.update();
+        app.world_mut()
+            .entity_mut(near)
+            .get_mut::<Transform>()
+            .unwrap()
+            .set_changed();
+    }
+
+    let world = app.world();
+    let global = |entity: Entity| *world.get::<GlobalTransform>(entity).unwrap();
+    assert_eq!(
+        world.get::<Transform>(tile).unwrap().translation,
+        Vec3::new(20.0, 0.0, -0.1)
+    );
+    let tile_global = global(tile).affine();
+    assert!(
+        (Vec3::from(tile_global.translation) - iso.project(Vec2::new(20.0, 0.0)).extend(-0.1))
+            .length()
+            < 0.001
+    );
+    assert!((tile_global.matrix3.x_axis.truncate() - right).length() < 0.001);
+
+    // Players stand upright where the map puts them, nearer ones in front
+    let (near_global, far_global) = (global(near), global(far));
+    assert!(
+        (near_global.translation().truncate() - iso.project(Vec2::new(0.0, -20.0))).length()
+            < 0.001
+    );
+    assert_eq!(near_global.compute_transform().scale, Vec3::ONE);
+    assert_eq!(near_global.compute_transform().rotation, Quat::IDENTITY);
+    assert!(near_global.translation().z > far_global.translation().z);
+}
+
+#[test]
+fn tiles_are_one_flat_resource_rather_than_an_entity_each() {
+    let mut app = headless_app();
+    let grid = GridMath::new(app.world().resource::<GridSettings>());
+    let tiles = app.world().resource::<Tiles>();
+    assert_eq!(tiles.len(), (grid.width * grid.height) as usize);
+    assert!(app.world().entities().len() < tiles.len() as u32 / 4);
+
+    // Looked up by position, row by row from the bottom
+    let spawn = player(&mut app).spawn_tile;
+    let owner = player_entity(&mut app);
+    let tiles = app.world().resource::<Tiles>();
+    let tile = tiles.get(spawn.0, spawn.1).unwrap();
+    assert_eq!(
+        (tile.x, tile.y, tile.owner),
+        (spawn.0, spawn.1, Some(owner))
+    );
+    assert!(tiles.get(grid.width, 0).is_none());
+    assert_eq!(tiles.iter().nth(grid.width as usize).unwrap().y, 1);
+
+    // Big maps are a single allocation
+    let huge = Tiles::new(&GridSettings {
+        grid_width: 1000,
+        grid_height: 1000,
+        ..default()
+    });
+    assert_eq!(huge.len(), 1_000_000);
+    assert_eq!(huge.get(999, 999).unwrap().value, 1);
+}
+
+#[test]
+fn board_picks_up_only_the_tiles_that_look_different() {
+    let grid_settings = GridSettings {
+        grid_width: 6,
+        grid_height: 4,
+        value_zones: vec![ValueZone {
+            center: (4, 2),
+            radius: 0,
+            value: 3,
+        }],
+        ..default()
+    };
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(grid_settings.clone())
+        .insert_resource(Tiles::new(&grid_settings))
+        .init_resource::<Board>()
+        .init_resource::<ActiveFloor>()
+        .init_resource::<Vision>()
+        .add_systems(Update, update_board_system);
+    app.update();
+
+    let board = app.world().resource::<Board>();
+    assert_eq!(board.tiles.len(), 24);
+    let light = Color::srgb(0.9, 0.9, 0.9).to_linear().to_vec4();
+    assert_eq!(board.get(1, 0).unwrap().color, light);
+    assert_eq!(
+        board.get(4, 2).unwrap().value_dot,
+        value_dot_color(3).to_linear().to_vec4()
+    );
+    assert_eq!(board.get(3, 2).unwrap().value_dot, Vec4::ZERO);
+
+    let red = Color::srgba(1.0, 0.0, 0.0, 0.5);
+    let mut tiles = app.world_mut().resource_mut::<Tiles>();
+    tiles.get_mut(2, 3).unwrap().1 .0 = red;
+    app.update();
+    let board = app.world().resource::<Board>();
+    assert_eq!(board.get(2, 3).unwrap().color, red.to_linear().to_vec4());
+    assert_eq!(board.get(1, 0).unwrap().color, light);
+
+    // Touched but no different, nothing to send
+    let last_changed = app.world().resource_ref::<Board>().last_changed();
+    app.world_mut().resource_mut::<Tiles>().set_changed();
+    app.update();
+    assert_eq!(
+        app.world().resource_ref::<Board>().last_changed(),
+        last_changed
+    );
+
+    // No tiles, nothing drawn
+    app.insert_resource(Tiles::default());
+    app.update();
+    assert!(app.world().resource::<Board>().tiles.is_empty());
+}
+
+#[test]
+fn ownership_layers_resync_every_tile_after_a_resize() {
+    let owner = Entity::from_raw(1);
+    let wide = GridSettings {
+        grid_width: 8,
+        grid_height: 4,
+        ..default()
+    };
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(wide.clone())
+        .insert_resource(Tiles::new(&wide))
+        .init_resource::<OwnershipLayers>()
+        .add_systems(Update, sync_ownership_layers_system);
+    let claim = |tiles: &mut Tiles, at: &[(i32, i32)]| {
+        for &(x, y) in at {
+            tiles.get_mut(x, y).unwrap().0.owner = Some(owner);
+        }
+    };
+    claim(
+        &mut app.world_mut().resource_mut::<Tiles>(),
+        &[(7, 0), (7, 3)],
+    );
+    app.update();
+    assert_eq!(
+        app.world().resource::<OwnershipLayers>().tile_count(owner),
+        2
+    );
+
+    // Same number of tiles the other way round, laid out a frame after the
+    // settings change. Two of the owned tiles sit at the same index as last
+    // frame's, so only a full resync picks them up.
+    let tall = GridSettings {
+        grid_width: 4,
+        grid_height: 8,
+        ..default()
+    };
+    let mut tiles = Tiles::new(&tall);
+    claim(&mut tiles, &[(3, 1), (0, 2), (3, 7)]);
+    app.insert_resource(tall);
+    app.update();
+    app.insert_resource(tiles);
+    app.update();
+    let layers = app.world().resource::<OwnershipLayers>();
+    assert_eq!((layers.width, layers.height), (4, 8));
+    let layer = layers.layer(owner).unwrap();
+    let mut owned: Vec<_> = layer.ones().collect();
+    owned.sort();
+    assert_eq!(owned, vec![7, 8, 31]);
+}
+
+#[test]
+fn trails_keep_their_corners_and_only_the_newest_points() {
+    let rules = TrailPointRules {
+        max_points: 4,
+        tolerance: 0.5,
+    };
+    let mut trail = Trail {
+        owner: Entity::PLACEHOLDER,
+        points: Default::default(),
+        is_active: true,
+    };
+
+    // A straight run is just its two ends, however long it gets
+    for x in 0..10 {
+        trail.push(Vec2::new(x as f32 * 6.0, 0.1 * (x % 2) as f32), &rules);
+    }
+    assert_eq!(trail.points.len(), 2);
+    assert_eq!(trail.points[1].x, 54.0);
+
+    // Turning keeps the corner, doubling back keeps the turning point
+    trail.push(Vec2::new(54.0, 6.0), &rules);
+    trail.push(Vec2::new(54.0, 12.0), &rules);
+    trail.push(Vec2::new(54.0, 0.0), &rules);
+    assert_eq!(
+        Vec::from(trail.points.clone()),
+        vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(54.0, 0.1),
+            Vec2::new(54.0, 12.0),
+            Vec2::new(54.0, 0.0),
+        ]
+    );
+
+    // Past the cap the oldest point goes
+    trail.push(Vec2::new(60.0, 0.0), &rules);
+    assert_eq!(trail.points.len(), 4);
+    assert_eq!(trail.points[0], Vec2::new(54.0, 0.1));
+}
@@ -0,0 +1,1224 @@
+// Exercises the networking layer over loopback sockets and in-memory pipes
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+use landio::components::{
+    GridSettings, GridTopology, LocalPlayer, MapShape, Player, Tiles, UpperFloor, ValueZone,
+};
+use landio::events::{GameError, GameErrorKind, RetryAction, RetryEvent};
+use landio::net::backfill::BackfillBot;
+use landio::net::client::{ConnectionStatus, NetClient, NetClientPlugin};
+use landio::net::discovery::{fetch_master_list, LanBeacon, LanProbe, MasterListing};
+use landio::net::interpolation::{PositionSample, SnapshotBuffer, MAX_EXTRAPOLATION};
+use landio::net::invite::Invite;
+use landio::net::memory::MemoryTransport;
+use landio::net::multi::MultiTransport;
+use landio::net::prediction::{predict_step, reconcile, PredictedInput, Prediction};
+use landio::net::protocol::{
+    ClientMessage, PlayerState, RejectReason, ServerInfo, ServerMessage, TileUpdate,
+    PROTOCOL_VERSION,
+};
+use landio::net::rotation::{upcoming_entries, MatchRotation, Playlist};
+use landio::net::server::{NetServer, NetServerPlugin, EMOTE_COOLDOWN_SECONDS};
+use landio::net::udp::UdpTransport;
+use landio::net::websocket::{accept_key, WebSocketTransport};
+use landio::net::webtransport::{read_header_block, WebTransportTransport};
+use landio::net::{Channel, ConnectionId, NetTransport, Transport, TransportEvent};
+use landio::resources::{GameRules, GameSpeed, GameState};
+use landio::states::AppState;
+use landio::systems::countdown::{CountdownText, MatchCountdown};
+use landio::systems::emotes::Emote;
+use landio::GamePlugin;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FRAME: Duration = Duration::from_millis(1000 / 60);
+
+// Polls both ends until `done` has seen what it wants or a second goes by
+fn pump(
+    server: &mut dyn Transport,
+    client: &mut dyn Transport,
+    mut done: impl FnMut(&[TransportEvent], &[TransportEvent]) -> bool,
+) -> (Vec<TransportEvent>, Vec<TransportEvent>) {
+    let (mut server_events, mut client_events) = (Vec::new(), Vec::new());
+    let start = Instant::now();
+    while !done(&server_events, &client_events) {
+        assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+        server_events.extend(server.poll());
+        client_events.extend(client.poll());
+        thread::sleep(Duration::from_millis(1));
+    }
+    (server_events, client_events)
+}
+
+fn connected(events: &[TransportEvent]) -> Option<ConnectionId> {
+    events.iter().find_map(|event| match event {
+        TransportEvent::Connected(id) => Some(*id),
+        _ => None,
+    })
+}
+
+fn messages(events: &[TransportEvent]) -> Vec<Vec<u8>> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            TransportEvent::Message { payload, .. } => Some(payload.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Connects, then checks reliable messages arrive in order both ways
+fn round_trip(server: &mut dyn Transport, client: &mut dyn Transport) {
+    let (server_events, _) = pump(server, client, |server, client| {
+        connected(server).is_some() && connected(client).is_some()
+    });
+    let client_id = connected(&server_events).unwrap();
+
+    for i in 0..3u8 {
+        client.send(0, Channel::Reliable, &[i; 200]).unwrap();
+    }
+    server
+        .send(client_id, Channel::Reliable, b"welcome")
+        .unwrap();
+
+    let (server_events, client_events) = pump(server, client, |server, client| {
+        messages(server).len() == 3 && !messages(client).is_empty()
+    });
+    assert_eq!(
+        messages(&server_events),
+        vec![vec![0; 200], vec![1; 200], vec![2; 200]]
+    );
+    assert_eq!(messages(&client_events), vec![b"welcome".to_vec()]);
+}
+
+#[test]
+fn udp_transport_delivers_reliable_messages_in_order() {
+    let mut server = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let mut client = UdpTransport::connect(server.local_addr().unwrap()).unwrap();
+    round_trip(&mut server, &mut client);
+
+    // Acks of those messages time the link, nothing needed a resend
+    let start = Instant::now();
+    while client.stats(0).unwrap().rtt.is_none() {
+        assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+        server.poll();
+        client.poll();
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(client.stats(0).unwrap().loss, 0.0);
+
+    // Messages bigger than a datagram go in pieces, on either channel
+    let big: Vec<u8> = (0..50_000).map(|i| i as u8).collect();
+    client.send(0, Channel::Reliable, &big).unwrap();
+    client.send(0, Channel::Unreliable, &big[..4_000]).unwrap();
+    let (server_events, _) = pump(&mut server, &mut client, |server, _| {
+        messages(server).len() == 2
+    });
+    assert_eq!(
+        messages(&server_events),
+        vec![big.clone(), big[..4_000].to_vec()]
+    );
+}
+
+#[test]
+fn udp_transport_drops_peers_that_overrun_the_reliable_window() {
+    let mut server = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    peer.connect(server.local_addr().unwrap()).unwrap();
+    peer.send(b"LD\x01").unwrap();
+    let start = Instant::now();
+    let id = loop {
+        assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+        if let Some(id) = connected(&server.poll()) {
+            break id;
+        }
+        thread::sleep(Duration::from_millis(1));
+    };
+
+    // A reliable piece far ahead of anything sent so far
+    let mut far_ahead = b"LD\x06".to_vec();
+    far_ahead.extend_from_slice(&10_000u16.to_be_bytes());
+    far_ahead.extend_from_slice(&[0, 1, 2, 3]);
+    peer.send(&far_ahead).unwrap();
+    let start = Instant::now();
+    loop {
+        assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+        if server
+            .poll()
+            .iter()
+            .any(|event| matches!(event, TransportEvent::Disconnected(gone) if *gone == id))
+        {
+            break;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert!(server.connections().is_empty());
+}
+
+#[test]
+fn websocket_transport_upgrades_and_exchanges_frames() {
+    // Example from RFC 6455
+    assert_eq!(
+        accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+
+    let mut server = WebSocketTransport::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let mut client = WebSocketTransport::connect(addr, "localhost").unwrap();
+    round_trip(&mut server, &mut client);
+}
+
+// Opens a raw TCP connection to a WebSocket server and upgrades it
+fn raw_websocket(server: &mut WebSocketTransport) -> (TcpStream, ConnectionId) {
+    let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+    stream
+        .write_all(
+            b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+              Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+        )
+        .unwrap();
+    let start = Instant::now();
+    let id = loop {
+        assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+        if let Some(id) = connected(&server.poll()) {
+            break id;
+        }
+        thread::sleep(Duration::from_millis(1));
+    };
+    (stream, id)
+}
+
+// Polls the server until it reports `id` gone or a second goes by
+fn disconnects(server: &mut WebSocketTransport, id: ConnectionId) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        let events = server.poll();
+        if events
+            .iter()
+            .any(|event| matches!(event, TransportEvent::Disconnected(gone) if *gone == id))
+        {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    false
+}
+
+#[test]
+fn websocket_transport_drops_peers_that_break_the_protocol() {
+    let mut server = WebSocketTransport::bind("127.0.0.1:0").unwrap();
+
+    // Client frames have to be masked
+    let (mut stream, id) = raw_websocket(&mut server);
+    stream.write_all(&[0x82, 0x02, 1, 2]).unwrap();
+    assert!(disconnects(&mut server, id));
+
+    // A fragmented message can't grow past the frame limit
+    let (mut stream, id) = raw_websocket(&mut server);
+    let mut fragment = vec![0x02, 0x80 | 127];
+    fragment.extend_from_slice(&(1u64 << 19).to_be_bytes());
+    fragment.extend_from_slice(&[0; 4]);
+    fragment.resize(fragment.len() + (1 << 19), 0);
+    let writer = thread::spawn(move || {
+        let _ = stream.write_all(&fragment);
+        fragment[0] = 0x00;
+        for _ in 0..3 {
+            let _ = stream.write_all(&fragment);
+        }
+    });
+    assert!(disconnects(&mut server, id));
+    writer.join().unwrap();
+
+    // Nor can the HTTP head before the upgrade
+    let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    let _ = stream.write_all(&[b'x'; 9 * 1024]);
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(100) {
+        server.poll();
+        thread::sleep(Duration::from_millis(1));
+    }
+    match stream.read(&mut [0; 64]) {
+        Ok(len) => assert_eq!(len, 0),
+        Err(err) => assert!(!matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        )),
+    }
+
+    // A client won't take an upgrade answered with the wrong accept key
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client =
+        WebSocketTransport::connect(listener.local_addr().unwrap(), "localhost").unwrap();
+    let (mut impostor, _) = listener.accept().unwrap();
+    impostor
+        .write_all(
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+              Connection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n",
+        )
+        .unwrap();
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(100) {
+        assert!(connected(&client.poll()).is_none());
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert!(client.connections().is_empty());
+}
+
+#[test]
+fn webtransport_transport_opens_a_session_and_carries_both_channels() {
+    let mut server = WebTransportTransport::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr().unwrap();
+    let hash = server.certificate_hash().unwrap();
+    let mut client = WebTransportTransport::connect(addr, hash).unwrap();
+    round_trip(&mut server, &mut client);
+
+    // Unreliable messages go as datagrams, which loopback doesn't drop
+    let client_id = server.connections()[0];
+    server
+        .send(client_id, Channel::Unreliable, b"snapshot")
+        .unwrap();
+    client.send(0, Channel::Unreliable, b"input").unwrap();
+    let (server_events, client_events) = pump(&mut server, &mut client, |server, client| {
+        !messages(server).is_empty() && !messages(client).is_empty()
+    });
+    assert_eq!(messages(&server_events), vec![b"input".to_vec()]);
+    assert_eq!(messages(&client_events), vec![b"snapshot".to_vec()]);
+    assert!(client.stats(0).unwrap().rtt.is_some());
+
+    // A client pinning some other certificate never gets a session
+    let mut stranger = WebTransportTransport::connect(addr, [0; 32]).unwrap();
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(300) {
+        assert!(connected(&server.poll()).is_none());
+        assert!(connected(&stranger.poll()).is_none());
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn webtransport_reads_connect_requests_from_header_blocks() {
+    let field = |name: &str, value: &str| (name.to_string(), value.to_string());
+
+    // :method CONNECT from the static table, a Huffman coded :authority (the
+    // RFC 7541 example) and a literal :protocol
+    let mut block = vec![0, 0, 0xC0 | 15, 0x50, 0x80 | 12];
+    block.extend_from_slice(&[
+        0xF1, 0xE3, 0xC2, 0xE5, 0xF2, 0x3A, 0x6B, 0xA0, 0xAB, 0x90, 0xF4, 0xFF,
+    ]);
+    block.extend_from_slice(&[0x20 | 7, 9 - 7]);
+    block.extend_from_slice(b":protocol");
+    block.push(12);
+    block.extend_from_slice(b"webtransport");
+    assert_eq!(
+        read_header_block(&block),
+        Some(vec![
+            field(":method", "CONNECT"),
+            field(":authority", "www.example.com"),
+            field(":protocol", "webtransport"),
+        ])
+    );
+
+    // A plain GET reads fine but isn't a session request
+    assert_eq!(
+        read_header_block(&[0, 0, 0xC0 | 17, 0xC0 | 1]),
+        Some(vec![field(":method", "GET"), field(":path", "/")])
+    );
+
+    // Nothing can point into the dynamic table, which servers never set up
+    assert_eq!(read_header_block(&[1, 0, 0x80]), None);
+    assert_eq!(read_header_block(&[0, 0, 0x10]), None);
+}
+
+#[test]
+fn multi_transport_serves_udp_and_websocket_clients_together() {
+    let udp = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let websocket = WebSocketTransport::bind("127.0.0.1:0").unwrap();
+    let (udp_addr, websocket_addr) = (udp.local_addr().unwrap(), websocket.local_addr().unwrap());
+    let mut server = MultiTransport::default().with(udp).with(websocket);
+
+    let mut desktop = UdpTransport::connect(udp_addr).unwrap();
+    round_trip(&mut server, &mut desktop);
+    let mut browser = WebSocketTransport::connect(websocket_addr, "localhost").unwrap();
+    round_trip(&mut server, &mut browser);
+
+    // Both transports number their first client 1, the server tells them apart
+    let ids = server.connections();
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+    server.send(ids[1], Channel::Reliable, b"browser").unwrap();
+    let (_, browser_events) = pump(&mut server, &mut browser, |_, client| {
+        !messages(client).is_empty()
+    });
+    assert_eq!(messages(&browser_events), vec![b"browser".to_vec()]);
+    assert!(messages(&desktop.poll()).is_empty());
+
+    server.disconnect(ids[1]);
+    assert_eq!(server.connections(), vec![ids[0]]);
+    assert!(server.send(ids[1], Channel::Reliable, b"gone").is_err());
+}
+
+#[test]
+fn memory_transport_reports_disconnects() {
+    let (mut server, mut clients) = MemoryTransport::server_with_clients(1);
+    let mut client = clients.remove(0);
+    round_trip(&mut server, &mut client);
+
+    server.disconnect(1);
+    assert!(client.poll().contains(&TransportEvent::Disconnected(0)));
+    assert!(client.connections().is_empty());
+}
+
+#[test]
+fn protocol_messages_survive_a_round_trip() {
+    let client_messages = [
+        ClientMessage::JoinRequest {
+            protocol_version: PROTOCOL_VERSION,
+            name: "Ada".into(),
+            resume: Some(42),
+            code: Some("K7QX2M".into()),
+            observer: true,
+        },
+        ClientMessage::InputTick {
+            tick: 70_000,
+            acked_tick: 69_990,
+            direction: Vec2::new(-1.0, 0.0),
+        },
+        ClientMessage::CastVote { option: 2 },
+        ClientMessage::SendEmote {
+            emote: Emote::NiceCut,
+        },
+    ];
+    for message in client_messages {
+        assert_eq!(ClientMessage::decode(&message.encode()), Ok(message));
+    }
+
+    let server_messages = [
+        ServerMessage::JoinAccepted {
+            player: None,
+            session: 0,
+            grid_width: 40,
+            grid_height: 30,
+            value_zones: vec![ValueZone {
+                center: (20, 15),
+                radius: 4,
+                value: 3,
+            }],
+            game_speed: GameSpeed::Blitz,
+            topology: GridTopology::Mirrored,
+            shape: MapShape::Mask(vec![".##.".into(), "####".into()]),
+            upper_floor: Some(UpperFloor {
+                stairs: vec![(3, 4), (10, 2)],
+            }),
+        },
+        ServerMessage::JoinRejected {
+            reason: RejectReason::VersionMismatch { server_version: 7 },
+        },
+        ServerMessage::Snapshot {
+            tick: 12,
+            last_input: 9,
+            live: true,
+            players: vec![PlayerState {
+                player: u64::MAX,
+                tile: (0, 29),
+                progress: 0.375,
+                direction: Vec2::Y,
+                buffered_direction: Some(Vec2::X),
+                speed: 5.5,
+                score: 25,
+                drawing_trail: true,
+                alive: false,
+                color: [51, 178, 229],
+                ping_ms: Some(48),
+                loss: 3,
+            }],
+        },
+        ServerMessage::TileDelta {
+            tick: 3,
+            tiles: vec![
+                TileUpdate {
+                    x: 4,
+                    y: 5,
+                    owner: Some(2),
+                    trail_owner: Some(1 << 40),
+                },
+                TileUpdate {
+                    x: 6,
+                    y: 7,
+                    owner: None,
+                    trail_owner: None,
+                },
+            ],
+        },
+        ServerMessage::DeathNotice {
+            player: 3,
+            killer: Some(4),
+            tile: (-1, 2),
+        },
+        ServerMessage::VoteStarted {
+            options: vec!["Classic".into(), "Skirmish".into()],
+            seconds: 15.0,
+        },
+        ServerMessage::VoteTally { votes: vec![0, 3] },
+        ServerMessage::VoteEnded { option: 1 },
+        ServerMessage::Emote {
+            player: 5,
+            emote: Emote::GoodGame,
+        },
+        ServerMessage::Countdown { seconds: 2.5 },
+    ];
+    for message in server_messages {
+        let bytes = message.encode();
+        assert_eq!(ServerMessage::decode(&bytes), Ok(message));
+        // Cut short anywhere, it's an error rather than a panic
+        for len in 0..bytes.len() {
+            assert!(ServerMessage::decode(&bytes[..len]).is_err());
+        }
+    }
+}
+
+// Headless server in a match, listening on the given transport
+fn server_app(transport: MemoryTransport) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin, GamePlugin, NetServerPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .insert_resource(NetTransport::new(transport))
+        .init_resource::<ButtonInput<KeyCode>>();
+    app.update();
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Playing);
+    app.update();
+    app
+}
+
+fn client_app(transport: MemoryTransport) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin, GamePlugin, NetClientPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME))
+        .insert_resource(NetTransport::new(transport))
+        .insert_resource(NetClient::new("Ada"))
+        .init_resource::<ButtonInput<KeyCode>>();
+    app.update();
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Online);
+    app
+}
+
+#[test]
+fn server_turns_away_clients_on_another_protocol_version() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(1);
+    let mut server = server_app(server);
+    let mut client = clients.remove(0);
+
+    // A later version's join request: still the first message with the
+    // version up front, but with fields this version can't read after it
+    let mut request = postcard::to_stdvec(&(0u32, PROTOCOL_VERSION + 1)).unwrap();
+    request.extend([0xFF; 3]);
+    assert_eq!(
+        ClientMessage::decode(&request),
+        Ok(ClientMessage::JoinRequest {
+            protocol_version: PROTOCOL_VERSION + 1,
+            name: String::new(),
+            resume: None,
+            code: None,
+            observer: false,
+        })
+    );
+    client.send(0, Channel::Reliable, &request).unwrap();
+    server.update();
+
+    let replies: Vec<ServerMessage> = messages(&client.poll())
+        .iter()
+        .map(|payload| ServerMessage::decode(payload).unwrap())
+        .collect();
+    assert_eq!(
+        replies,
+        vec![ServerMessage::JoinRejected {
+            reason: RejectReason::VersionMismatch {
+                server_version: PROTOCOL_VERSION
+            }
+        }]
+    );
+    assert!(server.world().resource::<NetServer>().clients.is_empty());
+}
+
+#[test]
+fn client_joins_and_mirrors_the_server_match() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(1);
+    let mut server = server_app(server);
+    server.world_mut().resource_mut::<GameRules>().game_speed = GameSpeed::Blitz;
+    let mut client = client_app(clients.remove(0));
+
+    for _ in 0..5 {
+        client.update();
+        server.update();
+    }
+    client.update();
+
+    assert_eq!(
+        client.world().resource::<NetClient>().status,
+        ConnectionStatus::Joined
+    );
+    assert_eq!(server.world().resource::<NetServer>().clients.len(), 1);
+
+    // The client plays at the server's pace
+    assert_eq!(
+        client.world().resource::<GameRules>().game_speed,
+        GameSpeed::Blitz
+    );
+    assert_eq!(
+        client.world().resource::<Time<Virtual>>().relative_speed(),
+        GameSpeed::Blitz.time_scale()
+    );
+
+    // Our player and its starting land showed up on the client
+    let world = client.world_mut();
+    let local = world
+        .query_filtered::<Entity, (With<Player>, With<LocalPlayer>)>()
+        .single(world);
+    let owned = world
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| tile.owner == Some(local))
+        .count();
+    assert_eq!(owned, 25);
+}
+
+#[test]
+fn clients_count_down_to_the_servers_go() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(1);
+    let mut server = server_app(server);
+    let mut client = client_app(clients.remove(0));
+
+    for _ in 0..5 {
+        client.update();
+        server.update();
+    }
+    client.update();
+
+    // Joined partway through, the client shows what's left of the server's
+    let remaining = |app: &App| {
+        app.world()
+            .get_resource::<MatchCountdown>()
+            .map(|countdown| countdown.timer.remaining_secs())
+    };
+    let (on_server, on_client) = (remaining(&server).unwrap(), remaining(&client).unwrap());
+    assert!(on_server < 3.0);
+    assert!((on_server - on_client).abs() <= 2.0 * FRAME.as_secs_f32());
+    let world = client.world_mut();
+    assert_eq!(
+        world
+            .query_filtered::<(), With<CountdownText>>()
+            .iter(world)
+            .count(),
+        1
+    );
+
+    // Both say GO together, and only the server starts the match
+    for _ in 0..200 {
+        if remaining(&server).is_none() {
+            break;
+        }
+        server.update();
+        client.update();
+    }
+    client.update();
+    assert!(remaining(&server).is_none());
+    assert!(remaining(&client).is_none());
+    assert!(server.world().resource::<GameState>().game_running);
+    assert!(!client.world().resource::<GameState>().game_running);
+}
+
+// Heading right from the middle of the map
+fn moving_player() -> (Player, Transform) {
+    let player = Player {
+        speed: 5.0,
+        direction: Vec2::X,
+        buffered_direction: None,
+        score: 0,
+        bonus: 0,
+        color: Color::WHITE,
+        is_drawing_trail: false,
+        last_tile_pos: (20, 15),
+        progress: 0.0,
+        spawn_tile: (20, 15),
+        retracing: false,
+    };
+    (player, Transform::from_xyz(10.0, 10.0, 0.0))
+}
+
+fn server_state(player: &Player) -> PlayerState {
+    PlayerState {
+        player: 1,
+        tile: player.last_tile_pos,
+        progress: player.progress,
+        direction: player.direction,
+        buffered_direction: player.buffered_direction,
+        speed: player.speed,
+        score: 0,
+        drawing_trail: false,
+        alive: true,
+        color: [255; 3],
+        ping_ms: None,
+        loss: 0,
+    }
+}
+
+#[test]
+fn reconciling_replays_inputs_the_server_has_not_seen() {
+    let grid = GridSettings::default();
+    // Right for a while, then a turn up
+    let inputs: Vec<PredictedInput> = (1..=30)
+        .map(|tick| PredictedInput {
+            tick,
+            direction: if tick < 20 { Vec2::X } else { Vec2::Y },
+            delta_secs: FRAME.as_secs_f32(),
+        })
+        .collect();
+
+    let (mut server_player, mut server_transform) = moving_player();
+    let (mut player, mut transform) = moving_player();
+    let mut prediction = Prediction {
+        active: true,
+        ..Prediction::default()
+    };
+    for input in inputs.iter() {
+        predict_step(&mut player, &mut transform.translation, input, &grid);
+        prediction.inputs.push_back(*input);
+    }
+    // The server has played the first 25 of them the same way
+    for input in inputs.iter().take(25) {
+        predict_step(
+            &mut server_player,
+            &mut server_transform.translation,
+            input,
+            &grid,
+        );
+    }
+
+    let predicted = transform.translation;
+    let state = server_state(&server_player);
+    reconcile(
+        &mut prediction,
+        &state,
+        25,
+        &mut player,
+        &mut transform,
+        &grid,
+    );
+    assert_eq!(prediction.inputs.len(), 5);
+    assert!(prediction.last_error < 0.01);
+    assert_eq!(transform.translation, predicted);
+
+    // If the server disagrees, the player ends up where its state leads
+    let mut state = state;
+    state.tile.1 -= 1;
+    reconcile(
+        &mut prediction,
+        &state,
+        25,
+        &mut player,
+        &mut transform,
+        &grid,
+    );
+    assert!((prediction.last_error - 20.0).abs() < 0.01);
+}
+
+#[test]
+fn remote_players_are_interpolated_between_snapshots() {
+    let tile_size = GridSettings::default().tile_size;
+    let mut buffer = SnapshotBuffer::default();
+    for (time, x) in [(1.0, 0.0), (1.1, 10.0), (1.2, 20.0)] {
+        buffer.push(PositionSample {
+            time,
+            position: Vec2::new(x, 0.0),
+            velocity: Vec2::new(100.0, 0.0),
+        });
+    }
+    // A late duplicate is ignored
+    buffer.push(PositionSample {
+        time: 1.1,
+        position: Vec2::new(500.0, 0.0),
+        velocity: Vec2::ZERO,
+    });
+
+    let x_at = |buffer: &SnapshotBuffer, time: f32| buffer.sample(time, tile_size).unwrap().x;
+    let x = |time: f32| x_at(&buffer, time);
+    assert!((x(1.05) - 5.0).abs() < 0.01);
+    assert!((x(1.15) - 15.0).abs() < 0.01);
+    // Past the newest snapshot it keeps going, but not forever
+    assert!((x(1.3) - 30.0).abs() < 0.01);
+    assert!((x(5.0) - (20.0 + 100.0 * MAX_EXTRAPOLATION)).abs() < 0.01);
+
+    // A respawn across the map is a jump, not a slide
+    buffer.push(PositionSample {
+        time: 1.3,
+        position: Vec2::new(-300.0, 0.0),
+        velocity: Vec2::ZERO,
+    });
+    assert_eq!(x_at(&buffer, 1.25), 20.0);
+}
+
+// Sends a join request straight from a bare transport and returns the reply
+fn raw_join(server: &mut App, client: &mut MemoryTransport, resume: Option<u64>) -> ServerMessage {
+    let request = ClientMessage::JoinRequest {
+        protocol_version: PROTOCOL_VERSION,
+        name: "Ada".into(),
+        resume,
+        code: None,
+        observer: false,
+    };
+    client
+        .send(0, Channel::Reliable, &request.encode())
+        .unwrap();
+    server.update();
+    messages(&client.poll())
+        .iter()
+        .map(|payload| ServerMessage::decode(payload).unwrap())
+        .find(|message| matches!(message, ServerMessage::JoinAccepted { .. }))
+        .expect("no join reply")
+}
+
+#[test]
+fn dropped_players_are_held_for_a_reconnect() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(3);
+    let mut server = server_app(server);
+    let (mut first, mut second, mut refused) =
+        (clients.remove(0), clients.remove(0), clients.remove(0));
+
+    let ServerMessage::JoinAccepted {
+        player: Some(player),
+        session,
+        ..
+    } = raw_join(&mut server, &mut first, None)
+    else {
+        unreachable!()
+    };
+    first.disconnect(0);
+    server.update();
+    assert_eq!(server.world().resource::<NetServer>().away.len(), 1);
+
+    // A resume that gets turned away leaves the player held
+    server.world_mut().resource_mut::<NetServer>().code = Some("K7QX2M".into());
+    let request = ClientMessage::JoinRequest {
+        protocol_version: PROTOCOL_VERSION,
+        name: "Ada".into(),
+        resume: Some(session),
+        code: None,
+        observer: false,
+    };
+    refused
+        .send(0, Channel::Reliable, &request.encode())
+        .unwrap();
+    server.update();
+    assert_eq!(server.world().resource::<NetServer>().away.len(), 1);
+    server.world_mut().resource_mut::<NetServer>().code = None;
+
+    // Same player back on a new connection
+    let ServerMessage::JoinAccepted {
+        player: Some(resumed),
+        ..
+    } = raw_join(&mut server, &mut second, Some(session))
+    else {
+        unreachable!()
+    };
+    assert_eq!(resumed, player);
+    assert!(server.world().resource::<NetServer>().away.is_empty());
+
+    // Gone once the grace window runs out
+    server.world_mut().resource_mut::<NetServer>().grace_seconds = 0.05;
+    second.disconnect(0);
+    for _ in 0..6 {
+        server.update();
+    }
+    let server_state = server.world().resource::<NetServer>();
+    assert!(server_state.away.is_empty() && server_state.clients.is_empty());
+    assert!(server
+        .world()
+        .get_entity(Entity::from_bits(player))
+        .is_err());
+}
+
+#[test]
+fn emotes_are_passed_on_to_everyone() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(2);
+    let mut server = server_app(server);
+    let (mut first, mut second) = (clients.remove(0), clients.remove(0));
+
+    let ServerMessage::JoinAccepted {
+        player: Some(player),
+        ..
+    } = raw_join(&mut server, &mut first, None)
+    else {
+        unreachable!()
+    };
+    raw_join(&mut server, &mut second, None);
+
+    let mut emotes_after = |server: &mut App, sent: &[Emote], frames: usize| {
+        for &emote in sent {
+            first
+                .send(
+                    0,
+                    Channel::Reliable,
+                    &ClientMessage::SendEmote { emote }.encode(),
+                )
+                .unwrap();
+        }
+        for _ in 0..frames {
+            server.update();
+        }
+        messages(&second.poll())
+            .iter()
+            .filter_map(|payload| ServerMessage::decode(payload).ok())
+            .filter(|message| matches!(message, ServerMessage::Emote { .. }))
+            .collect::<Vec<_>>()
+    };
+
+    // Spamming the wheel gets the first one through and drops the rest
+    // until the cooldown is up
+    assert_eq!(
+        emotes_after(&mut server, &[Emote::Hello, Emote::UhOh], 2),
+        vec![ServerMessage::Emote {
+            player,
+            emote: Emote::Hello,
+        }]
+    );
+    let cooldown = (EMOTE_COOLDOWN_SECONDS / FRAME.as_secs_f32()).ceil() as usize;
+    assert_eq!(
+        emotes_after(&mut server, &[Emote::NiceCut], cooldown),
+        vec![]
+    );
+    assert_eq!(
+        emotes_after(&mut server, &[Emote::GoodGame], 2),
+        vec![ServerMessage::Emote {
+            player,
+            emote: Emote::GoodGame,
+        }]
+    );
+}
+
+#[test]
+fn playlist_votes_rotate_in_order_and_wrap() {
+    assert_eq!(upcoming_entries(1, 4, 3), vec![2, 3, 0]);
+    // Short playlists offer each entry once
+    assert_eq!(upcoming_entries(0, 2, 3), vec![1, 0]);
+}
+
+#[test]
+fn clients_vote_on_the_next_map_between_matches() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(1);
+    let mut server = server_app(server);
+    let mut client = client_app(clients.remove(0));
+    server.world_mut().resource_mut::<Playlist>().vote_seconds = 0.2;
+
+    for _ in 0..5 {
+        client.update();
+        server.update();
+    }
+    let first_player = client.world().resource::<NetClient>().player;
+
+    // Run the clock out
+    *server.world_mut().resource_mut::<GameState>() = GameState {
+        timer: Timer::from_seconds(0.01, TimerMode::Once),
+        game_running: true,
+        ..default()
+    };
+    server.update();
+    server.update();
+    assert_eq!(
+        *server.world().resource::<State<AppState>>().get(),
+        AppState::Intermission
+    );
+    client.update();
+    let ballot = client
+        .world()
+        .resource::<NetClient>()
+        .ballot
+        .clone()
+        .unwrap();
+    assert_eq!(ballot.options, vec!["Casual", "Skirmish", "Classic"]);
+
+    // Skirmish, which isn't the next one in rotation
+    client
+        .world_mut()
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::Digit2);
+    for _ in 0..20 {
+        client.update();
+        server.update();
+    }
+
+    assert_eq!(server.world().resource::<MatchRotation>().current, 2);
+    assert_eq!(
+        *server.world().resource::<State<AppState>>().get(),
+        AppState::Playing
+    );
+    // The client is on the new, smaller map with a fresh player
+    let net_client = client.world().resource::<NetClient>();
+    assert!(net_client.ballot.is_none());
+    assert_ne!(net_client.player, first_player);
+    assert_eq!(client.world().resource::<GridSettings>().grid_width, 30);
+    let world = client.world_mut();
+    assert_eq!(world.resource::<Tiles>().len(), 30 * 22);
+}
+
+#[test]
+fn lan_probes_find_servers_and_get_their_info() {
+    let beacon = LanBeacon::bind(0, 5000).unwrap();
+    let mut probe = LanProbe::bind(beacon.local_addr().unwrap().port()).unwrap();
+    probe.probe(Ipv4Addr::LOCALHOST.into()).unwrap();
+
+    let info = ServerInfo {
+        name: "Den".into(),
+        map: "Classic".into(),
+        players: 3,
+        max_players: 8,
+        port: 5000,
+        protocol_version: PROTOCOL_VERSION,
+        locked: true,
+    };
+    let start = Instant::now();
+    let replies = loop {
+        beacon.answer(&info);
+        let replies = probe.replies();
+        if !replies.is_empty() {
+            break replies;
+        }
+        assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+        thread::sleep(Duration::from_millis(1));
+    };
+    let (address, reply, _) = &replies[0];
+    assert_eq!(*address, SocketAddr::from((Ipv4Addr::LOCALHOST, 5000)));
+    assert_eq!(*reply, info);
+}
+
+#[test]
+fn master_server_lists_are_fetched_over_http() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/servers", listener.local_addr().unwrap());
+    let master = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let len = stream.read(&mut request).unwrap();
+        let body = r#"[{"name":"Den","address":"127.0.0.1:5000","map":"Casual","players":2,"max_players":8}]"#;
+        write!(
+            stream,
+            "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        String::from_utf8_lossy(&request[..len]).into_owned()
+    });
+
+    let listings = fetch_master_list(&url).unwrap();
+    assert!(master.join().unwrap().starts_with("GET /servers HTTP/1.0"));
+    assert_eq!(
+        listings,
+        vec![MasterListing {
+            name: "Den".into(),
+            address: "127.0.0.1:5000".into(),
+            map: "Casual".into(),
+            players: 2,
+            max_players: 8,
+            locked: false,
+        }]
+    );
+}
+
+#[test]
+fn locked_lobbies_only_let_in_clients_with_the_code() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(3);
+    let mut server = server_app(server);
+    server.world_mut().resource_mut::<NetServer>().code = Some("K7QX2M".into());
+    // Connected but never asks to join
+    let mut lurker = clients.pop().unwrap();
+
+    let mut stranger = clients.remove(0);
+    let request = ClientMessage::JoinRequest {
+        protocol_version: PROTOCOL_VERSION,
+        name: "Stranger".into(),
+        resume: None,
+        code: Some("AAAAAA".into()),
+        observer: false,
+    };
+    stranger
+        .send(0, Channel::Reliable, &request.encode())
+        .unwrap();
+    server.update();
+    let events = stranger.poll();
+    let replies: Vec<ServerMessage> = messages(&events)
+        .iter()
+        .map(|payload| ServerMessage::decode(payload).unwrap())
+        .collect();
+    assert_eq!(
+        replies,
+        vec![ServerMessage::JoinRejected {
+            reason: RejectReason::WrongCode
+        }]
+    );
+    // Turned away and hung up on
+    assert!(matches!(
+        events.last(),
+        Some(TransportEvent::Disconnected(_))
+    ));
+
+    let invite = Invite::parse("192.168.1.5:5000/K7QX2M");
+    assert_eq!(invite.to_string(), "192.168.1.5:5000/K7QX2M");
+    let mut client = client_app(clients.remove(0));
+    client.insert_resource(NetClient::new("Ada").with_invite(invite));
+    for _ in 0..5 {
+        client.update();
+        server.update();
+    }
+    assert_eq!(
+        client.world().resource::<NetClient>().status,
+        ConnectionStatus::Joined
+    );
+    // None of the match goes to the stranger, or to anyone who hasn't joined
+    assert!(messages(&stranger.poll()).is_empty());
+    assert!(messages(&lurker.poll()).is_empty());
+}
+
+#[test]
+fn bots_fill_empty_slots_and_hand_them_to_joining_players() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(1);
+    let mut server = server_app(server);
+    server.world_mut().resource_mut::<NetServer>().fill_to = 3;
+    server.update();
+    server.update();
+
+    let world = server.world_mut();
+    let bots: Vec<Entity> = world
+        .query_filtered::<Entity, With<BackfillBot>>()
+        .iter(world)
+        .collect();
+    assert_eq!(bots.len(), 3);
+
+    // The newcomer gets one of the bots' players, land and all
+    let mut client = clients.remove(0);
+    let ServerMessage::JoinAccepted {
+        player: Some(player),
+        ..
+    } = raw_join(&mut server, &mut client, None)
+    else {
+        unreachable!()
+    };
+    server.update();
+    assert!(bots.contains(&Entity::from_bits(player)));
+
+    let world = server.world_mut();
+    assert_eq!(
+        world
+            .query_filtered::<Entity, With<BackfillBot>>()
+            .iter(world)
+            .count(),
+        2
+    );
+    assert_eq!(world.query::<&Player>().iter(world).count(), 3);
+    let owned = world
+        .resource::<Tiles>()
+        .iter()
+        .filter(|tile| tile.owner == Some(Entity::from_bits(player)))
+        .count();
+    assert!(owned > 0);
+}
+
+#[test]
+fn observers_watch_the_match_without_a_player() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(2);
+    let mut server = server_app(server);
+    server.world_mut().resource_mut::<NetServer>().max_players = 1;
+    let mut player = clients.remove(0);
+    raw_join(&mut server, &mut player, None);
+
+    // A full server still lets observers in
+    let mut observer = client_app(clients.remove(0));
+    observer.insert_resource(NetClient::new("Caster").with_observer());
+    for _ in 0..5 {
+        observer.update();
+        server.update();
+    }
+    observer.update();
+
+    let server_state = server.world().resource::<NetServer>();
+    assert_eq!(server_state.clients.len(), 1);
+    assert_eq!(server_state.observers.len(), 1);
+    let client = observer.world().resource::<NetClient>();
+    assert_eq!(client.status, ConnectionStatus::Joined);
+    assert_eq!(client.player, None);
+    assert_eq!(client.players.len(), 1);
+
+    // Mirrored like anyone else's, but nobody is ours
+    let world = observer.world_mut();
+    assert_eq!(
+        world
+            .query_filtered::<Entity, With<LocalPlayer>>()
+            .iter(world)
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn a_connection_that_cant_be_recovered_is_reported_with_a_retry() {
+    let (server, mut clients) = MemoryTransport::server_with_clients(1);
+    let mut server = server_app(server);
+    let mut client = client_app(clients.remove(0));
+    client.insert_resource(
+        NetClient::new("Ada").with_reconnect(Box::new(|| Err(std::io::Error::other("offline")))),
+    );
+    for _ in 0..5 {
+        client.update();
+        server.update();
+    }
+
+    server
+        .world_mut()
+        .resource_mut::<NetTransport>()
+        .0
+        .disconnect(1);
+    client.update();
+    assert_eq!(
+        client.world().resource::<NetClient>().status,
+        ConnectionStatus::Reconnecting
+    );
+
+    // Sit out the grace window a quarter second at a time
+    client.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+    let mut errors = Vec::new();
+    for _ in 0..130 {
+        client.update();
+        let events = client.world().resource::<Events<GameError>>();
+        errors.extend(events.get_cursor().read(events).cloned());
+        if client.world().resource::<NetClient>().status == ConnectionStatus::Disconnected {
+            break;
+        }
+    }
+    assert_eq!(
+        client.world().resource::<NetClient>().status,
+        ConnectionStatus::Disconnected
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, GameErrorKind::ConnectionLost);
+    assert_eq!(errors[0].retry, Some(RetryAction::Reconnect));
+
+    client.world_mut().send_event(RetryEvent {
+        action: RetryAction::Reconnect,
+    });
+    client.update();
+    assert_eq!(
+        client.world().resource::<NetClient>().status,
+        ConnectionStatus::Reconnecting
+    );
+}